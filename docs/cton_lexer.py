@@ -42,6 +42,7 @@ class CretonneLexer(RegexLexer):
             (keywords('uext', 'sext'), Name.Attribute),
             # Well known value types.
             (r'\b(b\d+|i\d+|f32|f64)(x\d+)?\b', Keyword.Type),
+            (r'\b(r32|r64)\b', Keyword.Type),
             # v<nn> = value
             # ss<nn> = stack slot
             # jt<nn> = jump table
@@ -0,0 +1,30 @@
+//! Compile random functions for Intel x86-64 with the verifier enabled.
+//!
+//! Any panic in the code generator, verifier failure, or other compilation error is a crash.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cretonne;
+extern crate cton_fuzzgen;
+
+use cretonne::Context;
+use cretonne::ir::FunctionName;
+use cretonne::isa;
+use cretonne::settings::{self, Configurable};
+use cton_fuzzgen::{generate_function, Config};
+
+fuzz_target!(|data: &[u8]| {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("is_64bit").unwrap();
+    flag_builder.enable("enable_verifier").unwrap();
+    let mut isa_builder = isa::lookup("intel").unwrap();
+    isa_builder.enable("haswell").unwrap();
+    let isa = isa_builder.finish(settings::Flags::new(&flag_builder));
+
+    let mut ctx = Context::new();
+    ctx.func = generate_function(FunctionName::new("fuzz"), data, &Config::default());
+    if let Err(e) = ctx.compile(&*isa) {
+        panic!("{}\n{}", e, ctx.func.display(&*isa));
+    }
+});
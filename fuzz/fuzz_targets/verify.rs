@@ -0,0 +1,22 @@
+//! Check that the generated functions pass the verifier.
+//!
+//! This tests the generator itself and the `FunctionBuilder` it uses.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cretonne;
+extern crate cton_fuzzgen;
+
+use cretonne::ir::FunctionName;
+use cretonne::settings;
+use cretonne::verify_function;
+use cton_fuzzgen::{generate_function, Config};
+
+fuzz_target!(|data: &[u8]| {
+    let flags = settings::Flags::new(&settings::builder());
+    let func = generate_function(FunctionName::new("fuzz"), data, &Config::default());
+    if let Err(e) = verify_function(&func, &flags) {
+        panic!("{}\n{}", e, func.display(None));
+    }
+});
@@ -13,6 +13,8 @@ from .immediates import boolean, intcc, floatcc, memflags, regunit, trapcode
 from . import entities
 from .entities import ebb, sig_ref, func_ref, stack_slot, heap
 
+NullAry = InstructionFormat()
+
 Unary = InstructionFormat(VALUE)
 UnaryImm = InstructionFormat(imm64)
 UnaryIeee32 = InstructionFormat(ieee32)
@@ -50,6 +52,7 @@ BranchTable = InstructionFormat(VALUE, entities.jump_table)
 
 Call = InstructionFormat(func_ref, VARIABLE_ARGS)
 IndirectCall = InstructionFormat(sig_ref, VALUE, VARIABLE_ARGS)
+Invoke = InstructionFormat(func_ref, ebb, VARIABLE_ARGS)
 FuncAddr = InstructionFormat(func_ref)
 
 Load = InstructionFormat(memflags, VALUE, offset32)
@@ -8,7 +8,7 @@ from __future__ import absolute_import
 from cdsl.operands import Operand, VARIABLE_ARGS
 from cdsl.typevar import TypeVar
 from cdsl.instructions import Instruction, InstructionGroup
-from base.types import f32, f64, b1, iflags, fflags
+from base.types import f32, f64, b1, iflags, fflags, r32, r64
 from base.immediates import imm64, uimm8, uimm32, ieee32, ieee64, offset32
 from base.immediates import boolean, intcc, floatcc, memflags, regunit
 from base.immediates import trapcode
@@ -30,11 +30,14 @@ TxN = TypeVar(
         'TxN', 'A SIMD vector type',
         ints=True, floats=True, bools=True, scalars=False, simd=True)
 Any = TypeVar(
-        'Any', 'Any integer, float, or boolean scalar or vector type',
-        ints=True, floats=True, bools=True, scalars=True, simd=True)
+        'Any',
+        'Any integer, float, boolean, or reference scalar or vector type',
+        ints=True, floats=True, bools=True, scalars=True, simd=True,
+        specials=[r32, r64])
 Mem = TypeVar(
         'Mem', 'Any type that can be stored in memory',
-        ints=True, floats=True, simd=True)
+        ints=True, floats=True, simd=True, specials=[r32, r64])
+Ref = TypeVar('Ref', 'A reference type', specials=[r32, r64])
 MemTo = TypeVar(
         'MemTo', 'Any type that can be stored in memory',
         ints=True, floats=True, simd=True)
@@ -206,6 +209,54 @@ call_indirect = Instruction(
         """,
         ins=(SIG, callee, args), outs=rvals, is_call=True)
 
+PAD = Operand(
+        'PAD', entities.ebb,
+        doc='landing pad reached when the callee unwinds')
+
+invoke = Instruction(
+        'invoke', r"""
+        Direct function call with an exceptional successor.
+
+        Call a function which has been declared in the preamble, like
+        :inst:`call`. If the callee returns normally, execution continues at
+        the following instruction and the results in ``rvals`` are available.
+        If the callee unwinds, control is transferred to the landing pad
+        ``PAD`` instead.
+
+        The landing pad EBB can't take any arguments from the
+        :inst:`invoke` instruction, and the call results are not available
+        there. The exception object must be obtained from the runtime.
+
+        The :inst:`invoke` instruction is a branch to the landing pad, so the
+        normal successor is simply the rest of the EBB. It is common to
+        follow an :inst:`invoke` with a :inst:`jump` to a separate EBB.
+        """,
+        ins=(FN, PAD, args), outs=rvals, is_call=True, is_branch=True)
+
+return_call = Instruction(
+        'return_call', r"""
+        Direct tail call.
+
+        Call a function which has been declared in the preamble, and return its
+        results directly to the caller of the current function. The stack frame
+        of the current function is replaced by the callee's, so the instruction
+        never returns.
+
+        The callee's signature must have the same calling convention and
+        return types as the current function's.
+        """,
+        ins=(FN, args), is_call=True, is_terminator=True)
+
+return_call_indirect = Instruction(
+        'return_call_indirect', r"""
+        Indirect tail call.
+
+        Tail call the function pointed to by `callee` with the given
+        arguments, like :inst:`return_call`. The called function must match
+        the specified signature.
+        """,
+        ins=(SIG, callee, args), is_call=True, is_terminator=True)
+
 func_addr = Instruction(
         'func_addr', r"""
         Get the address of a function.
@@ -389,6 +440,14 @@ global_addr = Instruction(
         """,
         ins=GV, outs=addr)
 
+# A specialized form of global_addr instructions that only handles
+# symbolic names.
+globalsym_addr = Instruction(
+        'globalsym_addr', r"""
+        Compute the address of global variable GV, which is a symbolic name.
+        """,
+        ins=GV, outs=addr)
+
 #
 # WebAssembly bounds-checked heap accesses.
 #
@@ -461,6 +520,15 @@ bconst = Instruction(
         """,
         ins=N, outs=a)
 
+a = Operand('a', Ref, doc='A null reference')
+null = Instruction(
+        'null', r"""
+        Null reference constant.
+
+        Create a reference value that doesn't refer to any object.
+        """,
+        outs=a)
+
 #
 # Generics.
 #
@@ -559,6 +627,36 @@ regfill = Instruction(
         """,
         ins=(x, SS, dst),
         other_side_effects=True)
+
+Offset = Operand('Offset', imm64, 'Offset from current stack pointer')
+
+adjust_sp_imm = Instruction(
+        'adjust_sp_imm', r"""
+        Adds ``Offset`` immediate offset value to the stack pointer register.
+
+        This instruction is used in the prologue and epilogue of functions to
+        allocate and deallocate the stack frame. It is inserted after register
+        allocation, so the stack pointer is not visible as an SSA value.
+        """,
+        ins=(Offset,),
+        other_side_effects=True)
+
+R = Operand('args', VARIABLE_ARGS, doc='Live references')
+
+safepoint = Instruction(
+        'safepoint', r"""
+        Mark a safepoint where a garbage collector may run.
+
+        The arguments are the references that are live across the safepoint.
+        They must all be in stack slots, and a stack map listing those stack
+        slots is emitted at the current code offset.
+
+        When the ``enable_safepoints`` setting is on, the register allocator
+        inserts this instruction after every call that returns, so the stack
+        map applies to the call's return address.
+        """,
+        ins=R, other_side_effects=True)
+
 #
 # Vector operations
 #
@@ -694,6 +792,16 @@ icmp_imm = Instruction(
         """,
         ins=(Cond, x, Y), outs=a)
 
+x = Operand('x', Ref)
+
+is_null = Instruction(
+        'is_null', r"""
+        Test if a reference is null.
+
+        Return true if ``x`` is the null reference created by :inst:`null`.
+        """,
+        ins=x, outs=a)
+
 f = Operand('f', iflags)
 x = Operand('x', iB)
 y = Operand('y', iB)
@@ -40,6 +40,16 @@ return_at_end = BoolSetting(
 
 is_compressed = BoolSetting("Enable compressed instructions")
 
+is_pic = BoolSetting(
+        """
+        Generate position-independent code.
+
+        Calls to other functions go through the PLT, and the addresses of
+        functions and symbolic global variables are loaded from the GOT. This
+        is only supported for 64-bit Intel code so far, and compiling 32-bit
+        Intel code fails when it is enabled.
+        """)
+
 enable_float = BoolSetting(
         """Enable the use of floating-point instructions""",
         default=True)
@@ -52,6 +62,15 @@ enable_atomics = BoolSetting(
         """Enable the use of atomic instructions""",
         default=True)
 
+enable_safepoints = BoolSetting(
+        """
+        Insert safepoints for precise garbage collection.
+
+        The register allocator inserts a :inst:`safepoint` instruction after
+        every call, listing the references that are live across the call.
+        The code generator emits a stack map for each safepoint.
+        """)
+
 #
 # Settings specific to the `spiderwasm` calling convention.
 #
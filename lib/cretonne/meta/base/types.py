@@ -2,7 +2,7 @@
 The base.types module predefines all the Cretonne scalar types.
 """
 from __future__ import absolute_import
-from cdsl.types import IntType, FloatType, BoolType, FlagsType
+from cdsl.types import IntType, FloatType, BoolType, FlagsType, ReferenceType
 
 #: Boolean.
 b1 = BoolType(1)    #: 1-bit bool. Type is abstract (can't be stored in mem)
@@ -44,3 +44,19 @@ fflags = FlagsType(
         CPU flags representing the result of a floating point comparison. These
         flags can be tested with a :type:`floatcc` condition code.
         """)
+
+#: A 32-bit reference.
+r32 = ReferenceType(
+        32, """
+        A 32-bit reference to an object managed by a garbage collector. The
+        register allocator spills live references across safepoints so they
+        can be found in stack maps.
+        """)
+
+#: A 64-bit reference.
+r64 = ReferenceType(
+        64, """
+        A 64-bit reference to an object managed by a garbage collector. The
+        register allocator spills live references across safepoints so they
+        can be found in stack maps.
+        """)
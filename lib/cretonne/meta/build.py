@@ -13,6 +13,7 @@ import gen_encoding
 import gen_legalizer
 import gen_registers
 import gen_binemit
+import gen_decode
 
 parser = argparse.ArgumentParser(description='Generate sources for Cretonne.')
 parser.add_argument('--out-dir', help='set output directory')
@@ -29,4 +30,5 @@ gen_encoding.generate(isas, out_dir)
 gen_legalizer.generate(isas, out_dir)
 gen_registers.generate(isas, out_dir)
 gen_binemit.generate(isas, out_dir)
+gen_decode.generate(isas, out_dir)
 gen_build_deps.generate()
@@ -7,6 +7,7 @@ for patern matching an rewriting of cretonne instructions.
 from __future__ import absolute_import
 from . import instructions
 from .typevar import TypeVar
+from .types import ValueType, FlagsType
 from .predicates import IsEqual, And, TypePredicate, CtrlTypePredicate
 
 try:
@@ -242,12 +243,16 @@ class Var(Atom):
         # type: () -> TypeVar
         """Get the type variable representing the type of this variable."""
         if not self.typevar:
-            # Create a TypeVar allowing all types.
+            # Create a TypeVar allowing all types. Flags types are included so
+            # patterns can pass CPU flags between instructions.
+            flags = [t for t in ValueType.all_special_types
+                     if isinstance(t, FlagsType)]
             tv = TypeVar(
                     'typeof_{}'.format(self),
                     'Type of the pattern variable `{}`'.format(self),
                     ints=True, floats=True, bools=True,
-                    scalars=True, simd=True, bitvecs=True)
+                    scalars=True, simd=True, bitvecs=True,
+                    specials=flags)
             self.original_typevar = tv
             self.typevar = tv
         return self.typevar
@@ -325,6 +325,8 @@ class EncRecipe(object):
     :param instp: Instruction predicate.
     :param isap: ISA predicate.
     :param emit: Rust code for binary emission.
+    :param decode: Rust code for decoding binary machine code.
+    :param asm: Rust code for printing decoded machine code as assembly.
     """
 
     def __init__(
@@ -338,7 +340,9 @@ class EncRecipe(object):
             clobbers_flags=True,  # type: bool
             instp=None,           # type: PredNode
             isap=None,            # type: PredNode
-            emit=None             # type: str
+            emit=None,            # type: str
+            decode=None,          # type: str
+            asm=None              # type: str
             ):
         # type: (...) -> None
         self.name = name
@@ -350,6 +354,8 @@ class EncRecipe(object):
         self.instp = instp
         self.isap = isap
         self.emit = emit
+        self.decode = decode
+        self.asm = asm
         if instp:
             assert instp.predicate_context() == format
         self.number = None  # type: int
@@ -460,7 +466,9 @@ class Encoding(object):
                 "Format {} must match recipe: {}".format(
                     self.inst.format, recipe.format))
 
-        if self.inst.is_branch:
+        # The landing pad of an `invoke` is recorded in the call-site table, it
+        # is not encoded in the instruction.
+        if self.inst.is_branch and not self.inst.is_call:
             assert recipe.branch_range, (
                     'Recipe {} for {} must have a branch_range'
                     .format(recipe, self.inst.name))
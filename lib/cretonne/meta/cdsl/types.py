@@ -303,6 +303,33 @@ class FlagsType(SpecialType):
         return 'FlagsType({})'.format(self.name)
 
 
+class ReferenceType(SpecialType):
+    """
+    A type representing a reference to an object managed by a garbage
+    collector.
+
+    References are opaque pointers of a fixed size. They can't be used to form
+    vectors.
+    """
+
+    def __init__(self, bits, doc):
+        # type: (int, str) -> None
+        super(ReferenceType, self).__init__(
+                name='r{:d}'.format(bits),
+                membytes=bits // 8,
+                doc=doc)
+        self.bits = bits
+
+    def __repr__(self):
+        # type: () -> str
+        return 'ReferenceType(bits={})'.format(self.bits)
+
+    def lane_bits(self):
+        # type: () -> int
+        """Return the number of bits in a lane."""
+        return self.bits
+
+
 class BVType(ValueType):
     """A flat bitvector type. Used for semantics description only."""
 
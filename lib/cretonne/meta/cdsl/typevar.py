@@ -213,7 +213,7 @@ class TypeSet(object):
                 tuple(sorted(list(self.floats))),
                 tuple(sorted(list(self.bools))),
                 tuple(sorted(list(self.bitvecs))),
-                tuple(sorted(list(self.specials))))
+                tuple(sorted(s.number for s in self.specials)))
 
     def __hash__(self):
         # type: () -> int
@@ -245,7 +245,8 @@ class TypeSet(object):
         if len(self.bitvecs) > 0:
             s += ', bitvecs={}'.format(pp_set(self.bitvecs))
         if len(self.specials) > 0:
-            s += ', specials=[{}]'.format(pp_set(self.specials))
+            s += ', specials=[{}]'.format(pp_set(
+                    t.name for t in self.specials))
         return s + ')'
 
     def emit_fields(self, fmt):
@@ -264,6 +265,12 @@ class TypeSet(object):
             fmt.line('{}: BitSet::<u{}>({}),'
                      .format(field, bits, encode_bitset(vals, bits)))
 
+        # Reference types are the only special types that can appear in a
+        # polymorphic type set.
+        refs = [int_log2(t.bits) for t in self.specials
+                if isinstance(t, types.ReferenceType)]
+        fmt.line('refs: BitSet::<u8>({}),'.format(encode_bitset(refs, 8)))
+
     def __iand__(self, other):
         # type: (TypeSet) -> TypeSet
         """
@@ -521,7 +528,7 @@ class TypeSet(object):
                 assert nlanes == 1
                 yield types.BVType.with_bits(bits)
 
-        for spec in self.specials:
+        for spec in sorted(self.specials, key=lambda s: s.number):
             yield spec
 
     def get_singleton(self):
@@ -0,0 +1,189 @@
+"""
+Generate machine code decoders and disassemblers for each ISA.
+
+The decoder tries the encodings of a CPU mode in order until the `decode`
+code snippet of an encoding recipe accepts the machine code. The `asm` code
+snippet of the recipe then prints the machine instructions it read as
+assembly text.
+"""
+
+from __future__ import absolute_import
+from cdsl.registers import RegClass, Register, Stack
+from cdsl.predicates import And, IsEqual
+import srcgen
+
+try:
+    from typing import Sequence, List, Set, Tuple, Any  # noqa
+    from cdsl.isa import TargetISA, CPUMode, Encoding, EncRecipe  # noqa
+    from cdsl.isa import OperandConstraint  # noqa
+except ImportError:
+    pass
+
+
+def operand(cst, prefix, i):
+    # type: (OperandConstraint, str, int) -> str
+    """
+    Get a Rust expression for the `Operand` decoded for the `i`'th input or
+    output operand with the constraint `cst`.
+
+    The recipe's `decode` snippet binds registers to bank-relative register
+    numbers in `in_reg0` etc., and stack operands to `Operand` values in
+    `in_stk0` etc.
+    """
+    if isinstance(cst, RegClass):
+        v = '{}_reg{}'.format(prefix, i)
+        if cst.bank.first_unit != 0:
+            v = '{} + {}'.format(cst.bank.first_unit, v)
+        return 'Operand::Reg({})'.format(v)
+    elif isinstance(cst, Register):
+        return 'Operand::Reg({})'.format(cst.unit)
+    else:
+        assert isinstance(cst, Stack)
+        return '{}_stk{}'.format(prefix, i)
+
+
+def regmove_operand(cst, var):
+    # type: (OperandConstraint, str) -> str
+    """
+    Get a Rust expression for the `Operand` of a register move with the
+    register class of `cst`.
+    """
+    if isinstance(cst, Stack):
+        cst = cst.regclass
+    assert isinstance(cst, RegClass)
+    if cst.bank.first_unit != 0:
+        var = '{} + {}'.format(cst.bank.first_unit, var)
+    return 'Operand::Reg({})'.format(var)
+
+
+def gen_recipe(recipe, fmt):
+    # type: (EncRecipe, srcgen.Formatter) -> None
+    """
+    Generate code to decode a single recipe.
+
+    - Call hand-written code to read the machine code and bind the registers
+      of the operands.
+    - Record the operands, knowing the register constraints.
+    - Call hand-written code to print the machine instructions.
+    """
+    assert recipe.asm is not None, \
+        'Recipe {} can be decoded, but has no asm'.format(recipe.name)
+    fmt.multi_line(recipe.decode)
+
+    # Register moves keep their registers in immediate fields, and the
+    # snippet binds `src` and `dst` instead.
+    fname = recipe.format.name
+    if fname == 'RegMove':
+        fmt.format('d.arg({});', regmove_operand(recipe.ins[0], 'src'))
+        fmt.format('d.dst({});', regmove_operand(recipe.ins[0], 'dst'))
+    elif fname == 'RegSpill':
+        fmt.format('d.arg({});', regmove_operand(recipe.ins[0], 'src'))
+        fmt.line('d.dst(dst);')
+    elif fname == 'RegFill':
+        fmt.line('d.arg(src);')
+        fmt.format('d.dst({});', regmove_operand(recipe.ins[0], 'dst'))
+    else:
+        for i, cst in enumerate(recipe.ins):
+            fmt.format('d.arg({});', operand(cst, 'in', i))
+        for i, cst in enumerate(recipe.outs):
+            if isinstance(cst, int):
+                # Tied to an input operand.
+                fmt.format('d.out({});', operand(recipe.ins[cst], 'in', cst))
+            else:
+                fmt.format('d.out({});', operand(cst, 'out', i))
+    fmt.multi_line(recipe.asm)
+    fmt.line('return Some(());')
+
+
+def fixed_cond(enc):
+    # type: (Encoding) -> str
+    """
+    Get a Rust expression for the condition code required by the instruction
+    predicate of `enc`.
+
+    Some encodings only apply to a single condition code, so it can't be
+    recovered from the machine code.
+    """
+    preds = [enc.instp]
+    while preds:
+        p = preds.pop()
+        if isinstance(p, And):
+            preds.extend(p.parts)
+        elif isinstance(p, IsEqual):
+            kind = p.field.kind.name
+            if kind == 'intcc':
+                return 'Some(Cond::Int({}))'.format(p.value)
+            if kind == 'floatcc':
+                return 'Some(Cond::Float({}))'.format(p.value)
+    return 'None'
+
+
+def gen_entries(cpumode, fmt):
+    # type: (CPUMode, srcgen.Formatter) -> None
+    """
+    Emit the table of encodings for `cpumode` that can be decoded.
+    """
+    entries = []  # type: List[Encoding]
+    seen = set()  # type: Set[Tuple[Any, ...]]
+    for enc in cpumode.encodings:
+        if enc.recipe.decode is None or enc.recipe.size == 0:
+            continue
+        key = (enc.inst, enc.ctrl_typevar(), enc.recipe.number,
+               enc.encbits, fixed_cond(enc))
+        if key in seen:
+            continue
+        seen.add(key)
+        entries.append(enc)
+
+    fmt.doc_comment(
+            'Encodings in the {} CPU mode, in decoding order.'
+            .format(cpumode.name))
+    with fmt.indented(
+            'pub static DECODE_{}: [DecodeEntry; {}] = ['
+            .format(cpumode.name.upper(), len(entries)), '];'):
+        for enc in entries:
+            ty = enc.ctrl_typevar()
+            with fmt.indented('DecodeEntry {', '},'):
+                fmt.format('opcode: Opcode::{},', enc.inst.camel_name)
+                fmt.format(
+                        'ctrl_type: {},',
+                        ty.rust_name() if ty else 'ir::types::VOID')
+                fmt.format('cond: {},', fixed_cond(enc))
+                fmt.format('recipe: {},', enc.recipe.number)
+                fmt.format('bits: {:#06x},', enc.encbits)
+
+
+def gen_isa(isa, fmt):
+    # type: (TargetISA, srcgen.Formatter) -> None
+    """
+    Generate a decoder for `isa`.
+    """
+    for cpumode in isa.cpumodes:
+        gen_entries(cpumode, fmt)
+
+    fmt.doc_comment(
+            '''
+            Decode machine code for the {} ISA, assuming the `opcode`
+            instruction was encoded with `recipe` and `bits`.
+            '''.format(isa.name))
+    fmt.line('#[allow(unused_variables, unreachable_code)]')
+    with fmt.indented(
+            'pub fn decode_recipe(opcode: Opcode, recipe: u16, bits: u16, '
+            'd: &mut Decoder) -> Option<()> {', '}'):
+        with fmt.indented('match recipe {', '}'):
+            for i, recipe in enumerate(isa.all_recipes):
+                if recipe.decode is None:
+                    continue
+                fmt.comment(recipe.name)
+                with fmt.indented('{} => {{'.format(i), '}'):
+                    gen_recipe(recipe, fmt)
+            fmt.line('_ => {}')
+        fmt.line('None')
+
+
+def generate(isas, out_dir):
+    # type: (Sequence[TargetISA], str) -> None
+    for isa in isas:
+        fmt = srcgen.Formatter()
+        gen_isa(isa, fmt)
+        fmt.update_file('decode-{}.rs'.format(isa.name), out_dir)
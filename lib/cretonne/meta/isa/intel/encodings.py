@@ -4,6 +4,7 @@ Intel Encodings.
 from __future__ import absolute_import
 from cdsl.predicates import IsUnsignedInt
 from base import instructions as base
+from base import types
 from base.formats import UnaryImm
 from .defs import I32, I64
 from . import recipes as r
@@ -100,6 +101,34 @@ def enc_i32_i64_ld_st(inst, w_bit, recipe, *args, **kwargs):
         I64.enc(inst.i64.any, *recipe(*args, **kwargs))
 
 
+def enc_r32_r64(inst, recipe, *args, **kwargs):
+    # type: (MaybeBoundInst, r.TailRecipe, *int, **int) -> None
+    """
+    Add encodings for `inst.r32` to I32.
+    Add encodings for `inst.r32` to I64 with and without REX.
+    Add encodings for `inst.r64` to I64 with a REX.W prefix.
+    """
+    I32.enc(inst.r32, *recipe(*args, **kwargs))
+
+    I64.enc(inst.r32, *recipe.rex(*args, **kwargs))
+    I64.enc(inst.r32, *recipe(*args, **kwargs))
+
+    I64.enc(inst.r64, *recipe.rex(*args, w=1, **kwargs))
+
+
+def enc_r32_r64_ld_st(inst, recipe, *args, **kwargs):
+    # type: (MaybeBoundInst, r.TailRecipe, *int, **int) -> None
+    """
+    Same as `enc_r32_r64`, but for loads and stores with any address type.
+    """
+    I32.enc(inst.r32.any, *recipe(*args, **kwargs))
+
+    I64.enc(inst.r32.any, *recipe.rex(*args, **kwargs))
+    I64.enc(inst.r32.any, *recipe(*args, **kwargs))
+
+    I64.enc(inst.r64.any, *recipe.rex(*args, w=1, **kwargs))
+
+
 for inst,           opc in [
         (base.iadd, 0x01),
         (base.isub, 0x29),
@@ -186,6 +215,10 @@ I64.enc(base.ctz.i64, *r.urm.rex(0xf3, 0x0f, 0xbc, w=1),
 I64.enc(base.ctz.i32, *r.urm.rex(0xf3, 0x0f, 0xbc), isap=cfg.use_bmi1)
 I64.enc(base.ctz.i32, *r.urm(0xf3, 0x0f, 0xbc), isap=cfg.use_bmi1)
 
+# Bit scans, used to legalize `clz` and `ctz` without LZCNT or BMI1.
+enc_i32_i64(x86.bsr, r.bsf_and_bsr, 0x0f, 0xbd)
+enc_i32_i64(x86.bsf, r.bsf_and_bsr, 0x0f, 0xbc)
+
 #
 # Loads and stores.
 #
@@ -210,6 +243,9 @@ enc_i32_i64(base.regspill, r.rsp32, 0x89)
 enc_both(base.spill.b1, r.spSib32, 0x89)
 enc_both(base.regspill.b1, r.rsp32, 0x89)
 
+# Address of a stack slot: `lea`.
+enc_i32_i64(base.stack_addr, r.spaddr32, 0x8d)
+
 for recipe in [r.ld, r.ldDisp8, r.ldDisp32]:
     enc_i32_i64_ld_st(base.load, True, recipe, 0x8b)
     enc_i64(base.uload32.i64, recipe, 0x8b)
@@ -226,6 +262,33 @@ enc_i32_i64(base.regfill, r.rfi32, 0x8b)
 enc_both(base.fill.b1, r.fiSib32, 0x8b)
 enc_both(base.regfill.b1, r.rfi32, 0x8b)
 
+#
+# Reference types.
+#
+# These come after the integer encodings with the same bits so the
+# decoder prefers the integer instructions.
+#
+
+enc_r32_r64(base.copy, r.umr, 0x89)
+enc_r32_r64(base.regmove, r.rmov, 0x89)
+enc_r32_r64(base.spill, r.spSib32, 0x89)
+enc_r32_r64(base.regspill, r.rsp32, 0x89)
+enc_r32_r64(base.fill, r.fiSib32, 0x8b)
+enc_r32_r64(base.regfill, r.rfi32, 0x8b)
+
+for recipe in [r.st, r.stDisp8, r.stDisp32]:
+    enc_r32_r64_ld_st(base.store, recipe, 0x89)
+
+for recipe in [r.ld, r.ldDisp8, r.ldDisp32]:
+    enc_r32_r64_ld_st(base.load, recipe, 0x8b)
+
+enc_r32_r64(base.null, r.rnull, 0x31)
+enc_r32_r64(base.is_null, r.isnull, 0x85)
+
+# Safepoints emit no code, just a stack map.
+I32.enc(base.safepoint, r.safepoint, 0)
+I64.enc(base.safepoint, r.safepoint, 0)
+
 #
 # Float loads and stores.
 #
@@ -256,18 +319,38 @@ enc_both(base.regspill.f32, r.frsp32, 0x66, 0x0f, 0x7e)
 enc_both(base.spill.f64, r.fspSib32, 0x66, 0x0f, 0xd6)
 enc_both(base.regspill.f64, r.frsp32, 0x66, 0x0f, 0xd6)
 
+# Callee-saved XMM registers are saved and restored with all 128 bits using
+# `movups`.
+enc_i64(base.spill.bind(types.f64.by(2)), r.fspSib32, 0x0f, 0x11)
+enc_i64(base.fill.bind(types.f64.by(2)), r.ffiSib32, 0x0f, 0x10)
+
 #
 # Function addresses.
 #
 
 I32.enc(base.func_addr.i32, *r.fnaddr4(0xb8))
-I64.enc(base.func_addr.i64, *r.fnaddr8.rex(0xb8, w=1))
+I64.enc(base.func_addr.i64, *r.fnaddr8.rex(0xb8, w=1), isap=cfg.not_pic)
+I64.enc(base.func_addr.i64, *r.got_fnaddr8.rex(0x8b, w=1), isap=cfg.use_pic)
+
+#
+# Global addresses.
+#
+
+I32.enc(base.globalsym_addr.i32, *r.gvaddr4(0xb8))
+I64.enc(base.globalsym_addr.i64, *r.gvaddr8.rex(0xb8, w=1), isap=cfg.not_pic)
+I64.enc(base.globalsym_addr.i64, *r.got_gvaddr8.rex(0x8b, w=1),
+        isap=cfg.use_pic)
 
 #
 # Call/return
 #
 I32.enc(base.call, *r.call_id(0xe8))
-I64.enc(base.call, *r.call_id(0xe8))
+I64.enc(base.call, *r.call_id(0xe8), isap=cfg.not_pic)
+I64.enc(base.call, *r.call_plt_id(0xe8), isap=cfg.use_pic)
+
+I32.enc(base.invoke, *r.invoke_id(0xe8))
+I64.enc(base.invoke, *r.invoke_id(0xe8), isap=cfg.not_pic)
+I64.enc(base.invoke, *r.invoke_plt_id(0xe8), isap=cfg.use_pic)
 
 I32.enc(base.call_indirect.i32, *r.call_r(0xff, rrr=2))
 I64.enc(base.call_indirect.i64, *r.call_r.rex(0xff, rrr=2))
@@ -276,6 +359,25 @@ I64.enc(base.call_indirect.i64, *r.call_r(0xff, rrr=2))
 I32.enc(base.x_return, *r.ret(0xc3))
 I64.enc(base.x_return, *r.ret(0xc3))
 
+# Tail calls are only supported in 64-bit mode.
+I64.enc(base.return_call, *r.tcall_id(0xe9), isap=cfg.not_pic)
+I64.enc(base.return_call, *r.tcall_plt_id(0xe9), isap=cfg.use_pic)
+I64.enc(base.return_call_indirect.i64, *r.tjmp_r(0xff, rrr=4))
+
+#
+# Stack frames
+#
+I32.enc(x86.push.i32, *r.pushq(0x50))
+enc_i64(x86.push.i64, r.pushq, 0x50)
+
+I32.enc(x86.pop.i32, *r.popq(0x58))
+enc_i64(x86.pop.i64, r.popq, 0x58)
+
+I32.enc(base.adjust_sp_imm, *r.adjustsp_ib(0x83))
+I32.enc(base.adjust_sp_imm, *r.adjustsp_id(0x81))
+I64.enc(base.adjust_sp_imm, *r.adjustsp_ib.rex(0x83, w=1))
+I64.enc(base.adjust_sp_imm, *r.adjustsp_id.rex(0x81, w=1))
+
 #
 # Branches
 #
@@ -8,6 +8,7 @@ target ISA.
 from cdsl.operands import Operand
 from cdsl.typevar import TypeVar
 from cdsl.instructions import Instruction, InstructionGroup
+from base.types import iflags
 
 
 GROUP = InstructionGroup("x86", "Intel-specific instruction set")
@@ -99,4 +100,53 @@ fmax = Instruction(
         """,
         ins=(x, y), outs=a)
 
+x = Operand('x', iWord)
+
+push = Instruction(
+        'x86_push', r"""
+        Pushes a value onto the stack.
+
+        Decrements the stack pointer and stores the specified value on to the
+        top.
+
+        This is polymorphic in i32 and i64. However, it is only implemented
+        for i64 in 64-bit mode, and only for i32 in 32-bit mode.
+        """,
+        ins=x, can_store=True, other_side_effects=True)
+
+pop = Instruction(
+        'x86_pop', r"""
+        Pops a value from the stack.
+
+        Loads a value from the top of the stack and then increments the stack
+        pointer.
+
+        This is polymorphic in i32 and i64. However, it is only implemented
+        for i64 in 64-bit mode, and only for i32 in 32-bit mode.
+        """,
+        outs=x, can_load=True, other_side_effects=True)
+
+y = Operand('y', iWord)
+rflags = Operand('rflags', iflags)
+
+bsr = Instruction(
+        'x86_bsr', r"""
+        Bit Scan Reverse.
+
+        Return the bit index of the most significant 1 bit in `x`. The result
+        is undefined when `x` is zero, but the ZF flag in `rflags` is set in
+        that case so it can be detected and handled.
+        """,
+        ins=x, outs=(y, rflags))
+
+bsf = Instruction(
+        'x86_bsf', r"""
+        Bit Scan Forward.
+
+        Return the bit index of the least significant 1 bit in `x`. The result
+        is undefined when `x` is zero, but the ZF flag in `rflags` is set in
+        that case so it can be detected and handled.
+        """,
+        ins=x, outs=(y, rflags))
+
 GROUP.close()
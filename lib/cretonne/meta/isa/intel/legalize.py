@@ -4,7 +4,7 @@ Custom legalization patterns for Intel.
 from __future__ import absolute_import
 from cdsl.ast import Var
 from cdsl.xform import Rtl, XFormGroup
-from base.immediates import imm64, floatcc
+from base.immediates import imm64, intcc, floatcc
 from base.types import i32, i64
 from base import legalize as shared
 from base import instructions as insts
@@ -100,3 +100,84 @@ intel_expand.custom_legalize(insts.fcvt_from_uint, 'expand_fcvt_from_uint')
 # Conversions from float to int can trap.
 intel_expand.custom_legalize(insts.fcvt_to_sint, 'expand_fcvt_to_sint')
 intel_expand.custom_legalize(insts.fcvt_to_uint, 'expand_fcvt_to_uint')
+
+#
+# Bit counting without LZCNT, BMI1, or POPCNT.
+#
+# The `bsr` and `bsf` instructions leave their result undefined when the input
+# is zero, but they set the ZF flag so that case can be selected separately.
+index1 = Var('index1')
+index2 = Var('index2')
+rflags = Var('rflags')
+zero = Var('zero')
+c_minus_one = Var('c_minus_one')
+c_msb = Var('c_msb')
+c_bits = Var('c_bits')
+
+for ty in [i32, i64]:
+    intel_expand.legalize(
+            a << insts.clz.bind(ty)(x),
+            Rtl(
+                c_minus_one << insts.iconst(imm64(-1)),
+                c_msb << insts.iconst(imm64(ty.lane_bits() - 1)),
+                (index1, rflags) << x86.bsr(x),
+                zero << insts.trueif(intcc.eq, rflags),
+                index2 << insts.select(zero, c_minus_one, index1),
+                a << insts.isub(c_msb, index2)
+            ))
+
+    intel_expand.legalize(
+            a << insts.ctz.bind(ty)(x),
+            Rtl(
+                c_bits << insts.iconst(imm64(ty.lane_bits())),
+                (index1, rflags) << x86.bsf(x),
+                zero << insts.trueif(intcc.eq, rflags),
+                a << insts.select(zero, c_bits, index1)
+            ))
+
+# Population count by adding up the bits in each nibble, then each byte.
+# Subtracting `x >> 1`, `x >> 2`, and `x >> 3` with the bits that would cross a
+# nibble boundary masked off leaves the number of set bits in each nibble.
+v1 = Var('v1')
+v2 = Var('v2')
+v3 = Var('v3')
+v4 = Var('v4')
+v5 = Var('v5')
+v6 = Var('v6')
+v7 = Var('v7')
+v8 = Var('v8')
+v9 = Var('v9')
+v10 = Var('v10')
+v11 = Var('v11')
+v12 = Var('v12')
+v13 = Var('v13')
+c_77 = Var('c_77')
+c_0f = Var('c_0f')
+c_01 = Var('c_01')
+
+popcnt_masks = [
+        (i32, 0x77777777, 0x0f0f0f0f, 0x01010101),
+        (i64, 0x7777777777777777, 0x0f0f0f0f0f0f0f0f, 0x0101010101010101)]
+
+for ty, c77, c0f, c01 in popcnt_masks:
+    intel_expand.legalize(
+            a << insts.popcnt.bind(ty)(x),
+            Rtl(
+                c_77 << insts.iconst(imm64(c77)),
+                v1 << insts.ushr_imm(x, imm64(1)),
+                v2 << insts.band(v1, c_77),
+                v3 << insts.isub(x, v2),
+                v4 << insts.ushr_imm(v2, imm64(1)),
+                v5 << insts.band(v4, c_77),
+                v6 << insts.isub(v3, v5),
+                v7 << insts.ushr_imm(v5, imm64(1)),
+                v8 << insts.band(v7, c_77),
+                v9 << insts.isub(v6, v8),
+                v10 << insts.ushr_imm(v9, imm64(4)),
+                v11 << insts.iadd(v9, v10),
+                c_0f << insts.iconst(imm64(c0f)),
+                v12 << insts.band(v11, c_0f),
+                c_01 << insts.iconst(imm64(c01)),
+                v13 << insts.imul(v12, c_01),
+                a << insts.ushr_imm(v13, imm64(ty.lane_bits() - 8))
+            ))
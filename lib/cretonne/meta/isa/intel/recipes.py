@@ -6,11 +6,12 @@ from cdsl.isa import EncRecipe
 from cdsl.predicates import IsSignedInt, IsEqual, Or
 from cdsl.registers import RegClass
 from base.formats import Unary, UnaryImm, Binary, BinaryImm, MultiAry
-from base.formats import Trap, Call, IndirectCall, Store, Load
+from base.formats import NullAry, Trap, Call, IndirectCall, Store, Load
+from base.formats import Invoke
 from base.formats import IntCompare, FloatCompare, IntCond, FloatCond
 from base.formats import Jump, Branch, BranchInt, BranchFloat
-from base.formats import Ternary, FuncAddr
-from base.formats import RegMove, RegSpill, RegFill
+from base.formats import Ternary, FuncAddr, UnaryGlobalVar
+from base.formats import RegMove, RegSpill, RegFill, StackLoad
 from .registers import GPR, ABCD, FPR, GPR8, FPR8, FLAG, StackGPR32, StackFPR32
 from .defs import supported_floatccs
 
@@ -99,6 +100,18 @@ def replace_put_op(emit, prefix):
         return emit.replace('PUT_OP', 'put_' + prefix.lower())
 
 
+def replace_get_op(decode, prefix):
+    # type: (str, str) -> str
+    """
+    Given a snippet of Rust code (or None), replace the `GET_OP` macro with the
+    corresponding `get_*` function from the `decode.rs` module.
+    """
+    if decode is None:
+        return None
+    else:
+        return decode.replace('GET_OP', 'get_' + prefix.lower())
+
+
 # Register class mapping for no-REX instructions.
 NOREX_MAP = {
         GPR: GPR8,
@@ -137,6 +150,13 @@ class TailRecipe:
     The `emit` parameter contains Rust code to actually emit an encoding, like
     `EncRecipe` does it. Additionally, the text `PUT_OP` is substituted with
     the proper `put_*` function from the `intel/binemit.rs` module.
+
+    The `decode` parameter contains Rust code to decode an encoding, like
+    `EncRecipe` does it. The text `GET_OP` is substituted with the proper
+    `get_*` function from the `intel/decode.rs` module.
+
+    The `asm` parameter contains Rust code to print a decoded encoding as AT&T
+    assembly, like `EncRecipe` does it.
     """
 
     def __init__(
@@ -152,7 +172,9 @@ class TailRecipe:
             isap=None,              # type: PredNode
             when_prefixed=None,     # type: TailRecipe
             requires_prefix=False,  # type: bool
-            emit=None               # type: str
+            emit=None,              # type: str
+            decode=None,            # type: str
+            asm=None                # type: str
             ):
         # type: (...) -> None
         self.name = name
@@ -167,6 +189,8 @@ class TailRecipe:
         self.when_prefixed = when_prefixed
         self.requires_prefix = requires_prefix
         self.emit = emit
+        self.decode = decode
+        self.asm = asm
 
         # Cached recipes, keyed by name prefix.
         self.recipes = dict()  # type: Dict[str, EncRecipe]
@@ -199,7 +223,9 @@ class TailRecipe:
                 clobbers_flags=self.clobbers_flags,
                 instp=self.instp,
                 isap=self.isap,
-                emit=replace_put_op(self.emit, name))
+                emit=replace_put_op(self.emit, name),
+                decode=replace_get_op(self.decode, name),
+                asm=self.asm)
 
             recipe.ins = map_regs_norex(recipe.ins)
             recipe.outs = map_regs_norex(recipe.outs)
@@ -242,7 +268,9 @@ class TailRecipe:
                 clobbers_flags=self.clobbers_flags,
                 instp=self.instp,
                 isap=self.isap,
-                emit=replace_put_op(self.emit, name))
+                emit=replace_put_op(self.emit, name),
+                decode=replace_get_op(self.decode, name),
+                asm=self.asm)
             self.recipes[name] = recipe
 
         return (self.recipes[name], bits)
@@ -268,10 +296,21 @@ def floatccs(iform):
 # copies and no-op conversions.
 null = EncRecipe('null', Unary, size=0, ins=GPR, outs=0, emit='')
 
+# A safepoint emits no code. It records a stack map of the references passed
+# as arguments.
+safepoint = EncRecipe(
+        'safepoint', MultiAry, size=0, ins=(), outs=(),
+        emit='''
+        let args = func.dfg.inst_args(inst);
+        sink.add_stackmap(StackMap::from_values(args, func, divert));
+        ''')
+
 # XX opcode, no ModR/M.
 trap = TailRecipe(
         'trap', Trap, size=0, ins=(), outs=(),
-        emit='PUT_OP(bits, BASE_REX, sink);')
+        emit='PUT_OP(bits, BASE_REX, sink);',
+        decode='GET_OP(bits, 0, d)?;',
+        asm='asm_inst(bits, &[], d)?;')
 
 # XX /r
 rr = TailRecipe(
@@ -279,6 +318,14 @@ rr = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
         modrm_rr(in_reg0, in_reg1, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, in_reg1) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg1), int_reg(d, bits, in_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r with operands swapped. (RM form).
@@ -287,6 +334,14 @@ rrx = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
         modrm_rr(in_reg1, in_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg1), int_reg(d, bits, in_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r with FPR ins and outs. A form.
@@ -295,6 +350,14 @@ fa = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
         modrm_rr(in_reg1, in_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [xmm(in_reg1), xmm(in_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r with FPR ins and outs. A form with input operands swapped.
@@ -303,6 +366,14 @@ fax = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
         modrm_rr(in_reg0, in_reg1, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, in_reg1) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [xmm(in_reg0), xmm(in_reg1)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r, but for a unary operator with separate input/output register, like
@@ -313,6 +384,14 @@ umr = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(out_reg0, in_reg0), sink);
         modrm_rr(out_reg0, in_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (out_reg0, in_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # Same as umr, but with FPR -> GPR registers.
@@ -322,6 +401,14 @@ rfumr = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(out_reg0, in_reg0), sink);
         modrm_rr(out_reg0, in_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (out_reg0, in_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [xmm(in_reg0), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r, but for a unary operator with separate input/output register.
@@ -331,6 +418,31 @@ urm = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
         modrm_rr(in_reg0, out_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [src_reg(d, bits, in_reg0), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# XX /r, RM form, for bsf and bsr. The result register is undefined when the
+# input is zero, which is reported in the ZF flag.
+bsf_and_bsr = TailRecipe(
+        'bsf_and_bsr', Unary, size=1, ins=GPR, outs=(GPR, FLAG.eflags),
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+        modrm_rr(in_reg0, out_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r. Same as urm, but input limited to ABCD.
@@ -340,6 +452,14 @@ urm_abcd = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
         modrm_rr(in_reg0, out_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [src_reg(d, bits, in_reg0), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r, RM form, FPR -> FPR.
@@ -349,6 +469,14 @@ furm = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
         modrm_rr(in_reg0, out_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [xmm(in_reg0), xmm(out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r, RM form, GPR -> FPR.
@@ -358,6 +486,14 @@ frurm = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
         modrm_rr(in_reg0, out_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), xmm(out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r, RM form, FPR -> GPR.
@@ -367,6 +503,14 @@ rfurm = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
         modrm_rr(in_reg0, out_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [xmm(in_reg0), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r, RMI form for one of the roundXX SSE 4.1 instructions.
@@ -382,6 +526,22 @@ furmi_rnd = TailRecipe(
             Opcode::Trunc => 0b11,
             x => panic!("{} unexpected for furmi_rnd", opcode),
         });
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_rr(d)?;
+        let mode = match opcode {
+            Opcode::Nearest => 0b00,
+            Opcode::Floor => 0b01,
+            Opcode::Ceil => 0b10,
+            Opcode::Trunc => 0b11,
+            _ => return None,
+        };
+        d.expect1(mode)?;
+        ''',
+        asm='''
+        let ops = [format!("${}", mode), xmm(in_reg0), xmm(out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r, for regmove instructions.
@@ -391,6 +551,14 @@ rmov = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(dst, src), sink);
         modrm_rr(dst, src, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (dst, src) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, src), int_reg(d, bits, dst)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r, for regmove instructions (FPR version, RM encoded).
@@ -400,6 +568,14 @@ frmov = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(src, dst), sink);
         modrm_rr(src, dst, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (src, dst) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [xmm(src), xmm(dst)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /n with one arg in %rcx, for shifts.
@@ -408,6 +584,14 @@ rc = TailRecipe(
         emit='''
         PUT_OP(bits, rex1(in_reg0), sink);
         modrm_r_bits(in_reg0, bits, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let in_reg0 = modrm_r_bits(bits, d)?;
+        ''',
+        asm='''
+        let ops = ["%cl".to_string(), int_reg(d, bits, in_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /n for division: inputs in %rax, %rdx, r. Outputs in %rax, %rdx.
@@ -417,6 +601,14 @@ div = TailRecipe(
         emit='''
         PUT_OP(bits, rex1(in_reg2), sink);
         modrm_r_bits(in_reg2, bits, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let in_reg2 = modrm_r_bits(bits, d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg2)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /n ib with 8-bit immediate sign-extended.
@@ -428,6 +620,16 @@ rib = TailRecipe(
         modrm_r_bits(in_reg0, bits, sink);
         let imm: i64 = imm.into();
         sink.put1(imm as u8);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let in_reg0 = modrm_r_bits(bits, d)?;
+        let imm = d.get1()? as i8;
+        d.push(Operand::Imm(imm.into()));
+        ''',
+        asm='''
+        let ops = [format!("${}", imm), int_reg(d, bits, in_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /n id with 32-bit immediate sign-extended.
@@ -439,6 +641,16 @@ rid = TailRecipe(
         modrm_r_bits(in_reg0, bits, sink);
         let imm: i64 = imm.into();
         sink.put4(imm as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let in_reg0 = modrm_r_bits(bits, d)?;
+        let imm = d.get4()? as i32;
+        d.push(Operand::Imm(imm.into()));
+        ''',
+        asm='''
+        let ops = [format!("${}", imm), int_reg(d, bits, in_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /n id with 32-bit immediate sign-extended. UnaryImm version.
@@ -450,6 +662,16 @@ uid = TailRecipe(
         modrm_r_bits(out_reg0, bits, sink);
         let imm: i64 = imm.into();
         sink.put4(imm as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let out_reg0 = modrm_r_bits(bits, d)?;
+        let imm = d.get4()? as i32;
+        d.push(Operand::Imm(imm.into()));
+        ''',
+        asm='''
+        let ops = [format!("${}", imm), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX+rd id unary with 32-bit immediate. Note no recipe predicate.
@@ -461,6 +683,17 @@ puid = TailRecipe(
         PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
         let imm: i64 = imm.into();
         sink.put4(imm as u32);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 7, d)?;
+        let out_reg0 = op_reg(low, d);
+        // The 32-bit move zero-extends into a 64-bit register.
+        let imm = d.get4()?;
+        d.push(Operand::Imm(imm.into()));
+        ''',
+        asm='''
+        let ops = [format!("${}", imm), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX+rd iq unary with 64-bit immediate.
@@ -470,6 +703,25 @@ puiq = TailRecipe(
         PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
         let imm: i64 = imm.into();
         sink.put8(imm as u64);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 7, d)?;
+        let out_reg0 = op_reg(low, d);
+        let imm = d.get8()? as i64;
+        d.push(Operand::Imm(imm));
+        ''',
+        asm='''
+        let ops = [format!("${}", imm), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# XX /r xor of a register with itself, producing a null reference.
+# There is no decoder since the encoding is identical to `rr`.
+rnull = TailRecipe(
+        'rnull', NullAry, size=1, ins=(), outs=GPR,
+        emit='''
+        PUT_OP(bits, rex2(out_reg0, out_reg0), sink);
+        modrm_rr(out_reg0, out_reg0, sink);
         ''')
 
 # XX+rd id with Abs4 function relocation.
@@ -480,6 +732,16 @@ fnaddr4 = TailRecipe(
         sink.reloc_func(RelocKind::Abs4.into(), func_ref);
         // Write the immediate as `!0` for the benefit of BaldrMonkey.
         sink.put4(!0);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 7, d)?;
+        let out_reg0 = op_reg(low, d);
+        // Relocation.
+        d.skip(4)?;
+        ''',
+        asm='''
+        let ops = ["$<reloc>".to_string(), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX+rd iq with Abs8 function relocation.
@@ -490,6 +752,102 @@ fnaddr8 = TailRecipe(
         sink.reloc_func(RelocKind::Abs8.into(), func_ref);
         // Write the immediate as `!0` for the benefit of BaldrMonkey.
         sink.put8(!0);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 7, d)?;
+        let out_reg0 = op_reg(low, d);
+        // Relocation.
+        d.skip(8)?;
+        ''',
+        asm='''
+        let ops = ["$<reloc>".to_string(), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# XX+rd id with Abs4 globalsym relocation.
+gvaddr4 = TailRecipe(
+        'gvaddr4', UnaryGlobalVar, size=4, ins=(), outs=GPR,
+        emit='''
+        PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+        sink.reloc_external(RelocKind::Abs4.into(),
+                            func.global_vars[global_var].symbol_name(),
+                            0);
+        sink.put4(0);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 7, d)?;
+        let out_reg0 = op_reg(low, d);
+        // Relocation.
+        d.skip(4)?;
+        ''',
+        asm='''
+        let ops = ["$<reloc>".to_string(), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# XX+rd iq with Abs8 globalsym relocation.
+gvaddr8 = TailRecipe(
+        'gvaddr8', UnaryGlobalVar, size=8, ins=(), outs=GPR,
+        emit='''
+        PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+        sink.reloc_external(RelocKind::Abs8.into(),
+                            func.global_vars[global_var].symbol_name(),
+                            0);
+        sink.put8(0);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 7, d)?;
+        let out_reg0 = op_reg(low, d);
+        // Relocation.
+        d.skip(8)?;
+        ''',
+        asm='''
+        let ops = ["$<reloc>".to_string(), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# XX /r load of a function address from the GOT with a RIP-relative GOTPCRel4
+# relocation.
+got_fnaddr8 = TailRecipe(
+        'got_fnaddr8', FuncAddr, size=5, ins=(), outs=GPR,
+        emit='''
+        PUT_OP(bits, rex2(0, out_reg0), sink);
+        modrm_riprel(out_reg0, sink);
+        sink.reloc_func(RelocKind::GOTPCRel4.into(), func_ref);
+        sink.put4(0);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let out_reg0 = modrm_riprel(d)?;
+        // Relocation.
+        d.skip(4)?;
+        ''',
+        asm='''
+        let ops = ["<reloc>(%rip)".to_string(), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# XX /r load of a globalsym address from the GOT with a RIP-relative GOTPCRel4
+# relocation.
+got_gvaddr8 = TailRecipe(
+        'got_gvaddr8', UnaryGlobalVar, size=5, ins=(), outs=GPR,
+        emit='''
+        PUT_OP(bits, rex2(0, out_reg0), sink);
+        modrm_riprel(out_reg0, sink);
+        sink.reloc_external(RelocKind::GOTPCRel4.into(),
+                            func.global_vars[global_var].symbol_name(),
+                            0);
+        sink.put4(0);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let out_reg0 = modrm_riprel(d)?;
+        // Relocation.
+        d.skip(4)?;
+        ''',
+        asm='''
+        let ops = ["<reloc>(%rip)".to_string(), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 #
@@ -504,6 +862,14 @@ st = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
         modrm_rm(in_reg1, in_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_rm(d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), addr(d, in_reg1, 0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r register-indirect store with no offset.
@@ -516,6 +882,14 @@ st_abcd = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
         modrm_rm(in_reg1, in_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_rm(d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), addr(d, in_reg1, 0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r register-indirect store of FPR with no offset.
@@ -526,6 +900,14 @@ fst = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
         modrm_rm(in_reg1, in_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_rm(d)?;
+        ''',
+        asm='''
+        let ops = [xmm(in_reg0), addr(d, in_reg1, 0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r register-indirect store with 8-bit offset.
@@ -538,6 +920,16 @@ stDisp8 = TailRecipe(
         modrm_disp8(in_reg1, in_reg0, sink);
         let offset: i32 = offset.into();
         sink.put1(offset as u8);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_disp8(d)?;
+        let offset = d.get1()? as i8;
+        d.push(Operand::Offset(offset.into()));
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), addr(d, in_reg1, offset.into())];
+        asm_inst(bits, &ops, d)?;
         ''')
 stDisp8_abcd = TailRecipe(
         'stDisp8_abcd', Store, size=2, ins=(ABCD, GPR), outs=(),
@@ -549,6 +941,16 @@ stDisp8_abcd = TailRecipe(
         modrm_disp8(in_reg1, in_reg0, sink);
         let offset: i32 = offset.into();
         sink.put1(offset as u8);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_disp8(d)?;
+        let offset = d.get1()? as i8;
+        d.push(Operand::Offset(offset.into()));
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), addr(d, in_reg1, offset.into())];
+        asm_inst(bits, &ops, d)?;
         ''')
 fstDisp8 = TailRecipe(
         'fstDisp8', Store, size=2, ins=(FPR, GPR), outs=(),
@@ -559,6 +961,16 @@ fstDisp8 = TailRecipe(
         modrm_disp8(in_reg1, in_reg0, sink);
         let offset: i32 = offset.into();
         sink.put1(offset as u8);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_disp8(d)?;
+        let offset = d.get1()? as i8;
+        d.push(Operand::Offset(offset.into()));
+        ''',
+        asm='''
+        let ops = [xmm(in_reg0), addr(d, in_reg1, offset.into())];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r register-indirect store with 32-bit offset.
@@ -570,6 +982,16 @@ stDisp32 = TailRecipe(
         modrm_disp32(in_reg1, in_reg0, sink);
         let offset: i32 = offset.into();
         sink.put4(offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_disp32(d)?;
+        let offset = d.get4()? as i32;
+        d.push(Operand::Offset(offset));
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), addr(d, in_reg1, offset.into())];
+        asm_inst(bits, &ops, d)?;
         ''')
 stDisp32_abcd = TailRecipe(
         'stDisp32_abcd', Store, size=5, ins=(ABCD, GPR), outs=(),
@@ -580,6 +1002,16 @@ stDisp32_abcd = TailRecipe(
         modrm_disp32(in_reg1, in_reg0, sink);
         let offset: i32 = offset.into();
         sink.put4(offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_disp32(d)?;
+        let offset = d.get4()? as i32;
+        d.push(Operand::Offset(offset));
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), addr(d, in_reg1, offset.into())];
+        asm_inst(bits, &ops, d)?;
         ''')
 fstDisp32 = TailRecipe(
         'fstDisp32', Store, size=5, ins=(FPR, GPR), outs=(),
@@ -589,6 +1021,16 @@ fstDisp32 = TailRecipe(
         modrm_disp32(in_reg1, in_reg0, sink);
         let offset: i32 = offset.into();
         sink.put4(offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_disp32(d)?;
+        let offset = d.get4()? as i32;
+        d.push(Operand::Offset(offset));
+        ''',
+        asm='''
+        let ops = [xmm(in_reg0), addr(d, in_reg1, offset.into())];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # Unary spill with SIB and 32-bit displacement.
@@ -601,6 +1043,17 @@ spSib32 = TailRecipe(
         modrm_sib_disp32(in_reg0, sink);
         sib_noindex(base, sink);
         sink.put4(out_stk0.offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let in_reg0 = modrm_sib_disp32(d)?;
+        let base = sib_noindex(d)?;
+        let disp = d.get4()? as i32;
+        let out_stk0 = Operand::Stack(base, disp);
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), addr(d, base, disp)];
+        asm_inst(bits, &ops, d)?;
         ''')
 fspSib32 = TailRecipe(
         'fspSib32', Unary, size=6, ins=FPR, outs=StackFPR32,
@@ -611,6 +1064,17 @@ fspSib32 = TailRecipe(
         modrm_sib_disp32(in_reg0, sink);
         sib_noindex(base, sink);
         sink.put4(out_stk0.offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let in_reg0 = modrm_sib_disp32(d)?;
+        let base = sib_noindex(d)?;
+        let disp = d.get4()? as i32;
+        let out_stk0 = Operand::Stack(base, disp);
+        ''',
+        asm='''
+        let ops = [xmm(in_reg0), addr(d, base, disp)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # Regspill using RSP-relative addressing.
@@ -624,6 +1088,17 @@ rsp32 = TailRecipe(
         modrm_sib_disp32(src, sink);
         sib_noindex(base, sink);
         sink.put4(dst.offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let src = modrm_sib_disp32(d)?;
+        let base = sib_noindex(d)?;
+        let disp = d.get4()? as i32;
+        let dst = Operand::Stack(base, disp);
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, src), addr(d, base, disp)];
+        asm_inst(bits, &ops, d)?;
         ''')
 frsp32 = TailRecipe(
         'frsp32', RegSpill, size=6, ins=FPR, outs=(),
@@ -635,6 +1110,42 @@ frsp32 = TailRecipe(
         modrm_sib_disp32(src, sink);
         sib_noindex(base, sink);
         sink.put4(dst.offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let src = modrm_sib_disp32(d)?;
+        let base = sib_noindex(d)?;
+        let disp = d.get4()? as i32;
+        let dst = Operand::Stack(base, disp);
+        ''',
+        asm='''
+        let ops = [xmm(src), addr(d, base, disp)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# Stack slot address using RSP-relative addressing.
+spaddr32 = TailRecipe(
+        'spaddr32', StackLoad, size=6, ins=(), outs=GPR,
+        clobbers_flags=False,
+        emit='''
+        let sp = StackRef::sp(stack_slot, &func.stack_slots);
+        let base = stk_base(sp.base);
+        PUT_OP(bits, rex2(base, out_reg0), sink);
+        modrm_sib_disp32(out_reg0, sink);
+        sib_noindex(base, sink);
+        let offset: i32 = offset.into();
+        sink.put4((sp.offset + offset) as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let out_reg0 = modrm_sib_disp32(d)?;
+        let base = sib_noindex(d)?;
+        let offset = d.get4()? as i32;
+        d.push(Operand::Stack(base, offset));
+        ''',
+        asm='''
+        let ops = [addr(d, base, offset), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 #
@@ -649,6 +1160,14 @@ ld = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
         modrm_rm(in_reg0, out_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_rm(d)?;
+        ''',
+        asm='''
+        let ops = [addr(d, in_reg0, 0), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r float load with no offset.
@@ -659,6 +1178,14 @@ fld = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
         modrm_rm(in_reg0, out_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_rm(d)?;
+        ''',
+        asm='''
+        let ops = [addr(d, in_reg0, 0), xmm(out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r load with 8-bit offset.
@@ -671,6 +1198,17 @@ ldDisp8 = TailRecipe(
         modrm_disp8(in_reg0, out_reg0, sink);
         let offset: i32 = offset.into();
         sink.put1(offset as u8);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_disp8(d)?;
+        let offset = d.get1()? as i8;
+        d.push(Operand::Offset(offset.into()));
+        ''',
+        asm='''
+        let mem = addr(d, in_reg0, offset.into());
+        let ops = [mem, int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r float load with 8-bit offset.
@@ -683,6 +1221,16 @@ fldDisp8 = TailRecipe(
         modrm_disp8(in_reg0, out_reg0, sink);
         let offset: i32 = offset.into();
         sink.put1(offset as u8);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_disp8(d)?;
+        let offset = d.get1()? as i8;
+        d.push(Operand::Offset(offset.into()));
+        ''',
+        asm='''
+        let ops = [addr(d, in_reg0, offset.into()), xmm(out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r load with 32-bit offset.
@@ -695,6 +1243,17 @@ ldDisp32 = TailRecipe(
         modrm_disp32(in_reg0, out_reg0, sink);
         let offset: i32 = offset.into();
         sink.put4(offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_disp32(d)?;
+        let offset = d.get4()? as i32;
+        d.push(Operand::Offset(offset));
+        ''',
+        asm='''
+        let mem = addr(d, in_reg0, offset.into());
+        let ops = [mem, int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r float load with 32-bit offset.
@@ -707,6 +1266,16 @@ fldDisp32 = TailRecipe(
         modrm_disp32(in_reg0, out_reg0, sink);
         let offset: i32 = offset.into();
         sink.put4(offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, out_reg0) = modrm_disp32(d)?;
+        let offset = d.get4()? as i32;
+        d.push(Operand::Offset(offset));
+        ''',
+        asm='''
+        let ops = [addr(d, in_reg0, offset.into()), xmm(out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # Unary fill with SIB and 32-bit displacement.
@@ -719,6 +1288,17 @@ fiSib32 = TailRecipe(
         modrm_sib_disp32(out_reg0, sink);
         sib_noindex(base, sink);
         sink.put4(in_stk0.offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let out_reg0 = modrm_sib_disp32(d)?;
+        let base = sib_noindex(d)?;
+        let disp = d.get4()? as i32;
+        let in_stk0 = Operand::Stack(base, disp);
+        ''',
+        asm='''
+        let ops = [addr(d, base, disp), int_reg(d, bits, out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 ffiSib32 = TailRecipe(
         'ffiSib32', Unary, size=6, ins=StackFPR32, outs=FPR,
@@ -729,6 +1309,17 @@ ffiSib32 = TailRecipe(
         modrm_sib_disp32(out_reg0, sink);
         sib_noindex(base, sink);
         sink.put4(in_stk0.offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let out_reg0 = modrm_sib_disp32(d)?;
+        let base = sib_noindex(d)?;
+        let disp = d.get4()? as i32;
+        let in_stk0 = Operand::Stack(base, disp);
+        ''',
+        asm='''
+        let ops = [addr(d, base, disp), xmm(out_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # Regfill with RSP-relative 32-bit displacement.
@@ -742,6 +1333,17 @@ rfi32 = TailRecipe(
         modrm_sib_disp32(dst, sink);
         sib_noindex(base, sink);
         sink.put4(src.offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let dst = modrm_sib_disp32(d)?;
+        let base = sib_noindex(d)?;
+        let disp = d.get4()? as i32;
+        let src = Operand::Stack(base, disp);
+        ''',
+        asm='''
+        let ops = [addr(d, base, disp), int_reg(d, bits, dst)];
+        asm_inst(bits, &ops, d)?;
         ''')
 frfi32 = TailRecipe(
         'frfi32', RegFill, size=6, ins=StackFPR32, outs=(),
@@ -753,6 +1355,17 @@ frfi32 = TailRecipe(
         modrm_sib_disp32(dst, sink);
         sib_noindex(base, sink);
         sink.put4(src.offset as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let dst = modrm_sib_disp32(d)?;
+        let base = sib_noindex(d)?;
+        let disp = d.get4()? as i32;
+        let src = Operand::Stack(base, disp);
+        ''',
+        asm='''
+        let ops = [addr(d, base, disp), xmm(dst)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 #
@@ -764,6 +1377,66 @@ call_id = TailRecipe(
         PUT_OP(bits, BASE_REX, sink);
         sink.reloc_func(RelocKind::PCRel4.into(), func_ref);
         sink.put4(0);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        // Relocation.
+        d.skip(4)?;
+        ''',
+        asm='''
+        asm_inst(bits, &["<reloc>".to_string()], d)?;
+        ''')
+
+call_plt_id = TailRecipe(
+        'call_plt_id', Call, size=4, ins=(), outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_func(RelocKind::PLTRel4.into(), func_ref);
+        sink.put4(0);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        // Relocation.
+        d.skip(4)?;
+        ''',
+        asm='''
+        asm_inst(bits, &["<reloc>".to_string()], d)?;
+        ''')
+
+# Calls with a landing pad. The return address is recorded in the call-site
+# table.
+invoke_id = TailRecipe(
+        'invoke_id', Invoke, size=4, ins=(), outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_func(RelocKind::PCRel4.into(), func_ref);
+        sink.put4(0);
+        sink.add_call_site(destination);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        // Relocation.
+        d.skip(4)?;
+        ''',
+        asm='''
+        asm_inst(bits, &["<reloc>".to_string()], d)?;
+        ''')
+
+invoke_plt_id = TailRecipe(
+        'invoke_plt_id', Invoke, size=4, ins=(), outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_func(RelocKind::PLTRel4.into(), func_ref);
+        sink.put4(0);
+        sink.add_call_site(destination);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        // Relocation.
+        d.skip(4)?;
+        ''',
+        asm='''
+        asm_inst(bits, &["<reloc>".to_string()], d)?;
         ''')
 
 call_r = TailRecipe(
@@ -771,12 +1444,145 @@ call_r = TailRecipe(
         emit='''
         PUT_OP(bits, rex1(in_reg0), sink);
         modrm_r_bits(in_reg0, bits, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let in_reg0 = modrm_r_bits(bits, d)?;
+        ''',
+        asm='''
+        let ops = [format!("*{}", ptr_reg(d, in_reg0))];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 ret = TailRecipe(
         'ret', MultiAry, size=0, ins=(), outs=(),
         emit='''
         PUT_OP(bits, BASE_REX, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        ''',
+        asm='''
+        asm_inst(bits, &[], d)?;
+        ''')
+
+# XX rel32 tail call, encoded as a `jmp` with a relocation.
+# There is no decoder since the encoding is identical to `jmpd`.
+tcall_id = TailRecipe(
+        'tcall_id', Call, size=4, ins=(), outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_func(RelocKind::PCRel4.into(), func_ref);
+        sink.put4(0);
+        ''')
+
+tcall_plt_id = TailRecipe(
+        'tcall_plt_id', Call, size=4, ins=(), outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_func(RelocKind::PLTRel4.into(), func_ref);
+        sink.put4(0);
+        ''')
+
+# XX /n indirect tail call. The callee address is in %rax which is neither
+# callee-saved nor used by the epilogue.
+tjmp_r = TailRecipe(
+        'tjmp_r', IndirectCall, size=1, ins=GPR.rax, outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        modrm_r_bits(RU::rax as RegUnit, bits, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        if modrm_r_bits(bits, d)? != RU::rax as RegUnit {
+            return None;
+        }
+        ''',
+        asm='''
+        let ops = [format!("*{}", ptr_reg(d, RU::rax as RegUnit))];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+#
+# Stack frames
+#
+
+# XX+rd push of a GPR. No ModR/M.
+pushq = TailRecipe(
+        'pushq', Unary, size=0, ins=GPR, outs=(),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits | (in_reg0 & 7), rex1(in_reg0), sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 7, d)?;
+        let in_reg0 = op_reg(low, d);
+        ''',
+        asm='''
+        let ops = [ptr_reg(d, in_reg0)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# XX+rd pop into a GPR. No ModR/M.
+popq = TailRecipe(
+        'popq', NullAry, size=0, ins=(), outs=GPR,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits | (out_reg0 & 7), rex1(out_reg0), sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 7, d)?;
+        let out_reg0 = op_reg(low, d);
+        ''',
+        asm='''
+        let ops = [ptr_reg(d, out_reg0)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# XX /n ib adding a sign-extended 8-bit immediate to the stack pointer.
+adjustsp_ib = TailRecipe(
+        'adjustsp_ib', UnaryImm, size=2, ins=(), outs=(),
+        instp=IsSignedInt(UnaryImm.imm, 8),
+        emit='''
+        PUT_OP(bits, rex1(RU::rsp as RegUnit), sink);
+        modrm_r_bits(RU::rsp as RegUnit, bits, sink);
+        let imm: i64 = imm.into();
+        sink.put1(imm as u8);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        if modrm_r_bits(bits, d)? != RU::rsp as RegUnit {
+            return None;
+        }
+        let imm = d.get1()? as i8;
+        d.push(Operand::Imm(imm.into()));
+        ''',
+        asm='''
+        let ops = [format!("${}", imm), int_reg(d, bits, RU::rsp as RegUnit)];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# XX /n id adding a sign-extended 32-bit immediate to the stack pointer.
+adjustsp_id = TailRecipe(
+        'adjustsp_id', UnaryImm, size=5, ins=(), outs=(),
+        instp=IsSignedInt(UnaryImm.imm, 32),
+        emit='''
+        PUT_OP(bits, rex1(RU::rsp as RegUnit), sink);
+        modrm_r_bits(RU::rsp as RegUnit, bits, sink);
+        let imm: i64 = imm.into();
+        sink.put4(imm as u32);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        if modrm_r_bits(bits, d)? != RU::rsp as RegUnit {
+            return None;
+        }
+        let imm = d.get4()? as i32;
+        d.push(Operand::Imm(imm.into()));
+        ''',
+        asm='''
+        let ops = [format!("${}", imm), int_reg(d, bits, RU::rsp as RegUnit)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 #
@@ -789,6 +1595,13 @@ jmpb = TailRecipe(
         emit='''
         PUT_OP(bits, BASE_REX, sink);
         disp1(destination, func, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let dest = disp1(d)?;
+        ''',
+        asm='''
+        asm_inst(bits, &[target(dest)], d)?;
         ''')
 
 jmpd = TailRecipe(
@@ -798,6 +1611,13 @@ jmpd = TailRecipe(
         emit='''
         PUT_OP(bits, BASE_REX, sink);
         disp4(destination, func, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let dest = disp4(d)?;
+        ''',
+        asm='''
+        asm_inst(bits, &[target(dest)], d)?;
         ''')
 
 brib = TailRecipe(
@@ -807,6 +1627,14 @@ brib = TailRecipe(
         emit='''
         PUT_OP(bits | icc2opc(cond), BASE_REX, sink);
         disp1(destination, func, sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 0xf, d)?;
+        d.cond(Cond::Int(opc2icc(low)?));
+        let dest = disp1(d)?;
+        ''',
+        asm='''
+        d.asm(format!("j{} {}", cc(low), target(dest)));
         ''')
 
 brid = TailRecipe(
@@ -816,6 +1644,14 @@ brid = TailRecipe(
         emit='''
         PUT_OP(bits | icc2opc(cond), BASE_REX, sink);
         disp4(destination, func, sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 0xf, d)?;
+        d.cond(Cond::Int(opc2icc(low)?));
+        let dest = disp4(d)?;
+        ''',
+        asm='''
+        d.asm(format!("j{} {}", cc(low), target(dest)));
         ''')
 
 brfb = TailRecipe(
@@ -826,6 +1662,14 @@ brfb = TailRecipe(
         emit='''
         PUT_OP(bits | fcc2opc(cond), BASE_REX, sink);
         disp1(destination, func, sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 0xf, d)?;
+        d.cond(Cond::Float(opc2fcc(low)?));
+        let dest = disp1(d)?;
+        ''',
+        asm='''
+        d.asm(format!("j{} {}", cc(low), target(dest)));
         ''')
 
 brfd = TailRecipe(
@@ -836,6 +1680,14 @@ brfd = TailRecipe(
         emit='''
         PUT_OP(bits | fcc2opc(cond), BASE_REX, sink);
         disp4(destination, func, sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 0xf, d)?;
+        d.cond(Cond::Float(opc2fcc(low)?));
+        let dest = disp4(d)?;
+        ''',
+        asm='''
+        d.asm(format!("j{} {}", cc(low), target(dest)));
         ''')
 
 #
@@ -855,6 +1707,15 @@ seti = TailRecipe(
         emit='''
         PUT_OP(bits | icc2opc(cond), rex1(out_reg0), sink);
         modrm_r_bits(out_reg0, bits, sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 0xf, d)?;
+        d.cond(Cond::Int(opc2icc(low)?));
+        let out_reg0 = modrm_r_bits(bits, d)?;
+        ''',
+        asm='''
+        let text = format!("set{} {}", cc(low), gpr(d, 8, out_reg0));
+        d.asm(text);
         ''')
 seti_abcd = TailRecipe(
         'seti_abcd', IntCond, size=1, ins=FLAG.eflags, outs=ABCD,
@@ -863,6 +1724,15 @@ seti_abcd = TailRecipe(
         emit='''
         PUT_OP(bits | icc2opc(cond), rex1(out_reg0), sink);
         modrm_r_bits(out_reg0, bits, sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 0xf, d)?;
+        d.cond(Cond::Int(opc2icc(low)?));
+        let out_reg0 = modrm_r_bits(bits, d)?;
+        ''',
+        asm='''
+        let text = format!("set{} {}", cc(low), gpr(d, 8, out_reg0));
+        d.asm(text);
         ''')
 
 setf = TailRecipe(
@@ -872,6 +1742,15 @@ setf = TailRecipe(
         emit='''
         PUT_OP(bits | fcc2opc(cond), rex1(out_reg0), sink);
         modrm_r_bits(out_reg0, bits, sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 0xf, d)?;
+        d.cond(Cond::Float(opc2fcc(low)?));
+        let out_reg0 = modrm_r_bits(bits, d)?;
+        ''',
+        asm='''
+        let text = format!("set{} {}", cc(low), gpr(d, 8, out_reg0));
+        d.asm(text);
         ''')
 setf_abcd = TailRecipe(
         'setf_abcd', FloatCond, size=1, ins=FLAG.eflags, outs=ABCD,
@@ -880,6 +1759,15 @@ setf_abcd = TailRecipe(
         emit='''
         PUT_OP(bits | fcc2opc(cond), rex1(out_reg0), sink);
         modrm_r_bits(out_reg0, bits, sink);
+        ''',
+        decode='''
+        let low = GET_OP(bits, 0xf, d)?;
+        d.cond(Cond::Float(opc2fcc(low)?));
+        let out_reg0 = modrm_r_bits(bits, d)?;
+        ''',
+        asm='''
+        let text = format!("set{} {}", cc(low), gpr(d, 8, out_reg0));
+        d.asm(text);
         ''')
 
 #
@@ -892,6 +1780,14 @@ rcmp = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
         modrm_rr(in_reg0, in_reg1, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, in_reg1) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg1), int_reg(d, bits, in_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # XX /r, RM form. Compare two FPR registers and set flags.
@@ -900,6 +1796,14 @@ fcmp = TailRecipe(
         emit='''
         PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
         modrm_rr(in_reg1, in_reg0, sink);
+        ''',
+        decode='''
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_rr(d)?;
+        ''',
+        asm='''
+        let ops = [xmm(in_reg1), xmm(in_reg0)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # Test-and-branch.
@@ -925,6 +1829,23 @@ tjccb = TailRecipe(
         // Jcc instruction.
         sink.put1(bits as u8);
         disp1(destination, func, sink);
+        ''',
+        decode='''
+        // test r, r.
+        GET_OP((bits & 0xff00) | 0x85, 0, d)?;
+        let (in_reg0, reg) = modrm_rr(d)?;
+        if reg != in_reg0 {
+            return None;
+        }
+        // Jcc instruction.
+        d.expect1(bits as u8)?;
+        let dest = disp1(d)?;
+        ''',
+        asm='''
+        let test = (bits & 0xff00) | 0x85;
+        let ops = [int_reg(d, test, in_reg0), int_reg(d, test, in_reg0)];
+        asm_inst(test, &ops, d)?;
+        d.asm(format!("j{} {}", cc(bits as u8), target(dest)));
         ''')
 
 tjccd = TailRecipe(
@@ -938,6 +1859,24 @@ tjccd = TailRecipe(
         sink.put1(0x0f);
         sink.put1(bits as u8);
         disp4(destination, func, sink);
+        ''',
+        decode='''
+        // test r, r.
+        GET_OP((bits & 0xff00) | 0x85, 0, d)?;
+        let (in_reg0, reg) = modrm_rr(d)?;
+        if reg != in_reg0 {
+            return None;
+        }
+        // Jcc instruction.
+        d.expect1(0x0f)?;
+        d.expect1(bits as u8)?;
+        let dest = disp4(d)?;
+        ''',
+        asm='''
+        let test = (bits & 0xff00) | 0x85;
+        let ops = [int_reg(d, test, in_reg0), int_reg(d, test, in_reg0)];
+        asm_inst(test, &ops, d)?;
+        d.asm(format!("j{} {}", cc(bits as u8), target(dest)));
         ''')
 
 # 8-bit test-and-branch.
@@ -955,6 +1894,22 @@ t8jccb = TailRecipe(
         // Jcc instruction.
         sink.put1(bits as u8);
         disp1(destination, func, sink);
+        ''',
+        decode='''
+        // test8 r, r.
+        GET_OP((bits & 0xff00) | 0x84, 0, d)?;
+        let (in_reg0, reg) = modrm_rr(d)?;
+        if reg != in_reg0 {
+            return None;
+        }
+        // Jcc instruction.
+        d.expect1(bits as u8)?;
+        let dest = disp1(d)?;
+        ''',
+        asm='''
+        let text = format!("testb {0}, {0}", gpr(d, 8, in_reg0));
+        d.asm(text);
+        d.asm(format!("j{} {}", cc(bits as u8), target(dest)));
         ''')
 t8jccb_abcd = TailRecipe(
         't8jccb_abcd', Branch, size=1 + 2, ins=ABCD, outs=(),
@@ -967,6 +1922,22 @@ t8jccb_abcd = TailRecipe(
         // Jcc instruction.
         sink.put1(bits as u8);
         disp1(destination, func, sink);
+        ''',
+        decode='''
+        // test8 r, r.
+        GET_OP((bits & 0xff00) | 0x84, 0, d)?;
+        let (in_reg0, reg) = modrm_rr(d)?;
+        if reg != in_reg0 {
+            return None;
+        }
+        // Jcc instruction.
+        d.expect1(bits as u8)?;
+        let dest = disp1(d)?;
+        ''',
+        asm='''
+        let text = format!("testb {0}, {0}", gpr(d, 8, in_reg0));
+        d.asm(text);
+        d.asm(format!("j{} {}", cc(bits as u8), target(dest)));
         ''')
 
 t8jccd = TailRecipe(
@@ -981,6 +1952,23 @@ t8jccd = TailRecipe(
         sink.put1(0x0f);
         sink.put1(bits as u8);
         disp4(destination, func, sink);
+        ''',
+        decode='''
+        // test8 r, r.
+        GET_OP((bits & 0xff00) | 0x84, 0, d)?;
+        let (in_reg0, reg) = modrm_rr(d)?;
+        if reg != in_reg0 {
+            return None;
+        }
+        // Jcc instruction.
+        d.expect1(0x0f)?;
+        d.expect1(bits as u8)?;
+        let dest = disp4(d)?;
+        ''',
+        asm='''
+        let text = format!("testb {0}, {0}", gpr(d, 8, in_reg0));
+        d.asm(text);
+        d.asm(format!("j{} {}", cc(bits as u8), target(dest)));
         ''')
 t8jccd_abcd = TailRecipe(
         't8jccd_abcd', Branch, size=1 + 6, ins=ABCD, outs=(),
@@ -994,6 +1982,23 @@ t8jccd_abcd = TailRecipe(
         sink.put1(0x0f);
         sink.put1(bits as u8);
         disp4(destination, func, sink);
+        ''',
+        decode='''
+        // test8 r, r.
+        GET_OP((bits & 0xff00) | 0x84, 0, d)?;
+        let (in_reg0, reg) = modrm_rr(d)?;
+        if reg != in_reg0 {
+            return None;
+        }
+        // Jcc instruction.
+        d.expect1(0x0f)?;
+        d.expect1(bits as u8)?;
+        let dest = disp4(d)?;
+        ''',
+        asm='''
+        let text = format!("testb {0}, {0}", gpr(d, 8, in_reg0));
+        d.asm(text);
+        d.asm(format!("j{} {}", cc(bits as u8), target(dest)));
         ''')
 
 # Worst case test-and-branch recipe for brz.b1 and brnz.b1 in 32-bit mode.
@@ -1013,6 +2018,22 @@ t8jccd_long = TailRecipe(
         sink.put1(0x0f);
         sink.put1(bits as u8);
         disp4(destination, func, sink);
+        ''',
+        decode='''
+        // test32 r, 0xff.
+        GET_OP((bits & 0xff00) | 0xf7, 0, d)?;
+        let in_reg0 = modrm_r_bits(bits, d)?;
+        d.expect4(0xff)?;
+        // Jcc instruction.
+        d.expect1(0x0f)?;
+        d.expect1(bits as u8)?;
+        let dest = disp4(d)?;
+        ''',
+        asm='''
+        let test = (bits & 0xff00) | 0xf7;
+        let ops = ["$255".to_string(), int_reg(d, test, in_reg0)];
+        asm_inst(test, &ops, d)?;
+        d.asm(format!("j{} {}", cc(bits as u8), target(dest)));
         ''')
 
 # Comparison that produces a `b1` result in a GPR.
@@ -1055,6 +2076,67 @@ icscc = TailRecipe(
         sink.put1(0x0f);
         sink.put1(setcc);
         modrm_rr(out_reg0, 0, sink);
+        ''',
+        decode='''
+        // Comparison instruction.
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, in_reg1) = modrm_rr(d)?;
+        // `setCC` instruction, no REX.
+        d.prefix = 0;
+        d.expect1(0x0f)?;
+        let setcc = d.get1()?;
+        if setcc & 0xf0 != 0x90 {
+            return None;
+        }
+        d.cond(Cond::Int(opc2icc(setcc & 0xf)?));
+        let (out_reg0, reg) = modrm_rr(d)?;
+        if reg != 0 {
+            return None;
+        }
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg1), int_reg(d, bits, in_reg0)];
+        asm_inst(bits, &ops, d)?;
+        let text = format!("set{} {}", cc(setcc), gpr(d, 8, out_reg0));
+        d.asm(text);
+        ''')
+
+# XX /r test of a register with itself followed by `sete`. This is like the
+# `icscc` recipe, but comparing a single reference against null.
+#
+# The omission of a `when_prefixed` alternative is deliberate here.
+isnull = TailRecipe(
+        'isnull', Unary, size=1 + 3, ins=GPR, outs=ABCD,
+        emit='''
+        // `test` instruction.
+        PUT_OP(bits, rex2(in_reg0, in_reg0), sink);
+        modrm_rr(in_reg0, in_reg0, sink);
+        // `sete` instruction, no REX.
+        sink.put1(0x0f);
+        sink.put1(0x94);
+        modrm_rr(out_reg0, 0, sink);
+        ''',
+        decode='''
+        // `test` instruction.
+        GET_OP(bits, 0, d)?;
+        let (in_reg0, reg) = modrm_rr(d)?;
+        if reg != in_reg0 {
+            return None;
+        }
+        // `sete` instruction, no REX.
+        d.prefix = 0;
+        d.expect1(0x0f)?;
+        d.expect1(0x94)?;
+        let (out_reg0, reg) = modrm_rr(d)?;
+        if reg != 0 {
+            return None;
+        }
+        ''',
+        asm='''
+        let ops = [int_reg(d, bits, in_reg0), int_reg(d, bits, in_reg0)];
+        asm_inst(bits, &ops, d)?;
+        let text = format!("sete {}", gpr(d, 8, out_reg0));
+        d.asm(text);
         ''')
 
 
@@ -1101,6 +2183,29 @@ fcscc = TailRecipe(
         sink.put1(0x0f);
         sink.put1(setcc);
         modrm_rr(out_reg0, 0, sink);
+        ''',
+        decode='''
+        // Comparison instruction.
+        GET_OP(bits, 0, d)?;
+        let (in_reg1, in_reg0) = modrm_rr(d)?;
+        // `setCC` instruction, no REX.
+        d.prefix = 0;
+        d.expect1(0x0f)?;
+        let setcc = d.get1()?;
+        if setcc & 0xf0 != 0x90 {
+            return None;
+        }
+        d.cond(Cond::Float(opc2fcc(setcc & 0xf)?));
+        let (out_reg0, reg) = modrm_rr(d)?;
+        if reg != 0 {
+            return None;
+        }
+        ''',
+        asm='''
+        let ops = [xmm(in_reg1), xmm(in_reg0)];
+        asm_inst(bits, &ops, d)?;
+        let text = format!("set{} {}", cc(setcc), gpr(d, 8, out_reg0));
+        d.asm(text);
         ''')
 
 TailRecipe.check_names(globals())
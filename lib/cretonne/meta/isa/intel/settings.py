@@ -3,7 +3,7 @@ Intel settings.
 """
 from __future__ import absolute_import
 from cdsl.settings import SettingGroup, BoolSetting, Preset
-from cdsl.predicates import And
+from cdsl.predicates import And, Not
 import base.settings as shared
 from .defs import ISA
 
@@ -38,6 +38,10 @@ use_popcnt = And(has_popcnt, has_sse42)
 use_bmi1 = And(has_bmi1)
 use_lzcnt = And(has_lzcnt)
 
+# Position-independent code, see the shared `is_pic` setting.
+use_pic = And(shared.is_pic)
+not_pic = Not(shared.is_pic)
+
 # Presets corresponding to Intel CPUs.
 
 nehalem = Preset(
@@ -8,7 +8,8 @@ from .defs import RV32, RV64
 from .recipes import OPIMM, OPIMM32, OP, OP32, LUI, BRANCH, JALR, JAL
 from .recipes import LOAD, STORE
 from .recipes import R, Rshamt, Ricmp, I, Iz, Iicmp, Iret, Icall, Icopy
-from .recipes import U, UJ, UJcall, SB, SBzero, GPsp, GPfi, Irmov
+from .recipes import U, UJ, UJcall, UJinvoke, SB, SBzero, GPsp, GPfi, Irmov
+from .recipes import Inull, Iisnull, safepoint
 from .settings import use_m
 from cdsl.ast import Var
 from base.legalize import narrow, expand
@@ -109,6 +110,8 @@ RV32.enc(base.jump, UJ, JAL())
 RV64.enc(base.jump, UJ, JAL())
 RV32.enc(base.call, UJcall, JAL())
 RV64.enc(base.call, UJcall, JAL())
+RV32.enc(base.invoke, UJinvoke, JAL())
+RV64.enc(base.invoke, UJinvoke, JAL())
 
 # Conditional branches.
 for cond,           f3 in [
@@ -155,3 +158,21 @@ RV64.enc(base.copy.i32, Icopy, OPIMM32(0b000))
 RV32.enc(base.regmove.i32, Irmov, OPIMM(0b000))
 RV64.enc(base.regmove.i64, Irmov, OPIMM(0b000))
 RV64.enc(base.regmove.i32, Irmov, OPIMM32(0b000))
+
+# Reference types.
+RV32.enc(base.spill.r32, GPsp, STORE(0b010))
+RV64.enc(base.spill.r64, GPsp, STORE(0b011))
+RV32.enc(base.fill.r32, GPfi, LOAD(0b010))
+RV64.enc(base.fill.r64, GPfi, LOAD(0b011))
+RV32.enc(base.copy.r32, Icopy, OPIMM(0b000))
+RV64.enc(base.copy.r64, Icopy, OPIMM(0b000))
+RV32.enc(base.regmove.r32, Irmov, OPIMM(0b000))
+RV64.enc(base.regmove.r64, Irmov, OPIMM(0b000))
+RV32.enc(base.null.r32, Inull, OPIMM(0b000))
+RV64.enc(base.null.r64, Inull, OPIMM(0b000))
+RV32.enc(base.is_null.r32, Iisnull, OPIMM(0b011))
+RV64.enc(base.is_null.r64, Iisnull, OPIMM(0b011))
+
+# Safepoints emit no code, just a stack map.
+RV32.enc(base.safepoint, safepoint, 0)
+RV64.enc(base.safepoint, safepoint, 0)
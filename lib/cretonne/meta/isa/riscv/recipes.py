@@ -13,8 +13,8 @@ from cdsl.isa import EncRecipe
 from cdsl.predicates import IsSignedInt
 from cdsl.registers import Stack
 from base.formats import Binary, BinaryImm, MultiAry, IntCompare, IntCompareImm
-from base.formats import Unary, UnaryImm, BranchIcmp, Branch, Jump
-from base.formats import Call, IndirectCall, RegMove
+from base.formats import Unary, UnaryImm, BranchIcmp, Branch, Jump, NullAry
+from base.formats import Call, IndirectCall, Invoke, RegMove
 from .registers import GPR
 
 # The low 7 bits of a RISC-V instruction is the base opcode. All 32-bit
@@ -94,34 +94,79 @@ def LUI():
 # The encbits are `opcode[6:2] | (funct3 << 5) | (funct7 << 8)
 R = EncRecipe(
         'R', Binary, size=4, ins=(GPR, GPR), outs=GPR,
-        emit='put_r(bits, in_reg0, in_reg1, out_reg0, sink);')
+        emit='put_r(bits, in_reg0, in_reg1, out_reg0, sink);',
+        decode='let (in_reg0, in_reg1, out_reg0) = get_r(bits, d)?;',
+        asm='''
+        let ops = [xreg(out_reg0), xreg(in_reg0), xreg(in_reg1)];
+        asm_inst(bits, &ops, d)?;
+        ''')
 
 # R-type with an immediate shift amount instead of rs2.
 Rshamt = EncRecipe(
         'Rshamt', BinaryImm, size=4, ins=GPR, outs=GPR,
-        emit='put_rshamt(bits, in_reg0, imm.into(), out_reg0, sink);')
+        emit='put_rshamt(bits, in_reg0, imm.into(), out_reg0, sink);''',
+        decode='''
+        let (in_reg0, shamt, out_reg0) = get_rshamt(bits, d)?;
+        d.push(Operand::Imm(shamt));
+        ''',
+        asm='''
+        let ops = [xreg(out_reg0), xreg(in_reg0), shamt.to_string()];
+        asm_inst(bits, &ops, d)?;
+        ''')
 
 # R-type encoding of an integer comparison.
 Ricmp = EncRecipe(
         'Ricmp', IntCompare, size=4, ins=(GPR, GPR), outs=GPR,
-        emit='put_r(bits, in_reg0, in_reg1, out_reg0, sink);')
+        emit='put_r(bits, in_reg0, in_reg1, out_reg0, sink);',
+        decode='let (in_reg0, in_reg1, out_reg0) = get_r(bits, d)?;',
+        asm='''
+        let ops = [xreg(out_reg0), xreg(in_reg0), xreg(in_reg1)];
+        asm_inst(bits, &ops, d)?;
+        ''')
 
 I = EncRecipe(
         'I', BinaryImm, size=4, ins=GPR, outs=GPR,
         instp=IsSignedInt(BinaryImm.imm, 12),
-        emit='put_i(bits, in_reg0, imm.into(), out_reg0, sink);')
+        emit='put_i(bits, in_reg0, imm.into(), out_reg0, sink);''',
+        decode='''
+        let (in_reg0, imm, out_reg0) = get_i(bits, d)?;
+        d.push(Operand::Imm(imm));
+        ''',
+        asm='''
+        let ops = [xreg(out_reg0), xreg(in_reg0), imm.to_string()];
+        asm_inst(bits, &ops, d)?;
+        ''')
 
 # I-type instruction with a hardcoded %x0 rs1.
 Iz = EncRecipe(
         'Iz', UnaryImm, size=4, ins=(), outs=GPR,
         instp=IsSignedInt(UnaryImm.imm, 12),
-        emit='put_i(bits, 0, imm.into(), out_reg0, sink);')
+        emit='put_i(bits, 0, imm.into(), out_reg0, sink);''',
+        decode='''
+        let (rs1, imm, out_reg0) = get_i(bits, d)?;
+        if rs1 != 0 {
+            return None;
+        }
+        d.push(Operand::Imm(imm));
+        ''',
+        asm='''
+        let ops = [xreg(out_reg0), xreg(0), imm.to_string()];
+        asm_inst(bits, &ops, d)?;
+        ''')
 
 # I-type encoding of an integer comparison.
 Iicmp = EncRecipe(
         'Iicmp', IntCompareImm, size=4, ins=GPR, outs=GPR,
         instp=IsSignedInt(IntCompareImm.imm, 12),
-        emit='put_i(bits, in_reg0, imm.into(), out_reg0, sink);')
+        emit='put_i(bits, in_reg0, imm.into(), out_reg0, sink);''',
+        decode='''
+        let (in_reg0, imm, out_reg0) = get_i(bits, d)?;
+        d.push(Operand::Imm(imm));
+        ''',
+        asm='''
+        let ops = [xreg(out_reg0), xreg(in_reg0), imm.to_string()];
+        asm_inst(bits, &ops, d)?;
+        ''')
 
 # I-type encoding for `jalr` as a return instruction. We won't use the
 # immediate offset.
@@ -136,7 +181,13 @@ Iret = EncRecipe(
               0, // no offset.
               0, // rd = %x0: no address written.
               sink);
-        ''')
+        ''',
+        decode='''
+        if get_i(bits, d)? != (1, 0, 0) {
+            return None;
+        }
+        ''',
+        asm='asm_inst(bits, &[xreg(0), format!("0({})", xreg(1))], d)?;')
 
 # I-type encoding for `jalr` as an indirect call.
 Icall = EncRecipe(
@@ -148,24 +199,81 @@ Icall = EncRecipe(
               0, // no offset.
               1, // rd = %x1: link register.
               sink);
+        ''',
+        decode='''
+        let (in_reg0, imm, rd) = get_i(bits, d)?;
+        if imm != 0 || rd != 1 {
+            return None;
+        }
+        ''',
+        asm='''
+        let ops = [xreg(1), format!("0({})", xreg(in_reg0))];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 
 # Copy of a GPR is implemented as addi x, 0.
 Icopy = EncRecipe(
         'Icopy', Unary, size=4, ins=GPR, outs=GPR,
-        emit='put_i(bits, in_reg0, 0, out_reg0, sink);')
+        emit='put_i(bits, in_reg0, 0, out_reg0, sink);''',
+        decode='''
+        let (in_reg0, imm, out_reg0) = get_i(bits, d)?;
+        if imm != 0 {
+            return None;
+        }
+        ''',
+        asm='''
+        let ops = [xreg(out_reg0), xreg(in_reg0), imm.to_string()];
+        asm_inst(bits, &ops, d)?;
+        ''')
 
 # Same for a GPR regmove.
 Irmov = EncRecipe(
         'Irmov', RegMove, size=4, ins=GPR, outs=(),
-        emit='put_i(bits, src, 0, dst, sink);')
+        emit='put_i(bits, src, 0, dst, sink);''',
+        decode='''
+        let (src, imm, dst) = get_i(bits, d)?;
+        if imm != 0 {
+            return None;
+        }
+        ''',
+        asm='''
+        let ops = [xreg(dst), xreg(src), imm.to_string()];
+        asm_inst(bits, &ops, d)?;
+        ''')
+
+# A null reference is implemented as addi x, %x0, 0.
+Inull = EncRecipe(
+        'Inull', NullAry, size=4, ins=(), outs=GPR,
+        emit='put_i(bits, 0, 0, out_reg0, sink);')
+
+# A null reference test is implemented as sltiu x, y, 1.
+Iisnull = EncRecipe(
+        'Iisnull', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_i(bits, in_reg0, 1, out_reg0, sink);')
+
+# A safepoint emits no code. It records a stack map of the references passed
+# as arguments.
+safepoint = EncRecipe(
+        'safepoint', MultiAry, size=0, ins=(), outs=(),
+        emit='''
+        let args = func.dfg.inst_args(inst);
+        sink.add_stackmap(StackMap::from_values(args, func, divert));
+        ''')
 
 # U-type instructions have a 20-bit immediate that targets bits 12-31.
 U = EncRecipe(
         'U', UnaryImm, size=4, ins=(), outs=GPR,
         instp=IsSignedInt(UnaryImm.imm, 32, 12),
-        emit='put_u(bits, imm.into(), out_reg0, sink);')
+        emit='put_u(bits, imm.into(), out_reg0, sink);''',
+        decode='''
+        let (imm, out_reg0) = get_u(bits, d)?;
+        d.push(Operand::Imm(imm));
+        ''',
+        asm='''
+        let ops = [xreg(out_reg0), format!("{:#x}", (imm >> 12) & 0xfffff)];
+        asm_inst(bits, &ops, d)?;
+        ''')
 
 # UJ-type unconditional branch instructions.
 UJ = EncRecipe(
@@ -174,7 +282,16 @@ UJ = EncRecipe(
         let dest = i64::from(func.offsets[destination]);
         let disp = dest - i64::from(sink.offset());
         put_uj(bits, disp, 0, sink);
-        ''')
+        ''',
+        decode='''
+        let (disp, rd) = get_uj(bits, d)?;
+        if rd != 0 {
+            return None;
+        }
+        let dest = d.start().wrapping_add(disp as u32);
+        d.push(Operand::Target(dest));
+        ''',
+        asm='asm_inst(bits, &[xreg(0), target(dest)], d)?;')
 
 UJcall = EncRecipe(
         'UJcall', Call, size=4, ins=(), outs=(),
@@ -182,6 +299,24 @@ UJcall = EncRecipe(
         sink.reloc_func(RelocKind::Call.into(), func_ref);
         // rd=%x1 is the standard link register.
         put_uj(bits, 0, 1, sink);
+        ''',
+        decode='''
+        // The displacement is a relocation.
+        let (_, rd) = get_uj(bits, d)?;
+        if rd != 1 {
+            return None;
+        }
+        ''',
+        asm='asm_inst(bits, &[xreg(1), "<reloc>".to_string()], d)?;')
+
+# Like UJcall, but also record the return address in the call-site table.
+UJinvoke = EncRecipe(
+        'UJinvoke', Invoke, size=4, ins=(), outs=(),
+        emit='''
+        sink.reloc_func(RelocKind::Call.into(), func_ref);
+        // rd=%x1 is the standard link register.
+        put_uj(bits, 0, 1, sink);
+        sink.add_call_site(destination);
         ''')
 
 # SB-type branch instructions.
@@ -193,6 +328,15 @@ SB = EncRecipe(
         let dest = i64::from(func.offsets[destination]);
         let disp = dest - i64::from(sink.offset());
         put_sb(bits, disp, in_reg0, in_reg1, sink);
+        ''',
+        decode='''
+        let (disp, in_reg0, in_reg1) = get_sb(bits, d)?;
+        let dest = d.start().wrapping_add(disp as u32);
+        d.push(Operand::Target(dest));
+        ''',
+        asm='''
+        let ops = [xreg(in_reg0), xreg(in_reg1), target(dest)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # SB-type branch instruction with rs2 fixed to zero.
@@ -204,6 +348,18 @@ SBzero = EncRecipe(
         let dest = i64::from(func.offsets[destination]);
         let disp = dest - i64::from(sink.offset());
         put_sb(bits, disp, in_reg0, 0, sink);
+        ''',
+        decode='''
+        let (disp, in_reg0, rs2) = get_sb(bits, d)?;
+        if rs2 != 0 {
+            return None;
+        }
+        let dest = d.start().wrapping_add(disp as u32);
+        d.push(Operand::Target(dest));
+        ''',
+        asm='''
+        let ops = [xreg(in_reg0), xreg(rs2), target(dest)];
+        asm_inst(bits, &ops, d)?;
         ''')
 
 # Spill of a GPR.
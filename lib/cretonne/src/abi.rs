@@ -3,8 +3,9 @@
 //! This module provides functions and data structures that are useful for implementing the
 //! `TargetIsa::legalize_signature()` method.
 
-use ir::{ArgumentLoc, AbiParam, ArgumentExtension, Type};
-use std::cmp::Ordering;
+use ir::{ArgumentLoc, AbiParam, ArgumentExtension, ArgumentPurpose, Signature, StructArg, Type};
+use ir::types::{F32, F64};
+use std::cmp::{max, min, Ordering};
 
 /// Legalization action to perform on a single argument or return value when converting a
 /// signature.
@@ -21,6 +22,12 @@ pub enum ArgAction {
     /// This action can split an integer type into two smaller integer arguments, or it can split a
     /// SIMD vector into halves.
     Convert(ValueConversion),
+
+    /// Replace a `StructArgument` with the pieces of the struct, then call again for each piece.
+    ///
+    /// When the flag is set, the 8-byte words that only contain floating point fields become
+    /// floating point pieces. See `struct_pieces()`.
+    SplitStruct(bool),
 }
 
 impl From<ArgumentLoc> for ArgAction {
@@ -84,6 +91,31 @@ pub trait ArgAssigner {
     fn assign(&mut self, arg: &AbiParam) -> ArgAction;
 }
 
+/// Get the `StructPart` pieces of the struct `sa` passed by value.
+///
+/// The struct is split into words the size of `pointer_type`. Each word becomes an integer piece,
+/// except that the 8-byte words in `sa.float_words` become `f64` or `f32` pieces when
+/// `use_floats` is set. The last word is cut off at the end of the struct, and its integer piece
+/// has the smallest type of at least 32 bits that can hold it.
+pub fn struct_pieces(sa: StructArg, pointer_type: Type, use_floats: bool) -> Vec<AbiParam> {
+    let word = pointer_type.bytes();
+    let mut pieces = Vec::new();
+    let mut offset = 0;
+    while offset < sa.size {
+        let bytes = min(word, sa.size - offset);
+        let index = offset / 8;
+        let ty = if use_floats && index < 8 && sa.float_words & (1 << index) != 0 {
+            if bytes > 4 { F64 } else { F32 }
+        } else {
+            let bits = max(bytes.next_power_of_two() * 8, 32);
+            Type::int(bits as u16).expect("Bad struct word size")
+        };
+        pieces.push(AbiParam::special(ty, ArgumentPurpose::StructPart(sa)));
+        offset += word;
+    }
+    pieces
+}
+
 /// Legalize the arguments in `args` using the given argument assigner.
 ///
 /// This function can be used for both arguments and return values.
@@ -117,10 +149,43 @@ pub fn legalize_args<AA: ArgAssigner>(args: &mut Vec<AbiParam>, aa: &mut AA) {
                     args.insert(argno + 1, new_arg);
                 }
             }
+            // Replace a struct with its pieces. Then revisit all of them.
+            ArgAction::SplitStruct(use_floats) => {
+                let sa = match arg.purpose {
+                    ArgumentPurpose::StructArgument(sa) => sa,
+                    _ => panic!("Can't split non-struct argument {}", arg),
+                };
+                let pieces = struct_pieces(sa, arg.value_type, use_floats);
+                args.splice(argno..argno + 1, pieces);
+            }
         }
     }
 }
 
+/// Legalize the parameters of `sig` using the given argument assigner.
+///
+/// The parameters of a variadic signature are legalized in two steps: First the fixed parameters,
+/// then `start_variadic` is called so the assigner can switch to the conventions used for
+/// variadic arguments, and finally the remaining parameters are legalized. The `fixed_params`
+/// count is updated to cover the legalized fixed parameters.
+pub fn legalize_params<AA, F>(sig: &mut Signature, aa: &mut AA, start_variadic: F)
+where
+    AA: ArgAssigner,
+    F: FnOnce(&mut AA),
+{
+    let fixed = match sig.fixed_params {
+        Some(fixed) => fixed,
+        None => return legalize_args(&mut sig.params, aa),
+    };
+
+    let mut variadic = sig.params.split_off(fixed);
+    legalize_args(&mut sig.params, aa);
+    sig.fixed_params = Some(sig.params.len());
+    start_variadic(aa);
+    legalize_args(&mut variadic, aa);
+    sig.params.append(&mut variadic);
+}
+
 /// Determine the right action to take when passing a `have` value type to a call signature where
 /// the next argument is `arg` which has a different value type.
 ///
@@ -162,10 +227,15 @@ pub fn legalize_abi_value(have: Type, arg: &AbiParam) -> ValueConversion {
         }
         // We have the same number of bits as the argument.
         Ordering::Equal => {
-            // This must be an integer vector that is split and then extended.
             assert!(arg.value_type.is_int());
-            assert!(have.is_vector());
-            ValueConversion::VectorSplit
+            if have.is_float() {
+                // A float passed in an integer register, like a variadic argument on RISC-V.
+                ValueConversion::IntBits
+            } else {
+                // This must be an integer vector that is split and then extended.
+                assert!(have.is_vector());
+                ValueConversion::VectorSplit
+            }
         }
         // We have more bits than the argument.
         Ordering::Greater => {
@@ -188,6 +258,29 @@ mod tests {
     use ir::types;
     use ir::AbiParam;
 
+    #[test]
+    fn pieces() {
+        let mut sa = StructArg::new(12, 4);
+        sa.float_words = 1;
+        let types = |pieces: Vec<AbiParam>| {
+            pieces.iter().map(|p| p.value_type).collect::<Vec<_>>()
+        };
+        assert_eq!(types(struct_pieces(sa, types::I64, false)), [types::I64, types::I32]);
+        assert_eq!(types(struct_pieces(sa, types::I64, true)), [types::F64, types::I32]);
+        assert_eq!(
+            types(struct_pieces(sa, types::I32, false)),
+            [types::I32, types::I32, types::I32]
+        );
+
+        // The last word is cut off.
+        let sa = StructArg::new(11, 1);
+        assert_eq!(types(struct_pieces(sa, types::I64, false)), [types::I64, types::I32]);
+        let sa = StructArg::new(7, 1);
+        assert_eq!(types(struct_pieces(sa, types::I64, false)), [types::I64]);
+        let sa = StructArg::new(2, 2);
+        assert_eq!(types(struct_pieces(sa, types::I64, false)), [types::I32]);
+    }
+
     #[test]
     fn legalize() {
         let mut arg = AbiParam::new(types::I32);
@@ -221,5 +314,11 @@ mod tests {
             legalize_abi_value(types::F64, &arg),
             ValueConversion::IntBits
         );
+
+        // 32-bit float is passed as an integer of the same size.
+        assert_eq!(
+            legalize_abi_value(types::F32, &arg),
+            ValueConversion::IntBits
+        );
     }
 }
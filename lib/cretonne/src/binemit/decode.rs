@@ -0,0 +1,372 @@
+//! Decoding and disassembling binary machine code.
+//!
+//! The decoder reverses binary emission: It decodes machine code produced by a target ISA back into
+//! the Cretonne instructions and encodings that produced it, along with the registers and immediate
+//! operands of each instruction. Each decoded instruction also carries the assembly text of the
+//! machine instructions it was read from. A single encoding may emit several machine instructions,
+//! like the `cmp` and `setCC` pair of an Intel `icmp`, and their mnemonics are separated by `; `.
+//!
+//! Each ISA has a decoder generated from the `decode` and `asm` code snippets of its encoding
+//! recipes, and a table of the encodings in each CPU mode. An instruction is decoded by trying the
+//! encodings in table order until one of them matches. When several instructions share the same
+//! machine code, like `copy` and `regmove`, the first one in the encoding table is used.
+
+use binemit::CodeOffset;
+use ir::{Opcode, Type, types};
+use ir::condcodes::{IntCC, FloatCC};
+use ir::immediates::{Imm64, Offset32};
+use isa::{EncInfo, Encoding, RegInfo, RegUnit};
+use std::fmt;
+
+/// An entry in the table of encodings used for decoding.
+pub struct DecodeEntry {
+    /// The encoded instruction.
+    pub opcode: Opcode,
+    /// The controlling type variable of the instruction, or `VOID`.
+    pub ctrl_type: Type,
+    /// Condition code required by the instruction predicate of the encoding.
+    pub cond: Option<Cond>,
+    /// The encoding recipe number.
+    pub recipe: u16,
+    /// The encoding bits.
+    pub bits: u16,
+}
+
+/// A condition code operand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cond {
+    /// An integer condition code.
+    Int(IntCC),
+    /// A floating point condition code.
+    Float(FloatCC),
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cond::Int(cc) => write!(f, "{}", cc),
+            Cond::Float(cc) => write!(f, "{}", cc),
+        }
+    }
+}
+
+/// An operand of a decoded instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    /// A register.
+    Reg(RegUnit),
+    /// A stack location addressed by a base register and a displacement.
+    Stack(RegUnit, i32),
+    /// An immediate operand.
+    Imm(i64),
+    /// An address offset which applies to the preceding register operand.
+    Offset(i32),
+    /// A branch target given as an offset from the start of the code.
+    Target(CodeOffset),
+}
+
+/// A machine code instruction decoded into the Cretonne instruction that produced it.
+pub struct DecodedInst {
+    /// Offset of the instruction from the start of the code.
+    pub offset: CodeOffset,
+    /// Size of the instruction in bytes.
+    pub size: CodeOffset,
+    /// The encoding that produced the machine code.
+    pub encoding: Encoding,
+    /// The instruction opcode.
+    pub opcode: Opcode,
+    /// The controlling type variable of the instruction, or `VOID`.
+    pub ctrl_type: Type,
+    /// Condition code operand.
+    pub cond: Option<Cond>,
+    /// Output operands.
+    pub outs: Vec<Operand>,
+    /// Input operands followed by immediate operands.
+    pub args: Vec<Operand>,
+    /// Destination of a `regmove`, `regspill`, or `regfill` instruction.
+    pub dst: Option<Operand>,
+    /// The machine instructions in the target's assembly language.
+    pub asm: String,
+}
+
+impl DecodedInst {
+    /// Get an object that displays this instruction, using the register names from `regs`.
+    ///
+    /// The format resembles Cretonne IL, with registers and immediates in place of values:
+    ///
+    /// ```text
+    /// %rax = iadd.i32 %rax, %rcx
+    /// ```
+    pub fn display<'a>(&'a self, regs: &'a RegInfo) -> DisplayDecodedInst<'a> {
+        DisplayDecodedInst { inst: self, regs }
+    }
+}
+
+/// Object for displaying a decoded instruction.
+pub struct DisplayDecodedInst<'a> {
+    inst: &'a DecodedInst,
+    regs: &'a RegInfo,
+}
+
+impl<'a> DisplayDecodedInst<'a> {
+    fn write_operand(&self, f: &mut fmt::Formatter, op: Operand) -> fmt::Result {
+        match op {
+            Operand::Reg(ru) => write!(f, "{}", self.regs.display_regunit(ru)),
+            Operand::Stack(base, disp) => {
+                write!(f, "[{}{}]", self.regs.display_regunit(base), Offset32::new(disp))
+            }
+            Operand::Imm(imm) => write!(f, "{}", Imm64::new(imm)),
+            Operand::Offset(disp) => write!(f, "{}", Offset32::new(disp)),
+            Operand::Target(offset) => write!(f, "@{:04x}", offset),
+        }
+    }
+}
+
+impl<'a> fmt::Display for DisplayDecodedInst<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inst = self.inst;
+        for (i, &op) in inst.outs.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            self.write_operand(f, op)?;
+        }
+        if !inst.outs.is_empty() {
+            write!(f, " = ")?;
+        }
+        write!(f, "{}", inst.opcode)?;
+        if inst.ctrl_type != types::VOID {
+            write!(f, ".{}", inst.ctrl_type)?;
+        }
+        if let Some(cond) = inst.cond {
+            write!(f, " {}", cond)?;
+        }
+        for (i, &op) in inst.args.iter().enumerate() {
+            match op {
+                // Offsets are attached to the preceding address operand.
+                Operand::Offset(_) => {}
+                _ if i == 0 => write!(f, " ")?,
+                _ => write!(f, ", ")?,
+            }
+            self.write_operand(f, op)?;
+        }
+        if let Some(dst) = inst.dst {
+            write!(f, " -> ")?;
+            self.write_operand(f, dst)?;
+        }
+        Ok(())
+    }
+}
+
+/// State for decoding a single instruction.
+///
+/// The generated decoders read bytes from the `Decoder` and record the operands they find.
+pub struct Decoder<'a> {
+    code: &'a [u8],
+    start: usize,
+    pos: usize,
+    is_64bit: bool,
+
+    /// ISA-specific prefix bits that apply to the rest of the instruction, like an Intel REX
+    /// prefix.
+    pub prefix: u8,
+
+    cond: Option<Cond>,
+    outs: Vec<Operand>,
+    args: Vec<Operand>,
+    imms: Vec<Operand>,
+    dst: Option<Operand>,
+    asm: String,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(code: &'a [u8], offset: CodeOffset, is_64bit: bool) -> Decoder<'a> {
+        Decoder {
+            code,
+            start: offset as usize,
+            pos: offset as usize,
+            is_64bit,
+            prefix: 0,
+            cond: None,
+            outs: Vec::new(),
+            args: Vec::new(),
+            imms: Vec::new(),
+            dst: None,
+            asm: String::new(),
+        }
+    }
+
+    /// Is the code being decoded running in a 64-bit CPU mode?
+    pub fn is_64bit(&self) -> bool {
+        self.is_64bit
+    }
+
+    /// Get the offset of the instruction being decoded.
+    pub fn start(&self) -> CodeOffset {
+        self.start as CodeOffset
+    }
+
+    /// Get the offset of the next byte to be decoded.
+    pub fn pos(&self) -> CodeOffset {
+        self.pos as CodeOffset
+    }
+
+    /// Read 1 byte.
+    pub fn get1(&mut self) -> Option<u8> {
+        let b = *self.code.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    /// Read 4 bytes in little-endian byte order.
+    pub fn get4(&mut self) -> Option<u32> {
+        let lo = u32::from(self.get2()?);
+        let hi = u32::from(self.get2()?);
+        Some(lo | (hi << 16))
+    }
+
+    /// Read 8 bytes in little-endian byte order.
+    pub fn get8(&mut self) -> Option<u64> {
+        let lo = u64::from(self.get4()?);
+        let hi = u64::from(self.get4()?);
+        Some(lo | (hi << 32))
+    }
+
+    fn get2(&mut self) -> Option<u16> {
+        let lo = u16::from(self.get1()?);
+        let hi = u16::from(self.get1()?);
+        Some(lo | (hi << 8))
+    }
+
+    /// Read 1 byte, failing if it isn't `x`.
+    pub fn expect1(&mut self, x: u8) -> Option<()> {
+        if self.get1()? == x { Some(()) } else { None }
+    }
+
+    /// Read 4 bytes, failing if they aren't `x`.
+    pub fn expect4(&mut self, x: u32) -> Option<()> {
+        if self.get4()? == x { Some(()) } else { None }
+    }
+
+    /// Skip `n` bytes whose contents are filled in by a relocation.
+    pub fn skip(&mut self, n: usize) -> Option<()> {
+        if self.pos + n <= self.code.len() {
+            self.pos += n;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Record the condition code operand.
+    pub fn cond(&mut self, cond: Cond) {
+        self.cond = Some(cond);
+    }
+
+    /// Record an immediate operand, to be displayed after the input operands.
+    pub fn push(&mut self, op: Operand) {
+        self.imms.push(op);
+    }
+
+    /// Record an output operand.
+    pub fn out(&mut self, op: Operand) {
+        self.outs.push(op);
+    }
+
+    /// Record an input operand.
+    pub fn arg(&mut self, op: Operand) {
+        self.args.push(op);
+    }
+
+    /// Record the destination of a register move.
+    pub fn dst(&mut self, op: Operand) {
+        self.dst = Some(op);
+    }
+
+    /// Record the assembly text of the next machine instruction.
+    pub fn asm(&mut self, text: String) {
+        if !self.asm.is_empty() {
+            self.asm.push_str("; ");
+        }
+        self.asm.push_str(&text);
+    }
+}
+
+/// Signature of the generated function that decodes an instruction with a given opcode, recipe,
+/// and encoding bits.
+pub type DecodeRecipe = fn(Opcode, u16, u16, &mut Decoder) -> Option<()>;
+
+/// Decode the instruction at `offset` in `code` by trying the encodings in `entries` in order.
+///
+/// This function is called from the `TargetIsa::decode_inst()` implementations with the
+/// tables and decoder of the ISA, and the CPU mode that `entries` belong to.
+pub fn decode_inst(
+    code: &[u8],
+    offset: CodeOffset,
+    is_64bit: bool,
+    entries: &[DecodeEntry],
+    encinfo: &EncInfo,
+    decode: DecodeRecipe,
+) -> Option<DecodedInst> {
+    for entry in entries {
+        let mut d = Decoder::new(code, offset, is_64bit);
+        if decode(entry.opcode, entry.recipe, entry.bits, &mut d).is_none() {
+            continue;
+        }
+        let encoding = Encoding::new(entry.recipe, entry.bits);
+        let size = d.pos() - d.start();
+        debug_assert_eq!(
+            size,
+            encinfo.bytes(encoding),
+            "Inconsistent decoded size for {}",
+            encinfo.display(encoding)
+        );
+        let mut args = d.args;
+        args.extend(d.imms);
+        return Some(DecodedInst {
+            offset,
+            size,
+            encoding,
+            opcode: entry.opcode,
+            ctrl_type: entry.ctrl_type,
+            cond: d.cond.or(entry.cond),
+            outs: d.outs,
+            args,
+            dst: d.dst,
+            asm: d.asm,
+        });
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Decoder;
+
+    #[test]
+    fn decoder_bytes() {
+        let code = [0x90, 0x01, 0x02, 0x03, 0x04, 0xf4];
+        let mut d = Decoder::new(&code, 1, true);
+        assert_eq!(d.start(), 1);
+        assert_eq!(d.get4(), Some(0x04030201));
+        assert_eq!(d.pos(), 5);
+        assert_eq!(d.expect1(0x90), None);
+        assert_eq!(d.get1(), None);
+        assert_eq!(d.skip(1), None);
+
+        let mut d = Decoder::new(&code, 0, true);
+        assert_eq!(d.expect1(0x90), Some(()));
+        assert_eq!(d.skip(4), Some(()));
+        assert_eq!(d.get8(), None);
+    }
+    #[test]
+    fn decoder_asm() {
+        let code = [0x90];
+        let mut d = Decoder::new(&code, 0, false);
+        assert!(!d.is_64bit());
+        d.asm("cmpl %esi, %ecx".to_string());
+        assert_eq!(d.asm, "cmpl %esi, %ecx");
+        d.asm("setl %dl".to_string());
+        assert_eq!(d.asm, "cmpl %esi, %ecx; setl %dl");
+    }
+}
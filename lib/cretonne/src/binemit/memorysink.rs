@@ -13,9 +13,11 @@
 //! that a `MemoryCodeSink` will always write binary machine code to raw memory. It forwards any
 //! relocations to a `RelocSink` trait object. Relocations are less frequent than the
 //! `CodeSink::put*` methods, so the performance impact of the virtual callbacks is less severe.
+//! Stack maps and call sites are forwarded to `StackMapSink` and `CallSiteSink` trait objects in
+//! the same way.
 
-use ir::{Ebb, FuncRef, JumpTable};
-use super::{CodeSink, CodeOffset, Reloc};
+use ir::{Ebb, FuncRef, FunctionName, JumpTable};
+use super::{CodeSink, CodeOffset, Reloc, Addend, StackMap};
 use std::ptr::write_unaligned;
 
 /// A `CodeSink` that writes binary machine code directly into memory.
@@ -25,7 +27,9 @@ use std::ptr::write_unaligned;
 /// sure to allocate enough memory for the whole function. The number of bytes required is returned
 /// by the `Context::compile()` function.
 ///
-/// Any relocations in the function are forwarded to the `RelocSink` trait object.
+/// Any relocations in the function are forwarded to the `RelocSink` trait object, any stack maps
+/// are forwarded to the `StackMapSink` trait object, and any call sites with landing pads are
+/// forwarded to the `CallSiteSink` trait object.
 ///
 /// Note that `MemoryCodeSink` writes multi-byte values in the native byte order of the host. This
 /// is not the right thing to do for cross compilation.
@@ -33,15 +37,24 @@ pub struct MemoryCodeSink<'a> {
     data: *mut u8,
     offset: isize,
     relocs: &'a mut RelocSink,
+    stackmaps: &'a mut StackMapSink,
+    callsites: &'a mut CallSiteSink,
 }
 
 impl<'a> MemoryCodeSink<'a> {
     /// Create a new memory code sink that writes a function to the memory pointed to by `data`.
-    pub fn new(data: *mut u8, relocs: &mut RelocSink) -> MemoryCodeSink {
+    pub fn new(
+        data: *mut u8,
+        relocs: &'a mut RelocSink,
+        stackmaps: &'a mut StackMapSink,
+        callsites: &'a mut CallSiteSink,
+    ) -> MemoryCodeSink<'a> {
         MemoryCodeSink {
             data,
             offset: 0,
             relocs,
+            stackmaps,
+            callsites,
         }
     }
 }
@@ -54,10 +67,46 @@ pub trait RelocSink {
     /// Add a relocation referencing an external function at the current offset.
     fn reloc_func(&mut self, CodeOffset, Reloc, FuncRef);
 
+    /// Add a relocation referencing an external symbol plus the addend at the current offset.
+    fn reloc_external(&mut self, CodeOffset, Reloc, &FunctionName, Addend);
+
     /// Add a relocation referencing a jump table.
     fn reloc_jt(&mut self, CodeOffset, Reloc, JumpTable);
 }
 
+/// A trait for receiving stack maps for code that is emitted directly into memory.
+pub trait StackMapSink {
+    /// Add a stack map for the safepoint at `CodeOffset`.
+    fn add_stackmap(&mut self, CodeOffset, StackMap);
+}
+
+/// A `StackMapSink` that discards all stack maps.
+///
+/// Use this when compiling code that doesn't contain any safepoints.
+pub struct NullStackMapSink;
+
+impl StackMapSink for NullStackMapSink {
+    fn add_stackmap(&mut self, _: CodeOffset, _: StackMap) {}
+}
+
+/// A trait for receiving the call-site table for code that is emitted directly into memory.
+///
+/// Each entry maps the return address of an `invoke` instruction to its landing pad. The code
+/// offset of the landing pad EBB can be found in `Function::offsets` after compilation.
+pub trait CallSiteSink {
+    /// Add a call site whose return address is `CodeOffset`, unwinding to the landing pad `Ebb`.
+    fn add_call_site(&mut self, CodeOffset, Ebb);
+}
+
+/// A `CallSiteSink` that discards all call sites.
+///
+/// Use this when compiling code that doesn't contain any `invoke` instructions.
+pub struct NullCallSiteSink;
+
+impl CallSiteSink for NullCallSiteSink {
+    fn add_call_site(&mut self, _: CodeOffset, _: Ebb) {}
+}
+
 impl<'a> CodeSink for MemoryCodeSink<'a> {
     fn offset(&self) -> CodeOffset {
         self.offset as CodeOffset
@@ -101,8 +150,23 @@ impl<'a> CodeSink for MemoryCodeSink<'a> {
         self.relocs.reloc_func(ofs, rel, func);
     }
 
+    fn reloc_external(&mut self, rel: Reloc, name: &FunctionName, addend: Addend) {
+        let ofs = self.offset();
+        self.relocs.reloc_external(ofs, rel, name, addend);
+    }
+
     fn reloc_jt(&mut self, rel: Reloc, jt: JumpTable) {
         let ofs = self.offset();
         self.relocs.reloc_jt(ofs, rel, jt);
     }
+
+    fn add_stackmap(&mut self, map: StackMap) {
+        let ofs = self.offset();
+        self.stackmaps.add_stackmap(ofs, map);
+    }
+
+    fn add_call_site(&mut self, landing_pad: Ebb) {
+        let ofs = self.offset();
+        self.callsites.add_call_site(ofs, landing_pad);
+    }
 }
@@ -5,11 +5,19 @@
 
 mod relaxation;
 mod memorysink;
+mod decode;
+mod stackmap;
+mod recorder;
 
 pub use self::relaxation::relax_branches;
-pub use self::memorysink::{MemoryCodeSink, RelocSink};
+pub use self::memorysink::{MemoryCodeSink, RelocSink, StackMapSink, NullStackMapSink,
+                           CallSiteSink, NullCallSiteSink};
+pub use self::decode::{DecodedInst, DisplayDecodedInst, Operand, Cond};
+pub use self::decode::{Decoder, DecodeEntry, DecodeRecipe, decode_inst};
+pub use self::stackmap::StackMap;
+pub use self::recorder::{RelocRecorder, Relocation, RelocTarget};
 
-use ir::{Ebb, FuncRef, JumpTable, Function, Inst};
+use ir::{Ebb, FuncRef, FunctionName, JumpTable, Function, Inst};
 use regalloc::RegDiversions;
 
 /// Offset in bytes from the beginning of the function.
@@ -19,8 +27,12 @@ use regalloc::RegDiversions;
 pub type CodeOffset = u32;
 
 /// Relocation kinds depend on the current ISA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Reloc(pub u16);
 
+/// Addend to add to the symbol value.
+pub type Addend = i64;
+
 /// Abstract interface for adding bytes to the code segment.
 ///
 /// A `CodeSink` will receive all of the machine code for a function. It also accepts relocations
@@ -47,8 +59,17 @@ pub trait CodeSink {
     /// Add a relocation referencing an external function at the current offset.
     fn reloc_func(&mut self, Reloc, FuncRef);
 
+    /// Add a relocation referencing an external symbol plus the addend at the current offset.
+    fn reloc_external(&mut self, Reloc, &FunctionName, Addend);
+
     /// Add a relocation referencing a jump table.
     fn reloc_jt(&mut self, Reloc, JumpTable);
+
+    /// Add a stack map for the safepoint at the current offset.
+    fn add_stackmap(&mut self, StackMap);
+
+    /// Add a call site unwinding to a landing pad. The current offset is the return address.
+    fn add_call_site(&mut self, Ebb);
 }
 
 /// Report a bad encoding error.
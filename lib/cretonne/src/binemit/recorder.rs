@@ -0,0 +1,92 @@
+//! A relocation sink that records the relocations of a function.
+//!
+//! Embedders that emit code with `Context::emit_to_memory()` usually want to apply the relocations
+//! themselves once the addresses of the other functions and symbols are known. `RelocRecorder`
+//! collects them in a list, with the targets identified independently of the function they came
+//! from.
+
+use ir::{Ebb, FuncRef, Function, FunctionName, JumpTable};
+use super::{RelocSink, CodeOffset, Reloc, Addend};
+
+/// The target of a relocation in a compiled function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RelocTarget {
+    /// An EBB in the same function. Its offset is in `func.offsets`.
+    Ebb(Ebb),
+
+    /// An external function, identified by the name in its `ExtFuncData`.
+    Func(FunctionName),
+
+    /// A jump table in the same function.
+    JumpTable(JumpTable),
+
+    /// An external symbol with an addend, from a `globalsym` global variable.
+    External(FunctionName, Addend),
+}
+
+/// A relocation in the machine code of a compiled function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Relocation {
+    /// Offset of the relocation from the beginning of the function's code.
+    pub offset: CodeOffset,
+
+    /// ISA-dependent relocation kind. See `TargetIsa::reloc_names()`.
+    pub reloc: Reloc,
+
+    /// What the relocation refers to.
+    pub target: RelocTarget,
+}
+
+/// A `RelocSink` that records the relocations of `func` as a list of `Relocation`s.
+pub struct RelocRecorder<'a> {
+    func: &'a Function,
+    relocs: Vec<Relocation>,
+}
+
+impl<'a> RelocRecorder<'a> {
+    /// Create a recorder for the relocations emitted for `func`.
+    pub fn new(func: &'a Function) -> RelocRecorder<'a> {
+        RelocRecorder {
+            func,
+            relocs: Vec::new(),
+        }
+    }
+
+    /// Get the recorded relocations, in the order they were emitted.
+    pub fn into_relocs(self) -> Vec<Relocation> {
+        self.relocs
+    }
+
+    fn push(&mut self, offset: CodeOffset, reloc: Reloc, target: RelocTarget) {
+        self.relocs.push(Relocation {
+            offset,
+            reloc,
+            target,
+        });
+    }
+}
+
+impl<'a> RelocSink for RelocRecorder<'a> {
+    fn reloc_ebb(&mut self, offset: CodeOffset, reloc: Reloc, ebb: Ebb) {
+        self.push(offset, reloc, RelocTarget::Ebb(ebb));
+    }
+
+    fn reloc_func(&mut self, offset: CodeOffset, reloc: Reloc, func: FuncRef) {
+        let name = self.func.dfg.ext_funcs[func].name.clone();
+        self.push(offset, reloc, RelocTarget::Func(name));
+    }
+
+    fn reloc_external(
+        &mut self,
+        offset: CodeOffset,
+        reloc: Reloc,
+        name: &FunctionName,
+        addend: Addend,
+    ) {
+        self.push(offset, reloc, RelocTarget::External(name.clone(), addend));
+    }
+
+    fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable) {
+        self.push(offset, reloc, RelocTarget::JumpTable(jt));
+    }
+}
@@ -0,0 +1,70 @@
+//! Stack maps for precise garbage collection.
+//!
+//! A stack map is emitted for every `safepoint` instruction. It describes the stack slots that
+//! hold live references when control returns from the call preceding the safepoint.
+
+use ir::{Function, Value};
+use ir::stackslot::StackOffset;
+use isa::StackRef;
+use regalloc::RegDiversions;
+use std::fmt;
+
+/// The set of stack locations holding live references at a safepoint.
+///
+/// Each location is given as a byte offset from the stack pointer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackMap {
+    offsets: Vec<StackOffset>,
+}
+
+impl StackMap {
+    /// Create a stack map describing the stack locations of `values`.
+    ///
+    /// All of the values must be assigned to stack slots, and the stack frame layout must have
+    /// been computed. The offsets are relative to the stack pointer during the preceding call, so
+    /// the safepoint must come before any stack pointer adjustment following the call.
+    pub fn from_values(values: &[Value], func: &Function, divert: &RegDiversions) -> StackMap {
+        let mut offsets: Vec<StackOffset> = values
+            .iter()
+            .map(|&v| {
+                let ss = divert.stack(v, &func.locations);
+                StackRef::sp(ss, &func.stack_slots).offset
+            })
+            .collect();
+        offsets.sort();
+        offsets.dedup();
+        StackMap { offsets }
+    }
+
+    /// Get the stack pointer offsets of the live references in increasing order.
+    pub fn offsets(&self) -> &[StackOffset] {
+        &self.offsets
+    }
+}
+
+impl fmt::Display for StackMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "stackmap(")?;
+        for (i, offset) in self.offsets.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", offset)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StackMap;
+
+    #[test]
+    fn display() {
+        let map = StackMap { offsets: vec![] };
+        assert_eq!(map.to_string(), "stackmap()");
+        let map = StackMap { offsets: vec![0, 8, 24] };
+        assert_eq!(map.offsets(), &[0, 8, 24]);
+        assert_eq!(map.to_string(), "stackmap(0, 8, 24)");
+    }
+}
@@ -0,0 +1,312 @@
+//! Binary format for Cretonne IL.
+//!
+//! The binary format is a compact alternative to the textual `.cton` format which is faster to read
+//! and write. It is intended for caching functions between runs, not for long-term storage: The
+//! format is versioned, and a reader rejects data written by a different format version.
+//!
+//! Unlike the text format, the binary format preserves the entity numbers of a function exactly,
+//! including detached values and instructions that are not inserted in the layout. A function
+//! that is written and read back is identical to the original, except for the transient EBB
+//! offsets computed by `binemit::relax_branches()`. Encodings and value locations are only
+//! included when requested.
+//!
+//! # Format
+//!
+//! A file starts with the 4-byte magic number `CTNB` followed by the format version as a 32-bit
+//! little-endian integer. Then follows the number of functions and the functions themselves.
+//!
+//! Numbers are written as LEB128 variable-length integers. Signed numbers are zigzag encoded
+//! first, so small negative numbers are also short. Entity references are written as their
+//! entity number, and optional entity references as the entity number plus one, using 0 for
+//! `None`. Floating point immediates are written as their little-endian bit patterns.
+//!
+//! A function consists of these sections, in order:
+//!
+//! 1. The function name and signature.
+//! 2. The number of EBBs, instructions, and values in the data flow graph.
+//! 3. The stack slots, global variables, heaps, signatures, external functions, and jump tables.
+//! 4. The value table, the opcodes used in the function, and the instructions with their results.
+//! 5. The parameters of every EBB.
+//! 6. The layout of EBBs and instructions.
+//! 7. The source locations.
+//! 8. Optionally, the encodings and value locations.
+//!
+//! Enumerated types like condition codes are written as indexes into the tables in this module.
+//! Opcodes are written as indexes into a table of opcode names at the start of each function, so
+//! the format doesn't depend on the numbering of opcodes.
+
+use ir::{types, Type, CallConv, ArgumentPurpose, ArgumentExtension, StackSlotKind, TrapCode};
+use ir::condcodes::{IntCC, FloatCC};
+use std::error;
+use std::fmt;
+use std::result;
+
+pub use self::read::{read_functions, is_binary_format};
+pub use self::write::write_functions;
+
+mod read;
+mod write;
+
+/// Magic number at the start of a binary IL file.
+pub const MAGIC: [u8; 4] = *b"CTNB";
+
+/// Version of the binary format.
+///
+/// This must be incremented whenever the format changes.
+pub const VERSION: u32 = 7;
+
+/// An error reading binary IL.
+#[derive(Debug)]
+pub struct Error {
+    /// Byte offset of the error in the input.
+    pub offset: usize,
+    /// Error message.
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Result of reading binary IL.
+pub type Result<T> = result::Result<T, Error>;
+
+// Lane types. A type is written as the index of its lane type in the low 4 bits, and the log2 of
+// the number of lanes in the high 4 bits.
+static LANE_TYPES: [Type; 16] = [
+    types::VOID,
+    types::IFLAGS,
+    types::FFLAGS,
+    types::B1,
+    types::B8,
+    types::B16,
+    types::B32,
+    types::B64,
+    types::I8,
+    types::I16,
+    types::I32,
+    types::I64,
+    types::F32,
+    types::F64,
+    types::R32,
+    types::R64,
+];
+
+static INTCC: [IntCC; 10] = [
+    IntCC::Equal,
+    IntCC::NotEqual,
+    IntCC::SignedLessThan,
+    IntCC::SignedGreaterThanOrEqual,
+    IntCC::SignedGreaterThan,
+    IntCC::SignedLessThanOrEqual,
+    IntCC::UnsignedLessThan,
+    IntCC::UnsignedGreaterThanOrEqual,
+    IntCC::UnsignedGreaterThan,
+    IntCC::UnsignedLessThanOrEqual,
+];
+
+static FLOATCC: [FloatCC; 14] = [
+    FloatCC::Ordered,
+    FloatCC::Unordered,
+    FloatCC::Equal,
+    FloatCC::NotEqual,
+    FloatCC::OrderedNotEqual,
+    FloatCC::UnorderedOrEqual,
+    FloatCC::LessThan,
+    FloatCC::LessThanOrEqual,
+    FloatCC::GreaterThan,
+    FloatCC::GreaterThanOrEqual,
+    FloatCC::UnorderedOrLessThan,
+    FloatCC::UnorderedOrLessThanOrEqual,
+    FloatCC::UnorderedOrGreaterThan,
+    FloatCC::UnorderedOrGreaterThanOrEqual,
+];
+
+static CALL_CONVS: [CallConv; 5] = [
+    CallConv::Native,
+    CallConv::SpiderWASM,
+    CallConv::Tail,
+    CallConv::SystemV,
+    CallConv::WindowsFastcall,
+];
+
+static PURPOSES: [ArgumentPurpose; 8] = [
+    ArgumentPurpose::Normal,
+    ArgumentPurpose::StructReturn,
+    ArgumentPurpose::Link,
+    ArgumentPurpose::FramePointer,
+    ArgumentPurpose::CalleeSaved,
+    ArgumentPurpose::VMContext,
+    ArgumentPurpose::SignatureId,
+    ArgumentPurpose::FloatRegCount,
+];
+
+static EXTENSIONS: [ArgumentExtension; 3] = [
+    ArgumentExtension::None,
+    ArgumentExtension::Uext,
+    ArgumentExtension::Sext,
+];
+
+static STACK_SLOT_KINDS: [StackSlotKind; 5] = [
+    StackSlotKind::SpillSlot,
+    StackSlotKind::Local,
+    StackSlotKind::IncomingArg,
+    StackSlotKind::OutgoingArg,
+    StackSlotKind::EmergencySlot,
+];
+
+// Trap codes other than `User(n)`, which is written as `n + TRAP_CODES.len()`.
+static TRAP_CODES: [TrapCode; 9] = [
+    TrapCode::StackOverflow,
+    TrapCode::HeapOutOfBounds,
+    TrapCode::OutOfBounds,
+    TrapCode::IndirectCallToNull,
+    TrapCode::BadSignature,
+    TrapCode::IntegerOverflow,
+    TrapCode::IntegerDivisionByZero,
+    TrapCode::BadConversionToInteger,
+    TrapCode::Interrupt,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cursor::{Cursor, FuncCursor};
+    use ir::{Function, FunctionName, InstBuilder, Signature, AbiParam, StructArg, ValueLoc};
+    use ir::{StackSlotData, JumpTableData, ExtFuncData};
+    use ir::immediates::{Ieee32, Ieee64};
+    use isa::Encoding;
+
+    fn function() -> Function {
+        let mut sig = Signature::new(CallConv::SpiderWASM);
+        sig.params.push(AbiParam::new(types::I32).sext());
+        sig.returns.push(AbiParam::new(types::F64));
+        let mut func = Function::with_name_signature(FunctionName::new("sample"), sig.clone());
+        let ss = func.create_stack_slot(StackSlotData::new(StackSlotKind::Local, 8));
+        let mut callee = sig;
+        callee.fixed_params = Some(1);
+        let callee = func.import_signature(callee);
+        let mut sa = StructArg::new(12, 4);
+        sa.float_words = 2;
+        let mut by_value = Signature::new(CallConv::Native);
+        by_value.params.push(AbiParam::special(types::I64, ArgumentPurpose::StructArgument(sa)));
+        by_value.params.push(AbiParam::special(types::I32, ArgumentPurpose::StructPart(sa)));
+        func.import_signature(by_value);
+        let fn0 = func.import_function(ExtFuncData {
+            name: FunctionName::new("callee"),
+            signature: callee,
+        });
+
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let mut jt = JumpTableData::new();
+        jt.set_entry(1, ebb1);
+        let jt = func.create_jump_table(jt);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let arg = pos.func.dfg.append_ebb_param(ebb0, types::I32);
+            let x = pos.ins().iconst(types::I32, -5);
+            let y = pos.ins().icmp(IntCC::SignedLessThan, arg, x);
+            pos.ins().brnz(y, ebb1, &[]);
+            pos.ins().br_table(arg, jt);
+            pos.ins().stack_store(x, ss, 4);
+            pos.ins().trap(TrapCode::User(17));
+
+            pos.insert_ebb(ebb1);
+            let f = pos.ins().f64const(Ieee64::with_float(1.5));
+            let g = pos.ins().f32const(Ieee32::with_float(-0.5));
+            let g = pos.ins().fpromote(types::F64, g);
+            let c = pos.ins().fcmp(FloatCC::UnorderedOrLessThan, f, g);
+            let n = pos.ins().null(types::R64);
+            pos.ins().safepoint(&[n]);
+            let r = pos.ins().call(fn0, &[arg]);
+            let r = pos.func.dfg.inst_results(r)[0];
+            pos.ins().invoke(fn0, ebb1, &[arg]);
+            pos.ins().brz(c, ebb0, &[]);
+            pos.ins().return_(&[r]);
+        }
+        func
+    }
+
+    fn roundtrip(func: &Function, isa_data: bool) -> Function {
+        let bytes = write_functions(&[func.clone()], isa_data);
+        assert!(is_binary_format(&bytes));
+        let mut funcs = read_functions(&bytes).unwrap();
+        assert_eq!(funcs.len(), 1);
+        let copy = funcs.pop().unwrap();
+        // Writing the function again must produce the same bytes.
+        assert_eq!(write_functions(&[copy.clone()], isa_data), bytes);
+        copy
+    }
+
+    #[test]
+    fn text_roundtrip() {
+        let func = function();
+        let copy = roundtrip(&func, false);
+        assert_eq!(copy.to_string(), func.to_string());
+        assert_eq!(copy.name, func.name);
+    }
+
+    #[test]
+    fn detached_values() {
+        let mut func = function();
+        let ebb0 = func.layout.entry_block().unwrap();
+        let inst = func.layout.first_inst(ebb0).unwrap();
+        let old = func.dfg.first_result(inst);
+        // The old result is left detached, and the new one gets a higher number.
+        let new = func.dfg.replace_result(old, types::I32);
+        let alias = func.dfg.make_value_alias(new);
+        func.dfg.change_to_alias(old, alias);
+
+        let copy = roundtrip(&func, false);
+        assert_eq!(copy.dfg.num_values(), func.dfg.num_values());
+        assert_eq!(copy.dfg.first_result(inst), new);
+        assert_eq!(copy.dfg.value_data(old), func.dfg.value_data(old));
+        assert_eq!(copy.dfg.resolve_aliases(old), new);
+        assert_eq!(copy.to_string(), func.to_string());
+    }
+
+    #[test]
+    fn isa_data() {
+        let mut func = function();
+        let ebb0 = func.layout.entry_block().unwrap();
+        let inst = func.layout.first_inst(ebb0).unwrap();
+        let value = func.dfg.first_result(inst);
+        func.encodings[inst] = Encoding::new(3, 0x1234);
+        func.locations[value] = ValueLoc::Reg(7);
+
+        let copy = roundtrip(&func, true);
+        assert_eq!(copy.encodings[inst], Encoding::new(3, 0x1234));
+        assert_eq!(copy.locations[value], ValueLoc::Reg(7));
+
+        let copy = roundtrip(&func, false);
+        assert!(copy.encodings.is_empty());
+        assert!(copy.locations.is_empty());
+    }
+
+    #[test]
+    fn bad_input() {
+        assert!(!is_binary_format(b"function %f() {}"));
+        let err = read_functions(b"function %f() {}").unwrap_err();
+        assert_eq!(err.to_string(), "offset 0: not a binary Cretonne IL file");
+
+        let mut bytes = write_functions(&[function()], false);
+        bytes[4] = 99;
+        let err = read_functions(&bytes).unwrap_err();
+        assert_eq!(err.to_string(), "offset 4: unsupported format version 99");
+
+        let mut bytes = write_functions(&[function()], false);
+        let len = bytes.len();
+        bytes.truncate(len - 1);
+        assert!(read_functions(&bytes).is_err());
+    }
+}
@@ -0,0 +1,795 @@
+//! Reading binary IL.
+//!
+//! The reader checks that entity references are in range and that the data flow graph and layout
+//! are consistent, so malformed input produces an error instead of a panic. Like the text format
+//! parser, it doesn't check types or other semantic properties. That is left to the verifier.
+
+use entity::EntityRef;
+use ir::{Function, FunctionName, Type, Signature, AbiParam, ArgumentLoc, ArgumentPurpose,
+         StructArg, ValueLoc, InstructionData, Opcode, GlobalVarData, HeapData, HeapBase,
+         HeapStyle, TrapCode, MemFlags, ExtFuncData, JumpTableData, StackSlotData, SourceLoc,
+         Inst, Ebb, Value, ValueList, ValueListPool};
+use ir::dfg::ValueData;
+use ir::immediates::{Imm64, Ieee32, Ieee64, Offset32, Uimm32};
+use ir::instructions::InstructionFormat;
+use isa::Encoding;
+use std::str;
+use super::{Error, Result, MAGIC, VERSION, LANE_TYPES, INTCC, FLOATCC, CALL_CONVS, PURPOSES,
+            EXTENSIONS, STACK_SLOT_KINDS, TRAP_CODES};
+
+/// Does `data` look like binary IL?
+///
+/// This only checks the magic number, so it can be used to distinguish binary and text IL.
+pub fn is_binary_format(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Read all the functions in `data`, which must be in the binary format.
+pub fn read_functions(data: &[u8]) -> Result<Vec<Function>> {
+    let mut r = Reader { data, pos: 0 };
+    if !is_binary_format(data) {
+        return r.err("not a binary Cretonne IL file");
+    }
+    r.pos = MAGIC.len();
+    let mut version = 0;
+    for i in 0..4 {
+        version |= u32::from(r.byte()?) << (8 * i);
+    }
+    if version != VERSION {
+        r.pos = MAGIC.len();
+        return r.err(format!("unsupported format version {}", version));
+    }
+    let count = r.len()?;
+    let mut funcs = Vec::with_capacity(count);
+    for _ in 0..count {
+        funcs.push(r.function()?);
+    }
+    if r.pos != data.len() {
+        return r.err("trailing data after functions");
+    }
+    Ok(funcs)
+}
+
+/// Number of entities in the function being read, for checking entity references in
+/// instructions.
+struct Counts {
+    ebbs: usize,
+    slots: usize,
+    gvs: usize,
+    heaps: usize,
+    sigs: usize,
+    funcs: usize,
+    jts: usize,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+/// Look up an index read from the input in one of the enum tables.
+fn lookup<T: Copy>(table: &[T], index: u64) -> Option<T> {
+    if index < table.len() as u64 {
+        Some(table[index as usize])
+    } else {
+        None
+    }
+}
+
+impl<'a> Reader<'a> {
+    fn err<T, S: Into<String>>(&self, message: S) -> Result<T> {
+        Err(Error {
+            offset: self.pos,
+            message: message.into(),
+        })
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        match self.data.get(self.pos) {
+            Some(&b) => {
+                self.pos += 1;
+                Ok(b)
+            }
+            None => self.err("unexpected end of data"),
+        }
+    }
+
+    /// Read an unsigned LEB128 number.
+    fn uint(&mut self) -> Result<u64> {
+        let mut x = 0;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift == 63 && b > 1 || shift > 63 {
+                return self.err("integer too large");
+            }
+            x |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(x);
+            }
+            shift += 7;
+        }
+    }
+
+    /// Read a zigzag encoded signed number.
+    fn sint(&mut self) -> Result<i64> {
+        let x = self.uint()?;
+        Ok((x >> 1) as i64 ^ -((x & 1) as i64))
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let x = self.uint()?;
+        if x > u64::from(u16::max_value()) {
+            return self.err("integer too large");
+        }
+        Ok(x as u16)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let x = self.uint()?;
+        if x > u64::from(u32::max_value()) {
+            return self.err("integer too large");
+        }
+        Ok(x as u32)
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        let x = self.sint()?;
+        if x < i64::from(i32::min_value()) || x > i64::from(i32::max_value()) {
+            return self.err("integer too large");
+        }
+        Ok(x as i32)
+    }
+
+    /// Read the length of a sequence whose elements take up at least one byte each.
+    fn len(&mut self) -> Result<usize> {
+        let x = self.uint()?;
+        if x > (self.data.len() - self.pos) as u64 {
+            return self.err("length exceeds the remaining data");
+        }
+        Ok(x as usize)
+    }
+
+    /// Read the size of an entity map for entities with `limit` primary entries.
+    fn map_size(&mut self, limit: usize) -> Result<usize> {
+        let x = self.uint()?;
+        if x > limit as u64 {
+            return self.err(format!("map size {} exceeds {}", x, limit));
+        }
+        Ok(x as usize)
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => self.err("invalid boolean"),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.len()?;
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Read an index into one of the enum tables.
+    fn table<T: Copy>(&mut self, table: &[T], what: &str) -> Result<T> {
+        let index = self.uint()?;
+        match lookup(table, index) {
+            Some(x) => Ok(x),
+            None => self.err(format!("invalid {} {}", what, index)),
+        }
+    }
+
+    /// Read an entity reference which must be less than `limit`.
+    fn entity<E: EntityRef>(&mut self, limit: usize, what: &str) -> Result<E> {
+        let index = self.uint()?;
+        if index >= limit as u64 {
+            return self.err(format!("invalid {} reference {}", what, index));
+        }
+        Ok(E::new(index as usize))
+    }
+
+    /// Read an optional entity reference which must be less than `limit`.
+    fn opt_entity<E: EntityRef>(&mut self, limit: usize, what: &str) -> Result<Option<E>> {
+        let index = self.uint()?;
+        if index > limit as u64 {
+            return self.err(format!("invalid {} reference {}", what, index - 1));
+        }
+        Ok(if index == 0 {
+            None
+        } else {
+            Some(E::new(index as usize - 1))
+        })
+    }
+
+    fn ty(&mut self) -> Result<Type> {
+        let b = self.byte()?;
+        let ty = lookup(&LANE_TYPES, u64::from(b & 0xf)).and_then(|lane| {
+            let lanes = b >> 4;
+            if lanes == 0 {
+                Some(lane)
+            } else {
+                lane.by(1 << lanes)
+            }
+        });
+        match ty {
+            Some(ty) => Ok(ty),
+            None => self.err(format!("invalid type {:#04x}", b)),
+        }
+    }
+
+    fn trap_code(&mut self) -> Result<TrapCode> {
+        let x = self.uint()?;
+        if let Some(code) = lookup(&TRAP_CODES, x) {
+            return Ok(code);
+        }
+        let n = x - TRAP_CODES.len() as u64;
+        if n > u64::from(u16::max_value()) {
+            return self.err(format!("invalid trap code {}", x));
+        }
+        Ok(TrapCode::User(n as u16))
+    }
+
+    fn purpose(&mut self) -> Result<ArgumentPurpose> {
+        let x = self.uint()?;
+        if let Some(purpose) = lookup(&PURPOSES, x) {
+            return Ok(purpose);
+        }
+        let n = x - PURPOSES.len() as u64;
+        if n > 1 {
+            return self.err(format!("invalid argument purpose {}", x));
+        }
+        let mut sa = StructArg::new(self.u32()?, 1);
+        sa.align = self.u32()?;
+        if !sa.align.is_power_of_two() {
+            return self.err(format!("invalid struct alignment {}", sa.align));
+        }
+        sa.float_words = self.byte()?;
+        Ok(if n == 0 {
+            ArgumentPurpose::StructArgument(sa)
+        } else {
+            ArgumentPurpose::StructPart(sa)
+        })
+    }
+
+    fn mem_flags(&mut self) -> Result<MemFlags> {
+        let b = self.byte()?;
+        if b > 3 {
+            return self.err(format!("invalid memory flags {:#04x}", b));
+        }
+        let mut flags = MemFlags::new();
+        if b & 1 != 0 {
+            flags.set_notrap();
+        }
+        if b & 2 != 0 {
+            flags.set_aligned();
+        }
+        Ok(flags)
+    }
+
+    fn signature(&mut self) -> Result<Signature> {
+        let mut sig = Signature::new(self.table(&CALL_CONVS, "calling convention")?);
+        for _ in 0..self.len()? {
+            sig.params.push(self.abi_param()?);
+        }
+        for _ in 0..self.len()? {
+            sig.returns.push(self.abi_param()?);
+        }
+        sig.fixed_params = match self.uint()? {
+            0 => None,
+            n if n <= sig.params.len() as u64 + 1 => Some((n - 1) as usize),
+            _ => return self.err("too many fixed parameters"),
+        };
+        sig.argument_bytes = match self.uint()? {
+            0 => None,
+            n if n <= u64::from(u32::max_value()) + 1 => Some((n - 1) as u32),
+            _ => return self.err("integer too large"),
+        };
+        Ok(sig)
+    }
+
+    fn abi_param(&mut self) -> Result<AbiParam> {
+        let mut param = AbiParam::new(self.ty()?);
+        param.purpose = self.purpose()?;
+        param.extension = self.table(&EXTENSIONS, "argument extension")?;
+        param.location = match self.byte()? {
+            0 => ArgumentLoc::Unassigned,
+            1 => ArgumentLoc::Reg(self.u16()?),
+            2 => ArgumentLoc::Stack(self.i32()?),
+            _ => return self.err("invalid argument location"),
+        };
+        Ok(param)
+    }
+
+    fn global_var(&mut self, num_gvs: usize) -> Result<GlobalVarData> {
+        Ok(match self.byte()? {
+            0 => GlobalVarData::VmCtx { offset: Offset32::new(self.i32()?) },
+            1 => GlobalVarData::Deref {
+                base: self.entity(num_gvs, "global variable")?,
+                offset: Offset32::new(self.i32()?),
+            },
+            2 => GlobalVarData::Sym { name: FunctionName::new(self.bytes()?) },
+            _ => return self.err("invalid global variable"),
+        })
+    }
+
+    fn heap(&mut self, num_gvs: usize) -> Result<HeapData> {
+        let base = match self.byte()? {
+            0 => HeapBase::ReservedReg,
+            1 => HeapBase::GlobalVar(self.entity(num_gvs, "global variable")?),
+            _ => return self.err("invalid heap base"),
+        };
+        let min_size = Imm64::new(self.sint()?);
+        let guard_size = Imm64::new(self.sint()?);
+        let style = match self.byte()? {
+            0 => HeapStyle::Dynamic { bound_gv: self.entity(num_gvs, "global variable")? },
+            1 => HeapStyle::Static { bound: Imm64::new(self.sint()?) },
+            _ => return self.err("invalid heap style"),
+        };
+        Ok(HeapData {
+            base,
+            min_size,
+            guard_size,
+            style,
+        })
+    }
+
+    fn function(&mut self) -> Result<Function> {
+        let name = FunctionName::new(self.bytes()?);
+        let sig = self.signature()?;
+        let mut func = Function::with_name_signature(name, sig);
+        let num_ebbs = self.len()?;
+        let num_insts = self.len()?;
+        let num_values = self.len()?;
+
+        for _ in 0..self.len()? {
+            let kind = self.table(&STACK_SLOT_KINDS, "stack slot kind")?;
+            let mut data = StackSlotData::new(kind, self.u32()?);
+            data.offset = self.i32()?;
+            func.stack_slots.push(data);
+        }
+        func.stack_slots.frame_size = match self.uint()? {
+            0 => None,
+            n if n <= u64::from(u32::max_value()) + 1 => Some((n - 1) as u32),
+            _ => return self.err("integer too large"),
+        };
+        let num_slots = func.stack_slots.keys().count();
+
+        let num_gvs = self.len()?;
+        for _ in 0..num_gvs {
+            let data = self.global_var(num_gvs)?;
+            func.create_global_var(data);
+        }
+        for _ in 0..self.len()? {
+            let data = self.heap(num_gvs)?;
+            func.create_heap(data);
+        }
+        let num_sigs = self.len()?;
+        for _ in 0..num_sigs {
+            let sig = self.signature()?;
+            func.import_signature(sig);
+        }
+        let num_funcs = self.len()?;
+        for _ in 0..num_funcs {
+            let name = FunctionName::new(self.bytes()?);
+            let signature = self.entity(num_sigs, "signature")?;
+            func.import_function(ExtFuncData { name, signature });
+        }
+        let num_jts = self.len()?;
+        for _ in 0..num_jts {
+            let len = self.len()?;
+            let mut data = JumpTableData::with_capacity(len);
+            for i in 0..len {
+                if let Some(ebb) = self.opt_entity(num_ebbs, "EBB")? {
+                    data.set_entry(i, ebb);
+                }
+            }
+            func.create_jump_table(data);
+        }
+
+        let n = Counts {
+            ebbs: num_ebbs,
+            slots: num_slots,
+            gvs: num_gvs,
+            heaps: func.heaps.len(),
+            sigs: num_sigs,
+            funcs: num_funcs,
+            jts: num_jts,
+        };
+        for _ in 0..num_ebbs {
+            func.dfg.make_ebb();
+        }
+        for _ in 0..num_values {
+            let data = match self.byte()? {
+                0 => ValueData::Inst {
+                    ty: self.ty()?,
+                    inst: self.entity(num_insts, "instruction")?,
+                    num: self.u16()?,
+                },
+                1 => ValueData::Param {
+                    ty: self.ty()?,
+                    ebb: self.entity(num_ebbs, "EBB")?,
+                    num: self.u16()?,
+                },
+                2 => ValueData::Alias {
+                    ty: self.ty()?,
+                    original: self.entity(num_values, "value")?,
+                },
+                _ => return self.err("invalid value definition"),
+            };
+            func.dfg.make_value_with_data(data);
+        }
+        self.check_aliases(&func)?;
+
+        let mut opcodes = Vec::new();
+        for _ in 0..self.len()? {
+            let name = self.bytes()?;
+            match str::from_utf8(name).ok().and_then(|s| s.parse::<Opcode>().ok()) {
+                Some(opcode) => opcodes.push(opcode),
+                None => return self.err("unknown opcode"),
+            }
+        }
+
+        let mut attached = vec![false; num_values];
+        for i in 0..num_insts {
+            let opcode = self.table(&opcodes, "opcode index")?;
+            let mut args = Vec::new();
+            for _ in 0..self.len()? {
+                args.push(self.entity(num_values, "value")?);
+            }
+            let data = self.inst_data(opcode, &args, &mut func.dfg.value_lists, &n)?;
+            let inst = func.dfg.make_inst(data);
+            debug_assert_eq!(inst, Inst::new(i));
+            let num_results = self.len()?;
+            let expected = opcode.constraints().fixed_results() +
+                func.dfg.non_tail_call_signature(inst).map_or(0, |sig| {
+                    func.dfg.signatures[sig].returns.len()
+                });
+            if num_results != expected {
+                return self.err(format!("{} should have {} results", inst, expected));
+            }
+            for num in 0..num_results {
+                let res = self.entity(num_values, "value")?;
+                match func.dfg.value_data(res) {
+                    ValueData::Inst { inst: i, num: n, .. } if i == inst &&
+                                                              n as usize == num => {}
+                    _ => return self.err(format!("{} is not result {} of {}", res, num, inst)),
+                }
+                self.attach(&mut attached, res)?;
+                func.dfg.attach_result(inst, res);
+            }
+        }
+        for ebb in (0..num_ebbs).map(Ebb::new) {
+            for num in 0..self.len()? {
+                let param = self.entity(num_values, "value")?;
+                match func.dfg.value_data(param) {
+                    ValueData::Param { ebb: e, num: n, .. } if e == ebb && n as usize == num => {}
+                    _ => return self.err(format!("{} is not parameter {} of {}", param, num, ebb)),
+                }
+                self.attach(&mut attached, param)?;
+                func.dfg.attach_ebb_param(ebb, param);
+            }
+        }
+
+        let mut inserted = vec![false; num_insts];
+        for _ in 0..self.len()? {
+            let ebb = self.entity(num_ebbs, "EBB")?;
+            if func.layout.is_ebb_inserted(ebb) {
+                return self.err(format!("{} appears twice in the layout", ebb));
+            }
+            func.layout.append_ebb(ebb);
+            for _ in 0..self.len()? {
+                let inst: Inst = self.entity(num_insts, "instruction")?;
+                if inserted[inst.index()] {
+                    return self.err(format!("{} appears twice in the layout", inst));
+                }
+                inserted[inst.index()] = true;
+                func.layout.append_inst(inst, ebb);
+            }
+        }
+
+        func.srclocs.resize(self.map_size(num_insts)?);
+        for _ in 0..self.len()? {
+            let inst: Inst = self.entity(num_insts, "instruction")?;
+            func.srclocs[inst] = SourceLoc::new(self.u32()?);
+        }
+
+        if self.bool()? {
+            func.encodings.resize(self.map_size(num_insts)?);
+            for _ in 0..self.len()? {
+                let inst: Inst = self.entity(num_insts, "instruction")?;
+                let recipe = self.u16()?;
+                let bits = self.u16()?;
+                func.encodings[inst] = Encoding::new(recipe, bits);
+            }
+            func.locations.resize(self.map_size(num_values)?);
+            for _ in 0..self.len()? {
+                let value: Value = self.entity(num_values, "value")?;
+                func.locations[value] = match self.byte()? {
+                    0 => ValueLoc::Reg(self.u16()?),
+                    1 => ValueLoc::Stack(self.entity(num_slots, "stack slot")?),
+                    _ => return self.err("invalid value location"),
+                };
+            }
+        }
+
+        Ok(func)
+    }
+
+    /// Check that the value aliases in `func` don't form cycles.
+    fn check_aliases(&self, func: &Function) -> Result<()> {
+        // 0: not visited, 1: on the current alias chain, 2: known to resolve.
+        let mut state = vec![0u8; func.dfg.num_values()];
+        for start in (0..state.len()).map(Value::new) {
+            let mut v = start;
+            loop {
+                match state[v.index()] {
+                    1 => return self.err(format!("{} is part of an alias cycle", v)),
+                    2 => break,
+                    _ => state[v.index()] = 1,
+                }
+                match func.dfg.value_data(v) {
+                    ValueData::Alias { original, .. } => v = original,
+                    _ => break,
+                }
+            }
+            let mut v = start;
+            while state[v.index()] == 1 {
+                state[v.index()] = 2;
+                if let ValueData::Alias { original, .. } = func.dfg.value_data(v) {
+                    v = original;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Mark `v` as attached to an instruction or EBB.
+    fn attach(&self, attached: &mut [bool], v: Value) -> Result<()> {
+        if attached[v.index()] {
+            return self.err(format!("{} is attached twice", v));
+        }
+        attached[v.index()] = true;
+        Ok(())
+    }
+
+    /// Read the immediate operands of an instruction and build its `InstructionData`.
+    fn inst_data(
+        &mut self,
+        opcode: Opcode,
+        args: &[Value],
+        pool: &mut ValueListPool,
+        n: &Counts,
+    ) -> Result<InstructionData> {
+        // Check the number of value operands. Formats with a value list have a minimum number.
+        let (fixed, variable) = match opcode.format() {
+            InstructionFormat::Unary |
+            InstructionFormat::BinaryImm |
+            InstructionFormat::ExtractLane |
+            InstructionFormat::IntCompareImm |
+            InstructionFormat::IntCond |
+            InstructionFormat::FloatCond |
+            InstructionFormat::BranchTable |
+            InstructionFormat::StackStore |
+            InstructionFormat::HeapAddr |
+            InstructionFormat::Load |
+            InstructionFormat::RegMove |
+            InstructionFormat::RegSpill |
+            InstructionFormat::RegFill |
+            InstructionFormat::CondTrap => (1, false),
+            InstructionFormat::Binary |
+            InstructionFormat::InsertLane |
+            InstructionFormat::IntCompare |
+            InstructionFormat::FloatCompare |
+            InstructionFormat::Store => (2, false),
+            InstructionFormat::Ternary => (3, false),
+            InstructionFormat::MultiAry |
+            InstructionFormat::Jump |
+            InstructionFormat::Call |
+            InstructionFormat::Invoke => (0, true),
+            InstructionFormat::Branch |
+            InstructionFormat::BranchInt |
+            InstructionFormat::BranchFloat |
+            InstructionFormat::IndirectCall => (1, true),
+            InstructionFormat::BranchIcmp => (2, true),
+            _ => (0, false),
+        };
+        if args.len() < fixed || (!variable && args.len() > fixed) {
+            return self.err(format!("wrong number of arguments for {}", opcode));
+        }
+        let mut list = ValueList::new();
+        if variable {
+            list.extend(args.iter().cloned(), pool);
+        }
+
+        Ok(match opcode.format() {
+            InstructionFormat::NullAry => InstructionData::NullAry { opcode },
+            InstructionFormat::Unary => InstructionData::Unary {
+                opcode,
+                arg: args[0],
+            },
+            InstructionFormat::UnaryImm => InstructionData::UnaryImm {
+                opcode,
+                imm: Imm64::new(self.sint()?),
+            },
+            InstructionFormat::UnaryIeee32 => InstructionData::UnaryIeee32 {
+                opcode,
+                imm: Ieee32::with_bits(self.u32()?),
+            },
+            InstructionFormat::UnaryIeee64 => InstructionData::UnaryIeee64 {
+                opcode,
+                imm: Ieee64::with_bits(self.uint()?),
+            },
+            InstructionFormat::UnaryBool => InstructionData::UnaryBool {
+                opcode,
+                imm: self.bool()?,
+            },
+            InstructionFormat::UnaryGlobalVar => InstructionData::UnaryGlobalVar {
+                opcode,
+                global_var: self.entity(n.gvs, "global variable")?,
+            },
+            InstructionFormat::Binary => InstructionData::Binary {
+                opcode,
+                args: [args[0], args[1]],
+            },
+            InstructionFormat::BinaryImm => InstructionData::BinaryImm {
+                opcode,
+                arg: args[0],
+                imm: Imm64::new(self.sint()?),
+            },
+            InstructionFormat::Ternary => InstructionData::Ternary {
+                opcode,
+                args: [args[0], args[1], args[2]],
+            },
+            InstructionFormat::MultiAry => InstructionData::MultiAry { opcode, args: list },
+            InstructionFormat::InsertLane => InstructionData::InsertLane {
+                opcode,
+                lane: self.byte()?,
+                args: [args[0], args[1]],
+            },
+            InstructionFormat::ExtractLane => InstructionData::ExtractLane {
+                opcode,
+                lane: self.byte()?,
+                arg: args[0],
+            },
+            InstructionFormat::IntCompare => InstructionData::IntCompare {
+                opcode,
+                cond: self.table(&INTCC, "condition code")?,
+                args: [args[0], args[1]],
+            },
+            InstructionFormat::IntCompareImm => InstructionData::IntCompareImm {
+                opcode,
+                cond: self.table(&INTCC, "condition code")?,
+                arg: args[0],
+                imm: Imm64::new(self.sint()?),
+            },
+            InstructionFormat::IntCond => InstructionData::IntCond {
+                opcode,
+                cond: self.table(&INTCC, "condition code")?,
+                arg: args[0],
+            },
+            InstructionFormat::FloatCompare => InstructionData::FloatCompare {
+                opcode,
+                cond: self.table(&FLOATCC, "condition code")?,
+                args: [args[0], args[1]],
+            },
+            InstructionFormat::FloatCond => InstructionData::FloatCond {
+                opcode,
+                cond: self.table(&FLOATCC, "condition code")?,
+                arg: args[0],
+            },
+            InstructionFormat::Jump => InstructionData::Jump {
+                opcode,
+                destination: self.entity(n.ebbs, "EBB")?,
+                args: list,
+            },
+            InstructionFormat::Branch => InstructionData::Branch {
+                opcode,
+                destination: self.entity(n.ebbs, "EBB")?,
+                args: list,
+            },
+            InstructionFormat::BranchInt => InstructionData::BranchInt {
+                opcode,
+                cond: self.table(&INTCC, "condition code")?,
+                destination: self.entity(n.ebbs, "EBB")?,
+                args: list,
+            },
+            InstructionFormat::BranchIcmp => InstructionData::BranchIcmp {
+                opcode,
+                cond: self.table(&INTCC, "condition code")?,
+                destination: self.entity(n.ebbs, "EBB")?,
+                args: list,
+            },
+            InstructionFormat::BranchFloat => InstructionData::BranchFloat {
+                opcode,
+                cond: self.table(&FLOATCC, "condition code")?,
+                destination: self.entity(n.ebbs, "EBB")?,
+                args: list,
+            },
+            InstructionFormat::BranchTable => InstructionData::BranchTable {
+                opcode,
+                arg: args[0],
+                table: self.entity(n.jts, "jump table")?,
+            },
+            InstructionFormat::Call => InstructionData::Call {
+                opcode,
+                func_ref: self.entity(n.funcs, "function")?,
+                args: list,
+            },
+            InstructionFormat::Invoke => InstructionData::Invoke {
+                opcode,
+                func_ref: self.entity(n.funcs, "function")?,
+                destination: self.entity(n.ebbs, "EBB")?,
+                args: list,
+            },
+            InstructionFormat::IndirectCall => InstructionData::IndirectCall {
+                opcode,
+                sig_ref: self.entity(n.sigs, "signature")?,
+                args: list,
+            },
+            InstructionFormat::FuncAddr => InstructionData::FuncAddr {
+                opcode,
+                func_ref: self.entity(n.funcs, "function")?,
+            },
+            InstructionFormat::StackLoad => InstructionData::StackLoad {
+                opcode,
+                stack_slot: self.entity(n.slots, "stack slot")?,
+                offset: Offset32::new(self.i32()?),
+            },
+            InstructionFormat::StackStore => InstructionData::StackStore {
+                opcode,
+                arg: args[0],
+                stack_slot: self.entity(n.slots, "stack slot")?,
+                offset: Offset32::new(self.i32()?),
+            },
+            InstructionFormat::HeapAddr => InstructionData::HeapAddr {
+                opcode,
+                heap: self.entity(n.heaps, "heap")?,
+                arg: args[0],
+                imm: Uimm32::from(self.u32()?),
+            },
+            InstructionFormat::Load => InstructionData::Load {
+                opcode,
+                flags: self.mem_flags()?,
+                arg: args[0],
+                offset: Offset32::new(self.i32()?),
+            },
+            InstructionFormat::Store => InstructionData::Store {
+                opcode,
+                flags: self.mem_flags()?,
+                args: [args[0], args[1]],
+                offset: Offset32::new(self.i32()?),
+            },
+            InstructionFormat::RegMove => InstructionData::RegMove {
+                opcode,
+                arg: args[0],
+                src: self.u16()?,
+                dst: self.u16()?,
+            },
+            InstructionFormat::RegSpill => InstructionData::RegSpill {
+                opcode,
+                arg: args[0],
+                src: self.u16()?,
+                dst: self.entity(n.slots, "stack slot")?,
+            },
+            InstructionFormat::RegFill => InstructionData::RegFill {
+                opcode,
+                arg: args[0],
+                src: self.entity(n.slots, "stack slot")?,
+                dst: self.u16()?,
+            },
+            InstructionFormat::Trap => InstructionData::Trap {
+                opcode,
+                code: self.trap_code()?,
+            },
+            InstructionFormat::CondTrap => InstructionData::CondTrap {
+                opcode,
+                arg: args[0],
+                code: self.trap_code()?,
+            },
+        })
+    }
+}
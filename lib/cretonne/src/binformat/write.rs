@@ -0,0 +1,437 @@
+//! Writing binary IL.
+
+use entity::EntityRef;
+use ir::{Function, Type, Signature, AbiParam, ArgumentLoc, ArgumentPurpose, StructArg,
+         ValueLoc, InstructionData,
+         GlobalVarData, HeapData, HeapBase, HeapStyle, TrapCode, MemFlags, Inst, Ebb,
+         Value};
+use ir::dfg::ValueData;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use super::{MAGIC, VERSION, LANE_TYPES, INTCC, FLOATCC, CALL_CONVS, PURPOSES, EXTENSIONS,
+            STACK_SLOT_KINDS, TRAP_CODES};
+
+/// Write `funcs` in the binary format.
+///
+/// If `isa_data` is set, the instruction encodings and value locations of the functions are
+/// included.
+pub fn write_functions(funcs: &[Function], isa_data: bool) -> Vec<u8> {
+    let mut w = Writer { buf: Vec::new() };
+    w.buf.extend_from_slice(&MAGIC);
+    for i in 0..4 {
+        w.byte((VERSION >> (8 * i)) as u8);
+    }
+    w.uint(funcs.len() as u64);
+    for func in funcs {
+        w.function(func, isa_data);
+    }
+    w.buf
+}
+
+struct Writer {
+    buf: Vec<u8>,
+}
+
+/// Get the index of `item` in one of the enum tables.
+fn index_of<T: PartialEq + Debug>(table: &[T], item: &T) -> u64 {
+    match table.iter().position(|x| x == item) {
+        Some(i) => i as u64,
+        None => panic!("{:?} missing from binary format table", item),
+    }
+}
+
+impl Writer {
+    fn byte(&mut self, b: u8) {
+        self.buf.push(b);
+    }
+
+    /// Write an unsigned LEB128 number.
+    fn uint(&mut self, mut x: u64) {
+        loop {
+            let b = (x & 0x7f) as u8;
+            x >>= 7;
+            if x == 0 {
+                self.byte(b);
+                return;
+            }
+            self.byte(b | 0x80);
+        }
+    }
+
+    /// Write a zigzag encoded signed number.
+    fn sint(&mut self, x: i64) {
+        self.uint(((x << 1) ^ (x >> 63)) as u64);
+    }
+
+    fn bool(&mut self, b: bool) {
+        self.byte(b as u8);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.uint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn entity<E: EntityRef>(&mut self, e: E) {
+        self.uint(e.index() as u64);
+    }
+
+    fn opt_entity<E: EntityRef>(&mut self, e: Option<E>) {
+        match e {
+            Some(e) => self.uint(e.index() as u64 + 1),
+            None => self.uint(0),
+        }
+    }
+
+    fn ty(&mut self, ty: Type) {
+        let lane = index_of(&LANE_TYPES, &ty.lane_type()) as u8;
+        self.byte(lane | ty.log2_lane_count() << 4);
+    }
+
+    fn trap_code(&mut self, code: TrapCode) {
+        match code {
+            TrapCode::User(n) => self.uint(TRAP_CODES.len() as u64 + u64::from(n)),
+            _ => self.uint(index_of(&TRAP_CODES, &code)),
+        }
+    }
+
+    // Struct purposes are written after the indexes of `PURPOSES`.
+    fn purpose(&mut self, purpose: ArgumentPurpose) {
+        match purpose {
+            ArgumentPurpose::StructArgument(sa) => {
+                self.uint(PURPOSES.len() as u64);
+                self.struct_arg(sa);
+            }
+            ArgumentPurpose::StructPart(sa) => {
+                self.uint(PURPOSES.len() as u64 + 1);
+                self.struct_arg(sa);
+            }
+            _ => self.uint(index_of(&PURPOSES, &purpose)),
+        }
+    }
+
+    fn struct_arg(&mut self, sa: StructArg) {
+        self.uint(u64::from(sa.size));
+        self.uint(u64::from(sa.align));
+        self.byte(sa.float_words);
+    }
+
+    fn mem_flags(&mut self, flags: MemFlags) {
+        self.byte(flags.notrap() as u8 | (flags.aligned() as u8) << 1);
+    }
+
+    fn signature(&mut self, sig: &Signature) {
+        self.uint(index_of(&CALL_CONVS, &sig.call_conv));
+        for params in &[&sig.params, &sig.returns] {
+            self.uint(params.len() as u64);
+            for param in params.iter() {
+                self.abi_param(param);
+            }
+        }
+        match sig.fixed_params {
+            Some(n) => self.uint(n as u64 + 1),
+            None => self.uint(0),
+        }
+        match sig.argument_bytes {
+            Some(n) => self.uint(u64::from(n) + 1),
+            None => self.uint(0),
+        }
+    }
+
+    fn abi_param(&mut self, param: &AbiParam) {
+        self.ty(param.value_type);
+        self.purpose(param.purpose);
+        self.uint(index_of(&EXTENSIONS, &param.extension));
+        match param.location {
+            ArgumentLoc::Unassigned => self.byte(0),
+            ArgumentLoc::Reg(ru) => {
+                self.byte(1);
+                self.uint(u64::from(ru));
+            }
+            ArgumentLoc::Stack(offset) => {
+                self.byte(2);
+                self.sint(i64::from(offset));
+            }
+        }
+    }
+
+    fn global_var(&mut self, data: &GlobalVarData) {
+        match *data {
+            GlobalVarData::VmCtx { offset } => {
+                self.byte(0);
+                self.sint(offset.into());
+            }
+            GlobalVarData::Deref { base, offset } => {
+                self.byte(1);
+                self.entity(base);
+                self.sint(offset.into());
+            }
+            GlobalVarData::Sym { ref name } => {
+                self.byte(2);
+                self.bytes(name.as_ref());
+            }
+        }
+    }
+
+    fn heap(&mut self, data: &HeapData) {
+        match data.base {
+            HeapBase::ReservedReg => self.byte(0),
+            HeapBase::GlobalVar(gv) => {
+                self.byte(1);
+                self.entity(gv);
+            }
+        }
+        self.sint(data.min_size.into());
+        self.sint(data.guard_size.into());
+        match data.style {
+            HeapStyle::Dynamic { bound_gv } => {
+                self.byte(0);
+                self.entity(bound_gv);
+            }
+            HeapStyle::Static { bound } => {
+                self.byte(1);
+                self.sint(bound.into());
+            }
+        }
+    }
+
+    fn function(&mut self, func: &Function, isa_data: bool) {
+        let dfg = &func.dfg;
+        self.bytes(func.name.as_ref());
+        self.signature(&func.signature);
+        self.uint(dfg.num_ebbs() as u64);
+        self.uint(dfg.num_insts() as u64);
+        self.uint(dfg.num_values() as u64);
+
+        let slots: Vec<_> = func.stack_slots.keys().collect();
+        self.uint(slots.len() as u64);
+        for ss in slots {
+            let data = &func.stack_slots[ss];
+            self.uint(index_of(&STACK_SLOT_KINDS, &data.kind));
+            self.uint(u64::from(data.size));
+            self.sint(i64::from(data.offset));
+        }
+        match func.stack_slots.frame_size {
+            Some(n) => self.uint(u64::from(n) + 1),
+            None => self.uint(0),
+        }
+
+        self.uint(func.global_vars.len() as u64);
+        for gv in func.global_vars.keys() {
+            self.global_var(&func.global_vars[gv]);
+        }
+        self.uint(func.heaps.len() as u64);
+        for heap in func.heaps.keys() {
+            self.heap(&func.heaps[heap]);
+        }
+        self.uint(dfg.signatures.len() as u64);
+        for sig in dfg.signatures.keys() {
+            self.signature(&dfg.signatures[sig]);
+        }
+        self.uint(dfg.ext_funcs.len() as u64);
+        for fref in dfg.ext_funcs.keys() {
+            let ext = &dfg.ext_funcs[fref];
+            self.bytes(ext.name.as_ref());
+            self.entity(ext.signature);
+        }
+        self.uint(func.jump_tables.len() as u64);
+        for jt in func.jump_tables.keys() {
+            let jt = &func.jump_tables[jt];
+            // Trailing holes can't be represented in a `JumpTableData`, so they are dropped.
+            let len = jt.entries().last().map_or(0, |(i, _)| i + 1);
+            self.uint(len as u64);
+            for i in 0..len {
+                self.opt_entity(jt.get_entry(i));
+            }
+        }
+
+        for v in (0..dfg.num_values()).map(Value::new) {
+            match dfg.value_data(v) {
+                ValueData::Inst { ty, num, inst } => {
+                    self.byte(0);
+                    self.ty(ty);
+                    self.entity(inst);
+                    self.uint(u64::from(num));
+                }
+                ValueData::Param { ty, num, ebb } => {
+                    self.byte(1);
+                    self.ty(ty);
+                    self.entity(ebb);
+                    self.uint(u64::from(num));
+                }
+                ValueData::Alias { ty, original } => {
+                    self.byte(2);
+                    self.ty(ty);
+                    self.entity(original);
+                }
+            }
+        }
+
+        // Number the opcodes in order of appearance.
+        let mut opcodes = HashMap::new();
+        let mut opcode_names = Vec::new();
+        for inst in (0..dfg.num_insts()).map(Inst::new) {
+            let opcode = dfg[inst].opcode();
+            if !opcodes.contains_key(&opcode) {
+                opcodes.insert(opcode, opcode_names.len());
+                opcode_names.push(opcode);
+            }
+        }
+        self.uint(opcode_names.len() as u64);
+        for opcode in &opcode_names {
+            self.bytes(opcode.to_string().as_bytes());
+        }
+
+        for inst in (0..dfg.num_insts()).map(Inst::new) {
+            let data = &dfg[inst];
+            self.uint(opcodes[&data.opcode()] as u64);
+            let args = data.arguments(&dfg.value_lists);
+            self.uint(args.len() as u64);
+            for &arg in args {
+                self.entity(arg);
+            }
+            self.inst_immediates(data);
+            let results = dfg.inst_results(inst);
+            self.uint(results.len() as u64);
+            for &res in results {
+                self.entity(res);
+            }
+        }
+        for ebb in (0..dfg.num_ebbs()).map(Ebb::new) {
+            let params = dfg.ebb_params(ebb);
+            self.uint(params.len() as u64);
+            for &param in params {
+                self.entity(param);
+            }
+        }
+
+        self.uint(func.layout.ebbs().count() as u64);
+        for ebb in func.layout.ebbs() {
+            self.entity(ebb);
+            self.uint(func.layout.ebb_insts(ebb).count() as u64);
+            for inst in func.layout.ebb_insts(ebb) {
+                self.entity(inst);
+            }
+        }
+
+        // The size of the maps is significant to the text format, so it is preserved too.
+        self.uint(func.srclocs.keys().count() as u64);
+        let srclocs: Vec<_> = func.srclocs
+            .keys()
+            .filter(|&inst| !func.srclocs[inst].is_default())
+            .collect();
+        self.uint(srclocs.len() as u64);
+        for inst in srclocs {
+            self.entity(inst);
+            self.uint(u64::from(func.srclocs[inst].bits()));
+        }
+
+        self.bool(isa_data);
+        if isa_data {
+            self.uint(func.encodings.keys().count() as u64);
+            let encodings: Vec<_> = func.encodings
+                .keys()
+                .filter(|&inst| func.encodings[inst].is_legal())
+                .collect();
+            self.uint(encodings.len() as u64);
+            for inst in encodings {
+                let enc = func.encodings[inst];
+                self.entity(inst);
+                self.uint(enc.recipe() as u64);
+                self.uint(u64::from(enc.bits()));
+            }
+
+            self.uint(func.locations.keys().count() as u64);
+            let locations: Vec<_> = func.locations
+                .keys()
+                .filter(|&v| func.locations[v].is_assigned())
+                .collect();
+            self.uint(locations.len() as u64);
+            for v in locations {
+                self.entity(v);
+                match func.locations[v] {
+                    ValueLoc::Reg(ru) => {
+                        self.byte(0);
+                        self.uint(u64::from(ru));
+                    }
+                    ValueLoc::Stack(ss) => {
+                        self.byte(1);
+                        self.entity(ss);
+                    }
+                    ValueLoc::Unassigned => unreachable!(),
+                }
+            }
+        }
+    }
+
+    /// Write the non-value operands of an instruction.
+    fn inst_immediates(&mut self, data: &InstructionData) {
+        use ir::instructions::InstructionData::*;
+        match *data {
+            NullAry { .. } | Unary { .. } | Binary { .. } | Ternary { .. } | MultiAry { .. } => {}
+            UnaryImm { imm, .. } |
+            BinaryImm { imm, .. } => self.sint(imm.into()),
+            UnaryIeee32 { imm, .. } => self.uint(u64::from(imm.bits())),
+            UnaryIeee64 { imm, .. } => self.uint(imm.bits()),
+            UnaryBool { imm, .. } => self.bool(imm),
+            UnaryGlobalVar { global_var, .. } => self.entity(global_var),
+            InsertLane { lane, .. } |
+            ExtractLane { lane, .. } => self.byte(lane),
+            IntCompare { cond, .. } |
+            IntCond { cond, .. } => self.uint(index_of(&INTCC, &cond)),
+            IntCompareImm { cond, imm, .. } => {
+                self.uint(index_of(&INTCC, &cond));
+                self.sint(imm.into());
+            }
+            FloatCompare { cond, .. } |
+            FloatCond { cond, .. } => self.uint(index_of(&FLOATCC, &cond)),
+            Jump { destination, .. } |
+            Branch { destination, .. } => self.entity(destination),
+            BranchInt { cond, destination, .. } |
+            BranchIcmp { cond, destination, .. } => {
+                self.uint(index_of(&INTCC, &cond));
+                self.entity(destination);
+            }
+            BranchFloat { cond, destination, .. } => {
+                self.uint(index_of(&FLOATCC, &cond));
+                self.entity(destination);
+            }
+            BranchTable { table, .. } => self.entity(table),
+            Call { func_ref, .. } |
+            FuncAddr { func_ref, .. } => self.entity(func_ref),
+            IndirectCall { sig_ref, .. } => self.entity(sig_ref),
+            Invoke { func_ref, destination, .. } => {
+                self.entity(func_ref);
+                self.entity(destination);
+            }
+            StackLoad { stack_slot, offset, .. } |
+            StackStore { stack_slot, offset, .. } => {
+                self.entity(stack_slot);
+                self.sint(offset.into());
+            }
+            HeapAddr { heap, imm, .. } => {
+                self.entity(heap);
+                self.uint(u64::from(Into::<u32>::into(imm)));
+            }
+            Load { flags, offset, .. } |
+            Store { flags, offset, .. } => {
+                self.mem_flags(flags);
+                self.sint(offset.into());
+            }
+            RegMove { src, dst, .. } => {
+                self.uint(u64::from(src));
+                self.uint(u64::from(dst));
+            }
+            RegSpill { src, dst, .. } => {
+                self.uint(u64::from(src));
+                self.entity(dst);
+            }
+            RegFill { src, dst, .. } => {
+                self.entity(src);
+                self.uint(u64::from(dst));
+            }
+            Trap { code, .. } |
+            CondTrap { code, .. } => self.trap_code(code),
+        }
+    }
+}
@@ -9,7 +9,8 @@
 //! contexts concurrently. Typically, you would have one context per compilation thread and only a
 //! single ISA instance.
 
-use binemit::{CodeOffset, relax_branches, MemoryCodeSink, RelocSink};
+use binemit::{CodeOffset, relax_branches, MemoryCodeSink, RelocSink, StackMapSink, CallSiteSink};
+use dce::do_dce;
 use dominator_tree::DominatorTree;
 use flowgraph::ControlFlowGraph;
 use ir::Function;
@@ -90,8 +91,21 @@ impl Context {
     /// code is returned by `compile` above.
     ///
     /// The machine code is not relocated. Instead, any relocations are emitted into `relocs`.
-    pub fn emit_to_memory(&self, mem: *mut u8, relocs: &mut RelocSink, isa: &TargetIsa) {
-        isa.emit_function(&self.func, &mut MemoryCodeSink::new(mem, relocs));
+    /// Stack maps for the function's safepoints are emitted into `stackmaps`, and the call-site
+    /// table mapping the return addresses of `invoke` instructions to their landing pads is
+    /// emitted into `callsites`.
+    pub fn emit_to_memory(
+        &self,
+        mem: *mut u8,
+        relocs: &mut RelocSink,
+        stackmaps: &mut StackMapSink,
+        callsites: &mut CallSiteSink,
+        isa: &TargetIsa,
+    ) {
+        isa.emit_function(
+            &self.func,
+            &mut MemoryCodeSink::new(mem, relocs, stackmaps, callsites),
+        );
     }
 
     /// Run the verifier on the function.
@@ -152,6 +166,12 @@ impl Context {
         self.verify_if(fisa)
     }
 
+    /// Perform dead code elimination on the function.
+    pub fn dce<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CtonResult {
+        do_dce(&mut self.func, &self.domtree);
+        self.verify_if(fisa)
+    }
+
     /// Perform LICM on the function.
     pub fn licm<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CtonResult {
         do_licm(
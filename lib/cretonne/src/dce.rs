@@ -0,0 +1,99 @@
+//! A dead code elimination (DCE) pass.
+//!
+//! Dead code here means instructions that have no side effects and whose result values are not
+//! used by any other instruction.
+
+use cursor::{Cursor, FuncCursor};
+use dominator_tree::DominatorTree;
+use entity::EntitySet;
+use ir::{DataFlowGraph, Function, Inst, Opcode, Value};
+
+/// Test whether the given opcode is unsafe to even consider for DCE.
+fn trivially_unsafe_for_dce(opcode: Opcode) -> bool {
+    opcode.is_call() || opcode.is_branch() || opcode.is_terminator() ||
+        opcode.is_return() || opcode.can_trap() || opcode.other_side_effects() ||
+        opcode.can_store() || opcode.can_load()
+}
+
+/// Test whether any of the results of `inst` are live.
+fn any_inst_results_used(inst: Inst, live: &EntitySet<Value>, dfg: &DataFlowGraph) -> bool {
+    dfg.inst_results(inst).iter().any(|&v| live.contains(v))
+}
+
+/// Mark all the arguments of `inst` as live.
+fn mark_args_live(inst: Inst, live: &mut EntitySet<Value>, dfg: &DataFlowGraph) {
+    for &arg in dfg.inst_args(inst) {
+        live.insert(dfg.resolve_aliases(arg));
+    }
+}
+
+/// Perform DCE on `func`.
+///
+/// Instructions in unreachable EBBs are left alone, and so are the values they use.
+pub fn do_dce(func: &mut Function, domtree: &DominatorTree) {
+    debug_assert!(domtree.is_valid());
+
+    let mut live = EntitySet::new();
+    let mut pos = FuncCursor::new(func);
+
+    while let Some(ebb) = pos.next_ebb() {
+        if domtree.is_reachable(ebb) {
+            continue;
+        }
+        while let Some(inst) = pos.next_inst() {
+            mark_args_live(inst, &mut live, &pos.func.dfg);
+        }
+    }
+
+    // Visit EBBs in post-order and their instructions bottom-up, so all the uses of a value are
+    // seen before its definition.
+    for &ebb in domtree.cfg_postorder() {
+        pos.goto_bottom(ebb);
+        while let Some(inst) = pos.prev_inst() {
+            let opcode = pos.func.dfg[inst].opcode();
+            if trivially_unsafe_for_dce(opcode) ||
+                any_inst_results_used(inst, &live, &pos.func.dfg)
+            {
+                mark_args_live(inst, &mut live, &pos.func.dfg);
+                continue;
+            }
+            dbg!("Removing dead {}", pos.func.dfg.display_inst(inst, None));
+            pos.remove_inst();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::do_dce;
+    use cursor::{Cursor, FuncCursor};
+    use dominator_tree::DominatorTree;
+    use flowgraph::ControlFlowGraph;
+    use ir::{Function, InstBuilder, Opcode};
+    use ir::types::I32;
+
+    #[test]
+    fn dead_chain() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb0, I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let v1 = pos.ins().iadd(arg, arg);
+            pos.ins().imul(v1, arg);
+            let v3 = pos.ins().isub(arg, arg);
+            pos.ins().return_(&[v3]);
+        }
+
+        let cfg = ControlFlowGraph::with_function(&func);
+        let domtree = DominatorTree::with_function(&func, &cfg);
+        do_dce(&mut func, &domtree);
+
+        let opcodes: Vec<_> = func.layout
+            .ebb_insts(ebb0)
+            .map(|inst| func.dfg[inst].opcode())
+            .collect();
+        assert_eq!(opcodes, [Opcode::Isub, Opcode::Return]);
+    }
+}
@@ -129,6 +129,25 @@ impl DominatorTree {
         }
     }
 
+    /// Returns `true` if the results of `inst` are available at `b`.
+    ///
+    /// This is the same as `dominates(inst, b)`, except when `inst` is a branch with results. Such
+    /// an `invoke` instruction dominates its landing pad, but its results are only defined when
+    /// it falls through to the next instruction.
+    pub fn dominates_results<B>(&self, inst: Inst, b: B, layout: &Layout) -> bool
+    where
+        B: Into<ExpandedProgramPoint>,
+    {
+        let b = b.into();
+        let ebb = layout.inst_ebb(inst).expect("Instruction not in layout.");
+        match self.last_dominator(ebb, b, layout) {
+            // `b` is only reached from `inst` by following its branch edge.
+            Some(last) if last == inst => b == inst.into(),
+            Some(last) => layout.cmp(inst, last) == Ordering::Less,
+            None => false,
+        }
+    }
+
     /// Find the last instruction in `a` that dominates `b`.
     /// If no instructions in `a` dominate `b`, return `None`.
     fn last_dominator<B>(&self, a: Ebb, b: B, layout: &Layout) -> Option<Inst>
@@ -312,6 +312,23 @@ impl DataFlowGraph {
         let data = ValueData::Alias { ty, original: src };
         self.make_value(data)
     }
+
+    /// Get the raw value table entry for `v`.
+    ///
+    /// Unlike `value_def()`, this doesn't resolve aliases, and it also returns the stale
+    /// definitions of detached values.
+    pub fn value_data(&self, v: Value) -> ValueData {
+        self.values[v]
+    }
+
+    /// Append a raw entry to the value table.
+    ///
+    /// This is a very low-level operation used when reading serialized functions. The new value is
+    /// not attached to any instruction or EBB, even if `data` says it is. Use `attach_result()` or
+    /// `attach_ebb_param()` for that.
+    pub fn make_value_with_data(&mut self, data: ValueData) -> Value {
+        self.make_value(data)
+    }
 }
 
 /// Where did a value come from?
@@ -333,18 +350,22 @@ impl ValueDef {
     }
 }
 
-// Internal table storage for extended values.
-#[derive(Clone, Debug)]
-enum ValueData {
-    // Value is defined by an instruction.
+/// Table storage for extended values.
+///
+/// This is the raw contents of the value table. Most code should use `value_def()` instead which
+/// resolves aliases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum ValueData {
+    /// Value is defined by an instruction.
     Inst { ty: Type, num: u16, inst: Inst },
 
-    // Value is an EBB parameter.
+    /// Value is an EBB parameter.
     Param { ty: Type, num: u16, ebb: Ebb },
 
-    // Value is an alias of another value.
-    // An alias value can't be linked as an instruction result or EBB parameter. It is used as a
-    // placeholder when the original instruction or EBB has been rewritten or modified.
+    /// Value is an alias of another value.
+    /// An alias value can't be linked as an instruction result or EBB parameter. It is used as a
+    /// placeholder when the original instruction or EBB has been rewritten or modified.
     Alias { ty: Type, original: Value },
 }
 
@@ -461,7 +482,7 @@ impl DataFlowGraph {
         }
 
         // Get the call signature if this is a function call.
-        if let Some(sig) = self.call_signature(inst) {
+        if let Some(sig) = self.non_tail_call_signature(inst) {
             // Create result values corresponding to the call return types.
             let var_results = self.signatures[sig].returns.len();
             total_results += var_results;
@@ -604,6 +625,18 @@ impl DataFlowGraph {
         }
     }
 
+    /// Get the signature of a call instruction that returns to its caller.
+    ///
+    /// Tail calls never return, so they don't produce the callee's results. Returns `None` for
+    /// tail calls and instructions that are not calls.
+    pub fn non_tail_call_signature(&self, inst: Inst) -> Option<SigRef> {
+        if self.insts[inst].opcode().is_terminator() {
+            None
+        } else {
+            self.call_signature(inst)
+        }
+    }
+
     /// Check if `inst` is a branch.
     pub fn analyze_branch(&self, inst: Inst) -> BranchInfo {
         self.insts[inst].analyze_branch(&self.value_lists)
@@ -630,7 +663,7 @@ impl DataFlowGraph {
         }
 
         // Not a fixed result, try to extract a return type from the call signature.
-        self.call_signature(inst).and_then(|sigref| {
+        self.non_tail_call_signature(inst).and_then(|sigref| {
             self.signatures[sigref]
                 .returns
                 .get(result_idx - fixed_results)
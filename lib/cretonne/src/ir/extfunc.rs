@@ -18,7 +18,7 @@ use std::str::FromStr;
 ///
 /// A signature can optionally include ISA-specific ABI information which specifies exactly how
 /// arguments and return values are passed.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Signature {
     /// The arguments passed to the function.
     pub params: Vec<AbiParam>,
@@ -28,6 +28,11 @@ pub struct Signature {
     /// Calling convention.
     pub call_conv: CallConv,
 
+    /// For a variadic function, the number of fixed parameters. The remaining parameters are the
+    /// variadic arguments passed by a particular call. This is `None` when the function is not
+    /// variadic.
+    pub fixed_params: Option<usize>,
+
     /// When the signature has been legalized to a specific ISA, this holds the size of the
     /// argument array on the stack. Before legalization, this is `None`.
     ///
@@ -43,6 +48,7 @@ impl Signature {
             params: Vec::new(),
             returns: Vec::new(),
             call_conv,
+            fixed_params: None,
             argument_bytes: None,
         }
     }
@@ -52,9 +58,15 @@ impl Signature {
         self.params.clear();
         self.returns.clear();
         self.call_conv = call_conv;
+        self.fixed_params = None;
         self.argument_bytes = None;
     }
 
+    /// Is this the signature of a variadic function?
+    pub fn is_variadic(&self) -> bool {
+        self.fixed_params.is_some()
+    }
+
     /// Compute the size of the stack arguments and mark signature as legalized.
     ///
     /// Even if there are no stack arguments, this will set `params` to `Some(0)` instead
@@ -72,6 +84,21 @@ impl Signature {
         self.argument_bytes = Some(bytes);
     }
 
+    /// Get the offset of the stack arguments passed by a tail call to `callee`.
+    ///
+    /// Both `self` and `callee` must be legalized. The offset is relative to the incoming arguments
+    /// of a function with signature `self`. When the callee pops its own stack arguments, they are
+    /// placed such that popping them also pops the arguments passed to the current function.
+    pub fn tail_call_arguments_offset(&self, callee: &Signature) -> i32 {
+        let bytes = self.argument_bytes.expect("Caller signature not legalized");
+        let callee_bytes = callee.argument_bytes.expect("Callee signature not legalized");
+        if callee.call_conv.callee_pops() {
+            bytes as i32 - callee_bytes as i32
+        } else {
+            0
+        }
+    }
+
     /// Return an object that can display `self` with correct register names.
     pub fn display<'a, R: Into<Option<&'a RegInfo>>>(&'a self, regs: R) -> DisplaySignature<'a> {
         DisplaySignature(self, regs.into())
@@ -102,7 +129,20 @@ fn write_list(f: &mut fmt::Formatter, args: &[AbiParam], regs: Option<&RegInfo>)
 impl<'a> fmt::Display for DisplaySignature<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "(")?;
-        write_list(f, &self.0.params, self.1)?;
+        match self.0.fixed_params {
+            None => write_list(f, &self.0.params, self.1)?,
+            Some(fixed) => {
+                let (fixed, variadic) = self.0.params.split_at(fixed);
+                write_list(f, fixed, self.1)?;
+                if !fixed.is_empty() {
+                    write!(f, ", ")?;
+                }
+                write!(f, "...")?;
+                for arg in variadic {
+                    write!(f, ", {}", arg.display(self.1))?;
+                }
+            }
+        }
         write!(f, ")")?;
         if !self.0.returns.is_empty() {
             write!(f, " -> ")?;
@@ -122,7 +162,7 @@ impl fmt::Display for Signature {
 ///
 /// This describes the value type being passed to or from a function along with flags that affect
 /// how the argument is passed.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct AbiParam {
     /// Type of the argument value.
     pub value_type: Type,
@@ -290,14 +330,42 @@ pub enum ArgumentPurpose {
     /// This is a special-purpose argument used to identify the calling convention expected by the
     /// caller in an indirect call. The callee can verify that the expected signature ID matches.
     SignatureId,
-}
 
-/// Text format names of the `ArgumentPurpose` variants.
-static PURPOSE_NAMES: [&str; 7] = ["normal", "sret", "link", "fp", "csr", "vmctx", "sigid"];
+    /// The number of floating point registers used by the arguments of a variadic call.
+    ///
+    /// Some ABIs, like System V on x86-64, pass this count in a register so a variadic callee
+    /// knows which registers to save. The legalizer computes the value at each call site.
+    FloatRegCount,
+
+    /// A struct passed by value.
+    ///
+    /// The argument value is a pointer to the contents of the struct, and the callee gets a
+    /// pointer to its own copy. The ABI legalizer replaces the argument with `StructPart` pieces
+    /// of the struct, or it leaves the argument alone if the ABI passes the struct by reference.
+    StructArgument(StructArg),
+
+    /// A piece of a struct passed by value, after legalization.
+    ///
+    /// A struct argument is split into consecutive pieces covering the struct in order. Each
+    /// piece covers as many bytes as its value type, except the last piece which is cut off at
+    /// the end of the struct.
+    StructPart(StructArg),
+}
 
 impl fmt::Display for ArgumentPurpose {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(PURPOSE_NAMES[*self as usize])
+        f.write_str(match *self {
+            ArgumentPurpose::Normal => "normal",
+            ArgumentPurpose::StructReturn => "sret",
+            ArgumentPurpose::Link => "link",
+            ArgumentPurpose::FramePointer => "fp",
+            ArgumentPurpose::CalleeSaved => "csr",
+            ArgumentPurpose::VMContext => "vmctx",
+            ArgumentPurpose::SignatureId => "sigid",
+            ArgumentPurpose::FloatRegCount => "fregs",
+            ArgumentPurpose::StructArgument(sa) => return write!(f, "sarg{}", sa),
+            ArgumentPurpose::StructPart(sa) => return write!(f, "spart{}", sa),
+        })
     }
 }
 
@@ -312,11 +380,51 @@ impl FromStr for ArgumentPurpose {
             "csr" => Ok(ArgumentPurpose::CalleeSaved),
             "vmctx" => Ok(ArgumentPurpose::VMContext),
             "sigid" => Ok(ArgumentPurpose::SignatureId),
+            "fregs" => Ok(ArgumentPurpose::FloatRegCount),
             _ => Err(()),
         }
     }
 }
 
+/// The layout of a struct passed by value.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct StructArg {
+    /// Size of the struct in bytes.
+    pub size: u32,
+
+    /// Alignment of the struct in bytes. This is a power of two.
+    pub align: u32,
+
+    /// Bit mask of the 8-byte words of the struct that only contain floating point fields.
+    ///
+    /// Some ABIs pass these words in floating point registers. Structs larger than 64 bytes are
+    /// never passed in registers, so 8 bits are enough.
+    pub float_words: u8,
+}
+
+impl StructArg {
+    /// Create a struct layout without any floating point words.
+    pub fn new(size: u32, align: u32) -> StructArg {
+        debug_assert!(align.is_power_of_two(), "Bad struct alignment {}", align);
+        StructArg {
+            size,
+            align,
+            float_words: 0,
+        }
+    }
+}
+
+/// The text format is `(size, align)` or `(size, align, float_words)`.
+impl fmt::Display for StructArg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {}", self.size, self.align)?;
+        if self.float_words != 0 {
+            write!(f, ", {}", self.float_words)?;
+        }
+        write!(f, ")")
+    }
+}
+
 /// An external function.
 ///
 /// Information about a function that can be called directly with a direct `call` instruction.
@@ -344,11 +452,35 @@ impl fmt::Display for ExtFuncData {
 pub enum CallConv {
     /// The C calling convention.
     ///
-    /// This is the native calling convention that a C compiler would use on the platform.
+    /// This is the native calling convention that a C compiler would use on the platform. On Intel
+    /// targets, it is currently the same as `SystemV`.
     Native,
 
+    /// The System V AMD64 ABI used by Linux, macOS, and most other Unix-like systems.
+    SystemV,
+
+    /// The Windows x64 calling convention.
+    ///
+    /// The first four arguments are passed in registers, and the caller reserves 32 bytes of
+    /// shadow space for them above the return address.
+    WindowsFastcall,
+
     /// A JIT-compiled WebAssembly function in the SpiderMonkey VM.
     SpiderWASM,
+
+    /// A calling convention that supports guaranteed tail calls.
+    ///
+    /// Arguments are passed like the native calling convention, but the callee pops its stack
+    /// arguments when it returns. This makes it possible to tail call a function that needs more
+    /// stack arguments than the caller received.
+    Tail,
+}
+
+impl CallConv {
+    /// Does the called function pop its stack arguments when it returns?
+    pub fn callee_pops(self) -> bool {
+        self == CallConv::Tail
+    }
 }
 
 impl fmt::Display for CallConv {
@@ -356,7 +488,10 @@ impl fmt::Display for CallConv {
         use self::CallConv::*;
         f.write_str(match *self {
             Native => "native",
+            SystemV => "system_v",
+            WindowsFastcall => "windows_fastcall",
             SpiderWASM => "spiderwasm",
+            Tail => "tail",
         })
     }
 }
@@ -368,7 +503,10 @@ impl FromStr for CallConv {
         use self::CallConv::*;
         match s {
             "native" => Ok(Native),
+            "system_v" => Ok(SystemV),
+            "windows_fastcall" => Ok(WindowsFastcall),
             "spiderwasm" => Ok(SpiderWASM),
+            "tail" => Ok(Tail),
             _ => Err(()),
         }
     }
@@ -393,22 +531,39 @@ mod tests {
     #[test]
     fn argument_purpose() {
         let all_purpose = [
-            ArgumentPurpose::Normal,
-            ArgumentPurpose::StructReturn,
-            ArgumentPurpose::Link,
-            ArgumentPurpose::FramePointer,
-            ArgumentPurpose::CalleeSaved,
-            ArgumentPurpose::VMContext,
+            (ArgumentPurpose::Normal, "normal"),
+            (ArgumentPurpose::StructReturn, "sret"),
+            (ArgumentPurpose::Link, "link"),
+            (ArgumentPurpose::FramePointer, "fp"),
+            (ArgumentPurpose::CalleeSaved, "csr"),
+            (ArgumentPurpose::VMContext, "vmctx"),
+            (ArgumentPurpose::SignatureId, "sigid"),
+            (ArgumentPurpose::FloatRegCount, "fregs"),
         ];
-        for (&e, &n) in all_purpose.iter().zip(PURPOSE_NAMES.iter()) {
+        for &(e, n) in &all_purpose {
             assert_eq!(e.to_string(), n);
             assert_eq!(Ok(e), n.parse());
         }
     }
 
+    #[test]
+    fn struct_argument() {
+        let mut sa = StructArg::new(24, 8);
+        assert_eq!(ArgumentPurpose::StructArgument(sa).to_string(), "sarg(24, 8)");
+        sa.float_words = 5;
+        assert_eq!(ArgumentPurpose::StructPart(sa).to_string(), "spart(24, 8, 5)");
+    }
+
     #[test]
     fn call_conv() {
-        for &cc in &[CallConv::Native, CallConv::SpiderWASM] {
+        for &cc in &[
+            CallConv::Native,
+            CallConv::SystemV,
+            CallConv::WindowsFastcall,
+            CallConv::SpiderWASM,
+            CallConv::Tail,
+        ]
+        {
             assert_eq!(Ok(cc), cc.to_string().parse())
         }
     }
@@ -443,4 +598,19 @@ mod tests {
             "(i32 [24], i32x4 [8]) -> f32, b8 spiderwasm"
         );
     }
+
+    #[test]
+    fn variadic_signatures() {
+        let mut sig = Signature::new(CallConv::Native);
+        sig.fixed_params = Some(0);
+        assert!(sig.is_variadic());
+        assert_eq!(sig.to_string(), "(...) native");
+        sig.params.push(AbiParam::new(I32));
+        assert_eq!(sig.to_string(), "(..., i32) native");
+        sig.fixed_params = Some(1);
+        sig.params.push(AbiParam::new(F32));
+        assert_eq!(sig.to_string(), "(i32, ..., f32) native");
+        sig.clear(CallConv::Native);
+        assert!(!sig.is_variadic());
+    }
 }
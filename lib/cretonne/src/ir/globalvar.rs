@@ -1,6 +1,6 @@
 //! Global variables.
 
-use ir::GlobalVar;
+use ir::{FunctionName, GlobalVar};
 use ir::immediates::Offset32;
 use std::fmt;
 
@@ -25,6 +25,23 @@ pub enum GlobalVarData {
         /// Byte offset to be added to the pointer loaded from `base`.
         offset: Offset32,
     },
+
+    /// Variable is at an address identified by a symbolic name. Cretonne itself does not interpret
+    /// this name; it's used by embedders to link with other data structures.
+    Sym {
+        /// The symbolic name.
+        name: FunctionName,
+    },
+}
+
+impl GlobalVarData {
+    /// Assume that `self` is a `GlobalVarData::Sym` and return its name.
+    pub fn symbol_name(&self) -> &FunctionName {
+        match *self {
+            GlobalVarData::Sym { ref name } => name,
+            _ => panic!("only symbols have names"),
+        }
+    }
 }
 
 impl fmt::Display for GlobalVarData {
@@ -32,6 +49,7 @@ impl fmt::Display for GlobalVarData {
         match *self {
             GlobalVarData::VmCtx { offset } => write!(f, "vmctx{}", offset),
             GlobalVarData::Deref { base, offset } => write!(f, "deref({}){}", base, offset),
+            GlobalVarData::Sym { ref name } => write!(f, "globalsym {}", name),
         }
     }
 }
@@ -103,6 +103,7 @@ impl FromStr for Opcode {
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 #[allow(missing_docs)]
 pub enum InstructionData {
+    NullAry { opcode: Opcode },
     Unary { opcode: Opcode, arg: Value },
     UnaryImm { opcode: Opcode, imm: Imm64 },
     UnaryIeee32 { opcode: Opcode, imm: Ieee32 },
@@ -199,6 +200,12 @@ pub enum InstructionData {
         sig_ref: SigRef,
         args: ValueList,
     },
+    Invoke {
+        opcode: Opcode,
+        func_ref: FuncRef,
+        destination: Ebb,
+        args: ValueList,
+    },
     FuncAddr { opcode: Opcode, func_ref: FuncRef },
     StackLoad {
         opcode: Opcode,
@@ -346,6 +353,8 @@ impl InstructionData {
                 ..
             } => BranchInfo::SingleDest(destination, &args.as_slice(pool)[2..]),
             InstructionData::BranchTable { table, .. } => BranchInfo::Table(table),
+            // The landing pad of an `invoke` doesn't receive any arguments.
+            InstructionData::Invoke { destination, .. } => BranchInfo::SingleDest(destination, &[]),
             _ => BranchInfo::NotABranch,
         }
     }
@@ -359,6 +368,7 @@ impl InstructionData {
             InstructionData::Jump { destination, .. } => Some(destination),
             InstructionData::Branch { destination, .. } => Some(destination),
             InstructionData::BranchIcmp { destination, .. } => Some(destination),
+            InstructionData::Invoke { destination, .. } => Some(destination),
             _ => None,
         }
     }
@@ -372,6 +382,7 @@ impl InstructionData {
             InstructionData::Jump { ref mut destination, .. } => Some(destination),
             InstructionData::Branch { ref mut destination, .. } => Some(destination),
             InstructionData::BranchIcmp { ref mut destination, .. } => Some(destination),
+            InstructionData::Invoke { ref mut destination, .. } => Some(destination),
             _ => None,
         }
     }
@@ -381,7 +392,8 @@ impl InstructionData {
     /// Any instruction that can call another function reveals its call signature here.
     pub fn analyze_call<'a>(&'a self, pool: &'a ValueListPool) -> CallInfo<'a> {
         match *self {
-            InstructionData::Call { func_ref, ref args, .. } => {
+            InstructionData::Call { func_ref, ref args, .. } |
+            InstructionData::Invoke { func_ref, ref args, .. } => {
                 CallInfo::Direct(func_ref, args.as_slice(pool))
             }
             InstructionData::IndirectCall { sig_ref, ref args, .. } => {
@@ -559,6 +571,8 @@ pub struct ValueTypeSet {
     pub floats: BitSet8,
     /// Allowed bool widths
     pub bools: BitSet8,
+    /// Allowed reference widths
+    pub refs: BitSet8,
 }
 
 impl ValueTypeSet {
@@ -573,6 +587,8 @@ impl ValueTypeSet {
             self.floats.contains(l2b)
         } else if scalar.is_bool() {
             self.bools.contains(l2b)
+        } else if scalar.is_ref() {
+            self.refs.contains(l2b)
         } else {
             false
         }
@@ -594,6 +610,9 @@ impl ValueTypeSet {
             types::F32
         } else if self.bools.max().unwrap_or(0) > 5 {
             types::B32
+        } else if let Some(l2b) = self.refs.max() {
+            // Reference types can't be vectors.
+            return if l2b > 5 { types::R64 } else { types::R32 };
         } else {
             types::B1
         };
@@ -766,6 +785,7 @@ mod tests {
             ints: BitSet8::from_range(4, 7),
             floats: BitSet8::from_range(0, 0),
             bools: BitSet8::from_range(3, 7),
+            refs: BitSet8::from_range(0, 0),
         };
         assert!(!vts.contains(I8));
         assert!(vts.contains(I32));
@@ -782,6 +802,7 @@ mod tests {
             ints: BitSet8::from_range(0, 0),
             floats: BitSet8::from_range(5, 7),
             bools: BitSet8::from_range(3, 7),
+            refs: BitSet8::from_range(0, 0),
         };
         assert_eq!(vts.example().to_string(), "f32");
 
@@ -790,6 +811,7 @@ mod tests {
             ints: BitSet8::from_range(0, 0),
             floats: BitSet8::from_range(5, 7),
             bools: BitSet8::from_range(3, 7),
+            refs: BitSet8::from_range(0, 0),
         };
         assert_eq!(vts.example().to_string(), "f32x2");
 
@@ -798,17 +820,32 @@ mod tests {
             ints: BitSet8::from_range(0, 0),
             floats: BitSet8::from_range(0, 0),
             bools: BitSet8::from_range(3, 7),
+            refs: BitSet8::from_range(0, 0),
         };
         assert!(!vts.contains(B32X2));
         assert!(vts.contains(B32X4));
         assert_eq!(vts.example().to_string(), "b32x4");
 
+        let vts = ValueTypeSet {
+            // TypeSet(lanes={1}, specials=[r32, r64])
+            lanes: BitSet16::from_range(0, 1),
+            ints: BitSet8::from_range(0, 0),
+            floats: BitSet8::from_range(0, 0),
+            bools: BitSet8::from_range(0, 0),
+            refs: BitSet8::from_range(5, 7),
+        };
+        assert!(vts.contains(R32));
+        assert!(vts.contains(R64));
+        assert!(!vts.contains(I64));
+        assert_eq!(vts.example().to_string(), "r64");
+
         let vts = ValueTypeSet {
             // TypeSet(lanes=(1, 256), ints=(8, 64))
             lanes: BitSet16::from_range(0, 9),
             ints: BitSet8::from_range(3, 7),
             floats: BitSet8::from_range(0, 0),
             bools: BitSet8::from_range(0, 0),
+            refs: BitSet8::from_range(0, 0),
         };
         assert!(vts.contains(I32));
         assert!(vts.contains(I32X4));
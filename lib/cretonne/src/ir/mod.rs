@@ -25,7 +25,7 @@ pub use ir::builder::{InstBuilder, InstBuilderBase, InstInserterBase, InsertBuil
 pub use ir::dfg::{DataFlowGraph, ValueDef};
 pub use ir::entities::{Ebb, Inst, Value, StackSlot, GlobalVar, JumpTable, FuncRef, SigRef, Heap};
 pub use ir::extfunc::{Signature, CallConv, AbiParam, ArgumentExtension, ArgumentPurpose,
-                      ExtFuncData};
+                      StructArg, ExtFuncData};
 pub use ir::funcname::FunctionName;
 pub use ir::function::Function;
 pub use ir::globalvar::GlobalVarData;
@@ -243,6 +243,23 @@ impl StackSlots {
         self.push(data)
     }
 
+    /// Get a stack slot representing an incoming argument.
+    ///
+    /// This may create a new stack slot, or reuse an existing incoming stack slot with the
+    /// requested offset and size. Tail calls use this to pass stack arguments in the incoming
+    /// argument area of the current function.
+    pub fn get_incoming_arg(&mut self, ty: Type, offset: StackOffset) -> StackSlot {
+        let size = ty.bytes();
+        let existing = self.keys().find(|&ss| {
+            let slot = &self[ss];
+            slot.kind == StackSlotKind::IncomingArg && slot.offset == offset && slot.size == size
+        });
+        match existing {
+            Some(ss) => ss,
+            None => self.make_incoming_arg(ty, offset),
+        }
+    }
+
     /// Get a stack slot representing an outgoing argument.
     ///
     /// This may create a new stack slot, or reuse an existing outgoing stack slot with the
@@ -21,6 +21,9 @@ use std::fmt::{self, Display, Debug, Formatter};
 /// Boolean types: `B1`, `B8`, `B16`, `B32`, and `B64`. These all encode 'true' or 'false'. The
 /// larger types use redundant bits.
 ///
+/// Reference types: `R32` and `R64`. These are pointers to objects managed by a garbage
+/// collector. They can't be part of a SIMD vector.
+///
 /// SIMD vector types have power-of-two lanes, up to 256. Lanes can be any int/float/bool type.
 ///
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
@@ -59,8 +62,8 @@ impl Type {
             B1 => 0,
             B8 | I8 => 3,
             B16 | I16 => 4,
-            B32 | I32 | F32 => 5,
-            B64 | I64 | F64 => 6,
+            B32 | I32 | F32 | R32 => 5,
+            B64 | I64 | F64 | R64 => 6,
             _ => 0,
         }
     }
@@ -71,8 +74,8 @@ impl Type {
             B1 => 1,
             B8 | I8 => 8,
             B16 | I16 => 16,
-            B32 | I32 | F32 => 32,
-            B64 | I64 | F64 => 64,
+            B32 | I32 | F32 | R32 => 32,
+            B64 | I64 | F64 | R64 => 64,
             _ => 0,
         }
     }
@@ -200,6 +203,14 @@ impl Type {
         }
     }
 
+    /// Is this a reference type?
+    pub fn is_ref(self) -> bool {
+        match self {
+            R32 | R64 => true,
+            _ => false,
+        }
+    }
+
     /// Is this a CPU flags type?
     pub fn is_flags(self) -> bool {
         match self {
@@ -242,7 +253,7 @@ impl Type {
     /// If this is already a SIMD vector type, this produces a SIMD vector type with `n *
     /// self.lane_count()` lanes.
     pub fn by(self, n: u16) -> Option<Type> {
-        if self.lane_bits() == 0 || !n.is_power_of_two() {
+        if self.is_special() || !n.is_power_of_two() {
             return None;
         }
         let log2_lanes: u32 = n.trailing_zeros();
@@ -293,6 +304,8 @@ impl Display for Type {
                 VOID => "void",
                 IFLAGS => "iflags",
                 FFLAGS => "fflags",
+                R32 => "r32",
+                R64 => "r64",
                 _ => panic!("Invalid Type(0x{:x})", self.0),
             })
         }
@@ -314,6 +327,8 @@ impl Debug for Type {
                 VOID => write!(f, "types::VOID"),
                 IFLAGS => write!(f, "types::IFLAGS"),
                 FFLAGS => write!(f, "types::FFLAGS"),
+                R32 => write!(f, "types::R32"),
+                R64 => write!(f, "types::R64"),
                 _ => write!(f, "Type(0x{:x})", self.0),
             }
         }
@@ -338,6 +353,10 @@ mod tests {
         assert_eq!(0, IFLAGS.bits());
         assert_eq!(FFLAGS, FFLAGS.lane_type());
         assert_eq!(0, FFLAGS.bits());
+        assert_eq!(R32, R32.lane_type());
+        assert_eq!(32, R32.bits());
+        assert_eq!(R64, R64.lane_type());
+        assert_eq!(8, R64.bytes());
         assert_eq!(B1, B1.lane_type());
         assert_eq!(B8, B8.lane_type());
         assert_eq!(B16, B16.lane_type());
@@ -353,6 +372,8 @@ mod tests {
         assert_eq!(VOID.lane_bits(), 0);
         assert_eq!(IFLAGS.lane_bits(), 0);
         assert_eq!(FFLAGS.lane_bits(), 0);
+        assert_eq!(R32.lane_bits(), 32);
+        assert_eq!(R64.lane_bits(), 64);
         assert_eq!(B1.lane_bits(), 1);
         assert_eq!(B8.lane_bits(), 8);
         assert_eq!(B16.lane_bits(), 16);
@@ -383,6 +404,7 @@ mod tests {
         assert_eq!(I64.half_width(), Some(I32));
         assert_eq!(F32.half_width(), None);
         assert_eq!(F64.half_width(), Some(F32));
+        assert_eq!(R64.half_width(), None);
 
         assert_eq!(VOID.double_width(), None);
         assert_eq!(IFLAGS.double_width(), None);
@@ -423,6 +445,8 @@ mod tests {
         assert_eq!(VOID.to_string(), "void");
         assert_eq!(IFLAGS.to_string(), "iflags");
         assert_eq!(FFLAGS.to_string(), "fflags");
+        assert_eq!(R32.to_string(), "r32");
+        assert_eq!(R64.to_string(), "r64");
         assert_eq!(B1.to_string(), "b1");
         assert_eq!(B8.to_string(), "b8");
         assert_eq!(B16.to_string(), "b16");
@@ -448,6 +472,7 @@ mod tests {
         assert_eq!(I8.by(3), None);
         assert_eq!(I8.by(512), None);
         assert_eq!(VOID.by(4), None);
+        assert_eq!(R32.by(4), None);
     }
 
     #[test]
@@ -1,19 +1,71 @@
 //! Intel ABI implementation.
 
 use ir;
-use isa::{RegClass, RegUnit};
+use isa::{RegClass, RegUnit, TargetIsa};
 use regalloc::AllocatableSet;
 use settings as shared_settings;
 use super::registers::{GPR, FPR, RU};
-use abi::{ArgAction, ValueConversion, ArgAssigner, legalize_args};
-use ir::{AbiParam, ArgumentPurpose, ArgumentLoc, ArgumentExtension};
+use abi::{ArgAction, ValueConversion, ArgAssigner, legalize_args, legalize_params};
+use cursor::{Cursor, EncCursor};
+use ir::{AbiParam, ArgumentPurpose, ArgumentLoc, ArgumentExtension, CallConv, InstBuilder,
+         InstructionData, ValueLoc, StackSlot, StackSlotData, StackSlotKind};
+use ir::stackslot::{StackSize, StackOffset};
+use result::{self, CtonError};
+use stack_layout::layout_stack;
 
 /// Argument registers for x86-64
 static ARG_GPRS: [RU; 6] = [RU::rdi, RU::rsi, RU::rdx, RU::rcx, RU::r8, RU::r9];
 
+/// Argument registers for Windows x64.
+static ARG_GPRS_WIN: [RU; 4] = [RU::rcx, RU::rdx, RU::r8, RU::r9];
+
 /// Return value registers.
 static RET_GPRS: [RU; 3] = [RU::rax, RU::rdx, RU::rcx];
 
+/// Return value registers for Windows x64.
+static RET_GPRS_WIN: [RU; 1] = [RU::rax];
+
+/// Callee-saved registers for x86-64.
+static CSR_GPRS_64: [RU; 5] = [RU::rbx, RU::r12, RU::r13, RU::r14, RU::r15];
+
+/// Callee-saved registers for Windows x64. `%rbp` is also callee-saved, but never allocated.
+static CSR_GPRS_WIN: [RU; 7] = [
+    RU::rbx,
+    RU::rsi,
+    RU::rdi,
+    RU::r12,
+    RU::r13,
+    RU::r14,
+    RU::r15,
+];
+
+/// Callee-saved XMM registers for Windows x64. All 128 bits must be preserved.
+static CSR_FPRS_WIN: [RU; 10] = [
+    RU::xmm6,
+    RU::xmm7,
+    RU::xmm8,
+    RU::xmm9,
+    RU::xmm10,
+    RU::xmm11,
+    RU::xmm12,
+    RU::xmm13,
+    RU::xmm14,
+    RU::xmm15,
+];
+
+/// Callee-saved registers for 32-bit x86.
+static CSR_GPRS_32: [RU; 3] = [RU::rbx, RU::rsi, RU::rdi];
+
+/// Scratch register used to move the return address around in 64-bit prologues and epilogues. It
+/// is neither callee-saved nor used to pass arguments.
+const SCRATCH: RU = RU::r11;
+
+/// The stack pointer is kept aligned to this many bytes at calls.
+const STACK_ALIGNMENT: StackSize = 16;
+
+/// Size of the shadow space a Windows x64 caller reserves for the register arguments.
+const WIN_SHADOW_SPACE: StackSize = 32;
+
 struct Args {
     pointer_bytes: u32,
     pointer_bits: u16,
@@ -22,6 +74,12 @@ struct Args {
     gpr_used: usize,
     fpr_limit: usize,
     fpr_used: usize,
+    /// Do integer and float arguments share argument positions, like on Windows x64?
+    shared_slots: bool,
+    /// Are we assigning the variadic arguments of a call?
+    variadic: bool,
+    /// Are the struct pieces being assigned passed in memory?
+    struct_in_memory: bool,
     offset: u32,
 }
 
@@ -35,21 +93,94 @@ impl Args {
             gpr_used: 0,
             fpr_limit,
             fpr_used: 0,
+            shared_slots: false,
+            variadic: false,
+            struct_in_memory: false,
             offset: 0,
         }
     }
+
+    /// Create an assigner for Windows x64 where the n'th argument is passed in either the n'th
+    /// GPR or the n'th XMM register, and stack arguments start above the shadow space.
+    fn new_win(gpr: &'static [RU]) -> Args {
+        let mut args = Args::new(64, gpr, gpr.len());
+        args.shared_slots = true;
+        args.offset = WIN_SHADOW_SPACE;
+        args
+    }
+
+    /// Skip the next argument register, which has been assigned explicitly.
+    fn skip_reg(&mut self) {
+        self.gpr_used += 1;
+        self.fpr_used += 1;
+    }
+
+    /// Classify a struct argument, and decide if its pieces go in registers or in memory.
+    ///
+    /// Returns `None` if the struct is passed by reference.
+    fn classify_struct(&mut self, sa: ir::StructArg) -> Option<ArgAction> {
+        if self.shared_slots {
+            // Windows x64 passes structs of 1, 2, 4, or 8 bytes as integers. Other structs are
+            // passed by reference.
+            if sa.size > 8 || !sa.size.is_power_of_two() {
+                return None;
+            }
+            self.struct_in_memory = false;
+            return Some(ArgAction::SplitStruct(false));
+        }
+
+        // System V x86-64 classifies the 8-byte words of a struct of up to 16 bytes as INTEGER or
+        // SSE. If there aren't enough registers for all of the words, or the struct is larger,
+        // it is passed in memory. 32-bit x86 always passes structs in memory.
+        let words = (sa.size + 7) / 8;
+        let sse = (0..words).filter(|&w| sa.float_words & (1 << w) != 0).count();
+        let integer = words as usize - sse;
+        self.struct_in_memory = self.pointer_bits == 32 || sa.size > 16 ||
+            self.gpr_used + integer > self.gpr.len() ||
+            self.fpr_used + sse > self.fpr_limit;
+        if self.struct_in_memory {
+            let align = sa.align.max(self.pointer_bytes);
+            self.offset = (self.offset + align - 1) & !(align - 1);
+        }
+        Some(ArgAction::SplitStruct(!self.struct_in_memory))
+    }
+
+    /// Assign the next stack location.
+    fn assign_stack(&mut self) -> ArgAction {
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += self.pointer_bytes;
+        assert!(self.offset <= i32::max_value() as u32);
+        loc.into()
+    }
 }
 
 impl ArgAssigner for Args {
     fn assign(&mut self, arg: &AbiParam) -> ArgAction {
         let ty = arg.value_type;
 
+        // Struct arguments are split into pieces which may all go in memory.
+        match arg.purpose {
+            ArgumentPurpose::StructArgument(sa) => {
+                if let Some(action) = self.classify_struct(sa) {
+                    return action;
+                }
+            }
+            ArgumentPurpose::StructPart(_) if self.struct_in_memory => return self.assign_stack(),
+            _ => {}
+        }
+
         // Check for a legal type.
         // We don't support SIMD yet, so break all vectors down.
         if ty.is_vector() {
             return ValueConversion::VectorSplit.into();
         }
 
+        // Windows x64 passes variadic floating point arguments in the integer registers, which is
+        // where the callee's `va_arg` looks for them.
+        if self.variadic && self.shared_slots && ty.is_float() {
+            return ValueConversion::IntBits.into();
+        }
+
         // Large integers and booleans are broken down to fit in a register.
         if !ty.is_float() && ty.bits() > self.pointer_bits {
             return ValueConversion::IntSplit.into();
@@ -78,6 +209,10 @@ impl ArgAssigner for Args {
                 }
                 // This is SpiderMonkey's `WasmTableCallSigReg`.
                 ArgumentPurpose::SignatureId => return ArgumentLoc::Reg(RU::rbx as RegUnit).into(),
+                // Windows x64 passes the `sret` pointer as the first argument.
+                ArgumentPurpose::StructReturn if self.shared_slots => {
+                    return ArgumentLoc::Reg(self.gpr[0] as RegUnit).into()
+                }
                 _ => {}
             }
         }
@@ -86,6 +221,9 @@ impl ArgAssigner for Args {
         if !ty.is_float() && self.gpr_used < self.gpr.len() {
             let reg = self.gpr[self.gpr_used] as RegUnit;
             self.gpr_used += 1;
+            if self.shared_slots {
+                self.fpr_used += 1;
+            }
             return ArgumentLoc::Reg(reg).into();
         }
 
@@ -93,39 +231,100 @@ impl ArgAssigner for Args {
         if ty.is_float() && self.fpr_used < self.fpr_limit {
             let reg = FPR.unit(self.fpr_used);
             self.fpr_used += 1;
+            if self.shared_slots {
+                self.gpr_used += 1;
+            }
             return ArgumentLoc::Reg(reg).into();
         }
 
         // Assign a stack location.
-        let loc = ArgumentLoc::Stack(self.offset as i32);
-        self.offset += self.pointer_bytes;
-        assert!(self.offset <= i32::max_value() as u32);
-        loc.into()
+        self.assign_stack()
+    }
+}
+
+/// Is `sig` using the Windows x64 calling convention?
+fn is_win64(sig: &ir::Signature, flags: &shared_settings::Flags) -> bool {
+    sig.call_conv == CallConv::WindowsFastcall && flags.is_64bit()
+}
+
+/// Get an assigner for the return values of `sig`.
+fn return_args(sig: &ir::Signature, flags: &shared_settings::Flags) -> Args {
+    if is_win64(sig, flags) {
+        Args::new(64, &RET_GPRS_WIN, 1)
+    } else if flags.is_64bit() {
+        Args::new(64, &RET_GPRS, 2)
+    } else {
+        Args::new(32, &RET_GPRS, 2)
     }
 }
 
 /// Legalize `sig`.
+///
+/// On x86-64, the `native` calling convention is the same as `system_v`. 32-bit x86 passes all
+/// arguments on the stack for every calling convention.
 pub fn legalize_signature(sig: &mut ir::Signature, flags: &shared_settings::Flags, _current: bool) {
-    let bits;
-    let mut args;
-
-    if flags.is_64bit() {
-        bits = 64;
-        args = Args::new(bits, &ARG_GPRS, 8);
+    let mut args = if is_win64(sig, flags) {
+        Args::new_win(&ARG_GPRS_WIN)
+    } else if flags.is_64bit() {
+        Args::new(64, &ARG_GPRS, 8)
     } else {
-        bits = 32;
-        args = Args::new(bits, &[], 0);
+        Args::new(32, &[], 0)
+    };
+
+    let mut returns = sig.returns.clone();
+    legalize_args(&mut returns, &mut return_args(sig, flags));
+
+    // If there are more return values than return registers, the caller passes a pointer to
+    // memory for the return values in an `sret` argument. The callee returns the same pointer in
+    // `%rax`.
+    if sig.special_param_index(ArgumentPurpose::StructReturn).is_none() &&
+        returns.iter().any(|rt| {
+            rt.purpose == ArgumentPurpose::Normal && rt.location.is_stack()
+        })
+    {
+        let sret = AbiParam::special(args.pointer_type, ArgumentPurpose::StructReturn);
+        sig.params.push(sret);
+        sig.returns.retain(|rt| rt.purpose != ArgumentPurpose::Normal);
+        sig.returns.push(sret);
+        returns = sig.returns.clone();
+        legalize_args(&mut returns, &mut return_args(sig, flags));
     }
+    sig.returns = returns;
 
-    legalize_args(&mut sig.params, &mut args);
+    // The `sret` pointer takes the first argument register on Windows x64.
+    if args.shared_slots && sig.special_param_index(ArgumentPurpose::StructReturn).is_some() {
+        args.skip_reg();
+    }
 
-    let mut rets = Args::new(bits, &RET_GPRS, 2);
-    legalize_args(&mut sig.returns, &mut rets);
+    // A System V variadic callee expects an upper bound on the number of vector registers used
+    // for arguments in `%al`. The legalizer computes the value at each call site.
+    if sig.is_variadic() && flags.is_64bit() && !args.shared_slots &&
+        sig.special_param_index(ArgumentPurpose::FloatRegCount).is_none()
+    {
+        sig.params.push(AbiParam::special_reg(
+            ir::types::I32,
+            ArgumentPurpose::FloatRegCount,
+            RU::rax as RegUnit,
+        ));
+    }
+    legalize_params(sig, &mut args, |args| args.variadic = true);
+
+    // When the callee pops its stack arguments, their size must preserve the stack alignment.
+    // Pad the arguments at the bottom of the argument area.
+    if sig.call_conv.callee_pops() {
+        let align = STACK_ALIGNMENT as u32;
+        let padding = (args.offset + align - 1) / align * align - args.offset;
+        for param in &mut sig.params {
+            if let ArgumentLoc::Stack(ref mut offset) = param.location {
+                *offset += padding as i32;
+            }
+        }
+    }
 }
 
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() { GPR } else { FPR }
+    if ty.is_int() || ty.is_ref() { GPR } else { FPR }
 }
 
 /// Get the set of allocatable registers for `func`.
@@ -147,3 +346,320 @@ pub fn allocatable_registers(
 
     regs
 }
+
+/// Insert a prologue and epilogues into `func`, and compute the stack frame layout.
+pub fn prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> result::CtonResult {
+    // The 32-bit encodings ignore `is_pic`, so reject it rather than emit absolute addresses.
+    if isa.flags().is_pic() && !isa.flags().is_64bit() {
+        return Err(result::CtonError::Unsupported);
+    }
+    match func.signature.call_conv {
+        CallConv::SpiderWASM => spiderwasm_prologue_epilogue(func, isa),
+        CallConv::Native |
+        CallConv::SystemV |
+        CallConv::WindowsFastcall |
+        CallConv::Tail => native_prologue_epilogue(func, isa),
+    }
+}
+
+/// The SpiderMonkey prologue is generated outside Cretonne, so only lay out the stack frame.
+fn spiderwasm_prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> result::CtonResult {
+    let word_size = if isa.flags().is_64bit() { 8 } else { 4 };
+
+    // Account for the SpiderMonkey standard prologue pushes.
+    let bytes = isa.flags().spiderwasm_prologue_words() as StackSize * word_size;
+    let mut ss = StackSlotData::new(StackSlotKind::IncomingArg, bytes);
+    ss.offset = -(bytes as StackOffset);
+    func.stack_slots.push(ss);
+
+    layout_stack(&mut func.stack_slots, word_size)?;
+    Ok(())
+}
+
+/// Insert a native prologue and epilogues.
+///
+/// The frame doesn't use a frame pointer. From high to low addresses, it consists of:
+///
+/// 1. Incoming stack arguments.
+/// 2. A gap for the stack arguments of tail calls that need more than the incoming arguments.
+/// 3. The return address.
+/// 4. The callee-saved GPRs used by the function.
+/// 5. Spill slots, locals, saved XMM registers, and outgoing arguments.
+///
+/// The gap is only needed for functions using a calling convention where the callee pops its
+/// arguments. The return address is moved down to make room for it in the prologue.
+fn native_prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> result::CtonResult {
+    let win64 = is_win64(&func.signature, isa.flags());
+    let (word_size, word_type, gpr_csrs, fpr_csrs) = if win64 {
+        (8, ir::types::I64, &CSR_GPRS_WIN[..], &CSR_FPRS_WIN[..])
+    } else if isa.flags().is_64bit() {
+        (8, ir::types::I64, &CSR_GPRS_64[..], &[][..])
+    } else {
+        (4, ir::types::I32, &CSR_GPRS_32[..], &[][..])
+    };
+    let csrs = used_callee_saved_registers(func, gpr_csrs);
+
+    // XMM registers can't be pushed, so they are saved in 16-byte spill slots instead.
+    let fpr_csrs = used_callee_saved_registers(func, fpr_csrs)
+        .into_iter()
+        .map(|reg| {
+            let ss = func.stack_slots.push(
+                StackSlotData::new(StackSlotKind::SpillSlot, 16),
+            );
+            (reg, ss)
+        })
+        .collect();
+
+    // Calls to Windows x64 functions need shadow space at the bottom of the outgoing arguments.
+    if calls_win64(func, isa.flags()) {
+        func.stack_slots.push(StackSlotData::new(
+            StackSlotKind::OutgoingArg,
+            WIN_SHADOW_SPACE,
+        ));
+    }
+
+    // Tail calls made by this function have assigned their stack arguments to incoming argument
+    // slots, possibly below our own incoming arguments.
+    let gap = func.stack_slots
+        .keys()
+        .filter(|&ss| func.stack_slots[ss].kind == StackSlotKind::IncomingArg)
+        .map(|ss| -func.stack_slots[ss].offset)
+        .fold(0, |gap, offset| if offset > gap { offset } else { gap }) as StackSize;
+
+    // The return address and the saved registers are pushed below the incoming arguments.
+    let header = word_size * (1 + csrs.len() as StackSize);
+    let mut ss = StackSlotData::new(StackSlotKind::IncomingArg, header);
+    ss.offset = -((gap + header) as StackOffset);
+    func.stack_slots.push(ss);
+
+    // Moving the return address requires a scratch register which 32-bit mode doesn't have.
+    let pops_arguments = func.signature.call_conv.callee_pops() &&
+        func.signature.argument_bytes.unwrap_or(0) > 0;
+    if !isa.flags().is_64bit() && (gap > 0 || pops_arguments) {
+        return Err(CtonError::ImplLimitExceeded);
+    }
+
+    let frame_size = layout_stack(&mut func.stack_slots, STACK_ALIGNMENT)?;
+    let local_size = (frame_size - gap - header) as i64;
+
+    let frame = Frame {
+        word_type,
+        csrs,
+        fpr_csrs,
+        gap: gap as i64,
+        local_size,
+    };
+    let saved_fprs = insert_prologue(func, isa, &frame);
+    insert_epilogues(func, isa, &frame, &saved_fprs);
+    Ok(())
+}
+
+/// The layout of a native stack frame.
+struct Frame {
+    word_type: ir::Type,
+    /// Callee-saved GPRs that are pushed by the prologue.
+    csrs: Vec<RU>,
+    /// Callee-saved XMM registers and the spill slots they are saved in.
+    fpr_csrs: Vec<(RU, StackSlot)>,
+    gap: i64,
+    local_size: i64,
+}
+
+/// Does `func` contain any calls to Windows x64 functions that return?
+fn calls_win64(func: &ir::Function, flags: &shared_settings::Flags) -> bool {
+    func.layout.ebbs().any(|ebb| {
+        func.layout.ebb_insts(ebb).any(|inst| {
+            func.dfg.non_tail_call_signature(inst).map_or(false, |sig| {
+                is_win64(&func.dfg.signatures[sig], flags)
+            })
+        })
+    })
+}
+
+/// Get the callee-saved registers from `csrs` that are used by `func`.
+fn used_callee_saved_registers(func: &ir::Function, csrs: &[RU]) -> Vec<RU> {
+    let mut used = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for &v in func.dfg.ebb_params(ebb) {
+            if let ValueLoc::Reg(reg) = func.locations[v] {
+                used.push(reg);
+            }
+        }
+        for inst in func.layout.ebb_insts(ebb) {
+            for &v in func.dfg.inst_results(inst) {
+                if let ValueLoc::Reg(reg) = func.locations[v] {
+                    used.push(reg);
+                }
+            }
+            // Register diversions can use registers that no value is assigned to.
+            match func.dfg[inst] {
+                InstructionData::RegMove { dst, .. } |
+                InstructionData::RegFill { dst, .. } => used.push(dst),
+                _ => {}
+            }
+        }
+    }
+    csrs.iter()
+        .cloned()
+        .filter(|&reg| used.contains(&(reg as RegUnit)))
+        .collect()
+}
+
+/// Insert the prologue at the top of the entry block.
+///
+/// The callee-saved registers are added as `CalleeSaved` parameters of the function, so they can
+/// be pushed and returned again by the epilogues. Returns the spilled values of the callee-saved
+/// XMM registers.
+fn insert_prologue(func: &mut ir::Function, isa: &TargetIsa, frame: &Frame) -> Vec<ir::Value> {
+    let entry = match func.layout.entry_block() {
+        Some(ebb) => ebb,
+        None => return Vec::new(),
+    };
+    let mut csr_params = Vec::new();
+    for &reg in &frame.csrs {
+        csr_params.push(add_csr_param(func, entry, frame.word_type, reg));
+    }
+    let mut fpr_params = Vec::new();
+    for &(reg, _) in &frame.fpr_csrs {
+        fpr_params.push(add_csr_param(func, entry, ir::types::F64X2, reg));
+    }
+
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry);
+    move_return_address(&mut pos, frame, -frame.gap);
+    for param in csr_params {
+        pos.ins().x86_push(param);
+    }
+    if frame.local_size > 0 {
+        pos.ins().adjust_sp_imm(-frame.local_size);
+    }
+    let mut saved = Vec::new();
+    for (param, &(_, ss)) in fpr_params.into_iter().zip(&frame.fpr_csrs) {
+        let value = pos.ins().spill(param);
+        pos.func.locations[value] = ValueLoc::Stack(ss);
+        saved.push(value);
+    }
+    saved
+}
+
+/// Add a `CalleeSaved` parameter in `reg` to `func` and its entry block.
+fn add_csr_param(func: &mut ir::Function, entry: ir::Ebb, ty: ir::Type, reg: RU) -> ir::Value {
+    let param = func.dfg.append_ebb_param(entry, ty);
+    func.locations[param] = ValueLoc::Reg(reg as RegUnit);
+    func.signature.params.push(AbiParam::special_reg(
+        ty,
+        ArgumentPurpose::CalleeSaved,
+        reg as RegUnit,
+    ));
+    param
+}
+
+/// Insert an epilogue before every return and tail call, and adjust the stack pointer after calls
+/// to functions that pop their own arguments.
+fn insert_epilogues(
+    func: &mut ir::Function,
+    isa: &TargetIsa,
+    frame: &Frame,
+    saved_fprs: &[ir::Value],
+) {
+    for &reg in &frame.csrs {
+        func.signature.returns.push(AbiParam::special_reg(
+            frame.word_type,
+            ArgumentPurpose::CalleeSaved,
+            reg as RegUnit,
+        ));
+    }
+    for &(reg, _) in &frame.fpr_csrs {
+        func.signature.returns.push(AbiParam::special_reg(
+            ir::types::F64X2,
+            ArgumentPurpose::CalleeSaved,
+            reg as RegUnit,
+        ));
+    }
+
+    let mut pos = EncCursor::new(func, isa);
+    while pos.next_ebb().is_some() {
+        while let Some(inst) = pos.next_inst() {
+            let opcode = pos.func.dfg[inst].opcode();
+            if opcode.is_return() {
+                pos.use_srcloc(inst);
+                insert_epilogue(&mut pos, frame, saved_fprs, inst);
+                // The callee pops our incoming arguments and the tail call gap.
+                let popped = if pos.func.signature.call_conv.callee_pops() {
+                    i64::from(pos.func.signature.argument_bytes.unwrap_or(0)) + frame.gap
+                } else {
+                    0
+                };
+                move_return_address(&mut pos, frame, popped);
+            } else if opcode.is_call() {
+                let sig_ref = pos.func.dfg.call_signature(inst).unwrap();
+                if opcode.is_terminator() {
+                    pos.use_srcloc(inst);
+                    insert_epilogue(&mut pos, frame, saved_fprs, inst);
+                    let offset = pos.func.signature.tail_call_arguments_offset(
+                        &pos.func.dfg.signatures[sig_ref],
+                    );
+                    move_return_address(&mut pos, frame, i64::from(offset) + frame.gap);
+                } else {
+                    let sig = &pos.func.dfg.signatures[sig_ref];
+                    let bytes = i64::from(sig.argument_bytes.unwrap_or(0));
+                    if sig.call_conv.callee_pops() && bytes > 0 {
+                        // Restore the outgoing argument area popped by the callee. The stack map
+                        // of a safepoint describes the frame at the return address, as it was
+                        // during the call, so the adjustment goes after it.
+                        pos.use_srcloc(inst);
+                        pos.goto_after_inst(inst);
+                        if let Some(next) = pos.current_inst() {
+                            if pos.func.dfg[next].opcode() == ir::Opcode::Safepoint {
+                                pos.goto_after_inst(next);
+                            }
+                        }
+                        let adjust = pos.ins().adjust_sp_imm(-bytes);
+                        pos.goto_inst(adjust);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Insert an epilogue before the return or tail call `inst`, leaving the return address on the
+/// top of the stack.
+///
+/// The restored callee-saved registers are appended to the arguments of a return instruction.
+fn insert_epilogue(pos: &mut EncCursor, frame: &Frame, saved_fprs: &[ir::Value], inst: ir::Inst) {
+    pos.goto_inst(inst);
+    let mut restored_fprs = Vec::new();
+    for (&saved, &(reg, _)) in saved_fprs.iter().zip(&frame.fpr_csrs) {
+        let value = pos.ins().fill(saved);
+        pos.func.locations[value] = ValueLoc::Reg(reg as RegUnit);
+        restored_fprs.push(value);
+    }
+    if frame.local_size > 0 {
+        pos.ins().adjust_sp_imm(frame.local_size);
+    }
+    let mut restored = Vec::new();
+    for &reg in frame.csrs.iter().rev() {
+        let value = pos.ins().x86_pop(frame.word_type);
+        pos.func.locations[value] = ValueLoc::Reg(reg as RegUnit);
+        restored.push(value);
+    }
+    if pos.func.dfg[inst].opcode().is_return() {
+        // Return the registers in the order of the signature.
+        for value in restored.into_iter().rev().chain(restored_fprs) {
+            pos.func.dfg.append_inst_arg(inst, value);
+        }
+    }
+}
+
+/// Move the return address on the top of the stack by `offset` bytes.
+///
+/// The stack pointer is left pointing at the moved return address.
+fn move_return_address(pos: &mut EncCursor, frame: &Frame, offset: i64) {
+    if offset == 0 {
+        return;
+    }
+    let ret_addr = pos.ins().x86_pop(frame.word_type);
+    pos.func.locations[ret_addr] = ValueLoc::Reg(SCRATCH as RegUnit);
+    pos.ins().adjust_sp_imm(offset);
+    pos.ins().x86_push(ret_addr);
+}
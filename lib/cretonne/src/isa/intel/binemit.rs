@@ -1,6 +1,6 @@
 //! Emitting binary Intel machine code.
 
-use binemit::{CodeSink, Reloc, bad_encoding};
+use binemit::{CodeSink, Reloc, StackMap, bad_encoding};
 use ir::{Function, Inst, Ebb, InstructionData, Opcode};
 use ir::condcodes::{IntCC, FloatCC};
 use isa::{RegUnit, StackRef, StackBase, StackBaseMask};
@@ -19,9 +19,16 @@ pub enum RelocKind {
 
     /// An 8-byte absolute function reference.
     Abs8,
+
+    /// A 4-byte relative reference to the PLT entry of a function. Based from relocation + 4
+    /// bytes.
+    PLTRel4,
+
+    /// A 4-byte relative reference to the GOT entry of a symbol. Based from relocation + 4 bytes.
+    GOTPCRel4,
 }
 
-pub static RELOC_NAMES: [&'static str; 3] = ["PCRel4", "Abs4", "Abs8"];
+pub static RELOC_NAMES: [&'static str; 5] = ["PCRel4", "Abs4", "Abs8", "PLTRel4", "GOTPCRel4"];
 
 impl Into<Reloc> for RelocKind {
     fn into(self) -> Reloc {
@@ -40,13 +47,13 @@ fn stk_base(base: StackBase) -> RegUnit {
 }
 
 // Mandatory prefix bytes for Mp* opcodes.
-const PREFIX: [u8; 3] = [0x66, 0xf3, 0xf2];
+pub const PREFIX: [u8; 3] = [0x66, 0xf3, 0xf2];
 
 // Second byte for three-byte opcodes for mm=0b10 and mm=0b11.
-const OP3_BYTE2: [u8; 2] = [0x38, 0x3a];
+pub const OP3_BYTE2: [u8; 2] = [0x38, 0x3a];
 
 // A REX prefix with no bits set: 0b0100WRXB.
-const BASE_REX: u8 = 0b0100_0000;
+pub const BASE_REX: u8 = 0b0100_0000;
 
 // Create a single-register REX prefix, setting the B bit to bit 3 of the register.
 // This is used for instructions that encode a register in the low 3 bits of the opcode and for
@@ -200,6 +207,12 @@ fn modrm_rm<CS: CodeSink + ?Sized>(rm: RegUnit, reg: RegUnit, sink: &mut CS) {
     sink.put1(b);
 }
 
+/// Emit a mode 00 ModR/M byte with `rm` = 0b101. In 64-bit mode, this is RIP-relative addressing
+/// with a 32-bit displacement.
+fn modrm_riprel<CS: CodeSink + ?Sized>(reg: RegUnit, sink: &mut CS) {
+    modrm_rm(0b101, reg, sink)
+}
+
 /// Emit a mode 01 ModR/M byte. This is a register-indirect addressing mode with 8-bit
 /// displacement.
 /// Register %rsp is invalid for `rm`. It indicates the presence of a SIB byte.
@@ -0,0 +1,471 @@
+//! Decoding binary Intel machine code.
+//!
+//! The functions here are the inverses of the corresponding functions in `binemit.rs`. They are
+//! called from the `decode` snippets in the encoding recipes. The functions at the end of the file
+//! format AT&T assembly for the `asm` snippets.
+
+use binemit::{CodeOffset, Decoder, DecodeEntry, Operand, Cond};
+use ir::{self, Opcode};
+use ir::condcodes::{IntCC, FloatCC};
+use isa::RegUnit;
+use super::binemit::{PREFIX, OP3_BYTE2, BASE_REX};
+use super::registers::RU;
+
+include!(concat!(env!("OUT_DIR"), "/decode-intel.rs"));
+
+// Read a REX prefix, checking the W bit against `bits`.
+//
+// The R, X, and B bits are saved in `d.prefix` for the following ModR/M, SIB, and opcode bytes.
+fn rex_prefix(bits: u16, d: &mut Decoder) -> Option<()> {
+    let rex = d.get1()?;
+    let w = ((bits >> 15) & 1) as u8;
+    if rex & 0xf8 != BASE_REX | (w << 3) {
+        return None;
+    }
+    d.prefix = rex;
+    Some(())
+}
+
+// Get the REX bit at position `n` as the high bit of a 4-bit register number.
+fn rex_bit(d: &Decoder, n: u8) -> RegUnit {
+    RegUnit::from((d.prefix >> n) & 1) << 3
+}
+
+// Read a mandatory prefix byte for Mp* opcodes.
+fn mandatory_prefix(bits: u16, d: &mut Decoder) -> Option<()> {
+    let pp = (bits >> 8) & 3;
+    if pp == 0 {
+        return None;
+    }
+    d.expect1(PREFIX[(pp - 1) as usize])
+}
+
+// Read the escape bytes of a three-byte opcode.
+fn op3_escape(bits: u16, d: &mut Decoder) -> Option<()> {
+    let mm = (bits >> 10) & 3;
+    if mm < 2 {
+        return None;
+    }
+    d.expect1(0x0f)?;
+    d.expect1(OP3_BYTE2[(mm - 2) as usize])
+}
+
+// Read the final opcode byte. The bits in `low` are not part of the opcode. They are returned.
+fn opcode_byte(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    let op = d.get1()?;
+    if op & !low == bits as u8 & !low {
+        Some(op & low)
+    } else {
+        None
+    }
+}
+
+// Read a single-byte opcode with no REX prefix.
+fn get_op1(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    opcode_byte(bits, low, d)
+}
+
+// Read a single-byte opcode with REX prefix.
+fn get_rexop1(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    rex_prefix(bits, d)?;
+    opcode_byte(bits, low, d)
+}
+
+// Read a two-byte opcode: 0F XX
+fn get_op2(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    d.expect1(0x0f)?;
+    opcode_byte(bits, low, d)
+}
+
+// Read a two-byte opcode: 0F XX with REX prefix.
+fn get_rexop2(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    rex_prefix(bits, d)?;
+    d.expect1(0x0f)?;
+    opcode_byte(bits, low, d)
+}
+
+// Read a single-byte opcode with mandatory prefix.
+fn get_mp1(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    mandatory_prefix(bits, d)?;
+    opcode_byte(bits, low, d)
+}
+
+// Read a single-byte opcode with mandatory prefix and REX.
+fn get_rexmp1(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    mandatory_prefix(bits, d)?;
+    rex_prefix(bits, d)?;
+    opcode_byte(bits, low, d)
+}
+
+// Read a two-byte opcode (0F XX) with mandatory prefix.
+fn get_mp2(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    mandatory_prefix(bits, d)?;
+    d.expect1(0x0f)?;
+    opcode_byte(bits, low, d)
+}
+
+// Read a two-byte opcode (0F XX) with mandatory prefix and REX.
+fn get_rexmp2(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    mandatory_prefix(bits, d)?;
+    rex_prefix(bits, d)?;
+    d.expect1(0x0f)?;
+    opcode_byte(bits, low, d)
+}
+
+// Read a three-byte opcode (0F 3[8A] XX) with mandatory prefix.
+fn get_mp3(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    mandatory_prefix(bits, d)?;
+    op3_escape(bits, d)?;
+    opcode_byte(bits, low, d)
+}
+
+// Read a three-byte opcode (0F 3[8A] XX) with mandatory prefix and REX.
+fn get_rexmp3(bits: u16, low: u8, d: &mut Decoder) -> Option<u8> {
+    mandatory_prefix(bits, d)?;
+    rex_prefix(bits, d)?;
+    op3_escape(bits, d)?;
+    opcode_byte(bits, low, d)
+}
+
+/// Get the register encoded in the low 3 bits of an opcode, extended by REX.B.
+fn op_reg(low: u8, d: &Decoder) -> RegUnit {
+    RegUnit::from(low) | rex_bit(d, 0)
+}
+
+/// Read a ModR/M byte with the addressing mode `mode`. Return the `rm` and `reg` fields, extended
+/// by REX.B and REX.R.
+fn modrm(mode: u8, d: &mut Decoder) -> Option<(RegUnit, RegUnit)> {
+    let b = d.get1()?;
+    if b >> 6 != mode {
+        return None;
+    }
+    let rm = RegUnit::from(b & 7) | rex_bit(d, 0);
+    let reg = RegUnit::from((b >> 3) & 7) | rex_bit(d, 2);
+    Some((rm, reg))
+}
+
+/// Read a ModR/M byte for reg-reg operands.
+fn modrm_rr(d: &mut Decoder) -> Option<(RegUnit, RegUnit)> {
+    modrm(0b11, d)
+}
+
+/// Read a ModR/M byte where the reg bits are part of the opcode.
+fn modrm_r_bits(bits: u16, d: &mut Decoder) -> Option<RegUnit> {
+    let (rm, reg) = modrm(0b11, d)?;
+    if reg == (bits >> 12) & 7 {
+        Some(rm)
+    } else {
+        None
+    }
+}
+
+/// Read a mode 00 ModR/M byte for register-indirect addressing with no offset.
+fn modrm_rm(d: &mut Decoder) -> Option<(RegUnit, RegUnit)> {
+    let (rm, reg) = modrm(0b00, d)?;
+    match rm & 7 {
+        0b100 | 0b101 => None,
+        _ => Some((rm, reg)),
+    }
+}
+
+/// Read a mode 00 ModR/M byte for RIP-relative addressing. Return the `reg` field.
+fn modrm_riprel(d: &mut Decoder) -> Option<RegUnit> {
+    let (rm, reg) = modrm(0b00, d)?;
+    if rm & 7 == 0b101 { Some(reg) } else { None }
+}
+
+/// Read a mode 01 ModR/M byte for register-indirect addressing with 8-bit displacement.
+fn modrm_disp8(d: &mut Decoder) -> Option<(RegUnit, RegUnit)> {
+    let (rm, reg) = modrm(0b01, d)?;
+    if rm & 7 == 0b100 { None } else { Some((rm, reg)) }
+}
+
+/// Read a mode 10 ModR/M byte for register-indirect addressing with 32-bit displacement.
+fn modrm_disp32(d: &mut Decoder) -> Option<(RegUnit, RegUnit)> {
+    let (rm, reg) = modrm(0b10, d)?;
+    if rm & 7 == 0b100 { None } else { Some((rm, reg)) }
+}
+
+/// Read a mode 10 ModR/M byte indicating that a SIB byte is present. Return the `reg` field.
+fn modrm_sib_disp32(d: &mut Decoder) -> Option<RegUnit> {
+    let (rm, reg) = modrm(0b10, d)?;
+    if rm & 7 == 0b100 { Some(reg) } else { None }
+}
+
+/// Read a SIB byte with a base register and no scale+index. Return the base register.
+fn sib_noindex(d: &mut Decoder) -> Option<RegUnit> {
+    let b = d.get1()?;
+    // SIB        SS_III_BBB.
+    if b & 0b11_111_000 != 0b00_100_000 {
+        return None;
+    }
+    Some(RegUnit::from(b & 7) | rex_bit(d, 0))
+}
+
+/// Get the integer condition code for the low 4 bits of an opcode.
+///
+/// This is the inverse of `icc2opc`.
+fn opc2icc(opc: u8) -> Option<IntCC> {
+    use ir::condcodes::IntCC::*;
+    Some(match opc {
+        0x2 => UnsignedLessThan,
+        0x3 => UnsignedGreaterThanOrEqual,
+        0x4 => Equal,
+        0x5 => NotEqual,
+        0x6 => UnsignedLessThanOrEqual,
+        0x7 => UnsignedGreaterThan,
+        0xc => SignedLessThan,
+        0xd => SignedGreaterThanOrEqual,
+        0xe => SignedLessThanOrEqual,
+        0xf => SignedGreaterThan,
+        _ => return None,
+    })
+}
+
+/// Get the floating point condition code for the low 4 bits of an opcode.
+///
+/// This is the inverse of `fcc2opc`.
+fn opc2fcc(opc: u8) -> Option<FloatCC> {
+    use ir::condcodes::FloatCC::*;
+    Some(match opc {
+        0xb => Ordered,
+        0xa => Unordered,
+        0x5 => OrderedNotEqual,
+        0x4 => UnorderedOrEqual,
+        0x7 => GreaterThan,
+        0x3 => GreaterThanOrEqual,
+        0x2 => UnorderedOrLessThan,
+        0x6 => UnorderedOrLessThanOrEqual,
+        _ => return None,
+    })
+}
+
+/// Read a single-byte branch displacement and record the branch target, which is returned.
+fn disp1(d: &mut Decoder) -> Option<CodeOffset> {
+    let delta = d.get1()? as i8;
+    let target = d.pos().wrapping_add(delta as u32);
+    d.push(Operand::Target(target));
+    Some(target)
+}
+
+/// Read a 4-byte branch displacement and record the branch target, which is returned.
+fn disp4(d: &mut Decoder) -> Option<CodeOffset> {
+    let delta = d.get4()?;
+    let target = d.pos().wrapping_add(delta);
+    d.push(Operand::Target(target));
+    Some(target)
+}
+
+/// Get the size in bits of the integer operands of an instruction with encoding `bits`.
+fn opsize(bits: u16) -> u8 {
+    if bits & 0x8000 != 0 {
+        64
+    } else if bits & 0x0fff == 0x0088 {
+        // MOV r/m8, r8 is the only byte-sized opcode.
+        8
+    } else if (bits >> 8) & 0xf == 0b0001 {
+        // The 66 prefix without a 0F escape is the operand-size override.
+        16
+    } else {
+        32
+    }
+}
+
+/// Get the size in bits of the source operand of an extending move with encoding `bits`.
+fn src_size(bits: u16) -> u8 {
+    match bits & 0x7fff {
+        0x0063 => 32,
+        0x04b6 | 0x04be => 8,
+        0x04b7 | 0x04bf => 16,
+        _ => opsize(bits),
+    }
+}
+
+/// Get the AT&T mnemonic suffix for `size`-bit operands.
+fn suffix(size: u8) -> char {
+    match size {
+        8 => 'b',
+        16 => 'w',
+        32 => 'l',
+        _ => 'q',
+    }
+}
+
+/// Get the AT&T mnemonic suffix for pointer-sized operands.
+fn ptr_suffix(d: &Decoder) -> char {
+    if d.is_64bit() { 'q' } else { 'l' }
+}
+
+/// Get the AT&T mnemonic of the opcode in `bits`, including its operand size suffix.
+///
+/// Opcodes that encode a condition code in their low bits are not included. The recipes print
+/// those with `cc()`.
+fn mnemonic(bits: u16, d: &Decoder) -> Option<String> {
+    let sized = |name: &str| format!("{}{}", name, suffix(opsize(bits)));
+    let rex_w = bits & 0x8000 != 0;
+    let name = match bits & 0x7fff {
+        0x0001 | 0x0081 | 0x0083 => return Some(sized("add")),
+        0x0009 | 0x1081 | 0x1083 => return Some(sized("or")),
+        0x0021 | 0x4081 | 0x4083 => return Some(sized("and")),
+        0x0029 => return Some(sized("sub")),
+        0x0031 | 0x6081 | 0x6083 => return Some(sized("xor")),
+        0x0039 => return Some(sized("cmp")),
+        0x0085 | 0x00f7 => return Some(sized("test")),
+        0x0088 | 0x0089 | 0x008b | 0x00c7 | 0x0189 => return Some(sized("mov")),
+        0x00b8 if rex_w => "movabsq",
+        0x00b8 => return Some(sized("mov")),
+        0x008d => return Some(sized("lea")),
+        0x0050 => return Some(format!("push{}", ptr_suffix(d))),
+        0x0058 => return Some(format!("pop{}", ptr_suffix(d))),
+        0x0063 => "movslq",
+        0x00c3 => "ret",
+        0x00d3 => return Some(sized("rol")),
+        0x10d3 => return Some(sized("ror")),
+        0x40d3 => return Some(sized("shl")),
+        0x50d3 => return Some(sized("shr")),
+        0x70d3 => return Some(sized("sar")),
+        0x60f7 => return Some(sized("div")),
+        0x70f7 => return Some(sized("idiv")),
+        0x00e8 | 0x20ff => "call",
+        0x00e9 | 0x00eb | 0x40ff => "jmp",
+        0x040b => "ud2",
+        0x0410 | 0x0411 => "movups",
+        0x0428 => "movaps",
+        0x042e => "ucomiss",
+        0x052e => "ucomisd",
+        0x0454 => "andps",
+        0x0455 => "andnps",
+        0x0456 => "orps",
+        0x0457 => "xorps",
+        0x04af => return Some(sized("imul")),
+        0x04b6 => return Some(sized("movzb")),
+        0x04b7 => return Some(sized("movzw")),
+        0x04bc => return Some(sized("bsf")),
+        0x04bd => return Some(sized("bsr")),
+        0x04be => return Some(sized("movsb")),
+        0x04bf => return Some(sized("movsw")),
+        0x056e | 0x057e if rex_w => "movq",
+        0x056e | 0x057e => "movd",
+        0x05d6 | 0x067e => "movq",
+        0x062a => return Some(sized("cvtsi2ss")),
+        0x072a => return Some(sized("cvtsi2sd")),
+        0x062c => "cvttss2si",
+        0x072c => "cvttsd2si",
+        0x065a => "cvtss2sd",
+        0x075a => "cvtsd2ss",
+        0x0651 => "sqrtss",
+        0x0751 => "sqrtsd",
+        0x0658 => "addss",
+        0x0758 => "addsd",
+        0x0659 => "mulss",
+        0x0759 => "mulsd",
+        0x065c => "subss",
+        0x075c => "subsd",
+        0x065d => "minss",
+        0x075d => "minsd",
+        0x065e => "divss",
+        0x075e => "divsd",
+        0x065f => "maxss",
+        0x075f => "maxsd",
+        0x06b8 => return Some(sized("popcnt")),
+        0x06bc => return Some(sized("tzcnt")),
+        0x06bd => return Some(sized("lzcnt")),
+        0x0d0a => "roundss",
+        0x0d0b => "roundsd",
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// Get the AT&T condition code suffix of a `Jcc` or `SETcc` opcode with the low 4 bits `low`.
+fn cc(low: u8) -> &'static str {
+    const NAMES: [&str; 16] = [
+        "o",
+        "no",
+        "b",
+        "ae",
+        "e",
+        "ne",
+        "be",
+        "a",
+        "s",
+        "ns",
+        "p",
+        "np",
+        "l",
+        "ge",
+        "le",
+        "g",
+    ];
+    NAMES[(low & 0xf) as usize]
+}
+
+/// Get the AT&T name of the general purpose register `reg` with `size` bits.
+fn gpr(d: &Decoder, size: u8, reg: RegUnit) -> String {
+    const WORDS: [&str; 8] = ["ax", "cx", "dx", "bx", "sp", "bp", "si", "di"];
+    const BYTES: [&str; 8] = ["al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil"];
+    const HIGH_BYTES: [&str; 4] = ["ah", "ch", "dh", "bh"];
+    let r = reg as usize;
+    if r >= 8 {
+        let suffix = match size {
+            8 => "b",
+            16 => "w",
+            32 => "d",
+            _ => "",
+        };
+        return format!("%r{}{}", r, suffix);
+    }
+    match size {
+        // Without a REX prefix, registers 4-7 are the high bytes of the ABCD registers.
+        8 if d.prefix == 0 && r >= 4 => format!("%{}", HIGH_BYTES[r - 4]),
+        8 => format!("%{}", BYTES[r]),
+        16 => format!("%{}", WORDS[r]),
+        32 => format!("%e{}", WORDS[r]),
+        _ => format!("%r{}", WORDS[r]),
+    }
+}
+
+/// Get the AT&T name of the register `reg` as an integer operand of the instruction `bits`.
+fn int_reg(d: &Decoder, bits: u16, reg: RegUnit) -> String {
+    gpr(d, opsize(bits), reg)
+}
+
+/// Get the AT&T name of the register `reg` as the source operand of the extending move `bits`.
+fn src_reg(d: &Decoder, bits: u16, reg: RegUnit) -> String {
+    gpr(d, src_size(bits), reg)
+}
+
+/// Get the AT&T name of the pointer-sized register `reg`.
+fn ptr_reg(d: &Decoder, reg: RegUnit) -> String {
+    gpr(d, if d.is_64bit() { 64 } else { 32 }, reg)
+}
+
+/// Get the AT&T name of the XMM register `reg`.
+fn xmm(reg: RegUnit) -> String {
+    format!("%xmm{}", reg)
+}
+
+/// Get an AT&T memory operand addressing `base` plus `disp`.
+fn addr(d: &Decoder, base: RegUnit, disp: i32) -> String {
+    if disp == 0 {
+        format!("({})", ptr_reg(d, base))
+    } else {
+        format!("{}({})", disp, ptr_reg(d, base))
+    }
+}
+
+/// Get a branch target operand.
+fn target(offset: CodeOffset) -> String {
+    format!("{:#x}", offset)
+}
+
+/// Print the instruction `bits` with AT&T `operands`, sources first.
+fn asm_inst(bits: u16, operands: &[String], d: &mut Decoder) -> Option<()> {
+    let op = mnemonic(bits, d)?;
+    if operands.is_empty() {
+        d.asm(op);
+    } else {
+        d.asm(format!("{} {}", op, operands.join(", ")));
+    }
+    Some(())
+}
@@ -3,22 +3,26 @@
 pub mod settings;
 mod abi;
 mod binemit;
+mod decode;
 mod enc_tables;
 mod registers;
 
-use binemit::{CodeSink, MemoryCodeSink, emit_function};
+use binemit::{CodeSink, CodeOffset, MemoryCodeSink, DecodeEntry, DecodedInst};
+use binemit::{emit_function, decode_inst};
 use super::super::settings as shared_settings;
 use isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use isa::Builder as IsaBuilder;
 use isa::{TargetIsa, RegInfo, RegClass, EncInfo};
 use ir;
 use regalloc;
+use result;
 
 #[allow(dead_code)]
 struct Isa {
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
+    decode_table: &'static [DecodeEntry],
 }
 
 /// Get an ISA builder for creating Intel targets.
@@ -33,15 +37,16 @@ fn isa_constructor(
     shared_flags: shared_settings::Flags,
     builder: &shared_settings::Builder,
 ) -> Box<TargetIsa> {
-    let level1 = if shared_flags.is_64bit() {
-        &enc_tables::LEVEL1_I64[..]
+    let (level1, decode_table) = if shared_flags.is_64bit() {
+        (&enc_tables::LEVEL1_I64[..], &decode::DECODE_I64[..])
     } else {
-        &enc_tables::LEVEL1_I32[..]
+        (&enc_tables::LEVEL1_I32[..], &decode::DECODE_I32[..])
     };
     Box::new(Isa {
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
         cpumode: level1,
+        decode_table,
     })
 }
 
@@ -94,6 +99,10 @@ impl TargetIsa for Isa {
         abi::allocatable_registers(func, &self.shared_flags)
     }
 
+    fn prologue_epilogue(&self, func: &mut ir::Function) -> result::CtonResult {
+        abi::prologue_epilogue(func, self)
+    }
+
     fn emit_inst(
         &self,
         func: &ir::Function,
@@ -111,4 +120,15 @@ impl TargetIsa for Isa {
     fn reloc_names(&self) -> &'static [&'static str] {
         &binemit::RELOC_NAMES
     }
+
+    fn decode_inst(&self, code: &[u8], offset: CodeOffset) -> Option<DecodedInst> {
+        decode_inst(
+            code,
+            offset,
+            self.shared_flags.is_64bit(),
+            self.decode_table,
+            &enc_tables::INFO,
+            decode::decode_recipe,
+        )
+    }
 }
@@ -146,7 +146,9 @@ pub type Legalize = fn(ir::Inst,
                        -> bool;
 
 /// Methods that are specialized to a target ISA.
-pub trait TargetIsa {
+///
+/// An ISA instance is immutable, so it can be shared by compilation threads.
+pub trait TargetIsa: Sync {
     /// Get the name of this ISA.
     fn name(&self) -> &'static str;
 
@@ -276,4 +278,16 @@ pub trait TargetIsa {
     /// This array can be indexed by the contents of `binemit::Reloc` objects passed to a
     /// `CodeSink`.
     fn reloc_names(&self) -> &'static [&'static str];
+
+    /// Decode the instruction at `offset` in `code`, which was emitted by this ISA.
+    ///
+    /// Returns `None` if the machine code doesn't match any of the encodings in the current CPU
+    /// mode, or if the ISA doesn't support decoding.
+    fn decode_inst(
+        &self,
+        _code: &[u8],
+        _offset: binemit::CodeOffset,
+    ) -> Option<binemit::DecodedInst> {
+        None
+    }
 }
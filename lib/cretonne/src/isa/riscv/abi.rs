@@ -3,9 +3,10 @@
 //! This module implements the RISC-V calling convention through the primary `legalize_signature()`
 //! entry point.
 //!
-//! This doesn't support the soft-float ABI at the moment.
+//! This doesn't support the soft-float ABI at the moment. Structs passed by value always use the
+//! integer registers, the hard-float rules for small structs of floats are not implemented.
 
-use abi::{ArgAction, ValueConversion, ArgAssigner, legalize_args};
+use abi::{ArgAction, ValueConversion, ArgAssigner, legalize_args, legalize_params};
 use ir::{self, Type, AbiParam, ArgumentLoc, ArgumentExtension, ArgumentPurpose};
 use isa::RegClass;
 use regalloc::AllocatableSet;
@@ -20,6 +21,8 @@ struct Args {
     regs: u32,
     reg_limit: u32,
     offset: u32,
+    /// Are we assigning the variadic arguments of a call?
+    variadic: bool,
 }
 
 impl Args {
@@ -31,6 +34,7 @@ impl Args {
             regs: 0,
             reg_limit: if enable_e { 6 } else { 8 },
             offset: 0,
+            variadic: false,
         }
     }
 }
@@ -43,12 +47,30 @@ impl ArgAssigner for Args {
 
         let ty = arg.value_type;
 
+        // Structs of up to 2 x XLEN are passed in integer registers like a large integer. Larger
+        // structs are passed by reference.
+        if let ArgumentPurpose::StructArgument(sa) = arg.purpose {
+            if sa.size <= 2 * self.pointer_bytes {
+                if sa.align == 2 * self.pointer_bytes {
+                    self.regs = align(self.regs, 2);
+                    self.offset = align(self.offset, 2 * self.pointer_bytes);
+                }
+                return ArgAction::SplitStruct(false);
+            }
+        }
+
         // Check for a legal type.
         // RISC-V doesn't have SIMD at all, so break all vectors down.
         if ty.is_vector() {
             return ValueConversion::VectorSplit.into();
         }
 
+        // Variadic floating point arguments are passed in integer registers, so a 2 x XLEN `f64`
+        // ends up in an aligned register pair below.
+        if self.variadic && ty.is_float() {
+            return ValueConversion::IntBits.into();
+        }
+
         // Large integers and booleans are broken down to fit in a register.
         if !ty.is_float() && ty.bits() > self.pointer_bits {
             // Align registers and stack to a multiple of two pointers.
@@ -95,7 +117,7 @@ pub fn legalize_signature(
     let bits = if flags.is_64bit() { 64 } else { 32 };
 
     let mut args = Args::new(bits, isa_flags.enable_e());
-    legalize_args(&mut sig.params, &mut args);
+    legalize_params(sig, &mut args, |args| args.variadic = true);
 
     let mut rets = Args::new(bits, isa_flags.enable_e());
     legalize_args(&mut sig.returns, &mut rets);
@@ -1,6 +1,6 @@
 //! Emitting binary RISC-V machine code.
 
-use binemit::{CodeSink, Reloc, bad_encoding};
+use binemit::{CodeSink, Reloc, StackMap, bad_encoding};
 use ir::{Function, Inst, InstructionData};
 use isa::{RegUnit, StackRef, StackBaseMask};
 use predicates::is_signed_int;
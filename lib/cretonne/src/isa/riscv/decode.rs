@@ -0,0 +1,176 @@
+//! Decoding binary RISC-V machine code.
+//!
+//! The functions here are the inverses of the corresponding functions in `binemit.rs`. They are
+//! called from the `decode` snippets in the encoding recipes. The functions at the end of the file
+//! format assembly for the `asm` snippets.
+
+use binemit::{CodeOffset, Decoder, DecodeEntry, Operand, Cond};
+use ir::{self, Opcode};
+use isa::RegUnit;
+
+include!(concat!(env!("OUT_DIR"), "/decode-riscv.rs"));
+
+/// Read a 32-bit instruction word, checking the base opcode and `funct3` against `bits`.
+fn get_word(bits: u16, funct3: bool, d: &mut Decoder) -> Option<u32> {
+    let bits = bits as u32;
+    let opcode5 = bits & 0x1f;
+    let i = d.get4()?;
+    if i & 0x7f != 0x3 | (opcode5 << 2) {
+        return None;
+    }
+    if funct3 && (i >> 12) & 0x7 != (bits >> 5) & 0x7 {
+        return None;
+    }
+    Some(i)
+}
+
+/// Get the register field at bit `shift` of an instruction word.
+fn reg(i: u32, shift: u32) -> RegUnit {
+    ((i >> shift) & 0x1f) as RegUnit
+}
+
+/// R-type instructions.
+///
+/// Returns `(rs1, rs2, rd)`.
+fn get_r(bits: u16, d: &mut Decoder) -> Option<(RegUnit, RegUnit, RegUnit)> {
+    let i = get_word(bits, true, d)?;
+    let funct7 = (bits as u32 >> 8) & 0x7f;
+    if i >> 25 != funct7 {
+        return None;
+    }
+    Some((reg(i, 15), reg(i, 20), reg(i, 7)))
+}
+
+/// R-type instructions with a shift amount instead of rs2.
+///
+/// Returns `(rs1, shamt, rd)`.
+fn get_rshamt(bits: u16, d: &mut Decoder) -> Option<(RegUnit, i64, RegUnit)> {
+    let i = get_word(bits, true, d)?;
+    // Bit 25 belongs to the shift amount in RV64.
+    let funct7 = (bits as u32 >> 8) & 0x7f;
+    if i >> 26 != funct7 >> 1 {
+        return None;
+    }
+    Some((reg(i, 15), i64::from((i >> 20) & 0x3f), reg(i, 7)))
+}
+
+/// I-type instructions.
+///
+/// Returns `(rs1, imm, rd)`.
+fn get_i(bits: u16, d: &mut Decoder) -> Option<(RegUnit, i64, RegUnit)> {
+    let i = get_word(bits, true, d)?;
+    let imm = (i as i32) >> 20;
+    Some((reg(i, 15), i64::from(imm), reg(i, 7)))
+}
+
+/// U-type instructions.
+///
+/// Returns `(imm, rd)`.
+fn get_u(bits: u16, d: &mut Decoder) -> Option<(i64, RegUnit)> {
+    let i = get_word(bits, false, d)?;
+    let imm = (i & 0xfffff000) as i32;
+    Some((i64::from(imm), reg(i, 7)))
+}
+
+/// SB-type branch instructions.
+///
+/// Returns `(imm, rs1, rs2)`.
+fn get_sb(bits: u16, d: &mut Decoder) -> Option<(i64, RegUnit, RegUnit)> {
+    let i = get_word(bits, true, d)?;
+
+    // The displacement is completely hashed up.
+    let mut imm = 0;
+    imm |= ((i >> 7) & 0x1) << 11;
+    imm |= ((i >> 8) & 0xf) << 1;
+    imm |= ((i >> 25) & 0x3f) << 5;
+    imm |= ((i >> 31) & 0x1) << 12;
+
+    // Sign-extend from 13 bits.
+    let imm = ((imm << 19) as i32) >> 19;
+    Some((i64::from(imm), reg(i, 15), reg(i, 20)))
+}
+
+/// UJ-type jump instructions.
+///
+/// Returns `(imm, rd)`.
+fn get_uj(bits: u16, d: &mut Decoder) -> Option<(i64, RegUnit)> {
+    let i = get_word(bits, false, d)?;
+
+    // The displacement is completely hashed up.
+    let mut imm = 0;
+    imm |= i & 0xff000;
+    imm |= ((i >> 20) & 0x1) << 11;
+    imm |= ((i >> 21) & 0x3ff) << 1;
+    imm |= ((i >> 31) & 0x1) << 20;
+
+    // Sign-extend from 21 bits.
+    let imm = ((imm << 11) as i32) >> 11;
+    Some((i64::from(imm), reg(i, 7)))
+}
+
+/// Get the assembler mnemonic of the instruction with encoding `bits`.
+fn mnemonic(bits: u16) -> Option<&'static str> {
+    let opcode5 = bits & 0x1f;
+    let funct3 = (bits >> 5) & 0x7;
+    let funct7 = (bits >> 8) & 0x7f;
+    let name = match (opcode5, funct3, funct7) {
+        (0b01100, 0b000, 0b0000000) => "add",
+        (0b01100, 0b000, 0b0100000) => "sub",
+        (0b01100, 0b000, 0b0000001) => "mul",
+        (0b01100, 0b001, 0b0000000) => "sll",
+        (0b01100, 0b010, 0b0000000) => "slt",
+        (0b01100, 0b011, 0b0000000) => "sltu",
+        (0b01100, 0b100, 0b0000000) => "xor",
+        (0b01100, 0b101, 0b0000000) => "srl",
+        (0b01100, 0b101, 0b0100000) => "sra",
+        (0b01100, 0b110, 0b0000000) => "or",
+        (0b01100, 0b111, 0b0000000) => "and",
+        (0b01110, 0b000, 0b0000000) => "addw",
+        (0b01110, 0b000, 0b0100000) => "subw",
+        (0b01110, 0b000, 0b0000001) => "mulw",
+        (0b01110, 0b001, 0b0000000) => "sllw",
+        (0b01110, 0b101, 0b0000000) => "srlw",
+        (0b01110, 0b101, 0b0100000) => "sraw",
+        (0b00100, 0b000, _) => "addi",
+        (0b00100, 0b001, _) => "slli",
+        (0b00100, 0b010, _) => "slti",
+        (0b00100, 0b011, _) => "sltiu",
+        (0b00100, 0b100, _) => "xori",
+        (0b00100, 0b101, 0b0000000) => "srli",
+        (0b00100, 0b101, 0b0100000) => "srai",
+        (0b00100, 0b110, _) => "ori",
+        (0b00100, 0b111, _) => "andi",
+        (0b00110, 0b000, _) => "addiw",
+        (0b00110, 0b001, _) => "slliw",
+        (0b00110, 0b101, 0b0000000) => "srliw",
+        (0b00110, 0b101, 0b0100000) => "sraiw",
+        (0b11000, 0b000, _) => "beq",
+        (0b11000, 0b001, _) => "bne",
+        (0b11000, 0b100, _) => "blt",
+        (0b11000, 0b101, _) => "bge",
+        (0b11000, 0b110, _) => "bltu",
+        (0b11000, 0b111, _) => "bgeu",
+        (0b11001, 0b000, _) => "jalr",
+        (0b11011, _, _) => "jal",
+        (0b01101, _, _) => "lui",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Get the assembler name of the integer register `reg`.
+fn xreg(reg: RegUnit) -> String {
+    format!("x{}", reg)
+}
+
+/// Get a branch target operand.
+fn target(offset: CodeOffset) -> String {
+    format!("{:#x}", offset)
+}
+
+/// Print the instruction `bits` with `operands`, destination first.
+fn asm_inst(bits: u16, operands: &[String], d: &mut Decoder) -> Option<()> {
+    let op = mnemonic(bits)?;
+    d.asm(format!("{} {}", op, operands.join(", ")));
+    Some(())
+}
@@ -3,11 +3,13 @@
 pub mod settings;
 mod abi;
 mod binemit;
+mod decode;
 mod enc_tables;
 mod registers;
 
 use super::super::settings as shared_settings;
-use binemit::{CodeSink, MemoryCodeSink, emit_function};
+use binemit::{CodeSink, CodeOffset, MemoryCodeSink, DecodeEntry, DecodedInst};
+use binemit::{emit_function, decode_inst};
 use isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use isa::Builder as IsaBuilder;
 use isa::{TargetIsa, RegInfo, RegClass, EncInfo};
@@ -19,6 +21,7 @@ struct Isa {
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
+    decode_table: &'static [DecodeEntry],
 }
 
 /// Get an ISA builder for creating RISC-V targets.
@@ -33,15 +36,16 @@ fn isa_constructor(
     shared_flags: shared_settings::Flags,
     builder: &shared_settings::Builder,
 ) -> Box<TargetIsa> {
-    let level1 = if shared_flags.is_64bit() {
-        &enc_tables::LEVEL1_RV64[..]
+    let (level1, decode_table) = if shared_flags.is_64bit() {
+        (&enc_tables::LEVEL1_RV64[..], &decode::DECODE_RV64[..])
     } else {
-        &enc_tables::LEVEL1_RV32[..]
+        (&enc_tables::LEVEL1_RV32[..], &decode::DECODE_RV32[..])
     };
     Box::new(Isa {
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
         cpumode: level1,
+        decode_table,
     })
 }
 
@@ -111,6 +115,17 @@ impl TargetIsa for Isa {
     fn reloc_names(&self) -> &'static [&'static str] {
         &binemit::RELOC_NAMES
     }
+
+    fn decode_inst(&self, code: &[u8], offset: CodeOffset) -> Option<DecodedInst> {
+        decode_inst(
+            code,
+            offset,
+            self.shared_flags.is_64bit(),
+            self.decode_table,
+            &enc_tables::INFO,
+            decode::decode_recipe,
+        )
+    }
 }
 
 #[cfg(test)]
@@ -14,15 +14,27 @@
 //! 2. The `handle_call_abi` and `handle_return_abi` functions rewrite call and return instructions
 //!    to match the new ABI signatures.
 //!
+//! When a signature has more return values than the ABI has return registers, the legalized
+//! signature returns them in memory instead. The caller passes a pointer to a stack slot in an
+//! `sret` argument, and the callee stores the return values there.
+//!
+//! Struct arguments passed by value are expanded already in the first phase, since the call
+//! arguments can't tell if that has happened. The caller loads the pieces of the struct, or makes a
+//! copy when the struct is passed by reference. The callee rebuilds a pointer to the struct from
+//! the pieces it receives.
+//!
 //! Between the two phases, preamble signatures and call/return arguments don't match. This
 //! intermediate state doesn't type check.
 
 use abi::{legalize_abi_value, ValueConversion};
 use cursor::{Cursor, FuncCursor};
+use entity::EntityMap;
 use flowgraph::ControlFlowGraph;
 use ir::{Function, DataFlowGraph, Inst, InstBuilder, Ebb, Type, Value, Signature, SigRef,
-         AbiParam, ArgumentPurpose, ArgumentLoc, ValueLoc};
+         AbiParam, ArgumentPurpose, ArgumentLoc, ValueLoc, MemFlags, StackSlotData,
+         StackSlotKind, StackSlot, StructArg};
 use ir::instructions::CallInfo;
+use ir::types::I32;
 use isa::TargetIsa;
 use legalizer::split::{isplit, vsplit};
 
@@ -32,6 +44,21 @@ use legalizer::split::{isplit, vsplit};
 /// change the entry block arguments, calls, or return instructions, so this can leave the function
 /// in a state with type discrepancies.
 pub fn legalize_signatures(func: &mut Function, isa: &TargetIsa) {
+    // Remember the positions of the struct arguments before the signatures change.
+    let mut struct_params = EntityMap::new();
+    for sig in func.dfg.signatures.keys() {
+        struct_params[sig] = func.dfg.signatures[sig]
+            .params
+            .iter()
+            .enumerate()
+            .filter(|&(_, p)| match p.purpose {
+                ArgumentPurpose::StructArgument(_) => true,
+                _ => false,
+            })
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+    }
+
     isa.legalize_signature(&mut func.signature, true);
     func.signature.compute_argument_bytes();
     for sig in func.dfg.signatures.keys() {
@@ -43,6 +70,191 @@ pub fn legalize_signatures(func: &mut Function, isa: &TargetIsa) {
         legalize_entry_params(func, entry);
         spill_entry_params(func, entry);
     }
+
+    if func.dfg.signatures.keys().any(
+        |sig| !struct_params[sig].is_empty(),
+    )
+    {
+        legalize_struct_calls(func, &struct_params);
+    }
+}
+
+/// How a struct argument is passed by a legalized signature.
+#[derive(Clone)]
+enum StructPassing {
+    /// A pointer to a copy of the struct is passed.
+    Reference(StructArg),
+    /// The struct is passed in pieces of the given types.
+    Pieces(StructArg, Vec<Type>),
+}
+
+/// Get the struct arguments of the legalized signature `sig` in order.
+fn struct_passing(sig: &Signature) -> Vec<StructPassing> {
+    let mut structs = Vec::new();
+    let mut params = sig.params.iter();
+    while let Some(param) = params.next() {
+        match param.purpose {
+            ArgumentPurpose::StructArgument(sa) => structs.push(StructPassing::Reference(sa)),
+            ArgumentPurpose::StructPart(sa) => {
+                let mut types = vec![param.value_type];
+                let mut offset = param.value_type.bytes();
+                while offset < sa.size {
+                    let piece = params.next().expect("Incomplete struct argument");
+                    debug_assert_eq!(piece.purpose, param.purpose);
+                    types.push(piece.value_type);
+                    offset += piece.value_type.bytes();
+                }
+                structs.push(StructPassing::Pieces(sa, types));
+            }
+            _ => {}
+        }
+    }
+    structs
+}
+
+/// Get the memory flags for accessing a struct passed by value.
+fn struct_flags() -> MemFlags {
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+    flags
+}
+
+/// Create a local stack slot for a copy of a `size`-byte struct aligned to `align`.
+fn struct_copy_slot(func: &mut Function, size: u32, align: u32) -> StackSlot {
+    // The alignment of a local stack slot follows from its size.
+    let size = (size + align - 1) & !(align - 1);
+    func.create_stack_slot(StackSlotData::new(StackSlotKind::Local, size))
+}
+
+/// Load a struct piece of type `ty` covering `bytes` bytes at `offset` from `ptr`.
+fn load_struct_piece(
+    pos: &mut FuncCursor,
+    ty: Type,
+    ptr: Value,
+    offset: u32,
+    bytes: u32,
+) -> Value {
+    if bytes == ty.bytes() {
+        return pos.ins().load(ty, struct_flags(), ptr, offset as i32);
+    }
+
+    // The last piece of a struct may cover 3, 5, 6, or 7 bytes, so it can't be loaded directly.
+    // Assemble it from smaller loads instead.
+    let mut value = None;
+    let mut done = 0;
+    for &size in &[4, 2, 1] {
+        if bytes - done < size {
+            continue;
+        }
+        let at = (offset + done) as i32;
+        let part = match size {
+            4 => pos.ins().uload32(struct_flags(), ptr, at),
+            2 => pos.ins().uload16(ty, struct_flags(), ptr, at),
+            _ => pos.ins().uload8(ty, struct_flags(), ptr, at),
+        };
+        value = Some(match value {
+            None => part,
+            Some(v) => {
+                let part = pos.ins().ishl_imm(part, i64::from(done * 8));
+                pos.ins().bor(v, part)
+            }
+        });
+        done += size;
+    }
+    value.unwrap()
+}
+
+/// Copy a `bytes`-byte word at `offset` from `src` to `dst`.
+fn copy_struct_word(pos: &mut FuncCursor, dst: Value, src: Value, offset: i32, bytes: u32) {
+    let flags = struct_flags();
+    match bytes {
+        1 => {
+            let word = pos.ins().uload8(I32, flags, src, offset);
+            pos.ins().istore8(flags, word, dst, offset);
+        }
+        2 => {
+            let word = pos.ins().uload16(I32, flags, src, offset);
+            pos.ins().istore16(flags, word, dst, offset);
+        }
+        _ => {
+            let ty = Type::int(bytes as u16 * 8).unwrap();
+            let word = pos.ins().load(ty, flags, src, offset);
+            pos.ins().store(flags, word, dst, offset);
+        }
+    }
+}
+
+/// Expand the struct arguments of all the calls in `func`.
+///
+/// The indexes of the struct parameters in the original signatures are in `struct_params`.
+fn legalize_struct_calls(func: &mut Function, struct_params: &EntityMap<SigRef, Vec<usize>>) {
+    let mut structs = EntityMap::new();
+    for sig in func.dfg.signatures.keys() {
+        structs[sig] = struct_passing(&func.dfg.signatures[sig]);
+    }
+
+    let mut pos = FuncCursor::new(func);
+    while let Some(_ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            if let Some(sig) = pos.func.dfg.call_signature(inst) {
+                if !struct_params[sig].is_empty() {
+                    legalize_struct_call(&mut pos, &struct_params[sig], &structs[sig]);
+                }
+            }
+        }
+    }
+}
+
+/// Expand the struct arguments of the call at `pos`.
+///
+/// A struct passed in pieces is loaded through the pointer argument. A struct passed by reference
+/// is copied, and the call gets a pointer to the copy instead.
+fn legalize_struct_call(pos: &mut FuncCursor, params: &[usize], structs: &[StructPassing]) {
+    let call = pos.current_inst().expect(
+        "Cursor must point to a call instruction",
+    );
+    let fixed_values = pos.func.dfg[call]
+        .opcode()
+        .constraints()
+        .fixed_value_arguments();
+    let mut vlist = pos.func.dfg[call].take_value_list().expect(
+        "Call must have a value list",
+    );
+
+    // Number of arguments inserted before the current one.
+    let mut inserted = 0;
+    for (&param, passing) in params.iter().zip(structs) {
+        let idx = fixed_values + param + inserted;
+        let ptr = vlist.get(idx, &pos.func.dfg.value_lists).unwrap();
+        let ptr_type = pos.func.dfg.value_type(ptr);
+        match *passing {
+            StructPassing::Reference(sa) => {
+                let ss = struct_copy_slot(pos.func, sa.size, sa.align.max(ptr_type.bytes()));
+                let copy = pos.ins().stack_addr(ptr_type, ss, 0);
+                let mut offset = 0;
+                while offset < sa.size {
+                    let bytes = ptr_type.bytes().min(sa.size - offset);
+                    let bytes = 1 << (31 - bytes.leading_zeros());
+                    copy_struct_word(pos, copy, ptr, offset as i32, bytes);
+                    offset += bytes;
+                }
+                vlist.as_mut_slice(&mut pos.func.dfg.value_lists)[idx] = copy;
+            }
+            StructPassing::Pieces(sa, ref types) => {
+                vlist.grow_at(idx + 1, types.len() - 1, &mut pos.func.dfg.value_lists);
+                let mut offset = 0;
+                for (i, &ty) in types.iter().enumerate() {
+                    let bytes = ty.bytes().min(sa.size - offset);
+                    let piece = load_struct_piece(pos, ty, ptr, offset, bytes);
+                    vlist.as_mut_slice(&mut pos.func.dfg.value_lists)[idx + i] = piece;
+                    offset += ty.bytes();
+                }
+                inserted += types.len() - 1;
+            }
+        }
+    }
+
+    pos.func.dfg[call].put_value_list(vlist);
 }
 
 /// Legalize the entry block parameters after `func`'s signature has been legalized.
@@ -58,6 +270,7 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
     let mut has_link = false;
     let mut has_vmctx = false;
     let mut has_sigid = false;
+    let mut has_fregs = false;
 
     // Insert position for argument conversion code.
     // We want to insert instructions before the first instruction in the entry block.
@@ -76,7 +289,9 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
 
         let abi_type = pos.func.signature.params[abi_arg];
         let arg_type = pos.func.dfg.value_type(arg);
-        if arg_type == abi_type.value_type {
+        if let ArgumentPurpose::StructPart(sa) = abi_type.purpose {
+            abi_arg = legalize_struct_param(&mut pos, entry, arg, sa, abi_arg);
+        } else if arg_type == abi_type.value_type {
             // No value translation is necessary, this argument matches the ABI type.
             // Just use the original EBB argument value. This is the most common case.
             pos.func.dfg.attach_ebb_param(entry, arg);
@@ -94,6 +309,8 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
                     assert!(!has_sigid, "Multiple sigid arguments found");
                     has_sigid = true;
                 }
+                // A struct passed by reference is already a pointer to our own copy.
+                ArgumentPurpose::StructArgument(_) => {}
                 _ => panic!("Unexpected special-purpose arg {}", abi_type),
             }
             abi_arg += 1;
@@ -125,7 +342,9 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
     for &arg in &pos.func.signature.params[abi_arg..] {
         match arg.purpose {
             // Any normal parameters should have been processed above.
-            ArgumentPurpose::Normal => {
+            ArgumentPurpose::Normal |
+            ArgumentPurpose::StructArgument(_) |
+            ArgumentPurpose::StructPart(_) => {
                 panic!("Leftover arg: {}", arg);
             }
             // The callee-save parameters should not appear until after register allocation is
@@ -151,6 +370,10 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
                 assert!(!has_sigid, "Multiple sigid parameters found");
                 has_sigid = true;
             }
+            ArgumentPurpose::FloatRegCount => {
+                assert!(!has_fregs, "Multiple fregs parameters found");
+                has_fregs = true;
+            }
         }
 
         // Just create entry block values to match here. We will use them in `handle_return_abi()`
@@ -159,6 +382,56 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
     }
 }
 
+/// Compute the struct argument `arg` from the `StructPart` parameters starting at `first`.
+///
+/// A struct passed entirely on the stack is used in place through an `IncomingArg` stack slot.
+/// Otherwise, the pieces are stored in a local copy of the struct.
+///
+/// Returns the index of the first ABI parameter after the struct.
+fn legalize_struct_param(
+    pos: &mut FuncCursor,
+    entry: Ebb,
+    arg: Value,
+    sa: StructArg,
+    first: usize,
+) -> usize {
+    let mut pieces = Vec::new();
+    let mut offset = 0;
+    let mut next = first;
+    while offset < sa.size {
+        let abi = pos.func.signature.params[next];
+        debug_assert_eq!(abi.purpose, ArgumentPurpose::StructPart(sa));
+        let piece = pos.func.dfg.append_ebb_param(entry, abi.value_type);
+        pieces.push((piece, offset, abi.location));
+        offset += abi.value_type.bytes();
+        next += 1;
+    }
+
+    let ptr_type = pos.func.dfg.value_type(arg);
+    let in_memory = pieces.iter().all(|&(_, offset, loc)| match (loc, pieces[0].2) {
+        (ArgumentLoc::Stack(at), ArgumentLoc::Stack(base)) => at == base + offset as i32,
+        _ => false,
+    });
+    let ss = if in_memory {
+        let mut data = StackSlotData::new(StackSlotKind::IncomingArg, sa.size);
+        if let ArgumentLoc::Stack(base) = pieces[0].2 {
+            data.offset = base;
+        }
+        pos.func.create_stack_slot(data)
+    } else {
+        let word = pos.func.dfg.value_type(pieces[0].0).bytes();
+        let ss = struct_copy_slot(pos.func, offset, sa.align.max(word));
+        ss
+    };
+    pos.ins().with_results([Some(arg)]).stack_addr(ptr_type, ss, 0);
+    if !in_memory {
+        for &(piece, offset, _) in &pieces {
+            pos.ins().store(struct_flags(), piece, arg, offset as i32);
+        }
+    }
+    next
+}
+
 /// Legalize the results returned from a call instruction to match the ABI signature.
 ///
 /// The cursor `pos` points to a call instruction with at least one return value. The cursor will
@@ -363,6 +636,8 @@ fn check_arg_types(dfg: &DataFlowGraph, args: &[Value], types: &[AbiParam]) -> b
 
 /// Check if the arguments of the call `inst` match the signature.
 ///
+/// Tail calls don't have results, so only their arguments are checked.
+///
 /// Returns `Ok(())` if the signature matches and no changes are needed, or `Err(sig_ref)` if the
 /// signature doesn't match.
 fn check_call_signature(dfg: &DataFlowGraph, inst: Inst) -> Result<(), SigRef> {
@@ -375,7 +650,8 @@ fn check_call_signature(dfg: &DataFlowGraph, inst: Inst) -> Result<(), SigRef> {
     let sig = &dfg.signatures[sig_ref];
 
     if check_arg_types(dfg, args, &sig.params[..]) &&
-        check_arg_types(dfg, dfg.inst_results(inst), &sig.returns[..])
+        (is_tail_call(dfg, inst) ||
+             check_arg_types(dfg, dfg.inst_results(inst), &sig.returns[..]))
     {
         // All types check out.
         Ok(())
@@ -385,6 +661,12 @@ fn check_call_signature(dfg: &DataFlowGraph, inst: Inst) -> Result<(), SigRef> {
     }
 }
 
+/// Is `inst` a tail call?
+fn is_tail_call(dfg: &DataFlowGraph, inst: Inst) -> bool {
+    let opcode = dfg[inst].opcode();
+    opcode.is_call() && opcode.is_terminator()
+}
+
 /// Check if the arguments of the return `inst` match the signature.
 fn check_return_signature(dfg: &DataFlowGraph, inst: Inst, sig: &Signature) -> bool {
     check_arg_types(dfg, dfg.inst_variable_args(inst), &sig.returns)
@@ -497,13 +779,37 @@ pub fn handle_call_abi(mut inst: Inst, func: &mut Function, cfg: &ControlFlowGra
         Err(s) => s,
     };
 
+    // Values returned in memory are loaded from a stack slot after the call. A tail call passes
+    // on the memory it was given by its own caller instead.
+    let tail_call = is_tail_call(&pos.func.dfg, inst);
+    if returns_in_memory(&pos.func.dfg.signatures[sig_ref]) {
+        if tail_call {
+            legalize_sret_tail_call(pos);
+        } else if !check_arg_types(
+            &pos.func.dfg,
+            pos.func.dfg.inst_results(inst),
+            &pos.func.dfg.signatures[sig_ref].returns,
+        )
+        {
+            legalize_sret_call(pos, sig_ref);
+        }
+    }
+
+    // Variadic calls may need to pass the number of floating point registers used.
+    if pos.func.dfg.signatures[sig_ref]
+        .special_param_index(ArgumentPurpose::FloatRegCount)
+        .is_some()
+    {
+        legalize_fregs_call(pos, sig_ref);
+    }
+
     // OK, we need to fix the call arguments to match the ABI signature.
     let abi_args = pos.func.dfg.signatures[sig_ref].params.len();
     legalize_inst_arguments(pos, cfg, abi_args, |func, abi_arg| {
         func.dfg.signatures[sig_ref].params[abi_arg]
     });
 
-    if !pos.func.dfg.signatures[sig_ref].returns.is_empty() {
+    if !tail_call && !pos.func.dfg.signatures[sig_ref].returns.is_empty() {
         inst = legalize_inst_results(pos, |func, abi_res| {
             func.dfg.signatures[sig_ref].returns[abi_res]
         });
@@ -550,6 +856,11 @@ pub fn handle_return_abi(inst: Inst, func: &mut Function, cfg: &ControlFlowGraph
     let pos = &mut FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
+    // Values returned in memory are stored through the `sret` argument.
+    if returns_in_memory(&pos.func.signature) {
+        legalize_sret_return(pos);
+    }
+
     legalize_inst_arguments(pos, cfg, abi_args, |func, abi_arg| {
         func.signature.returns[abi_arg]
     });
@@ -605,6 +916,189 @@ pub fn handle_return_abi(inst: Inst, func: &mut Function, cfg: &ControlFlowGraph
     true
 }
 
+/// Check if the legalized signature `sig` returns values in memory.
+///
+/// If there are more return values than return registers, `legalize_signature()` replaces them
+/// with an `sret` argument pointing to memory for the return values. It only does that when the
+/// signature doesn't have an `sret` argument already, and the legalized signature then returns
+/// the `sret` pointer as its only return value besides other special-purpose return values.
+fn returns_in_memory(sig: &Signature) -> bool {
+    sig.returns.iter().any(
+        |rt| rt.purpose == ArgumentPurpose::StructReturn,
+    ) &&
+        !sig.returns.iter().any(
+            |rt| rt.purpose == ArgumentPurpose::Normal,
+        )
+}
+
+/// Compute the layout of values of type `types` returned in memory.
+///
+/// The values are stored in order, each naturally aligned. Returns the offset of each value and
+/// the total size of the memory area.
+fn sret_layout<I>(types: I) -> (Vec<i32>, u32)
+where
+    I: Iterator<Item = Type>,
+{
+    let mut offsets = Vec::new();
+    let mut size = 0;
+    for ty in types {
+        let bytes = ty.bytes();
+        size = (size + bytes - 1) & !(bytes - 1);
+        offsets.push(size as i32);
+        size += bytes;
+    }
+    (offsets, size)
+}
+
+/// Get the memory flags for accessing values returned in memory.
+fn sret_flags() -> MemFlags {
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+    flags.set_aligned();
+    flags
+}
+
+/// Pass a pointer to memory for the return values to the call instruction at `pos`.
+///
+/// The call's return values are replaced by loads from a new stack slot whose address is passed
+/// as an extra `sret` argument. The loads use the `sret` pointer returned by the callee. The
+/// cursor is left pointing at the call instruction.
+fn legalize_sret_call(pos: &mut FuncCursor, sig_ref: SigRef) {
+    let call = pos.current_inst().expect(
+        "Cursor must point to a call instruction",
+    );
+    let ptr_type = pos.func.dfg.signatures[sig_ref]
+        .returns
+        .iter()
+        .find(|rt| rt.purpose == ArgumentPurpose::StructReturn)
+        .expect("No sret return value")
+        .value_type;
+
+    let results = pos.func.dfg.detach_results(call);
+    let (offsets, size) = {
+        let dfg = &pos.func.dfg;
+        sret_layout(
+            results.as_slice(&dfg.value_lists).iter().map(
+                |&v| dfg.value_type(v),
+            ),
+        )
+    };
+
+    let ss = pos.func.create_stack_slot(
+        StackSlotData::new(StackSlotKind::Local, size),
+    );
+    let addr = pos.ins().stack_addr(ptr_type, ss, 0);
+    let mut vlist = pos.func.dfg[call].take_value_list().expect(
+        "Call must have a value list",
+    );
+    vlist.push(addr, &mut pos.func.dfg.value_lists);
+    pos.func.dfg[call].put_value_list(vlist);
+
+    let sret = pos.func.dfg.append_result(call, ptr_type);
+    pos.goto_after_inst(call);
+    for (idx, offset) in offsets.into_iter().enumerate() {
+        let res = results.get(idx, &pos.func.dfg.value_lists).unwrap();
+        let ty = pos.func.dfg.value_type(res);
+        pos.ins().with_results([Some(res)]).load(
+            ty,
+            sret_flags(),
+            sret,
+            offset,
+        );
+    }
+    pos.goto_inst(call);
+}
+
+/// Pass the `sret` argument of the current function to the tail call at `pos`.
+///
+/// The callee stores its return values directly in the memory provided by our caller. Nothing
+/// changes if the `sret` argument is already passed.
+fn legalize_sret_tail_call(pos: &mut FuncCursor) {
+    let call = pos.current_inst().expect(
+        "Cursor must point to a tail call instruction",
+    );
+    let idx = pos.func
+        .signature
+        .special_param_index(ArgumentPurpose::StructReturn)
+        .expect("Tail call returning in memory from a function without an sret argument");
+    let sret = pos.func.dfg.ebb_params(
+        pos.func.layout.entry_block().unwrap(),
+    )
+        [idx];
+
+    if pos.func.dfg.inst_variable_args(call).contains(&sret) {
+        return;
+    }
+    let mut vlist = pos.func.dfg[call].take_value_list().expect(
+        "Call must have a value list",
+    );
+    vlist.push(sret, &mut pos.func.dfg.value_lists);
+    pos.func.dfg[call].put_value_list(vlist);
+}
+
+/// Pass the number of floating point argument registers to the variadic call at `pos`.
+///
+/// The count is the number of normal floating point arguments the legalized signature assigns to
+/// registers.
+fn legalize_fregs_call(pos: &mut FuncCursor, sig_ref: SigRef) {
+    let call = pos.current_inst().expect(
+        "Cursor must point to a call instruction",
+    );
+    let (ty, count) = {
+        let sig = &pos.func.dfg.signatures[sig_ref];
+        let idx = sig.special_param_index(ArgumentPurpose::FloatRegCount).unwrap();
+        let count = sig.params
+            .iter()
+            .filter(|p| {
+                p.purpose == ArgumentPurpose::Normal && p.value_type.is_float() &&
+                    p.location.is_reg()
+            })
+            .count();
+        (sig.params[idx].value_type, count)
+    };
+
+    let fregs = pos.ins().iconst(ty, count as i64);
+    let mut vlist = pos.func.dfg[call].take_value_list().expect(
+        "Call must have a value list",
+    );
+    vlist.push(fregs, &mut pos.func.dfg.value_lists);
+    pos.func.dfg[call].put_value_list(vlist);
+}
+
+/// Store the arguments of the return instruction at `pos` in memory.
+///
+/// The values are stored through the `sret` argument of the current function, and removed from the
+/// return instruction.
+fn legalize_sret_return(pos: &mut FuncCursor) {
+    let inst = pos.current_inst().expect(
+        "Cursor must point to a return instruction",
+    );
+    let idx = pos.func
+        .signature
+        .special_param_index(ArgumentPurpose::StructReturn)
+        .expect("No sret argument");
+    let sret = pos.func.dfg.ebb_params(
+        pos.func.layout.entry_block().unwrap(),
+    )
+        [idx];
+
+    let mut vlist = pos.func.dfg[inst].take_value_list().unwrap();
+    let (offsets, _) = {
+        let dfg = &pos.func.dfg;
+        sret_layout(
+            vlist.as_slice(&dfg.value_lists).iter().map(
+                |&v| dfg.value_type(v),
+            ),
+        )
+    };
+    for (idx, offset) in offsets.into_iter().enumerate() {
+        let val = vlist.get(idx, &pos.func.dfg.value_lists).unwrap();
+        pos.ins().store(sret_flags(), val, sret, offset);
+    }
+    vlist.clear(&mut pos.func.dfg.value_lists);
+    pos.func.dfg[inst].put_value_list(vlist);
+}
+
 /// Assign stack slots to incoming function parameters on the stack.
 ///
 /// Values that are passed into the function on the stack must be assigned to an `IncomingArg`
@@ -623,6 +1117,10 @@ fn spill_entry_params(func: &mut Function, entry: Ebb) {
 /// Values that are passed to a called function on the stack must be assigned to a matching
 /// `OutgoingArg` stack slot. The assignment must happen immediately before the call.
 ///
+/// A tail call passes its stack arguments in the incoming argument area of the current function
+/// instead, so they are assigned to `IncomingArg` stack slots. Since that overwrites our own
+/// incoming arguments, all arguments living there are copied first.
+///
 /// TODO: The outgoing stack slots can be written a bit earlier, as long as there are no branches
 /// or calls between writing the stack slots and the call instruction. Writing the slots earlier
 /// could help reduce register pressure before the call.
@@ -633,6 +1131,13 @@ fn spill_call_arguments(pos: &mut FuncCursor) -> bool {
     let sig_ref = pos.func.dfg.call_signature(inst).expect(
         "Call instruction expected.",
     );
+    let tail_offset = if is_tail_call(&pos.func.dfg, inst) {
+        Some(pos.func.signature.tail_call_arguments_offset(
+            &pos.func.dfg.signatures[sig_ref],
+        ))
+    } else {
+        None
+    };
 
     // Start by building a list of stack slots and arguments to be replaced.
     // This requires borrowing `pos.func.dfg`, so we can't change anything.
@@ -651,9 +1156,13 @@ fn spill_call_arguments(pos: &mut FuncCursor) -> bool {
                         // Assign `arg` to a new stack slot, unless it's already in the correct
                         // slot. The legalization needs to be idempotent, so we should see a
                         // correct outgoing slot on the second pass.
-                        let ss = stack_slots.get_outgoing_arg(abi.value_type, offset);
+                        let ty = abi.value_type;
+                        let ss = match tail_offset {
+                            Some(base) => stack_slots.get_incoming_arg(ty, base + offset),
+                            None => stack_slots.get_outgoing_arg(ty, offset),
+                        };
                         if locations[arg] != ValueLoc::Stack(ss) {
-                            Some((idx, arg, ss))
+                            Some((idx, ss))
                         } else {
                             None
                         }
@@ -668,8 +1177,13 @@ fn spill_call_arguments(pos: &mut FuncCursor) -> bool {
         return false;
     }
 
+    if tail_offset.is_some() {
+        copy_incoming_arguments(pos);
+    }
+
     // Insert the spill instructions and rewrite call arguments.
-    for (idx, arg, ss) in arglist {
+    for (idx, ss) in arglist {
+        let arg = pos.func.dfg.inst_variable_args(inst)[idx];
         let stack_val = pos.ins().spill(arg);
         pos.func.locations[stack_val] = ValueLoc::Stack(ss);
         pos.func.dfg.inst_variable_args_mut(inst)[idx] = stack_val;
@@ -678,3 +1192,22 @@ fn spill_call_arguments(pos: &mut FuncCursor) -> bool {
     // We changed stuff.
     true
 }
+
+/// Copy the arguments of the tail call at `pos` that live in the incoming argument area.
+///
+/// The incoming argument area is overwritten by the stack arguments of the tail call, so all
+/// values read from there must be read before the first stack argument is written.
+fn copy_incoming_arguments(pos: &mut FuncCursor) {
+    let inst = pos.current_inst().expect(
+        "Cursor must point to a call instruction",
+    );
+    for idx in 0..pos.func.dfg.inst_args(inst).len() {
+        let arg = pos.func.dfg.inst_args(inst)[idx];
+        if let ValueLoc::Stack(ss) = pos.func.locations[arg] {
+            if pos.func.stack_slots[ss].kind == StackSlotKind::IncomingArg {
+                let copy = pos.ins().copy(arg);
+                pos.func.dfg.inst_args_mut(inst)[idx] = copy;
+            }
+        }
+    }
+}
@@ -16,6 +16,7 @@ pub mod dbg;
 pub mod entity;
 
 pub mod binemit;
+pub mod binformat;
 pub mod bitset;
 pub mod cursor;
 pub mod dominator_tree;
@@ -32,6 +33,7 @@ pub mod verifier;
 mod abi;
 mod constant_hash;
 mod context;
+mod dce;
 mod iterators;
 mod legalizer;
 mod licm;
@@ -384,7 +384,7 @@ impl<'a> Context<'a> {
         if constraints.fixed_outs {
             self.program_fixed_outputs(constraints.outs, defs, throughs);
         }
-        if let Some(sig) = call_sig {
+        if let Some(sig) = self.cur.func.dfg.non_tail_call_signature(inst) {
             self.program_output_abi(sig, defs, throughs);
         }
         self.program_output_constraints(inst, constraints.outs, defs);
@@ -13,6 +13,7 @@ use regalloc::coloring::Coloring;
 use regalloc::live_value_tracker::LiveValueTracker;
 use regalloc::liveness::Liveness;
 use regalloc::reload::Reload;
+use regalloc::safepoint::Safepoints;
 use regalloc::spilling::Spilling;
 use regalloc::virtregs::VirtRegs;
 use result::CtonResult;
@@ -29,6 +30,7 @@ pub struct Context {
     spilling: Spilling,
     reload: Reload,
     coloring: Coloring,
+    safepoints: Safepoints,
 }
 
 impl Context {
@@ -46,6 +48,7 @@ impl Context {
             spilling: Spilling::new(),
             reload: Reload::new(),
             coloring: Coloring::new(),
+            safepoints: Safepoints::new(),
         }
     }
 
@@ -135,6 +138,11 @@ impl Context {
             &mut self.tracker,
         );
 
+        // Pass: Safepoints.
+        if isa.flags().enable_safepoints() {
+            self.safepoints.run(isa, func, &mut self.liveness);
+        }
+
         if isa.flags().enable_verifier() {
             verify_context(func, cfg, domtree, isa)?;
             verify_liveness(isa, func, cfg, &self.liveness)?;
@@ -184,6 +184,7 @@ use regalloc::affinity::Affinity;
 use regalloc::liverange::LiveRange;
 use std::mem;
 use std::ops::Index;
+use std::slice;
 
 /// A set of live ranges, indexed by value number.
 type LiveRangeSet = SparseMap<Value, LiveRange>;
@@ -312,6 +313,11 @@ impl Liveness {
         self.ranges.get(value)
     }
 
+    /// Iterate over all the live ranges that have been computed.
+    pub fn ranges(&self) -> slice::Iter<LiveRange> {
+        self.ranges.values()
+    }
+
     /// Create a new live range for `value`.
     ///
     /// The new live range will be defined at `def` with no extent, like a dead value.
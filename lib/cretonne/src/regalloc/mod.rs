@@ -15,6 +15,7 @@ mod context;
 mod diversion;
 mod pressure;
 mod reload;
+mod safepoint;
 mod solver;
 mod spilling;
 
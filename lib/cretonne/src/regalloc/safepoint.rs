@@ -0,0 +1,81 @@
+//! Safepoint insertion.
+//!
+//! When the `enable_safepoints` setting is on, every call that returns to its caller is a
+//! safepoint where a garbage collector may run. The collector needs to find all the references
+//! that are live across the call so it can mark them and update them if objects are moved.
+//!
+//! The spilling pass has already moved all values that are live across a call into stack slots.
+//! This pass runs after coloring and inserts a `safepoint` instruction immediately after each
+//! call, listing the reference-typed values that are live through the call. The `safepoint`
+//! instruction emits no code, but it produces a stack map at the call's return address.
+//!
+//! An `invoke` instruction returns to the same address when the callee unwinds to its landing
+//! pad, so its stack map also lists the references that are live in to the landing pad. The live
+//! ranges of those references are extended to the `safepoint` instruction.
+
+use cursor::{Cursor, EncCursor};
+use entity::SparseMapValue;
+use ir::{Function, InstBuilder, Value};
+use isa::TargetIsa;
+use regalloc::liveness::Liveness;
+
+/// Persistent data structures for the safepoint pass.
+pub struct Safepoints {
+    refs: Vec<Value>,
+    live: Vec<Value>,
+}
+
+impl Safepoints {
+    /// Create a new safepoint pass.
+    pub fn new() -> Safepoints {
+        Safepoints {
+            refs: Vec::new(),
+            live: Vec::new(),
+        }
+    }
+
+    /// Insert a `safepoint` instruction after every call in `func`.
+    pub fn run(&mut self, isa: &TargetIsa, func: &mut Function, liveness: &mut Liveness) {
+        // Collect the reference values once. Most functions have none.
+        self.refs.clear();
+        self.refs.extend(liveness.ranges().map(|lr| lr.key()).filter(|&v| {
+            func.dfg.value_type(v).is_ref()
+        }));
+
+        let mut pos = EncCursor::new(func, isa);
+        while let Some(ebb) = pos.next_ebb() {
+            while let Some(inst) = pos.next_inst() {
+                if pos.func.dfg.non_tail_call_signature(inst).is_none() {
+                    continue;
+                }
+
+                let pad = pos.func.dfg[inst].branch_destination();
+                self.live.clear();
+                for &value in &self.refs {
+                    let lr = &liveness[value];
+                    if lr.reaches_use(inst, ebb, &pos.func.layout) &&
+                        (!lr.killed_at(inst, ebb, &pos.func.layout) ||
+                             pad.map_or(false, |pad| lr.is_livein(pad, &pos.func.layout)))
+                    {
+                        self.live.push(value);
+                    }
+                }
+
+                // The stack map applies to the return address which is where the instruction
+                // following the call begins.
+                pos.use_srcloc(inst);
+                pos.goto_after_inst(inst);
+                let safepoint = pos.ins().safepoint(&self.live);
+                pos.goto_inst(safepoint);
+
+                // References that are only live in to the landing pad must now reach the
+                // safepoint too.
+                for &value in &self.live {
+                    if liveness[value].killed_at(inst, ebb, &pos.func.layout) {
+                        liveness.extend_locally(value, ebb, safepoint, &pos.func.layout);
+                    }
+                }
+            }
+        }
+    }
+}
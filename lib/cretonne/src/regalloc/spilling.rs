@@ -220,6 +220,13 @@ impl<'a> Context<'a> {
         debug_assert_eq!(self.cur.current_inst(), Some(inst));
         debug_assert_eq!(self.cur.current_ebb(), Some(ebb));
 
+        // Values that are live in to the landing pad of an `invoke` must survive the call.
+        if self.cur.func.dfg[inst].opcode().is_call() {
+            if let Some(pad) = self.cur.func.dfg[inst].branch_destination() {
+                self.spill_landing_pad_liveins(pad, tracker);
+            }
+        }
+
         // We may need to resolve register constraints if there are any noteworthy uses.
         assert!(self.reg_uses.is_empty());
         self.collect_reg_uses(inst, ebb, constraints);
@@ -242,6 +249,8 @@ impl<'a> Context<'a> {
 
         // If inst is a call, spill all register values that are live across the call.
         // This means that we don't currently take advantage of callee-saved registers.
+        // The safepoint pass also depends on this: references live across a call must be in
+        // stack slots where the stack map can describe them.
         // TODO: Be more sophisticated.
         if call_sig.is_some() {
             for lv in throughs {
@@ -280,6 +289,21 @@ impl<'a> Context<'a> {
         self.take_live_regs(defs);
     }
 
+    /// Spill all register values that are live in to the landing pad `pad` of an `invoke`.
+    ///
+    /// The live value tracker considers these values killed by the `invoke` when they aren't used
+    /// on the normal path, so they would not be spilled as values live across the call.
+    fn spill_landing_pad_liveins(&mut self, pad: Ebb, tracker: &mut LiveValueTracker) {
+        for lv in tracker.live() {
+            if lv.affinity.is_reg() && !self.spills.contains(&lv.value) &&
+                self.liveness[lv.value].is_livein(pad, &self.cur.func.layout)
+            {
+                self.spill_reg(lv.value);
+            }
+        }
+        self.process_spills(tracker);
+    }
+
     // Collect register uses that are noteworthy in one of the following ways:
     //
     // 1. It's a fixed register constraint.
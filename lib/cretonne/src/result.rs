@@ -15,6 +15,12 @@ pub enum CtonError {
     /// code. This should never happen for validated WebAssembly code.
     InvalidInput,
 
+    /// The input uses a feature that is not supported.
+    ///
+    /// This error code is used by a WebAssembly translator when it encounters valid WebAssembly
+    /// code that it, or the environment it is translating for, can't handle.
+    Unsupported,
+
     /// An IL verifier error.
     ///
     /// This always represents a bug, either in the code that generated IL for Cretonne, or a bug
@@ -44,6 +50,7 @@ impl fmt::Display for CtonError {
         match *self {
             CtonError::Verifier(ref e) => write!(f, "Verifier error: {}", e),
             CtonError::InvalidInput |
+            CtonError::Unsupported |
             CtonError::ImplLimitExceeded |
             CtonError::CodeTooLarge => f.write_str(self.description()),
         }
@@ -54,6 +61,7 @@ impl StdError for CtonError {
     fn description(&self) -> &str {
         match *self {
             CtonError::InvalidInput => "Invalid input code",
+            CtonError::Unsupported => "Unsupported feature in input code",
             CtonError::Verifier(ref e) => &e.message,
             CtonError::ImplLimitExceeded => "Implementation limit exceeded",
             CtonError::CodeTooLarge => "Code for function is too large",
@@ -63,6 +71,7 @@ impl StdError for CtonError {
         match *self {
             CtonError::Verifier(ref e) => Some(e),
             CtonError::InvalidInput |
+            CtonError::Unsupported |
             CtonError::ImplLimitExceeded |
             CtonError::CodeTooLarge => None,
         }
@@ -349,9 +349,11 @@ mod tests {
                     is_64bit = false\n\
                     return_at_end = false\n\
                     is_compressed = false\n\
+                    is_pic = false\n\
                     enable_float = true\n\
                     enable_simd = true\n\
                     enable_atomics = true\n\
+                    enable_safepoints = false\n\
                     spiderwasm_prologue_words = 0\n"
         );
         assert_eq!(f.opt_level(), super::OptLevel::Default);
@@ -3,6 +3,7 @@
 use dominator_tree::DominatorTree;
 use flowgraph::ControlFlowGraph;
 use ir::Function;
+use ir::instructions::BranchInfo;
 use regalloc::liveness::Liveness;
 use regalloc::virtregs::VirtRegs;
 use std::cmp::Ordering;
@@ -103,7 +104,11 @@ impl<'a> CssaVerifier<'a> {
         for ebb in self.func.layout.ebbs() {
             let ebb_params = self.func.dfg.ebb_params(ebb);
             for &(_, pred) in self.cfg.get_predecessors(ebb) {
-                let pred_args = self.func.dfg.inst_variable_args(pred);
+                // The variable arguments of an `invoke` are not passed to its landing pad.
+                let pred_args = match self.func.dfg.analyze_branch(pred) {
+                    BranchInfo::SingleDest(_, args) => args,
+                    _ => &[],
+                };
                 // This should have been caught by an earlier verifier pass.
                 assert_eq!(
                     ebb_params.len(),
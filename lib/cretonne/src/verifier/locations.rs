@@ -69,6 +69,10 @@ impl<'a> LocationVerifier<'a> {
                     self.check_return_abi(inst, &divert)?;
                 }
 
+                if opcode == ir::Opcode::Safepoint {
+                    self.check_safepoint(inst, &divert)?;
+                }
+
                 if opcode.is_branch() && !divert.is_empty() {
                     self.check_cfg_edges(inst, &divert)?;
                 }
@@ -129,13 +133,24 @@ impl<'a> LocationVerifier<'a> {
         let varargs = self.func.dfg.inst_variable_args(inst);
         let results = self.func.dfg.inst_results(inst);
 
+        // Tail calls pass their stack arguments in the incoming argument area of the caller.
+        let (kind, base) = if self.func.dfg[inst].opcode().is_terminator() {
+            (
+                ir::StackSlotKind::IncomingArg,
+                self.func.signature.tail_call_arguments_offset(sig),
+            )
+        } else {
+            (ir::StackSlotKind::OutgoingArg, 0)
+        };
+
         for (abi, &value) in sig.params.iter().zip(varargs) {
             self.check_abi_location(
                 inst,
                 value,
                 abi,
+                base,
                 divert.get(value, &self.func.locations),
-                ir::StackSlotKind::OutgoingArg,
+                kind,
             )?;
         }
 
@@ -144,6 +159,7 @@ impl<'a> LocationVerifier<'a> {
                 inst,
                 value,
                 abi,
+                0,
                 self.func.locations[value],
                 ir::StackSlotKind::OutgoingArg,
             )?;
@@ -152,6 +168,23 @@ impl<'a> LocationVerifier<'a> {
         Ok(())
     }
 
+    /// Check that the references listed by a safepoint are in stack slots.
+    fn check_safepoint(&self, inst: ir::Inst, divert: &RegDiversions) -> Result {
+        for &value in self.func.dfg.inst_args(inst) {
+            let loc = divert.get(value, &self.func.locations);
+            if let ir::ValueLoc::Stack(_) = loc {
+                continue;
+            }
+            return err!(
+                inst,
+                "safepoint reference {} must be in a stack slot, not {}",
+                value,
+                loc.display(&self.reginfo)
+            );
+        }
+        Ok(())
+    }
+
     /// Check the ABI argument locations for a return.
     fn check_return_abi(&self, inst: ir::Inst, divert: &RegDiversions) -> Result {
         let sig = &self.func.signature;
@@ -162,6 +195,7 @@ impl<'a> LocationVerifier<'a> {
                 inst,
                 value,
                 abi,
+                0,
                 divert.get(value, &self.func.locations),
                 ir::StackSlotKind::IncomingArg,
             )?;
@@ -171,11 +205,14 @@ impl<'a> LocationVerifier<'a> {
     }
 
     /// Check a single ABI location.
+    ///
+    /// Stack arguments are expected at their ABI offset plus `base`.
     fn check_abi_location(
         &self,
         inst: ir::Inst,
         value: ir::Value,
         abi: &ir::AbiParam,
+        base: i32,
         loc: ir::ValueLoc,
         want_kind: ir::StackSlotKind,
     ) -> Result {
@@ -193,6 +230,7 @@ impl<'a> LocationVerifier<'a> {
                 }
             }
             ir::ArgumentLoc::Stack(offset) => {
+                let offset = base + offset;
                 if let ir::ValueLoc::Stack(ss) = loc {
                     let slot = &self.func.stack_slots[ss];
                     if slot.kind != want_kind {
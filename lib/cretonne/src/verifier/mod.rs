@@ -37,9 +37,13 @@
 //!    - All EBBs in a jump_table must take no arguments.
 //!    - Function calls are type checked against their signature.
 //!    - The entry block must take arguments that match the signature of the current
-//!      function.
+//!      function. Variadic functions can be called, but not defined.
 //!    - All return instructions must have return value operands matching the current
 //!      function signature.
+//!    - Tail calls must use the calling convention and return types of the current function,
+//!      and they can't need more stack arguments than the caller can pass. They can't pass
+//!      struct arguments.
+//!    - Safepoints can only list values of reference types.
 //!
 //!   Global variables
 //!
@@ -240,7 +244,7 @@ impl<'a> Verifier<'a> {
 
         let fixed_results = inst_data.opcode().constraints().fixed_results();
         // var_results is 0 if we aren't a call instruction
-        let var_results = dfg.call_signature(inst)
+        let var_results = dfg.non_tail_call_signature(inst)
             .map(|sig| dfg.signatures[sig].returns.len())
             .unwrap_or(0);
         let total_results = fixed_results + var_results;
@@ -315,6 +319,16 @@ impl<'a> Verifier<'a> {
                 self.verify_func_ref(inst, func_ref)?;
                 self.verify_value_list(inst, args)?;
             }
+            Invoke {
+                func_ref,
+                destination,
+                ref args,
+                ..
+            } => {
+                self.verify_func_ref(inst, func_ref)?;
+                self.verify_ebb(inst, destination)?;
+                self.verify_value_list(inst, args)?;
+            }
             IndirectCall { sig_ref, ref args, .. } => {
                 self.verify_sig_ref(inst, sig_ref)?;
                 self.verify_value_list(inst, args)?;
@@ -340,6 +354,7 @@ impl<'a> Verifier<'a> {
             }
 
             // Exhaustive list so we can't forget to add new formats
+            NullAry { .. } |
             Unary { .. } |
             UnaryImm { .. } |
             UnaryIeee32 { .. } |
@@ -470,6 +485,16 @@ impl<'a> Verifier<'a> {
                 {
                     return err!(loc_inst, "uses value from non-dominating {}", def_inst);
                 }
+                // The results of an `invoke` are not available in its landing pad.
+                if self.domtree.is_reachable(self.func.layout.pp_ebb(loc_inst)) &&
+                    !self.domtree.dominates_results(
+                        def_inst,
+                        loc_inst,
+                        &self.func.layout,
+                    )
+                {
+                    return err!(loc_inst, "uses result of {} on its branch edge", def_inst);
+                }
             }
             ValueDef::Param(ebb, _) => {
                 // Value is defined by an existing EBB.
@@ -553,6 +578,10 @@ impl<'a> Verifier<'a> {
 
     fn typecheck_entry_block_params(&self) -> Result {
         if let Some(ebb) = self.func.layout.entry_block() {
+            if self.func.signature.is_variadic() {
+                return err!(ebb, "variadic functions can't be defined");
+            }
+
             let expected_types = &self.func.signature.params;
             let ebb_param_count = self.func.dfg.num_ebb_params(ebb);
 
@@ -604,6 +633,7 @@ impl<'a> Verifier<'a> {
         self.typecheck_fixed_args(inst, ctrl_type)?;
         self.typecheck_variable_args(inst)?;
         self.typecheck_return(inst)?;
+        self.typecheck_tail_call(inst)?;
         self.typecheck_special(inst, ctrl_type)?;
 
         Ok(())
@@ -675,6 +705,18 @@ impl<'a> Verifier<'a> {
 
     fn typecheck_variable_args(&self, inst: Inst) -> Result {
         match self.func.dfg[inst].analyze_branch(&self.func.dfg.value_lists) {
+            // The variable arguments of an `invoke` go to the callee, not the landing pad.
+            BranchInfo::SingleDest(ebb, _) if self.func.dfg[inst].opcode().is_call() => {
+                let arg_count = self.func.dfg.num_ebb_params(ebb);
+                if arg_count != 0 {
+                    return err!(
+                        inst,
+                        "landing pad {} can't have {} parameters",
+                        ebb,
+                        arg_count
+                    );
+                }
+            }
             BranchInfo::SingleDest(ebb, _) => {
                 let iter = self.func.dfg.ebb_params(ebb).iter().map(|&v| {
                     self.func.dfg.value_type(v)
@@ -760,15 +802,25 @@ impl<'a> Verifier<'a> {
     /// Check the locations assigned to outgoing call arguments.
     ///
     /// When a signature has been legalized, all values passed as outgoing arguments on the stack
-    /// must be assigned to a matching `OutgoingArg` stack slot.
+    /// must be assigned to a matching `OutgoingArg` stack slot. Tail calls pass their stack
+    /// arguments in `IncomingArg` stack slots instead.
     fn check_outgoing_args(&self, inst: Inst, sig_ref: SigRef) -> Result {
         let sig = &self.func.dfg.signatures[sig_ref];
 
         // Before legalization, there's nothing to check.
-        if sig.argument_bytes.is_none() {
+        if sig.argument_bytes.is_none() || self.func.signature.argument_bytes.is_none() {
             return Ok(());
         }
 
+        let (kind, base) = if self.func.dfg[inst].opcode().is_terminator() {
+            (
+                StackSlotKind::IncomingArg,
+                self.func.signature.tail_call_arguments_offset(sig),
+            )
+        } else {
+            (StackSlotKind::OutgoingArg, 0)
+        };
+
         let args = self.func.dfg.inst_variable_args(inst);
         let expected_args = &sig.params[..];
 
@@ -780,7 +832,7 @@ impl<'a> Verifier<'a> {
                     // Argument value is assigned to a stack slot as expected.
                     self.verify_stack_slot(inst, ss)?;
                     let slot = &self.func.stack_slots[ss];
-                    if slot.kind != StackSlotKind::OutgoingArg {
+                    if slot.kind != kind {
                         return err!(
                             inst,
                             "Outgoing stack argument {} in wrong stack slot: {} = {}",
@@ -789,12 +841,12 @@ impl<'a> Verifier<'a> {
                             slot
                         );
                     }
-                    if slot.offset != offset {
+                    if slot.offset != base + offset {
                         return err!(
                             inst,
                             "Outgoing stack argument {} should have offset {}: {} = {}",
                             arg,
-                            offset,
+                            base + offset,
                             ss,
                             slot
                         );
@@ -847,6 +899,58 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    /// Check that a tail call is compatible with the signature of the current function.
+    fn typecheck_tail_call(&self, inst: Inst) -> Result {
+        let opcode = self.func.dfg[inst].opcode();
+        if !opcode.is_call() || !opcode.is_terminator() {
+            return Ok(());
+        }
+        let sig = &self.func.dfg.signatures[self.func.dfg.call_signature(inst).unwrap()];
+        let caller = &self.func.signature;
+
+        if sig.call_conv != caller.call_conv {
+            return err!(
+                inst,
+                "tail call with calling convention {} from a {} function",
+                sig.call_conv,
+                caller.call_conv
+            );
+        }
+
+        // A struct passed by reference would point into the frame we're leaving.
+        if sig.params.iter().any(|abi| match abi.purpose {
+            ir::ArgumentPurpose::StructArgument(_) => true,
+            _ => false,
+        })
+        {
+            return err!(inst, "tail calls can't pass struct arguments");
+        }
+
+        // The prologue adds callee-saved registers to the caller's return values.
+        let types = sig.returns.iter().map(|abi| abi.value_type);
+        let caller_types = caller
+            .returns
+            .iter()
+            .filter(|abi| abi.purpose != ir::ArgumentPurpose::CalleeSaved)
+            .map(|abi| abi.value_type);
+        if !types.eq(caller_types) {
+            return err!(inst, "tail call return types must match function signature");
+        }
+
+        // Unless the callee pops its arguments, they must fit in our own incoming arguments.
+        if let (Some(bytes), Some(callee_bytes)) = (caller.argument_bytes, sig.argument_bytes) {
+            if !sig.call_conv.callee_pops() && callee_bytes > bytes {
+                return err!(
+                    inst,
+                    "tail call needs {} bytes of stack arguments, but the caller only has {}",
+                    callee_bytes,
+                    bytes
+                );
+            }
+        }
+        Ok(())
+    }
+
     // Check special-purpose type constraints that can't be expressed in the normal opcode
     // constraints.
     fn typecheck_special(&self, inst: Inst, ctrl_type: Type) -> Result {
@@ -893,6 +997,16 @@ impl<'a> Verifier<'a> {
                     _ => {}
                 }
             }
+            ir::InstructionData::MultiAry {
+                opcode: Opcode::Safepoint,
+                ref args,
+            } => {
+                for &arg in args.as_slice(&self.func.dfg.value_lists) {
+                    if !self.func.dfg.value_type(arg).is_ref() {
+                        return err!(inst, "safepoint argument {} must be a reference", arg);
+                    }
+                }
+            }
             _ => {}
         }
         Ok(())
@@ -279,6 +279,7 @@ pub fn write_operands(
     let pool = &dfg.value_lists;
     use ir::instructions::InstructionData::*;
     match dfg[inst] {
+        NullAry { .. } => write!(w, ""),
         Unary { arg, .. } => write!(w, " {}", arg),
         UnaryImm { imm, .. } => write!(w, " {}", imm),
         UnaryIeee32 { imm, .. } => write!(w, " {}", imm),
@@ -353,6 +354,20 @@ pub fn write_operands(
         Call { func_ref, ref args, .. } => {
             write!(w, " {}({})", func_ref, DisplayValues(args.as_slice(pool)))
         }
+        Invoke {
+            func_ref,
+            destination,
+            ref args,
+            ..
+        } => {
+            write!(
+                w,
+                " {}({}), {}",
+                func_ref,
+                DisplayValues(args.as_slice(pool)),
+                destination
+            )
+        }
         IndirectCall { sig_ref, ref args, .. } => {
             let args = args.as_slice(pool);
             write!(
@@ -4,7 +4,7 @@ use pattern::Pattern;
 use regex::{Regex, Captures};
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::cmp::max;
+use std::cmp::{max, min};
 use std::fmt::{self, Display, Formatter};
 use std::mem;
 use MatchRange;
@@ -18,6 +18,9 @@ enum Directive {
     Unordered(Pattern),
     Not(Pattern),
     Regex(String, String),
+    Label(Pattern),
+    Dag(Pattern),
+    Count(usize, Pattern),
 }
 
 // Regular expression matching a directive.
@@ -26,7 +29,10 @@ enum Directive {
 // 1. Keyword.
 // 2. Rest of line / pattern.
 //
-const DIRECTIVE_RX: &str = r"\b(check|sameln|nextln|unordered|not|regex):\s+(.*)";
+const DIRECTIVE_RX: &str = concat!(
+    r"\b(check-label|check-dag|check-count|check|sameln|nextln|unordered|not|regex)",
+    r":\s+(.*)"
+);
 
 impl Directive {
     /// Create a new directive from a `DIRECTIVE_RX` match.
@@ -37,6 +43,9 @@ impl Directive {
         if cmd == "regex" {
             return Directive::regex(rest);
         }
+        if cmd == "check-count" {
+            return Directive::count(rest);
+        }
 
         // All other commands are followed by a pattern.
         let pat = rest.parse()?;
@@ -46,6 +55,19 @@ impl Directive {
             "sameln" => Ok(Directive::SameLn(pat)),
             "nextln" => Ok(Directive::NextLn(pat)),
             "unordered" => Ok(Directive::Unordered(pat)),
+            "check-dag" => Ok(Directive::Dag(pat)),
+            "check-label" => {
+                if !pat.defs().is_empty() {
+                    let msg = format!(
+                        "can't define variables '$({}=...' in check-label: {}",
+                        pat.defs()[0],
+                        rest
+                    );
+                    Err(Error::DuplicateDef(msg))
+                } else {
+                    Ok(Directive::Label(pat))
+                }
+            }
             "not" => {
                 if !pat.defs().is_empty() {
                     let msg = format!(
@@ -84,6 +106,17 @@ impl Directive {
             rest[varlen + 1..].trim_right().to_string(),
         ))
     }
+
+    /// Create a `check-count:` directive from a `N pattern` string.
+    fn count(rest: &str) -> Result<Directive> {
+        let numlen = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        match rest[0..numlen].parse() {
+            Ok(count) if count > 0 => Ok(Directive::Count(count, rest[numlen..].parse()?)),
+            _ => Err(Error::Syntax(
+                format!("expected a positive count in check-count: {}", rest),
+            )),
+        }
+    }
 }
 
 
@@ -174,15 +207,39 @@ impl Checker {
     fn run(&self, text: &str, vars: &VariableMap, recorder: &mut Recorder) -> Result<bool> {
         let mut state = State::new(text, vars, recorder);
 
-        // For each pending `not:` check, store (begin-offset, regex).
+        // Match the `check-label:` directives first. They divide the input into blocks, and the
+        // directives between two labels can only match in the block between the labels.
+        let labels = match self.find_labels(&mut state)? {
+            Some(labels) => labels,
+            None => return Ok(false),
+        };
+        let mut next_label = 0;
+        state.limit = labels.first().map_or(text.len(), |l| l.0);
+
+        // For each pending `not:` check, store (directive, begin-offset, regex).
         let mut nots = Vec::new();
 
+        // Ranges matched by the current group of `check-dag:` directives.
+        let mut dags = Vec::new();
+
         for (dct_idx, dct) in self.directives.iter().enumerate() {
+            // A group of `check-dag:` directives ends at the first directive of another kind.
+            match *dct {
+                Directive::Dag(_) |
+                Directive::Regex(..) => {}
+                _ => {
+                    if !state.end_dag_group(&mut dags, &mut nots) {
+                        return Ok(false);
+                    }
+                }
+            }
+
             let (pat, range) = match *dct {
                 Directive::Check(ref pat) => (pat, state.check()),
                 Directive::SameLn(ref pat) => (pat, state.sameln()),
                 Directive::NextLn(ref pat) => (pat, state.nextln()),
-                Directive::Unordered(ref pat) => (pat, state.unordered(pat)),
+                Directive::Unordered(ref pat) |
+                Directive::Dag(ref pat) => (pat, state.unordered(pat)),
                 Directive::Not(ref pat) => {
                     // Resolve `not:` directives immediately to get the right variable values, but
                     // don't match it until we know the end of the range.
@@ -194,52 +251,77 @@ impl Checker {
                     continue;
                 }
                 Directive::Regex(ref var, ref rx) => {
-                    state.vars.insert(
-                        var.clone(),
-                        VarDef {
-                            value: Value::Regex(Cow::Borrowed(rx)),
-                            offset: 0,
-                        },
-                    );
+                    state.define_regex(var, rx);
+                    continue;
+                }
+                Directive::Label(ref pat) => {
+                    // The label was already matched. Move on to its block.
+                    let range = labels[next_label];
+                    next_label += 1;
+                    state.limit = labels.get(next_label).map_or(text.len(), |l| l.0);
+                    state.recorder.directive(dct_idx);
+                    state.recorder.matched_check(pat.resolve(&state)?.as_str(), range);
+                    if !state.ordered_match(range, &mut nots) {
+                        return Ok(false);
+                    }
+                    continue;
+                }
+                Directive::Count(count, ref pat) => {
+                    // Match the pattern `count` times, like repeated `check:` directives.
+                    for _ in 0..count {
+                        state.recorder.directive(dct_idx);
+                        let range = state.check();
+                        match state.match_positive(pat, range, &[])? {
+                            Some(matched) => {
+                                if !state.ordered_match(matched, &mut nots) {
+                                    return Ok(false);
+                                }
+                            }
+                            None => return Ok(false),
+                        }
+                    }
                     continue;
                 }
             };
             // Check if `pat` matches in `range`.
             state.recorder.directive(dct_idx);
-            if let Some((match_begin, match_end)) = state.match_positive(pat, range)? {
-                if let Directive::Unordered(_) = *dct {
+            match *dct {
+                Directive::Unordered(_) => {
                     // This was an unordered match.
                     // Keep track of the largest matched position, but leave `last_ordered` alone.
-                    state.max_match = max(state.max_match, match_end);
-                } else {
-                    // Ordered match.
-                    state.last_ordered = match_end;
-                    state.max_match = match_end;
-
-                    // Verify any pending `not:` directives now that we know their range.
-                    for (not_idx, not_begin, rx) in nots.drain(..) {
-                        state.recorder.directive(not_idx);
-                        if let Some(mat) = rx.find(&text[not_begin..match_begin]) {
-                            // Matched `not:` pattern.
-                            state.recorder.matched_not(rx.as_str(), (
-                                not_begin + mat.start(),
-                                not_begin + mat.end(),
-                            ));
-                            return Ok(false);
-                        } else {
-                            state.recorder.missed_not(
-                                rx.as_str(),
-                                (not_begin, match_begin),
-                            );
+                    match state.match_positive(pat, range, &[])? {
+                        Some((_, match_end)) => state.max_match = max(state.max_match, match_end),
+                        None => return Ok(false),
+                    }
+                }
+                Directive::Dag(_) => {
+                    // Like an unordered match, but it can't overlap the other matches in the group.
+                    match state.match_positive(pat, range, &dags)? {
+                        Some(matched) => {
+                            state.max_match = max(state.max_match, matched.1);
+                            dags.push(matched);
                         }
+                        None => return Ok(false),
+                    }
+                }
+                _ => {
+                    match state.match_positive(pat, range, &[])? {
+                        Some(matched) => {
+                            if !state.ordered_match(matched, &mut nots) {
+                                return Ok(false);
+                            }
+                        }
+                        // No match!
+                        None => return Ok(false),
                     }
                 }
-            } else {
-                // No match!
-                return Ok(false);
             }
         }
 
+        if !state.end_dag_group(&mut dags, &mut nots) {
+            return Ok(false);
+        }
+
         // Verify any pending `not:` directives after the last ordered directive.
         for (not_idx, not_begin, rx) in nots.drain(..) {
             state.recorder.directive(not_idx);
@@ -252,8 +334,43 @@ impl Checker {
 
         Ok(true)
     }
+
+    /// Find the matches for all the `check-label:` directives, in order.
+    ///
+    /// Returns `None` if a label couldn't be found.
+    fn find_labels<'a>(&'a self, state: &mut State<'a>) -> Result<Option<Vec<MatchRange>>> {
+        let mut labels = Vec::new();
+        let mut pos = 0;
+        for (dct_idx, dct) in self.directives.iter().enumerate() {
+            match *dct {
+                Directive::Label(ref pat) => {
+                    let rx = pat.resolve(state)?;
+                    match rx.find(&state.text[pos..]) {
+                        Some(mat) => {
+                            labels.push((pos + mat.start(), pos + mat.end()));
+                            pos += mat.end();
+                        }
+                        None => {
+                            state.recorder.directive(dct_idx);
+                            state.recorder.missed_check(rx.as_str(), (pos, state.text.len()));
+                            return Ok(None);
+                        }
+                    }
+                }
+                // Labels can use the regex variables defined before them.
+                Directive::Regex(ref var, ref rx) => state.define_regex(var, rx),
+                _ => {}
+            }
+        }
+        // The regex variables are defined again in order when the other directives are matched.
+        state.vars.clear();
+        Ok(Some(labels))
+    }
 }
 
+// A pending `not:` directive: (directive, begin-offset, regex).
+type PendingNot = (usize, usize, Regex);
+
 /// A local definition of a variable.
 pub struct VarDef<'a> {
     /// The value given to the variable.
@@ -268,10 +385,14 @@ struct State<'a> {
     recorder: &'a mut Recorder,
 
     vars: HashMap<String, VarDef<'a>>,
+    // Values of the numeric variables. These are also in `vars` as the matched text.
+    nums: HashMap<String, i64>,
     // Offset after the last ordered match. This does not include recent unordered matches.
     last_ordered: usize,
     // Largest offset following a positive match, including unordered matches.
     max_match: usize,
+    // End of the current block of input delimited by `check-label:` matches.
+    limit: usize,
 }
 
 impl<'a> State<'a> {
@@ -281,11 +402,25 @@ impl<'a> State<'a> {
             env_vars,
             recorder,
             vars: HashMap::new(),
+            nums: HashMap::new(),
             last_ordered: 0,
             max_match: 0,
+            limit: text.len(),
         }
     }
 
+    // Define a regex variable from a `regex:` directive.
+    fn define_regex(&mut self, var: &str, rx: &'a str) {
+        self.nums.remove(var);
+        self.vars.insert(
+            var.to_string(),
+            VarDef {
+                value: Value::Regex(Cow::Borrowed(rx)),
+                offset: 0,
+            },
+        );
+    }
+
     // Get the offset following the match that defined `var`, or 0 if var is an environment
     // variable or unknown.
     fn def_offset(&self, var: &str) -> usize {
@@ -306,73 +441,148 @@ impl<'a> State<'a> {
 
     // Get the range in text to be matched by a `check:`.
     fn check(&self) -> MatchRange {
-        (self.max_match, self.text.len())
+        (self.max_match, self.limit)
     }
 
     // Get the range in text to be matched by a `sameln:`.
     fn sameln(&self) -> MatchRange {
         let b = self.max_match;
-        let e = self.bol(b);
+        let e = min(self.bol(b), self.limit);
         (b, e)
     }
 
     // Get the range in text to be matched by a `nextln:`.
     fn nextln(&self) -> MatchRange {
-        let b = self.bol(self.max_match);
-        let e = self.bol(b);
+        let b = min(self.bol(self.max_match), self.limit);
+        let e = min(self.bol(b), self.limit);
         (b, e)
     }
 
     // Get the beginning of the range in text to be matched by a `unordered:` or `not:` directive.
     // The unordered directive must match after the directives that define the variables used.
     fn unordered_begin(&self, pat: &Pattern) -> usize {
-        pat.parts()
+        let begin = pat.parts()
             .iter()
-            .filter_map(|part| part.ref_var())
+            .flat_map(|part| part.ref_vars())
             .map(|var| self.def_offset(var))
-            .fold(self.last_ordered, max)
+            .fold(self.last_ordered, max);
+        min(begin, self.limit)
     }
 
     // Get the range in text to be matched by a `unordered:` directive.
     fn unordered(&self, pat: &Pattern) -> MatchRange {
-        (self.unordered_begin(pat), self.text.len())
+        (self.unordered_begin(pat), self.limit)
     }
 
-    // Search for `pat` in `range`, return the range matched.
+    // Search for `pat` in `range`, skipping matches that overlap any of the `avoid` ranges.
+    // Return the range matched.
     // After a positive match, update variable definitions, if any.
-    fn match_positive(&mut self, pat: &Pattern, range: MatchRange) -> Result<Option<MatchRange>> {
+    fn match_positive(
+        &mut self,
+        pat: &Pattern,
+        range: MatchRange,
+        avoid: &[MatchRange],
+    ) -> Result<Option<MatchRange>> {
         let rx = pat.resolve(self)?;
-        let txt = &self.text[range.0..range.1];
+        let text = self.text;
+        let mut begin = range.0;
+        let matched_range = loop {
+            let r = match rx.find(&text[begin..range.1]) {
+                Some(mat) => (begin + mat.start(), begin + mat.end()),
+                None => break None,
+            };
+            match avoid
+                .iter()
+                .filter(|a| a.0 < r.1 && r.0 < a.1)
+                .map(|a| a.1)
+                .max() {
+                Some(end) => begin = end,
+                None => break Some(r),
+            }
+        };
+
+        let r = match matched_range {
+            Some(r) => r,
+            None => {
+                self.recorder.missed_check(rx.as_str(), range);
+                return Ok(None);
+            }
+        };
+        self.recorder.matched_check(rx.as_str(), r);
+
         let defs = pat.defs();
-        let matched_range = if defs.is_empty() {
-            // Pattern defines no variables. Fastest search is `find`.
-            rx.find(txt)
-        } else {
+        if !defs.is_empty() {
             // We need the captures to define variables.
-            rx.captures(txt).map(|caps| {
-                let matched_range = caps.get(0).expect("whole expression must match");
-                for var in defs {
-                    let txtval = caps.name(var).map(|mat| mat.as_str()).unwrap_or("");
-                    self.recorder.defined_var(var, txtval);
-                    let vardef = VarDef {
-                        value: Value::Text(Cow::Borrowed(txtval)),
-                        // This offset is the end of the whole matched pattern, not just the text
-                        // defining the variable.
-                        offset: range.0 + matched_range.end(),
-                    };
-                    self.vars.insert(var.clone(), vardef);
+            let caps = rx.captures(&text[begin..range.1]).expect(
+                "whole expression must match",
+            );
+            for (def, var) in defs.iter().enumerate() {
+                let txtval = caps.name(var).map(|mat| mat.as_str()).unwrap_or("");
+                self.recorder.defined_var(var, txtval);
+                match pat.def_radix(def) {
+                    Some(radix) => {
+                        let num = i64::from_str_radix(txtval, radix).map_err(|_| {
+                            Error::Syntax(format!("can't assign '{}' to ${}", txtval, var))
+                        })?;
+                        self.nums.insert(var.clone(), num);
+                    }
+                    None => {
+                        self.nums.remove(var);
+                    }
                 }
-                matched_range
-            })
+                let vardef = VarDef {
+                    value: Value::Text(Cow::Borrowed(txtval)),
+                    // This offset is the end of the whole matched pattern, not just the text
+                    // defining the variable.
+                    offset: r.1,
+                };
+                self.vars.insert(var.clone(), vardef);
+            }
+        }
+        Ok(Some(r))
+    }
+
+    // Record an ordered match of `matched`, and verify the pending `not:` directives before it.
+    // Return false if one of the `not:` directives matched.
+    fn ordered_match(&mut self, matched: MatchRange, nots: &mut Vec<PendingNot>) -> bool {
+        self.last_ordered = matched.1;
+        self.max_match = matched.1;
+        self.check_nots(nots, matched.0)
+    }
+
+    // Finish a group of `check-dag:` matches. The group acts like a single ordered match covering
+    // all the matches in the group.
+    // Return false if one of the pending `not:` directives matched before the group.
+    fn end_dag_group(&mut self, dags: &mut Vec<MatchRange>, nots: &mut Vec<PendingNot>) -> bool {
+        let begin = match dags.iter().map(|d| d.0).min() {
+            Some(begin) => begin,
+            None => return true,
         };
-        Ok(if let Some(mat) = matched_range {
-            let r = (range.0 + mat.start(), range.0 + mat.end());
-            self.recorder.matched_check(rx.as_str(), r);
-            Some(r)
-        } else {
-            self.recorder.missed_check(rx.as_str(), range);
-            None
-        })
+        dags.clear();
+        self.last_ordered = self.max_match;
+        self.check_nots(nots, begin)
+    }
+
+    // Verify the pending `not:` directives in the text before `end`.
+    // Return false if one of them matched.
+    fn check_nots(&mut self, nots: &mut Vec<PendingNot>, end: usize) -> bool {
+        for (not_idx, not_begin, rx) in nots.drain(..) {
+            if not_begin >= end {
+                continue;
+            }
+            self.recorder.directive(not_idx);
+            if let Some(mat) = rx.find(&self.text[not_begin..end]) {
+                // Matched `not:` pattern.
+                self.recorder.matched_not(
+                    rx.as_str(),
+                    (not_begin + mat.start(), not_begin + mat.end()),
+                );
+                return false;
+            } else {
+                self.recorder.missed_not(rx.as_str(), (not_begin, end));
+            }
+        }
+        true
     }
 }
 
@@ -386,6 +596,17 @@ impl<'a> VariableMap for State<'a> {
             self.env_vars.lookup(varname)
         }
     }
+
+    fn lookup_number(&self, varname: &str) -> Option<i64> {
+        if let Some(&num) = self.nums.get(varname) {
+            return Some(num);
+        }
+        match self.vars.get(varname) {
+            Some(&VarDef { value: Value::Text(ref s), .. }) => s.parse().ok(),
+            Some(_) => None,
+            None => self.env_vars.lookup_number(varname),
+        }
+    }
 }
 
 impl Display for Directive {
@@ -398,6 +619,9 @@ impl Display for Directive {
             Unordered(ref pat) => writeln!(f, "unordered: {}", pat),
             Not(ref pat) => writeln!(f, "not: {}", pat),
             Regex(ref var, ref rx) => writeln!(f, "regex: {}={}", var, rx),
+            Label(ref pat) => writeln!(f, "check-label: {}", pat),
+            Dag(ref pat) => writeln!(f, "check-dag: {}", pat),
+            Count(count, ref pat) => writeln!(f, "check-count: {} {}", count, pat),
         }
     }
 }
@@ -17,6 +17,9 @@
 //! <a href="#the-unordered-directive">unordered: <i>&lt;pattern&gt;</i></a>
 //! <a href="#the-not-directive">not: <i>&lt;pattern&gt;</i></a>
 //! <a href="#the-regex-directive">regex: <i>&lt;variable&gt;</i>=<i>&lt;regex&gt;</i></a>
+//! <a href="#the-check-label-directive">check-label: <i>&lt;pattern&gt;</i></a>
+//! <a href="#the-check-dag-directive">check-dag: <i>&lt;pattern&gt;</i></a>
+//! <a href="#the-check-count-directive">check-count: <i>&lt;n&gt;</i> <i>&lt;pattern&gt;</i></a>
 //! </pre>
 //! Each directive is described in more detail below.
 //!
@@ -189,6 +192,59 @@
 //!
 //! See [the Rust regex crate](../regex/index.html#syntax) for the regular expression syntax.
 //!
+//! ## The `check-label:` directive
+//!
+//! Divide the input into blocks that are checked separately.
+//!
+//! ```sh
+//! #0 check-label: function %a
+//! #1 check: one
+//! #2 check-label: function %b
+//! #3 check: two
+//! ```
+//!
+//! The `check-label:` directives are matched in order before any other directives. The input
+//! between two label matches forms a block, and the directives between two labels can only match
+//! inside the corresponding block. The directives above will match `"function %a one function %b
+//! two"`, but not `"function %a function %b one two"`.
+//!
+//! Limiting the directives to a block gives better error messages, since a failed match can't
+//! skip ahead to the next block. A `not:` directive at the end of a block only applies to the
+//! rest of that block.
+//!
+//! The pattern in a `check-label:` directive can't define any variables, and it can only use
+//! variables defined by `regex:` directives.
+//!
+//! ## The `check-dag:` directive
+//!
+//! Match a group of patterns in any order, without overlapping each other.
+//!
+//! ```sh
+//! #0 check-dag: one
+//! #1 check-dag: two
+//! #2 not: three
+//! #3 check-dag: four
+//! #4 check-dag: five
+//! ```
+//!
+//! A sequence of `check-dag:` directives forms a group that matches like `unordered:` directives,
+//! except that the matches can't overlap. The directives above will match `"two one four five"`,
+//! but not `"one four two five"`. The second group must match after the whole first group.
+//!
+//! A `not:` directive between two groups checks the text between the last match in the first
+//! group and the first match in the second group, so the directives above don't match `"one two
+//! three four five"`.
+//!
+//! ## The `check-count:` directive
+//!
+//! Match a pattern a number of times.
+//!
+//! ```sh
+//! #0 check-count: 3 nop
+//! ```
+//!
+//! This is the same as three `check: nop` directives.
+//!
 //! # Patterns and variables
 //!
 //! Patterns are plain text strings to be matched in the input file. The dollar sign is used as an
@@ -214,6 +270,31 @@
 //! Regular expression variables are defined with the `regex:` directive. They match the regular
 //! expression each time they are used, so the matches don't need to be identical.
 //!
+//! ## Numeric variables
+//!
+//! Numeric variables hold an integer value which can be used in simple arithmetic:
+//!
+//! <pre>
+//! $(#<i>&lt;var&gt;</i>:)          Match a decimal number, and define <i>&lt;var&gt;</i> as its
+//!                     value.
+//! $(#%x,<i>&lt;var&gt;</i>:)       Match a hexadecimal number, and define <i>&lt;var&gt;</i> as
+//!                     its value.
+//! $(#<i>&lt;expr&gt;</i>)          Match the value of <i>&lt;expr&gt;</i> in decimal.
+//! $(#%x,<i>&lt;expr&gt;</i>)       Match the value of <i>&lt;expr&gt;</i> in hexadecimal.
+//! </pre>
+//!
+//! An expression is a sequence of numeric variables and decimal constants separated by `+` or `-`
+//! operators. The value of an expression can be matched with leading zeros, and hexadecimal
+//! values are matched ignoring case. For example, this checks that two stack offsets differ by 8:
+//!
+//! ```sh
+//! check: store v1, ss0+$(#off:)
+//! check: load ss0+$(#off+8)
+//! ```
+//!
+//! A numeric variable can also be used as `$var`, matching the same text again. Plain text
+//! variables that contain a decimal number can be used in expressions too.
+//!
 //! ## Word boundaries
 //!
 //! If a pattern begins or ends with a (plain text) letter or number, it will only match on a word
@@ -17,6 +17,8 @@ use regex::{Regex, RegexBuilder, escape};
 ///    to variable `foo`.
 /// 4. Variable definition from regex variable, `$(foo=$RX)`. Lookup variable `RX` which should
 ///    expand to a regex, match the regex, and assign matching text to variable `foo`.
+/// 5. Numeric variable definition, `$(#foo:)`. Match a number and assign its value to `foo`.
+/// 6. Numeric expression, `$(#foo+8)`. Match the value of the expression.
 ///
 pub struct Pattern {
     parts: Vec<Part>,
@@ -39,15 +41,38 @@ pub enum Part {
     DefLit { def: usize, regex: String },
     /// Lookup variable `var`, match resulting regex, assign matching text to variable `defs[def]`.
     DefVar { def: usize, var: String },
+    /// Match a decimal or hexadecimal number, assign its value to numeric variable `defs[def]`.
+    DefNum { def: usize, hex: bool },
+    /// Match the value of a sum of terms, written in decimal or hexadecimal.
+    /// Each term is negated when its flag is set.
+    Num { hex: bool, terms: Vec<(bool, NumTerm)> },
+}
+
+/// A term in a numeric expression.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NumTerm {
+    /// The value of a numeric variable.
+    Var(String),
+    /// A constant.
+    Const(i64),
 }
 
 impl Part {
-    /// Get the variabled referenced by this part, if any.
-    pub fn ref_var(&self) -> Option<&str> {
+    /// Get the variables referenced by this part.
+    pub fn ref_vars(&self) -> Vec<&str> {
         match *self {
-            Part::Var(ref var) => Some(var),
-            Part::DefVar { ref var, .. } => Some(var),
-            _ => None,
+            Part::Var(ref var) => vec![var],
+            Part::DefVar { ref var, .. } => vec![var],
+            Part::Num { ref terms, .. } => {
+                terms
+                    .iter()
+                    .filter_map(|&(_, ref term)| match *term {
+                        NumTerm::Var(ref var) => Some(var.as_str()),
+                        NumTerm::Const(_) => None,
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
         }
     }
 }
@@ -119,6 +144,11 @@ impl Pattern {
             ));
         }
 
+        // Numeric variables and expressions start with `$(#`.
+        if s[2..].starts_with('#') {
+            return self.parse_numeric(s);
+        }
+
         // Match the variable name, allowing for an empty varname in `$()`, or `$(=...)`.
         let varname_end = 2 + varname_prefix(&s[2..]);
         let varname = s[2..varname_end].to_string();
@@ -216,6 +246,78 @@ impl Pattern {
     }
 }
 
+impl Pattern {
+    /// Parse a numeric part `$(#...)` from a prefix of `s`.
+    ///
+    /// This is either a definition `$(#var:)` or an expression like `$(#var+8)`. Both can be
+    /// prefixed by a format specifier `%d,` or `%x,` to use decimal or hexadecimal numbers.
+    fn parse_numeric(&mut self, s: &str) -> Result<(Part, usize)> {
+        let end = match s.find(')') {
+            Some(end) => end,
+            None => return Err(Error::Syntax(format!("unterminated {}", s))),
+        };
+        let whole = &s[0..end + 1];
+        let mut body = &s[3..end];
+        let hex = if body.starts_with("%x,") {
+            body = &body[3..];
+            true
+        } else {
+            if body.starts_with("%d,") {
+                body = &body[3..];
+            }
+            false
+        };
+
+        // Match `$(#var:)`.
+        if body.ends_with(':') {
+            let var = &body[0..body.len() - 1];
+            if var.is_empty() || varname_prefix(var) != var.len() {
+                return Err(Error::Syntax(
+                    format!("invalid numeric variable name in {}", whole),
+                ));
+            }
+            let def = self.add_def(var)?;
+            return Ok((Part::DefNum { def, hex }, end + 1));
+        }
+
+        // Match an expression `$(#term+term-...)`.
+        let mut terms = Vec::new();
+        let mut neg = false;
+        let mut rest = body.trim_left();
+        loop {
+            let len = varname_prefix(rest);
+            if len == 0 {
+                return Err(Error::Syntax(
+                    format!("expected variable or number in {}", whole),
+                ));
+            }
+            let word = &rest[0..len];
+            let term = if word.bytes().all(|b| b.is_ascii_digit()) {
+                NumTerm::Const(word.parse().map_err(|_| {
+                    Error::Syntax(format!("number too large in {}", whole))
+                })?)
+            } else {
+                NumTerm::Var(word.to_string())
+            };
+            terms.push((neg, term));
+
+            rest = rest[len..].trim_left();
+            neg = match rest.chars().next() {
+                None => break,
+                Some('+') => false,
+                Some('-') => true,
+                Some(_) => {
+                    return Err(Error::Syntax(
+                        format!("expected '+' or '-' in {}", whole),
+                    ))
+                }
+            };
+            rest = rest[1..].trim_left();
+        }
+        Ok((Part::Num { hex, terms }, end + 1))
+    }
+}
+
 /// Compute the length of a regular expression terminated by `)` or `}`.
 /// Handle nested and escaped parentheses in the rx, but don't actualy parse it.
 /// Return the position of the terminating brace or the length of the string.
@@ -287,7 +389,7 @@ impl FromStr for Pattern {
         let mut pos = 0;
         while pos < s.len() {
             let (part, len) = pat.parse_part(&s[pos..])?;
-            if let Some(v) = part.ref_var() {
+            for v in part.ref_vars() {
                 if pat.defines_var(v) {
                     return Err(Error::Backref(format!(
                         "unsupported back-reference to '${}' \
@@ -314,6 +416,17 @@ impl Pattern {
         &self.defs
     }
 
+    /// Get the radix of the number matched for `defs()[def]`, or `None` if it is a text variable.
+    pub fn def_radix(&self, def: usize) -> Option<u32> {
+        self.parts
+            .iter()
+            .filter_map(|part| match *part {
+                Part::DefNum { def: d, hex } if d == def => Some(if hex { 16 } else { 10 }),
+                _ => None,
+            })
+            .next()
+    }
+
     /// Resolve all variable references in this pattern, turning it into a regular expression.
     pub fn resolve(&self, vmap: &VariableMap) -> Result<Regex> {
         let mut out = String::new();
@@ -357,6 +470,31 @@ impl Pattern {
                         Some(Value::Regex(rx)) => write!(out, "{})", rx).unwrap(),
                     }
                 }
+                Part::DefNum { def, hex } => {
+                    let digits = if hex { "[0-9a-fA-F]+" } else { "-?[0-9]+" };
+                    write!(out, "(?P<{}>{})", self.defs[def], digits).unwrap();
+                }
+                Part::Num { hex, ref terms } => {
+                    let value = eval(terms, vmap)?;
+                    // Allow leading zeros, so fixed-width numbers can be matched.
+                    if hex {
+                        if value < 0 {
+                            return Err(Error::Syntax(
+                                format!("can't match negative value {} in hexadecimal", value),
+                            ));
+                        }
+                        write!(out, "0*(?i:{:x})", value).unwrap();
+                    } else if value < 0 {
+                        // The magnitude of `i64::MIN` only fits in a `u64`.
+                        let magnitude = match value.checked_neg() {
+                            Some(neg) => neg as u64,
+                            None => value as u64,
+                        };
+                        write!(out, "-0*{}", magnitude).unwrap();
+                    } else {
+                        write!(out, "0*{}", value).unwrap();
+                    }
+                }
             }
 
         }
@@ -375,6 +513,36 @@ impl Pattern {
     }
 }
 
+// Evaluate the terms of a numeric expression.
+fn eval(terms: &[(bool, NumTerm)], vmap: &VariableMap) -> Result<i64> {
+    let mut sum = 0i64;
+    for &(neg, ref term) in terms {
+        let value = match *term {
+            NumTerm::Const(c) => c,
+            NumTerm::Var(ref var) => {
+                match vmap.lookup_number(var) {
+                    Some(value) => value,
+                    None => {
+                        return Err(Error::UndefVariable(
+                            format!("undefined numeric variable ${}", var),
+                        ))
+                    }
+                }
+            }
+        };
+        let next = if neg {
+            sum.checked_sub(value)
+        } else {
+            sum.checked_add(value)
+        };
+        sum = match next {
+            Some(sum) => sum,
+            None => return Err(Error::Syntax("overflow in numeric expression".to_string())),
+        };
+    }
+    Ok(sum)
+}
+
 impl Display for Pattern {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         for part in &self.parts {
@@ -392,6 +560,24 @@ impl Display for Pattern {
                     write!(f, "$({}={})", defvar, litrx)
                 }
                 DefVar { def, ref var } => write!(f, "$({}=${})", self.defs[def], var),
+                DefNum { def, hex } => {
+                    write!(f, "$(#{}{}:)", if hex { "%x," } else { "" }, self.defs[def])
+                }
+                Num { hex, ref terms } => {
+                    write!(f, "$(#{}", if hex { "%x," } else { "" })?;
+                    for (idx, &(neg, ref term)) in terms.iter().enumerate() {
+                        if neg {
+                            write!(f, "-")?;
+                        } else if idx > 0 {
+                            write!(f, "+")?;
+                        }
+                        match *term {
+                            NumTerm::Var(ref var) => write!(f, "{}", var)?,
+                            NumTerm::Const(c) => write!(f, "{}", c)?,
+                        }
+                    }
+                    write!(f, ")")
+                }
             }?;
         }
         Ok(())
@@ -590,4 +776,62 @@ mod tests {
             "[Var(\"foo\"), Text(\"=\"), Var(\"bar\")]"
         );
     }
+
+    #[test]
+    fn numeric() {
+        use super::{Pattern, Part, NumTerm};
+        let mut pat = Pattern::new();
+
+        assert_eq!(pat.parse_part("$(#off:) x").unwrap(), (
+            Part::DefNum {
+                def: 0,
+                hex: false,
+            },
+            8,
+        ));
+        assert_eq!(pat.parse_part("$(#%x,addr:)").unwrap(), (
+            Part::DefNum {
+                def: 1,
+                hex: true,
+            },
+            12,
+        ));
+        assert_eq!(pat.parse_part("$(#%x,off + 8 - n)").unwrap(), (
+            Part::Num {
+                hex: true,
+                terms: vec![
+                    (false, NumTerm::Var("off".to_string())),
+                    (false, NumTerm::Const(8)),
+                    (true, NumTerm::Var("n".to_string())),
+                ],
+            },
+            18,
+        ));
+
+        assert_eq!(
+            pat.parse_part("$(#off").unwrap_err().to_string(),
+            "unterminated $(#off"
+        );
+        assert_eq!(
+            pat.parse_part("$(#o.f:)").unwrap_err().to_string(),
+            "invalid numeric variable name in $(#o.f:)"
+        );
+        assert_eq!(
+            pat.parse_part("$(#off+)").unwrap_err().to_string(),
+            "expected variable or number in $(#off+)"
+        );
+        assert_eq!(
+            pat.parse_part("$(#off*2)").unwrap_err().to_string(),
+            "expected '+' or '-' in $(#off*2)"
+        );
+
+        let p: Pattern = "at $(#%x,a:) and $(#b:), $(#%x,c+8-d)".parse().unwrap();
+        assert_eq!(p.to_string(), "at $(#%x,a:) and $(#b:), $(#%x,c+8-d)");
+        assert_eq!(p.def_radix(0), Some(16));
+        assert_eq!(p.def_radix(1), Some(10));
+        assert_eq!(
+            "$(#a:) $(#a+1)".parse::<Pattern>().err().unwrap().to_string(),
+            "unsupported back-reference to '$a' defined in same pattern"
+        );
+    }
 }
@@ -29,6 +29,16 @@ pub enum Value<'a> {
 pub trait VariableMap {
     /// Get the value of the variable `varname`, or return `None` for an unknown variable name.
     fn lookup(&self, varname: &str) -> Option<Value>;
+
+    /// Get the value of the numeric variable `varname`, or return `None` if it isn't a number.
+    ///
+    /// The default implementation interprets a plain text variable as a decimal number.
+    fn lookup_number(&self, varname: &str) -> Option<i64> {
+        match self.lookup(varname) {
+            Some(Value::Text(s)) => s.parse().ok(),
+            _ => None,
+        }
+    }
 }
 
 impl VariableMap for () {
@@ -400,3 +400,225 @@ fn trailing_unordered() {
         Ok(true)
     );
 }
+
+#[test]
+fn label() {
+    let c = CheckerBuilder::new()
+        .text(
+            "
+        check-label: function %a
+        check: one
+        not: two
+        check-label: function %b
+        check: two
+        ",
+        )
+        .unwrap()
+        .finish();
+
+    let t = "
+        function %a
+        one
+        function %b
+        two
+        ";
+    assert_eq!(c.check(t, NO_VARIABLES).map_err(e2s), Ok(true));
+
+    // The `check: one` directive can't match in the block for %b.
+    let t = "
+        function %a
+        function %b
+        one two
+        ";
+    assert_eq!(c.check(t, NO_VARIABLES).map_err(e2s), Ok(false));
+
+    // The `not: two` directive only applies to the block for %a.
+    let t = "
+        function %a
+        one two
+        function %b
+        two
+        ";
+    assert_eq!(c.check(t, NO_VARIABLES).map_err(e2s), Ok(false));
+
+    let t = "
+        function %b
+        two
+        function %a
+        one
+        ";
+    assert_eq!(c.check(t, NO_VARIABLES).map_err(e2s), Ok(false));
+}
+
+#[test]
+fn dag() {
+    let c = CheckerBuilder::new()
+        .text(
+            "
+        check: zero
+        check-dag: one
+        check-dag: one
+        check-dag: two
+        not: three
+        check-dag: four
+        check: five
+        ",
+        )
+        .unwrap()
+        .finish();
+
+    assert_eq!(
+        c.check("zero one two one four five", NO_VARIABLES).map_err(e2s),
+        Ok(true)
+    );
+    assert_eq!(
+        c.check("zero two one one four five", NO_VARIABLES).map_err(e2s),
+        Ok(true)
+    );
+
+    // The two `check-dag: one` directives can't match the same text.
+    assert_eq!(
+        c.check("zero one two four five", NO_VARIABLES).map_err(e2s),
+        Ok(false)
+    );
+
+    // The second group must match after the first one.
+    assert_eq!(
+        c.check("zero one four one two five", NO_VARIABLES).map_err(e2s),
+        Ok(false)
+    );
+
+    // The `not:` directive applies between the groups.
+    assert_eq!(
+        c.check("zero one two one three four five", NO_VARIABLES)
+            .map_err(e2s),
+        Ok(false)
+    );
+    assert_eq!(
+        c.check("zero one three two one four five", NO_VARIABLES)
+            .map_err(e2s),
+        Ok(true)
+    );
+}
+
+#[test]
+fn count() {
+    let c = CheckerBuilder::new()
+        .text(
+            "
+        check: begin
+        check-count: 3 nop
+        nextln: end
+        ",
+        )
+        .unwrap()
+        .finish();
+
+    assert_eq!(
+        c.check("begin\nnop\nnop\nnop\nend", NO_VARIABLES).map_err(e2s),
+        Ok(true)
+    );
+    assert_eq!(
+        c.check("begin\nnop\nnop\nend", NO_VARIABLES).map_err(e2s),
+        Ok(false)
+    );
+    assert_eq!(
+        c.check("begin\nnop\nnop\nnop\nnop\nend", NO_VARIABLES)
+            .map_err(e2s),
+        Ok(false)
+    );
+
+    assert_eq!(
+        CheckerBuilder::new()
+            .directive("check-count: 0 nop")
+            .map_err(e2s),
+        Err(
+            "expected a positive count in check-count: 0 nop".to_string(),
+        )
+    );
+}
+
+#[test]
+fn numeric() {
+    let c = CheckerBuilder::new()
+        .text(
+            "
+        check: store v1, ss0+$(#off:)
+        check: load ss0+$(#off+8)
+        check: @$(#%x,pc:) jump
+        check: @$(#%x,pc+off+8)
+        ",
+        )
+        .unwrap()
+        .finish();
+
+    assert_eq!(
+        c.check(
+            "store v1, ss0+16\nload ss0+24\n@001c jump\n@0034",
+            NO_VARIABLES,
+        ).map_err(e2s),
+        Ok(true)
+    );
+    assert_eq!(
+        c.check(
+            "store v1, ss0+16\nload ss0+16\n@001c jump\n@0034",
+            NO_VARIABLES,
+        ).map_err(e2s),
+        Ok(false)
+    );
+    assert_eq!(
+        c.check(
+            "store v1, ss0+16\nload ss0+24\n@001C jump\n@34",
+            NO_VARIABLES,
+        ).map_err(e2s),
+        Ok(true)
+    );
+
+    let c = CheckerBuilder::new()
+        .text("check: $(#n-1)")
+        .unwrap()
+        .finish();
+    assert_eq!(
+        c.check("0", NO_VARIABLES).map_err(e2s),
+        Err("undefined numeric variable $n".to_string())
+    );
+
+    // The most negative number can still be matched.
+    let c = CheckerBuilder::new()
+        .text("check: $(#n:)\nsameln: $(#n-1)")
+        .unwrap()
+        .finish();
+    assert_eq!(
+        c.check("-9223372036854775807 -9223372036854775808", NO_VARIABLES)
+            .map_err(e2s),
+        Ok(true)
+    );
+}
+
+#[test]
+fn explain_new_directives() {
+    let c = CheckerBuilder::new()
+        .text(
+            "
+        check-label: %a
+        check-dag: $(#x:)
+        check-count: 2 $(#x+1)
+        ",
+        )
+        .unwrap()
+        .finish();
+    assert_eq!(
+        c.to_string(),
+        "#0 check-label: %a\n#1 check-dag: $(#x:)\n#2 check-count: 2 $(#x+1)\n"
+    );
+
+    let (ok, explain) = c.explain("%a 4 5 5", NO_VARIABLES).unwrap();
+    assert!(ok);
+    assert!(explain.contains("Matched #0: %a"));
+    assert!(explain.contains("Define x=4"));
+    assert_eq!(explain.matches("Matched #2: 0*5").count(), 2);
+
+    let (ok, explain) = c.explain("%b 4 5 5", NO_VARIABLES).unwrap();
+    assert!(!ok);
+    assert!(explain.contains("Missed #0: %a"));
+}
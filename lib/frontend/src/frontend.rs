@@ -478,6 +478,14 @@ where
         self.builder.ebbs[self.position.ebb].filled
     }
 
+    /// Stops building the function after an error.
+    ///
+    /// The function is left incomplete and should be discarded. Unlike dropping the builder, this
+    /// doesn't check that all the `Ebb`s are filled and sealed.
+    pub fn abandon(mut self) {
+        self.builder.clear();
+    }
+
     /// Returns a displayable object for the function as it is.
     ///
     /// Useful for debug purposes. Use it with `None` for standard printing.
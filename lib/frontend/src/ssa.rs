@@ -488,6 +488,8 @@ where
                     cur.ins().f32const(Ieee32::with_bits(0))
                 } else if ty == F64 {
                     cur.ins().f64const(Ieee64::with_bits(0))
+                } else if ty.is_ref() {
+                    cur.ins().null(ty)
                 } else {
                     panic!("value used but never declared and initialization not supported")
                 };
@@ -558,33 +560,55 @@ where
                 panic!("you have declared a non-branch instruction as a predecessor to an ebb");
             }
             // For a single destination appending a jump argument to the instruction
-            // is sufficient.
-            BranchInfo::SingleDest(_, _) => {
+            // is sufficient, except for `invoke` whose arguments go to the callee.
+            BranchInfo::SingleDest(_, _) if !func.dfg[jump_inst].opcode().is_call() => {
                 func.dfg.append_inst_arg(jump_inst, val);
                 None
             }
+            BranchInfo::SingleDest(_, _) => {
+                // The landing pad of an `invoke` can't take arguments either.
+                // We have to split the edge.
+                let split = self.split_edge(func, jump_inst_block, jump_inst, dest_ebb, val, var);
+                *func.dfg[jump_inst].branch_destination_mut().unwrap() = split.0;
+                Some(split)
+            }
             BranchInfo::Table(jt) => {
                 // In the case of a jump table, the situation is tricky because br_table doesn't
                 // support arguments.
                 // We have to split the critical edge
-                let middle_ebb = func.dfg.make_ebb();
-                func.layout.append_ebb(middle_ebb);
-                let block = self.declare_ebb_header_block(middle_ebb);
-                self.blocks[block].add_predecessor(jump_inst_block, jump_inst);
-                self.mark_ebb_header_block_sealed(block);
+                let split = self.split_edge(func, jump_inst_block, jump_inst, dest_ebb, val, var);
                 for old_dest in func.jump_tables[jt].as_mut_slice() {
                     if old_dest.unwrap() == dest_ebb {
-                        *old_dest = PackedOption::from(middle_ebb);
+                        *old_dest = PackedOption::from(split.0);
                     }
                 }
-                let mut cur = FuncCursor::new(func).at_bottom(middle_ebb);
-                let middle_jump_inst = cur.ins().jump(dest_ebb, &[val]);
-                self.def_var(var, val, block);
-                Some((middle_ebb, block, middle_jump_inst))
+                Some(split)
             }
         }
     }
 
+    /// Create a new EBB on the edge from `jump_inst` to `dest_ebb` that jumps to `dest_ebb`
+    /// passing `val` as an argument. The caller is responsible for retargeting `jump_inst`.
+    fn split_edge(
+        &mut self,
+        func: &mut Function,
+        jump_inst_block: Block,
+        jump_inst: Inst,
+        dest_ebb: Ebb,
+        val: Value,
+        var: Variable,
+    ) -> (Ebb, Block, Inst) {
+        let middle_ebb = func.dfg.make_ebb();
+        func.layout.append_ebb(middle_ebb);
+        let block = self.declare_ebb_header_block(middle_ebb);
+        self.blocks[block].add_predecessor(jump_inst_block, jump_inst);
+        self.mark_ebb_header_block_sealed(block);
+        let mut cur = FuncCursor::new(func).at_bottom(middle_ebb);
+        let middle_jump_inst = cur.ins().jump(dest_ebb, &[val]);
+        self.def_var(var, val, block);
+        (middle_ebb, block, middle_jump_inst)
+    }
+
     /// Returns the list of `Ebb`s that have been declared as predecessors of the argument.
     pub fn predecessors(&self, ebb: Ebb) -> &[(Block, Inst)] {
         let block = self.header_block(ebb);
@@ -647,7 +671,8 @@ where
 mod tests {
     use cretonne::cursor::{Cursor, FuncCursor};
     use cretonne::entity::EntityRef;
-    use cretonne::ir::{Function, InstBuilder, Inst, JumpTableData};
+    use cretonne::ir::{Function, InstBuilder, Inst, JumpTableData, Signature, CallConv,
+                       ExtFuncData, FunctionName};
     use cretonne::ir::types::*;
     use cretonne::verify_function;
     use cretonne::ir::instructions::BranchInfo;
@@ -980,6 +1005,78 @@ mod tests {
         }
     }
 
+    #[test]
+    fn invoke_with_args() {
+        // This tests the on-demand splitting of the landing pad edge of an invoke
+        let mut func = Function::new();
+        let mut ssa: SSABuilder<Variable> = SSABuilder::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let sig = func.import_signature(Signature::new(CallConv::Native));
+        let fn0 = func.import_function(ExtFuncData {
+            name: FunctionName::new("callee"),
+            signature: sig,
+        });
+        // Here is the pseudo-program we want to translate:
+        // ebb0:
+        //    x = 1;
+        //    invoke fn0(), ebb1
+        //    x = 2
+        //    jump ebb1
+        // ebb1:
+        //    x = x + 1
+        //    return
+        //
+        let block0 = ssa.declare_ebb_header_block(ebb0);
+        ssa.seal_ebb_header_block(ebb0, &mut func);
+        let x_var = Variable(0);
+        let x1 = {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb0);
+            cur.insert_ebb(ebb1);
+            cur.goto_bottom(ebb0);
+            cur.ins().iconst(I32, 1)
+        };
+        ssa.def_var(x_var, x1, block0);
+        let invoke = {
+            let mut cur = FuncCursor::new(&mut func).at_bottom(ebb0);
+            cur.ins().invoke(fn0, ebb1, &[])
+        };
+        let block1 = ssa.declare_ebb_body_block(block0);
+        let x2 = {
+            let mut cur = FuncCursor::new(&mut func).at_bottom(ebb0);
+            cur.ins().iconst(I32, 2)
+        };
+        ssa.def_var(x_var, x2, block1);
+        let jump_inst = {
+            let mut cur = FuncCursor::new(&mut func).at_bottom(ebb0);
+            cur.ins().jump(ebb1, &[])
+        };
+        let block2 = ssa.declare_ebb_header_block(ebb1);
+        ssa.declare_ebb_predecessor(ebb1, block0, invoke);
+        ssa.declare_ebb_predecessor(ebb1, block1, jump_inst);
+        ssa.seal_ebb_header_block(ebb1, &mut func);
+        let x3 = ssa.use_var(&mut func, x_var, I32, block2).0;
+        {
+            let mut cur = FuncCursor::new(&mut func).at_bottom(ebb1);
+            cur.ins().iadd_imm(x3, 1)
+        };
+        {
+            let mut cur = FuncCursor::new(&mut func).at_bottom(ebb1);
+            cur.ins().return_(&[])
+        };
+        // The call arguments are unchanged and the landing pad is a new EBB.
+        assert_eq!(func.dfg.inst_variable_args(invoke).len(), 0);
+        let pad = func.dfg[invoke].branch_destination().unwrap();
+        assert!(pad != ebb1);
+        assert_eq!(func.dfg.ebb_params(pad).len(), 0);
+        let flags = settings::Flags::new(&settings::builder());
+        match verify_function(&func, &flags) {
+            Ok(()) => {}
+            Err(err) => panic!(err.message),
+        }
+    }
+
     #[test]
     fn undef_values_reordering() {
         let mut func = Function::new();
@@ -0,0 +1,774 @@
+//! Generating random functions with the `FunctionBuilder`.
+
+use cretonne::entity::EntityRef;
+use cretonne::ir::{Function, FunctionName, Signature, CallConv, AbiParam, ArgumentPurpose,
+                   InstBuilder, Type, Value, Ebb, FuncRef, StackSlot, StackSlotData,
+                   StackSlotKind, Heap, HeapData, HeapBase, HeapStyle, GlobalVarData,
+                   ExtFuncData, JumpTableData, MemFlags, TrapCode};
+use cretonne::ir::condcodes::{IntCC, FloatCC};
+use cretonne::ir::immediates::{Ieee32, Ieee64};
+use cretonne::ir::types::*;
+use cton_frontend::{ILBuilder, FunctionBuilder};
+use input::Input;
+use std::u32;
+
+/// Limits on the size and shape of the generated functions.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Maximum number of normal function parameters.
+    pub max_params: usize,
+    /// Maximum number of function return values.
+    pub max_returns: usize,
+    /// Maximum number of variables of each type.
+    pub max_vars_per_type: usize,
+    /// Maximum number of EBBs, including the entry block.
+    pub max_ebbs: usize,
+    /// Maximum number of explicit parameters of the EBBs after the entry block.
+    pub max_ebb_params: usize,
+    /// Maximum number of instructions in an EBB, not counting the branches at the end.
+    pub max_insts_per_ebb: usize,
+    /// Maximum number of external functions called.
+    pub max_callees: usize,
+    /// Maximum number of explicit stack slots.
+    pub max_stack_slots: usize,
+    /// Give the function a `vmctx` parameter and a heap to load from and store to.
+    pub heap: bool,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            max_params: 6,
+            max_returns: 1,
+            max_vars_per_type: 3,
+            max_ebbs: 8,
+            max_ebb_params: 3,
+            max_insts_per_ebb: 16,
+            max_callees: 3,
+            max_stack_slots: 3,
+            heap: true,
+        }
+    }
+}
+
+/// Types of the variables in a generated function.
+const VAR_TYPES: [Type; 5] = [I32, I64, F32, F64, B1];
+
+/// Types of the values passed to and returned from functions.
+const ABI_TYPES: [Type; 4] = [I32, I64, F32, F64];
+
+const INT_CCS: [IntCC; 10] = [
+    IntCC::Equal,
+    IntCC::NotEqual,
+    IntCC::SignedLessThan,
+    IntCC::SignedGreaterThanOrEqual,
+    IntCC::SignedGreaterThan,
+    IntCC::SignedLessThanOrEqual,
+    IntCC::UnsignedLessThan,
+    IntCC::UnsignedGreaterThanOrEqual,
+    IntCC::UnsignedGreaterThan,
+    IntCC::UnsignedLessThanOrEqual,
+];
+
+const FLOAT_CCS: [FloatCC; 14] = [
+    FloatCC::Ordered,
+    FloatCC::Unordered,
+    FloatCC::Equal,
+    FloatCC::NotEqual,
+    FloatCC::OrderedNotEqual,
+    FloatCC::UnorderedOrEqual,
+    FloatCC::LessThan,
+    FloatCC::LessThanOrEqual,
+    FloatCC::GreaterThan,
+    FloatCC::GreaterThanOrEqual,
+    FloatCC::UnorderedOrLessThan,
+    FloatCC::UnorderedOrLessThanOrEqual,
+    FloatCC::UnorderedOrGreaterThan,
+    FloatCC::UnorderedOrGreaterThanOrEqual,
+];
+
+/// Sizes of the explicit stack slots.
+const SLOT_SIZES: [u32; 4] = [4, 8, 16, 32];
+
+/// A variable of the generated program, translated to SSA form by the `FunctionBuilder`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Variable(u32);
+
+impl EntityRef for Variable {
+    fn new(index: usize) -> Self {
+        assert!(index < (u32::MAX as usize));
+        Variable(index as u32)
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Default for Variable {
+    fn default() -> Variable {
+        Variable(u32::MAX)
+    }
+}
+
+/// Generate a random function named `name` from the fuzzer input `data`.
+///
+/// The function always passes the verifier. It uses 64-bit addresses, so it should be compiled
+/// for a 64-bit target.
+pub fn generate_function(name: FunctionName, data: &[u8], config: &Config) -> Function {
+    let mut input = Input::new(data);
+    let mut sig = signature(&mut input, config.max_params, config.max_returns);
+    if config.heap {
+        sig.params.push(AbiParam::special(I64, ArgumentPurpose::VMContext));
+    }
+    let mut func = Function::with_name_signature(name, sig);
+    let mut il_builder = ILBuilder::new();
+    {
+        let builder = FunctionBuilder::new(&mut func, &mut il_builder);
+        let mut gen = Generator {
+            input,
+            config,
+            builder,
+            vars: Vec::new(),
+            ebbs: Vec::new(),
+            callees: Vec::new(),
+            slots: Vec::new(),
+            heap: None,
+        };
+        gen.run();
+    }
+    // The builder gives every instruction a default source location. Drop them so the function
+    // prints without a source location column.
+    func.srclocs.clear();
+    func
+}
+
+/// Generate a random signature using the native calling convention.
+fn signature(input: &mut Input, max_params: usize, max_returns: usize) -> Signature {
+    let mut sig = Signature::new(CallConv::Native);
+    for _ in 0..input.below(max_params + 1) {
+        sig.params.push(AbiParam::new(*input.choose(&ABI_TYPES)));
+    }
+    for _ in 0..input.below(max_returns + 1) {
+        sig.returns.push(AbiParam::new(*input.choose(&ABI_TYPES)));
+    }
+    sig
+}
+
+struct Generator<'a, 'f> {
+    input: Input<'a>,
+    config: &'a Config,
+    builder: FunctionBuilder<'f, Variable>,
+    vars: Vec<(Variable, Type)>,
+    /// The EBBs in layout order, with the types of their explicit parameters.
+    ebbs: Vec<(Ebb, Vec<Type>)>,
+    callees: Vec<FuncRef>,
+    slots: Vec<(StackSlot, u32)>,
+    heap: Option<Heap>,
+}
+
+impl<'a, 'f> Generator<'a, 'f> {
+    fn run(&mut self) {
+        self.declare_entities();
+
+        let num_ebbs = 1 + self.input.below(self.config.max_ebbs.max(1));
+        for i in 0..num_ebbs {
+            let ebb = self.builder.create_ebb();
+            let mut types = Vec::new();
+            if i > 0 {
+                for _ in 0..self.input.below(self.config.max_ebb_params + 1) {
+                    let ty = *self.input.choose(&VAR_TYPES);
+                    self.builder.append_ebb_param(ebb, ty);
+                    types.push(ty);
+                }
+            }
+            self.ebbs.push((ebb, types));
+        }
+
+        for &ty in &VAR_TYPES {
+            for _ in 0..1 + self.input.below(self.config.max_vars_per_type.max(1)) {
+                let var = Variable::new(self.vars.len());
+                self.builder.declare_var(var, ty);
+                self.vars.push((var, ty));
+            }
+        }
+
+        let entry = self.ebbs[0].0;
+        self.builder.switch_to_block(entry, &[]);
+        self.builder.seal_block(entry);
+        self.define_vars();
+
+        for i in 0..num_ebbs {
+            let (ebb, ref types) = self.ebbs[i].clone();
+            if i > 0 {
+                let params = self.builder.ebb_params(ebb)[0..types.len()].to_vec();
+                self.builder.switch_to_block(ebb, &params);
+                for (&param, &ty) in params.iter().zip(types) {
+                    let var = self.var(ty);
+                    self.builder.def_var(var, param);
+                }
+            }
+            for _ in 0..self.input.below(self.config.max_insts_per_ebb + 1) {
+                self.statement();
+            }
+            self.terminator(i);
+        }
+
+        for &(ebb, _) in &self.ebbs[1..] {
+            self.builder.seal_block(ebb);
+        }
+    }
+
+    /// Declare the callees, stack slots and heap used by the function.
+    fn declare_entities(&mut self) {
+        for i in 0..self.input.below(self.config.max_callees + 1) {
+            let sig = signature(&mut self.input, 4, 1);
+            let signature = self.builder.import_signature(sig);
+            let callee = self.builder.import_function(ExtFuncData {
+                name: FunctionName::new(format!("callee{}", i)),
+                signature,
+            });
+            self.callees.push(callee);
+        }
+
+        for _ in 0..self.input.below(self.config.max_stack_slots + 1) {
+            let size = *self.input.choose(&SLOT_SIZES);
+            let slot = self.builder.create_stack_slot(
+                StackSlotData::new(StackSlotKind::Local, size),
+            );
+            self.slots.push((slot, size));
+        }
+
+        if self.config.heap {
+            let gv = self.builder.create_global_var(
+                GlobalVarData::VmCtx { offset: 0.into() },
+            );
+            self.heap = Some(self.builder.create_heap(HeapData {
+                base: HeapBase::GlobalVar(gv),
+                min_size: 0x1_0000.into(),
+                guard_size: 0x8000_0000.into(),
+                style: HeapStyle::Static { bound: 0x1_0000_0000.into() },
+            }));
+        }
+    }
+
+    /// Give all the variables an initial value in the entry block.
+    fn define_vars(&mut self) {
+        let params = self.builder
+            .func
+            .signature
+            .params
+            .iter()
+            .enumerate()
+            .filter(|&(_, p)| p.purpose == ArgumentPurpose::Normal)
+            .map(|(i, p)| (i, p.value_type))
+            .collect::<Vec<_>>();
+        for (var, ty) in self.vars.clone() {
+            let candidates = params
+                .iter()
+                .filter(|&&(_, t)| t == ty)
+                .map(|&(i, _)| i)
+                .collect::<Vec<_>>();
+            let value = if !candidates.is_empty() && self.input.flag() {
+                let i = *self.input.choose(&candidates);
+                self.builder.arg_value(i)
+            } else {
+                self.constant(ty)
+            };
+            self.builder.def_var(var, value);
+        }
+    }
+
+    /// Pick a random variable of type `ty`.
+    fn var(&mut self, ty: Type) -> Variable {
+        let vars = self.vars
+            .iter()
+            .filter(|&&(_, t)| t == ty)
+            .map(|&(v, _)| v)
+            .collect::<Vec<_>>();
+        *self.input.choose(&vars)
+    }
+
+    /// Get the current value of a random variable of type `ty`.
+    fn value(&mut self, ty: Type) -> Value {
+        let var = self.var(ty);
+        self.builder.use_var(var)
+    }
+
+    /// Get a random integer type.
+    fn int_type(&mut self) -> Type {
+        *self.input.choose(&[I32, I64])
+    }
+
+    /// Get a random float type.
+    fn float_type(&mut self) -> Type {
+        *self.input.choose(&[F32, F64])
+    }
+
+    fn constant(&mut self, ty: Type) -> Value {
+        match ty {
+            I32 => {
+                let imm = self.input.u32() as i32;
+                self.builder.ins().iconst(ty, i64::from(imm))
+            }
+            I64 => {
+                let imm = self.input.u64() as i64;
+                self.builder.ins().iconst(ty, imm)
+            }
+            F32 => {
+                let bits = self.input.u32();
+                self.builder.ins().f32const(Ieee32::with_bits(bits))
+            }
+            F64 => {
+                let bits = self.input.u64();
+                self.builder.ins().f64const(Ieee64::with_bits(bits))
+            }
+            B1 => {
+                // Intel has no encoding for `bconst`, so compare a constant instead.
+                let imm = self.input.u32() as i32;
+                let x = self.builder.ins().iconst(I32, i64::from(imm));
+                let cc = *self.input.choose(&INT_CCS);
+                self.builder.ins().icmp_imm(cc, x, 0)
+            }
+            _ => panic!("unexpected type {}", ty),
+        }
+    }
+
+    /// Generate an instruction that has a side effect or assigns a variable.
+    fn statement(&mut self) {
+        match self.input.below(10) {
+            4 => self.stack_store(),
+            5 => self.heap_store(),
+            6 => self.call(),
+            7 => {
+                let cond = self.condition();
+                let code = TrapCode::User(self.input.byte() as u16);
+                if self.input.flag() {
+                    self.builder.ins().trapz(cond, code);
+                } else {
+                    self.builder.ins().trapnz(cond, code);
+                }
+            }
+            _ => {
+                let ty = *self.input.choose(&VAR_TYPES);
+                let value = self.expression(ty);
+                let var = self.var(ty);
+                self.builder.def_var(var, value);
+            }
+        }
+    }
+
+    /// Generate an instruction computing a value of type `ty`.
+    fn expression(&mut self, ty: Type) -> Value {
+        match ty {
+            I32 | I64 => self.int_expression(ty),
+            F32 | F64 => self.float_expression(ty),
+            B1 => self.bool_expression(),
+            _ => panic!("unexpected type {}", ty),
+        }
+    }
+
+    fn int_expression(&mut self, ty: Type) -> Value {
+        match self.input.below(12) {
+            1 => {
+                let x = self.value(ty);
+                let op = self.input.below(15);
+                // Shift amounts can have any integer type.
+                let y = if op >= 6 && op < 11 {
+                    let amt_ty = self.int_type();
+                    self.value(amt_ty)
+                } else {
+                    self.value(ty)
+                };
+                let ins = self.builder.ins();
+                match op {
+                    0 => ins.iadd(x, y),
+                    1 => ins.isub(x, y),
+                    2 => ins.imul(x, y),
+                    3 => ins.band(x, y),
+                    4 => ins.bor(x, y),
+                    5 => ins.bxor(x, y),
+                    6 => ins.ishl(x, y),
+                    7 => ins.ushr(x, y),
+                    8 => ins.sshr(x, y),
+                    9 => ins.rotl(x, y),
+                    10 => ins.rotr(x, y),
+                    11 => ins.udiv(x, y),
+                    12 => ins.sdiv(x, y),
+                    13 => ins.urem(x, y),
+                    _ => ins.srem(x, y),
+                }
+            }
+            2 => {
+                let x = self.value(ty);
+                let op = self.input.below(10);
+                let imm = if op >= 5 {
+                    self.input.below(ty.bits() as usize) as i64
+                } else {
+                    i64::from(self.input.u32() as i32)
+                };
+                let ins = self.builder.ins();
+                match op {
+                    0 => ins.iadd_imm(x, imm),
+                    1 => ins.imul_imm(x, imm),
+                    2 => ins.band_imm(x, imm),
+                    3 => ins.bor_imm(x, imm),
+                    4 => ins.bxor_imm(x, imm),
+                    5 => ins.ishl_imm(x, imm),
+                    6 => ins.ushr_imm(x, imm),
+                    7 => ins.sshr_imm(x, imm),
+                    8 => ins.rotl_imm(x, imm),
+                    _ => ins.rotr_imm(x, imm),
+                }
+            }
+            3 => {
+                let x = self.value(ty);
+                let ins = self.builder.ins();
+                match self.input.below(3) {
+                    0 => ins.clz(x),
+                    1 => ins.ctz(x),
+                    _ => ins.popcnt(x),
+                }
+            }
+            4 => {
+                let c = self.value(B1);
+                self.builder.ins().bint(ty, c)
+            }
+            5 => {
+                if ty == I64 {
+                    let x = self.value(I32);
+                    if self.input.flag() {
+                        self.builder.ins().sextend(ty, x)
+                    } else {
+                        self.builder.ins().uextend(ty, x)
+                    }
+                } else {
+                    let x = self.value(I64);
+                    self.builder.ins().ireduce(ty, x)
+                }
+            }
+            6 => {
+                let float_ty = self.float_type();
+                let x = self.value(float_ty);
+                if self.input.flag() {
+                    self.builder.ins().fcvt_to_sint(ty, x)
+                } else {
+                    self.builder.ins().fcvt_to_uint(ty, x)
+                }
+            }
+            7 => {
+                let x = self.value(if ty == I32 { F32 } else { F64 });
+                self.builder.ins().bitcast(ty, x)
+            }
+            8 => self.select(ty),
+            9 => self.stack_load(ty),
+            10 => self.heap_load(ty),
+            _ => self.constant(ty),
+        }
+    }
+
+    fn float_expression(&mut self, ty: Type) -> Value {
+        match self.input.below(9) {
+            1 => {
+                let x = self.value(ty);
+                let y = self.value(ty);
+                let ins = self.builder.ins();
+                match self.input.below(7) {
+                    0 => ins.fadd(x, y),
+                    1 => ins.fsub(x, y),
+                    2 => ins.fmul(x, y),
+                    3 => ins.fdiv(x, y),
+                    4 => ins.fmin(x, y),
+                    5 => ins.fmax(x, y),
+                    _ => ins.fcopysign(x, y),
+                }
+            }
+            2 => {
+                let x = self.value(ty);
+                let ins = self.builder.ins();
+                match self.input.below(7) {
+                    0 => ins.fneg(x),
+                    1 => ins.fabs(x),
+                    2 => ins.sqrt(x),
+                    3 => ins.ceil(x),
+                    4 => ins.floor(x),
+                    5 => ins.trunc(x),
+                    _ => ins.nearest(x),
+                }
+            }
+            3 => {
+                let int_ty = self.int_type();
+                let x = self.value(int_ty);
+                if self.input.flag() {
+                    self.builder.ins().fcvt_from_sint(ty, x)
+                } else {
+                    self.builder.ins().fcvt_from_uint(ty, x)
+                }
+            }
+            4 => {
+                if ty == F64 {
+                    let x = self.value(F32);
+                    self.builder.ins().fpromote(ty, x)
+                } else {
+                    let x = self.value(F64);
+                    self.builder.ins().fdemote(ty, x)
+                }
+            }
+            5 => {
+                let x = self.value(if ty == F32 { I32 } else { I64 });
+                self.builder.ins().bitcast(ty, x)
+            }
+            6 => self.select(ty),
+            7 => self.stack_load(ty),
+            8 => self.heap_load(ty),
+            _ => self.constant(ty),
+        }
+    }
+
+    fn bool_expression(&mut self) -> Value {
+        match self.input.below(4) {
+            1 => {
+                let ty = self.int_type();
+                let cc = *self.input.choose(&INT_CCS);
+                let x = self.value(ty);
+                let y = self.value(ty);
+                self.builder.ins().icmp(cc, x, y)
+            }
+            2 => {
+                let ty = self.int_type();
+                let cc = *self.input.choose(&INT_CCS);
+                let x = self.value(ty);
+                let imm = i64::from(self.input.u32() as i32);
+                self.builder.ins().icmp_imm(cc, x, imm)
+            }
+            3 => {
+                let ty = self.float_type();
+                let cc = *self.input.choose(&FLOAT_CCS);
+                let x = self.value(ty);
+                let y = self.value(ty);
+                self.builder.ins().fcmp(cc, x, y)
+            }
+            _ => self.constant(B1),
+        }
+    }
+
+    fn select(&mut self, ty: Type) -> Value {
+        let c = self.value(B1);
+        let x = self.value(ty);
+        let y = self.value(ty);
+        self.builder.ins().select(c, x, y)
+    }
+
+    /// Get a value to branch or trap on: a boolean or an integer.
+    fn condition(&mut self) -> Value {
+        let ty = *self.input.choose(&[B1, I32, I64]);
+        self.value(ty)
+    }
+
+    /// Pick a stack slot and an aligned offset to access `bytes` bytes in it.
+    fn stack_location(&mut self, bytes: u32) -> Option<(StackSlot, i32)> {
+        let slots = self.slots
+            .iter()
+            .filter(|&&(_, size)| size >= bytes)
+            .cloned()
+            .collect::<Vec<_>>();
+        if slots.is_empty() {
+            return None;
+        }
+        let (slot, size) = *self.input.choose(&slots);
+        let offset = self.input.below((size / bytes) as usize) as u32 * bytes;
+        Some((slot, offset as i32))
+    }
+
+    // Intel only has encodings for `stack_addr`, so access stack slots through their address
+    // instead of with `stack_load` and `stack_store`.
+
+    fn stack_load(&mut self, ty: Type) -> Value {
+        match self.stack_location(ty.bytes()) {
+            Some((slot, offset)) => {
+                let addr = self.builder.ins().stack_addr(I64, slot, offset);
+                self.builder.ins().load(ty, MemFlags::new(), addr, 0)
+            }
+            None => self.constant(ty),
+        }
+    }
+
+    fn stack_store(&mut self) {
+        let ty = *self.input.choose(&ABI_TYPES);
+        if let Some((slot, offset)) = self.stack_location(ty.bytes()) {
+            let x = self.value(ty);
+            let addr = self.builder.ins().stack_addr(I64, slot, offset);
+            self.builder.ins().store(MemFlags::new(), x, addr, 0);
+        }
+    }
+
+    /// Compute the address of a random heap location to access `bytes` bytes.
+    fn heap_address(&mut self, bytes: u32) -> Option<Value> {
+        self.heap.map(|heap| {
+            let index = self.value(I32);
+            self.builder.ins().heap_addr(I64, heap, index, bytes)
+        })
+    }
+
+    fn heap_load(&mut self, ty: Type) -> Value {
+        match self.heap_address(ty.bytes()) {
+            Some(addr) => self.builder.ins().load(ty, MemFlags::new(), addr, 0),
+            None => self.constant(ty),
+        }
+    }
+
+    fn heap_store(&mut self) {
+        let ty = *self.input.choose(&ABI_TYPES);
+        if let Some(addr) = self.heap_address(ty.bytes()) {
+            let x = self.value(ty);
+            self.builder.ins().store(MemFlags::new(), x, addr, 0);
+        }
+    }
+
+    fn call(&mut self) {
+        if self.callees.is_empty() {
+            return;
+        }
+        let callee = *self.input.choose(&self.callees);
+        let sig = self.builder.func.dfg.ext_funcs[callee].signature;
+        let (params, returns) = {
+            let sig = &self.builder.func.dfg.signatures[sig];
+            let types = |params: &[AbiParam]| {
+                params.iter().map(|p| p.value_type).collect::<Vec<_>>()
+            };
+            (types(&sig.params), types(&sig.returns))
+        };
+        let args = params.iter().map(|&ty| self.value(ty)).collect::<Vec<_>>();
+        let call = self.builder.ins().call(callee, &args);
+        let results = self.builder.inst_results(call).to_vec();
+        for (result, ty) in results.into_iter().zip(returns) {
+            let var = self.var(ty);
+            self.builder.def_var(var, result);
+        }
+    }
+
+    /// Get arguments for the explicit parameters of `ebb`.
+    fn branch_args(&mut self, ebb: Ebb) -> Vec<Value> {
+        let types = self.ebbs.iter().find(|e| e.0 == ebb).unwrap().1.clone();
+        types.into_iter().map(|ty| self.value(ty)).collect()
+    }
+
+    /// Pick a random branch target. The entry block can't be a branch target.
+    fn target(&mut self) -> Ebb {
+        let i = 1 + self.input.below(self.ebbs.len() - 1);
+        self.ebbs[i].0
+    }
+
+    /// Terminate the `i`th EBB. The next EBB is always a successor, so every EBB is reachable.
+    fn terminator(&mut self, i: usize) {
+        if i + 1 == self.ebbs.len() {
+            self.return_();
+            return;
+        }
+        let next = self.ebbs[i + 1].0;
+        match self.input.below(5) {
+            1 => {
+                let target = self.target();
+                self.cond_branch(target);
+                self.jump(next);
+            }
+            2 => {
+                self.cond_branch(next);
+                let target = self.target();
+                self.jump(target);
+            }
+            3 => {
+                // Jump tables can only target EBBs without parameters.
+                let targets = self.ebbs[1..]
+                    .iter()
+                    .filter(|e| e.1.is_empty())
+                    .map(|e| e.0)
+                    .collect::<Vec<_>>();
+                if !targets.is_empty() {
+                    let mut data = JumpTableData::new();
+                    for _ in 0..1 + self.input.below(4) {
+                        data.push_entry(*self.input.choose(&targets));
+                    }
+                    let jt = self.builder.create_jump_table(data);
+                    let x = self.value(I32);
+                    self.builder.ins().br_table(x, jt);
+                }
+                self.jump(next);
+            }
+            4 => {
+                self.cond_branch(next);
+                self.return_();
+            }
+            _ => self.jump(next),
+        }
+    }
+
+    fn jump(&mut self, ebb: Ebb) {
+        let args = self.branch_args(ebb);
+        self.builder.ins().jump(ebb, &args);
+    }
+
+    fn cond_branch(&mut self, ebb: Ebb) {
+        let cond = self.condition();
+        let args = self.branch_args(ebb);
+        if self.input.flag() {
+            self.builder.ins().brz(cond, ebb, &args);
+        } else {
+            self.builder.ins().brnz(cond, ebb, &args);
+        }
+    }
+
+    fn return_(&mut self) {
+        let types = self.builder
+            .func
+            .signature
+            .returns
+            .iter()
+            .map(|r| r.value_type)
+            .collect::<Vec<_>>();
+        let values = types.into_iter().map(|ty| self.value(ty)).collect::<Vec<_>>();
+        self.builder.ins().return_(&values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_function, Config};
+    use cretonne::ir::FunctionName;
+    use cretonne::settings;
+    use cretonne::verifier::verify_function;
+
+    /// Make `len` pseudo-random bytes from `seed`.
+    fn random_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 32) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn verifies() {
+        let flags = settings::Flags::new(&settings::builder());
+        let config = Config::default();
+        for seed in 0..200 {
+            let data = random_bytes(seed, 4000);
+            let func = generate_function(FunctionName::new("fuzz"), &data, &config);
+            if let Err(e) = verify_function(&func, &flags) {
+                panic!("seed {}: {}\n{}", seed, e, func.display(None));
+            }
+        }
+    }
+
+    #[test]
+    fn empty_input() {
+        let flags = settings::Flags::new(&settings::builder());
+        let func = generate_function(FunctionName::new("fuzz"), &[], &Config::default());
+        verify_function(&func, &flags).unwrap();
+        assert_eq!(func.layout.ebbs().count(), 1);
+    }
+}
@@ -0,0 +1,77 @@
+//! Reading random choices from a fuzzer input.
+
+/// A source of choices backed by a byte string.
+///
+/// Every choice consumes a few bytes of the input. When the input is exhausted, all choices return
+/// 0, so the first alternative of a choice should always be the simplest one. This way a short
+/// input produces a small function, and mutating a byte only changes a single decision.
+pub struct Input<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Input<'a> {
+    /// Create a source of choices reading from `data`.
+    pub fn new(data: &'a [u8]) -> Input<'a> {
+        Input { data, pos: 0 }
+    }
+
+    /// Get the next byte of the input, or 0 if it is exhausted.
+    pub fn byte(&mut self) -> u8 {
+        match self.data.get(self.pos) {
+            Some(&b) => {
+                self.pos += 1;
+                b
+            }
+            None => 0,
+        }
+    }
+
+    /// Get a 32-bit number built from the next four bytes of the input.
+    pub fn u32(&mut self) -> u32 {
+        (0..4).fold(0, |x, _| (x << 8) | u32::from(self.byte()))
+    }
+
+    /// Get a 64-bit number built from the next eight bytes of the input.
+    pub fn u64(&mut self) -> u64 {
+        let hi = u64::from(self.u32());
+        (hi << 32) | u64::from(self.u32())
+    }
+
+    /// Get a number in the range `0..n`, where `n` must be positive.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "empty range");
+        if n <= 256 {
+            self.byte() as usize % n
+        } else {
+            self.u32() as usize % n
+        }
+    }
+
+    /// Get a boolean.
+    pub fn flag(&mut self) -> bool {
+        self.byte() & 1 != 0
+    }
+
+    /// Choose an element of the non-empty slice `items`.
+    pub fn choose<'b, T>(&mut self, items: &'b [T]) -> &'b T {
+        let i = self.below(items.len());
+        &items[i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Input;
+
+    #[test]
+    fn exhausted() {
+        let mut input = Input::new(&[7, 1, 2, 3, 4]);
+        assert_eq!(input.below(5), 2);
+        assert_eq!(input.u32(), 0x0102_0304);
+        assert_eq!(input.byte(), 0);
+        assert_eq!(input.below(1000), 0);
+        assert_eq!(*input.choose(&["a", "b"]), "a");
+        assert!(!input.flag());
+    }
+}
@@ -0,0 +1,20 @@
+//! Random Cretonne IL function generator.
+//!
+//! This crate builds random functions for fuzzing the code generator. The functions are built with
+//! the `cton_frontend::FunctionBuilder` from a string of bytes, typically provided by a fuzzer
+//! like libFuzzer, and are always accepted by the verifier. They have random control flow graphs
+//! with EBB parameters, values of mixed types, calls to external functions, and accesses to stack
+//! slots and a heap.
+//!
+//! The same input always generates the same function, so a crashing input found by the fuzzer can
+//! be turned back into a function with `cton-util fuzzgen`.
+
+#![deny(missing_docs)]
+
+extern crate cretonne;
+extern crate cton_frontend;
+
+pub use generator::{Config, generate_function};
+
+mod generator;
+mod input;
@@ -0,0 +1,905 @@
+//! Execution of Cretonne IL functions.
+
+use cretonne::entity::EntityMap;
+use cretonne::ir::{ArgumentPurpose, Ebb, Function, FunctionName, GlobalVar, Heap, HeapBase,
+                   HeapStyle, GlobalVarData, Inst, InstructionData, Opcode, Signature, StackSlot,
+                   TrapCode, Type, Value};
+use cretonne::ir::types;
+use memory::{self, Memory};
+use ops;
+use std::error::Error as StdError;
+use std::fmt;
+use std::mem;
+use value::DataValue;
+
+/// Calls nested deeper than this trap with `TrapCode::StackOverflow`.
+const MAX_CALL_DEPTH: usize = 1000;
+
+/// The value of a `func_addr` instruction is this base plus the index of the function in the
+/// interpreter. Function addresses never overlap the simulated memory.
+const FUNCTION_ADDRESS_BASE: u64 = 0xfff0_0000;
+
+/// The result of calling a function in the interpreter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CallOutcome {
+    /// The function returned these values.
+    Return(Vec<DataValue>),
+    /// The function, or one of its callees, trapped.
+    Trap(TrapCode),
+}
+
+/// An error preventing the interpreter from running a function.
+///
+/// Traps are not errors; they are reported as `CallOutcome::Trap`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InterpreterError {
+    /// A called function is not known to the interpreter.
+    UnknownFunction(String),
+    /// The arguments don't match the signature of the called function.
+    BadArguments(String),
+    /// The function uses an instruction or feature the interpreter doesn't support.
+    Unsupported(String),
+    /// The function is malformed in a way the verifier would have rejected.
+    Malformed(String),
+}
+
+impl fmt::Display for InterpreterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InterpreterError::UnknownFunction(ref name) => write!(f, "unknown function {}", name),
+            InterpreterError::BadArguments(ref msg) => write!(f, "bad arguments: {}", msg),
+            InterpreterError::Unsupported(ref msg) => write!(f, "unsupported: {}", msg),
+            InterpreterError::Malformed(ref msg) => write!(f, "malformed function: {}", msg),
+        }
+    }
+}
+
+impl StdError for InterpreterError {
+    fn description(&self) -> &str {
+        match *self {
+            InterpreterError::UnknownFunction(_) => "unknown function",
+            InterpreterError::BadArguments(_) => "bad arguments",
+            InterpreterError::Unsupported(_) => "unsupported",
+            InterpreterError::Malformed(_) => "malformed function",
+        }
+    }
+}
+
+/// Internal reason for abandoning the execution of a function.
+enum Fault {
+    Trap(TrapCode),
+    Error(InterpreterError),
+}
+
+impl From<InterpreterError> for Fault {
+    fn from(e: InterpreterError) -> Fault {
+        Fault::Error(e)
+    }
+}
+
+impl From<TrapCode> for Fault {
+    fn from(code: TrapCode) -> Fault {
+        Fault::Trap(code)
+    }
+}
+
+type Exec<T> = Result<T, Fault>;
+
+/// What to do after executing an instruction.
+enum Control {
+    /// Assign these values to the instruction results and continue with the next instruction.
+    Results(Vec<DataValue>),
+    /// Jump to an EBB with arguments.
+    Jump(Ebb, Vec<DataValue>),
+    /// Call the function with the given index.
+    Call(usize, Vec<DataValue>),
+    /// Replace the current frame with a call to the function with the given index.
+    TailCall(usize, Vec<DataValue>),
+    /// Return from the function.
+    Return(Vec<DataValue>),
+}
+
+/// The state of a function being executed.
+struct Frame<'a> {
+    func: &'a Function,
+    values: EntityMap<Value, Option<DataValue>>,
+    slots: EntityMap<StackSlot, u64>,
+    vmctx: Option<u64>,
+    /// The top of memory before the stack slots of this frame were allocated.
+    top: u64,
+    /// The next instruction to execute.
+    pos: Option<Inst>,
+    /// The call instruction waiting for a callee to return.
+    call: Option<Inst>,
+}
+
+impl<'a> Frame<'a> {
+    /// Continue execution at `ebb`, passing `args` to its parameters.
+    fn jump(&mut self, ebb: Ebb, args: Vec<DataValue>) -> Exec<()> {
+        let params = self.func.dfg.ebb_params(ebb);
+        if params.len() != args.len() {
+            return Err(Fault::Error(InterpreterError::Malformed(format!(
+                "{} expects {} arguments, got {}",
+                ebb,
+                params.len(),
+                args.len()
+            ))));
+        }
+        for (&param, arg) in params.iter().zip(args) {
+            self.values[param] = Some(arg);
+        }
+        self.pos = self.func.layout.first_inst(ebb);
+        Ok(())
+    }
+
+    /// Assign `values` to the results of `inst`.
+    fn set_results(&mut self, inst: Inst, values: Vec<DataValue>) -> Exec<()> {
+        let results = self.func.dfg.inst_results(inst);
+        if results.len() != values.len() {
+            return Err(Fault::Error(InterpreterError::Malformed(format!(
+                "{} has {} results, got {} values",
+                inst,
+                results.len(),
+                values.len()
+            ))));
+        }
+        for (&res, v) in results.iter().zip(values) {
+            self.values[res] = Some(v);
+        }
+        Ok(())
+    }
+
+    fn get(&self, v: Value) -> Exec<DataValue> {
+        let v = self.func.dfg.resolve_aliases(v);
+        match self.values[v] {
+            Some(ref x) => Ok(x.clone()),
+            None => Err(Fault::Error(InterpreterError::Malformed(
+                format!("{} used before it is defined", v),
+            ))),
+        }
+    }
+
+    fn get_all(&self, vs: &[Value]) -> Exec<Vec<DataValue>> {
+        vs.iter().map(|&v| self.get(v)).collect()
+    }
+}
+
+/// An interpreter for a set of functions that can call each other.
+///
+/// Calls are resolved by name among the functions given to `Interpreter::new`. All functions
+/// share a single simulated `Memory` which holds their stack slots as well as any memory set up by
+/// the client, for example a VM context.
+pub struct Interpreter<'a> {
+    functions: Vec<&'a Function>,
+    memory: Memory,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Create an interpreter for `functions`.
+    pub fn new<I>(functions: I) -> Interpreter<'a>
+    where
+        I: IntoIterator<Item = &'a Function>,
+    {
+        Interpreter {
+            functions: functions.into_iter().collect(),
+            memory: Memory::new(),
+        }
+    }
+
+    /// Get the simulated memory.
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Get the simulated memory for modification.
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    /// Get the function with index `index`.
+    pub fn function(&self, index: usize) -> &'a Function {
+        self.functions[index]
+    }
+
+    /// Find the index of the function named `name`.
+    pub fn find_function(&self, name: &FunctionName) -> Option<usize> {
+        self.functions.iter().position(|f| f.name == *name)
+    }
+
+    /// Call the function named `name` with `args`.
+    pub fn call_by_name(
+        &mut self,
+        name: &FunctionName,
+        args: &[DataValue],
+    ) -> Result<CallOutcome, InterpreterError> {
+        let index = self.find_function(name).ok_or_else(|| {
+            InterpreterError::UnknownFunction(name.to_string())
+        })?;
+        self.call(index, args)
+    }
+
+    /// Call the function with index `index` with `args`.
+    pub fn call(
+        &mut self,
+        index: usize,
+        args: &[DataValue],
+    ) -> Result<CallOutcome, InterpreterError> {
+        let sig = &self.functions[index].signature;
+        if args.len() != sig.params.len() {
+            return Err(InterpreterError::BadArguments(format!(
+                "{} expects {} arguments, got {}",
+                self.functions[index].name,
+                sig.params.len(),
+                args.len()
+            )));
+        }
+        for (arg, param) in args.iter().zip(&sig.params) {
+            if !arg.has_type(param.value_type) {
+                return Err(InterpreterError::BadArguments(
+                    format!("{} is not a {}", arg, param.value_type),
+                ));
+            }
+        }
+        match self.call_function(index, args.to_vec()) {
+            Ok(results) => Ok(CallOutcome::Return(results)),
+            Err(Fault::Trap(code)) => Ok(CallOutcome::Trap(code)),
+            Err(Fault::Error(e)) => Err(e),
+        }
+    }
+
+    /// Call a function and run it to completion.
+    ///
+    /// Calls made by the function are executed with an explicit stack of frames rather than by
+    /// recursion, so deeply recursive IL can't overflow the host stack.
+    fn call_function(&mut self, index: usize, args: Vec<DataValue>) -> Exec<Vec<DataValue>> {
+        let top = self.memory.top();
+        let result = self.execute(index, args);
+        // A trap unwinds all frames at once.
+        self.memory.release(top);
+        result
+    }
+
+    fn execute(&mut self, index: usize, args: Vec<DataValue>) -> Exec<Vec<DataValue>> {
+        let mut stack = Vec::new();
+        let mut frame = self.enter(index, args)?;
+        loop {
+            let inst = frame.pos.ok_or_else(|| {
+                InterpreterError::Malformed(
+                    format!("{} doesn't end with a terminator", frame.func.name),
+                )
+            })?;
+            frame.pos = frame.func.layout.next_inst(inst);
+            match self.step(&frame, inst)? {
+                Control::Results(values) => frame.set_results(inst, values)?,
+                Control::Jump(ebb, values) => frame.jump(ebb, values)?,
+                Control::Call(callee, values) => {
+                    if stack.len() + 1 >= MAX_CALL_DEPTH {
+                        return Err(Fault::Trap(TrapCode::StackOverflow));
+                    }
+                    frame.call = Some(inst);
+                    let callee = self.enter(callee, values)?;
+                    stack.push(mem::replace(&mut frame, callee));
+                }
+                Control::TailCall(callee, values) => {
+                    self.memory.release(frame.top);
+                    frame = self.enter(callee, values)?;
+                }
+                Control::Return(values) => {
+                    self.memory.release(frame.top);
+                    frame = match stack.pop() {
+                        Some(caller) => caller,
+                        None => return Ok(values),
+                    };
+                    let call = frame.call.take().expect("caller must be at a call");
+                    frame.set_results(call, values)?;
+                }
+            }
+        }
+    }
+
+    /// Create a frame for calling a function, allocating its stack slots.
+    fn enter(&mut self, index: usize, args: Vec<DataValue>) -> Exec<Frame<'a>> {
+        let func = self.functions[index];
+        let mut frame = Frame {
+            func,
+            values: EntityMap::new(),
+            slots: EntityMap::new(),
+            vmctx: func.signature
+                .special_param_index(ArgumentPurpose::VMContext)
+                .and_then(|i| args.get(i))
+                .map(DataValue::to_bits),
+            top: self.memory.top(),
+            pos: None,
+            call: None,
+        };
+        for ss in func.stack_slots.keys() {
+            let size = u64::from(func.stack_slots[ss].size);
+            frame.slots[ss] = self.memory.allocate(size, 16).ok_or(
+                TrapCode::StackOverflow,
+            )?;
+        }
+        let entry = func.layout.entry_block().ok_or_else(|| {
+            InterpreterError::Malformed(format!("{} has no entry block", func.name))
+        })?;
+        frame.jump(entry, args)?;
+        Ok(frame)
+    }
+
+    /// Execute a single instruction.
+    fn step(&mut self, frame: &Frame<'a>, inst: Inst) -> Exec<Control> {
+        let func = frame.func;
+        let dfg = &func.dfg;
+        let data = &dfg[inst];
+        let opcode = data.opcode();
+        let args = frame.get_all(dfg.inst_fixed_args(inst))?;
+        let vargs = frame.get_all(dfg.inst_variable_args(inst))?;
+        // Type of the first argument and the first result, where they exist.
+        let arg_ty = dfg.inst_args(inst).first().map_or(
+            types::VOID,
+            |&v| dfg.value_type(v),
+        );
+        let res_ty = dfg.inst_results(inst).first().map_or(
+            types::VOID,
+            |&v| dfg.value_type(v),
+        );
+        let unsupported = || {
+            Err(Fault::Error(InterpreterError::Unsupported(
+                format!("{}", dfg.display_inst(inst, None)),
+            )))
+        };
+
+        let result = match *data {
+            InstructionData::NullAry { .. } => return unsupported(),
+            InstructionData::UnaryImm { .. } if opcode == Opcode::AdjustSpImm => {
+                return unsupported()
+            }
+            InstructionData::UnaryImm { imm, .. } => {
+                let x: i64 = imm.into();
+                splat(res_ty, DataValue::from_bits(res_ty.lane_type(), x as u64))
+            }
+            InstructionData::UnaryIeee32 { imm, .. } => DataValue::F32(imm),
+            InstructionData::UnaryIeee64 { imm, .. } => DataValue::F64(imm),
+            InstructionData::UnaryBool { imm, .. } => splat(res_ty, DataValue::B(imm)),
+            InstructionData::UnaryGlobalVar { global_var, .. } => {
+                DataValue::from_bits(res_ty, self.global_addr(frame, global_var, res_ty)?)
+            }
+            InstructionData::Unary { .. } => {
+                let x = &args[0];
+                match opcode {
+                    Opcode::Copy | Opcode::Spill | Opcode::Fill => x.clone(),
+                    Opcode::Bnot | Opcode::Clz | Opcode::Cls | Opcode::Ctz | Opcode::Popcnt |
+                    Opcode::Sqrt | Opcode::Fneg | Opcode::Fabs | Opcode::Ceil |
+                    Opcode::Floor | Opcode::Trunc | Opcode::Nearest => {
+                        ops::lanes1(arg_ty, x, |t, x| ops::unary(opcode, t, x))?
+                    }
+                    Opcode::Ireduce | Opcode::Uextend | Opcode::Sextend | Opcode::Breduce |
+                    Opcode::Bextend | Opcode::Bint | Opcode::Bmask | Opcode::Fpromote |
+                    Opcode::Fdemote | Opcode::FcvtToUint | Opcode::FcvtToSint |
+                    Opcode::FcvtFromUint | Opcode::FcvtFromSint => {
+                        let to = res_ty.lane_type();
+                        ops::lanes1(arg_ty, x, |t, x| ops::convert(opcode, t, to, x))?
+                    }
+                    Opcode::Bitcast => {
+                        let mut bytes = Vec::new();
+                        memory::encode(arg_ty, x, &mut bytes);
+                        memory::decode(res_ty, &bytes)
+                    }
+                    Opcode::Splat => splat(res_ty, x.clone()),
+                    Opcode::Isplit => {
+                        let bits = res_ty.lane_bits();
+                        let lo = ops::lanes1(arg_ty, x, |_, x| {
+                            Ok(DataValue::from_bits(res_ty.lane_type(), x.to_bits()))
+                        })?;
+                        let hi = ops::lanes1(arg_ty, x, |_, x| {
+                            Ok(DataValue::from_bits(
+                                res_ty.lane_type(),
+                                x.to_bits() >> bits,
+                            ))
+                        })?;
+                        return Ok(Control::Results(vec![lo, hi]));
+                    }
+                    Opcode::Vsplit => {
+                        let lanes = vector_lanes(x)?;
+                        let (lo, hi) = lanes.split_at(lanes.len() / 2);
+                        return Ok(Control::Results(
+                            vec![DataValue::V(lo.to_vec()), DataValue::V(hi.to_vec())],
+                        ));
+                    }
+                    _ => return unsupported(),
+                }
+            }
+            InstructionData::Binary { .. } => {
+                let (x, y) = (&args[0], &args[1]);
+                match opcode {
+                    Opcode::Ifcmp => ops::int_flags(arg_ty, x, y),
+                    Opcode::Ffcmp => ops::float_flags(x, y),
+                    Opcode::Fadd | Opcode::Fsub | Opcode::Fmul | Opcode::Fdiv | Opcode::Fmin |
+                    Opcode::Fmax | Opcode::Fcopysign => {
+                        ops::lanes2(arg_ty, x, y, |_, x, y| ops::float_binary(opcode, x, y))?
+                    }
+                    Opcode::Rotl | Opcode::Rotr | Opcode::Ishl | Opcode::Ushr | Opcode::Sshr => {
+                        // The shift amount is a scalar, even when shifting a vector.
+                        ops::lanes1(arg_ty, x, |t, x| ops::binary(opcode, t, x, y))?
+                    }
+                    Opcode::IaddCout | Opcode::IsubBout => {
+                        let (a, c) = if opcode == Opcode::IaddCout {
+                            ops::add_carry(arg_ty, x, y, false)
+                        } else {
+                            ops::sub_borrow(arg_ty, x, y, false)
+                        };
+                        return Ok(Control::Results(vec![a, DataValue::B(c)]));
+                    }
+                    Opcode::Iconcat => {
+                        let bits = arg_ty.lane_bits();
+                        ops::lanes2(arg_ty, x, y, |_, lo, hi| {
+                            Ok(DataValue::from_bits(
+                                res_ty.lane_type(),
+                                lo.to_bits() | hi.to_bits() << bits,
+                            ))
+                        })?
+                    }
+                    Opcode::Vconcat => {
+                        let mut lanes = vector_lanes(x)?.to_vec();
+                        lanes.extend_from_slice(vector_lanes(y)?);
+                        DataValue::V(lanes)
+                    }
+                    Opcode::X86Fmin | Opcode::X86Fmax => return unsupported(),
+                    _ => ops::lanes2(arg_ty, x, y, |t, x, y| ops::binary(opcode, t, x, y))?,
+                }
+            }
+            InstructionData::BinaryImm { imm, .. } => {
+                let x = &args[0];
+                let imm: i64 = imm.into();
+                if opcode == Opcode::IfcmpImm {
+                    ops::int_flags(arg_ty, x, &DataValue::from_bits(arg_ty, imm as u64))
+                } else {
+                    ops::lanes1(arg_ty, x, |t, x| {
+                        ops::binary(opcode, t, x, &DataValue::from_bits(t, imm as u64))
+                    })?
+                }
+            }
+            InstructionData::Ternary { .. } => {
+                let (x, y, z) = (&args[0], &args[1], &args[2]);
+                match opcode {
+                    Opcode::Select => if x.is_true() { y.clone() } else { z.clone() },
+                    Opcode::Vselect => {
+                        DataValue::V(
+                            vector_lanes(x)?
+                                .iter()
+                                .zip(vector_lanes(y)?.iter().zip(vector_lanes(z)?))
+                                .map(|(c, (a, b))| if c.is_true() {
+                                    a.clone()
+                                } else {
+                                    b.clone()
+                                })
+                                .collect(),
+                        )
+                    }
+                    Opcode::Fma => {
+                        match (x, y, z) {
+                            (&DataValue::V(ref xs),
+                             &DataValue::V(ref ys),
+                             &DataValue::V(ref zs)) => {
+                                DataValue::V(
+                                    xs.iter()
+                                        .zip(ys.iter().zip(zs))
+                                        .map(|(x, (y, z))| ops::fma(x, y, z))
+                                        .collect(),
+                                )
+                            }
+                            _ => ops::fma(x, y, z),
+                        }
+                    }
+                    Opcode::IaddCin | Opcode::IaddCarry => {
+                        let (a, c) = ops::add_carry(arg_ty, x, y, z.is_true());
+                        if opcode == Opcode::IaddCin {
+                            a
+                        } else {
+                            return Ok(Control::Results(vec![a, DataValue::B(c)]));
+                        }
+                    }
+                    Opcode::IsubBin | Opcode::IsubBorrow => {
+                        let (a, b) = ops::sub_borrow(arg_ty, x, y, z.is_true());
+                        if opcode == Opcode::IsubBin {
+                            a
+                        } else {
+                            return Ok(Control::Results(vec![a, DataValue::B(b)]));
+                        }
+                    }
+                    _ => return unsupported(),
+                }
+            }
+            InstructionData::MultiAry { .. } => {
+                match opcode {
+                    Opcode::Return => return Ok(Control::Return(vargs)),
+                    _ => return unsupported(),
+                }
+            }
+            InstructionData::InsertLane { lane, .. } => {
+                let mut lanes = vector_lanes(&args[0])?.to_vec();
+                let lane = lane_index(&lanes, lane)?;
+                lanes[lane] = args[1].clone();
+                DataValue::V(lanes)
+            }
+            InstructionData::ExtractLane { lane, .. } => {
+                let lanes = vector_lanes(&args[0])?;
+                lanes[lane_index(lanes, lane)?].clone()
+            }
+            InstructionData::IntCompare { cond, .. } => {
+                ops::lanes2(arg_ty, &args[0], &args[1], |t, x, y| {
+                    Ok(DataValue::B(ops::icmp(cond, t, x, y)))
+                })?
+            }
+            InstructionData::IntCompareImm { cond, imm, .. } => {
+                let imm: i64 = imm.into();
+                let y = DataValue::from_bits(arg_ty, imm as u64);
+                DataValue::B(ops::icmp(cond, arg_ty, &args[0], &y))
+            }
+            InstructionData::IntCond { cond, .. } => DataValue::B(ops::int_cond(cond, &args[0])),
+            InstructionData::FloatCompare { cond, .. } => {
+                ops::lanes2(arg_ty, &args[0], &args[1], |_, x, y| {
+                    Ok(DataValue::B(ops::fcmp(cond, x, y)))
+                })?
+            }
+            InstructionData::FloatCond { cond, .. } => {
+                DataValue::B(ops::float_cond(cond, &args[0]))
+            }
+            InstructionData::Jump { destination, .. } => {
+                return Ok(Control::Jump(destination, vargs));
+            }
+            InstructionData::Branch { destination, .. } => {
+                let taken = args[0].is_true() == (opcode == Opcode::Brnz);
+                return Ok(branch(taken, destination, vargs));
+            }
+            InstructionData::BranchIcmp { cond, destination, .. } => {
+                let taken = ops::icmp(cond, arg_ty, &args[0], &args[1]);
+                return Ok(branch(taken, destination, vargs));
+            }
+            InstructionData::BranchInt { cond, destination, .. } => {
+                let taken = ops::int_cond(cond, &args[0]);
+                return Ok(branch(taken, destination, vargs));
+            }
+            InstructionData::BranchFloat { cond, destination, .. } => {
+                let taken = ops::float_cond(cond, &args[0]);
+                return Ok(branch(taken, destination, vargs));
+            }
+            InstructionData::BranchTable { table, .. } => {
+                let index = args[0].to_bits();
+                // An index that doesn't fit in `usize` can't have a table entry.
+                let entry = if index <= usize::max_value() as u64 {
+                    func.jump_tables[table].get_entry(index as usize)
+                } else {
+                    None
+                };
+                return Ok(match entry {
+                    Some(ebb) => Control::Jump(ebb, Vec::new()),
+                    None => Control::Results(Vec::new()),
+                });
+            }
+            // The interpreter doesn't model unwinding, so an `invoke` always returns normally.
+            InstructionData::Call { func_ref, .. } |
+            InstructionData::Invoke { func_ref, .. } => {
+                let name = &dfg.ext_funcs[func_ref].name;
+                let index = self.find_function(name).ok_or_else(|| {
+                    InterpreterError::UnknownFunction(name.to_string())
+                })?;
+                return Ok(call(opcode, index, vargs));
+            }
+            InstructionData::IndirectCall { sig_ref, .. } => {
+                let addr = args[0].to_bits();
+                if addr == 0 {
+                    return Err(Fault::Trap(TrapCode::IndirectCallToNull));
+                }
+                let index = match addr.checked_sub(FUNCTION_ADDRESS_BASE) {
+                    Some(i) if i < self.functions.len() as u64 => i as usize,
+                    _ => {
+                        return Err(Fault::Error(InterpreterError::Unsupported(
+                            format!("call_indirect to non-function address {:#x}", addr),
+                        )))
+                    }
+                };
+                if !same_types(&dfg.signatures[sig_ref], &self.functions[index].signature) {
+                    return Err(Fault::Trap(TrapCode::BadSignature));
+                }
+                return Ok(call(opcode, index, vargs));
+            }
+            InstructionData::FuncAddr { func_ref, .. } => {
+                let name = &dfg.ext_funcs[func_ref].name;
+                let index = self.find_function(name).ok_or_else(|| {
+                    InterpreterError::UnknownFunction(name.to_string())
+                })?;
+                DataValue::from_bits(res_ty, FUNCTION_ADDRESS_BASE + index as u64)
+            }
+            InstructionData::StackLoad { stack_slot, offset, .. } => {
+                let addr = offset_addr(frame.slots[stack_slot], offset.into());
+                if opcode == Opcode::StackAddr {
+                    DataValue::from_bits(res_ty, addr)
+                } else {
+                    self.load(res_ty, addr)?
+                }
+            }
+            InstructionData::StackStore { stack_slot, offset, .. } => {
+                let addr = offset_addr(frame.slots[stack_slot], offset.into());
+                self.store(arg_ty, addr, &args[0])?;
+                return Ok(Control::Results(Vec::new()));
+            }
+            InstructionData::HeapAddr { heap, imm, .. } => {
+                let (base, bound) = self.heap_bounds(frame, heap, res_ty, arg_ty)?;
+                let offset = args[0].to_bits();
+                let size: u32 = imm.into();
+                if offset.checked_add(u64::from(size)).map_or(
+                    true,
+                    |end| end > bound,
+                )
+                {
+                    return Err(Fault::Trap(TrapCode::HeapOutOfBounds));
+                }
+                DataValue::from_bits(res_ty, base.wrapping_add(offset))
+            }
+            InstructionData::Load { offset, .. } => {
+                let addr = offset_addr(args[0].to_bits(), offset.into());
+                let mem_ty = match opcode {
+                    Opcode::Uload8 | Opcode::Sload8 => types::I8,
+                    Opcode::Uload16 | Opcode::Sload16 => types::I16,
+                    Opcode::Uload32 | Opcode::Sload32 => types::I32,
+                    _ => res_ty,
+                };
+                let x = self.load(mem_ty, addr)?;
+                match opcode {
+                    Opcode::Uload8 | Opcode::Uload16 | Opcode::Uload32 => {
+                        ops::convert(Opcode::Uextend, mem_ty, res_ty, &x)?
+                    }
+                    Opcode::Sload8 | Opcode::Sload16 | Opcode::Sload32 => {
+                        ops::convert(Opcode::Sextend, mem_ty, res_ty, &x)?
+                    }
+                    _ => x,
+                }
+            }
+            InstructionData::Store { offset, .. } => {
+                let addr = offset_addr(args[1].to_bits(), offset.into());
+                let mem_ty = match opcode {
+                    Opcode::Istore8 => types::I8,
+                    Opcode::Istore16 => types::I16,
+                    Opcode::Istore32 => types::I32,
+                    _ => arg_ty,
+                };
+                self.store(mem_ty, addr, &args[0])?;
+                return Ok(Control::Results(Vec::new()));
+            }
+            InstructionData::RegMove { .. } |
+            InstructionData::RegSpill { .. } |
+            InstructionData::RegFill { .. } => return unsupported(),
+            InstructionData::Trap { code, .. } => return Err(Fault::Trap(code)),
+            InstructionData::CondTrap { code, .. } => {
+                if args[0].is_true() == (opcode == Opcode::Trapnz) {
+                    return Err(Fault::Trap(code));
+                }
+                return Ok(Control::Results(Vec::new()));
+            }
+        };
+        Ok(Control::Results(vec![result]))
+    }
+
+    /// Load a value from memory, trapping if the address is invalid.
+    fn load(&self, ty: Type, addr: u64) -> Exec<DataValue> {
+        self.memory.load(ty, addr).ok_or(
+            Fault::Trap(TrapCode::HeapOutOfBounds),
+        )
+    }
+
+    /// Store a value to memory, trapping if the address is invalid.
+    fn store(&mut self, ty: Type, addr: u64, value: &DataValue) -> Exec<()> {
+        self.memory.store(ty, addr, value).ok_or(
+            Fault::Trap(TrapCode::HeapOutOfBounds),
+        )
+    }
+
+    /// Compute the address of a global variable. Pointers stored in memory have type `ptr_ty`.
+    fn global_addr(&self, frame: &Frame, gv: GlobalVar, ptr_ty: Type) -> Exec<u64> {
+        match frame.func.global_vars[gv] {
+            GlobalVarData::VmCtx { offset } => {
+                let vmctx = frame.vmctx.ok_or_else(|| {
+                    InterpreterError::Malformed(
+                        format!("{} needs a vmctx parameter", frame.func.name),
+                    )
+                })?;
+                Ok(offset_addr(vmctx, offset.into()))
+            }
+            GlobalVarData::Deref { base, offset } => {
+                let base = self.global_addr(frame, base, ptr_ty)?;
+                let ptr = self.load(ptr_ty, base)?.to_bits();
+                Ok(offset_addr(ptr, offset.into()))
+            }
+            GlobalVarData::Sym { ref name } => {
+                Err(Fault::Error(InterpreterError::Unsupported(
+                    format!("symbolic global variable {}", name),
+                )))
+            }
+        }
+    }
+
+    /// Get the base address and current bound of a heap. A dynamic bound is stored with the type
+    /// of the heap offsets, `offset_ty`.
+    fn heap_bounds(
+        &self,
+        frame: &Frame,
+        heap: Heap,
+        ptr_ty: Type,
+        offset_ty: Type,
+    ) -> Exec<(u64, u64)> {
+        let data = &frame.func.heaps[heap];
+        let base = match data.base {
+            HeapBase::GlobalVar(gv) => {
+                let addr = self.global_addr(frame, gv, ptr_ty)?;
+                self.load(ptr_ty, addr)?.to_bits()
+            }
+            HeapBase::ReservedReg => {
+                return Err(Fault::Error(InterpreterError::Unsupported(
+                    format!("{} has its base in a reserved register", heap),
+                )))
+            }
+        };
+        let bound = match data.style {
+            HeapStyle::Static { bound } => {
+                let bound: i64 = bound.into();
+                bound as u64
+            }
+            HeapStyle::Dynamic { bound_gv } => {
+                let addr = self.global_addr(frame, bound_gv, ptr_ty)?;
+                self.load(offset_ty, addr)?.to_bits()
+            }
+        };
+        Ok((base, bound))
+    }
+}
+
+/// Get the control flow for a conditional branch.
+fn branch(taken: bool, destination: Ebb, args: Vec<DataValue>) -> Control {
+    if taken {
+        Control::Jump(destination, args)
+    } else {
+        Control::Results(Vec::new())
+    }
+}
+
+/// Get the control flow for a call or tail call instruction.
+fn call(opcode: Opcode, callee: usize, args: Vec<DataValue>) -> Control {
+    if opcode.is_terminator() {
+        Control::TailCall(callee, args)
+    } else {
+        Control::Call(callee, args)
+    }
+}
+
+/// Broadcast a scalar to all lanes of `ty` if it is a vector type.
+fn splat(ty: Type, x: DataValue) -> DataValue {
+    if ty.is_vector() {
+        DataValue::V(vec![x; ty.lane_count() as usize])
+    } else {
+        x
+    }
+}
+
+/// Add a signed offset to an address.
+fn offset_addr(addr: u64, offset: i32) -> u64 {
+    addr.wrapping_add(i64::from(offset) as u64)
+}
+
+/// Get the lanes of a vector value.
+fn vector_lanes(x: &DataValue) -> Exec<&[DataValue]> {
+    match *x {
+        DataValue::V(ref lanes) => Ok(lanes),
+        _ => Err(Fault::Error(
+            InterpreterError::Malformed(format!("{} is not a vector", x)),
+        )),
+    }
+}
+
+/// Check that the lane immediate of an `insertlane` or `extractlane` is in range for `lanes`.
+fn lane_index(lanes: &[DataValue], lane: u8) -> Exec<usize> {
+    let lane = lane as usize;
+    if lane < lanes.len() {
+        Ok(lane)
+    } else {
+        Err(Fault::Error(InterpreterError::Malformed(format!(
+            "lane {} is out of range for a vector with {} lanes",
+            lane,
+            lanes.len()
+        ))))
+    }
+}
+
+/// Do two signatures have the same parameter and return types?
+fn same_types(a: &Signature, b: &Signature) -> bool {
+    let types = |params: &[::cretonne::ir::AbiParam]| {
+        params.iter().map(|p| p.value_type).collect::<Vec<_>>()
+    };
+    types(&a.params) == types(&b.params) && types(&a.returns) == types(&b.returns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cton_reader::parse_functions;
+
+    #[test]
+    fn calls() {
+        let functions = parse_functions(
+            "function %fact(i64) -> i64 {
+                 fn0 = function %fact(i64) -> i64
+             ebb0(v0: i64):
+                 brz v0, ebb1
+                 v1 = iadd_imm v0, -1
+                 v2 = call fn0(v1)
+                 v3 = imul v0, v2
+                 return v3
+             ebb1:
+                 v4 = iconst.i64 1
+                 return v4
+             }
+
+             function %slot(i32) -> i32 {
+                 ss0 = local 8
+             ebb0(v0: i32):
+                 stack_store v0, ss0+4
+                 v1 = stack_load.i32 ss0+4
+                 v2 = udiv v1, v0
+                 return v2
+             }",
+        ).unwrap();
+        let mut interp = Interpreter::new(&functions);
+        assert_eq!(
+            interp.call_by_name(&FunctionName::new("fact"), &[DataValue::I64(5)]),
+            Ok(CallOutcome::Return(vec![DataValue::I64(120)]))
+        );
+        assert_eq!(
+            interp.call_by_name(&FunctionName::new("fact"), &[DataValue::I64(-1)]),
+            Ok(CallOutcome::Trap(TrapCode::StackOverflow))
+        );
+        assert_eq!(
+            interp.call_by_name(&FunctionName::new("slot"), &[DataValue::I32(7)]),
+            Ok(CallOutcome::Return(vec![DataValue::I32(1)]))
+        );
+        assert_eq!(
+            interp.call_by_name(&FunctionName::new("slot"), &[DataValue::I32(0)]),
+            Ok(CallOutcome::Trap(TrapCode::IntegerDivisionByZero))
+        );
+        assert_eq!(interp.memory().top(), Memory::BASE);
+        assert!(
+            interp
+                .call_by_name(&FunctionName::new("slot"), &[DataValue::I64(0)])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn bad_lanes() {
+        let functions = parse_functions(
+            "function %insert(i32) -> i32x4 {
+             ebb0(v0: i32):
+                 v1 = splat.i32x4 v0
+                 v2 = insertlane v1, 4, v0
+                 return v2
+             }
+
+             function %extract(i32) -> i32 {
+             ebb0(v0: i32):
+                 v1 = splat.i32x4 v0
+                 v2 = extractlane v1, 9
+                 return v2
+             }",
+        ).unwrap();
+        let mut interp = Interpreter::new(&functions);
+        for &(name, msg) in &[
+            ("insert", "lane 4 is out of range for a vector with 4 lanes"),
+            ("extract", "lane 9 is out of range for a vector with 4 lanes"),
+        ]
+        {
+            assert_eq!(
+                interp.call_by_name(&FunctionName::new(name), &[DataValue::I32(1)]),
+                Err(InterpreterError::Malformed(msg.to_string()))
+            );
+        }
+        assert!(vector_lanes(&DataValue::I32(1)).is_err());
+    }
+}
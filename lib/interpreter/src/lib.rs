@@ -0,0 +1,25 @@
+//! Cretonne IL interpreter.
+//!
+//! This crate executes Cretonne IL functions directly, without compiling them. It is meant for
+//! testing: the results of interpreting a function can be compared with the expected results, or
+//! with the results of running the compiled code.
+//!
+//! The interpreter works on target-independent IL. It supports integer, floating point, boolean
+//! and vector types, loads and stores to a simulated memory holding stack slots and heaps, and
+//! calls between the functions given to it. Traps are reported as a `CallOutcome`.
+
+#![deny(missing_docs)]
+
+extern crate cretonne;
+
+#[cfg(test)]
+extern crate cton_reader;
+
+mod interpreter;
+mod memory;
+mod ops;
+mod value;
+
+pub use interpreter::{Interpreter, CallOutcome, InterpreterError};
+pub use memory::Memory;
+pub use value::DataValue;
@@ -0,0 +1,161 @@
+//! Simulated memory for the interpreter.
+//!
+//! The interpreter has a single flat address space. Memory is allocated from a bump allocator
+//! starting at `Memory::BASE`, so the null page is never valid. Stack slots are allocated from the
+//! same allocator when a function is called, and released when it returns.
+
+use cretonne::ir::Type;
+use value::DataValue;
+
+/// A flat, little-endian address space.
+pub struct Memory {
+    bytes: Vec<u8>,
+}
+
+impl Memory {
+    /// Address of the first byte of memory.
+    pub const BASE: u64 = 0x1000;
+
+    /// Memory can't grow beyond this address. Function addresses live above it.
+    pub const LIMIT: u64 = 0x1000_0000;
+
+    /// Create an empty address space.
+    pub fn new() -> Memory {
+        Memory { bytes: Vec::new() }
+    }
+
+    /// Allocate `size` zeroed bytes aligned to `align` bytes, and return their address.
+    ///
+    /// Returns `None` if the address space is exhausted.
+    pub fn allocate(&mut self, size: u64, align: u64) -> Option<u64> {
+        let align = align.max(1);
+        let end = Memory::BASE + self.bytes.len() as u64;
+        let addr = (end + align - 1) / align * align;
+        if addr + size > Memory::LIMIT {
+            return None;
+        }
+        self.bytes.resize((addr + size - Memory::BASE) as usize, 0);
+        Some(addr)
+    }
+
+    /// Get the address following all allocated memory.
+    pub fn top(&self) -> u64 {
+        Memory::BASE + self.bytes.len() as u64
+    }
+
+    /// Release all memory allocated at or above `top`.
+    pub fn release(&mut self, top: u64) {
+        debug_assert!(top >= Memory::BASE);
+        self.bytes.truncate((top - Memory::BASE) as usize);
+    }
+
+    /// Get the `size` bytes at `addr`, or `None` if they are not all allocated.
+    pub fn read(&self, addr: u64, size: u64) -> Option<&[u8]> {
+        let range = self.range(addr, size)?;
+        Some(&self.bytes[range.0..range.1])
+    }
+
+    /// Overwrite the bytes at `addr`. Returns `None` if they are not all allocated.
+    pub fn write(&mut self, addr: u64, data: &[u8]) -> Option<()> {
+        let range = self.range(addr, data.len() as u64)?;
+        self.bytes[range.0..range.1].copy_from_slice(data);
+        Some(())
+    }
+
+    /// Load a value of type `ty` from `addr`.
+    pub fn load(&self, ty: Type, addr: u64) -> Option<DataValue> {
+        self.read(addr, u64::from(ty.bytes())).map(
+            |bytes| decode(ty, bytes),
+        )
+    }
+
+    /// Store `value` of type `ty` to `addr`.
+    pub fn store(&mut self, ty: Type, addr: u64, value: &DataValue) -> Option<()> {
+        let mut bytes = Vec::new();
+        encode(ty, value, &mut bytes);
+        self.write(addr, &bytes)
+    }
+
+    fn range(&self, addr: u64, size: u64) -> Option<(usize, usize)> {
+        let start = addr.checked_sub(Memory::BASE)?;
+        let end = start.checked_add(size)?;
+        if end > self.bytes.len() as u64 {
+            return None;
+        }
+        Some((start as usize, end as usize))
+    }
+}
+
+/// Append the in-memory representation of `value` with type `ty` to `bytes`.
+pub fn encode(ty: Type, value: &DataValue, bytes: &mut Vec<u8>) {
+    match *value {
+        DataValue::V(ref lanes) => {
+            for lane in lanes {
+                encode(ty.lane_type(), lane, bytes);
+            }
+        }
+        _ => {
+            let bits = if ty.is_bool() && value.is_true() {
+                !0
+            } else {
+                value.to_bits()
+            };
+            for i in 0..ty.bytes() {
+                bytes.push((bits >> (8 * i)) as u8);
+            }
+        }
+    }
+}
+
+/// Decode a value of type `ty` from its in-memory representation.
+pub fn decode(ty: Type, bytes: &[u8]) -> DataValue {
+    if ty.is_vector() {
+        let lane = ty.lane_type();
+        let size = lane.bytes() as usize;
+        return DataValue::V(
+            bytes
+                .chunks(size)
+                .map(|chunk| decode(lane, chunk))
+                .collect(),
+        );
+    }
+    let bits = bytes.iter().rev().fold(
+        0,
+        |bits, &byte| (bits << 8) | u64::from(byte),
+    );
+    DataValue::from_bits(ty, bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Memory;
+    use cretonne::ir::types::{I16, I32, I16X4};
+    use value::DataValue;
+
+    #[test]
+    fn load_store() {
+        let mut mem = Memory::new();
+        assert_eq!(mem.load(I32, 0), None);
+
+        let addr = mem.allocate(16, 8).unwrap();
+        assert_eq!(addr % 8, 0);
+        mem.store(I32, addr, &DataValue::I32(0x0102_0304)).unwrap();
+        assert_eq!(mem.read(addr, 4), Some(&[4, 3, 2, 1][..]));
+        assert_eq!(mem.load(I16, addr + 2), Some(DataValue::I16(0x0102)));
+        assert_eq!(
+            mem.load(I16X4, addr),
+            Some(DataValue::V(vec![
+                DataValue::I16(0x0304),
+                DataValue::I16(0x0102),
+                DataValue::I16(0),
+                DataValue::I16(0),
+            ]))
+        );
+        assert_eq!(mem.load(I32, addr + 14), None);
+
+        let top = mem.top();
+        mem.allocate(4, 4).unwrap();
+        mem.release(top);
+        assert_eq!(mem.load(I32, top), None);
+    }
+}
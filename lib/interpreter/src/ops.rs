@@ -0,0 +1,527 @@
+//! Computations performed by individual instructions.
+//!
+//! The functions in this module are pure: they compute the result of an instruction from its
+//! operand values, or the trap code if the instruction traps. Vector operands are handled one lane
+//! at a time.
+
+use cretonne::ir::{Opcode, TrapCode, Type};
+use cretonne::ir::condcodes::{IntCC, FloatCC};
+use cretonne::ir::immediates::{Ieee32, Ieee64};
+use cretonne::ir::types;
+use std::cmp::Ordering;
+use value::DataValue;
+
+/// The result of an instruction that may trap.
+pub type OpResult = Result<DataValue, TrapCode>;
+
+/// Apply `f` to each lane of `x` with type `ty`.
+pub fn lanes1<F>(ty: Type, x: &DataValue, f: F) -> OpResult
+where
+    F: Fn(Type, &DataValue) -> OpResult,
+{
+    match *x {
+        DataValue::V(ref xs) => {
+            let lane = ty.lane_type();
+            xs.iter().map(|x| f(lane, x)).collect::<Result<_, _>>().map(
+                DataValue::V,
+            )
+        }
+        _ => f(ty, x),
+    }
+}
+
+/// Apply `f` to each pair of lanes of `x` and `y` with type `ty`.
+pub fn lanes2<F>(ty: Type, x: &DataValue, y: &DataValue, f: F) -> OpResult
+where
+    F: Fn(Type, &DataValue, &DataValue) -> OpResult,
+{
+    match (x, y) {
+        (&DataValue::V(ref xs), &DataValue::V(ref ys)) => {
+            let lane = ty.lane_type();
+            xs.iter()
+                .zip(ys)
+                .map(|(x, y)| f(lane, x, y))
+                .collect::<Result<_, _>>()
+                .map(DataValue::V)
+        }
+        _ => f(ty, x, y),
+    }
+}
+
+/// Get a mask of the low `bits` bits.
+fn mask(bits: u32) -> u64 {
+    if bits >= 64 { !0 } else { (1 << bits) - 1 }
+}
+
+/// Sign-extend the low `bits` bits of `x`.
+fn sext(x: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((x << shift) as i64) >> shift
+}
+
+/// Create a scalar from the result of a bitwise operation. Booleans only use the low bit.
+fn from_bitwise(ty: Type, bits: u64) -> DataValue {
+    if ty.is_bool() {
+        DataValue::B(bits & 1 != 0)
+    } else {
+        DataValue::from_bits(ty, bits)
+    }
+}
+
+/// Compute a binary integer or bitwise operation on scalars of type `ty`.
+///
+/// The `_imm` forms of the instructions are computed by passing the immediate as `y`.
+pub fn binary(opcode: Opcode, ty: Type, x: &DataValue, y: &DataValue) -> OpResult {
+    use cretonne::ir::Opcode::*;
+    let bits = u32::from(ty.lane_bits());
+    let (a, b) = (x.to_bits(), y.to_bits());
+    let (sa, sb) = (sext(a, bits), sext(b, bits));
+    let r = match opcode {
+        Iadd | IaddImm => a.wrapping_add(b),
+        Isub => a.wrapping_sub(b),
+        IrsubImm => b.wrapping_sub(a),
+        Imul | ImulImm => a.wrapping_mul(b),
+        Udiv | UdivImm | Urem | UremImm if b == 0 => {
+            return Err(TrapCode::IntegerDivisionByZero)
+        }
+        Udiv | UdivImm => a / b,
+        Urem | UremImm => a % b,
+        Sdiv | SdivImm | Srem | SremImm if b == 0 => {
+            return Err(TrapCode::IntegerDivisionByZero)
+        }
+        Sdiv | SdivImm if sb == -1 && sa == sext(1 << (bits - 1), bits) => {
+            return Err(TrapCode::IntegerOverflow)
+        }
+        Sdiv | SdivImm => sa.wrapping_div(sb) as u64,
+        Srem | SremImm => sa.wrapping_rem(sb) as u64,
+        Band | BandImm => a & b,
+        Bor | BorImm => a | b,
+        Bxor | BxorImm => a ^ b,
+        BandNot => a & !b,
+        BorNot => a | !b,
+        BxorNot => a ^ !b,
+        Rotl | RotlImm | Rotr | RotrImm | Ishl | IshlImm | Ushr | UshrImm | Sshr | SshrImm => {
+            return Ok(shift(opcode, ty, a, b));
+        }
+        _ => panic!("{} is not a binary operation", opcode),
+    };
+    Ok(from_bitwise(ty, r & mask(bits)))
+}
+
+/// Compute a shift or rotate of `x` by `y` bits. The shift amount is taken modulo the width.
+fn shift(opcode: Opcode, ty: Type, x: u64, y: u64) -> DataValue {
+    use cretonne::ir::Opcode::*;
+    let bits = u32::from(ty.lane_bits());
+    let s = (y % u64::from(bits)) as u32;
+    let r = match opcode {
+        Ishl | IshlImm => x << s,
+        Ushr | UshrImm => x >> s,
+        Sshr | SshrImm => (sext(x, bits) >> s) as u64,
+        Rotl | RotlImm if s == 0 => x,
+        Rotl | RotlImm => (x << s) | (x >> (bits - s)),
+        Rotr | RotrImm if s == 0 => x,
+        Rotr | RotrImm => (x >> s) | (x << (bits - s)),
+        _ => panic!("{} is not a shift", opcode),
+    };
+    DataValue::from_bits(ty, r & mask(bits))
+}
+
+/// Add `x`, `y` and an incoming carry, returning the sum and the outgoing carry.
+pub fn add_carry(ty: Type, x: &DataValue, y: &DataValue, c_in: bool) -> (DataValue, bool) {
+    let bits = u32::from(ty.lane_bits());
+    let (sum, c1) = x.to_bits().overflowing_add(y.to_bits());
+    let (sum, c2) = sum.overflowing_add(c_in as u64);
+    // Narrower sums carry into the bits above the type instead of overflowing.
+    let c_out = c1 || c2 || (bits < 64 && sum >> bits != 0);
+    (DataValue::from_bits(ty, sum & mask(bits)), c_out)
+}
+
+/// Subtract `y` and an incoming borrow from `x`, returning the difference and the outgoing
+/// borrow.
+pub fn sub_borrow(ty: Type, x: &DataValue, y: &DataValue, b_in: bool) -> (DataValue, bool) {
+    let bits = u32::from(ty.lane_bits());
+    let (a, b) = (x.to_bits(), y.to_bits());
+    let diff = a.wrapping_sub(b).wrapping_sub(b_in as u64);
+    let b_out = a < b || (a == b && b_in);
+    (DataValue::from_bits(ty, diff & mask(bits)), b_out)
+}
+
+/// Compute a unary integer, bitwise or floating point operation on a scalar of type `ty`.
+pub fn unary(opcode: Opcode, ty: Type, x: &DataValue) -> OpResult {
+    use cretonne::ir::Opcode::*;
+    let bits = u32::from(ty.lane_bits());
+    let a = x.to_bits();
+    Ok(match opcode {
+        Bnot => from_bitwise(ty, !a & mask(bits)),
+        Clz => DataValue::from_bits(ty, u64::from(a.leading_zeros() - (64 - bits))),
+        Ctz => DataValue::from_bits(ty, u64::from(a.trailing_zeros().min(bits))),
+        Popcnt => DataValue::from_bits(ty, u64::from(a.count_ones())),
+        Cls => {
+            // Count the bits following the sign bit that are equal to it.
+            let sa = sext(a, bits);
+            let same = if sa < 0 { !sa } else { sa };
+            DataValue::from_bits(ty, u64::from(same.leading_zeros() - (64 - bits) - 1))
+        }
+        Fneg => DataValue::from_bits(ty, a ^ (1 << (bits - 1))),
+        Fabs => DataValue::from_bits(ty, a & !(1 << (bits - 1))),
+        Sqrt | Ceil | Floor | Trunc | Nearest => {
+            let f = |x: f64| match opcode {
+                Sqrt => x.sqrt(),
+                Ceil => x.ceil(),
+                Floor => x.floor(),
+                Trunc => x.trunc(),
+                _ => round_ties_even(x),
+            };
+            match *x {
+                // Rounding is exact in double precision, and so is the square root of a single
+                // precision float after rounding the result back.
+                DataValue::F32(_) => f32_value(f(f64::from(x.to_f32())) as f32),
+                _ => f64_value(f(x.to_f64())),
+            }
+        }
+        _ => panic!("{} is not a unary operation", opcode),
+    })
+}
+
+/// Round `x` to the nearest integer, with ties going to the even integer.
+fn round_ties_even(x: f64) -> f64 {
+    let r = x.round();
+    if (r - x).abs() == 0.5 {
+        // `round` takes ties away from zero. Halving first makes the tie land on an even integer.
+        2.0 * (x / 2.0).round()
+    } else {
+        r
+    }
+}
+
+fn f32_value(x: f32) -> DataValue {
+    DataValue::F32(Ieee32::with_bits(x.to_bits()))
+}
+
+fn f64_value(x: f64) -> DataValue {
+    DataValue::F64(Ieee64::with_bits(x.to_bits()))
+}
+
+/// Compute a binary floating point operation on scalars.
+pub fn float_binary(opcode: Opcode, x: &DataValue, y: &DataValue) -> OpResult {
+    use cretonne::ir::Opcode::*;
+    if opcode == Fcopysign {
+        let bits = match *x {
+            DataValue::F32(_) => 32,
+            _ => 64,
+        };
+        let sign = 1 << (bits - 1);
+        let ty = if bits == 32 { types::F32 } else { types::F64 };
+        return Ok(DataValue::from_bits(
+            ty,
+            (x.to_bits() & !sign) | (y.to_bits() & sign),
+        ));
+    }
+    let f = |a: f64, b: f64| match opcode {
+        Fadd => a + b,
+        Fsub => a - b,
+        Fmul => a * b,
+        Fdiv => a / b,
+        Fmin | Fmax if a.is_nan() || b.is_nan() => ::std::f64::NAN,
+        // Distinguish -0.0 and +0.0, which compare equal.
+        Fmin if a == b => if a.is_sign_negative() { a } else { b },
+        Fmax if a == b => if a.is_sign_positive() { a } else { b },
+        Fmin => a.min(b),
+        Fmax => a.max(b),
+        _ => panic!("{} is not a binary float operation", opcode),
+    };
+    Ok(match (x, y) {
+        // Single precision arithmetic is computed exactly in double precision before rounding.
+        (&DataValue::F32(_), &DataValue::F32(_)) => {
+            f32_value(f(f64::from(x.to_f32()), f64::from(y.to_f32())) as f32)
+        }
+        _ => f64_value(f(x.to_f64(), y.to_f64())),
+    })
+}
+
+/// Compute a fused multiply-add.
+pub fn fma(x: &DataValue, y: &DataValue, z: &DataValue) -> DataValue {
+    match *x {
+        DataValue::F32(_) => f32_value(x.to_f32().mul_add(y.to_f32(), z.to_f32())),
+        _ => f64_value(x.to_f64().mul_add(y.to_f64(), z.to_f64())),
+    }
+}
+
+/// Compare two integers, returning the signed and unsigned orders as integer CPU flags.
+pub fn int_flags(ty: Type, x: &DataValue, y: &DataValue) -> DataValue {
+    let bits = u32::from(ty.lane_bits());
+    let (a, b) = (x.to_bits(), y.to_bits());
+    DataValue::IFlags {
+        signed: sext(a, bits).cmp(&sext(b, bits)),
+        unsigned: a.cmp(&b),
+    }
+}
+
+/// Test integer CPU flags for a condition.
+pub fn int_cond(cond: IntCC, flags: &DataValue) -> bool {
+    let (signed, unsigned) = match *flags {
+        DataValue::IFlags { signed, unsigned } => (signed, unsigned),
+        _ => panic!("{} is not integer flags", flags),
+    };
+    match cond {
+        IntCC::Equal => signed == Ordering::Equal,
+        IntCC::NotEqual => signed != Ordering::Equal,
+        IntCC::SignedLessThan => signed == Ordering::Less,
+        IntCC::SignedGreaterThanOrEqual => signed != Ordering::Less,
+        IntCC::SignedGreaterThan => signed == Ordering::Greater,
+        IntCC::SignedLessThanOrEqual => signed != Ordering::Greater,
+        IntCC::UnsignedLessThan => unsigned == Ordering::Less,
+        IntCC::UnsignedGreaterThanOrEqual => unsigned != Ordering::Less,
+        IntCC::UnsignedGreaterThan => unsigned == Ordering::Greater,
+        IntCC::UnsignedLessThanOrEqual => unsigned != Ordering::Greater,
+    }
+}
+
+/// Compare two scalar integers.
+pub fn icmp(cond: IntCC, ty: Type, x: &DataValue, y: &DataValue) -> bool {
+    int_cond(cond, &int_flags(ty, x, y))
+}
+
+/// Compare two floats, returning their order as floating point CPU flags.
+pub fn float_flags(x: &DataValue, y: &DataValue) -> DataValue {
+    DataValue::FFlags(match (x, y) {
+        (&DataValue::F32(_), &DataValue::F32(_)) => x.to_f32().partial_cmp(&y.to_f32()),
+        _ => x.to_f64().partial_cmp(&y.to_f64()),
+    })
+}
+
+/// Test floating point CPU flags for a condition.
+pub fn float_cond(cond: FloatCC, flags: &DataValue) -> bool {
+    let order = match *flags {
+        DataValue::FFlags(order) => order,
+        _ => panic!("{} is not float flags", flags),
+    };
+    let (un, lt, eq, gt) = (
+        order.is_none(),
+        order == Some(Ordering::Less),
+        order == Some(Ordering::Equal),
+        order == Some(Ordering::Greater),
+    );
+    match cond {
+        FloatCC::Ordered => !un,
+        FloatCC::Unordered => un,
+        FloatCC::Equal => eq,
+        FloatCC::NotEqual => !eq,
+        FloatCC::OrderedNotEqual => lt || gt,
+        FloatCC::UnorderedOrEqual => un || eq,
+        FloatCC::LessThan => lt,
+        FloatCC::LessThanOrEqual => lt || eq,
+        FloatCC::GreaterThan => gt,
+        FloatCC::GreaterThanOrEqual => gt || eq,
+        FloatCC::UnorderedOrLessThan => un || lt,
+        FloatCC::UnorderedOrLessThanOrEqual => un || lt || eq,
+        FloatCC::UnorderedOrGreaterThan => un || gt,
+        FloatCC::UnorderedOrGreaterThanOrEqual => un || gt || eq,
+    }
+}
+
+/// Compare two scalar floats.
+pub fn fcmp(cond: FloatCC, x: &DataValue, y: &DataValue) -> bool {
+    float_cond(cond, &float_flags(x, y))
+}
+
+/// Convert a scalar of type `from` to type `to`.
+///
+/// This covers the integer, boolean and floating point conversions that don't depend on the
+/// memory representation of the values.
+pub fn convert(opcode: Opcode, from: Type, to: Type, x: &DataValue) -> OpResult {
+    use cretonne::ir::Opcode::*;
+    let from_bits = u32::from(from.lane_bits());
+    Ok(match opcode {
+        Ireduce | Uextend => DataValue::from_bits(to, x.to_bits()),
+        Sextend => DataValue::from_bits(to, sext(x.to_bits(), from_bits) as u64),
+        Breduce | Bextend => DataValue::B(x.is_true()),
+        Bint => DataValue::from_bits(to, x.is_true() as u64),
+        Bmask => DataValue::from_bits(to, if x.is_true() { !0 } else { 0 }),
+        Fpromote | Fdemote => {
+            match (x, to) {
+                (&DataValue::F32(_), types::F64) => f64_value(f64::from(x.to_f32())),
+                (&DataValue::F64(_), types::F32) => f32_value(x.to_f64() as f32),
+                _ => x.clone(),
+            }
+        }
+        FcvtToSint | FcvtToUint => {
+            let f = match *x {
+                DataValue::F32(_) => f64::from(x.to_f32()),
+                _ => x.to_f64(),
+            };
+            if f.is_nan() {
+                return Err(TrapCode::BadConversionToInteger);
+            }
+            let f = f.trunc();
+            let bits = i32::from(to.lane_bits());
+            let (min, max) = if opcode == FcvtToSint {
+                (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1))
+            } else {
+                (0.0, 2f64.powi(bits))
+            };
+            // The result must be representable, so `max` itself is out of range.
+            if f < min || f >= max {
+                return Err(TrapCode::IntegerOverflow);
+            }
+            let r = if opcode == FcvtToSint {
+                f as i64 as u64
+            } else {
+                f as u64
+            };
+            DataValue::from_bits(to, r)
+        }
+        FcvtFromSint | FcvtFromUint => {
+            let a = x.to_bits();
+            match to {
+                types::F32 => {
+                    f32_value(if opcode == FcvtFromSint {
+                        sext(a, from_bits) as f32
+                    } else {
+                        a as f32
+                    })
+                }
+                _ => {
+                    f64_value(if opcode == FcvtFromSint {
+                        sext(a, from_bits) as f64
+                    } else {
+                        a as f64
+                    })
+                }
+            }
+        }
+        _ => panic!("{} is not a conversion", opcode),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cretonne::ir::types::{I8, I32, I64, F32, F64};
+
+    fn f32v(x: f32) -> DataValue {
+        f32_value(x)
+    }
+
+    #[test]
+    fn integers() {
+        let i8v = DataValue::I8;
+        assert_eq!(binary(Opcode::Iadd, I8, &i8v(127), &i8v(1)), Ok(i8v(-128)));
+        assert_eq!(binary(Opcode::Udiv, I8, &i8v(-1), &i8v(2)), Ok(i8v(127)));
+        assert_eq!(binary(Opcode::Sdiv, I8, &i8v(-7), &i8v(2)), Ok(i8v(-3)));
+        assert_eq!(binary(Opcode::Srem, I8, &i8v(-7), &i8v(2)), Ok(i8v(-1)));
+        assert_eq!(
+            binary(Opcode::Sdiv, I8, &i8v(-128), &i8v(-1)),
+            Err(TrapCode::IntegerOverflow)
+        );
+        assert_eq!(binary(Opcode::Srem, I8, &i8v(-128), &i8v(-1)), Ok(i8v(0)));
+        assert_eq!(
+            binary(Opcode::Urem, I8, &i8v(1), &i8v(0)),
+            Err(TrapCode::IntegerDivisionByZero)
+        );
+        assert_eq!(binary(Opcode::Sshr, I8, &i8v(-128), &i8v(9)), Ok(i8v(-64)));
+        assert_eq!(binary(Opcode::Rotl, I8, &i8v(-127), &i8v(1)), Ok(i8v(3)));
+        assert_eq!(binary(Opcode::Rotr, I8, &i8v(3), &i8v(0)), Ok(i8v(3)));
+
+        assert_eq!(unary(Opcode::Clz, I32, &DataValue::I32(1)), Ok(DataValue::I32(31)));
+        assert_eq!(unary(Opcode::Ctz, I32, &DataValue::I32(0)), Ok(DataValue::I32(32)));
+        assert_eq!(unary(Opcode::Cls, I8, &i8v(0)), Ok(i8v(7)));
+        assert_eq!(unary(Opcode::Cls, I8, &i8v(-2)), Ok(i8v(6)));
+        assert_eq!(unary(Opcode::Popcnt, I8, &i8v(-1)), Ok(i8v(8)));
+
+        assert_eq!(
+            add_carry(I8, &i8v(-1), &i8v(0), true),
+            (i8v(0), true)
+        );
+        assert_eq!(
+            sub_borrow(I8, &i8v(0), &i8v(0), true),
+            (i8v(-1), true)
+        );
+        let i64v = DataValue::I64;
+        assert_eq!(
+            add_carry(I64, &i64v(-1), &i64v(0), true),
+            (i64v(0), true)
+        );
+        assert_eq!(
+            add_carry(I64, &i64v(-1), &i64v(-1), true),
+            (i64v(-1), true)
+        );
+        assert_eq!(
+            add_carry(I64, &i64v(1), &i64v(1), false),
+            (i64v(2), false)
+        );
+        assert_eq!(
+            sub_borrow(I64, &i64v(0), &i64v(-1), false),
+            (i64v(1), true)
+        );
+        assert_eq!(
+            sub_borrow(I64, &i64v(-1), &i64v(-1), true),
+            (i64v(-1), true)
+        );
+        assert_eq!(
+            sub_borrow(I64, &i64v(5), &i64v(2), true),
+            (i64v(2), false)
+        );
+
+        assert!(icmp(IntCC::SignedLessThan, I8, &i8v(-1), &i8v(0)));
+        assert!(icmp(IntCC::UnsignedGreaterThan, I8, &i8v(-1), &i8v(0)));
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(
+            float_binary(Opcode::Fmin, &f32v(-0.0), &f32v(0.0)),
+            Ok(f32v(-0.0))
+        );
+        assert!(
+            float_binary(Opcode::Fmax, &f32v(1.0), &f32v(::std::f32::NAN))
+                .unwrap()
+                .to_f32()
+                .is_nan()
+        );
+        assert_eq!(unary(Opcode::Nearest, F32, &f32v(2.5)), Ok(f32v(2.0)));
+        assert_eq!(unary(Opcode::Nearest, F32, &f32v(3.5)), Ok(f32v(4.0)));
+        assert_eq!(unary(Opcode::Nearest, F32, &f32v(-2.5)), Ok(f32v(-2.0)));
+        assert_eq!(unary(Opcode::Nearest, F32, &f32v(-0.5)), Ok(f32v(-0.0)));
+        assert_eq!(unary(Opcode::Nearest, F32, &f32v(2.6)), Ok(f32v(3.0)));
+        assert_eq!(
+            unary(Opcode::Nearest, F64, &f64_value(0.49999999999999994)),
+            Ok(f64_value(0.0))
+        );
+        assert_eq!(
+            unary(Opcode::Nearest, F64, &f64_value(4503599627370497.0)),
+            Ok(f64_value(4503599627370497.0))
+        );
+        assert_eq!(unary(Opcode::Fneg, F32, &f32v(1.0)), Ok(f32v(-1.0)));
+        assert!(fcmp(FloatCC::Unordered, &f32v(::std::f32::NAN), &f32v(0.0)));
+        assert!(!fcmp(FloatCC::Equal, &f32v(::std::f32::NAN), &f32v(0.0)));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            convert(Opcode::Sextend, I8, I32, &DataValue::I8(-1)),
+            Ok(DataValue::I32(-1))
+        );
+        assert_eq!(
+            convert(Opcode::Uextend, I8, I32, &DataValue::I8(-1)),
+            Ok(DataValue::I32(255))
+        );
+        assert_eq!(
+            convert(Opcode::FcvtToSint, F64, I32, &f64_value(-2147483648.9)),
+            Ok(DataValue::I32(-2147483648))
+        );
+        assert_eq!(
+            convert(Opcode::FcvtToSint, F64, I32, &f64_value(2147483648.0)),
+            Err(TrapCode::IntegerOverflow)
+        );
+        assert_eq!(
+            convert(Opcode::FcvtToUint, F32, I64, &f32v(::std::f32::NAN)),
+            Err(TrapCode::BadConversionToInteger)
+        );
+        assert_eq!(
+            convert(Opcode::FcvtFromUint, I32, F64, &DataValue::I32(-1)),
+            Ok(f64_value(4294967295.0))
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Run-time values manipulated by the interpreter.
+
+use cretonne::ir::Type;
+use cretonne::ir::immediates::{Imm64, Ieee32, Ieee64};
+use cretonne::ir::types;
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+
+/// A value computed by the interpreter.
+///
+/// Integers are stored with the Rust type matching their width. Cretonne integer types are
+/// sign-agnostic, so the signedness of the Rust type doesn't mean anything. Booleans of any width
+/// are represented as `B`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DataValue {
+    /// A boolean of any width.
+    B(bool),
+    /// An 8-bit integer.
+    I8(i8),
+    /// A 16-bit integer.
+    I16(i16),
+    /// A 32-bit integer.
+    I32(i32),
+    /// A 64-bit integer.
+    I64(i64),
+    /// A single precision float.
+    F32(Ieee32),
+    /// A double precision float.
+    F64(Ieee64),
+    /// A SIMD vector with one value per lane.
+    V(Vec<DataValue>),
+    /// Integer CPU flags, holding the signed and unsigned order of the compared values.
+    IFlags {
+        /// Signed comparison of the two values.
+        signed: Ordering,
+        /// Unsigned comparison of the two values.
+        unsigned: Ordering,
+    },
+    /// Floating point CPU flags, holding the order of the compared values or `None` if they were
+    /// unordered.
+    FFlags(Option<Ordering>),
+}
+
+impl DataValue {
+    /// Create an integer, boolean, or float scalar of type `ty` from the low bits of `bits`.
+    ///
+    /// Booleans are true when `bits` is non-zero.
+    pub fn from_bits(ty: Type, bits: u64) -> DataValue {
+        match ty {
+            types::I8 => DataValue::I8(bits as i8),
+            types::I16 => DataValue::I16(bits as i16),
+            types::I32 => DataValue::I32(bits as i32),
+            types::I64 => DataValue::I64(bits as i64),
+            types::F32 => DataValue::F32(Ieee32::with_bits(bits as u32)),
+            types::F64 => DataValue::F64(Ieee64::with_bits(bits)),
+            _ if ty.is_bool() => DataValue::B(bits != 0),
+            _ => panic!("no scalar data value of type {}", ty),
+        }
+    }
+
+    /// Get the bits of a scalar value, zero-extended to 64 bits.
+    ///
+    /// Booleans are 1 or 0.
+    pub fn to_bits(&self) -> u64 {
+        match *self {
+            DataValue::B(b) => b as u64,
+            DataValue::I8(x) => u64::from(x as u8),
+            DataValue::I16(x) => u64::from(x as u16),
+            DataValue::I32(x) => u64::from(x as u32),
+            DataValue::I64(x) => x as u64,
+            DataValue::F32(x) => u64::from(x.bits()),
+            DataValue::F64(x) => x.bits(),
+            _ => panic!("{} is not a scalar value", self),
+        }
+    }
+
+    /// Get the value of a scalar integer, sign-extended to 64 bits.
+    pub fn to_signed(&self) -> i64 {
+        match *self {
+            DataValue::I8(x) => i64::from(x),
+            DataValue::I16(x) => i64::from(x),
+            DataValue::I32(x) => i64::from(x),
+            DataValue::I64(x) => x,
+            _ => panic!("{} is not an integer", self),
+        }
+    }
+
+    /// Is this value true?
+    ///
+    /// Booleans are tested directly, and integers are true when they are non-zero.
+    pub fn is_true(&self) -> bool {
+        match *self {
+            DataValue::B(b) => b,
+            _ => self.to_bits() != 0,
+        }
+    }
+
+    /// Get the value of a single precision float.
+    pub fn to_f32(&self) -> f32 {
+        match *self {
+            DataValue::F32(x) => f32::from_bits(x.bits()),
+            _ => panic!("{} is not an f32", self),
+        }
+    }
+
+    /// Get the value of a double precision float.
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            DataValue::F64(x) => f64::from_bits(x.bits()),
+            _ => panic!("{} is not an f64", self),
+        }
+    }
+
+    /// Does this value have type `ty`?
+    pub fn has_type(&self, ty: Type) -> bool {
+        match *self {
+            DataValue::B(_) => ty.is_bool(),
+            DataValue::I8(_) => ty == types::I8,
+            DataValue::I16(_) => ty == types::I16,
+            DataValue::I32(_) => ty == types::I32,
+            DataValue::I64(_) => ty == types::I64,
+            DataValue::F32(_) => ty == types::F32,
+            DataValue::F64(_) => ty == types::F64,
+            DataValue::V(ref lanes) => {
+                ty.is_vector() && lanes.len() == ty.lane_count() as usize &&
+                    lanes.iter().all(|lane| lane.has_type(ty.lane_type()))
+            }
+            DataValue::IFlags { .. } => ty == types::IFLAGS,
+            DataValue::FFlags(_) => ty == types::FFLAGS,
+        }
+    }
+
+    /// Parse the text representation of a value of type `ty`.
+    ///
+    /// Integers and floats use the syntax of immediate operands in the textual IL, and booleans
+    /// are `true` or `false`. Vectors are written as a bracketed list of lanes: `[1 2 3 4]`.
+    pub fn parse(text: &str, ty: Type) -> Result<DataValue, String> {
+        let text = text.trim();
+        if ty.is_vector() {
+            if !text.starts_with('[') || !text.ends_with(']') {
+                return Err(format!("expected a bracketed list of {} lanes", ty));
+            }
+            let lanes = text[1..text.len() - 1]
+                .split_whitespace()
+                .map(|lane| DataValue::parse(lane, ty.lane_type()))
+                .collect::<Result<Vec<_>, _>>()?;
+            if lanes.len() != ty.lane_count() as usize {
+                return Err(format!("expected {} lanes for {}", ty.lane_count(), ty));
+            }
+            return Ok(DataValue::V(lanes));
+        }
+        match ty {
+            types::F32 => {
+                text.parse::<Ieee32>().map(DataValue::F32).map_err(|e| {
+                    format!("bad f32 '{}': {}", text, e)
+                })
+            }
+            types::F64 => {
+                text.parse::<Ieee64>().map(DataValue::F64).map_err(|e| {
+                    format!("bad f64 '{}': {}", text, e)
+                })
+            }
+            _ if ty.is_int() => {
+                let imm = text.parse::<Imm64>().map_err(
+                    |e| format!("bad {} '{}': {}", ty, text, e),
+                )?;
+                let x: i64 = imm.into();
+                Ok(DataValue::from_bits(ty, x as u64))
+            }
+            _ if ty.is_bool() => {
+                match text {
+                    "true" => Ok(DataValue::B(true)),
+                    "false" => Ok(DataValue::B(false)),
+                    _ => Err(format!("bad {} '{}'", ty, text)),
+                }
+            }
+            _ => Err(format!("can't parse values of type {}", ty)),
+        }
+    }
+
+    /// Compare two values, considering all NaNs of the same type to be equal.
+    ///
+    /// This is the equality used to check the results of a test run. Arithmetic on NaNs doesn't
+    /// produce NaNs with predictable bits.
+    pub fn same_as(&self, other: &DataValue) -> bool {
+        match (self, other) {
+            (&DataValue::F32(_), &DataValue::F32(_)) if self.to_f32().is_nan() => {
+                other.to_f32().is_nan()
+            }
+            (&DataValue::F64(_), &DataValue::F64(_)) if self.to_f64().is_nan() => {
+                other.to_f64().is_nan()
+            }
+            (&DataValue::V(ref a), &DataValue::V(ref b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.same_as(y))
+            }
+            _ => self == other,
+        }
+    }
+}
+
+impl Display for DataValue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            DataValue::B(b) => write!(f, "{}", b),
+            DataValue::I8(x) => write!(f, "{}", x),
+            DataValue::I16(x) => write!(f, "{}", x),
+            DataValue::I32(x) => write!(f, "{}", x),
+            DataValue::I64(x) => write!(f, "{}", x),
+            DataValue::F32(x) => write!(f, "{}", x),
+            DataValue::F64(x) => write!(f, "{}", x),
+            DataValue::V(ref lanes) => {
+                write!(f, "[")?;
+                for (i, lane) in lanes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", lane)?;
+                }
+                write!(f, "]")
+            }
+            DataValue::IFlags { signed, unsigned } => {
+                write!(f, "iflags(signed {:?}, unsigned {:?})", signed, unsigned)
+            }
+            DataValue::FFlags(order) => write!(f, "fflags({:?})", order),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DataValue;
+    use cretonne::ir::types::{I8, I32, F32, B1, I32X4};
+
+    #[test]
+    fn parse() {
+        assert_eq!(DataValue::parse("-1", I8), Ok(DataValue::I8(-1)));
+        assert_eq!(DataValue::parse("0xff", I8), Ok(DataValue::I8(-1)));
+        assert_eq!(DataValue::parse("true", B1), Ok(DataValue::B(true)));
+        assert_eq!(
+            DataValue::parse("0x1.8p1", F32).unwrap().to_f32(),
+            3.0
+        );
+        assert_eq!(
+            DataValue::parse("[1 2 3 4]", I32X4),
+            Ok(DataValue::V(
+                vec![
+                    DataValue::I32(1),
+                    DataValue::I32(2),
+                    DataValue::I32(3),
+                    DataValue::I32(4),
+                ],
+            ))
+        );
+        assert!(DataValue::parse("[1 2]", I32X4).is_err());
+        assert!(DataValue::parse("x", I32).is_err());
+    }
+
+    #[test]
+    fn bits() {
+        assert_eq!(DataValue::I8(-1).to_bits(), 0xff);
+        assert_eq!(DataValue::I8(-1).to_signed(), -1);
+        assert_eq!(DataValue::from_bits(I32, 0x1_0000_0005), DataValue::I32(5));
+        assert!(DataValue::parse("NaN", F32).unwrap().same_as(
+            &DataValue::parse("-NaN:0x1", F32).unwrap(),
+        ));
+    }
+}
@@ -0,0 +1,79 @@
+//! Defines the `Backend` trait.
+
+use cretonne::Context;
+use cretonne::isa::TargetIsa;
+use data_context::DataContext;
+use module::{Linkage, ModuleNamespace, ModuleResult};
+
+/// A `Backend` implements the functionality needed to support a `Module`.
+///
+/// Two backends are provided with this crate:
+///
+///  - `SimpleJitBackend`, which emits code and data into executable memory in the current
+///    process so it can be called directly.
+///  - `ObjectBackend`, which emits code and data into an ELF relocatable object file.
+pub trait Backend
+where
+    Self: Sized,
+{
+    /// The results of compiling a function.
+    type CompiledFunction;
+
+    /// The results of compiling a data object.
+    type CompiledData;
+
+    /// The completed output artifact for a function, if this is meaningful for the `Backend`.
+    type FinalizedFunction;
+
+    /// The completed output artifact for a data object, if this is meaningful for the `Backend`.
+    type FinalizedData;
+
+    /// The completed output artifact for the whole module, returned by `Module::finish`.
+    type Product;
+
+    /// Get the target ISA used by this backend.
+    fn isa(&self) -> &TargetIsa;
+
+    /// Declare a function, or update the linkage of a function declared before.
+    fn declare_function(&mut self, name: &str, linkage: Linkage);
+
+    /// Declare a data object, or update the linkage of a data object declared before.
+    fn declare_data(&mut self, name: &str, linkage: Linkage, writable: bool);
+
+    /// Define a function, producing the function body from the given `Context`.
+    ///
+    /// The function in `ctx` has been compiled, and the machine code is `code_size` bytes.
+    fn define_function(
+        &mut self,
+        name: &str,
+        ctx: &Context,
+        namespace: &ModuleNamespace<Self>,
+        code_size: u32,
+    ) -> ModuleResult<Self::CompiledFunction>;
+
+    /// Define a zero-initialized or initialized data object.
+    fn define_data(
+        &mut self,
+        name: &str,
+        writable: bool,
+        data_ctx: &DataContext,
+        namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<Self::CompiledData>;
+
+    /// Perform all outstanding relocations on the given function.
+    fn finalize_function(
+        &mut self,
+        func: &Self::CompiledFunction,
+        namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<Self::FinalizedFunction>;
+
+    /// Perform all outstanding relocations on the given data object.
+    fn finalize_data(
+        &mut self,
+        data: &Self::CompiledData,
+        namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<Self::FinalizedData>;
+
+    /// Consume this backend and return the product for the whole module.
+    fn finish(self) -> Self::Product;
+}
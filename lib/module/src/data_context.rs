@@ -0,0 +1,165 @@
+//! Defines `DataContext`.
+
+use cretonne::binemit::CodeOffset;
+use cretonne::entity::PrimaryMap;
+use cretonne::ir::{self, FunctionName};
+
+/// This specifies how data is to be initialized.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Init {
+    /// This indicates that no initialization has been specified yet.
+    Uninitialized,
+    /// Initialize the data with all zeros.
+    Zeros {
+        /// The size of the data.
+        size: usize,
+    },
+    /// Initialize the data with the specified contents.
+    Bytes {
+        /// The contents, which also implies the size of the data.
+        contents: Box<[u8]>,
+    },
+}
+
+impl Init {
+    /// Return the size of the data to be initialized.
+    pub fn size(&self) -> usize {
+        match *self {
+            Init::Uninitialized => panic!("data size not initialized yet"),
+            Init::Zeros { size } => size,
+            Init::Bytes { ref contents } => contents.len(),
+        }
+    }
+}
+
+/// A description of a data object.
+///
+/// Functions and data objects referenced by the data object are imported with a local reference,
+/// like the external functions and global variables in an `ir::Function`. The relocations record
+/// where their addresses are written into the data.
+pub struct DataDescription {
+    /// How the data should be initialized.
+    pub init: Init,
+    /// The required alignment of the data in bytes.
+    pub align: u32,
+    /// External function declarations.
+    pub function_decls: PrimaryMap<ir::FuncRef, FunctionName>,
+    /// External data object declarations.
+    pub data_decls: PrimaryMap<ir::GlobalVar, FunctionName>,
+    /// Function addresses to write at specified offsets.
+    pub function_relocs: Vec<(CodeOffset, ir::FuncRef)>,
+    /// Data addresses, plus an addend, to write at specified offsets.
+    pub data_relocs: Vec<(CodeOffset, ir::GlobalVar, i64)>,
+}
+
+/// This is to data objects what `cretonne::Context` is to functions.
+pub struct DataContext {
+    description: DataDescription,
+}
+
+impl DataContext {
+    /// Allocate a new context.
+    pub fn new() -> DataContext {
+        DataContext {
+            description: DataDescription {
+                init: Init::Uninitialized,
+                align: 1,
+                function_decls: PrimaryMap::new(),
+                data_decls: PrimaryMap::new(),
+                function_relocs: Vec::new(),
+                data_relocs: Vec::new(),
+            },
+        }
+    }
+
+    /// Clear all data structures in this context.
+    pub fn clear(&mut self) {
+        self.description.init = Init::Uninitialized;
+        self.description.align = 1;
+        self.description.function_decls.clear();
+        self.description.data_decls.clear();
+        self.description.function_relocs.clear();
+        self.description.data_relocs.clear();
+    }
+
+    /// Define a zero-initialized object with the given size.
+    pub fn define_zeroinit(&mut self, size: usize) {
+        debug_assert_eq!(self.description.init, Init::Uninitialized);
+        self.description.init = Init::Zeros { size };
+    }
+
+    /// Define an object initialized with the given contents.
+    pub fn define(&mut self, contents: Box<[u8]>) {
+        debug_assert_eq!(self.description.init, Init::Uninitialized);
+        self.description.init = Init::Bytes { contents };
+    }
+
+    /// Set the required alignment of the object, which must be a power of two.
+    pub fn set_align(&mut self, align: u32) {
+        debug_assert!(align.is_power_of_two());
+        self.description.align = align;
+    }
+
+    /// Declare an external function import.
+    ///
+    /// Users of the `Module` API generally should call `Module::declare_func_in_data` instead, as
+    /// it takes care of generating the appropriate name.
+    pub fn import_function(&mut self, name: FunctionName) -> ir::FuncRef {
+        self.description.function_decls.push(name)
+    }
+
+    /// Declare an external data object import.
+    ///
+    /// Users of the `Module` API generally should call `Module::declare_data_in_data` instead, as
+    /// it takes care of generating the appropriate name.
+    pub fn import_global_var(&mut self, name: FunctionName) -> ir::GlobalVar {
+        self.description.data_decls.push(name)
+    }
+
+    /// Write the address of `func` into the data at offset `offset`.
+    pub fn write_function_addr(&mut self, offset: CodeOffset, func: ir::FuncRef) {
+        self.description.function_relocs.push((offset, func))
+    }
+
+    /// Write the address of `data`, plus `addend`, into the data at offset `offset`.
+    pub fn write_data_addr(&mut self, offset: CodeOffset, data: ir::GlobalVar, addend: i64) {
+        self.description.data_relocs.push((offset, data, addend))
+    }
+
+    /// Reference the description of the data object.
+    pub fn description(&self) -> &DataDescription {
+        &self.description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataContext, Init};
+    use cretonne::ir::FunctionName;
+
+    #[test]
+    fn basic_data_context() {
+        let mut data_ctx = DataContext::new();
+        assert_eq!(data_ctx.description().init, Init::Uninitialized);
+
+        let func = data_ctx.import_function(FunctionName::new("foo"));
+        let data = data_ctx.import_global_var(FunctionName::new("bar"));
+        data_ctx.define(vec![0; 16].into_boxed_slice());
+        data_ctx.set_align(8);
+        data_ctx.write_function_addr(0, func);
+        data_ctx.write_data_addr(8, data, 4);
+
+        {
+            let description = data_ctx.description();
+            assert_eq!(description.init.size(), 16);
+            assert_eq!(description.align, 8);
+            assert_eq!(description.function_decls[func], FunctionName::new("foo"));
+            assert_eq!(description.function_relocs, [(0, func)]);
+            assert_eq!(description.data_relocs, [(8, data, 4)]);
+        }
+
+        data_ctx.clear();
+        assert_eq!(data_ctx.description().init, Init::Uninitialized);
+        assert!(data_ctx.description().function_relocs.is_empty());
+    }
+}
@@ -0,0 +1,38 @@
+//! Top-level lib.rs for `cton_module`.
+//!
+//! Cretonne compiles individual functions. This crate manages a whole module of functions and
+//! data objects on top of that:
+//!
+//! - Functions and data objects are declared by name with a `Linkage` saying whether they are
+//!   imported, local to the module, or exported from it.
+//! - Declared functions are imported into other functions with `Module::declare_func_in_func`,
+//!   and referenced from data objects with a `DataContext`.
+//! - Definitions are compiled and emitted by a `Backend`, which resolves the references between
+//!   them when the module is finalized.
+//!
+//! The `SimpleJitBackend` emits code into executable memory in the current process, and the
+//! `ObjectBackend` writes an ELF relocatable object file.
+
+#![deny(missing_docs)]
+
+#[macro_use]
+extern crate cretonne;
+#[cfg(unix)]
+extern crate libc;
+
+mod backend;
+mod data_context;
+mod module;
+mod object;
+mod relocs;
+#[cfg(unix)]
+mod simplejit;
+
+pub use backend::Backend;
+pub use data_context::{DataContext, DataDescription, Init};
+pub use module::{DataId, FuncId, FuncOrDataId, Linkage, Module, ModuleNamespace, ModuleResult};
+pub use module::{DataDeclaration, FunctionDeclaration, ModuleError};
+pub use object::ObjectBackend;
+#[cfg(unix)]
+pub use simplejit::{SimpleJitBackend, SimpleJitCompiledData, SimpleJitCompiledFunction,
+                    SimpleJitProduct};
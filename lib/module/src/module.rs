@@ -0,0 +1,533 @@
+//! Defines `Module` and related types.
+
+use backend::Backend;
+use cretonne::Context;
+use cretonne::entity::PrimaryMap;
+use cretonne::ir::{self, FunctionName};
+use cretonne::isa::TargetIsa;
+use cretonne::result::CtonError;
+use data_context::DataContext;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::str;
+
+/// A function identifier for use in the `Module` interface.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct FuncId(u32);
+entity_impl!(FuncId, "funcid");
+
+/// A data object identifier for use in the `Module` interface.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DataId(u32);
+entity_impl!(DataId, "dataid");
+
+/// Linkage refers to where an entity is defined and who can see it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Linkage {
+    /// Defined outside of the module.
+    Import,
+    /// Defined inside the module, but not visible outside it.
+    Local,
+    /// Defined inside the module, and visible outside it.
+    Export,
+}
+
+impl Linkage {
+    /// Combine the linkage of two declarations of the same entity.
+    ///
+    /// A definition in the module wins over an import, and an export wins over a local
+    /// definition.
+    fn merge(a: Linkage, b: Linkage) -> Linkage {
+        match (a, b) {
+            (Linkage::Export, _) | (_, Linkage::Export) => Linkage::Export,
+            (Linkage::Local, _) | (_, Linkage::Local) => Linkage::Local,
+            (Linkage::Import, Linkage::Import) => Linkage::Import,
+        }
+    }
+
+    /// Test whether this linkage can have a definition in the module.
+    pub fn is_definable(self) -> bool {
+        match self {
+            Linkage::Import => false,
+            Linkage::Local | Linkage::Export => true,
+        }
+    }
+
+    /// Test whether this linkage makes the symbol visible outside the module.
+    pub fn is_global(self) -> bool {
+        match self {
+            Linkage::Import | Linkage::Export => true,
+            Linkage::Local => false,
+        }
+    }
+}
+
+/// A declared name may refer to either a function or data declaration.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FuncOrDataId {
+    /// When it's a function.
+    Func(FuncId),
+    /// When it's a data object.
+    Data(DataId),
+}
+
+/// Information about a function which can be called.
+#[derive(Clone, Debug)]
+pub struct FunctionDeclaration {
+    /// The symbol name of the function.
+    pub name: String,
+    /// Where the function is defined.
+    pub linkage: Linkage,
+    /// The signature used to call the function.
+    pub signature: ir::Signature,
+}
+
+/// Information about a data object which can be accessed.
+#[derive(Clone, Debug)]
+pub struct DataDeclaration {
+    /// The symbol name of the data object.
+    pub name: String,
+    /// Where the data object is defined.
+    pub linkage: Linkage,
+    /// Can the data object be written to?
+    pub writable: bool,
+}
+
+/// An error returned from the `Module` interface.
+#[derive(Debug)]
+pub enum ModuleError {
+    /// The name was used without being declared first.
+    Undeclared(String),
+
+    /// The name was declared, but it is used in a way that requires a definition, and it hasn't
+    /// been defined.
+    Undefined(String),
+
+    /// The name was declared as both a function and a data object.
+    IncompatibleDeclaration(String),
+
+    /// A function was declared with different signatures.
+    IncompatibleSignature(String),
+
+    /// A function or data object was defined more than once.
+    DuplicateDefinition(String),
+
+    /// A function or data object declared as an import was defined.
+    InvalidImportDefinition(String),
+
+    /// The function failed to compile.
+    Compilation(CtonError),
+
+    /// The backend failed to define or finalize a function or data object.
+    Backend(String),
+}
+
+/// A convenient alias for a `Result` that uses `ModuleError` as the error type.
+pub type ModuleResult<T> = Result<T, ModuleError>;
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModuleError::Undeclared(ref name) => write!(f, "undeclared identifier: {}", name),
+            ModuleError::Undefined(ref name) => write!(f, "undefined identifier: {}", name),
+            ModuleError::IncompatibleDeclaration(ref name) => {
+                write!(f, "incompatible declaration of identifier: {}", name)
+            }
+            ModuleError::IncompatibleSignature(ref name) => {
+                write!(f, "function {} declared with incompatible signatures", name)
+            }
+            ModuleError::DuplicateDefinition(ref name) => {
+                write!(f, "duplicate definition of identifier: {}", name)
+            }
+            ModuleError::InvalidImportDefinition(ref name) => {
+                write!(f, "invalid to define identifier declared as an import: {}", name)
+            }
+            ModuleError::Compilation(ref e) => write!(f, "compilation error: {}", e),
+            ModuleError::Backend(ref msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl StdError for ModuleError {
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            ModuleError::Compilation(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<CtonError> for ModuleError {
+    fn from(e: CtonError) -> ModuleError {
+        ModuleError::Compilation(e)
+    }
+}
+
+/// A function belonging to a `Module`.
+struct ModuleFunction<B: Backend> {
+    decl: FunctionDeclaration,
+    compiled: Option<B::CompiledFunction>,
+    finalized: bool,
+}
+
+/// A data object belonging to a `Module`.
+struct ModuleData<B: Backend> {
+    decl: DataDeclaration,
+    compiled: Option<B::CompiledData>,
+    finalized: bool,
+}
+
+/// The functions and data objects of a module, as seen by a backend.
+///
+/// Backends use this to resolve the names referenced by a function or data object.
+pub struct ModuleNamespace<'a, B: 'a + Backend> {
+    names: &'a HashMap<String, FuncOrDataId>,
+    functions: &'a PrimaryMap<FuncId, ModuleFunction<B>>,
+    data_objects: &'a PrimaryMap<DataId, ModuleData<B>>,
+}
+
+impl<'a, B: Backend> ModuleNamespace<'a, B> {
+    fn lookup(&self, name: &FunctionName) -> Option<FuncOrDataId> {
+        str::from_utf8(name.as_ref()).ok().and_then(|name| {
+            self.names.get(name).cloned()
+        })
+    }
+
+    /// Get the declaration of the function called `name`, and its definition if it has been
+    /// defined.
+    pub fn get_function_definition(
+        &self,
+        name: &FunctionName,
+    ) -> ModuleResult<(&FunctionDeclaration, Option<&B::CompiledFunction>)> {
+        match self.lookup(name) {
+            Some(FuncOrDataId::Func(id)) => {
+                let f = &self.functions[id];
+                Ok((&f.decl, f.compiled.as_ref()))
+            }
+            Some(FuncOrDataId::Data(_)) => {
+                Err(ModuleError::IncompatibleDeclaration(name.to_string()))
+            }
+            None => Err(ModuleError::Undeclared(name.to_string())),
+        }
+    }
+
+    /// Get the declaration of the data object called `name`, and its definition if it has been
+    /// defined.
+    pub fn get_data_definition(
+        &self,
+        name: &FunctionName,
+    ) -> ModuleResult<(&DataDeclaration, Option<&B::CompiledData>)> {
+        match self.lookup(name) {
+            Some(FuncOrDataId::Data(id)) => {
+                let d = &self.data_objects[id];
+                Ok((&d.decl, d.compiled.as_ref()))
+            }
+            Some(FuncOrDataId::Func(_)) => {
+                Err(ModuleError::IncompatibleDeclaration(name.to_string()))
+            }
+            None => Err(ModuleError::Undeclared(name.to_string())),
+        }
+    }
+}
+
+/// A `Module` is a collection of functions and data objects that are linked together.
+///
+/// Functions and data objects are first declared with their linkage, so they can be referenced
+/// from other functions and data objects. Declarations with a definable linkage are then defined,
+/// and finally the module is finalized by its backend.
+pub struct Module<B: Backend> {
+    names: HashMap<String, FuncOrDataId>,
+    functions: PrimaryMap<FuncId, ModuleFunction<B>>,
+    data_objects: PrimaryMap<DataId, ModuleData<B>>,
+    backend: B,
+}
+
+impl<B: Backend> Module<B> {
+    /// Create a new `Module` using `backend` to define and finalize its contents.
+    pub fn new(backend: B) -> Module<B> {
+        Module {
+            names: HashMap::new(),
+            functions: PrimaryMap::new(),
+            data_objects: PrimaryMap::new(),
+            backend,
+        }
+    }
+
+    /// Get the target ISA used by the backend.
+    pub fn isa(&self) -> &TargetIsa {
+        self.backend.isa()
+    }
+
+    /// Look up a function or data object by name.
+    pub fn get_name(&self, name: &str) -> Option<FuncOrDataId> {
+        self.names.get(name).cloned()
+    }
+
+    /// Get the declaration of a function.
+    pub fn function_declaration(&self, func: FuncId) -> &FunctionDeclaration {
+        &self.functions[func].decl
+    }
+
+    /// Get the declaration of a data object.
+    pub fn data_declaration(&self, data: DataId) -> &DataDeclaration {
+        &self.data_objects[data].decl
+    }
+
+    /// Declare a function in this module.
+    ///
+    /// A function can be declared more than once, as long as the signatures match. The linkage
+    /// of the declarations is merged, so an import followed by a local definition is fine.
+    pub fn declare_function(
+        &mut self,
+        name: &str,
+        linkage: Linkage,
+        signature: &ir::Signature,
+    ) -> ModuleResult<FuncId> {
+        match self.names.get(name).cloned() {
+            Some(FuncOrDataId::Func(id)) => {
+                let existing = &mut self.functions[id];
+                if existing.decl.signature != *signature {
+                    return Err(ModuleError::IncompatibleSignature(name.to_string()));
+                }
+                existing.decl.linkage = Linkage::merge(existing.decl.linkage, linkage);
+                self.backend.declare_function(name, existing.decl.linkage);
+                Ok(id)
+            }
+            Some(FuncOrDataId::Data(_)) => Err(
+                ModuleError::IncompatibleDeclaration(name.to_string()),
+            ),
+            None => {
+                let id = self.functions.push(ModuleFunction {
+                    decl: FunctionDeclaration {
+                        name: name.to_string(),
+                        linkage,
+                        signature: signature.clone(),
+                    },
+                    compiled: None,
+                    finalized: false,
+                });
+                self.names.insert(name.to_string(), FuncOrDataId::Func(id));
+                self.backend.declare_function(name, linkage);
+                Ok(id)
+            }
+        }
+    }
+
+    /// Declare a data object in this module.
+    pub fn declare_data(
+        &mut self,
+        name: &str,
+        linkage: Linkage,
+        writable: bool,
+    ) -> ModuleResult<DataId> {
+        match self.names.get(name).cloned() {
+            Some(FuncOrDataId::Data(id)) => {
+                let existing = &mut self.data_objects[id];
+                existing.decl.linkage = Linkage::merge(existing.decl.linkage, linkage);
+                existing.decl.writable = existing.decl.writable || writable;
+                self.backend.declare_data(
+                    name,
+                    existing.decl.linkage,
+                    existing.decl.writable,
+                );
+                Ok(id)
+            }
+            Some(FuncOrDataId::Func(_)) => Err(
+                ModuleError::IncompatibleDeclaration(name.to_string()),
+            ),
+            None => {
+                let id = self.data_objects.push(ModuleData {
+                    decl: DataDeclaration {
+                        name: name.to_string(),
+                        linkage,
+                        writable,
+                    },
+                    compiled: None,
+                    finalized: false,
+                });
+                self.names.insert(name.to_string(), FuncOrDataId::Data(id));
+                self.backend.declare_data(name, linkage, writable);
+                Ok(id)
+            }
+        }
+    }
+
+    /// Import the function `func` into `in_func` so it can be called with the returned
+    /// reference.
+    pub fn declare_func_in_func(&self, func: FuncId, in_func: &mut ir::Function) -> ir::FuncRef {
+        let decl = &self.functions[func].decl;
+        let signature = in_func.import_signature(decl.signature.clone());
+        in_func.import_function(ir::ExtFuncData {
+            name: FunctionName::new(decl.name.as_str()),
+            signature,
+        })
+    }
+
+    /// Declare the data object `data` in `in_func` as a symbolic global variable, whose
+    /// `global_addr` is the address of the data object.
+    pub fn declare_data_in_func(&self, data: DataId, in_func: &mut ir::Function) -> ir::GlobalVar {
+        in_func.create_global_var(ir::GlobalVarData::Sym {
+            name: FunctionName::new(self.data_objects[data].decl.name.as_str()),
+        })
+    }
+
+    /// Reference the function `func` from the data object described by `ctx`.
+    pub fn declare_func_in_data(&self, func: FuncId, ctx: &mut DataContext) -> ir::FuncRef {
+        ctx.import_function(FunctionName::new(self.functions[func].decl.name.as_str()))
+    }
+
+    /// Reference the data object `data` from the data object described by `ctx`.
+    pub fn declare_data_in_data(&self, data: DataId, ctx: &mut DataContext) -> ir::GlobalVar {
+        ctx.import_global_var(FunctionName::new(self.data_objects[data].decl.name.as_str()))
+    }
+
+    /// Compile the function in `ctx` and define `func` as the result.
+    ///
+    /// Calls in the function refer to other functions by the names of their `ExtFuncData`, and
+    /// symbolic global variables refer to data objects by name. Both must have been declared in
+    /// this module.
+    pub fn define_function(&mut self, func: FuncId, ctx: &mut Context) -> ModuleResult<()> {
+        {
+            let info = &self.functions[func];
+            if info.compiled.is_some() {
+                return Err(ModuleError::DuplicateDefinition(info.decl.name.clone()));
+            }
+            if !info.decl.linkage.is_definable() {
+                return Err(ModuleError::InvalidImportDefinition(info.decl.name.clone()));
+            }
+        }
+
+        let code_size = ctx.compile(self.backend.isa())?;
+        let compiled = {
+            let namespace = ModuleNamespace {
+                names: &self.names,
+                functions: &self.functions,
+                data_objects: &self.data_objects,
+            };
+            let name = &self.functions[func].decl.name;
+            self.backend.define_function(
+                name,
+                ctx,
+                &namespace,
+                code_size,
+            )?
+        };
+        self.functions[func].compiled = Some(compiled);
+        Ok(())
+    }
+
+    /// Define the data object `data` with the contents described by `ctx`.
+    pub fn define_data(&mut self, data: DataId, ctx: &DataContext) -> ModuleResult<()> {
+        {
+            let info = &self.data_objects[data];
+            if info.compiled.is_some() {
+                return Err(ModuleError::DuplicateDefinition(info.decl.name.clone()));
+            }
+            if !info.decl.linkage.is_definable() {
+                return Err(ModuleError::InvalidImportDefinition(info.decl.name.clone()));
+            }
+        }
+
+        let compiled = {
+            let namespace = ModuleNamespace {
+                names: &self.names,
+                functions: &self.functions,
+                data_objects: &self.data_objects,
+            };
+            let decl = &self.data_objects[data].decl;
+            self.backend.define_data(
+                &decl.name,
+                decl.writable,
+                ctx,
+                &namespace,
+            )?
+        };
+        self.data_objects[data].compiled = Some(compiled);
+        Ok(())
+    }
+
+    /// Finalize the definition of `func`, resolving its references to other functions and data
+    /// objects.
+    ///
+    /// Everything the function references must have been defined, or be available as imports to
+    /// the backend.
+    pub fn finalize_function(&mut self, func: FuncId) -> ModuleResult<B::FinalizedFunction> {
+        let output = {
+            let info = &self.functions[func];
+            let compiled = match info.compiled {
+                Some(ref compiled) => compiled,
+                None => return Err(ModuleError::Undefined(info.decl.name.clone())),
+            };
+            let namespace = ModuleNamespace {
+                names: &self.names,
+                functions: &self.functions,
+                data_objects: &self.data_objects,
+            };
+            self.backend.finalize_function(compiled, &namespace)?
+        };
+        self.functions[func].finalized = true;
+        Ok(output)
+    }
+
+    /// Finalize the definition of `data`, resolving its references to functions and other data
+    /// objects.
+    pub fn finalize_data(&mut self, data: DataId) -> ModuleResult<B::FinalizedData> {
+        let output = {
+            let info = &self.data_objects[data];
+            let compiled = match info.compiled {
+                Some(ref compiled) => compiled,
+                None => return Err(ModuleError::Undefined(info.decl.name.clone())),
+            };
+            let namespace = ModuleNamespace {
+                names: &self.names,
+                functions: &self.functions,
+                data_objects: &self.data_objects,
+            };
+            self.backend.finalize_data(compiled, &namespace)?
+        };
+        self.data_objects[data].finalized = true;
+        Ok(output)
+    }
+
+    /// Finalize all the functions and data objects that have been defined, but not finalized.
+    pub fn finalize_all(&mut self) -> ModuleResult<()> {
+        for func in self.functions.keys() {
+            let info = &self.functions[func];
+            if info.compiled.is_some() && !info.finalized {
+                self.finalize_function(func)?;
+            }
+        }
+        for data in self.data_objects.keys() {
+            let info = &self.data_objects[data];
+            if info.compiled.is_some() && !info.finalized {
+                self.finalize_data(data)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finalize everything and consume the module, returning the product of the backend.
+    pub fn finish(mut self) -> ModuleResult<B::Product> {
+        self.finalize_all()?;
+        Ok(self.backend.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Linkage;
+
+    #[test]
+    fn merge_linkage() {
+        use self::Linkage::*;
+        assert_eq!(Linkage::merge(Import, Import), Import);
+        assert_eq!(Linkage::merge(Import, Local), Local);
+        assert_eq!(Linkage::merge(Local, Import), Local);
+        assert_eq!(Linkage::merge(Export, Import), Export);
+        assert_eq!(Linkage::merge(Local, Export), Export);
+        assert!(!Import.is_definable());
+        assert!(Local.is_definable());
+        assert!(!Local.is_global());
+    }
+}
@@ -0,0 +1,473 @@
+//! A `Backend` that writes an ELF relocatable object file.
+//!
+//! Only 64-bit Intel code is supported. Functions are placed in `.text`, writable data objects in
+//! `.data` or `.bss`, and read-only data objects in `.rodata`. References to other functions and
+//! data objects become ELF relocations, so the object file can be linked with a system linker.
+//! Read-only data objects containing addresses are placed in `.data` too, so the linker doesn't
+//! have to relocate a read-only section.
+
+use backend::Backend;
+use cretonne::Context;
+use cretonne::isa::TargetIsa;
+use data_context::{DataContext, Init};
+use module::{Linkage, ModuleError, ModuleNamespace, ModuleResult};
+use relocs::{RelocRecord, RelocTarget, emit_function, data_relocs};
+use std::collections::HashMap;
+
+// Section indexes in the object file.
+const SHN_UNDEF: u16 = 0;
+const TEXT: usize = 1;
+const DATA: usize = 2;
+const RODATA: usize = 3;
+const BSS: usize = 4;
+const RELA_TEXT: usize = 5;
+const RELA_DATA: usize = 6;
+const RELA_RODATA: usize = 7;
+const SYMTAB: usize = 8;
+const STRTAB: usize = 9;
+const SHSTRTAB: usize = 10;
+const NOTE_GNU_STACK: usize = 11;
+const SECTION_NAMES: [&str; 12] = [
+    "",
+    ".text",
+    ".data",
+    ".rodata",
+    ".bss",
+    ".rela.text",
+    ".rela.data",
+    ".rela.rodata",
+    ".symtab",
+    ".strtab",
+    ".shstrtab",
+    ".note.GNU-stack",
+];
+
+// ELF constants.
+const E_SHOFF: usize = 40;
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+const SHF_WRITE: u64 = 1;
+const SHF_ALLOC: u64 = 2;
+const SHF_EXECINSTR: u64 = 4;
+const SHF_INFO_LINK: u64 = 0x40;
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const EM_X86_64: u16 = 62;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_GOTPCREL: u32 = 9;
+const R_X86_64_32: u32 = 10;
+
+/// A symbol declared in the module.
+struct Symbol {
+    name: String,
+    linkage: Linkage,
+    is_func: bool,
+    // Section index, offset, and size of the definition.
+    def: Option<(usize, u64, u64)>,
+}
+
+/// A relocation in one of the sections, referencing a symbol by name.
+struct Relocation {
+    section: usize,
+    offset: u64,
+    kind: u32,
+    symbol: String,
+    addend: i64,
+}
+
+/// A `Backend` for writing an ELF relocatable object file.
+///
+/// The product of the module is the contents of the object file.
+pub struct ObjectBackend {
+    isa: Box<TargetIsa>,
+    symbols: Vec<Symbol>,
+    symbol_index: HashMap<String, usize>,
+    sections: [Vec<u8>; 4],
+    bss_size: u64,
+    align: [u64; 5],
+    relocs: Vec<Relocation>,
+}
+
+impl ObjectBackend {
+    /// Create a new backend generating code for `isa`.
+    pub fn new(isa: Box<TargetIsa>) -> ModuleResult<ObjectBackend> {
+        if isa.name() != "intel" || !isa.flags().is_64bit() {
+            return Err(ModuleError::Backend(format!(
+                "object files are not supported for {}",
+                isa.name()
+            )));
+        }
+        Ok(ObjectBackend {
+            isa,
+            symbols: Vec::new(),
+            symbol_index: HashMap::new(),
+            sections: [Vec::new(), Vec::new(), Vec::new(), Vec::new()],
+            bss_size: 0,
+            align: [1; 5],
+            relocs: Vec::new(),
+        })
+    }
+
+    fn declare(&mut self, name: &str, linkage: Linkage, is_func: bool) {
+        if let Some(&idx) = self.symbol_index.get(name) {
+            self.symbols[idx].linkage = linkage;
+            return;
+        }
+        self.symbol_index.insert(name.to_string(), self.symbols.len());
+        self.symbols.push(Symbol {
+            name: name.to_string(),
+            linkage,
+            is_func,
+            def: None,
+        });
+    }
+
+    /// Append `bytes` to `section`, aligned to `align`, and return the offset.
+    fn append(&mut self, section: usize, bytes: &[u8], align: u64) -> u64 {
+        self.align[section] = self.align[section].max(align);
+        let data = &mut self.sections[section];
+        while data.len() as u64 % align != 0 {
+            data.push(0);
+        }
+        let offset = data.len() as u64;
+        data.extend_from_slice(bytes);
+        offset
+    }
+
+    /// Record the definition of the symbol `name`.
+    fn define(&mut self, name: &str, section: usize, offset: u64, size: u64) {
+        let idx = self.symbol_index[name];
+        self.symbols[idx].def = Some((section, offset, size));
+    }
+
+    /// Convert the relocations of a function or data object named `name` to ELF relocations.
+    fn add_relocs(&mut self, name: &str, section: usize, base: u64, relocs: &[RelocRecord]) {
+        for reloc in relocs {
+            let (symbol, is_func) = match reloc.target {
+                RelocTarget::Func(ref target) => (symbol_name(target.as_ref()), true),
+                RelocTarget::Data(ref target) => (symbol_name(target.as_ref()), false),
+                RelocTarget::Local(offset) => {
+                    // Refer to a local code offset relative to the function symbol.
+                    let addend = reloc.addend + i64::from(offset);
+                    self.add_reloc(section, base, reloc, name.to_string(), addend, false);
+                    continue;
+                }
+            };
+            self.add_reloc(section, base, reloc, symbol, reloc.addend, is_func);
+        }
+    }
+
+    fn add_reloc(
+        &mut self,
+        section: usize,
+        base: u64,
+        reloc: &RelocRecord,
+        symbol: String,
+        addend: i64,
+        is_func: bool,
+    ) {
+        let (kind, addend) = match reloc.kind {
+            "Abs8" => (R_X86_64_64, addend),
+            "Abs4" => (R_X86_64_32, addend),
+            // The displacement is relative to the end of the 4-byte field.
+            "PCRel4" if is_func => (R_X86_64_PLT32, addend - 4),
+            "PCRel4" => (R_X86_64_PC32, addend - 4),
+            "PLTRel4" => (R_X86_64_PLT32, addend - 4),
+            "GOTPCRel4" => (R_X86_64_GOTPCREL, addend - 4),
+            kind => panic!("unsupported {} relocation", kind),
+        };
+        self.relocs.push(Relocation {
+            section,
+            offset: base + u64::from(reloc.offset),
+            kind,
+            symbol,
+            addend,
+        });
+    }
+}
+
+/// Get the symbol name for a function or data object name.
+fn symbol_name(name: &[u8]) -> String {
+    String::from_utf8_lossy(name).into_owned()
+}
+
+impl Backend for ObjectBackend {
+    type CompiledFunction = ();
+    type CompiledData = ();
+    type FinalizedFunction = ();
+    type FinalizedData = ();
+
+    /// The contents of the object file.
+    type Product = Vec<u8>;
+
+    fn isa(&self) -> &TargetIsa {
+        &*self.isa
+    }
+
+    fn declare_function(&mut self, name: &str, linkage: Linkage) {
+        self.declare(name, linkage, true);
+    }
+
+    fn declare_data(&mut self, name: &str, linkage: Linkage, _writable: bool) {
+        self.declare(name, linkage, false);
+    }
+
+    fn define_function(
+        &mut self,
+        name: &str,
+        ctx: &Context,
+        _namespace: &ModuleNamespace<Self>,
+        code_size: u32,
+    ) -> ModuleResult<()> {
+        let mut code = vec![0; code_size as usize];
+        let relocs = emit_function(name, ctx, &*self.isa, &mut code)?;
+        let offset = self.append(TEXT, &code, 16);
+        self.define(name, TEXT, offset, code.len() as u64);
+        self.add_relocs(name, TEXT, offset, &relocs);
+        Ok(())
+    }
+
+    fn define_data(
+        &mut self,
+        name: &str,
+        writable: bool,
+        data_ctx: &DataContext,
+        _namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<()> {
+        let description = data_ctx.description();
+        let align = u64::from(description.align);
+        let relocs = data_relocs(description, &*self.isa);
+        let (section, offset, size) = match description.init {
+            Init::Uninitialized => {
+                return Err(ModuleError::Backend(
+                    format!("data object {} has no initializer", name),
+                ))
+            }
+            Init::Zeros { size } if writable && relocs.is_empty() => {
+                self.align[BSS] = self.align[BSS].max(align);
+                let offset = (self.bss_size + align - 1) & !(align - 1);
+                self.bss_size = offset + size as u64;
+                (BSS, offset, size)
+            }
+            Init::Zeros { size } => {
+                let section = if writable || !relocs.is_empty() { DATA } else { RODATA };
+                (section, self.append(section, &vec![0; size], align), size)
+            }
+            Init::Bytes { ref contents } => {
+                let section = if writable || !relocs.is_empty() { DATA } else { RODATA };
+                (section, self.append(section, contents, align), contents.len())
+            }
+        };
+        self.define(name, section, offset, size as u64);
+        self.add_relocs(name, section, offset, &relocs);
+        Ok(())
+    }
+
+    fn finalize_function(
+        &mut self,
+        _func: &(),
+        _namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<()> {
+        // Nothing to do.
+        Ok(())
+    }
+
+    fn finalize_data(
+        &mut self,
+        _data: &(),
+        _namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<()> {
+        // Nothing to do.
+        Ok(())
+    }
+
+    fn finish(self) -> Vec<u8> {
+        ObjectWriter::new().write(self)
+    }
+}
+
+/// Builder for the bytes of an ELF file.
+struct ObjectWriter {
+    out: Vec<u8>,
+}
+
+impl ObjectWriter {
+    fn new() -> ObjectWriter {
+        ObjectWriter { out: Vec::new() }
+    }
+
+    fn put1(&mut self, x: u8) {
+        self.out.push(x);
+    }
+
+    fn put2(&mut self, x: u16) {
+        for i in 0..2 {
+            self.out.push((x >> (8 * i)) as u8);
+        }
+    }
+
+    fn put4(&mut self, x: u32) {
+        for i in 0..4 {
+            self.out.push((x >> (8 * i)) as u8);
+        }
+    }
+
+    fn put8(&mut self, x: u64) {
+        for i in 0..8 {
+            self.out.push((x >> (8 * i)) as u8);
+        }
+    }
+
+    fn align(&mut self, align: u64) {
+        while self.out.len() as u64 % align != 0 {
+            self.out.push(0);
+        }
+    }
+
+    fn write(mut self, backend: ObjectBackend) -> Vec<u8> {
+        // Local symbols must come before global symbols in the symbol table.
+        let mut order: Vec<&Symbol> = backend.symbols.iter().collect();
+        order.sort_by_key(|sym| is_global(sym));
+        let first_global = order.iter().position(|sym| is_global(sym)).unwrap_or(
+            order.len(),
+        ) + 1;
+
+        // Build the string tables.
+        let mut strtab = vec![0];
+        let mut symtab_index = HashMap::new();
+        let mut symtab = Vec::new();
+        for (idx, sym) in order.iter().enumerate() {
+            symtab_index.insert(sym.name.as_str(), idx + 1);
+            symtab.push((strtab.len() as u32, *sym));
+            strtab.extend_from_slice(sym.name.as_bytes());
+            strtab.push(0);
+        }
+        let mut shstrtab = vec![0];
+        let mut shnames = Vec::new();
+        for name in &SECTION_NAMES {
+            if name.is_empty() {
+                shnames.push(0);
+            } else {
+                shnames.push(shstrtab.len() as u32);
+                shstrtab.extend_from_slice(name.as_bytes());
+                shstrtab.push(0);
+            }
+        }
+
+        // ELF header, with the section header offset patched in below.
+        self.out.extend_from_slice(b"\x7fELF");
+        self.put1(2); // ELFCLASS64
+        self.put1(1); // ELFDATA2LSB
+        self.put1(1); // EV_CURRENT
+        self.put1(0); // ELFOSABI_NONE
+        self.put8(0);
+        self.put2(1); // ET_REL
+        self.put2(EM_X86_64);
+        self.put4(1); // EV_CURRENT
+        self.put8(0); // e_entry
+        self.put8(0); // e_phoff
+        self.put8(0); // e_shoff, at E_SHOFF
+        self.put4(0); // e_flags
+        self.put2(64); // e_ehsize
+        self.put2(0); // e_phentsize
+        self.put2(0); // e_phnum
+        self.put2(64); // e_shentsize
+        self.put2(SECTION_NAMES.len() as u16);
+        self.put2(SHSTRTAB as u16);
+
+        // Section contents, as (offset, size) pairs.
+        let mut contents = vec![(0, 0); SECTION_NAMES.len()];
+        for &section in &[TEXT, DATA, RODATA] {
+            self.align(backend.align[section]);
+            contents[section] = (self.out.len() as u64, backend.sections[section].len() as u64);
+            self.out.extend_from_slice(&backend.sections[section]);
+        }
+        contents[BSS] = (self.out.len() as u64, backend.bss_size);
+        for &(rela, section) in &[(RELA_TEXT, TEXT), (RELA_DATA, DATA), (RELA_RODATA, RODATA)] {
+            self.align(8);
+            let start = self.out.len() as u64;
+            for reloc in backend.relocs.iter().filter(|r| r.section == section) {
+                let sym = symtab_index[reloc.symbol.as_str()] as u64;
+                self.put8(reloc.offset);
+                self.put8((sym << 32) | u64::from(reloc.kind));
+                self.put8(reloc.addend as u64);
+            }
+            contents[rela] = (start, self.out.len() as u64 - start);
+        }
+        self.align(8);
+        let start = self.out.len() as u64;
+        self.out.extend_from_slice(&[0; 24]);
+        for &(name, sym) in &symtab {
+            let (shndx, value, size) = match sym.def {
+                Some((section, offset, size)) => (section as u16, offset, size),
+                None => (SHN_UNDEF, 0, 0),
+            };
+            let bind = if is_global(sym) { STB_GLOBAL } else { STB_LOCAL };
+            let kind = match (sym.def, sym.is_func) {
+                (None, _) => STT_NOTYPE,
+                (Some(_), true) => STT_FUNC,
+                (Some(_), false) => STT_OBJECT,
+            };
+            self.put4(name);
+            self.put1((bind << 4) | kind);
+            self.put1(0);
+            self.put2(shndx);
+            self.put8(value);
+            self.put8(size);
+        }
+        contents[SYMTAB] = (start, self.out.len() as u64 - start);
+        contents[STRTAB] = (self.out.len() as u64, strtab.len() as u64);
+        self.out.extend_from_slice(&strtab);
+        contents[SHSTRTAB] = (self.out.len() as u64, shstrtab.len() as u64);
+        self.out.extend_from_slice(&shstrtab);
+        contents[NOTE_GNU_STACK] = (self.out.len() as u64, 0);
+
+        // Section headers.
+        self.align(8);
+        let shoff = self.out.len() as u64;
+        for i in 0..8 {
+            self.out[E_SHOFF + i] = (shoff >> (8 * i)) as u8;
+        }
+        self.out.extend_from_slice(&[0; 64]);
+        for section in 1..SECTION_NAMES.len() {
+            let (sh_type, flags, link, info, align, entsize) = match section {
+                TEXT => (SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, 0, 0, backend.align[TEXT], 0),
+                DATA => (SHT_PROGBITS, SHF_ALLOC | SHF_WRITE, 0, 0, backend.align[DATA], 0),
+                RODATA => (SHT_PROGBITS, SHF_ALLOC, 0, 0, backend.align[RODATA], 0),
+                BSS => (SHT_NOBITS, SHF_ALLOC | SHF_WRITE, 0, 0, backend.align[BSS], 0),
+                RELA_TEXT => (SHT_RELA, SHF_INFO_LINK, SYMTAB, TEXT, 8, 24),
+                RELA_DATA => (SHT_RELA, SHF_INFO_LINK, SYMTAB, DATA, 8, 24),
+                RELA_RODATA => (SHT_RELA, SHF_INFO_LINK, SYMTAB, RODATA, 8, 24),
+                SYMTAB => (SHT_SYMTAB, 0, STRTAB, first_global, 8, 24),
+                // An empty `.note.GNU-stack` section means the code doesn't need an executable
+                // stack.
+                NOTE_GNU_STACK => (SHT_PROGBITS, 0, 0, 0, 1, 0),
+                _ => (SHT_STRTAB, 0, 0, 0, 1, 0),
+            };
+            self.put4(shnames[section]);
+            self.put4(sh_type);
+            self.put8(flags);
+            self.put8(0); // sh_addr
+            self.put8(contents[section].0);
+            self.put8(contents[section].1);
+            self.put4(link as u32);
+            self.put4(info as u32);
+            self.put8(align);
+            self.put8(entsize);
+        }
+        self.out
+    }
+}
+
+/// Is `sym` visible outside the object file?
+fn is_global(sym: &Symbol) -> bool {
+    sym.linkage.is_global() || sym.def.is_none()
+}
@@ -0,0 +1,98 @@
+//! Collecting the relocations of functions and data objects for the backends.
+
+use cretonne::Context;
+use cretonne::binemit::{self, CodeOffset, RelocRecorder, NullStackMapSink, NullCallSiteSink};
+use cretonne::ir::FunctionName;
+use cretonne::isa::TargetIsa;
+use data_context::DataDescription;
+use module::{ModuleError, ModuleResult};
+
+/// The target of a relocation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RelocTarget {
+    /// A function, by name.
+    Func(FunctionName),
+    /// A data object, by name.
+    Data(FunctionName),
+    /// An offset in the code of the function containing the relocation.
+    Local(CodeOffset),
+}
+
+/// A relocation that the backend must apply.
+#[derive(Clone, Debug)]
+pub struct RelocRecord {
+    /// Offset of the relocated field in the function or data object.
+    pub offset: CodeOffset,
+    /// The name of the relocation kind, as given by `TargetIsa::reloc_names()`.
+    pub kind: &'static str,
+    /// The relocation target.
+    pub target: RelocTarget,
+    /// Constant to add to the address of the target.
+    pub addend: i64,
+}
+
+/// Emit the compiled function in `ctx` to `mem`, and return its relocations.
+///
+/// The memory must have room for the code size returned by `Context::compile()`.
+pub fn emit_function(
+    name: &str,
+    ctx: &Context,
+    isa: &TargetIsa,
+    mem: &mut [u8],
+) -> ModuleResult<Vec<RelocRecord>> {
+    let mut sink = RelocRecorder::new(&ctx.func);
+    ctx.emit_to_memory(
+        mem.as_mut_ptr(),
+        &mut sink,
+        &mut NullStackMapSink,
+        &mut NullCallSiteSink,
+        isa,
+    );
+    let names = isa.reloc_names();
+    sink.into_relocs()
+        .into_iter()
+        .map(|reloc| {
+            let (target, addend) = match reloc.target {
+                binemit::RelocTarget::Ebb(ebb) => (RelocTarget::Local(ctx.func.offsets[ebb]), 0),
+                binemit::RelocTarget::Func(callee) => (RelocTarget::Func(callee), 0),
+                // Symbolic global variables are declared by `Module::declare_data_in_func()`.
+                binemit::RelocTarget::External(sym, addend) => (RelocTarget::Data(sym), addend),
+                binemit::RelocTarget::JumpTable(jt) => {
+                    return Err(ModuleError::Backend(
+                        format!("{}: can't relocate {}", name, jt),
+                    ))
+                }
+            };
+            Ok(RelocRecord {
+                offset: reloc.offset,
+                kind: names[reloc.reloc.0 as usize],
+                target,
+                addend,
+            })
+        })
+        .collect()
+}
+
+/// Get the relocations for the addresses written into a data object.
+///
+/// Addresses are written as absolute pointer-sized values.
+pub fn data_relocs(data: &DataDescription, isa: &TargetIsa) -> Vec<RelocRecord> {
+    let kind = if isa.flags().is_64bit() { "Abs8" } else { "Abs4" };
+    let funcs = data.function_relocs.iter().map(|&(offset, func)| {
+        RelocRecord {
+            offset,
+            kind,
+            target: RelocTarget::Func(data.function_decls[func].clone()),
+            addend: 0,
+        }
+    });
+    let datas = data.data_relocs.iter().map(|&(offset, gv, addend)| {
+        RelocRecord {
+            offset,
+            kind,
+            target: RelocTarget::Data(data.data_decls[gv].clone()),
+            addend,
+        }
+    });
+    funcs.chain(datas).collect()
+}
@@ -0,0 +1,360 @@
+//! A `Backend` that emits code and data into memory in the current process.
+
+use backend::Backend;
+use cretonne::Context;
+use cretonne::isa::TargetIsa;
+use data_context::{DataContext, Init};
+use libc;
+use module::{Linkage, ModuleError, ModuleNamespace, ModuleResult};
+use relocs::{RelocRecord, RelocTarget, emit_function, data_relocs};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::ptr;
+
+/// A region of memory mapped for code or data.
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mapping {
+    /// Map `size` bytes of zeroed, writable memory.
+    fn new(size: usize) -> ModuleResult<Mapping> {
+        let len = size.max(1);
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(ModuleError::Backend("failed to map memory".to_string()));
+        }
+        Ok(Mapping {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    /// Change the protection of the whole mapping.
+    fn protect(&self, prot: libc::c_int) -> ModuleResult<()> {
+        if unsafe { libc::mprotect(self.ptr as *mut libc::c_void, self.len, prot) } == 0 {
+            Ok(())
+        } else {
+            Err(ModuleError::Backend(
+                "failed to change memory protection".to_string(),
+            ))
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// A function defined in a `SimpleJitBackend`.
+pub struct SimpleJitCompiledFunction {
+    code: *mut u8,
+    relocs: Vec<RelocRecord>,
+    /// The targets of the GOT entries following the code, one pointer-sized slot each.
+    got: Vec<RelocTarget>,
+    /// Offset of the GOT from the start of the code.
+    got_offset: usize,
+}
+
+/// A data object defined in a `SimpleJitBackend`.
+pub struct SimpleJitCompiledData {
+    storage: *mut u8,
+    size: usize,
+    writable: bool,
+    relocs: Vec<RelocRecord>,
+}
+
+/// The memory holding the code and data of a finished `SimpleJitBackend`.
+///
+/// Pointers returned by `Module::finalize_function` and `Module::finalize_data` remain valid as
+/// long as this exists.
+pub struct SimpleJitProduct {
+    _memory: Vec<Mapping>,
+}
+
+/// A `Backend` for JIT compiling into the current process.
+///
+/// Each function and data object gets its own memory mapping. Functions are made executable and
+/// read-only data objects are made read-only when they are finalized.
+///
+/// Position-independent code is supported by giving each function its own GOT, placed after its
+/// code in the same mapping. Calls through the PLT go directly to the called function.
+///
+/// Imported functions and data objects are resolved with the symbols registered with `symbol()`,
+/// and then with the symbols of the running process.
+pub struct SimpleJitBackend {
+    isa: Box<TargetIsa>,
+    symbols: HashMap<String, *const u8>,
+    memory: Vec<Mapping>,
+}
+
+impl SimpleJitBackend {
+    /// Create a new backend generating code for `isa`, which must be the host ISA.
+    pub fn new(isa: Box<TargetIsa>) -> SimpleJitBackend {
+        SimpleJitBackend {
+            isa,
+            symbols: HashMap::new(),
+            memory: Vec::new(),
+        }
+    }
+
+    /// Define the address of an imported symbol.
+    pub fn symbol(&mut self, name: &str, ptr: *const u8) {
+        self.symbols.insert(name.to_string(), ptr);
+    }
+
+    fn allocate(&mut self, size: usize) -> ModuleResult<*mut u8> {
+        let mapping = Mapping::new(size)?;
+        let ptr = mapping.ptr;
+        self.memory.push(mapping);
+        Ok(ptr)
+    }
+
+    fn protect(&self, ptr: *mut u8, prot: libc::c_int) -> ModuleResult<()> {
+        self.memory
+            .iter()
+            .find(|m| m.ptr == ptr)
+            .expect("memory was allocated by this backend")
+            .protect(prot)
+    }
+
+    /// Look up an imported symbol.
+    fn lookup_symbol(&self, name: &str) -> ModuleResult<*const u8> {
+        if let Some(&ptr) = self.symbols.get(name) {
+            return Ok(ptr);
+        }
+        let c_name = CString::new(name).map_err(|_| {
+            ModuleError::Backend(format!("invalid symbol name: {}", name))
+        })?;
+        let ptr = unsafe { libc::dlsym(libc::RTLD_DEFAULT, c_name.as_ptr()) };
+        if ptr.is_null() {
+            Err(ModuleError::Backend(format!("can't resolve symbol: {}", name)))
+        } else {
+            Ok(ptr as *const u8)
+        }
+    }
+
+    /// Get the address of a relocation target.
+    fn target_address(
+        &self,
+        base: *mut u8,
+        target: &RelocTarget,
+        namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<*const u8> {
+        match *target {
+            RelocTarget::Local(offset) => Ok(unsafe { base.offset(offset as isize) }),
+            RelocTarget::Func(ref name) => {
+                let (decl, compiled) = namespace.get_function_definition(name)?;
+                match compiled {
+                    Some(compiled) => Ok(compiled.code),
+                    None if decl.linkage == Linkage::Import => self.lookup_symbol(&decl.name),
+                    None => Err(ModuleError::Undefined(decl.name.clone())),
+                }
+            }
+            RelocTarget::Data(ref name) => {
+                let (decl, compiled) = namespace.get_data_definition(name)?;
+                match compiled {
+                    Some(compiled) => Ok(compiled.storage),
+                    None if decl.linkage == Linkage::Import => self.lookup_symbol(&decl.name),
+                    None => Err(ModuleError::Undefined(decl.name.clone())),
+                }
+            }
+        }
+    }
+
+    /// Apply `relocs` to the memory at `base`.
+    ///
+    /// The GOT entries for `got` are at `got_base`, and are filled in first.
+    fn relocate(
+        &self,
+        base: *mut u8,
+        relocs: &[RelocRecord],
+        got: &[RelocTarget],
+        got_base: *mut u8,
+        namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<()> {
+        let got_slot = |index: usize| unsafe { got_base.offset((index * 8) as isize) };
+        for (index, target) in got.iter().enumerate() {
+            let address = self.target_address(base, target, namespace)?;
+            unsafe {
+                ptr::write_unaligned(got_slot(index) as *mut *const u8, address);
+            }
+        }
+        for reloc in relocs {
+            let target = if reloc.kind == "GOTPCRel4" {
+                // The displacement is relative to the GOT entry for the target.
+                match got.iter().position(|t| *t == reloc.target) {
+                    Some(index) => got_slot(index) as u64,
+                    None => {
+                        return Err(ModuleError::Backend(
+                            format!("{:?} has no GOT entry", reloc.target),
+                        ))
+                    }
+                }
+            } else {
+                self.target_address(base, &reloc.target, namespace)? as u64
+            };
+            let value = target.wrapping_add(reloc.addend as u64);
+            unsafe {
+                let site = base.offset(reloc.offset as isize);
+                match reloc.kind {
+                    "Abs8" => ptr::write_unaligned(site as *mut u64, value),
+                    "Abs4" if value <= u64::from(u32::max_value()) => {
+                        ptr::write_unaligned(site as *mut u32, value as u32)
+                    }
+                    // Calls go directly to the function, there is no PLT. GOT-relative
+                    // relocations point at the GOT entry, which was found above.
+                    "PCRel4" | "PLTRel4" | "GOTPCRel4" => {
+                        let disp = value.wrapping_sub(site as u64 + 4) as i64;
+                        if disp != i64::from(disp as i32) {
+                            return Err(ModuleError::Backend(format!(
+                                "{:?} is out of range for {}",
+                                reloc.target,
+                                reloc.kind
+                            )));
+                        }
+                        ptr::write_unaligned(site as *mut i32, disp as i32)
+                    }
+                    _ => {
+                        return Err(ModuleError::Backend(
+                            format!("can't apply {} relocation", reloc.kind),
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Backend for SimpleJitBackend {
+    type CompiledFunction = SimpleJitCompiledFunction;
+    type CompiledData = SimpleJitCompiledData;
+
+    /// The address of the function's code.
+    type FinalizedFunction = *const u8;
+
+    /// The address and size of the data object.
+    type FinalizedData = (*mut u8, usize);
+
+    type Product = SimpleJitProduct;
+
+    fn isa(&self) -> &TargetIsa {
+        &*self.isa
+    }
+
+    fn declare_function(&mut self, _name: &str, _linkage: Linkage) {
+        // Nothing to do.
+    }
+
+    fn declare_data(&mut self, _name: &str, _linkage: Linkage, _writable: bool) {
+        // Nothing to do.
+    }
+
+    fn define_function(
+        &mut self,
+        name: &str,
+        ctx: &Context,
+        _namespace: &ModuleNamespace<Self>,
+        code_size: u32,
+    ) -> ModuleResult<Self::CompiledFunction> {
+        let size = code_size as usize;
+        let mut mem = vec![0; size];
+        let relocs = emit_function(name, ctx, &*self.isa, &mut mem)?;
+
+        // Allocate a GOT entry for each distinct target of a GOT-relative relocation.
+        let mut got = Vec::new();
+        for reloc in &relocs {
+            if reloc.kind == "GOTPCRel4" && !got.contains(&reloc.target) {
+                got.push(reloc.target.clone());
+            }
+        }
+        let got_offset = (size + 7) & !7;
+        let code = self.allocate(got_offset + 8 * got.len())?;
+        unsafe {
+            ptr::copy_nonoverlapping(mem.as_ptr(), code, size);
+        }
+        Ok(SimpleJitCompiledFunction {
+            code,
+            relocs,
+            got,
+            got_offset,
+        })
+    }
+
+    fn define_data(
+        &mut self,
+        name: &str,
+        writable: bool,
+        data_ctx: &DataContext,
+        _namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<Self::CompiledData> {
+        let description = data_ctx.description();
+        let size = match description.init {
+            Init::Uninitialized => {
+                return Err(ModuleError::Backend(
+                    format!("data object {} has no initializer", name),
+                ))
+            }
+            Init::Zeros { size } => size,
+            Init::Bytes { ref contents } => contents.len(),
+        };
+        // The memory is page aligned, which is enough for any data object.
+        let storage = self.allocate(size)?;
+        if let Init::Bytes { ref contents } = description.init {
+            unsafe {
+                ptr::copy_nonoverlapping(contents.as_ptr(), storage, size);
+            }
+        }
+        Ok(SimpleJitCompiledData {
+            storage,
+            size,
+            writable,
+            relocs: data_relocs(description, &*self.isa),
+        })
+    }
+
+    fn finalize_function(
+        &mut self,
+        func: &Self::CompiledFunction,
+        namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<Self::FinalizedFunction> {
+        // The function may have been finalized before, so make it writable again first.
+        self.protect(func.code, libc::PROT_READ | libc::PROT_WRITE)?;
+        let got_base = unsafe { func.code.offset(func.got_offset as isize) };
+        self.relocate(func.code, &func.relocs, &func.got, got_base, namespace)?;
+        self.protect(func.code, libc::PROT_READ | libc::PROT_EXEC)?;
+        Ok(func.code)
+    }
+
+    fn finalize_data(
+        &mut self,
+        data: &Self::CompiledData,
+        namespace: &ModuleNamespace<Self>,
+    ) -> ModuleResult<Self::FinalizedData> {
+        self.protect(data.storage, libc::PROT_READ | libc::PROT_WRITE)?;
+        self.relocate(data.storage, &data.relocs, &[], data.storage, namespace)?;
+        if !data.writable {
+            self.protect(data.storage, libc::PROT_READ)?;
+        }
+        Ok((data.storage, data.size))
+    }
+
+    fn finish(self) -> Self::Product {
+        SimpleJitProduct { _memory: self.memory }
+    }
+}
@@ -0,0 +1,270 @@
+extern crate cretonne;
+extern crate cton_module;
+extern crate cton_native;
+extern crate cton_reader;
+
+use cretonne::Context;
+use cretonne::ir::{CallConv, Function, Signature, AbiParam};
+use cretonne::ir::types::{I32, I64};
+use cretonne::isa::{self, TargetIsa};
+use cretonne::result::CtonError;
+use cretonne::settings::{self, Configurable};
+use cton_module::*;
+use cton_reader::parse_functions;
+
+const FUNCTIONS: &str = "
+function %double(i32) -> i32 {
+ebb0(v0: i32):
+    v1 = iadd v0, v0
+    return v1
+}
+
+function %quad(i32) -> i32 {
+    fn0 = function %double(i32) -> i32
+
+ebb0(v0: i32):
+    v1 = call fn0(v0)
+    v2 = call fn0(v1)
+    return v2
+}
+
+function %table_entry() -> i64 {
+    gv0 = globalsym %table
+
+ebb0:
+    v0 = global_addr.i64 gv0
+    v1 = load.i64 v0
+    return v1
+}
+";
+
+fn intel_isa(pic: bool) -> Box<TargetIsa> {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("is_64bit").unwrap();
+    if pic {
+        flag_builder.enable("is_pic").unwrap();
+    }
+    isa::lookup("intel").unwrap().finish(
+        settings::Flags::new(&flag_builder),
+    )
+}
+
+fn int_sig() -> Signature {
+    let mut sig = Signature::new(CallConv::Native);
+    sig.params.push(AbiParam::new(I32));
+    sig.returns.push(AbiParam::new(I32));
+    sig
+}
+
+fn parse(name: &str) -> Function {
+    parse_functions(FUNCTIONS)
+        .unwrap()
+        .into_iter()
+        .find(|f| f.name.to_string() == name)
+        .unwrap()
+}
+
+/// Declare and define the test functions in `module`, and a data object pointing to `%double`.
+fn define_all<B: Backend>(module: &mut Module<B>) -> (FuncId, FuncId, FuncId, DataId) {
+    let double = module
+        .declare_function("double", Linkage::Local, &int_sig())
+        .unwrap();
+    let quad = module
+        .declare_function("quad", Linkage::Export, &int_sig())
+        .unwrap();
+    let mut entry_sig = Signature::new(CallConv::Native);
+    entry_sig.returns.push(AbiParam::new(I64));
+    let table_entry = module
+        .declare_function("table_entry", Linkage::Export, &entry_sig)
+        .unwrap();
+    let table = module.declare_data("table", Linkage::Export, false).unwrap();
+
+    for &(id, name) in &[
+        (double, "%double"),
+        (quad, "%quad"),
+        (table_entry, "%table_entry"),
+    ]
+    {
+        let mut ctx = Context::new();
+        ctx.func = parse(name);
+        module.define_function(id, &mut ctx).unwrap();
+    }
+
+    let mut data_ctx = DataContext::new();
+    data_ctx.define_zeroinit(8);
+    data_ctx.set_align(8);
+    let func_ref = module.declare_func_in_data(double, &mut data_ctx);
+    data_ctx.write_function_addr(0, func_ref);
+    module.define_data(table, &data_ctx).unwrap();
+
+    (double, quad, table_entry, table)
+}
+
+#[test]
+fn declarations() {
+    let mut module = Module::new(ObjectBackend::new(intel_isa(false)).unwrap());
+
+    let f = module
+        .declare_function("f", Linkage::Import, &int_sig())
+        .unwrap();
+    assert_eq!(
+        module.declare_function("f", Linkage::Export, &int_sig()).unwrap(),
+        f
+    );
+    assert_eq!(module.function_declaration(f).linkage, Linkage::Export);
+    assert_eq!(module.get_name("f"), Some(FuncOrDataId::Func(f)));
+    assert_eq!(module.get_name("g"), None);
+
+    let other_sig = Signature::new(CallConv::Native);
+    match module.declare_function("f", Linkage::Import, &other_sig) {
+        Err(ModuleError::IncompatibleSignature(ref name)) => assert_eq!(name, "f"),
+        _ => panic!("expected an incompatible signature"),
+    }
+    match module.declare_data("f", Linkage::Import, false) {
+        Err(ModuleError::IncompatibleDeclaration(ref name)) => assert_eq!(name, "f"),
+        _ => panic!("expected an incompatible declaration"),
+    }
+
+    let d = module.declare_data("d", Linkage::Import, false).unwrap();
+    let mut data_ctx = DataContext::new();
+    data_ctx.define_zeroinit(4);
+    match module.define_data(d, &data_ctx) {
+        Err(ModuleError::InvalidImportDefinition(ref name)) => assert_eq!(name, "d"),
+        _ => panic!("expected an invalid import definition"),
+    }
+    module.declare_data("d", Linkage::Local, true).unwrap();
+    assert!(module.data_declaration(d).writable);
+    module.define_data(d, &data_ctx).unwrap();
+    match module.define_data(d, &data_ctx) {
+        Err(ModuleError::DuplicateDefinition(ref name)) => assert_eq!(name, "d"),
+        _ => panic!("expected a duplicate definition"),
+    }
+
+    let mut func = Function::new();
+    let gv = module.declare_data_in_func(d, &mut func);
+    assert_eq!(func.global_vars[gv].to_string(), "globalsym %d");
+
+    match module.finalize_function(f) {
+        Err(ModuleError::Undefined(ref name)) => assert_eq!(name, "f"),
+        _ => panic!("expected an undefined function"),
+    }
+    let e = module.declare_data("e", Linkage::Local, false).unwrap();
+    match module.finalize_data(e) {
+        Err(ModuleError::Undefined(ref name)) => assert_eq!(name, "e"),
+        _ => panic!("expected an undefined data object"),
+    }
+}
+
+#[test]
+fn object_file() {
+    let mut module = Module::new(ObjectBackend::new(intel_isa(false)).unwrap());
+    define_all(&mut module);
+    let obj = module.finish().unwrap();
+
+    assert_eq!(&obj[0..4], b"\x7fELF");
+    for name in &["double", "quad", "table_entry", "table", ".rela.text", ".rela.data"] {
+        let mut symbol = name.as_bytes().to_vec();
+        symbol.push(0);
+        assert!(
+            obj.windows(symbol.len()).any(|w| w == &symbol[..]),
+            "missing {}",
+            name
+        );
+    }
+}
+
+/// Read a little-endian integer of `len` bytes at `offset` in `obj`.
+fn read_le(obj: &[u8], offset: usize, len: usize) -> u64 {
+    obj[offset..offset + len].iter().rev().fold(
+        0,
+        |acc, &b| acc << 8 | u64::from(b),
+    )
+}
+
+/// Get the types of all the relocations in the `SHT_RELA` sections of an ELF64 object file.
+fn rela_types(obj: &[u8]) -> Vec<u32> {
+    let shoff = read_le(obj, 0x28, 8) as usize;
+    let shentsize = read_le(obj, 0x3a, 2) as usize;
+    let shnum = read_le(obj, 0x3c, 2) as usize;
+    let mut types = Vec::new();
+    for i in 0..shnum {
+        let sh = shoff + i * shentsize;
+        // Only look at `SHT_RELA` sections.
+        if read_le(obj, sh + 4, 4) != 4 {
+            continue;
+        }
+        let offset = read_le(obj, sh + 0x18, 8) as usize;
+        let size = read_le(obj, sh + 0x20, 8) as usize;
+        for rela in obj[offset..offset + size].chunks(24) {
+            types.push(read_le(rela, 8, 4) as u32);
+        }
+    }
+    types
+}
+
+#[test]
+fn pic_object_file() {
+    const R_X86_64_PLT32: u32 = 4;
+    const R_X86_64_GOTPCREL: u32 = 9;
+
+    let mut module = Module::new(ObjectBackend::new(intel_isa(true)).unwrap());
+    define_all(&mut module);
+    let obj = module.finish().unwrap();
+    assert_eq!(&obj[0..4], b"\x7fELF");
+
+    let types = rela_types(&obj);
+    assert!(types.contains(&R_X86_64_PLT32), "no PLT32 in {:?}", types);
+    assert!(types.contains(&R_X86_64_GOTPCREL), "no GOTPCREL in {:?}", types);
+}
+
+#[test]
+fn pic_requires_64bit() {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("is_pic").unwrap();
+    let isa = isa::lookup("intel").unwrap().finish(
+        settings::Flags::new(&flag_builder),
+    );
+    let mut ctx = Context::new();
+    ctx.func = parse("%double");
+    assert_eq!(ctx.compile(&*isa), Err(CtonError::Unsupported));
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+fn simplejit_test(pic: bool) {
+    use std::mem;
+
+    let isa = {
+        let (mut flag_builder, isa_builder) = cton_native::builders().unwrap();
+        if pic {
+            flag_builder.enable("is_pic").unwrap();
+        }
+        isa_builder.finish(settings::Flags::new(&flag_builder))
+    };
+    let mut module = Module::new(SimpleJitBackend::new(isa));
+    let (double, quad, table_entry, table) = define_all(&mut module);
+
+    let quad_ptr = module.finalize_function(quad).unwrap();
+    let table_entry_ptr = module.finalize_function(table_entry).unwrap();
+    let double_ptr = module.finalize_function(double).unwrap();
+    let (table_ptr, table_size) = module.finalize_data(table).unwrap();
+    let _product = module.finish().unwrap();
+
+    let quad_fn: extern "C" fn(i32) -> i32 = unsafe { mem::transmute(quad_ptr) };
+    assert_eq!(quad_fn(5), 20);
+    assert_eq!(table_size, 8);
+    assert_eq!(unsafe { *(table_ptr as *const *const u8) }, double_ptr);
+    let table_entry_fn: extern "C" fn() -> *const u8 = unsafe { mem::transmute(table_entry_ptr) };
+    assert_eq!(table_entry_fn(), double_ptr);
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+#[test]
+fn simplejit() {
+    simplejit_test(false);
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+#[test]
+fn simplejit_pic() {
+    simplejit_test(true);
+}
@@ -6,6 +6,7 @@
 extern crate cretonne;
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[macro_use]
 extern crate raw_cpuid;
 
 use cretonne::isa;
@@ -72,11 +73,14 @@ fn parse_x86_cpuid(isa_builder: &mut isa::Builder) {
             isa_builder.enable("has_avx").unwrap();
         }
     }
-    if let Some(info) = cpuid.get_extended_feature_info() {
-        if info.has_bmi1() {
+    // Query leaf 7 directly: `CpuId::get_extended_feature_info()` asserts that only one sub-leaf
+    // exists, which doesn't hold on newer processors.
+    if cpuid!(0).eax >= 7 {
+        let ebx = cpuid!(7, 0).ebx;
+        if ebx & (1 << 3) != 0 {
             isa_builder.enable("has_bmi1").unwrap();
         }
-        if info.has_bmi2() {
+        if ebx & (1 << 8) != 0 {
             isa_builder.enable("has_bmi2").unwrap();
         }
     }
@@ -28,6 +28,7 @@ pub enum Token<'a> {
     Minus, // '-'
     Comma, // ','
     Dot, // '.'
+    Ellipsis, // '...'
     Colon, // ':'
     Equal, // '='
     Arrow, // '->'
@@ -348,6 +349,8 @@ impl<'a> Lexer<'a> {
             "b16" => types::B16,
             "b32" => types::B32,
             "b64" => types::B64,
+            "r32" if !is_vector => types::R32,
+            "r64" if !is_vector => types::R64,
             _ => return None,
         };
         if is_vector {
@@ -425,7 +428,13 @@ impl<'a> Lexer<'a> {
                 Some('[') => Some(self.scan_char(Token::LBracket)),
                 Some(']') => Some(self.scan_char(Token::RBracket)),
                 Some(',') => Some(self.scan_char(Token::Comma)),
-                Some('.') => Some(self.scan_char(Token::Dot)),
+                Some('.') => {
+                    if self.looking_at("...") {
+                        Some(self.scan_chars(3, Token::Ellipsis))
+                    } else {
+                        Some(self.scan_char(Token::Dot))
+                    }
+                }
                 Some(':') => Some(self.scan_char(Token::Colon)),
                 Some('=') => Some(self.scan_char(Token::Equal)),
                 Some('+') => Some(self.scan_number()),
@@ -526,7 +535,7 @@ mod tests {
 
     #[test]
     fn lex_chars() {
-        let mut lex = Lexer::new("(); hello\n = :{, }.");
+        let mut lex = Lexer::new("(); hello\n = :{, }. ...");
         assert_eq!(lex.next(), token(Token::LPar, 1));
         assert_eq!(lex.next(), token(Token::RPar, 1));
         assert_eq!(lex.next(), token(Token::Comment("; hello"), 1));
@@ -536,6 +545,7 @@ mod tests {
         assert_eq!(lex.next(), token(Token::Comma, 2));
         assert_eq!(lex.next(), token(Token::RBrace, 2));
         assert_eq!(lex.next(), token(Token::Dot, 2));
+        assert_eq!(lex.next(), token(Token::Ellipsis, 2));
         assert_eq!(lex.next(), None);
     }
 
@@ -12,7 +12,8 @@ use std::mem;
 use cretonne::ir::{Function, Ebb, Opcode, Value, Type, FunctionName, CallConv, StackSlotData,
                    JumpTable, JumpTableData, Signature, AbiParam, ArgumentExtension, ExtFuncData,
                    SigRef, FuncRef, StackSlot, ValueLoc, ArgumentLoc, MemFlags, GlobalVar,
-                   GlobalVarData, Heap, HeapData, HeapStyle, HeapBase};
+                   GlobalVarData, Heap, HeapData, HeapStyle, HeapBase, ArgumentPurpose,
+                   StructArg};
 use cretonne::ir;
 use cretonne::ir::types::VOID;
 use cretonne::ir::immediates::{Imm64, Uimm32, Offset32, Ieee32, Ieee64};
@@ -928,9 +929,9 @@ impl<'a> Parser<'a> {
             Token::LPar,
             "expected function signature: ( args... )",
         )?;
-        // signature ::=  "(" * [abi-param-list] ")" ["->" retlist] [callconv]
+        // signature ::=  "(" * [param-list] ")" ["->" retlist] [callconv]
         if self.token() != Some(Token::RPar) {
-            sig.params = self.parse_abi_param_list(unique_isa)?;
+            self.parse_param_list(&mut sig, unique_isa)?;
         }
         self.match_token(
             Token::RPar,
@@ -958,6 +959,31 @@ impl<'a> Parser<'a> {
         Ok(sig)
     }
 
+    // Parse the list of function parameters, which may contain a '...' separating the fixed
+    // parameters from the variadic ones.
+    //
+    // param-list ::= * ( abi-param | "..." ) { "," ( abi-param | "..." ) }
+    //
+    fn parse_param_list(
+        &mut self,
+        sig: &mut Signature,
+        unique_isa: Option<&TargetIsa>,
+    ) -> Result<()> {
+        loop {
+            if self.optional(Token::Ellipsis) {
+                if sig.fixed_params.is_some() {
+                    return err!(self.loc, "multiple '...' in signature");
+                }
+                sig.fixed_params = Some(sig.params.len());
+            } else {
+                sig.params.push(self.parse_abi_param(unique_isa)?);
+            }
+            if !self.optional(Token::Comma) {
+                return Ok(());
+            }
+        }
+    }
+
     // Parse list of function parameter / return value types.
     //
     // paramlist ::= * param { "," param }
@@ -987,6 +1013,16 @@ impl<'a> Parser<'a> {
             match s {
                 "uext" => arg.extension = ArgumentExtension::Uext,
                 "sext" => arg.extension = ArgumentExtension::Sext,
+                "sarg" | "spart" => {
+                    self.consume();
+                    let sa = self.parse_struct_arg()?;
+                    arg.purpose = if s == "sarg" {
+                        ArgumentPurpose::StructArgument(sa)
+                    } else {
+                        ArgumentPurpose::StructPart(sa)
+                    };
+                    continue;
+                }
                 _ => {
                     if let Ok(purpose) = s.parse() {
                         arg.purpose = purpose;
@@ -1004,6 +1040,26 @@ impl<'a> Parser<'a> {
         Ok(arg)
     }
 
+    // Parse the layout of a struct argument.
+    //
+    // struct-arg ::= "(" uimm32 "," uimm32 [ "," uimm8 ] ")"
+    //
+    fn parse_struct_arg(&mut self) -> Result<StructArg> {
+        self.match_token(Token::LPar, "expected '(' before struct layout")?;
+        let size = self.match_uimm32("expected struct size")?.into();
+        self.match_token(Token::Comma, "expected ',' after struct size")?;
+        let align: u32 = self.match_uimm32("expected struct alignment")?.into();
+        if !align.is_power_of_two() {
+            return err!(self.loc, "struct alignment must be a power of two");
+        }
+        let mut sa = StructArg::new(size, align);
+        if self.optional(Token::Comma) {
+            sa.float_words = self.match_uimm8("expected floating point word mask")?;
+        }
+        self.match_token(Token::RPar, "expected ')' after struct layout")?;
+        Ok(sa)
+    }
+
     // Parse an argument location specifier; either a register or a byte offset into the stack.
     fn parse_argument_location(&mut self, unique_isa: Option<&TargetIsa>) -> Result<ArgumentLoc> {
         // argumentloc ::= '[' regname | uimm32 ']'
@@ -1142,6 +1198,7 @@ impl<'a> Parser<'a> {
     // global-var-decl ::= * GlobalVar(gv) "=" global-var-desc
     // global-var-desc ::= "vmctx" offset32
     //                   | "deref" "(" GlobalVar(base) ")" offset32
+    //                   | "globalsym" name
     //
     fn parse_global_var_decl(&mut self) -> Result<(u32, GlobalVarData)> {
         let number = self.match_gv("expected global variable number: gv«n»")?;
@@ -1168,6 +1225,10 @@ impl<'a> Parser<'a> {
                 let offset = self.optional_offset32()?;
                 GlobalVarData::Deref { base, offset }
             }
+            "globalsym" => {
+                let name = self.parse_function_name()?;
+                GlobalVarData::Sym { name }
+            }
             other => return err!(self.loc, "Unknown global variable kind '{}'", other),
         };
 
@@ -1860,6 +1921,7 @@ impl<'a> Parser<'a> {
         opcode: Opcode,
     ) -> Result<InstructionData> {
         let idata = match opcode.format() {
+            InstructionFormat::NullAry => InstructionData::NullAry { opcode },
             InstructionFormat::Unary => {
                 InstructionData::Unary {
                     opcode,
@@ -2138,6 +2200,33 @@ impl<'a> Parser<'a> {
                     args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
                 }
             }
+            InstructionFormat::Invoke => {
+                let func_ref = self.match_fn("expected function reference").and_then(
+                    |num| {
+                        ctx.get_fn(num, &self.loc)
+                    },
+                )?;
+                self.match_token(
+                    Token::LPar,
+                    "expected '(' before arguments",
+                )?;
+                let args = self.parse_value_list()?;
+                self.match_token(
+                    Token::RPar,
+                    "expected ')' after arguments",
+                )?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let ebb_num = self.match_ebb("expected landing pad EBB")?;
+                InstructionData::Invoke {
+                    opcode,
+                    func_ref,
+                    destination: ebb_num,
+                    args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
+                }
+            }
             InstructionFormat::IndirectCall => {
                 let sig_ref = self.match_sig("expected signature reference").and_then(
                     |num| {
@@ -2422,6 +2511,40 @@ mod tests {
                 .to_string(),
             "1: expected ')' after function arguments"
         );
+
+        // Struct arguments.
+        assert_eq!(
+            Parser::new("(i64 sarg(24, 8), f64 spart(16, 8, 1) [4])")
+                .parse_signature(None)
+                .unwrap()
+                .to_string(),
+            "(i64 sarg(24, 8), f64 spart(16, 8, 1) [4]) native"
+        );
+        assert_eq!(
+            Parser::new("(i64 sarg(24, 3))")
+                .parse_signature(None)
+                .unwrap_err()
+                .to_string(),
+            "1: struct alignment must be a power of two"
+        );
+
+        // Variadic signatures.
+        let sig3 = Parser::new("(i64, ..., f64) -> i32")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(sig3.fixed_params, Some(1));
+        assert_eq!(sig3.to_string(), "(i64, ..., f64) -> i32 native");
+        assert_eq!(
+            Parser::new("(...)").parse_signature(None).unwrap().to_string(),
+            "(...) native"
+        );
+        assert_eq!(
+            Parser::new("(i8, ..., ...)")
+                .parse_signature(None)
+                .unwrap_err()
+                .to_string(),
+            "1: multiple '...' in signature"
+        );
     }
 
     #[test]
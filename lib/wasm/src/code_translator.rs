@@ -27,23 +27,27 @@ use cretonne::ir::types::*;
 use cretonne::ir::condcodes::{IntCC, FloatCC};
 use cton_frontend::FunctionBuilder;
 use wasmparser::{Operator, MemoryImmediate};
-use translation_utils::{f32_translation, f64_translation, type_to_type, num_return_values, Local};
-use translation_utils::{TableIndex, SignatureIndex, FunctionIndex, MemoryIndex};
+use translation_utils::{f32_translation, f64_translation, blocktype_params_results, Local};
+use translation_utils::{TableIndex, SignatureIndex, FunctionIndex, MemoryIndex, DataIndex};
+use ext_operators::ExtOperator;
 use state::{TranslationState, ControlStackFrame};
 use std::collections::HashMap;
 use environ::{FuncEnvironment, GlobalValue};
 use std::u32;
+use cretonne::result::CtonResult;
 
-/// Translates wasm operators into Cretonne IL instructions. Returns `true` if it inserted
-/// a return.
+/// Translates wasm operators into Cretonne IL instructions.
+///
+/// Returns `CtonError::Unsupported` for block types that can't be translated.
 pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
     op: &Operator,
     builder: &mut FunctionBuilder<Local>,
     state: &mut TranslationState,
     environ: &mut FE,
-) {
+) -> CtonResult {
     if state.in_unreachable_code() {
-        return translate_unreachable_operator(op, builder, state);
+        translate_unreachable_operator(op, builder, state);
+        return Ok(());
     }
 
     // This big match treats all Wasm code operators.
@@ -117,38 +121,25 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
          *  the destination block (since `br` instructions targeting it only appear inside the
          *  block and have already been translated) and modify the value stack to use the
          *  possible `Ebb`'s arguments values.
+         *
+         *  A block can take parameters from the value stack. They stay on the stack for the
+         *  body of a `block`, while a `loop` receives them as arguments of its header `Ebb` so
+         *  the branches back to the header can pass new values.
          ***********************************************************************************/
         Operator::Block { ty } => {
-            let next = builder.create_ebb();
-            if let Ok(ty_cre) = type_to_type(&ty) {
-                builder.append_ebb_param(next, ty_cre);
-            }
-            state.push_block(next, num_return_values(ty));
+            let (params, results) = blocktype_params_results(ty)?;
+            translate_block(params, results, builder, state);
         }
         Operator::Loop { ty } => {
-            let loop_body = builder.create_ebb();
-            let next = builder.create_ebb();
-            if let Ok(ty_cre) = type_to_type(&ty) {
-                builder.append_ebb_param(next, ty_cre);
-            }
-            builder.ins().jump(loop_body, &[]);
-            state.push_loop(loop_body, next, num_return_values(ty));
-            builder.switch_to_block(loop_body, &[]);
+            let (params, results) = blocktype_params_results(ty)?;
+            translate_loop(params, results, builder, state);
+            // Every back-edge of the loop goes through the header, so this check runs once per
+            // iteration.
+            environ.translate_interrupt_check(builder);
         }
         Operator::If { ty } => {
-            let val = state.pop1();
-            let if_not = builder.create_ebb();
-            let jump_inst = builder.ins().brz(val, if_not, &[]);
-            // Here we append an argument to an Ebb targeted by an argumentless jump instruction
-            // But in fact there are two cases:
-            // - either the If does not have a Else clause, in that case ty = EmptyBlock
-            //   and we add nothing;
-            // - either the If have an Else clause, in that case the destination of this jump
-            //   instruction will be changed later when we translate the Else operator.
-            if let Ok(ty_cre) = type_to_type(&ty) {
-                builder.append_ebb_param(if_not, ty_cre);
-            }
-            state.push_if(jump_inst, if_not, num_return_values(ty));
+            let (params, results) = blocktype_params_results(ty)?;
+            translate_if(params, results, builder, state);
         }
         Operator::Else => {
             // We take the control frame pushed by the if, use its ebb as the else body
@@ -166,11 +157,8 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             };
             builder.ins().jump(destination, state.peekn(return_count));
             state.popn(return_count);
-            // We change the target of the branch instruction
-            let else_ebb = builder.create_ebb();
-            builder.change_jump_destination(branch_inst, else_ebb);
-            builder.seal_block(else_ebb);
-            builder.switch_to_block(else_ebb, &[]);
+            let else_params = switch_to_else_ebb(builder, branch_inst, destination);
+            state.stack.extend_from_slice(else_params);
         }
         Operator::End => {
             let frame = state.control_stack.pop().unwrap();
@@ -220,12 +208,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 let frame = &mut state.control_stack[i];
                 // We signal that all the code that follows until the next End is unreachable
                 frame.set_reachable();
-                let return_count = if frame.is_loop() {
-                    0
-                } else {
-                    frame.num_return_values()
-                };
-                (return_count, frame.br_destination())
+                (frame.num_br_args(), frame.br_destination())
             };
             builder.ins().jump(
                 br_destination,
@@ -242,12 +225,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 // The values returned by the branch are still available for the reachable
                 // code that comes after it
                 frame.set_reachable();
-                let return_count = if frame.is_loop() {
-                    0
-                } else {
-                    frame.num_return_values()
-                };
-                (return_count, frame.br_destination())
+                (frame.num_br_args(), frame.br_destination())
             };
             builder.ins().brnz(
                 val,
@@ -265,12 +243,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             }
             let jump_args_count = {
                 let i = state.control_stack.len() - 1 - (min_depth as usize);
-                let min_depth_frame = &state.control_stack[i];
-                if min_depth_frame.is_loop() {
-                    0
-                } else {
-                    min_depth_frame.num_return_values()
-                }
+                state.control_stack[i].num_br_args()
             };
             if jump_args_count == 0 {
                 // No jump arguments
@@ -825,6 +798,124 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             state.push1(builder.ins().bint(I32, val));
         }
     }
+    Ok(())
+}
+
+/// Translates an operator from a WebAssembly extension that `wasmparser` doesn't decode.
+///
+/// Returns `CtonError::Unsupported` if the environment doesn't support the operator.
+pub fn translate_ext_operator<FE: FuncEnvironment + ?Sized>(
+    op: ExtOperator,
+    builder: &mut FunctionBuilder<Local>,
+    state: &mut TranslationState,
+    environ: &mut FE,
+) -> CtonResult {
+    // Blocks in unreachable code still need a phantom frame so the matching `end` is found. None
+    // of the other extension operators affect the control stack.
+    if state.in_unreachable_code() {
+        match op {
+            ExtOperator::Block { .. } |
+            ExtOperator::Loop { .. } |
+            ExtOperator::If { .. } => state.phantom_unreachable_stack_depth += 1,
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    match op {
+        /******************************* Sign extension ************************************
+         *  Sign-extend the low bits of an integer in place.
+         ***********************************************************************************/
+        ExtOperator::I32Extend8S => {
+            let val = state.pop1();
+            let val = builder.ins().ireduce(I8, val);
+            state.push1(builder.ins().sextend(I32, val));
+        }
+        ExtOperator::I32Extend16S => {
+            let val = state.pop1();
+            let val = builder.ins().ireduce(I16, val);
+            state.push1(builder.ins().sextend(I32, val));
+        }
+        ExtOperator::I64Extend8S => {
+            let val = state.pop1();
+            let val = builder.ins().ireduce(I8, val);
+            state.push1(builder.ins().sextend(I64, val));
+        }
+        ExtOperator::I64Extend16S => {
+            let val = state.pop1();
+            let val = builder.ins().ireduce(I16, val);
+            state.push1(builder.ins().sextend(I64, val));
+        }
+        ExtOperator::I64Extend32S => {
+            let val = state.pop1();
+            let val = builder.ins().ireduce(I32, val);
+            state.push1(builder.ins().sextend(I64, val));
+        }
+        /******************************* Bulk memory ***************************************
+         *  The heap regions are bounds checked here, and the operation itself is handled by
+         *  the environment. The memory index immediate is reserved, so it is always 0.
+         ***********************************************************************************/
+        ExtOperator::MemoryCopy => {
+            let heap = state.get_heap(builder.func, 0, environ);
+            let (dst, src, len) = state.pop3();
+            translate_bounds_check(heap, dst, len, builder, environ);
+            translate_bounds_check(heap, src, len, builder, environ);
+            environ.translate_memory_copy(builder.cursor(), 0, heap, dst, src, len)?;
+        }
+        ExtOperator::MemoryFill => {
+            let heap = state.get_heap(builder.func, 0, environ);
+            let (dst, val, len) = state.pop3();
+            translate_bounds_check(heap, dst, len, builder, environ);
+            environ.translate_memory_fill(builder.cursor(), 0, heap, dst, val, len)?;
+        }
+        ExtOperator::MemoryInit { segment } => {
+            let heap = state.get_heap(builder.func, 0, environ);
+            let (dst, src, len) = state.pop3();
+            translate_bounds_check(heap, dst, len, builder, environ);
+            environ.translate_memory_init(
+                builder.cursor(),
+                0,
+                heap,
+                segment as DataIndex,
+                dst,
+                src,
+                len,
+            )?;
+        }
+        ExtOperator::DataDrop { segment } => {
+            environ.translate_data_drop(builder.cursor(), segment as DataIndex)?;
+        }
+        /****************************** Multi-value blocks *********************************
+         *  Blocks whose parameter and result types are given by a function type. They are
+         *  translated like the MVP blocks.
+         ***********************************************************************************/
+        ExtOperator::Block { sig } => {
+            let (params, results) = signature_params_results(sig, environ);
+            translate_block(&params, &results, builder, state);
+        }
+        ExtOperator::Loop { sig } => {
+            let (params, results) = signature_params_results(sig, environ);
+            translate_loop(&params, &results, builder, state);
+            environ.translate_interrupt_check(builder);
+        }
+        ExtOperator::If { sig } => {
+            let (params, results) = signature_params_results(sig, environ);
+            translate_if(&params, &results, builder, state);
+        }
+    }
+    Ok(())
+}
+
+/// Get the parameter and result types of a block whose type is the function type `sig`.
+fn signature_params_results<FE: FuncEnvironment + ?Sized>(
+    sig: u32,
+    environ: &FE,
+) -> (Vec<ir::Type>, Vec<ir::Type>) {
+    let sig = environ.get_signature(sig as SignatureIndex);
+    (
+        sig.params.iter().map(|param| param.value_type).collect(),
+        sig.returns.iter().map(|ret| ret.value_type).collect(),
+    )
 }
 
 /// Deals with a Wasm instruction located in an unreachable portion of the code. Most of them
@@ -887,23 +978,21 @@ fn translate_unreachable_operator(
             } else {
                 // Encountering an real else means that the code in the else
                 // clause is reachable again
-                let (branch_inst, original_stack_size) = match control_stack[control_stack.len() -
-                                                                                   1] {
-                    ControlStackFrame::If {
-                        branch_inst,
-                        original_stack_size,
-                        ..
-                    } => (branch_inst, original_stack_size),
-                    _ => panic!("should not happen"),
-                };
-                // We change the target of the branch instruction
-                let else_ebb = builder.create_ebb();
-                builder.change_jump_destination(branch_inst, else_ebb);
-                builder.seal_block(else_ebb);
-                builder.switch_to_block(else_ebb, &[]);
+                let (destination, branch_inst, original_stack_size) =
+                    match control_stack[control_stack.len() - 1] {
+                        ControlStackFrame::If {
+                            destination,
+                            branch_inst,
+                            original_stack_size,
+                            ..
+                        } => (destination, branch_inst, original_stack_size),
+                        _ => panic!("should not happen"),
+                    };
                 // Now we have to split off the stack the values not used
                 // by unreachable code that hasn't been translated
                 stack.truncate(original_stack_size);
+                let else_params = switch_to_else_ebb(builder, branch_inst, destination);
+                stack.extend_from_slice(else_params);
                 state.real_unreachable_stack_depth = 0;
             }
         }
@@ -913,6 +1002,141 @@ fn translate_unreachable_operator(
     }
 }
 
+/// Translate a `block` with the given parameter and result types.
+///
+/// The parameters stay on the value stack for the body of the block.
+fn translate_block(
+    params: &[ir::Type],
+    results: &[ir::Type],
+    builder: &mut FunctionBuilder<Local>,
+    state: &mut TranslationState,
+) {
+    let next = builder.create_ebb();
+    for &ty in results {
+        builder.append_ebb_param(next, ty);
+    }
+    state.push_block(next, params.len(), results.len());
+}
+
+/// Translate a `loop` with the given parameter and result types.
+///
+/// The parameters are passed as arguments to the loop header, so the branches back to the header
+/// can pass new values. This leaves the builder positioned in the header.
+fn translate_loop(
+    params: &[ir::Type],
+    results: &[ir::Type],
+    builder: &mut FunctionBuilder<Local>,
+    state: &mut TranslationState,
+) {
+    let loop_body = builder.create_ebb();
+    let next = builder.create_ebb();
+    for &ty in results {
+        builder.append_ebb_param(next, ty);
+    }
+    builder.ins().jump(loop_body, state.peekn(params.len()));
+    state.push_loop(loop_body, next, params.len(), results.len());
+    builder.switch_to_block(loop_body, state.peekn(params.len()));
+    state.popn(params.len());
+    state.stack.extend_from_slice(builder.ebb_params(loop_body));
+}
+
+/// Translate an `if` with the given parameter and result types. The condition is on top of the
+/// value stack, above the parameters.
+fn translate_if(
+    params: &[ir::Type],
+    results: &[ir::Type],
+    builder: &mut FunctionBuilder<Local>,
+    state: &mut TranslationState,
+) {
+    let val = state.pop1();
+    let if_not = builder.create_ebb();
+    for &ty in results {
+        builder.append_ebb_param(if_not, ty);
+    }
+    // The parameters of the If are passed to whichever clause is executed. There are two
+    // cases:
+    // - the parameter and result types are the same, and the If may not have an Else
+    //   clause. We branch directly to the code after the If with the parameters as
+    //   results. If we find an Else clause, the destination of this branch will be
+    //   changed when we translate the Else operator;
+    // - the types are different, so there must be an Else clause and we create its
+    //   `Ebb` right away, with the parameters as its arguments.
+    let jump_inst = if params == results {
+        builder.ins().brz(val, if_not, state.peekn(params.len()))
+    } else {
+        let else_ebb = builder.create_ebb();
+        for &ty in params {
+            builder.append_ebb_param(else_ebb, ty);
+        }
+        let jump_inst = builder.ins().brz(val, else_ebb, state.peekn(params.len()));
+        builder.seal_block(else_ebb);
+        jump_inst
+    };
+    state.push_if(jump_inst, if_not, params.len(), results.len());
+}
+
+/// Switch to the `Ebb` holding the `else` clause of an `if` and return its parameters.
+///
+/// The `if` was translated into the conditional branch `branch_inst`. If it goes directly to the
+/// `destination` following the `if`, we create the `else` clause `Ebb` and redirect the branch to
+/// it.
+fn switch_to_else_ebb<'a>(
+    builder: &'a mut FunctionBuilder<Local>,
+    branch_inst: ir::Inst,
+    destination: Ebb,
+) -> &'a [ir::Value] {
+    let else_ebb = match builder.func.dfg[branch_inst].branch_destination() {
+        Some(ebb) if ebb != destination => ebb,
+        _ => {
+            // We change the target of the branch instruction
+            let else_ebb = builder.create_ebb();
+            let num_params = builder.func.dfg.inst_variable_args(branch_inst).len();
+            for i in 0..num_params {
+                let param = builder.func.dfg.inst_variable_args(branch_inst)[i];
+                let ty = builder.func.dfg.value_type(param);
+                builder.append_ebb_param(else_ebb, ty);
+            }
+            builder.change_jump_destination(branch_inst, else_ebb);
+            builder.seal_block(else_ebb);
+            else_ebb
+        }
+    };
+    builder.switch_to_block(else_ebb, &[])
+}
+
+/// Trap with `HeapOutOfBounds` unless the `len` bytes starting at the heap offset `offset` are
+/// inside the bound of `heap`. Both `offset` and `len` have type `i32`.
+fn translate_bounds_check<FE: FuncEnvironment + ?Sized>(
+    heap: ir::Heap,
+    offset: ir::Value,
+    len: ir::Value,
+    builder: &mut FunctionBuilder<Local>,
+    environ: &mut FE,
+) {
+    // The bound of a dynamic heap is stored with the type of the heap offsets, just like the
+    // `heap_addr` legalization expects.
+    let offset_ty = builder.func.dfg.value_type(offset);
+
+    // Compute the end of the region with 64-bit arithmetic so it can't wrap around.
+    let offset = builder.ins().uextend(I64, offset);
+    let len = builder.ins().uextend(I64, len);
+    let end = builder.ins().iadd(offset, len);
+    let bound = match builder.func.heaps[heap].style {
+        ir::HeapStyle::Static { bound } => builder.ins().iconst(I64, bound),
+        ir::HeapStyle::Dynamic { bound_gv } => {
+            let addr = builder.ins().global_addr(environ.native_pointer(), bound_gv);
+            let bound = builder.ins().load(offset_ty, MemFlags::new(), addr, 0);
+            if offset_ty == I64 {
+                bound
+            } else {
+                builder.ins().uextend(I64, bound)
+            }
+        }
+    };
+    let oob = builder.ins().icmp(IntCC::UnsignedGreaterThan, end, bound);
+    builder.ins().trapnz(oob, ir::TrapCode::HeapOutOfBounds);
+}
+
 // Get the address+offset to use for a heap access.
 fn get_heap_addr(
     heap: ir::Heap,
@@ -998,3 +1222,117 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
         base,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use cretonne::ir::{self, InstBuilder};
+    use cretonne::ir::types::*;
+    use cretonne::verifier::verify_function;
+    use cton_frontend::{ILBuilder, FunctionBuilder};
+    use environ::{DummyEnvironment, FuncEnvironment};
+    use state::TranslationState;
+    use translation_utils::Local;
+    use wasmparser::Operator;
+    use super::{translate_operator, translate_block, translate_loop, translate_if};
+
+    /// Build a function with the signature `params -> results` whose arguments are on the value
+    /// stack when `body` runs. The final `end` is translated after `body` and returns the values
+    /// left on the stack.
+    ///
+    /// The blocks are translated directly so the tests don't depend on how the block types are
+    /// decoded. The `multi_value.wasm` module in `wasmtests` covers the decoding.
+    fn build<F>(params: &[ir::Type], results: &[ir::Type], body: F) -> ir::Function
+    where
+        F: FnOnce(&mut FunctionBuilder<Local>, &mut TranslationState),
+    {
+        let mut sig = ir::Signature::new(ir::CallConv::Native);
+        sig.params.extend(params.iter().map(|&ty| ir::AbiParam::new(ty)));
+        sig.returns.extend(results.iter().map(|&ty| ir::AbiParam::new(ty)));
+        let mut func = ir::Function::with_name_signature(ir::FunctionName::new("multi"), sig);
+        let runtime = DummyEnvironment::default();
+        let mut il_builder = ILBuilder::new();
+        let mut state = TranslationState::new();
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut il_builder);
+            let entry = builder.create_ebb();
+            builder.switch_to_block(entry, &[]);
+            builder.seal_block(entry);
+            let exit = builder.create_ebb();
+            state.initialize(&builder.func.signature, exit);
+            for i in 0..params.len() {
+                let arg = builder.arg_value(i);
+                state.push1(arg);
+            }
+
+            body(&mut builder, &mut state);
+
+            translate_operator(&Operator::End, &mut builder, &mut state, &mut runtime.func_env())
+                .unwrap();
+            builder.ins().return_(&state.stack);
+        }
+        if let Err(err) = verify_function(&func, runtime.func_env().flags()) {
+            panic!("{}\n{}", func.display(None), err);
+        }
+        func
+    }
+
+    #[test]
+    fn if_else_with_params() {
+        // (param i32 i32) (result i64)
+        // (if (param i32) (result i64) (get_local 1)
+        //     (then (i64.extend_u/i32))
+        //     (else (i64.extend_s/i32)))
+        let func = build(&[I32, I32], &[I64], |builder, state| {
+            let runtime = DummyEnvironment::default();
+            let mut environ = runtime.func_env();
+            translate_if(&[I32], &[I64], builder, state);
+            let x = state.pop1();
+            state.push1(builder.ins().uextend(I64, x));
+            translate_operator(&Operator::Else, builder, state, &mut environ).unwrap();
+            let x = state.pop1();
+            state.push1(builder.ins().sextend(I64, x));
+            translate_operator(&Operator::End, builder, state, &mut environ).unwrap();
+        });
+
+        // The `else` clause receives the parameter as an `Ebb` argument.
+        let text = func.display(None).to_string();
+        assert!(text.contains("brz v1, ebb3(v0)"), "{}", text);
+        assert!(text.contains("ebb3(v3: i32):"), "{}", text);
+    }
+
+    #[test]
+    fn if_without_else_with_params() {
+        // (param i32 i32) (result i32)
+        // (if (param i32) (result i32) (get_local 1) (then (i32.const 1) (i32.add)))
+        build(&[I32, I32], &[I32], |builder, state| {
+            let runtime = DummyEnvironment::default();
+            let mut environ = runtime.func_env();
+            translate_if(&[I32], &[I32], builder, state);
+            let x = state.pop1();
+            state.push1(builder.ins().iadd_imm(x, 1));
+            translate_operator(&Operator::End, builder, state, &mut environ).unwrap();
+        });
+    }
+
+    #[test]
+    fn block_and_loop_with_params() {
+        // (param i32) (result i32)
+        // (block (param i32) (result i32)
+        //     (loop (param i32) (result i32)
+        //         (i32.const -1) (i32.add) (tee_local 1) (get_local 1) (br_if 0)))
+        build(&[I32], &[I32], |builder, state| {
+            let runtime = DummyEnvironment::default();
+            let mut environ = runtime.func_env();
+            translate_block(&[I32], &[I32], builder, state);
+            translate_loop(&[I32], &[I32], builder, state);
+            let x = state.pop1();
+            let x = builder.ins().iadd_imm(x, -1);
+            state.push1(x);
+            state.push1(x);
+            translate_operator(&Operator::BrIf { relative_depth: 0 }, builder, state, &mut environ)
+                .unwrap();
+            translate_operator(&Operator::End, builder, state, &mut environ).unwrap();
+            translate_operator(&Operator::End, builder, state, &mut environ).unwrap();
+        });
+    }
+}
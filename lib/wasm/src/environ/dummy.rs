@@ -1,11 +1,15 @@
 use environ::{FuncEnvironment, GlobalValue, ModuleEnvironment};
 use translation_utils::{Global, Memory, Table, GlobalIndex, TableIndex, SignatureIndex,
-                        FunctionIndex, MemoryIndex};
+                        FunctionIndex, MemoryIndex, DataIndex, Local};
 use func_translator::FuncTranslator;
+use module_compiler::FunctionBody;
 use cretonne::ir::{self, InstBuilder};
 use cretonne::ir::types::*;
+use cretonne::ir::condcodes::IntCC;
 use cretonne::cursor::FuncCursor;
 use cretonne::settings;
+use cretonne::result::CtonResult;
+use cton_frontend::FunctionBuilder;
 use wasmparser;
 use std::error::Error;
 
@@ -62,6 +66,9 @@ pub struct DummyModuleInfo {
 
     /// The start function.
     pub start_func: Option<FunctionIndex>,
+
+    /// Insert fuel checks at function entries and loop headers.
+    pub fuel: bool,
 }
 
 impl DummyModuleInfo {
@@ -77,6 +84,7 @@ impl DummyModuleInfo {
             memories: Vec::new(),
             globals: Vec::new(),
             start_func: None,
+            fuel: false,
         }
     }
 }
@@ -84,15 +92,20 @@ impl DummyModuleInfo {
 /// This `ModuleEnvironment` implementation is a "naïve" one, doing essentially nothing and
 /// emitting placeholders when forced to. Don't try to execute code translated for this
 /// environment, essentially here for translation debug purposes.
-pub struct DummyEnvironment {
+pub struct DummyEnvironment<'data> {
     /// Module information.
     pub info: DummyModuleInfo,
 
     /// Function translation.
     trans: FuncTranslator,
+
+    /// Function bodies collected by `define_function_body` when translation is deferred.
+    ///
+    /// This is `None` when function bodies are translated immediately.
+    deferred_bodies: Option<Vec<&'data [u8]>>,
 }
 
-impl DummyEnvironment {
+impl<'data> DummyEnvironment<'data> {
     /// Allocates the data structures with default flags.
     pub fn default() -> Self {
         Self::with_flags(settings::Flags::new(&settings::builder()))
@@ -103,9 +116,43 @@ impl DummyEnvironment {
         Self {
             info: DummyModuleInfo::with_flags(flags),
             trans: FuncTranslator::new(),
+            deferred_bodies: None,
+        }
+    }
+
+    /// Allocates the data structures with the given flags, collecting the function bodies
+    /// instead of translating them.
+    ///
+    /// The collected bodies are returned by `deferred_function_bodies()`, and `function_bodies`
+    /// stays empty.
+    pub fn with_flags_deferred(flags: settings::Flags) -> Self {
+        Self {
+            info: DummyModuleInfo::with_flags(flags),
+            trans: FuncTranslator::new(),
+            deferred_bodies: Some(Vec::new()),
         }
     }
 
+    /// Get the function bodies collected by an environment created with `with_flags_deferred()`,
+    /// ready to be passed to [`compile_module`](../fn.compile_module.html).
+    pub fn deferred_function_bodies(&self) -> Vec<FunctionBody<'data>> {
+        let num_func_imports = self.get_num_func_imports();
+        self.deferred_bodies
+            .as_ref()
+            .expect("function bodies were not deferred")
+            .iter()
+            .enumerate()
+            .map(|(def_index, &code)| {
+                let func_index = num_func_imports + def_index;
+                FunctionBody {
+                    name: get_func_name(func_index),
+                    signature: self.get_signature(self.get_func_type(func_index)).clone(),
+                    code,
+                }
+            })
+            .collect()
+    }
+
     /// Return a `DummyFuncEnvironment` for translating functions within this
     /// `DummyEnvironment`.
     pub fn func_env(&self) -> DummyFuncEnvironment {
@@ -116,11 +163,43 @@ impl DummyEnvironment {
 /// The FuncEnvironment implementation for use by the `DummyEnvironment`.
 pub struct DummyFuncEnvironment<'dummy_environment> {
     pub mod_info: &'dummy_environment DummyModuleInfo,
+
+    /// The `vmctx` global variable and the `out_of_fuel` runtime function used by the interrupt
+    /// checks, created by the first check in a function.
+    fuel_refs: Option<(ir::GlobalVar, ir::FuncRef)>,
 }
 
 impl<'dummy_environment> DummyFuncEnvironment<'dummy_environment> {
     pub fn new(mod_info: &'dummy_environment DummyModuleInfo) -> Self {
-        Self { mod_info }
+        Self {
+            mod_info,
+            fuel_refs: None,
+        }
+    }
+
+    /// Get the references used by the interrupt checks in `func`, creating them if needed.
+    ///
+    /// The environment can be used to translate several functions, so the cached references are
+    /// only reused if they refer to the same entities in `func`.
+    fn fuel_refs(&mut self, func: &mut ir::Function) -> (ir::GlobalVar, ir::FuncRef) {
+        let vmctx = ir::GlobalVarData::VmCtx { offset: 0.into() };
+        let name = runtime_name("out_of_fuel");
+        if let Some((gv, callee)) = self.fuel_refs {
+            let same_gv = func.global_vars.is_valid(gv) &&
+                match func.global_vars[gv] {
+                    ir::GlobalVarData::VmCtx { offset } => offset == 0.into(),
+                    _ => false,
+                };
+            if same_gv && func.dfg.ext_funcs.is_valid(callee) &&
+                func.dfg.ext_funcs[callee].name == name
+            {
+                return (gv, callee);
+            }
+        }
+        let gv = func.create_global_var(vmctx);
+        let callee = import_runtime(func, name, 0);
+        self.fuel_refs = Some((gv, callee));
+        (gv, callee)
     }
 }
 
@@ -148,6 +227,10 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         })
     }
 
+    fn get_signature(&self, index: SignatureIndex) -> &ir::Signature {
+        &self.mod_info.signatures[index]
+    }
+
     fn make_indirect_sig(&mut self, func: &mut ir::Function, index: SignatureIndex) -> ir::SigRef {
         // A real implementation would probably change the calling convention and add `vmctx` and
         // signature index arguments.
@@ -193,9 +276,106 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
     ) -> ir::Value {
         pos.ins().iconst(I32, -1)
     }
+
+    fn translate_memory_copy(
+        &mut self,
+        mut pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
+    ) -> CtonResult {
+        call_runtime(&mut pos, "memory_copy", &[dst, src, len]);
+        Ok(())
+    }
+
+    fn translate_memory_fill(
+        &mut self,
+        mut pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        dst: ir::Value,
+        val: ir::Value,
+        len: ir::Value,
+    ) -> CtonResult {
+        call_runtime(&mut pos, "memory_fill", &[dst, val, len]);
+        Ok(())
+    }
+
+    fn translate_memory_init(
+        &mut self,
+        mut pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        seg_index: DataIndex,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
+    ) -> CtonResult {
+        let seg = pos.ins().iconst(I32, seg_index as i64);
+        call_runtime(&mut pos, "memory_init", &[seg, dst, src, len]);
+        Ok(())
+    }
+
+    fn translate_data_drop(&mut self, mut pos: FuncCursor, seg_index: DataIndex) -> CtonResult {
+        let seg = pos.ins().iconst(I32, seg_index as i64);
+        call_runtime(&mut pos, "data_drop", &[seg]);
+        Ok(())
+    }
+
+    fn translate_interrupt_check(&mut self, builder: &mut FunctionBuilder<Local>) {
+        if !self.mod_info.fuel {
+            return;
+        }
+
+        // Burn one unit of the fuel counter at `vmctx+0`. When it runs out, call into the runtime
+        // which can refuel and return, or stop the execution.
+        let ptr = self.native_pointer();
+        let (gv, callee) = self.fuel_refs(builder.func);
+        let addr = builder.ins().global_addr(ptr, gv);
+        let fuel = builder.ins().load(I64, ir::MemFlags::new(), addr, 0);
+        let fuel = builder.ins().iadd_imm(fuel, -1);
+        builder.ins().store(ir::MemFlags::new(), fuel, addr, 0);
+        let exhausted = builder.ins().icmp_imm(IntCC::SignedLessThan, fuel, 0);
+
+        let out_of_fuel = builder.create_ebb();
+        let resume = builder.create_ebb();
+        builder.ins().brnz(exhausted, out_of_fuel, &[]);
+        builder.ins().jump(resume, &[]);
+
+        builder.switch_to_block(out_of_fuel, &[]);
+        builder.seal_block(out_of_fuel);
+        builder.ins().call(callee, &[]);
+        builder.ins().jump(resume, &[]);
+
+        builder.switch_to_block(resume, &[]);
+        builder.seal_block(resume);
+    }
 }
 
-impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
+/// Insert a call to the runtime function `name` taking `args`, which all have type `i32`.
+///
+/// A real implementation would also pass the `vmctx` so the runtime can find the memory.
+fn call_runtime(pos: &mut FuncCursor, name: &str, args: &[ir::Value]) {
+    let callee = import_runtime(pos.func, runtime_name(name), args.len());
+    pos.ins().call(callee, args);
+}
+
+/// Get the name of the runtime function `name`.
+fn runtime_name(name: &str) -> ir::FunctionName {
+    ir::FunctionName::new(format!("wasm_rt_{}", name))
+}
+
+/// Import the runtime function `name` taking `num_args` arguments of type `i32` into `func`.
+fn import_runtime(func: &mut ir::Function, name: ir::FunctionName, num_args: usize) -> ir::FuncRef {
+    let mut sig = ir::Signature::new(ir::CallConv::Native);
+    sig.params.extend((0..num_args).map(|_| ir::AbiParam::new(I32)));
+    let signature = func.import_signature(sig);
+    func.import_function(ir::ExtFuncData { name, signature })
+}
+
+impl<'data> ModuleEnvironment<'data> for DummyEnvironment<'data> {
     fn get_func_name(&self, func_index: FunctionIndex) -> ir::FunctionName {
         get_func_name(func_index)
     }
@@ -308,6 +488,10 @@ impl<'data> ModuleEnvironment<'data> for DummyEnvironment {
 
     /// Provides the contents of a function body.
     fn define_function_body(&mut self, body_bytes: &'data [u8]) -> Result<(), String> {
+        if let Some(ref mut bodies) = self.deferred_bodies {
+            bodies.push(body_bytes);
+            return Ok(());
+        }
         let function_index = self.get_num_func_imports() + self.info.function_bodies.len();
         let name = get_func_name(function_index);
         let sig = self.get_signature(self.get_func_type(function_index))
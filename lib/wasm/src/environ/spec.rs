@@ -3,8 +3,10 @@
 use cretonne::ir::{self, InstBuilder};
 use cretonne::cursor::FuncCursor;
 use cretonne::settings::Flags;
+use cretonne::result::{CtonResult, CtonError};
+use cton_frontend::FunctionBuilder;
 use translation_utils::{SignatureIndex, FunctionIndex, TableIndex, GlobalIndex, MemoryIndex,
-                        Global, Table, Memory};
+                        DataIndex, Global, Table, Memory, Local};
 
 /// The value of a WebAssembly global variable.
 #[derive(Clone, Copy)]
@@ -56,6 +58,12 @@ pub trait FuncEnvironment {
     /// The index space covers both imported and locally declared memories.
     fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> ir::Heap;
 
+    /// Get the WebAssembly signature with the given index.
+    ///
+    /// This is used for the block types that refer to a function type, so the signature must not
+    /// contain any special-purpose parameters or return values.
+    fn get_signature(&self, index: SignatureIndex) -> &ir::Signature;
+
     /// Set up a signature definition in the preamble of `func` that can be used for an indirect
     /// call with signature `index`.
     ///
@@ -144,6 +152,103 @@ pub trait FuncEnvironment {
         index: MemoryIndex,
         heap: ir::Heap,
     ) -> ir::Value;
+
+    /// Translate a `memory.copy` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to copy within, and `heap` is the heap
+    /// reference returned by `make_heap` for the same index.
+    ///
+    /// Copy `len` bytes from the heap offset `src` to the heap offset `dst`. The two regions may
+    /// overlap. All three values have type `i32`. The translator has already inserted code that
+    /// traps with `TrapCode::HeapOutOfBounds` if either region is outside the heap bound.
+    ///
+    /// The inserted instructions must not contain branches, so a copy loop should be implemented
+    /// as a call to a runtime function. Copies with a small constant `len` can be expanded inline.
+    ///
+    /// The default implementation returns `CtonError::Unsupported`, so modules using the bulk
+    /// memory operators fail to translate unless the environment supports them.
+    fn translate_memory_copy(
+        &mut self,
+        _pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        _dst: ir::Value,
+        _src: ir::Value,
+        _len: ir::Value,
+    ) -> CtonResult {
+        Err(CtonError::Unsupported)
+    }
+
+    /// Translate a `memory.fill` WebAssembly instruction.
+    ///
+    /// Set `len` bytes starting at the heap offset `dst` to the low 8 bits of `val`. All three
+    /// values have type `i32`. The translator has already inserted code that traps with
+    /// `TrapCode::HeapOutOfBounds` if the region is outside the heap bound.
+    ///
+    /// See `translate_memory_copy` for the other arguments, the restrictions, and the default
+    /// implementation.
+    fn translate_memory_fill(
+        &mut self,
+        _pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        _dst: ir::Value,
+        _val: ir::Value,
+        _len: ir::Value,
+    ) -> CtonResult {
+        Err(CtonError::Unsupported)
+    }
+
+    /// Translate a `memory.init` WebAssembly instruction.
+    ///
+    /// Copy `len` bytes from the offset `src` in the passive data segment `seg_index` to the heap
+    /// offset `dst`. All three values have type `i32`. The translator has already inserted code
+    /// that traps with `TrapCode::HeapOutOfBounds` if the destination region is outside the heap
+    /// bound. Only the environment knows the segment sizes, so it must trap with
+    /// `TrapCode::HeapOutOfBounds` if the source region is outside the segment, or if the segment
+    /// has been dropped.
+    ///
+    /// See `translate_memory_copy` for the other arguments, the restrictions, and the default
+    /// implementation.
+    fn translate_memory_init(
+        &mut self,
+        _pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        _seg_index: DataIndex,
+        _dst: ir::Value,
+        _src: ir::Value,
+        _len: ir::Value,
+    ) -> CtonResult {
+        Err(CtonError::Unsupported)
+    }
+
+    /// Translate a `data.drop` WebAssembly instruction.
+    ///
+    /// Drop the passive data segment `seg_index` so that later `memory.init` instructions using it
+    /// trap, and its memory can be released.
+    ///
+    /// See `translate_memory_copy` for the restrictions and the default implementation.
+    fn translate_data_drop(&mut self, _pos: FuncCursor, _seg_index: DataIndex) -> CtonResult {
+        Err(CtonError::Unsupported)
+    }
+
+    /// Translate an interrupt check at the current position of `builder`.
+    ///
+    /// This is called once at the function entry and once at the header of every loop, so it
+    /// runs on every loop iteration. An environment that needs to stop runaway code can insert a
+    /// cheap check here, such as decrementing a fuel counter or comparing an epoch counter
+    /// stored in the `vmctx` against a deadline.
+    ///
+    /// When called, `builder` is positioned at the end of a reachable EBB that isn't filled. The
+    /// check can simply trap with `TrapCode::Interrupt` using `trapnz`, or it can branch, for
+    /// example to an out-of-line call into the runtime. EBBs created by the check must be sealed
+    /// once all of their predecessors have been inserted, and the check must leave `builder`
+    /// switched to an EBB that isn't filled, where the translation of the function continues.
+    ///
+    /// Interrupt checks are opt-in: The default implementation inserts nothing, so translated
+    /// code can't be interrupted unless the environment implements this method.
+    fn translate_interrupt_check(&mut self, _builder: &mut FunctionBuilder<Local>) {}
 }
 
 /// An object satisfyng the `ModuleEnvironment` trait can be passed as argument to the
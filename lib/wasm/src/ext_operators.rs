@@ -0,0 +1,256 @@
+//! Decoding of operators from post-MVP WebAssembly extensions.
+//!
+//! The version of `wasmparser` used by this crate only decodes the MVP operator set. This module
+//! decodes the operators from the sign-extension and bulk memory extensions, so they can be
+//! translated along with the MVP operators.
+//!
+//! It also decodes the parts of the multi-value extension that `wasmparser` rejects: block types
+//! that refer to a function type, and function types with more than one result.
+
+use wasmparser::{BinaryReader, BinaryReaderError, FuncType, Type};
+
+/// An operator from a WebAssembly extension that `wasmparser` doesn't decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtOperator {
+    I32Extend8S,
+    I32Extend16S,
+    I64Extend8S,
+    I64Extend16S,
+    I64Extend32S,
+    MemoryInit { segment: u32 },
+    DataDrop { segment: u32 },
+    MemoryCopy,
+    MemoryFill,
+    /// A `block` whose type is the function type `sig` from the type section.
+    Block { sig: u32 },
+    /// A `loop` whose type is the function type `sig` from the type section.
+    Loop { sig: u32 },
+    /// An `if` whose type is the function type `sig` from the type section.
+    If { sig: u32 },
+}
+
+/// Prefix byte of the bulk memory operators.
+const MISC_PREFIX: u32 = 0xfc;
+
+/// Maximum number of parameters or results in a function type, the same limit `wasmparser` uses
+/// for parameters.
+const MAX_FUNCTION_TYPE_VALUES: u32 = 1000;
+
+/// Read an extension operator from `reader` if there is one at the current position.
+///
+/// The `code` slice must be the whole buffer that `reader` was created from. `BinaryReader` can't
+/// be cloned or rewound, so the operator is decoded from a second reader over the same bytes.
+///
+/// Returns `Ok(None)` without consuming anything if the next operator is not an extension
+/// operator, so it can be read with `BinaryReader::read_operator()` instead.
+pub fn read_ext_operator(
+    code: &[u8],
+    reader: &mut BinaryReader,
+) -> Result<Option<ExtOperator>, BinaryReaderError> {
+    let start = reader.current_position();
+    let mut peek = BinaryReader::new(&code[start..]);
+    match decode(&mut peek) {
+        Ok(Some(op)) => {
+            reader.read_bytes(peek.current_position())?;
+            Ok(Some(op))
+        }
+        Ok(None) => Ok(None),
+        Err(BinaryReaderError { message, offset }) => Err(BinaryReaderError {
+            message,
+            offset: start + offset,
+        }),
+    }
+}
+
+/// Decode an extension operator at the beginning of `peek`.
+fn decode(peek: &mut BinaryReader) -> Result<Option<ExtOperator>, BinaryReaderError> {
+    let opcode = peek.read_u8()?;
+    let op = match opcode {
+        0xc0 => ExtOperator::I32Extend8S,
+        0xc1 => ExtOperator::I32Extend16S,
+        0xc2 => ExtOperator::I64Extend8S,
+        0xc3 => ExtOperator::I64Extend16S,
+        0xc4 => ExtOperator::I64Extend32S,
+        0x02 | 0x03 | 0x04 => {
+            // Value types and the empty block type are encoded as negative numbers, and
+            // `wasmparser` decodes them. A type index is non-negative.
+            let sig = match read_type_index(peek)? {
+                Some(sig) => sig,
+                None => return Ok(None),
+            };
+            match opcode {
+                0x02 => ExtOperator::Block { sig },
+                0x03 => ExtOperator::Loop { sig },
+                _ => ExtOperator::If { sig },
+            }
+        }
+        MISC_PREFIX => {
+            match peek.read_var_u32()? {
+                0x08 => {
+                    let segment = peek.read_var_u32()?;
+                    read_reserved_byte(peek)?;
+                    ExtOperator::MemoryInit { segment }
+                }
+                0x09 => ExtOperator::DataDrop { segment: peek.read_var_u32()? },
+                0x0a => {
+                    read_reserved_byte(peek)?;
+                    read_reserved_byte(peek)?;
+                    ExtOperator::MemoryCopy
+                }
+                0x0b => {
+                    read_reserved_byte(peek)?;
+                    ExtOperator::MemoryFill
+                }
+                _ => return Ok(None),
+            }
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(op))
+}
+
+/// Read a block type encoded as a signed 33-bit number, and return it if it is a type index.
+fn read_type_index(reader: &mut BinaryReader) -> Result<Option<u32>, BinaryReaderError> {
+    let offset = reader.current_position();
+    let ty = reader.read_var_i64()?;
+    if ty < 0 {
+        return Ok(None);
+    }
+    if ty > i64::from(u32::max_value()) || reader.current_position() - offset > 5 {
+        return Err(BinaryReaderError {
+            message: "invalid block type",
+            offset,
+        });
+    }
+    Ok(Some(ty as u32))
+}
+
+/// Read a function type entry of the type section.
+///
+/// Unlike `wasmparser`, this accepts function types with more than one result.
+pub fn read_func_type(reader: &mut BinaryReader) -> Result<FuncType, BinaryReaderError> {
+    let form = read_type(reader)?;
+    let params = read_value_types(reader)?;
+    let returns = read_value_types(reader)?;
+    Ok(FuncType {
+        form,
+        params,
+        returns,
+    })
+}
+
+/// Read a vector of parameter or result types.
+fn read_value_types(reader: &mut BinaryReader) -> Result<Vec<Type>, BinaryReaderError> {
+    let offset = reader.current_position();
+    let len = reader.read_var_u32()?;
+    if len > MAX_FUNCTION_TYPE_VALUES {
+        return Err(BinaryReaderError {
+            message: "function type size is out of bound",
+            offset,
+        });
+    }
+    (0..len).map(|_| read_type(reader)).collect()
+}
+
+/// Read a type encoded as a single byte.
+fn read_type(reader: &mut BinaryReader) -> Result<Type, BinaryReaderError> {
+    let offset = reader.current_position();
+    Ok(match reader.read_u8()? {
+        0x7f => Type::I32,
+        0x7e => Type::I64,
+        0x7d => Type::F32,
+        0x7c => Type::F64,
+        0x70 => Type::AnyFunc,
+        0x60 => Type::Func,
+        0x40 => Type::EmptyBlockType,
+        _ => {
+            return Err(BinaryReaderError {
+                message: "Invalid type",
+                offset,
+            })
+        }
+    })
+}
+
+/// Read the memory index immediate of a bulk memory operator. It is reserved and must be zero.
+fn read_reserved_byte(reader: &mut BinaryReader) -> Result<(), BinaryReaderError> {
+    let offset = reader.current_position();
+    if reader.read_u8()? != 0 {
+        return Err(BinaryReaderError {
+            message: "reserved byte must be zero",
+            offset,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_ext_operator, read_func_type, ExtOperator};
+    use wasmparser::{BinaryReader, Type};
+
+    #[test]
+    fn decode() {
+        let code = [
+            0xc0,                   // i32.extend8_s
+            0xfc, 0x08, 0x03, 0x00, // memory.init 3
+            0xfc, 0x0a, 0x00, 0x00, // memory.copy
+            0x6a,                   // i32.add
+        ];
+        let mut reader = BinaryReader::new(&code);
+        assert_eq!(
+            read_ext_operator(&code, &mut reader).unwrap(),
+            Some(ExtOperator::I32Extend8S)
+        );
+        assert_eq!(
+            read_ext_operator(&code, &mut reader).unwrap(),
+            Some(ExtOperator::MemoryInit { segment: 3 })
+        );
+        assert_eq!(
+            read_ext_operator(&code, &mut reader).unwrap(),
+            Some(ExtOperator::MemoryCopy)
+        );
+        assert_eq!(read_ext_operator(&code, &mut reader).unwrap(), None);
+        assert_eq!(reader.current_position(), 9);
+
+        let bad = [0xfc, 0x0b, 0x01];
+        assert!(read_ext_operator(&bad, &mut BinaryReader::new(&bad)).is_err());
+    }
+
+    #[test]
+    fn block_types() {
+        let code = [
+            0x02, 0x40,       // block
+            0x03, 0x7f,       // loop (result i32)
+            0x04, 0x81, 0x01, // if (type 129)
+            0x02, 0x03,       // block (type 3)
+        ];
+        let mut reader = BinaryReader::new(&code);
+        assert_eq!(read_ext_operator(&code, &mut reader).unwrap(), None);
+        reader.read_operator().unwrap();
+        assert_eq!(read_ext_operator(&code, &mut reader).unwrap(), None);
+        reader.read_operator().unwrap();
+        assert_eq!(
+            read_ext_operator(&code, &mut reader).unwrap(),
+            Some(ExtOperator::If { sig: 129 })
+        );
+        assert_eq!(
+            read_ext_operator(&code, &mut reader).unwrap(),
+            Some(ExtOperator::Block { sig: 3 })
+        );
+        assert!(reader.eof());
+    }
+
+    #[test]
+    fn func_types() {
+        // (func (param i64) (result i32 f64))
+        let data = [0x60, 0x01, 0x7e, 0x02, 0x7f, 0x7c];
+        let ty = read_func_type(&mut BinaryReader::new(&data)).unwrap();
+        assert_eq!(ty.form, Type::Func);
+        assert_eq!(ty.params, [Type::I64]);
+        assert_eq!(ty.returns, [Type::I32, Type::F64]);
+
+        let bad = [0x60, 0x01, 0x01, 0x00];
+        assert!(read_func_type(&mut BinaryReader::new(&bad)).is_err());
+    }
+}
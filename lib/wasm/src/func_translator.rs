@@ -4,12 +4,13 @@
 //! function to Cretonne IL guided by a `FuncEnvironment` which provides information about the
 //! WebAssembly module and the runtime environment.
 
-use code_translator::translate_operator;
+use code_translator::{translate_operator, translate_ext_operator};
 use cretonne::entity::EntityRef;
 use cretonne::ir::{self, InstBuilder};
 use cretonne::result::{CtonResult, CtonError};
 use cton_frontend::{ILBuilder, FunctionBuilder};
 use environ::FuncEnvironment;
+use ext_operators::read_ext_operator;
 use state::TranslationState;
 use translation_utils::Local;
 use wasmparser::{self, BinaryReader};
@@ -70,6 +71,13 @@ impl FuncTranslator {
         func: &mut ir::Function,
         environ: &mut FE,
     ) -> CtonResult {
+        // Extension operators are decoded from the raw function bytes, so take them out of the
+        // reader. This also makes the source locations relative to the start of the function.
+        let code = reader.read_bytes(reader.bytes_remaining()).map_err(
+            |_| CtonError::InvalidInput,
+        )?;
+        let mut reader = BinaryReader::new(code);
+
         dbg!(
             "translate({} bytes, {}{})",
             reader.bytes_remaining(),
@@ -95,8 +103,20 @@ impl FuncTranslator {
         let exit_block = builder.create_ebb();
         self.state.initialize(&builder.func.signature, exit_block);
 
-        parse_local_decls(&mut reader, &mut builder, num_args)?;
-        parse_function_body(reader, &mut builder, &mut self.state, environ)
+        if let Err(e) = parse_local_decls(&mut reader, &mut builder, num_args) {
+            builder.abandon();
+            return Err(e);
+        }
+
+        // Give the environment a chance to insert an interrupt check before the body runs.
+        builder.set_srcloc(cur_srcloc(&reader));
+        environ.translate_interrupt_check(&mut builder);
+
+        let result = parse_function_body(code, reader, &mut builder, &mut self.state, environ);
+        if result.is_err() {
+            builder.abandon();
+        }
+        result
     }
 }
 
@@ -185,11 +205,12 @@ fn declare_locals(
     Ok(())
 }
 
-/// Parse the function body in `reader`.
+/// Parse the function body in `reader`, which reads from the function bytes in `code`.
 ///
 /// This assumes that the local variable declarations have already been parsed and function
 /// arguments and locals are declared in the builder.
 fn parse_function_body<FE: FuncEnvironment + ?Sized>(
+    code: &[u8],
     mut reader: BinaryReader,
     builder: &mut FunctionBuilder<Local>,
     state: &mut TranslationState,
@@ -201,8 +222,15 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
     // Keep going until the final `End` operator which pops the outermost block.
     while !state.control_stack.is_empty() {
         builder.set_srcloc(cur_srcloc(&reader));
+        if let Some(op) = read_ext_operator(code, &mut reader).map_err(
+            |_| CtonError::InvalidInput,
+        )?
+        {
+            translate_ext_operator(op, builder, state, environ)?;
+            continue;
+        }
         let op = reader.read_operator().map_err(|_| CtonError::InvalidInput)?;
-        translate_operator(&op, builder, state, environ);
+        translate_operator(&op, builder, state, environ)?;
     }
 
     // The final `End` operator left us in the exit block where we need to manually add a return
@@ -232,8 +260,12 @@ fn cur_srcloc(reader: &BinaryReader) -> ir::SourceLoc {
 #[cfg(test)]
 mod tests {
     use cretonne::{ir, Context};
-    use cretonne::ir::types::I32;
-    use environ::{DummyEnvironment, FuncEnvironment};
+    use cretonne::result::CtonError;
+    use cretonne::cursor::FuncCursor;
+    use cretonne::ir::types::{I32, I64};
+    use cretonne::settings::Flags;
+    use environ::{DummyEnvironment, FuncEnvironment, GlobalValue};
+    use translation_utils::{FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex};
     use super::FuncTranslator;
 
     #[test]
@@ -335,4 +367,238 @@ mod tests {
         dbg!("{}", ctx.func.display(None));
         ctx.verify(runtime.func_env().flags()).unwrap();
     }
+
+    #[test]
+    fn multi_return() {
+        // Return two values, one of them computed by an if/else.
+        //
+        // (func $multi_return (param i32) (result i32 i64)
+        //     (get_local 0)
+        //     (if (result i64) (get_local 0)
+        //         (then (i64.const 1))
+        //         (else (i64.const 2))
+        //     )
+        // )
+        const BODY: [u8; 14] = [
+            0x00,       // local decl count
+            0x20, 0x00, // get_local 0
+            0x20, 0x00, // get_local 0
+            0x04, 0x7e, // if i64
+            0x42, 0x01, // i64.const 1
+            0x05,       // else
+            0x42, 0x02, // i64.const 2
+            0x0b,       // end
+            0x0b,       // end
+        ];
+
+        let mut trans = FuncTranslator::new();
+        let runtime = DummyEnvironment::default();
+        let mut ctx = Context::new();
+
+        ctx.func.name = ir::FunctionName::new("multi_return");
+        ctx.func.signature.params.push(ir::AbiParam::new(I32));
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+        ctx.func.signature.returns.push(ir::AbiParam::new(I64));
+
+        trans
+            .translate(&BODY, &mut ctx.func, &mut runtime.func_env())
+            .unwrap();
+        dbg!("{}", ctx.func.display(None));
+        ctx.verify(runtime.func_env().flags()).unwrap();
+    }
+
+    #[test]
+    fn bulk_memory() {
+        // Sign-extension and bulk memory operators are decoded by the translator itself.
+        //
+        // (func $bulk_memory (param i32 i32 i32) (result i32)
+        //     (memory.copy (get_local 0) (get_local 1) (get_local 2))
+        //     (i32.extend8_s (get_local 0))
+        // )
+        const BODY: [u8; 15] = [
+            0x00,                   // local decl count
+            0x20, 0x00,             // get_local 0
+            0x20, 0x01,             // get_local 1
+            0x20, 0x02,             // get_local 2
+            0xfc, 0x0a, 0x00, 0x00, // memory.copy
+            0x20, 0x00,             // get_local 0
+            0xc0,                   // i32.extend8_s
+            0x0b,                   // end
+        ];
+
+        let mut trans = FuncTranslator::new();
+        let runtime = DummyEnvironment::default();
+        let mut ctx = Context::new();
+
+        ctx.func.name = ir::FunctionName::new("bulk_memory");
+        for _ in 0..3 {
+            ctx.func.signature.params.push(ir::AbiParam::new(I32));
+        }
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+
+        trans
+            .translate(&BODY, &mut ctx.func, &mut runtime.func_env())
+            .unwrap();
+        dbg!("{}", ctx.func.display(None));
+        ctx.verify(runtime.func_env().flags()).unwrap();
+
+        // Both the source and the destination are bounds checked before calling the runtime.
+        let text = ctx.func.display(None).to_string();
+        assert_eq!(text.matches("trapnz").count(), 2);
+        assert_eq!(text.matches("heap_oob").count(), 2);
+        assert!(text.contains("%wasm_rt_memory_copy"));
+        assert!(text.contains("ireduce.i8"));
+        assert!(text.contains("sextend.i32"));
+
+        // The default `FuncEnvironment` methods reject the bulk memory operators.
+        let mut env = DefaultHooks { inner: runtime.func_env() };
+        let mut ctx = Context::new();
+        for _ in 0..3 {
+            ctx.func.signature.params.push(ir::AbiParam::new(I32));
+        }
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+        assert_eq!(
+            trans.translate(&BODY, &mut ctx.func, &mut env),
+            Err(CtonError::Unsupported)
+        );
+    }
+
+    /// A function environment that only implements the required methods, forwarding them to
+    /// `inner`, so the translator uses the default implementations of the optional ones.
+    struct DefaultHooks<FE: FuncEnvironment> {
+        inner: FE,
+    }
+
+    impl<FE: FuncEnvironment> FuncEnvironment for DefaultHooks<FE> {
+        fn flags(&self) -> &Flags {
+            self.inner.flags()
+        }
+
+        fn make_global(&mut self, func: &mut ir::Function, index: GlobalIndex) -> GlobalValue {
+            self.inner.make_global(func, index)
+        }
+
+        fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> ir::Heap {
+            self.inner.make_heap(func, index)
+        }
+
+        fn get_signature(&self, index: SignatureIndex) -> &ir::Signature {
+            self.inner.get_signature(index)
+        }
+
+        fn make_indirect_sig(
+            &mut self,
+            func: &mut ir::Function,
+            index: SignatureIndex,
+        ) -> ir::SigRef {
+            self.inner.make_indirect_sig(func, index)
+        }
+
+        fn make_direct_func(
+            &mut self,
+            func: &mut ir::Function,
+            index: FunctionIndex,
+        ) -> ir::FuncRef {
+            self.inner.make_direct_func(func, index)
+        }
+
+        fn translate_call_indirect(
+            &mut self,
+            pos: FuncCursor,
+            table_index: TableIndex,
+            sig_index: SignatureIndex,
+            sig_ref: ir::SigRef,
+            callee: ir::Value,
+            call_args: &[ir::Value],
+        ) -> ir::Inst {
+            self.inner.translate_call_indirect(
+                pos,
+                table_index,
+                sig_index,
+                sig_ref,
+                callee,
+                call_args,
+            )
+        }
+
+        fn translate_grow_memory(
+            &mut self,
+            pos: FuncCursor,
+            index: MemoryIndex,
+            heap: ir::Heap,
+            val: ir::Value,
+        ) -> ir::Value {
+            self.inner.translate_grow_memory(pos, index, heap, val)
+        }
+
+        fn translate_current_memory(
+            &mut self,
+            pos: FuncCursor,
+            index: MemoryIndex,
+            heap: ir::Heap,
+        ) -> ir::Value {
+            self.inner.translate_current_memory(pos, index, heap)
+        }
+    }
+
+    #[test]
+    fn interrupt_checks() {
+        // An infinite loop, translated with fuel checks.
+        const BODY: [u8; 16] = [
+            0x01,       // 1 local decl.
+            0x01, 0x7f, // 1 i32 local.
+            0x03, 0x7f, // loop i32
+            0x20, 0x00, // get_local 0
+            0x41, 0x01, // i32.const 1
+            0x6a,       // i32.add
+            0x21, 0x00, // set_local 0
+            0x0c, 0x00, // br 0
+            0x0b,       // end
+            0x0b,       // end
+        ];
+
+        let mut trans = FuncTranslator::new();
+        let mut runtime = DummyEnvironment::default();
+        runtime.info.fuel = true;
+        let mut ctx = Context::new();
+
+        ctx.func.name = ir::FunctionName::new("interrupt_checks");
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+
+        trans
+            .translate(&BODY, &mut ctx.func, &mut runtime.func_env())
+            .unwrap();
+        dbg!("{}", ctx.func.display(None));
+        ctx.verify(runtime.func_env().flags()).unwrap();
+
+        // One check at the function entry and one at the loop header, each branching to a call
+        // into the runtime. The checks share the `vmctx` global variable and the runtime function.
+        let text = ctx.func.display(None).to_string();
+        assert_eq!(text.matches("brnz").count(), 2);
+        assert_eq!(text.matches("= vmctx").count(), 1);
+        assert_eq!(text.matches("%wasm_rt_out_of_fuel").count(), 1);
+        assert_eq!(text.matches("call fn0()").count(), 2);
+
+        // The references cached by the environment are not reused for another function.
+        let mut env = runtime.func_env();
+        for _ in 0..2 {
+            let mut ctx = Context::new();
+            ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+            trans.translate(&BODY, &mut ctx.func, &mut env).unwrap();
+            ctx.verify(env.flags()).unwrap();
+            let text = ctx.func.display(None).to_string();
+            assert_eq!(text.matches("%wasm_rt_out_of_fuel").count(), 1);
+        }
+
+        // Without fuel, the loop isn't interruptible.
+        runtime.info.fuel = false;
+        let mut ctx = Context::new();
+        ctx.func.signature.returns.push(ir::AbiParam::new(I32));
+        trans
+            .translate(&BODY, &mut ctx.func, &mut runtime.func_env())
+            .unwrap();
+        let text = ctx.func.display(None).to_string();
+        assert!(!text.contains("brnz"));
+        assert!(!text.contains("call"));
+    }
 }
@@ -7,25 +7,36 @@
 //! The crate provides a `DummyEnvironment` struct that will allow to translate the code of the
 //! functions but will fail at execution.
 //!
-//! The main function of this module is [`translate_module`](fn.translate_module.html).
+//! The main function of this module is [`translate_module`](fn.translate_module.html), which
+//! expects a valid module. Modules from untrusted sources should be checked first with
+//! [`validate_module`](fn.validate_module.html). The
+//! function bodies of a module can then be translated and compiled in parallel with
+//! [`compile_module`](fn.compile_module.html).
 
 #![deny(missing_docs)]
 
 extern crate wasmparser;
 extern crate cton_frontend;
+extern crate crossbeam;
 #[macro_use(dbg)]
 extern crate cretonne;
 
 mod code_translator;
 mod func_translator;
+mod module_compiler;
 mod module_translator;
 mod environ;
+mod ext_operators;
 mod sections_translator;
 mod state;
 mod translation_utils;
+mod validator;
 
 pub use func_translator::FuncTranslator;
+pub use module_compiler::{compile_module, FunctionBody, CompiledFunction, CompileError, Relocation,
+                          RelocTarget};
 pub use module_translator::translate_module;
 pub use environ::{FuncEnvironment, ModuleEnvironment, DummyEnvironment, GlobalValue};
 pub use translation_utils::{FunctionIndex, GlobalIndex, TableIndex, MemoryIndex, SignatureIndex,
-                            Global, GlobalInit, Table, Memory};
+                            DataIndex, Global, GlobalInit, Table, Memory, Local};
+pub use validator::{validate_module, ValidationError, ValidationResult};
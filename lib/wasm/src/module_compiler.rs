@@ -0,0 +1,246 @@
+//! Parallel translation and compilation of all the functions in a WebAssembly module.
+//!
+//! The `translate_module` function hands function bodies to the `ModuleEnvironment` one at a time.
+//! An environment that collects the bodies instead of translating them immediately can pass them
+//! to `compile_module` which translates and compiles them on a pool of worker threads. Each worker
+//! owns a `FuncTranslator` and a `Context` which are reused for all the functions it compiles.
+//!
+//! The compiled functions are returned in the same order as the bodies, independently of how the
+//! work was distributed among the threads.
+
+use cretonne::Context;
+use cretonne::binemit::{RelocRecorder, NullStackMapSink, NullCallSiteSink};
+use cretonne::ir;
+use cretonne::isa::TargetIsa;
+use cretonne::result::CtonError;
+use crossbeam;
+use environ::FuncEnvironment;
+use func_translator::FuncTranslator;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub use cretonne::binemit::{Relocation, RelocTarget};
+
+/// A function body waiting to be translated and compiled.
+pub struct FunctionBody<'data> {
+    /// The name of the function.
+    pub name: ir::FunctionName,
+
+    /// The signature of the function, including any special-purpose arguments.
+    pub signature: ir::Signature,
+
+    /// The binary WebAssembly function code, as expected by `FuncTranslator::translate()`.
+    pub code: &'data [u8],
+}
+
+/// The result of compiling a single function.
+pub struct CompiledFunction {
+    /// The compiled function, with encodings and value locations assigned.
+    pub func: ir::Function,
+
+    /// The emitted machine code. Relocations have not been applied.
+    pub code: Vec<u8>,
+
+    /// Relocations that must be applied to `code`, in the order they were emitted.
+    pub relocs: Vec<Relocation>,
+}
+
+/// An error from translating or compiling one of the function bodies.
+pub struct CompileError {
+    /// The function as it was when the error occurred, so the error can be reported in context.
+    pub func: ir::Function,
+
+    /// The translation or compilation error.
+    pub error: CtonError,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.func.name, self.error)
+    }
+}
+
+impl fmt::Debug for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompileError")
+            .field("func", &self.func.name)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+/// Translate and compile a single function body, reusing `trans` and `ctx`.
+fn compile_function<FE: FuncEnvironment + ?Sized>(
+    body: &FunctionBody,
+    isa: &TargetIsa,
+    trans: &mut FuncTranslator,
+    ctx: &mut Context,
+    environ: &mut FE,
+) -> Result<CompiledFunction, CompileError> {
+    ctx.func = ir::Function::with_name_signature(body.name.clone(), body.signature.clone());
+    let code_size = match trans.translate(body.code, &mut ctx.func, environ).and_then(
+        |()| ctx.compile(isa),
+    ) {
+        Ok(code_size) => code_size,
+        Err(error) => {
+            return Err(CompileError {
+                func: ctx.func.clone(),
+                error,
+            })
+        }
+    };
+
+    let mut code = vec![0; code_size as usize];
+    let relocs = {
+        let mut sink = RelocRecorder::new(&ctx.func);
+        ctx.emit_to_memory(
+            code.as_mut_ptr(),
+            &mut sink,
+            &mut NullStackMapSink,
+            &mut NullCallSiteSink,
+            isa,
+        );
+        sink.into_relocs()
+    };
+
+    Ok(CompiledFunction {
+        func: ctx.func.clone(),
+        code,
+        relocs,
+    })
+}
+
+/// Translate and compile `bodies` for `isa` using up to `num_threads` worker threads.
+///
+/// Each worker thread calls `make_environ` once to get the `FuncEnvironment` it uses for all the
+/// functions it translates.
+///
+/// The compiled functions are returned in the same order as `bodies`. If any function fails to
+/// translate or compile, the error for the first such function in `bodies` is returned.
+pub fn compile_module<FE, F>(
+    bodies: &[FunctionBody],
+    isa: &TargetIsa,
+    num_threads: usize,
+    make_environ: F,
+) -> Result<Vec<CompiledFunction>, CompileError>
+where
+    FE: FuncEnvironment,
+    F: Fn() -> FE + Sync,
+{
+    let num_threads = num_threads.max(1).min(bodies.len());
+    let next_body = AtomicUsize::new(0);
+    let results = Mutex::new(
+        (0..bodies.len())
+            .map(|_| None)
+            .collect::<Vec<Option<Result<CompiledFunction, CompileError>>>>(),
+    );
+
+    crossbeam::scope(|scope| {
+        for _ in 0..num_threads {
+            scope.spawn(|| {
+                let mut environ = make_environ();
+                let mut trans = FuncTranslator::new();
+                let mut ctx = Context::new();
+                loop {
+                    let index = next_body.fetch_add(1, Ordering::Relaxed);
+                    if index >= bodies.len() {
+                        break;
+                    }
+                    let result =
+                        compile_function(&bodies[index], isa, &mut trans, &mut ctx, &mut environ);
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("function was not compiled"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use cretonne::Context;
+    use cretonne::ir;
+    use cretonne::ir::types::I32;
+    use cretonne::isa;
+    use cretonne::settings::{self, Configurable};
+    use environ::{DummyEnvironment, ModuleEnvironment};
+    use func_translator::FuncTranslator;
+    use super::{compile_function, compile_module, FunctionBody};
+
+    #[test]
+    fn deterministic_order() {
+        // (func (param i32) (result i32)
+        //     (i32.add (call N+1 (get_local 0)) (i32.const N))
+        // )
+        const NUM_FUNCS: u8 = 8;
+        let codes: Vec<[u8; 9]> = (0..NUM_FUNCS)
+            .map(|n| {
+                [
+                    0x00,                       // local decl count
+                    0x20, 0x00,                 // get_local 0
+                    0x10, (n + 1) % NUM_FUNCS,  // call N+1
+                    0x41, n,                    // i32.const N
+                    0x6a,                       // i32.add
+                    0x0b,                       // end
+                ]
+            })
+            .collect();
+
+        let mut sig = ir::Signature::new(ir::CallConv::Native);
+        sig.params.push(ir::AbiParam::new(I32));
+        sig.returns.push(ir::AbiParam::new(I32));
+        let bodies: Vec<FunctionBody> = codes
+            .iter()
+            .enumerate()
+            .map(|(i, code)| {
+                FunctionBody {
+                    name: ir::FunctionName::new(format!("f{}", i)),
+                    signature: sig.clone(),
+                    code,
+                }
+            })
+            .collect();
+
+        let mut flag_builder = settings::builder();
+        flag_builder.enable("is_64bit").unwrap();
+        let isa = match isa::lookup("intel") {
+            Ok(isa_builder) => isa_builder.finish(settings::Flags::new(&flag_builder)),
+            Err(_) => return,
+        };
+        let mut runtime = DummyEnvironment::default();
+        runtime.declare_signature(&sig);
+        for _ in 0..NUM_FUNCS {
+            runtime.declare_func_type(0);
+        }
+
+        // Compile the bodies one at a time, reusing a single `Context`.
+        let mut environ = runtime.func_env();
+        let mut trans = FuncTranslator::new();
+        let mut ctx = Context::new();
+        let serial: Vec<_> = bodies
+            .iter()
+            .map(|body| {
+                compile_function(body, &*isa, &mut trans, &mut ctx, &mut environ).unwrap()
+            })
+            .collect();
+
+        for &num_threads in &[1, 2, 3, 8, 16] {
+            let compiled = compile_module(&bodies, &*isa, num_threads, || runtime.func_env())
+                .unwrap();
+            assert_eq!(compiled.len(), bodies.len());
+            for ((body, func), expected) in bodies.iter().zip(&compiled).zip(&serial) {
+                assert_eq!(func.func.name, body.name);
+                assert_eq!(func.code, expected.code, "{} threads", num_threads);
+                assert_eq!(func.relocs, expected.relocs, "{} threads", num_threads);
+                assert_eq!(func.relocs.len(), 1);
+            }
+        }
+    }
+}
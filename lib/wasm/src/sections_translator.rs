@@ -11,8 +11,9 @@ use translation_utils::{type_to_type, TableIndex, FunctionIndex, GlobalIndex, Si
                         MemoryIndex, Global, GlobalInit, Table, TableElementType, Memory};
 use cretonne::ir::{Signature, AbiParam, CallConv};
 use cretonne;
-use wasmparser::{Parser, ParserState, FuncType, ImportSectionEntryType, ExternalKind, WasmDecoder,
-                 MemoryType, Operator};
+use wasmparser::{Parser, ParserState, ImportSectionEntryType, ExternalKind, WasmDecoder,
+                 MemoryType, Operator, BinaryReaderError};
+use ext_operators::read_func_type;
 use wasmparser;
 use std::str::from_utf8;
 use environ::ModuleEnvironment;
@@ -22,39 +23,43 @@ pub enum SectionParsingError {
 }
 
 /// Reads the Type Section of the wasm module and returns the corresponding function signatures.
+///
+/// `wasmparser` rejects function types with more than one result, so the entries are decoded with
+/// `read_func_type()` from the section contents instead.
 pub fn parse_function_signatures(
     parser: &mut Parser,
     environ: &mut ModuleEnvironment,
 ) -> Result<(), SectionParsingError> {
-    loop {
-        match *parser.read() {
-            ParserState::EndSection => break,
-            ParserState::TypeSectionEntry(FuncType {
-                                              form: wasmparser::Type::Func,
-                                              ref params,
-                                              ref returns,
-                                          }) => {
-                let mut sig = Signature::new(CallConv::Native);
-                sig.params.extend(params.iter().map(|ty| {
-                    let cret_arg: cretonne::ir::Type = type_to_type(ty).expect(
-                        "only numeric types are supported in function signatures",
-                    );
-                    AbiParam::new(cret_arg)
-                }));
-                sig.returns.extend(returns.iter().map(|ty| {
-                    let cret_arg: cretonne::ir::Type = type_to_type(ty).expect(
-                        "only numeric types are supported in function signatures",
-                    );
-                    AbiParam::new(cret_arg)
-                }));
-                environ.declare_signature(&sig);
-            }
-            ref s => return Err(SectionParsingError::WrongSectionContent(format!("{:?}", s))),
+    let mut reader = parser.create_binary_reader();
+    let count = reader.read_var_u32().map_err(wrong_content)?;
+    for _ in 0..count {
+        let ty = read_func_type(&mut reader).map_err(wrong_content)?;
+        if ty.form != wasmparser::Type::Func {
+            return Err(SectionParsingError::WrongSectionContent(format!("{:?}", ty)));
         }
+        let mut sig = Signature::new(CallConv::Native);
+        sig.params.extend(ty.params.iter().map(|ty| {
+            let cret_arg: cretonne::ir::Type = type_to_type(ty).expect(
+                "only numeric types are supported in function signatures",
+            );
+            AbiParam::new(cret_arg)
+        }));
+        sig.returns.extend(ty.returns.iter().map(|ty| {
+            let cret_arg: cretonne::ir::Type = type_to_type(ty).expect(
+                "only numeric types are supported in function signatures",
+            );
+            AbiParam::new(cret_arg)
+        }));
+        environ.declare_signature(&sig);
     }
     Ok(())
 }
 
+/// Convert a decoding error into a section parsing error.
+fn wrong_content(err: BinaryReaderError) -> SectionParsingError {
+    SectionParsingError::WrongSectionContent(format!("at offset {}: {}", err.offset, err.message))
+}
+
 /// Retrieves the imports from the imports section of the binary.
 pub fn parse_import_section<'data>(
     parser: &mut Parser<'data>,
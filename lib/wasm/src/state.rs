@@ -12,8 +12,10 @@ use translation_utils::{GlobalIndex, MemoryIndex, SignatureIndex, FunctionIndex}
 /// fields:
 ///
 /// - `destination`: reference to the `Ebb` that will hold the code after the control block;
+/// - `num_param_values`: number of values taken from the value stack by the control block;
 /// - `num_return_values`: number of values returned by the control block;
-/// - `original_stack_size`: size of the value stack at the beginning of the control block.
+/// - `original_stack_size`: size of the value stack at the beginning of the control block, not
+///   counting the parameters of the block.
 ///
 /// Moreover, the `if` frame has the `branch_inst` field that points to the `brz` instruction
 /// separating the `true` and `false` branch. The `loop` frame has a `header` field that references
@@ -23,12 +25,14 @@ pub enum ControlStackFrame {
     If {
         destination: Ebb,
         branch_inst: Inst,
+        num_param_values: usize,
         num_return_values: usize,
         original_stack_size: usize,
         reachable: bool,
     },
     Block {
         destination: Ebb,
+        num_param_values: usize,
         num_return_values: usize,
         original_stack_size: usize,
         reachable: bool,
@@ -36,6 +40,7 @@ pub enum ControlStackFrame {
     Loop {
         destination: Ebb,
         header: Ebb,
+        num_param_values: usize,
         num_return_values: usize,
         original_stack_size: usize,
         reachable: bool,
@@ -44,6 +49,13 @@ pub enum ControlStackFrame {
 
 /// Helper methods for the control stack objects.
 impl ControlStackFrame {
+    pub fn num_param_values(&self) -> usize {
+        match *self {
+            ControlStackFrame::If { num_param_values, .. } |
+            ControlStackFrame::Block { num_param_values, .. } |
+            ControlStackFrame::Loop { num_param_values, .. } => num_param_values,
+        }
+    }
     pub fn num_return_values(&self) -> usize {
         match *self {
             ControlStackFrame::If { num_return_values, .. } |
@@ -72,6 +84,17 @@ impl ControlStackFrame {
             ControlStackFrame::Loop { original_stack_size, .. } => original_stack_size,
         }
     }
+    /// Get the number of values passed by a branch to `br_destination()`.
+    ///
+    /// Branching to a loop restarts it with new parameters, while branching to any other block
+    /// exits it with its return values.
+    pub fn num_br_args(&self) -> usize {
+        if self.is_loop() {
+            self.num_param_values()
+        } else {
+            self.num_return_values()
+        }
+    }
     pub fn is_loop(&self) -> bool {
         match *self {
             ControlStackFrame::If { .. } |
@@ -158,6 +181,7 @@ impl TranslationState {
         self.clear();
         self.push_block(
             exit_block,
+            0,
             sig.returns
                 .iter()
                 .filter(|arg| arg.purpose == ir::ArgumentPurpose::Normal)
@@ -214,32 +238,52 @@ impl TranslationState {
     }
 
     // Push a block on the control stack.
-    pub fn push_block(&mut self, following_code: Ebb, num_result_types: usize) {
+    pub fn push_block(
+        &mut self,
+        following_code: Ebb,
+        num_param_types: usize,
+        num_result_types: usize,
+    ) {
         self.control_stack.push(ControlStackFrame::Block {
             destination: following_code,
-            original_stack_size: self.stack.len(),
+            original_stack_size: self.stack.len() - num_param_types,
+            num_param_values: num_param_types,
             num_return_values: num_result_types,
             reachable: false,
         });
     }
 
     // Push a loop on the control stack.
-    pub fn push_loop(&mut self, header: Ebb, following_code: Ebb, num_result_types: usize) {
+    pub fn push_loop(
+        &mut self,
+        header: Ebb,
+        following_code: Ebb,
+        num_param_types: usize,
+        num_result_types: usize,
+    ) {
         self.control_stack.push(ControlStackFrame::Loop {
             header,
             destination: following_code,
-            original_stack_size: self.stack.len(),
+            original_stack_size: self.stack.len() - num_param_types,
+            num_param_values: num_param_types,
             num_return_values: num_result_types,
             reachable: false,
         });
     }
 
     // Push an if on the control stack.
-    pub fn push_if(&mut self, branch_inst: Inst, following_code: Ebb, num_result_types: usize) {
+    pub fn push_if(
+        &mut self,
+        branch_inst: Inst,
+        following_code: Ebb,
+        num_param_types: usize,
+        num_result_types: usize,
+    ) {
         self.control_stack.push(ControlStackFrame::If {
             branch_inst,
             destination: following_code,
-            original_stack_size: self.stack.len(),
+            original_stack_size: self.stack.len() - num_param_types,
+            num_param_values: num_param_types,
             num_return_values: num_result_types,
             reachable: false,
         });
@@ -1,6 +1,7 @@
 ///! Helper functions and structures for the translation.
 use wasmparser;
 use cretonne;
+use cretonne::result::CtonError;
 use std::u32;
 
 /// Index of a function (imported or defined) inside the WebAssembly module.
@@ -13,6 +14,8 @@ pub type GlobalIndex = usize;
 pub type MemoryIndex = usize;
 /// Index of a signature (imported or defined) inside the WebAssembly module.
 pub type SignatureIndex = usize;
+/// Index of a data segment inside the WebAssembly module.
+pub type DataIndex = usize;
 
 /// WebAssembly global.
 #[derive(Debug, Clone, Copy)]
@@ -109,14 +112,22 @@ pub fn f64_translation(x: wasmparser::Ieee64) -> cretonne::ir::immediates::Ieee6
     cretonne::ir::immediates::Ieee64::with_bits(x.bits())
 }
 
-/// Translate a `wasmparser` type into its `Cretonne` equivalent, when possible
-pub fn num_return_values(ty: wasmparser::Type) -> usize {
-    match ty {
-        wasmparser::Type::EmptyBlockType => 0,
-        wasmparser::Type::I32 |
-        wasmparser::Type::F32 |
-        wasmparser::Type::I64 |
-        wasmparser::Type::F64 => 1,
-        _ => panic!("unsupported return value type"),
-    }
+/// Get the parameter and result types of a control block with the block type `ty`.
+///
+/// This handles the block types that `wasmparser` decodes: the empty block type and single value
+/// types. Other block types are reported as `CtonError::Unsupported`. With the multi-value
+/// extension, a block type can also refer to a function type. Those blocks are decoded as
+/// `ExtOperator`s and take their types from the signature instead.
+pub fn blocktype_params_results(
+    ty: wasmparser::Type,
+) -> Result<(&'static [cretonne::ir::Type], &'static [cretonne::ir::Type]), CtonError> {
+    use cretonne::ir::types::*;
+    Ok(match ty {
+        wasmparser::Type::EmptyBlockType => (&[], &[]),
+        wasmparser::Type::I32 => (&[], &[I32]),
+        wasmparser::Type::I64 => (&[], &[I64]),
+        wasmparser::Type::F32 => (&[], &[F32]),
+        wasmparser::Type::F64 => (&[], &[F64]),
+        _ => return Err(CtonError::Unsupported),
+    })
 }
@@ -0,0 +1,1350 @@
+//! Validation of WebAssembly modules.
+//!
+//! The translator assumes that its input is a valid WebAssembly module. Invalid input shows up as
+//! Cretonne verifier errors or panics during translation. This module implements the validation
+//! algorithm from the WebAssembly specification so that invalid modules can be rejected up front
+//! with an error pointing at the offending byte in the module.
+//!
+//! Function bodies are type checked with the operand stack and control stack of the [validation
+//! algorithm](http://webassembly.github.io/spec/appendix/algorithm.html), including the
+//! sign-extension and bulk memory operators and the multi-value blocks that the translator
+//! supports.
+
+use ext_operators::{read_ext_operator, read_func_type, ExtOperator};
+use std::collections::HashSet;
+use std::error::Error as StdError;
+use std::fmt::{self, Display, Formatter};
+use wasmparser::{BinaryReader, BinaryReaderError, ExternalKind, FuncType, GlobalType,
+                 ImportSectionEntryType, MemoryImmediate, MemoryType, Operator, Parser,
+                 ParserInput, ParserState, ResizableLimits, SectionCode, TableType, Type,
+                 WasmDecoder};
+
+/// Maximum number of 64 KB pages in a linear memory.
+const MAX_MEMORY_PAGES: u32 = 0x1_0000;
+
+/// A WebAssembly validation error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Byte offset of the invalid construct from the beginning of the module.
+    pub offset: usize,
+    /// Error message.
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new<S: Into<String>>(offset: usize, message: S) -> ValidationError {
+        ValidationError {
+            offset,
+            message: message.into(),
+        }
+    }
+
+    /// Convert a decoding error from a reader that starts at `base` in the module.
+    fn from_reader(base: usize, err: BinaryReaderError) -> ValidationError {
+        ValidationError::new(base + err.offset, err.message)
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "at offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl StdError for ValidationError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Validation result.
+pub type ValidationResult<T> = Result<T, ValidationError>;
+
+/// Validate the WebAssembly module in `data`.
+///
+/// This checks the module structure, the index spaces referenced by imports, exports, element and
+/// data segments and instructions, and type checks all the function bodies.
+pub fn validate_module(data: &[u8]) -> ValidationResult<()> {
+    ModuleValidator::new().validate(data)
+}
+
+/// Get the name of a value type as it appears in the text format.
+fn type_name(ty: Type) -> &'static str {
+    match ty {
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::F32 => "f32",
+        Type::F64 => "f64",
+        Type::AnyFunc => "anyfunc",
+        Type::Func => "func",
+        Type::EmptyBlockType => "empty block type",
+    }
+}
+
+fn is_value_type(ty: Type) -> bool {
+    match ty {
+        Type::I32 | Type::I64 | Type::F32 | Type::F64 => true,
+        _ => false,
+    }
+}
+
+/// Get the size of the entry count at the beginning of `data`.
+fn entry_count_size(data: &[u8]) -> usize {
+    let mut reader = BinaryReader::new(data);
+    match reader.read_var_u32() {
+        Ok(_) => reader.current_position(),
+        Err(_) => 0,
+    }
+}
+
+/// Get the position of a non-custom section in the required section order.
+fn section_rank(code: &SectionCode) -> Option<u8> {
+    match *code {
+        SectionCode::Custom { .. } => None,
+        SectionCode::Type => Some(1),
+        SectionCode::Import => Some(2),
+        SectionCode::Function => Some(3),
+        SectionCode::Table => Some(4),
+        SectionCode::Memory => Some(5),
+        SectionCode::Global => Some(6),
+        SectionCode::Export => Some(7),
+        SectionCode::Start => Some(8),
+        SectionCode::Element => Some(9),
+        SectionCode::Code => Some(10),
+        SectionCode::Data => Some(11),
+    }
+}
+
+/// An initializer expression being validated.
+struct InitExpr {
+    /// The type the expression must produce.
+    ty: Type,
+    /// Number of operators seen so far, not counting the final `end`.
+    num_operators: usize,
+}
+
+/// The module-level state collected while validating the sections in order.
+struct ModuleValidator<'data> {
+    last_section: u8,
+    types: Vec<FuncType>,
+    /// Signature index of every function, imported functions first.
+    functions: Vec<u32>,
+    num_imported_functions: usize,
+    num_function_bodies: usize,
+    tables: Vec<TableType>,
+    memories: Vec<MemoryType>,
+    globals: Vec<GlobalType>,
+    num_imported_globals: usize,
+    export_names: HashSet<&'data [u8]>,
+    init_expr: Option<InitExpr>,
+    num_data_segments: usize,
+    /// The largest data segment index used by a function body, and where it was used.
+    ///
+    /// The data section follows the code section, so these references can only be checked at the
+    /// end of the module.
+    max_data_segment: Option<(u32, usize)>,
+}
+
+impl<'data> ModuleValidator<'data> {
+    fn new() -> ModuleValidator<'data> {
+        ModuleValidator {
+            last_section: 0,
+            types: Vec::new(),
+            functions: Vec::new(),
+            num_imported_functions: 0,
+            num_function_bodies: 0,
+            tables: Vec::new(),
+            memories: Vec::new(),
+            globals: Vec::new(),
+            num_imported_globals: 0,
+            export_names: HashSet::new(),
+            init_expr: None,
+            num_data_segments: 0,
+            max_data_segment: None,
+        }
+    }
+
+    fn validate(mut self, data: &'data [u8]) -> ValidationResult<()> {
+        let mut parser = Parser::new(data);
+        let mut next_input = ParserInput::Default;
+        // The parser reads the entry count of a section along with its first entry.
+        let mut at_entry_count = false;
+        loop {
+            let mut offset = parser.current_position();
+            if at_entry_count {
+                offset += entry_count_size(&data[offset..]);
+                at_entry_count = false;
+            }
+            next_input = match *parser.read_with_input(next_input) {
+                ParserState::Error(err) => return Err(ValidationError::from_reader(0, err)),
+                ParserState::EndWasm => break,
+                ParserState::BeginSection { code: SectionCode::Type, range } => {
+                    self.begin_section(&SectionCode::Type, offset)?;
+                    let reader = parser.create_binary_reader();
+                    self.read_type_section(reader, range.start)?;
+                    ParserInput::Default
+                }
+                ParserState::BeginSection { ref code, .. } => {
+                    self.begin_section(code, offset)?;
+                    at_entry_count = match *code {
+                        SectionCode::Custom { .. } |
+                        SectionCode::Start => false,
+                        _ => true,
+                    };
+                    ParserInput::Default
+                }
+                ParserState::ImportSectionEntry { ref ty, .. } => {
+                    self.declare_import(ty, offset)?;
+                    ParserInput::Default
+                }
+                ParserState::FunctionSectionEntry(sig_index) => {
+                    self.check_type_index(sig_index, offset)?;
+                    self.functions.push(sig_index);
+                    ParserInput::Default
+                }
+                ParserState::TableSectionEntry(ty) => {
+                    self.declare_table(ty, offset)?;
+                    ParserInput::Default
+                }
+                ParserState::MemorySectionEntry(ty) => {
+                    self.declare_memory(ty, offset)?;
+                    ParserInput::Default
+                }
+                ParserState::BeginGlobalSectionEntry(ty) => {
+                    if !is_value_type(ty.content_type) {
+                        return Err(ValidationError::new(offset, "invalid global type"));
+                    }
+                    self.begin_init_expr(ty.content_type);
+                    self.globals.push(ty);
+                    ParserInput::Default
+                }
+                ParserState::ExportSectionEntry { field, kind, index } => {
+                    self.declare_export(field, kind, index, offset)?;
+                    ParserInput::Default
+                }
+                ParserState::StartSectionEntry(func_index) => {
+                    self.check_start(func_index, offset)?;
+                    ParserInput::Default
+                }
+                ParserState::BeginElementSectionEntry(table_index) => {
+                    match self.tables.get(table_index as usize) {
+                        Some(table) if table.element_type == Type::AnyFunc => {}
+                        Some(_) => {
+                            return Err(ValidationError::new(
+                                offset,
+                                "elements can only be stored in anyfunc tables",
+                            ))
+                        }
+                        None => {
+                            return Err(ValidationError::new(
+                                offset,
+                                format!("unknown table {}", table_index),
+                            ))
+                        }
+                    }
+                    self.begin_init_expr(Type::I32);
+                    ParserInput::Default
+                }
+                ParserState::ElementSectionEntryBody(ref elements) => {
+                    for &func_index in elements {
+                        self.check_function_index(func_index, offset)?;
+                    }
+                    ParserInput::Default
+                }
+                ParserState::BeginDataSectionEntry(memory_index) => {
+                    self.check_memory_index(memory_index, offset)?;
+                    self.begin_init_expr(Type::I32);
+                    self.num_data_segments += 1;
+                    ParserInput::Default
+                }
+                ParserState::InitExpressionOperator(ref op) => {
+                    self.init_expr_operator(op, offset)?;
+                    ParserInput::Default
+                }
+                ParserState::EndInitExpressionBody => {
+                    self.end_init_expr(offset)?;
+                    ParserInput::Default
+                }
+                ParserState::BeginFunctionBody { range } => {
+                    self.validate_function_body(&data[range.start..range.end], range.start)?;
+                    ParserInput::SkipFunctionBody
+                }
+                _ => ParserInput::Default,
+            };
+        }
+        self.finish(data.len())
+    }
+
+    fn begin_section(&mut self, code: &SectionCode, offset: usize) -> ValidationResult<()> {
+        if let Some(rank) = section_rank(code) {
+            if rank <= self.last_section {
+                return Err(ValidationError::new(
+                    offset,
+                    "section out of order or duplicated",
+                ));
+            }
+            self.last_section = rank;
+        }
+        Ok(())
+    }
+
+    /// Check the module-wide invariants that can only be checked once all sections are read.
+    fn finish(&self, end: usize) -> ValidationResult<()> {
+        if self.num_imported_functions + self.num_function_bodies != self.functions.len() {
+            return Err(ValidationError::new(
+                end,
+                "function and code section have inconsistent lengths",
+            ));
+        }
+        if let Some((segment, offset)) = self.max_data_segment {
+            if segment as usize >= self.num_data_segments {
+                return Err(ValidationError::new(
+                    offset,
+                    format!("unknown data segment {}", segment),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the type section whose contents start at offset `base` in the module.
+    ///
+    /// `wasmparser` rejects function types with more than one result, so the entries are decoded
+    /// with `read_func_type()` instead.
+    fn read_type_section(&mut self, mut reader: BinaryReader, base: usize) -> ValidationResult<()> {
+        let count = reader.read_var_u32().map_err(|err| {
+            ValidationError::from_reader(base, err)
+        })?;
+        for _ in 0..count {
+            let offset = base + reader.current_position();
+            let ty = read_func_type(&mut reader).map_err(|err| {
+                ValidationError::from_reader(base, err)
+            })?;
+            self.declare_type(&ty, offset)?;
+        }
+        if !reader.eof() {
+            return Err(ValidationError::new(
+                base + reader.current_position(),
+                "section size mismatch",
+            ));
+        }
+        Ok(())
+    }
+
+    fn declare_type(&mut self, ty: &FuncType, offset: usize) -> ValidationResult<()> {
+        if ty.form != Type::Func {
+            return Err(ValidationError::new(offset, "type entry must be a function type"));
+        }
+        if !ty.params.iter().chain(ty.returns.iter()).all(
+            |&ty| is_value_type(ty),
+        )
+        {
+            return Err(ValidationError::new(
+                offset,
+                "function types can only contain value types",
+            ));
+        }
+        self.types.push(ty.clone());
+        Ok(())
+    }
+
+    fn declare_import(
+        &mut self,
+        ty: &ImportSectionEntryType,
+        offset: usize,
+    ) -> ValidationResult<()> {
+        match *ty {
+            ImportSectionEntryType::Function(sig_index) => {
+                self.check_type_index(sig_index, offset)?;
+                self.functions.push(sig_index);
+                self.num_imported_functions += 1;
+            }
+            ImportSectionEntryType::Table(table) => self.declare_table(table, offset)?,
+            ImportSectionEntryType::Memory(memory) => self.declare_memory(memory, offset)?,
+            ImportSectionEntryType::Global(global) => {
+                if !is_value_type(global.content_type) {
+                    return Err(ValidationError::new(offset, "invalid global type"));
+                }
+                self.globals.push(global);
+                self.num_imported_globals += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn declare_table(&mut self, ty: TableType, offset: usize) -> ValidationResult<()> {
+        if ty.element_type != Type::AnyFunc {
+            return Err(ValidationError::new(offset, "table element type must be anyfunc"));
+        }
+        check_limits(ty.limits, None, offset)?;
+        if !self.tables.is_empty() {
+            return Err(ValidationError::new(offset, "multiple tables"));
+        }
+        self.tables.push(ty);
+        Ok(())
+    }
+
+    fn declare_memory(&mut self, ty: MemoryType, offset: usize) -> ValidationResult<()> {
+        check_limits(ty.limits, Some(MAX_MEMORY_PAGES), offset)?;
+        if !self.memories.is_empty() {
+            return Err(ValidationError::new(offset, "multiple memories"));
+        }
+        self.memories.push(ty);
+        Ok(())
+    }
+
+    fn declare_export(
+        &mut self,
+        field: &'data [u8],
+        kind: ExternalKind,
+        index: u32,
+        offset: usize,
+    ) -> ValidationResult<()> {
+        let (what, len) = match kind {
+            ExternalKind::Function => ("function", self.functions.len()),
+            ExternalKind::Table => ("table", self.tables.len()),
+            ExternalKind::Memory => ("memory", self.memories.len()),
+            ExternalKind::Global => ("global", self.globals.len()),
+        };
+        if index as usize >= len {
+            return Err(ValidationError::new(
+                offset,
+                format!("unknown {} {}", what, index),
+            ));
+        }
+        if !self.export_names.insert(field) {
+            return Err(ValidationError::new(
+                offset,
+                format!("duplicate export name \"{}\"", String::from_utf8_lossy(field)),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_start(&self, func_index: u32, offset: usize) -> ValidationResult<()> {
+        self.check_function_index(func_index, offset)?;
+        let ty = &self.types[self.functions[func_index as usize] as usize];
+        if !ty.params.is_empty() || !ty.returns.is_empty() {
+            return Err(ValidationError::new(
+                offset,
+                "start function must not take arguments or return values",
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_type_index(&self, sig_index: u32, offset: usize) -> ValidationResult<()> {
+        if sig_index as usize >= self.types.len() {
+            return Err(ValidationError::new(
+                offset,
+                format!("unknown type {}", sig_index),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_function_index(&self, func_index: u32, offset: usize) -> ValidationResult<()> {
+        if func_index as usize >= self.functions.len() {
+            return Err(ValidationError::new(
+                offset,
+                format!("unknown function {}", func_index),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_memory_index(&self, memory_index: u32, offset: usize) -> ValidationResult<()> {
+        if memory_index as usize >= self.memories.len() {
+            return Err(ValidationError::new(
+                offset,
+                format!("unknown memory {}", memory_index),
+            ));
+        }
+        Ok(())
+    }
+
+    fn begin_init_expr(&mut self, ty: Type) {
+        self.init_expr = Some(InitExpr {
+            ty,
+            num_operators: 0,
+        });
+    }
+
+    /// Check an operator in an initializer expression. Only constants and imported immutable
+    /// globals are allowed, and the expression must produce a single value.
+    fn init_expr_operator(&mut self, op: &Operator, offset: usize) -> ValidationResult<()> {
+        let ty = match *op {
+            Operator::I32Const { .. } => Type::I32,
+            Operator::I64Const { .. } => Type::I64,
+            Operator::F32Const { .. } => Type::F32,
+            Operator::F64Const { .. } => Type::F64,
+            Operator::GetGlobal { global_index } => {
+                let index = global_index as usize;
+                if index >= self.num_imported_globals {
+                    return Err(ValidationError::new(
+                        offset,
+                        format!("unknown imported global {}", global_index),
+                    ));
+                }
+                if self.globals[index].mutable {
+                    return Err(ValidationError::new(
+                        offset,
+                        "constant expression required: global is mutable",
+                    ));
+                }
+                self.globals[index].content_type
+            }
+            _ => return Err(ValidationError::new(offset, "constant expression required")),
+        };
+        let expr = self.init_expr.as_mut().expect("init expression outside of an entry");
+        expr.num_operators += 1;
+        if expr.num_operators > 1 {
+            return Err(ValidationError::new(
+                offset,
+                "type mismatch: constant expression produces more than one value",
+            ));
+        }
+        if ty != expr.ty {
+            return Err(ValidationError::new(
+                offset,
+                format!(
+                    "type mismatch: expected {}, found {}",
+                    type_name(expr.ty),
+                    type_name(ty)
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn end_init_expr(&mut self, offset: usize) -> ValidationResult<()> {
+        let expr = self.init_expr.take().expect("init expression outside of an entry");
+        if expr.num_operators == 0 {
+            return Err(ValidationError::new(
+                offset,
+                format!(
+                    "type mismatch: expected {}, found empty constant expression",
+                    type_name(expr.ty)
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Validate the next function body in the code section. The body bytes in `code` start at
+    /// offset `base` in the module.
+    fn validate_function_body(&mut self, code: &[u8], base: usize) -> ValidationResult<()> {
+        let func_index = self.num_imported_functions + self.num_function_bodies;
+        if func_index >= self.functions.len() {
+            return Err(ValidationError::new(
+                base,
+                "function and code section have inconsistent lengths",
+            ));
+        }
+        self.num_function_bodies += 1;
+        let sig = &self.types[self.functions[func_index] as usize];
+
+        let mut reader = BinaryReader::new(code);
+        let mut locals = sig.params.clone();
+        let num_decls = reader.read_local_count().map_err(|err| {
+            ValidationError::from_reader(base, err)
+        })?;
+        let mut num_locals = 0;
+        for _ in 0..num_decls {
+            let offset = base + reader.current_position();
+            let (count, ty) = reader.read_local_decl(&mut num_locals).map_err(|err| {
+                ValidationError::from_reader(base, err)
+            })?;
+            if !is_value_type(ty) {
+                return Err(ValidationError::new(offset, "invalid local type"));
+            }
+            locals.extend((0..count).map(|_| ty));
+        }
+
+        let max_data_segment = {
+            let mut func = FuncValidator::new(self, locals, sig.returns.clone());
+            while !func.frames.is_empty() {
+                func.offset = base + reader.current_position();
+                if reader.eof() {
+                    return Err(func.error("unexpected end of function body"));
+                }
+                let ext_op = read_ext_operator(code, &mut reader).map_err(|err| {
+                    ValidationError::from_reader(base, err)
+                })?;
+                if let Some(op) = ext_op {
+                    func.ext_operator(op)?;
+                    continue;
+                }
+                let op = reader.read_operator().map_err(|err| {
+                    ValidationError::from_reader(base, err)
+                })?;
+                func.operator(&op)?;
+            }
+            func.max_data_segment
+        };
+
+        if !reader.eof() {
+            return Err(ValidationError::new(
+                base + reader.current_position(),
+                "operators after the end of the function body",
+            ));
+        }
+        match (self.max_data_segment, max_data_segment) {
+            (Some((old, _)), Some((new, _))) if old >= new => {}
+            (_, None) => {}
+            (_, new) => self.max_data_segment = new,
+        }
+        Ok(())
+    }
+}
+
+/// Check resizable limits, with an optional upper bound for the sizes.
+fn check_limits(
+    limits: ResizableLimits,
+    bound: Option<u32>,
+    offset: usize,
+) -> ValidationResult<()> {
+    if let Some(bound) = bound {
+        if limits.initial > bound || limits.maximum.map_or(false, |max| max > bound) {
+            return Err(ValidationError::new(
+                offset,
+                format!("size limits must not exceed {}", bound),
+            ));
+        }
+    }
+    if limits.maximum.map_or(false, |max| max < limits.initial) {
+        return Err(ValidationError::new(
+            offset,
+            "size minimum must not be greater than maximum",
+        ));
+    }
+    Ok(())
+}
+
+/// The kind of control construct a control frame belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Function,
+    Block,
+    Loop,
+    If,
+    Else,
+}
+
+/// A control frame of the validation algorithm.
+struct ControlFrame {
+    kind: FrameKind,
+    params: Vec<Type>,
+    results: Vec<Type>,
+    /// Height of the operand stack when the frame was entered, not counting its parameters.
+    height: usize,
+    /// The rest of the frame is unreachable, so the operand stack is polymorphic below `height`.
+    unreachable: bool,
+}
+
+impl ControlFrame {
+    /// The types of the values passed by a branch to this frame. Branching to a loop restarts it,
+    /// while branching to any other frame exits it.
+    fn label_types(&self) -> &[Type] {
+        if self.kind == FrameKind::Loop {
+            &self.params
+        } else {
+            &self.results
+        }
+    }
+}
+
+const I32: Type = Type::I32;
+const I64: Type = Type::I64;
+const F32: Type = Type::F32;
+const F64: Type = Type::F64;
+
+/// Get the parameter and result types of an operator that just computes values.
+fn operator_type(op: &Operator) -> Option<(&'static [Type], &'static [Type])> {
+    Some(match *op {
+        Operator::I32Eqz => (&[I32], &[I32]),
+        Operator::I32Eq |
+        Operator::I32Ne |
+        Operator::I32LtS |
+        Operator::I32LtU |
+        Operator::I32GtS |
+        Operator::I32GtU |
+        Operator::I32LeS |
+        Operator::I32LeU |
+        Operator::I32GeS |
+        Operator::I32GeU => (&[I32, I32], &[I32]),
+        Operator::I64Eqz => (&[I64], &[I32]),
+        Operator::I64Eq |
+        Operator::I64Ne |
+        Operator::I64LtS |
+        Operator::I64LtU |
+        Operator::I64GtS |
+        Operator::I64GtU |
+        Operator::I64LeS |
+        Operator::I64LeU |
+        Operator::I64GeS |
+        Operator::I64GeU => (&[I64, I64], &[I32]),
+        Operator::F32Eq |
+        Operator::F32Ne |
+        Operator::F32Lt |
+        Operator::F32Gt |
+        Operator::F32Le |
+        Operator::F32Ge => (&[F32, F32], &[I32]),
+        Operator::F64Eq |
+        Operator::F64Ne |
+        Operator::F64Lt |
+        Operator::F64Gt |
+        Operator::F64Le |
+        Operator::F64Ge => (&[F64, F64], &[I32]),
+        Operator::I32Clz | Operator::I32Ctz | Operator::I32Popcnt => (&[I32], &[I32]),
+        Operator::I32Add |
+        Operator::I32Sub |
+        Operator::I32Mul |
+        Operator::I32DivS |
+        Operator::I32DivU |
+        Operator::I32RemS |
+        Operator::I32RemU |
+        Operator::I32And |
+        Operator::I32Or |
+        Operator::I32Xor |
+        Operator::I32Shl |
+        Operator::I32ShrS |
+        Operator::I32ShrU |
+        Operator::I32Rotl |
+        Operator::I32Rotr => (&[I32, I32], &[I32]),
+        Operator::I64Clz | Operator::I64Ctz | Operator::I64Popcnt => (&[I64], &[I64]),
+        Operator::I64Add |
+        Operator::I64Sub |
+        Operator::I64Mul |
+        Operator::I64DivS |
+        Operator::I64DivU |
+        Operator::I64RemS |
+        Operator::I64RemU |
+        Operator::I64And |
+        Operator::I64Or |
+        Operator::I64Xor |
+        Operator::I64Shl |
+        Operator::I64ShrS |
+        Operator::I64ShrU |
+        Operator::I64Rotl |
+        Operator::I64Rotr => (&[I64, I64], &[I64]),
+        Operator::F32Abs |
+        Operator::F32Neg |
+        Operator::F32Ceil |
+        Operator::F32Floor |
+        Operator::F32Trunc |
+        Operator::F32Nearest |
+        Operator::F32Sqrt => (&[F32], &[F32]),
+        Operator::F32Add |
+        Operator::F32Sub |
+        Operator::F32Mul |
+        Operator::F32Div |
+        Operator::F32Min |
+        Operator::F32Max |
+        Operator::F32Copysign => (&[F32, F32], &[F32]),
+        Operator::F64Abs |
+        Operator::F64Neg |
+        Operator::F64Ceil |
+        Operator::F64Floor |
+        Operator::F64Trunc |
+        Operator::F64Nearest |
+        Operator::F64Sqrt => (&[F64], &[F64]),
+        Operator::F64Add |
+        Operator::F64Sub |
+        Operator::F64Mul |
+        Operator::F64Div |
+        Operator::F64Min |
+        Operator::F64Max |
+        Operator::F64Copysign => (&[F64, F64], &[F64]),
+        Operator::I32WrapI64 => (&[I64], &[I32]),
+        Operator::I32TruncSF32 |
+        Operator::I32TruncUF32 |
+        Operator::I32TruncSSatF32 |
+        Operator::I32TruncUSatF32 |
+        Operator::I32ReinterpretF32 => (&[F32], &[I32]),
+        Operator::I32TruncSF64 |
+        Operator::I32TruncUF64 |
+        Operator::I32TruncSSatF64 |
+        Operator::I32TruncUSatF64 => (&[F64], &[I32]),
+        Operator::I64ExtendSI32 | Operator::I64ExtendUI32 => (&[I32], &[I64]),
+        Operator::I64TruncSF32 |
+        Operator::I64TruncUF32 |
+        Operator::I64TruncSSatF32 |
+        Operator::I64TruncUSatF32 => (&[F32], &[I64]),
+        Operator::I64TruncSF64 |
+        Operator::I64TruncUF64 |
+        Operator::I64TruncSSatF64 |
+        Operator::I64TruncUSatF64 |
+        Operator::I64ReinterpretF64 => (&[F64], &[I64]),
+        Operator::F32ConvertSI32 |
+        Operator::F32ConvertUI32 |
+        Operator::F32ReinterpretI32 => (&[I32], &[F32]),
+        Operator::F32ConvertSI64 | Operator::F32ConvertUI64 => (&[I64], &[F32]),
+        Operator::F32DemoteF64 => (&[F64], &[F32]),
+        Operator::F64ConvertSI32 | Operator::F64ConvertUI32 => (&[I32], &[F64]),
+        Operator::F64ConvertSI64 |
+        Operator::F64ConvertUI64 |
+        Operator::F64ReinterpretI64 => (&[I64], &[F64]),
+        Operator::F64PromoteF32 => (&[F32], &[F64]),
+        _ => return None,
+    })
+}
+
+/// Get the value type and the log2 of the natural alignment of a load.
+fn load_type<'a>(op: &'a Operator) -> Option<(Type, u32, &'a MemoryImmediate)> {
+    Some(match *op {
+        Operator::I32Load { ref memarg } => (I32, 2, memarg),
+        Operator::I64Load { ref memarg } => (I64, 3, memarg),
+        Operator::F32Load { ref memarg } => (F32, 2, memarg),
+        Operator::F64Load { ref memarg } => (F64, 3, memarg),
+        Operator::I32Load8S { ref memarg } |
+        Operator::I32Load8U { ref memarg } => (I32, 0, memarg),
+        Operator::I32Load16S { ref memarg } |
+        Operator::I32Load16U { ref memarg } => (I32, 1, memarg),
+        Operator::I64Load8S { ref memarg } |
+        Operator::I64Load8U { ref memarg } => (I64, 0, memarg),
+        Operator::I64Load16S { ref memarg } |
+        Operator::I64Load16U { ref memarg } => (I64, 1, memarg),
+        Operator::I64Load32S { ref memarg } |
+        Operator::I64Load32U { ref memarg } => (I64, 2, memarg),
+        _ => return None,
+    })
+}
+
+/// Get the value type and the log2 of the natural alignment of a store.
+fn store_type<'a>(op: &'a Operator) -> Option<(Type, u32, &'a MemoryImmediate)> {
+    Some(match *op {
+        Operator::I32Store { ref memarg } => (I32, 2, memarg),
+        Operator::I64Store { ref memarg } => (I64, 3, memarg),
+        Operator::F32Store { ref memarg } => (F32, 2, memarg),
+        Operator::F64Store { ref memarg } => (F64, 3, memarg),
+        Operator::I32Store8 { ref memarg } => (I32, 0, memarg),
+        Operator::I32Store16 { ref memarg } => (I32, 1, memarg),
+        Operator::I64Store8 { ref memarg } => (I64, 0, memarg),
+        Operator::I64Store16 { ref memarg } => (I64, 1, memarg),
+        Operator::I64Store32 { ref memarg } => (I64, 2, memarg),
+        _ => return None,
+    })
+}
+
+/// Type checker for a single function body.
+///
+/// The operand stack holds `None` for values of unknown type, which are produced by popping from
+/// the polymorphic stack of unreachable code.
+struct FuncValidator<'m, 'data: 'm> {
+    module: &'m ModuleValidator<'data>,
+    locals: Vec<Type>,
+    operands: Vec<Option<Type>>,
+    frames: Vec<ControlFrame>,
+    /// Offset of the current operator in the module.
+    offset: usize,
+    max_data_segment: Option<(u32, usize)>,
+}
+
+impl<'m, 'data> FuncValidator<'m, 'data> {
+    fn new(
+        module: &'m ModuleValidator<'data>,
+        locals: Vec<Type>,
+        results: Vec<Type>,
+    ) -> FuncValidator<'m, 'data> {
+        FuncValidator {
+            module,
+            locals,
+            operands: Vec::new(),
+            frames: vec![
+                ControlFrame {
+                    kind: FrameKind::Function,
+                    params: Vec::new(),
+                    results,
+                    height: 0,
+                    unreachable: false,
+                },
+            ],
+            offset: 0,
+            max_data_segment: None,
+        }
+    }
+
+    fn error<S: Into<String>>(&self, message: S) -> ValidationError {
+        ValidationError::new(self.offset, message)
+    }
+
+    fn push(&mut self, ty: Type) {
+        self.operands.push(Some(ty));
+    }
+
+    fn push_types(&mut self, types: &[Type]) {
+        self.operands.extend(types.iter().map(|&ty| Some(ty)));
+    }
+
+    fn pop(&mut self) -> ValidationResult<Option<Type>> {
+        let frame = self.frames.last().expect("empty control stack");
+        if self.operands.len() == frame.height {
+            if frame.unreachable {
+                return Ok(None);
+            }
+            return Err(self.error("type mismatch: operand stack underflow"));
+        }
+        Ok(self.operands.pop().expect("empty operand stack"))
+    }
+
+    fn pop_expected(&mut self, expected: Type) -> ValidationResult<()> {
+        match self.pop()? {
+            Some(actual) if actual != expected => Err(self.error(format!(
+                "type mismatch: expected {}, found {}",
+                type_name(expected),
+                type_name(actual)
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn pop_types(&mut self, types: &[Type]) -> ValidationResult<()> {
+        for &ty in types.iter().rev() {
+            self.pop_expected(ty)?;
+        }
+        Ok(())
+    }
+
+    /// Mark the rest of the current frame as unreachable.
+    fn set_unreachable(&mut self) {
+        let frame = self.frames.last_mut().expect("empty control stack");
+        self.operands.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    fn push_frame(&mut self, kind: FrameKind, params: Vec<Type>, results: Vec<Type>) {
+        self.frames.push(ControlFrame {
+            kind,
+            height: self.operands.len(),
+            params,
+            results,
+            unreachable: false,
+        });
+        let params = self.frames.last().unwrap().params.clone();
+        self.push_types(&params);
+    }
+
+    /// Pop the current frame after checking that the operand stack holds exactly its results.
+    fn pop_frame(&mut self) -> ValidationResult<ControlFrame> {
+        let results = self.frames.last().expect("empty control stack").results.clone();
+        self.pop_types(&results)?;
+        if self.operands.len() != self.frames.last().unwrap().height {
+            return Err(self.error(
+                "type mismatch: values remaining on the stack at the end of the block",
+            ));
+        }
+        Ok(self.frames.pop().unwrap())
+    }
+
+    /// Get the types passed to the label at `relative_depth`.
+    fn label_types(&self, relative_depth: u32) -> ValidationResult<Vec<Type>> {
+        let depth = relative_depth as usize;
+        if depth >= self.frames.len() {
+            return Err(self.error(format!("unknown label {}", relative_depth)));
+        }
+        Ok(self.frames[self.frames.len() - 1 - depth].label_types().to_vec())
+    }
+
+    fn block_type(&self, ty: Type) -> ValidationResult<(Vec<Type>, Vec<Type>)> {
+        match ty {
+            Type::EmptyBlockType => Ok((Vec::new(), Vec::new())),
+            ty if is_value_type(ty) => Ok((Vec::new(), vec![ty])),
+            _ => Err(self.error("invalid block type")),
+        }
+    }
+
+    fn local(&self, local_index: u32) -> ValidationResult<Type> {
+        self.locals.get(local_index as usize).cloned().ok_or_else(|| {
+            self.error(format!("unknown local {}", local_index))
+        })
+    }
+
+    fn global(&self, global_index: u32) -> ValidationResult<GlobalType> {
+        self.module
+            .globals
+            .get(global_index as usize)
+            .cloned()
+            .ok_or_else(|| self.error(format!("unknown global {}", global_index)))
+    }
+
+    fn func_type(&self, sig_index: u32) -> ValidationResult<FuncType> {
+        self.module.types.get(sig_index as usize).cloned().ok_or_else(
+            || {
+                self.error(format!("unknown type {}", sig_index))
+            },
+        )
+    }
+
+    fn check_memory(&self) -> ValidationResult<()> {
+        if self.module.memories.is_empty() {
+            return Err(self.error("unknown memory 0"));
+        }
+        Ok(())
+    }
+
+    fn check_memarg(&self, memarg: &MemoryImmediate, max_align: u32) -> ValidationResult<()> {
+        self.check_memory()?;
+        if memarg.flags > max_align {
+            return Err(self.error("alignment must not be larger than natural"));
+        }
+        Ok(())
+    }
+
+    fn check_reserved(&self, reserved: u32) -> ValidationResult<()> {
+        if reserved != 0 {
+            return Err(self.error("reserved immediate must be zero"));
+        }
+        Ok(())
+    }
+
+    /// Type check an MVP operator.
+    fn operator(&mut self, op: &Operator) -> ValidationResult<()> {
+        if let Some((params, results)) = operator_type(op) {
+            self.pop_types(params)?;
+            self.push_types(results);
+            return Ok(());
+        }
+        if let Some((ty, max_align, memarg)) = load_type(op) {
+            self.check_memarg(memarg, max_align)?;
+            self.pop_expected(I32)?;
+            self.push(ty);
+            return Ok(());
+        }
+        if let Some((ty, max_align, memarg)) = store_type(op) {
+            self.check_memarg(memarg, max_align)?;
+            self.pop_expected(ty)?;
+            self.pop_expected(I32)?;
+            return Ok(());
+        }
+        match *op {
+            Operator::Unreachable => self.set_unreachable(),
+            Operator::Nop => {}
+            Operator::Block { ty } => {
+                let (params, results) = self.block_type(ty)?;
+                self.pop_types(&params)?;
+                self.push_frame(FrameKind::Block, params, results);
+            }
+            Operator::Loop { ty } => {
+                let (params, results) = self.block_type(ty)?;
+                self.pop_types(&params)?;
+                self.push_frame(FrameKind::Loop, params, results);
+            }
+            Operator::If { ty } => {
+                let (params, results) = self.block_type(ty)?;
+                self.pop_expected(I32)?;
+                self.pop_types(&params)?;
+                self.push_frame(FrameKind::If, params, results);
+            }
+            Operator::Else => {
+                if self.frames.last().unwrap().kind != FrameKind::If {
+                    return Err(self.error("else without matching if"));
+                }
+                let frame = self.pop_frame()?;
+                self.push_frame(FrameKind::Else, frame.params, frame.results);
+            }
+            Operator::End => {
+                let frame = self.pop_frame()?;
+                // Without an `else`, the parameters of an `if` become its results when the
+                // condition is false.
+                if frame.kind == FrameKind::If && frame.params != frame.results {
+                    return Err(self.error(
+                        "type mismatch: if without else must not change the stack",
+                    ));
+                }
+                if !self.frames.is_empty() {
+                    self.push_types(&frame.results);
+                }
+            }
+            Operator::Br { relative_depth } => {
+                let types = self.label_types(relative_depth)?;
+                self.pop_types(&types)?;
+                self.set_unreachable();
+            }
+            Operator::BrIf { relative_depth } => {
+                let types = self.label_types(relative_depth)?;
+                self.pop_expected(I32)?;
+                self.pop_types(&types)?;
+                self.push_types(&types);
+            }
+            Operator::BrTable { ref table } => {
+                let (targets, default) = table.read_table();
+                let types = self.label_types(default)?;
+                for depth in targets {
+                    if self.label_types(depth)? != types {
+                        return Err(self.error(
+                            "type mismatch: br_table targets have inconsistent types",
+                        ));
+                    }
+                }
+                self.pop_expected(I32)?;
+                self.pop_types(&types)?;
+                self.set_unreachable();
+            }
+            Operator::Return => {
+                let results = self.frames[0].results.clone();
+                self.pop_types(&results)?;
+                self.set_unreachable();
+            }
+            Operator::Call { function_index } => {
+                let sig_index = match self.module.functions.get(function_index as usize) {
+                    Some(&sig_index) => sig_index,
+                    None => return Err(self.error(format!("unknown function {}", function_index))),
+                };
+                let ty = self.func_type(sig_index)?;
+                self.pop_types(&ty.params)?;
+                self.push_types(&ty.returns);
+            }
+            Operator::CallIndirect { index, table_index } => {
+                self.check_reserved(table_index)?;
+                if self.module.tables.is_empty() {
+                    return Err(self.error("unknown table 0"));
+                }
+                let ty = self.func_type(index)?;
+                self.pop_expected(I32)?;
+                self.pop_types(&ty.params)?;
+                self.push_types(&ty.returns);
+            }
+            Operator::Drop => {
+                self.pop()?;
+            }
+            Operator::Select => {
+                self.pop_expected(I32)?;
+                let ty = self.pop()?;
+                match ty {
+                    Some(ty) => {
+                        self.pop_expected(ty)?;
+                        self.push(ty);
+                    }
+                    None => {
+                        let ty = self.pop()?;
+                        self.operands.push(ty);
+                    }
+                }
+            }
+            Operator::GetLocal { local_index } => {
+                let ty = self.local(local_index)?;
+                self.push(ty);
+            }
+            Operator::SetLocal { local_index } => {
+                let ty = self.local(local_index)?;
+                self.pop_expected(ty)?;
+            }
+            Operator::TeeLocal { local_index } => {
+                let ty = self.local(local_index)?;
+                self.pop_expected(ty)?;
+                self.push(ty);
+            }
+            Operator::GetGlobal { global_index } => {
+                let global = self.global(global_index)?;
+                self.push(global.content_type);
+            }
+            Operator::SetGlobal { global_index } => {
+                let global = self.global(global_index)?;
+                if !global.mutable {
+                    return Err(self.error(format!("global {} is immutable", global_index)));
+                }
+                self.pop_expected(global.content_type)?;
+            }
+            Operator::CurrentMemory { reserved } => {
+                self.check_reserved(reserved)?;
+                self.check_memory()?;
+                self.push(I32);
+            }
+            Operator::GrowMemory { reserved } => {
+                self.check_reserved(reserved)?;
+                self.check_memory()?;
+                self.pop_expected(I32)?;
+                self.push(I32);
+            }
+            Operator::I32Const { .. } => self.push(I32),
+            Operator::I64Const { .. } => self.push(I64),
+            Operator::F32Const { .. } => self.push(F32),
+            Operator::F64Const { .. } => self.push(F64),
+            _ => return Err(self.error(format!("unsupported operator {:?}", op))),
+        }
+        Ok(())
+    }
+
+    /// Type check an operator from the sign-extension, bulk memory, or multi-value extensions.
+    fn ext_operator(&mut self, op: ExtOperator) -> ValidationResult<()> {
+        match op {
+            ExtOperator::I32Extend8S | ExtOperator::I32Extend16S => {
+                self.pop_expected(I32)?;
+                self.push(I32);
+            }
+            ExtOperator::I64Extend8S | ExtOperator::I64Extend16S | ExtOperator::I64Extend32S => {
+                self.pop_expected(I64)?;
+                self.push(I64);
+            }
+            ExtOperator::MemoryInit { segment } => {
+                self.check_memory()?;
+                self.use_data_segment(segment);
+                self.pop_types(&[I32, I32, I32])?;
+            }
+            ExtOperator::DataDrop { segment } => self.use_data_segment(segment),
+            ExtOperator::Block { sig } => {
+                let ty = self.func_type(sig)?;
+                self.pop_types(&ty.params)?;
+                self.push_frame(FrameKind::Block, ty.params, ty.returns);
+            }
+            ExtOperator::Loop { sig } => {
+                let ty = self.func_type(sig)?;
+                self.pop_types(&ty.params)?;
+                self.push_frame(FrameKind::Loop, ty.params, ty.returns);
+            }
+            ExtOperator::If { sig } => {
+                let ty = self.func_type(sig)?;
+                self.pop_expected(I32)?;
+                self.pop_types(&ty.params)?;
+                self.push_frame(FrameKind::If, ty.params, ty.returns);
+            }
+            ExtOperator::MemoryCopy | ExtOperator::MemoryFill => {
+                self.check_memory()?;
+                self.pop_types(&[I32, I32, I32])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a reference to a data segment, to be checked once the data section has been read.
+    fn use_data_segment(&mut self, segment: u32) {
+        if self.max_data_segment.map_or(true, |(max, _)| segment > max) {
+            self.max_data_segment = Some((segment, self.offset));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_module, ValidationError};
+
+    const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    /// Build a module with a single `(func (param i32) (result i32))` whose body is `body`,
+    /// followed by the sections in `tail`.
+    fn module(body: &[u8], tail: &[u8]) -> Vec<u8> {
+        let mut data = HEADER.to_vec();
+        data.extend_from_slice(&[0x01, 0x06, 0x01, 0x60, 0x01, 0x7f, 0x01, 0x7f]);
+        data.extend_from_slice(&[0x03, 0x02, 0x01, 0x00]);
+        data.extend_from_slice(tail);
+        data.extend_from_slice(&[0x0a, body.len() as u8 + 3, 0x01, body.len() as u8 + 1, 0x00]);
+        data.extend_from_slice(body);
+        data
+    }
+
+    fn error(data: &[u8]) -> ValidationError {
+        validate_module(data).unwrap_err()
+    }
+
+    #[test]
+    fn valid() {
+        validate_module(&HEADER).unwrap();
+        validate_module(&module(&[0x20, 0x00, 0x0b], &[])).unwrap();
+
+        // Unreachable code makes the operand stack polymorphic.
+        validate_module(&module(&[0x00, 0x6a, 0x0b], &[])).unwrap();
+
+        // (block (result i32) (br_if 0 (i32.const 1) (get_local 0)))
+        validate_module(&module(
+            &[0x02, 0x7f, 0x41, 0x01, 0x20, 0x00, 0x0d, 0x00, 0x0b, 0x0b],
+            &[],
+        )).unwrap();
+
+        // Sign-extension operators.
+        validate_module(&module(&[0x20, 0x00, 0xc0, 0x0b], &[])).unwrap();
+
+        // (block (type 0) (get_local 0))
+        validate_module(&module(&[0x20, 0x00, 0x02, 0x00, 0x0b, 0x0b], &[])).unwrap();
+    }
+
+    #[test]
+    fn function_bodies() {
+        // The body starts at offset 25: (i32.add (get_local 0) (i64.const 1))
+        let err = error(&module(&[0x20, 0x00, 0x42, 0x01, 0x6a, 0x0b], &[]));
+        assert_eq!(err.offset, 29);
+        assert_eq!(err.message, "type mismatch: expected i32, found i64");
+
+        let err = error(&module(&[0x20, 0x01, 0x0b], &[]));
+        assert_eq!(err.offset, 25);
+        assert_eq!(err.message, "unknown local 1");
+
+        let err = error(&module(&[0x0b], &[]));
+        assert_eq!(err.offset, 25);
+        assert_eq!(err.message, "type mismatch: operand stack underflow");
+
+        let err = error(&module(&[0x20, 0x00, 0x20, 0x00, 0x0b], &[]));
+        assert_eq!(
+            err.message,
+            "type mismatch: values remaining on the stack at the end of the block"
+        );
+
+        let err = error(&module(&[0x20, 0x00, 0x0c, 0x01, 0x0b], &[]));
+        assert_eq!(err.offset, 27);
+        assert_eq!(err.message, "unknown label 1");
+
+        let err = error(&module(&[0x20, 0x00, 0x02, 0x01, 0x0b, 0x0b], &[]));
+        assert_eq!(err.offset, 27);
+        assert_eq!(err.message, "unknown type 1");
+
+        let err = error(&module(&[0x20, 0x00, 0x28, 0x02, 0x00, 0x0b], &[]));
+        assert_eq!(err.offset, 27);
+        assert_eq!(err.message, "unknown memory 0");
+
+        let err = error(&module(&[0x20, 0x00, 0x0b, 0x01], &[]));
+        assert_eq!(err.offset, 28);
+        assert_eq!(err.message, "operators after the end of the function body");
+
+        let err = error(&module(&[0x20, 0x00], &[]));
+        assert_eq!(err.offset, 27);
+        assert_eq!(err.message, "unexpected end of function body");
+
+        // memory.init refers to a data segment that doesn't exist.
+        let mut data = HEADER.to_vec();
+        data.extend_from_slice(&[0x01, 0x04, 0x01, 0x60, 0x00, 0x00]);
+        data.extend_from_slice(&[0x03, 0x02, 0x01, 0x00]);
+        data.extend_from_slice(&[0x05, 0x03, 0x01, 0x00, 0x01]);
+        data.extend_from_slice(&[0x0a, 0x0e, 0x01, 0x0c, 0x00]);
+        data.extend_from_slice(&[0x41, 0x00, 0x41, 0x00, 0x41, 0x00, 0xfc, 0x08, 0x02, 0x00, 0x0b]);
+        let err = error(&data);
+        assert_eq!(err.offset, 34);
+        assert_eq!(err.message, "unknown data segment 2");
+    }
+
+    #[test]
+    fn module_structure() {
+        // (export "f" (func 1))
+        let err = error(&module(
+            &[0x20, 0x00, 0x0b],
+            &[0x07, 0x05, 0x01, 0x01, 0x66, 0x00, 0x01],
+        ));
+        assert_eq!(err.offset, 23);
+        assert_eq!(err.message, "unknown function 1");
+
+        // (export "f" (func 0)) (export "f" (func 0))
+        let err = error(&module(
+            &[0x20, 0x00, 0x0b],
+            &[0x07, 0x09, 0x02, 0x01, 0x66, 0x00, 0x00, 0x01, 0x66, 0x00, 0x00],
+        ));
+        assert_eq!(err.offset, 27);
+        assert_eq!(err.message, "duplicate export name \"f\"");
+
+        // (start 0) with a function that takes a parameter.
+        let err = error(&module(&[0x20, 0x00, 0x0b], &[0x08, 0x01, 0x00]));
+        assert_eq!(
+            err.message,
+            "start function must not take arguments or return values"
+        );
+
+        // Function section without a code section.
+        let mut data = HEADER.to_vec();
+        data.extend_from_slice(&[0x01, 0x04, 0x01, 0x60, 0x00, 0x00]);
+        data.extend_from_slice(&[0x03, 0x02, 0x01, 0x00]);
+        let err = error(&data);
+        assert_eq!(err.offset, 18);
+        assert_eq!(
+            err.message,
+            "function and code section have inconsistent lengths"
+        );
+    }
+}
@@ -2,7 +2,7 @@ extern crate cton_wasm;
 extern crate cretonne;
 extern crate tempdir;
 
-use cton_wasm::{translate_module, DummyEnvironment};
+use cton_wasm::{translate_module, validate_module, DummyEnvironment};
 use std::path::PathBuf;
 use std::fs::File;
 use std::error::Error;
@@ -20,18 +20,29 @@ use tempdir::TempDir;
 
 #[test]
 fn testsuite() {
-    let mut paths: Vec<_> = fs::read_dir("../../wasmtests")
-        .unwrap()
-        .map(|r| r.unwrap())
-        .collect();
-    paths.sort_by_key(|dir| dir.path());
     let flags = Flags::new(&settings::builder());
-    for path in paths {
-        let path = path.path();
+    for path in module_paths("../../wasmtests") {
         handle_module(path, &flags);
     }
 }
 
+/// Modules that are well-formed but don't pass validation, like the spec's `assert_invalid`
+/// modules.
+#[test]
+fn invalid() {
+    for path in module_paths("../../wasmtests/invalid") {
+        handle_rejected_module(path);
+    }
+}
+
+/// Modules that can't be decoded, like the spec's `assert_malformed` modules.
+#[test]
+fn malformed() {
+    for path in module_paths("../../wasmtests/malformed") {
+        handle_rejected_module(path);
+    }
+}
+
 #[test]
 fn return_at_end() {
     let mut flag_builder = settings::builder();
@@ -47,8 +58,23 @@ fn read_wasm_file(path: PathBuf) -> Result<Vec<u8>, io::Error> {
     Ok(buf)
 }
 
-fn handle_module(path: PathBuf, flags: &Flags) {
-    let data = match path.extension() {
+/// Get the sorted paths of the modules in `dir`, skipping subdirectories.
+fn module_paths(dir: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|r| r.unwrap().path())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Read the binary module in `path`, converting it from the text format if needed.
+///
+/// Returns `None` if the module is in the text format and `wat2wasm` isn't installed. When
+/// `check` is false, `wat2wasm` is asked not to validate the module.
+fn read_module(path: &PathBuf, check: bool) -> Option<Vec<u8>> {
+    Some(match path.extension() {
         None => {
             panic!("the file extension is not wasm or wat");
         }
@@ -59,11 +85,12 @@ fn handle_module(path: PathBuf, flags: &Flags) {
                     let tmp_dir = TempDir::new("cretonne-wasm").unwrap();
                     let file_path = tmp_dir.path().join("module.wasm");
                     File::create(file_path.clone()).unwrap();
-                    let result_output = Command::new("wat2wasm")
-                        .arg(path.clone())
-                        .arg("-o")
-                        .arg(file_path.to_str().unwrap())
-                        .output();
+                    let mut command = Command::new("wat2wasm");
+                    command.arg(path).arg("-o").arg(file_path.to_str().unwrap());
+                    if !check {
+                        command.arg("--no-check");
+                    }
+                    let result_output = command.output();
                     match result_output {
                         Err(e) => {
                             if e.kind() == io::ErrorKind::NotFound {
@@ -71,7 +98,7 @@ fn handle_module(path: PathBuf, flags: &Flags) {
                                     "wat2wasm not found; disabled test {}",
                                     path.to_str().unwrap()
                                 );
-                                return;
+                                return None;
                             }
                             panic!("error convering wat file: {}", e.description());
                         }
@@ -91,7 +118,15 @@ fn handle_module(path: PathBuf, flags: &Flags) {
                 None | Some(&_) => panic!("the file extension is not wasm or wat"),
             }
         }
+    })
+}
+
+fn handle_module(path: PathBuf, flags: &Flags) {
+    let data = match read_module(&path, true) {
+        Some(data) => data,
+        None => return,
     };
+    validate_module(&data).unwrap();
     let mut dummy_environ = DummyEnvironment::with_flags(flags.clone());
     translate_module(&data, &mut dummy_environ).unwrap();
     for func in &dummy_environ.info.function_bodies {
@@ -101,6 +136,16 @@ fn handle_module(path: PathBuf, flags: &Flags) {
     }
 }
 
+fn handle_rejected_module(path: PathBuf) {
+    let data = match read_module(&path, false) {
+        Some(data) => data,
+        None => return,
+    };
+    if validate_module(&data).is_ok() {
+        panic!("{} should have been rejected", path.display());
+    }
+}
+
 
 /// Pretty-print a verifier error.
 pub fn pretty_verifier_error(
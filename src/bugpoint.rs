@@ -0,0 +1,430 @@
+//! CLI tool to reduce a function that fails to compile.
+//!
+//! Reads a Cretonne IL file and finds the first function that makes `Context::compile` panic or
+//! return an error for the given ISA. Then repeatedly removes EBBs, instructions and EBB parameters
+//! from the function, and replaces values with constants, as long as the function still passes the
+//! verifier and fails to compile in the same way. The reduced function is printed as a `test
+//! compile` file.
+//!
+//! Two failures are the same if they are panics at the same source location, or errors with the
+//! same message when entity numbers are ignored.
+
+use cretonne::Context;
+use cretonne::cursor::{Cursor, FuncCursor};
+use cretonne::ir::{self, Function, Ebb, Inst, Value, Type, InstBuilder, Opcode, TrapCode};
+use cretonne::ir::instructions::BranchInfo;
+use cretonne::ir::immediates::{Ieee32, Ieee64};
+use cretonne::isa::TargetIsa;
+use cretonne::result::CtonError;
+use cretonne::verifier;
+use cretonne::write_function;
+use cton_reader::parse_functions;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use utils::{parse_sets_and_isa, read_to_string, OwnedFlagsOrIsa};
+use CommandResult;
+
+pub fn run(
+    files: Vec<String>,
+    flag_verbose: bool,
+    flag_set: Vec<String>,
+    flag_isa: String,
+) -> CommandResult {
+    let filename = match files.as_slice() {
+        &[ref f] => f.clone(),
+        _ => return Err("bugpoint reduces a single file".to_string()),
+    };
+    let isa = match parse_sets_and_isa(flag_set.clone(), flag_isa.clone())? {
+        OwnedFlagsOrIsa::Isa(isa) => isa,
+        OwnedFlagsOrIsa::Flags(_) => return Err("bugpoint requires a target isa".to_string()),
+    };
+    let buffer = read_to_string(&filename).map_err(
+        |e| format!("{}: {}", filename, e),
+    )?;
+    let functions = parse_functions(&buffer).map_err(
+        |e| format!("{}: {}", filename, e),
+    )?;
+
+    // Keep the panics of the code generator quiet while reducing.
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        let location = info.location().map(|l| format!("{}:{}", l.file(), l.line()));
+        PANIC_LOCATION.with(|p| *p.borrow_mut() = location);
+    }));
+    let result = reduce_first(functions, &*isa, flag_verbose);
+    panic::set_hook(default_hook);
+
+    let (func, failure) = result?;
+    let mut text = String::new();
+    write_function(&mut text, &func, None).map_err(|e| e.to_string())?;
+    println!("; Reduced from {} by cton-util bugpoint.", filename);
+    println!("; {}", failure.description);
+    println!("test compile");
+    for set in &flag_set {
+        println!("set {}", set);
+    }
+    println!("isa {}", flag_isa.trim());
+    println!("");
+    print!("{}", text);
+    Ok(())
+}
+
+thread_local! {
+    /// The source location of the last panic.
+    static PANIC_LOCATION: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// How compiling a function failed.
+struct Failure {
+    /// The part of the failure that must stay the same while reducing.
+    key: String,
+    /// A description of the failure.
+    description: String,
+}
+
+/// Reduce the first function in `functions` that fails to compile.
+fn reduce_first(
+    functions: Vec<Function>,
+    isa: &TargetIsa,
+    flag_verbose: bool,
+) -> Result<(Function, Failure), String> {
+    for func in functions {
+        if let Err(e) = verifier::verify_function(&func, isa) {
+            return Err(format!("{}: {}", func.name, e));
+        }
+        if let Some(failure) = check(&func, isa) {
+            if flag_verbose {
+                eprintln!("reducing {}: {}", func.name, failure.description);
+            }
+            let func = reduce(func, isa, &failure.key, flag_verbose);
+            return Ok((func, failure));
+        }
+        if flag_verbose {
+            eprintln!("{} compiles without errors", func.name);
+        }
+    }
+    Err("no function fails to compile".to_string())
+}
+
+/// Compile `func`, returning how it failed.
+///
+/// A function that doesn't pass the verifier is not considered a failure, since any function can
+/// be reduced to invalid IL.
+fn check(func: &Function, isa: &TargetIsa) -> Option<Failure> {
+    if verifier::verify_function(func, isa).is_err() {
+        return None;
+    }
+    let mut ctx = Context::new();
+    ctx.func = func.clone();
+    PANIC_LOCATION.with(|p| *p.borrow_mut() = None);
+    match panic::catch_unwind(AssertUnwindSafe(|| ctx.compile(isa))) {
+        Ok(Ok(_)) => None,
+        Ok(Err(e)) => {
+            let message = match e {
+                CtonError::Verifier(ref e) => e.message.clone(),
+                ref e => e.to_string(),
+            };
+            Some(Failure {
+                key: message.chars().filter(|c| !c.is_digit(10)).collect(),
+                description: e.to_string(),
+            })
+        }
+        Err(_) => {
+            let location = PANIC_LOCATION.with(|p| p.borrow_mut().take()).unwrap_or_default();
+            Some(Failure {
+                key: location.clone(),
+                description: format!("panicked at {}", location),
+            })
+        }
+    }
+}
+
+/// Ways to make a function smaller.
+#[derive(Clone, Copy, Debug)]
+enum Mutation {
+    /// Remove an EBB, replacing jumps to it with traps, removing conditional branches to it, and
+    /// clearing the jump table entries referring to it.
+    RemoveEbb,
+    /// Remove an instruction, replacing its results with constants.
+    RemoveInst,
+    /// Remove an EBB parameter, replacing it with a constant.
+    RemoveEbbParam,
+}
+
+const MUTATIONS: [Mutation; 3] = [
+    Mutation::RemoveEbb,
+    Mutation::RemoveInst,
+    Mutation::RemoveEbbParam,
+];
+
+impl Mutation {
+    /// The number of places in `func` where this mutation can be tried.
+    fn count(self, func: &Function) -> usize {
+        match self {
+            Mutation::RemoveEbb => func.layout.ebbs().count(),
+            Mutation::RemoveInst => insts(func).len(),
+            Mutation::RemoveEbbParam => ebb_params(func).len(),
+        }
+    }
+
+    /// Apply this mutation at the `i`th place in `func`. Return `false` if it doesn't apply.
+    fn apply(self, func: &mut Function, i: usize) -> bool {
+        match self {
+            Mutation::RemoveEbb => {
+                let ebb = func.layout.ebbs().nth(i).unwrap();
+                remove_ebb(func, ebb)
+            }
+            Mutation::RemoveInst => {
+                let inst = insts(func)[i];
+                remove_inst(func, inst)
+            }
+            Mutation::RemoveEbbParam => {
+                let (ebb, param) = ebb_params(func)[i];
+                remove_ebb_param(func, ebb, param)
+            }
+        }
+    }
+}
+
+/// Apply mutations to `func` until none of them can be applied without changing the failure.
+fn reduce(mut func: Function, isa: &TargetIsa, key: &str, flag_verbose: bool) -> Function {
+    loop {
+        let mut progress = false;
+        for &mutation in &MUTATIONS {
+            let mut i = 0;
+            while i < mutation.count(&func) {
+                let mut candidate = func.clone();
+                let interesting = mutation.apply(&mut candidate, i) &&
+                    check(&candidate, isa).map_or(false, |f| f.key == key);
+                if interesting {
+                    if flag_verbose {
+                        eprintln!("{:?} {}", mutation, i);
+                    }
+                    func = candidate;
+                    progress = true;
+                } else {
+                    i += 1;
+                }
+            }
+        }
+        if !progress {
+            return func;
+        }
+    }
+}
+
+/// Get all the instructions in `func` in layout order.
+fn insts(func: &Function) -> Vec<Inst> {
+    func.layout
+        .ebbs()
+        .flat_map(|ebb| func.layout.ebb_insts(ebb))
+        .collect()
+}
+
+/// Get the parameters of all the EBBs in `func`, except for the entry block.
+fn ebb_params(func: &Function) -> Vec<(Ebb, Value)> {
+    func.layout
+        .ebbs()
+        .skip(1)
+        .flat_map(|ebb| {
+            func.dfg.ebb_params(ebb).iter().map(move |&v| (ebb, v))
+        })
+        .collect()
+}
+
+/// Insert a constant of type `ty` before `inst`, if that type has constants.
+fn make_const(func: &mut Function, ty: Type, inst: Inst) -> Option<Value> {
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    if ty.is_int() {
+        Some(pos.ins().iconst(ty, 0))
+    } else if ty.is_bool() {
+        Some(pos.ins().bconst(ty, false))
+    } else if ty == ir::types::F32 {
+        Some(pos.ins().f32const(Ieee32::with_bits(0)))
+    } else if ty == ir::types::F64 {
+        Some(pos.ins().f64const(Ieee64::with_bits(0)))
+    } else {
+        None
+    }
+}
+
+/// Replace all uses of `old` in `func` with `new`.
+fn replace_uses(func: &mut Function, old: Value, new: Value) {
+    for inst in insts(func) {
+        func.dfg.resolve_aliases_in_arguments(inst);
+        for arg in func.dfg.inst_args_mut(inst) {
+            if *arg == old {
+                *arg = new;
+            }
+        }
+    }
+}
+
+fn is_used(func: &Function, value: Value) -> bool {
+    insts(func).into_iter().any(|inst| {
+        func.dfg.inst_args(inst).iter().any(|&arg| {
+            func.dfg.resolve_aliases(arg) == value
+        })
+    })
+}
+
+fn remove_inst(func: &mut Function, inst: Inst) -> bool {
+    if func.dfg[inst].opcode().is_terminator() {
+        return false;
+    }
+    let results = func.dfg.inst_results(inst).to_vec();
+    for result in results {
+        if !is_used(func, result) {
+            continue;
+        }
+        // Replacing a constant with another constant doesn't make progress.
+        match func.dfg[inst].opcode() {
+            Opcode::Iconst | Opcode::F32const | Opcode::F64const | Opcode::Bconst => return false,
+            _ => {}
+        }
+        let ty = func.dfg.value_type(result);
+        match make_const(func, ty, inst) {
+            Some(c) => replace_uses(func, result, c),
+            None => return false,
+        }
+    }
+    func.layout.remove_inst(inst);
+    true
+}
+
+fn remove_ebb(func: &mut Function, ebb: Ebb) -> bool {
+    if func.layout.entry_block() == Some(ebb) {
+        return false;
+    }
+    for inst in insts(func) {
+        if func.layout.inst_ebb(inst) == Some(ebb) {
+            continue;
+        }
+        match func.dfg.analyze_branch(inst) {
+            BranchInfo::SingleDest(dest, _) if dest == ebb => {
+                if func.dfg[inst].opcode().is_terminator() {
+                    func.dfg.replace(inst).trap(TrapCode::User(0));
+                } else {
+                    func.layout.remove_inst(inst);
+                }
+            }
+            _ => {}
+        }
+    }
+    while let Some(inst) = func.layout.first_inst(ebb) {
+        func.layout.remove_inst(inst);
+    }
+    func.layout.remove_ebb(ebb);
+
+    // A `br_table` falls through for a cleared entry, so clearing the entries referring to `ebb`
+    // works like removing a conditional branch. This also covers jump tables that are no longer
+    // used by any instruction.
+    for jt in func.jump_tables.keys() {
+        let data = &mut func.jump_tables[jt];
+        for idx in 0..data.len() {
+            if data.get_entry(idx) == Some(ebb) {
+                data.clear_entry(idx);
+            }
+        }
+    }
+    true
+}
+
+fn remove_ebb_param(func: &mut Function, ebb: Ebb, param: Value) -> bool {
+    // Jump tables can't pass arguments, so an EBB with parameters shouldn't be in one. Leave such
+    // an EBB alone instead of guessing how the branches to it should change.
+    if func.jump_tables.keys().any(|jt| func.jump_tables[jt].branches_to(ebb)) {
+        return false;
+    }
+    let first = match func.layout.first_inst(ebb) {
+        Some(inst) => inst,
+        None => return false,
+    };
+    if is_used(func, param) {
+        let ty = func.dfg.value_type(param);
+        match make_const(func, ty, first) {
+            Some(c) => replace_uses(func, param, c),
+            None => return false,
+        }
+    }
+    let num = func.dfg
+        .ebb_params(ebb)
+        .iter()
+        .position(|&v| v == param)
+        .unwrap();
+    func.dfg.remove_ebb_param(param);
+
+    // Remove the corresponding argument from all the branches to `ebb`.
+    for inst in insts(func) {
+        let targets_ebb = match func.dfg.analyze_branch(inst) {
+            BranchInfo::SingleDest(dest, _) => dest == ebb,
+            _ => false,
+        };
+        if targets_ebb {
+            let index = func.dfg.inst_fixed_args(inst).len() + num;
+            let mut args = func.dfg[inst].take_value_list().unwrap();
+            args.remove(index, &mut func.dfg.value_lists);
+            func.dfg[inst].put_value_list(args);
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use cretonne::isa;
+    use cretonne::settings::{self, Configurable};
+    use cretonne::verifier;
+    use cton_reader::parse_functions;
+    use super::{check, reduce_first, remove_ebb};
+
+    #[test]
+    fn reduce_jump_table() {
+        let functions = parse_functions(
+            "function %table(i32) -> i32 {
+                 jt0 = jump_table ebb1, ebb2, 0, ebb1
+
+             ebb0(v0: i32):
+                 br_table v0, jt0
+                 jump ebb3(v0)
+
+             ebb1:
+                 v1 = iconst.i32 1
+                 jump ebb3(v1)
+
+             ebb2:
+                 v2 = iadd_imm v0, 2
+                 jump ebb3(v2)
+
+             ebb3(v3: i32):
+                 v4 = imul v3, v3
+                 return v4
+             }",
+        ).unwrap();
+
+        // Position-independent code isn't supported for 32-bit Intel, so every function fails to
+        // compile with the same error.
+        let mut flag_builder = settings::builder();
+        flag_builder.enable("is_pic").unwrap();
+        let isa = match isa::lookup("intel") {
+            Ok(isa_builder) => isa_builder.finish(settings::Flags::new(&flag_builder)),
+            Err(_) => return,
+        };
+
+        // Removing an EBB in the jump table clears its entries, and the `br_table` stays valid.
+        let mut func = functions[0].clone();
+        let ebb1 = func.layout.ebbs().nth(1).unwrap();
+        assert!(remove_ebb(&mut func, ebb1));
+        verifier::verify_function(&func, &*isa).unwrap();
+        let jt0 = func.jump_tables.keys().next().unwrap();
+        assert!(!func.jump_tables[jt0].branches_to(ebb1));
+
+        let (func, failure) = reduce_first(functions, &*isa, false).ok().unwrap();
+        verifier::verify_function(&func, &*isa).unwrap();
+        assert_eq!(check(&func, &*isa).map(|f| f.key), Some(failure.key));
+        assert_eq!(func.layout.ebbs().count(), 1);
+        assert!(func.jump_tables.keys().all(|jt| {
+            func.jump_tables[jt].entries().next().is_none()
+        }));
+    }
+}
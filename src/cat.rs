@@ -2,15 +2,33 @@
 //!
 //! Read a sequence of Cretonne IL files and print them again to stdout. This has the effect of
 //! normalizing formatting and removing comments.
+//!
+//! The input files can be in either the text or the binary IL format. With `--binary`, all the
+//! functions are written to stdout in the binary format instead, so this command can convert
+//! between the two formats.
 
 use std::borrow::Cow;
+use std::io::{self, Write};
+use std::str;
+use cretonne::binformat;
 use cretonne::ir::Function;
 use cton_reader::{parse_functions, TestCommand};
 use CommandResult;
-use utils::read_to_string;
+use utils::read_to_end;
 use filetest::subtest::{self, SubTest, Context, Result as STResult};
 
-pub fn run(files: Vec<String>) -> CommandResult {
+pub fn run(files: Vec<String>, flag_binary: bool) -> CommandResult {
+    if flag_binary {
+        let mut funcs = Vec::new();
+        for f in files {
+            funcs.extend(read_functions(&f)?);
+        }
+        let bytes = binformat::write_functions(&funcs, true);
+        return io::stdout().write_all(&bytes).map_err(
+            |e| e.to_string(),
+        );
+    }
+
     for (i, f) in files.into_iter().enumerate() {
         if i != 0 {
             println!("");
@@ -20,13 +38,22 @@ pub fn run(files: Vec<String>) -> CommandResult {
     Ok(())
 }
 
-fn cat_one(filename: String) -> CommandResult {
-    let buffer = read_to_string(&filename).map_err(
+/// Read the functions in `filename`, which can be in the text or binary IL format.
+fn read_functions(filename: &str) -> Result<Vec<Function>, String> {
+    let buffer = read_to_end(filename).map_err(
         |e| format!("{}: {}", filename, e),
     )?;
-    let items = parse_functions(&buffer).map_err(
+    if binformat::is_binary_format(&buffer) {
+        return binformat::read_functions(&buffer).map_err(|e| format!("{}: {}", filename, e));
+    }
+    let text = str::from_utf8(&buffer).map_err(
         |e| format!("{}: {}", filename, e),
     )?;
+    parse_functions(text).map_err(|e| format!("{}: {}", filename, e))
+}
+
+fn cat_one(filename: String) -> CommandResult {
+    let items = read_functions(&filename)?;
 
     for (idx, func) in items.into_iter().enumerate() {
         if idx != 0 {
@@ -5,6 +5,10 @@
 use cton_reader::parse_functions;
 use std::path::PathBuf;
 use cretonne::Context;
+use cretonne::binemit::{Addend, CodeOffset, RelocSink, Reloc, NullStackMapSink,
+                        NullCallSiteSink};
+use cretonne::ir;
+use cretonne::isa::TargetIsa;
 use cretonne::settings::FlagsOrIsa;
 use std::path::Path;
 use utils::{pretty_error, read_to_string, parse_sets_and_isa};
@@ -12,6 +16,7 @@ use utils::{pretty_error, read_to_string, parse_sets_and_isa};
 pub fn run(
     files: Vec<String>,
     flag_print: bool,
+    flag_disasm: bool,
     flag_set: Vec<String>,
     flag_isa: String,
 ) -> Result<(), String> {
@@ -20,13 +25,20 @@ pub fn run(
     for filename in files {
         let path = Path::new(&filename);
         let name = String::from(path.as_os_str().to_string_lossy());
-        handle_module(flag_print, path.to_path_buf(), name, parsed.as_fisa())?;
+        handle_module(
+            flag_print,
+            flag_disasm,
+            path.to_path_buf(),
+            name,
+            parsed.as_fisa(),
+        )?;
     }
     Ok(())
 }
 
 fn handle_module(
     flag_print: bool,
+    flag_disasm: bool,
     path: PathBuf,
     name: String,
     fisa: FlagsOrIsa,
@@ -40,16 +52,78 @@ fn handle_module(
     for func in items.into_iter() {
         let mut context = Context::new();
         context.func = func;
-        if let Some(isa) = fisa.isa {
-            context.compile(isa).map_err(|err| {
-                pretty_error(&context.func, fisa.isa, err)
-            })?;
-        } else {
-            return Err(String::from("compilation requires a target isa"));
-        }
+        let isa = match fisa.isa {
+            Some(isa) => isa,
+            None => return Err(String::from("compilation requires a target isa")),
+        };
+        let size = context.compile(isa).map_err(|err| {
+            pretty_error(&context.func, fisa.isa, err)
+        })?;
         if flag_print {
             println!("{}", context.func.display(fisa.isa));
         }
+        if flag_disasm {
+            let mut code = vec![0; size as usize];
+            context.emit_to_memory(
+                code.as_mut_ptr(),
+                &mut IgnoreRelocs,
+                &mut NullStackMapSink,
+                &mut NullCallSiteSink,
+                isa,
+            );
+            println!("; {}: {} bytes", context.func.name, size);
+            print_disasm(&code, isa);
+        }
     }
     Ok(())
 }
+
+/// Print a disassembly listing of `code`.
+///
+/// Each instruction is followed by a comment with the Cretonne instruction that produced it. Bytes
+/// that can't be decoded are printed one at a time.
+fn print_disasm(code: &[u8], isa: &TargetIsa) {
+    let encinfo = isa.encoding_info();
+    let regs = isa.register_info();
+    let mut offset = 0;
+    while (offset as usize) < code.len() {
+        match isa.decode_inst(code, offset) {
+            Some(inst) => {
+                let end = (offset + inst.size) as usize;
+                println!(
+                    "{:04x}: {:<24} {:<32} ; [{}] {}",
+                    offset,
+                    hex_bytes(&code[offset as usize..end]),
+                    inst.asm,
+                    encinfo.display(inst.encoding),
+                    inst.display(&regs)
+                );
+                offset += inst.size;
+            }
+            None => {
+                println!("{:04x}: {:<24} ???", offset, hex_bytes(&code[offset as usize..][..1]));
+                offset += 1;
+            }
+        }
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A `RelocSink` that leaves the relocated bytes alone.
+///
+/// The disassembler doesn't look at relocated fields.
+struct IgnoreRelocs;
+
+impl RelocSink for IgnoreRelocs {
+    fn reloc_ebb(&mut self, _: CodeOffset, _: Reloc, _: ir::Ebb) {}
+    fn reloc_func(&mut self, _: CodeOffset, _: Reloc, _: ir::FuncRef) {}
+    fn reloc_external(&mut self, _: CodeOffset, _: Reloc, _: &ir::FunctionName, _: Addend) {}
+    fn reloc_jt(&mut self, _: CodeOffset, _: Reloc, _: ir::JumpTable) {}
+}
@@ -1,12 +1,17 @@
 #[macro_use(dbg)]
 extern crate cretonne;
 extern crate cton_reader;
+extern crate cton_interpreter;
+extern crate cton_fuzzgen;
+extern crate cton_native;
 extern crate cton_wasm;
 extern crate docopt;
 #[macro_use]
 extern crate serde_derive;
 extern crate filecheck;
+extern crate libc;
 extern crate num_cpus;
+extern crate regex;
 extern crate tempdir;
 extern crate term;
 
@@ -22,17 +27,25 @@ mod print_cfg;
 mod rsfilecheck;
 mod wasm;
 mod compile;
+mod opt;
+mod difftest;
+mod bugpoint;
+mod fuzzgen;
 
 const USAGE: &str = "
 Cretonne code generator utility
 
 Usage:
-    cton-util test [-v] <file>...
-    cton-util cat <file>...
+    cton-util test [-v] [--bless] [options] <file>...
+    cton-util cat [-b] <file>...
     cton-util filecheck [-v] <file>
     cton-util print-cfg <file>...
-    cton-util compile [-vp] [--set <set>]... [--isa <isa>] <file>...
-    cton-util wasm [-ctvp] [--set <set>]... [--isa <isa>] <file>...
+    cton-util compile [-vpD] [--set <set>]... [--isa <isa>] <file>...
+    cton-util opt [-vp] --passes=<passes> [options] [--set <set>]... [--isa <isa>] <file>...
+    cton-util difftest [-v] [--timeout=<secs>] [--set <set>]... <file>...
+    cton-util fuzzgen <file>...
+    cton-util bugpoint [-v] [--set <set>]... [--isa <isa>] <file>
+    cton-util wasm [-ctvp] [--no-validate] [--set <set>]... [--isa <isa>] <file>...
     cton-util --help | --version
 
 Options:
@@ -42,6 +55,24 @@ Options:
     -c, --check-translation
                     just checks the correctness of Cretonne IL translated from WebAssembly
     -p, --print     print the resulting Cretonne IL
+    -D, --disasm    disassemble the compiled machine code
+    -b, --binary    write functions in the binary IL format
+    --bless         update the expectations in test files instead of failing
+    --format=<fmt>  test report format: text, junit, or json [default: text]
+    --filter=<regex>
+                    only run test files whose path matches the regex
+    --subtest=<name>
+                    only run the named test command in each test file
+    --slowest=<n>   list the n slowest tests [default: 0]
+    --passes=<passes>
+                    comma-separated list of passes to run, in order
+    --print-after-all
+                    print the Cretonne IL after each pass
+    --verify-each   verify the Cretonne IL after each pass
+    --time-passes   report the time spent in each pass
+    --no-validate   skip validation of WebAssembly modules before translating them
+    --timeout=<secs>
+                    kill natively called functions after this many seconds [default: 10]
     -h, --help      print this help message
     --set=<set>     configure Cretonne settings
     --isa=<isa>     specify the Cretonne ISA
@@ -56,12 +87,29 @@ struct Args {
     cmd_filecheck: bool,
     cmd_print_cfg: bool,
     cmd_compile: bool,
+    cmd_opt: bool,
+    cmd_difftest: bool,
+    cmd_fuzzgen: bool,
+    cmd_bugpoint: bool,
     cmd_wasm: bool,
     arg_file: Vec<String>,
     flag_just_decode: bool,
     flag_check_translation: bool,
     flag_print: bool,
+    flag_disasm: bool,
+    flag_binary: bool,
+    flag_no_validate: bool,
+    flag_passes: String,
+    flag_print_after_all: bool,
+    flag_verify_each: bool,
+    flag_time_passes: bool,
     flag_verbose: bool,
+    flag_bless: bool,
+    flag_format: String,
+    flag_filter: String,
+    flag_subtest: String,
+    flag_slowest: usize,
+    flag_timeout: u32,
     flag_set: Vec<String>,
     flag_isa: String,
 }
@@ -82,15 +130,56 @@ fn cton_util() -> CommandResult {
 
     // Find the sub-command to execute.
     if args.cmd_test {
-        filetest::run(args.flag_verbose, args.arg_file)
+        filetest::run(
+            args.flag_verbose,
+            args.flag_bless,
+            &args.flag_format,
+            &args.flag_filter,
+            &args.flag_subtest,
+            args.flag_slowest,
+            args.arg_file,
+        )
     } else if args.cmd_cat {
-        cat::run(args.arg_file)
+        cat::run(args.arg_file, args.flag_binary)
     } else if args.cmd_filecheck {
         rsfilecheck::run(args.arg_file, args.flag_verbose)
     } else if args.cmd_print_cfg {
         print_cfg::run(args.arg_file)
     } else if args.cmd_compile {
-        compile::run(args.arg_file, args.flag_print, args.flag_set, args.flag_isa)
+        compile::run(
+            args.arg_file,
+            args.flag_print,
+            args.flag_disasm,
+            args.flag_set,
+            args.flag_isa,
+        )
+    } else if args.cmd_opt {
+        opt::run(
+            args.arg_file,
+            args.flag_passes,
+            args.flag_print,
+            args.flag_print_after_all,
+            args.flag_verify_each,
+            args.flag_time_passes,
+            args.flag_set,
+            args.flag_isa,
+        )
+    } else if args.cmd_difftest {
+        difftest::run(
+            args.arg_file,
+            args.flag_verbose,
+            args.flag_timeout,
+            args.flag_set,
+        )
+    } else if args.cmd_fuzzgen {
+        fuzzgen::run(args.arg_file)
+    } else if args.cmd_bugpoint {
+        bugpoint::run(
+            args.arg_file,
+            args.flag_verbose,
+            args.flag_set,
+            args.flag_isa,
+        )
     } else if args.cmd_wasm {
         wasm::run(
             args.arg_file,
@@ -98,6 +187,7 @@ fn cton_util() -> CommandResult {
             args.flag_just_decode,
             args.flag_check_translation,
             args.flag_print,
+            args.flag_no_validate,
             args.flag_set,
             args.flag_isa,
         )
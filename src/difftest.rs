@@ -0,0 +1,747 @@
+//! CLI tool to compare interpreted Cretonne IL against natively compiled code.
+//!
+//! Reads `.cton` test files and evaluates every `; run:` directive twice: once by interpreting the
+//! original IL, and once by compiling all the functions in the file for the host with
+//! `Context::compile` and calling the resulting machine code. The expected results written in the
+//! directives are ignored; the two executions are only compared with each other.
+//!
+//! Native calls run in a child process, so a trap in the compiled code is observed as a signal
+//! instead of killing `cton-util`. The interpreter and the native code agree on a trap if both of
+//! them trapped, regardless of the trap code. A child that runs for longer than the timeout is
+//! killed, and counts as a mismatch.
+//!
+//! Native execution is supported on x86-64 Unix hosts for functions taking up to six integer or
+//! `b1` arguments and up to eight float arguments, and returning at most one value. Integer
+//! arguments must be at least 32 bits wide, and SIMD values can't be used anywhere in the
+//! function. Other functions are reported as unsupported and their directives are skipped.
+
+use cretonne::Context;
+use cretonne::binemit::{RelocRecorder, Relocation, RelocTarget, NullStackMapSink,
+                        NullCallSiteSink};
+use cretonne::ir::{Function, FunctionName, Inst, Opcode, Type, ValueLoc};
+use cretonne::ir::types;
+use cretonne::isa::TargetIsa;
+use cretonne::settings;
+use cton_interpreter::{Interpreter, CallOutcome, DataValue};
+use cton_native;
+use cton_reader::{parse_test, parse_options, Location};
+use filetest::run::{parse_invocation, display_list, Invocation};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use utils::{pretty_error, read_to_string, match_directive};
+use CommandResult;
+
+pub fn run(
+    files: Vec<String>,
+    flag_verbose: bool,
+    flag_timeout: u32,
+    flag_set: Vec<String>,
+) -> CommandResult {
+    let isa = host_isa(flag_set)?;
+    let mut mismatches = 0;
+    for filename in files {
+        let path = Path::new(&filename);
+        let name = String::from(path.as_os_str().to_string_lossy());
+        mismatches += handle_file(flag_verbose, flag_timeout, path, &name, &*isa)?;
+    }
+    match mismatches {
+        0 => Ok(()),
+        1 => Err("1 mismatch between interpreted and native code".to_string()),
+        n => Err(format!("{} mismatches between interpreted and native code", n)),
+    }
+}
+
+/// Get the `TargetIsa` for the host, with the settings in `flag_set` applied.
+fn host_isa(flag_set: Vec<String>) -> Result<Box<TargetIsa>, String> {
+    let (mut flag_builder, isa_builder) = cton_native::builders().map_err(|_| {
+        "the host machine is not supported".to_string()
+    })?;
+    parse_options(
+        flag_set.iter().map(|x| x.as_str()),
+        &mut flag_builder,
+        &Location { line_number: 0 },
+    ).map_err(|err| err.to_string())?;
+    Ok(isa_builder.finish(settings::Flags::new(&flag_builder)))
+}
+
+/// Run all the `run:` directives in a file, returning the number of mismatches.
+///
+/// Native calls are killed after `timeout` seconds.
+fn handle_file(
+    flag_verbose: bool,
+    timeout: u32,
+    path: &Path,
+    name: &str,
+    isa: &TargetIsa,
+) -> Result<usize, String> {
+    let buffer = read_to_string(path).map_err(
+        |e| format!("{}: {}", name, e),
+    )?;
+    let testfile = parse_test(&buffer).map_err(|e| format!("{}: {}", name, e))?;
+    let functions = testfile
+        .functions
+        .iter()
+        .map(|&(ref func, _)| func.clone())
+        .collect::<Vec<_>>();
+    let (code, failed, unsupported) = compile_functions(&functions, isa).map_err(
+        |e| format!("{}: {}", name, e),
+    )?;
+
+    // A function that can't be compiled is a mismatch by itself. A function that can't be called
+    // natively is not.
+    let mut mismatches = failed.len();
+    for &(ref func, ref err) in &failed {
+        println!("{}: function {}: {}", name, func, err);
+    }
+    for &(ref func, ref reason) in &unsupported {
+        println!("{}: function {}: unsupported: {}", name, func, reason);
+    }
+    for &(_, ref details) in &testfile.functions {
+        for comment in &details.comments {
+            if let Some(text) = match_directive(comment.text, "run:") {
+                let matched = run_directive(
+                    flag_verbose,
+                    timeout,
+                    text,
+                    &functions,
+                    &code,
+                    &failed,
+                    &unsupported,
+                ).map_err(|e| format!("{}: run: {}: {}", name, text, e))?;
+                if !matched {
+                    println!("{}: run: {}", name, text);
+                    mismatches += 1;
+                }
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+/// Evaluate one directive with the interpreter and natively. Return `false` on a mismatch.
+fn run_directive(
+    flag_verbose: bool,
+    timeout: u32,
+    text: &str,
+    functions: &[Function],
+    code: &native::CodeMemory,
+    failed: &[(FunctionName, String)],
+    unsupported: &[(FunctionName, String)],
+) -> Result<bool, String> {
+    let Invocation { name, args, .. } = parse_invocation(text)?;
+    let index = functions.iter().position(|f| f.name == name).ok_or_else(
+        || {
+            format!("no function named {}", name)
+        },
+    )?;
+    let sig = &functions[index].signature;
+    if unsupported.iter().any(|&(ref n, _)| *n == name) {
+        if flag_verbose {
+            println!("skipping {}: {} is unsupported", text, name);
+        }
+        return Ok(true);
+    }
+    if failed.iter().any(|&(ref n, _)| *n == name) {
+        if flag_verbose {
+            println!("skipping {}: {} failed to compile", text, name);
+        }
+        return Ok(true);
+    }
+    if args.len() != sig.params.len() {
+        return Err(format!("{} expects {} arguments", name, sig.params.len()));
+    }
+    let args = args.iter()
+        .zip(&sig.params)
+        .map(|(text, param)| DataValue::parse(text, param.value_type))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let interpreted = match Interpreter::new(functions).call(index, &args) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            if flag_verbose {
+                println!("skipping {}: {}", text, e);
+            }
+            return Ok(true);
+        }
+    };
+    let native = code.call(&name, sig, &args, timeout)?;
+
+    let matched = same_outcome(&interpreted, &native);
+    if flag_verbose || !matched {
+        let interpreted = match interpreted {
+            CallOutcome::Return(ref values) => display_list(values),
+            CallOutcome::Trap(code) => format!("trap {}", code),
+        };
+        println!(
+            "{}: interpreter: {}, native: {}",
+            text,
+            interpreted,
+            native
+        );
+    }
+    Ok(matched)
+}
+
+/// Do the interpreter and the native code agree?
+fn same_outcome(interpreted: &CallOutcome, native: &native::Outcome) -> bool {
+    match (interpreted, native) {
+        (&CallOutcome::Return(ref a), &native::Outcome::Return(ref b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.same_as(y))
+        }
+        (&CallOutcome::Trap(_), &native::Outcome::Signal(_)) => true,
+        _ => false,
+    }
+}
+
+/// Functions left out of the executable memory, with the reason.
+type Excluded = Vec<(FunctionName, String)>;
+
+/// Compile `functions` for `isa` and link them into executable memory.
+///
+/// Functions that fail to compile, or that call a function that failed, are left out of the
+/// executable memory and returned along with the error message. A panic in the code generator
+/// counts as a failure to compile, and so does an instruction that was left without an encoding.
+///
+/// Functions that can't be called natively are not compiled at all. They are returned separately
+/// with the reason, along with the functions calling them.
+fn compile_functions(
+    functions: &[Function],
+    isa: &TargetIsa,
+) -> Result<(native::CodeMemory, Excluded, Excluded), String> {
+    let mut buffer = Vec::new();
+    let mut compiled = Vec::new();
+    let mut failed = Vec::new();
+    let mut unsupported = Vec::new();
+    for func in functions {
+        if let Err(reason) = check_function(func) {
+            unsupported.push((func.name.clone(), reason));
+            continue;
+        }
+        let mut ctx = Context::new();
+        ctx.func = func.clone();
+        let size = match panic::catch_unwind(AssertUnwindSafe(|| ctx.compile(isa))) {
+            Ok(Ok(size)) => size,
+            Ok(Err(e)) => {
+                failed.push((func.name.clone(), pretty_error(&ctx.func, Some(isa), e)));
+                continue;
+            }
+            Err(_) => {
+                failed.push((func.name.clone(), "the code generator panicked".to_string()));
+                continue;
+            }
+        };
+        if let Some(inst) = unencoded_inst(&ctx.func) {
+            let err = format!("no encoding for {}", ctx.func.dfg.display_inst(inst, isa));
+            failed.push((func.name.clone(), err));
+            continue;
+        }
+
+        // Start each function on a 16-byte boundary.
+        let start = (buffer.len() + 15) & !15;
+        buffer.resize(start + size as usize, 0);
+        let mut sink = RelocRecorder::new(&ctx.func);
+        ctx.emit_to_memory(
+            buffer[start..].as_mut_ptr(),
+            &mut sink,
+            &mut NullStackMapSink,
+            &mut NullCallSiteSink,
+            isa,
+        );
+        let mut relocs = Vec::new();
+        for Relocation { offset, reloc, target } in sink.into_relocs() {
+            let target = match target {
+                RelocTarget::Func(name) => Err(name),
+                RelocTarget::Ebb(ebb) => Ok(start + ctx.func.offsets[ebb] as usize),
+                RelocTarget::JumpTable(jt) => {
+                    return Err(format!("{}: can't relocate {}", func.name, jt))
+                }
+                RelocTarget::External(name, _) => {
+                    return Err(format!("{}: can't relocate {}", func.name, name))
+                }
+            };
+            relocs.push((start + offset as usize, reloc, target));
+        }
+        compiled.push((func.name.clone(), start, relocs));
+    }
+
+    // Drop the functions calling a function that isn't available, until no more are dropped.
+    loop {
+        let missing = compiled.iter().position(|&(_, _, ref relocs)| {
+            relocs.iter().any(|&(_, _, ref target)| match *target {
+                Err(ref name) => !compiled.iter().any(|&(ref n, _, _)| n == name),
+                Ok(_) => false,
+            })
+        });
+        match missing {
+            Some(index) => {
+                let (name, _, relocs) = compiled.remove(index);
+                let calls_unsupported = relocs.iter().any(|&(_, _, ref target)| match *target {
+                    Err(ref callee) => unsupported.iter().any(|&(ref n, _)| n == callee),
+                    Ok(_) => false,
+                });
+                if calls_unsupported {
+                    unsupported.push((name, "calls an unsupported function".to_string()));
+                } else {
+                    failed.push((name, "calls a function that failed to compile".to_string()));
+                }
+            }
+            None => break,
+        }
+    }
+
+    let mut code = native::CodeMemory::new(&buffer)?;
+    let starts = compiled
+        .iter()
+        .map(|&(ref name, start, _)| (name.clone(), start))
+        .collect::<Vec<_>>();
+    for (_, _, relocs) in compiled {
+        for (site, reloc, target) in relocs {
+            let target = match target {
+                Ok(offset) => offset,
+                Err(name) => {
+                    starts
+                        .iter()
+                        .find(|&&(ref n, _)| *n == name)
+                        .map(|&(_, start)| start)
+                        .expect("calls to missing functions were dropped")
+                }
+            };
+            code.relocate(site, isa.reloc_names()[reloc.0 as usize], target)?;
+        }
+    }
+    code.finish(starts)?;
+    Ok((code, failed, unsupported))
+}
+
+/// Check that `func` can be compiled and called natively.
+fn check_function(func: &Function) -> Result<(), String> {
+    native::check_signature(&func.signature)?;
+    for ebb in func.layout.ebbs() {
+        let params = func.dfg.ebb_params(ebb).iter();
+        let results = func.layout.ebb_insts(ebb).flat_map(|inst| func.dfg.inst_results(inst));
+        for &value in params.chain(results) {
+            if func.dfg.value_type(value).is_vector() {
+                return Err("SIMD values are not supported".to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find an instruction in the compiled `func` that has no encoding, but isn't a ghost instruction.
+///
+/// An instruction without an encoding emits no code. That is only correct for `fallthrough`, and
+/// for instructions whose results were never assigned a location because no real instruction uses
+/// them.
+fn unencoded_inst(func: &Function) -> Option<Inst> {
+    func.layout.ebbs().flat_map(|ebb| func.layout.ebb_insts(ebb)).find(|&inst| {
+        if func.encodings[inst].is_legal() || func.dfg[inst].opcode() == Opcode::Fallthrough {
+            return false;
+        }
+        let results = func.dfg.inst_results(inst);
+        results.is_empty() || results.iter().any(|&v| func.locations[v] != ValueLoc::Unassigned)
+    })
+}
+
+/// Is `ty` passed in an integer register?
+fn is_int_like(ty: Type) -> bool {
+    ty.is_int() || ty == types::B1
+}
+
+#[cfg(all(unix, target_arch = "x86_64"))]
+mod native {
+    use cretonne::ir::{FunctionName, Signature, ArgumentPurpose};
+    use cretonne::ir::types;
+    use cton_interpreter::DataValue;
+    use filetest::run::display_list;
+    use libc;
+    use std::fmt;
+    use std::mem;
+    use std::ptr;
+    use super::is_int_like;
+
+    /// Integer argument registers in the System V ABI.
+    const INT_ARGS: usize = 6;
+
+    /// Float argument registers in the System V ABI.
+    const FLOAT_ARGS: usize = 8;
+
+    /// Every supported function can be called through this type. Integer and float arguments are
+    /// assigned to registers independently, so passing all integer arguments first and all float
+    /// arguments after them puts each argument in the right register.
+    type IntFn = extern "C" fn(u64, u64, u64, u64, u64, u64,
+                               f64, f64, f64, f64, f64, f64, f64, f64) -> u64;
+    type FloatFn = extern "C" fn(u64, u64, u64, u64, u64, u64,
+                                 f64, f64, f64, f64, f64, f64, f64, f64) -> f64;
+
+    /// Check that functions with signature `sig` can be called natively.
+    pub fn check_signature(sig: &Signature) -> Result<(), String> {
+        let mut ints = 0;
+        let mut floats = 0;
+        for param in sig.params.iter().chain(&sig.returns) {
+            if param.purpose != ArgumentPurpose::Normal {
+                return Err(format!("{} parameters are not supported", param.purpose));
+            }
+            let ty = param.value_type;
+            if ty.is_int() && ty.bits() < 32 {
+                return Err(format!("{} parameters are not supported", ty));
+            } else if is_int_like(ty) {
+                ints += 1;
+            } else if ty.is_float() {
+                floats += 1;
+            } else {
+                return Err(format!("{} values are not supported", ty));
+            }
+        }
+        if sig.returns.len() > 1 {
+            return Err("multiple return values are not supported".to_string());
+        }
+        if ints > INT_ARGS || floats > FLOAT_ARGS {
+            return Err("too many arguments".to_string());
+        }
+        Ok(())
+    }
+
+    /// The result of calling native code.
+    pub enum Outcome {
+        /// The function returned these values.
+        Return(Vec<DataValue>),
+        /// The child process running the function was killed by this signal.
+        Signal(i32),
+        /// The function didn't return within the timeout, given in seconds.
+        Timeout(u32),
+    }
+
+    impl fmt::Display for Outcome {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Outcome::Return(ref values) => write!(f, "{}", display_list(values)),
+                Outcome::Signal(sig) => write!(f, "signal {}", sig),
+                Outcome::Timeout(secs) => write!(f, "timed out after {} s", secs),
+            }
+        }
+    }
+
+    /// Executable memory holding the machine code of all the functions in a file.
+    pub struct CodeMemory {
+        ptr: *mut u8,
+        len: usize,
+        starts: Vec<(FunctionName, usize)>,
+    }
+
+    impl CodeMemory {
+        /// Copy `code` into newly mapped writable memory.
+        pub fn new(code: &[u8]) -> Result<CodeMemory, String> {
+            let len = code.len().max(1);
+            let ptr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANON,
+                    -1,
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err("failed to map memory for code".to_string());
+            }
+            let ptr = ptr as *mut u8;
+            unsafe {
+                ptr::copy_nonoverlapping(code.as_ptr(), ptr, code.len());
+            }
+            Ok(CodeMemory {
+                ptr,
+                len,
+                starts: Vec::new(),
+            })
+        }
+
+        /// Patch the relocation at `site` to refer to `target`, both offsets into the code.
+        pub fn relocate(&mut self, site: usize, kind: &str, target: usize) -> Result<(), String> {
+            let site_addr = self.ptr as u64 + site as u64;
+            let target_addr = self.ptr as u64 + target as u64;
+            unsafe {
+                let p = self.ptr.offset(site as isize);
+                match kind {
+                    "Abs8" => ptr::write_unaligned(p as *mut u64, target_addr),
+                    "Abs4" if target_addr <= u64::from(u32::max_value()) => {
+                        ptr::write_unaligned(p as *mut u32, target_addr as u32)
+                    }
+                    "PCRel4" => {
+                        let disp = target_addr.wrapping_sub(site_addr + 4) as i64;
+                        ptr::write_unaligned(p as *mut i32, disp as i32)
+                    }
+                    _ => return Err(format!("can't apply {} relocation", kind)),
+                }
+            }
+            Ok(())
+        }
+
+        /// Make the code executable, with the functions starting at `starts`.
+        pub fn finish(&mut self, starts: Vec<(FunctionName, usize)>) -> Result<(), String> {
+            let ok = unsafe {
+                libc::mprotect(
+                    self.ptr as *mut libc::c_void,
+                    self.len,
+                    libc::PROT_READ | libc::PROT_EXEC,
+                )
+            } == 0;
+            if !ok {
+                return Err("failed to make code executable".to_string());
+            }
+            self.starts = starts;
+            Ok(())
+        }
+
+        /// Call the function `name` with signature `sig` in a child process.
+        ///
+        /// The child is killed if the call takes more than `timeout` seconds.
+        pub fn call(
+            &self,
+            name: &FunctionName,
+            sig: &Signature,
+            args: &[DataValue],
+            timeout: u32,
+        ) -> Result<Outcome, String> {
+            let start = self.starts
+                .iter()
+                .find(|&&(ref n, _)| n == name)
+                .map(|&(_, start)| start)
+                .ok_or_else(|| format!("{} was not compiled", name))?;
+            let addr = self.ptr as usize + start;
+
+            let mut ints = [0u64; INT_ARGS];
+            let mut floats = [0f64; FLOAT_ARGS];
+            let (mut i, mut f) = (0, 0);
+            for (arg, param) in args.iter().zip(&sig.params) {
+                if is_int_like(param.value_type) {
+                    ints[i] = arg.to_bits();
+                    i += 1;
+                } else {
+                    // A single precision argument lives in the low bits of the register.
+                    floats[f] = f64::from_bits(arg.to_bits());
+                    f += 1;
+                }
+            }
+            let ret = sig.returns.first().map(|r| r.value_type);
+
+            let mut fds = [0; 2];
+            if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+                return Err("failed to create a pipe".to_string());
+            }
+            let pid = unsafe { libc::fork() };
+            if pid < 0 {
+                return Err("failed to fork".to_string());
+            }
+            if pid == 0 {
+                // In the child: call the function and send the returned bits to the parent.
+                // The default action for `SIGALRM` terminates the child if the call hangs.
+                unsafe {
+                    libc::alarm(timeout);
+                }
+                let bits = unsafe {
+                    if ret.map_or(false, |ty| ty.is_float()) {
+                        let func: FloatFn = mem::transmute(addr);
+                        func(
+                            ints[0],
+                            ints[1],
+                            ints[2],
+                            ints[3],
+                            ints[4],
+                            ints[5],
+                            floats[0],
+                            floats[1],
+                            floats[2],
+                            floats[3],
+                            floats[4],
+                            floats[5],
+                            floats[6],
+                            floats[7],
+                        ).to_bits()
+                    } else {
+                        let func: IntFn = mem::transmute(addr);
+                        func(
+                            ints[0],
+                            ints[1],
+                            ints[2],
+                            ints[3],
+                            ints[4],
+                            ints[5],
+                            floats[0],
+                            floats[1],
+                            floats[2],
+                            floats[3],
+                            floats[4],
+                            floats[5],
+                            floats[6],
+                            floats[7],
+                        )
+                    }
+                };
+                unsafe {
+                    libc::write(fds[1], &bits as *const u64 as *const libc::c_void, 8);
+                    libc::_exit(0);
+                }
+            }
+
+            let mut bits = 0u64;
+            let mut status = 0;
+            let read = unsafe {
+                libc::close(fds[1]);
+                let read = libc::read(fds[0], &mut bits as *mut u64 as *mut libc::c_void, 8);
+                libc::close(fds[0]);
+                libc::waitpid(pid, &mut status, 0);
+                read
+            };
+            if libc::WIFSIGNALED(status) {
+                return Ok(match libc::WTERMSIG(status) {
+                    libc::SIGALRM => Outcome::Timeout(timeout),
+                    sig => Outcome::Signal(sig),
+                });
+            }
+            if read != 8 {
+                return Err("native code didn't return a result".to_string());
+            }
+            Ok(Outcome::Return(match ret {
+                None => Vec::new(),
+                Some(types::B1) => vec![DataValue::B(bits & 1 != 0)],
+                Some(ty) => vec![DataValue::from_bits(ty, bits)],
+            }))
+        }
+    }
+
+    impl Drop for CodeMemory {
+        fn drop(&mut self) {
+            unsafe {
+                libc::munmap(self.ptr as *mut libc::c_void, self.len);
+            }
+        }
+    }
+}
+
+#[cfg(not(all(unix, target_arch = "x86_64")))]
+mod native {
+    use cretonne::ir::{FunctionName, Signature};
+    use cton_interpreter::DataValue;
+    use std::fmt;
+
+    const UNSUPPORTED: &str = "native execution is only supported on x86-64 Unix hosts";
+
+    pub fn check_signature(_sig: &Signature) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub enum Outcome {
+        Return(Vec<DataValue>),
+        Signal(i32),
+        Timeout(u32),
+    }
+
+    impl fmt::Display for Outcome {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(UNSUPPORTED)
+        }
+    }
+
+    pub struct CodeMemory;
+
+    impl CodeMemory {
+        pub fn new(_code: &[u8]) -> Result<CodeMemory, String> {
+            Err(UNSUPPORTED.to_string())
+        }
+
+        pub fn relocate(
+            &mut self,
+            _site: usize,
+            _kind: &str,
+            _target: usize,
+        ) -> Result<(), String> {
+            Err(UNSUPPORTED.to_string())
+        }
+
+        pub fn finish(&mut self, _starts: Vec<(FunctionName, usize)>) -> Result<(), String> {
+            Err(UNSUPPORTED.to_string())
+        }
+
+        pub fn call(
+            &self,
+            _name: &FunctionName,
+            _sig: &Signature,
+            _args: &[DataValue],
+            _timeout: u32,
+        ) -> Result<Outcome, String> {
+            Err(UNSUPPORTED.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cretonne::ir::TrapCode;
+    use cton_interpreter::{CallOutcome, DataValue};
+    use cton_reader::parse_functions;
+    use std::path::Path;
+    use super::native::Outcome;
+    use super::{same_outcome, host_isa, handle_file, compile_functions};
+
+    /// Run the directives in `name` natively, and check that there are no mismatches.
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    fn check_file(name: &str) {
+        let isa = host_isa(Vec::new()).unwrap();
+        let mismatches = handle_file(false, 10, Path::new(name), name, &*isa).unwrap();
+        assert_eq!(mismatches, 0, "{} has mismatches", name);
+    }
+
+    #[test]
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    fn frames() {
+        check_file("filetests/run/frames.cton");
+    }
+
+    #[test]
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    fn agree() {
+        check_file("filetests/run/native.cton");
+    }
+
+    #[test]
+    #[cfg(all(unix, target_arch = "x86_64"))]
+    fn unencoded() {
+        // The Intel ISA has no encoding for `stack_store`, so it would emit no code.
+        let functions = parse_functions(
+            "function %stack(i32) -> i32 {
+                ss0 = local 8
+            ebb0(v0: i32):
+                stack_store v0, ss0
+                v1 = stack_addr.i64 ss0
+                v2 = load.i32 v1
+                return v2
+            }",
+        ).unwrap();
+        let isa = host_isa(Vec::new()).unwrap();
+        let (_, failed, unsupported) = compile_functions(&functions, &*isa).unwrap();
+        assert!(unsupported.is_empty());
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0.to_string(), "%stack");
+        assert!(failed[0].1.starts_with("no encoding for stack_store"));
+    }
+
+    #[test]
+    fn outcomes() {
+        let ret = |v| CallOutcome::Return(vec![DataValue::I32(v)]);
+        let native = |v| Outcome::Return(vec![DataValue::I32(v)]);
+        let trap = CallOutcome::Trap(TrapCode::IntegerDivisionByZero);
+
+        assert!(same_outcome(&ret(3), &native(3)));
+        assert!(same_outcome(&trap, &Outcome::Signal(8)));
+        assert!(same_outcome(&CallOutcome::Return(vec![]), &Outcome::Return(vec![])));
+
+        assert!(!same_outcome(&ret(3), &native(4)));
+        assert!(!same_outcome(&ret(3), &Outcome::Return(vec![])));
+        assert!(!same_outcome(&ret(3), &Outcome::Signal(11)));
+        assert!(!same_outcome(&trap, &native(0)));
+        assert!(!same_outcome(&trap, &Outcome::Timeout(10)));
+        assert!(!same_outcome(&ret(3), &Outcome::Timeout(10)));
+    }
+}
@@ -2,6 +2,9 @@
 //!
 //! The `binemit` test command generates binary machine code for every instruction in the input
 //! functions and compares the results to the expected output.
+//!
+//! Instructions with a `dis:` directive are also disassembled from the emitted machine code, and
+//! the assembly text is compared to the directive.
 
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -27,10 +30,13 @@ pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
 }
 
 // Code sink that generates text.
+//
+// The bytes are also collected in memory order for the disassembler.
 struct TextSink {
     rnames: &'static [&'static str],
     offset: binemit::CodeOffset,
     text: String,
+    code: Vec<u8>,
 }
 
 impl TextSink {
@@ -40,12 +46,11 @@ impl TextSink {
             rnames: isa.reloc_names(),
             offset: 0,
             text: String::new(),
+            code: Vec::new(),
         }
     }
 }
 
-
-
 impl binemit::CodeSink for TextSink {
     fn offset(&self) -> binemit::CodeOffset {
         self.offset
@@ -53,21 +58,31 @@ impl binemit::CodeSink for TextSink {
 
     fn put1(&mut self, x: u8) {
         write!(self.text, "{:02x} ", x).unwrap();
+        self.code.push(x);
         self.offset += 1;
     }
 
     fn put2(&mut self, x: u16) {
         write!(self.text, "{:04x} ", x).unwrap();
+        for i in 0..2 {
+            self.code.push((x >> (8 * i)) as u8);
+        }
         self.offset += 2;
     }
 
     fn put4(&mut self, x: u32) {
         write!(self.text, "{:08x} ", x).unwrap();
+        for i in 0..4 {
+            self.code.push((x >> (8 * i)) as u8);
+        }
         self.offset += 4;
     }
 
     fn put8(&mut self, x: u64) {
         write!(self.text, "{:016x} ", x).unwrap();
+        for i in 0..8 {
+            self.code.push((x >> (8 * i)) as u8);
+        }
         self.offset += 8;
     }
 
@@ -79,9 +94,30 @@ impl binemit::CodeSink for TextSink {
         write!(self.text, "{}({}) ", self.rnames[reloc.0 as usize], fref).unwrap();
     }
 
+    fn reloc_external(
+        &mut self,
+        reloc: binemit::Reloc,
+        name: &ir::FunctionName,
+        addend: binemit::Addend,
+    ) {
+        write!(self.text, "{}({}", self.rnames[reloc.0 as usize], name).unwrap();
+        if addend != 0 {
+            write!(self.text, "{:+}", addend).unwrap();
+        }
+        write!(self.text, ") ").unwrap();
+    }
+
     fn reloc_jt(&mut self, reloc: binemit::Reloc, jt: ir::JumpTable) {
         write!(self.text, "{}({}) ", self.rnames[reloc.0 as usize], jt).unwrap();
     }
+
+    fn add_stackmap(&mut self, map: binemit::StackMap) {
+        write!(self.text, "{} ", map).unwrap();
+    }
+
+    fn add_call_site(&mut self, landing_pad: ir::Ebb) {
+        write!(self.text, "callsite({}) ", landing_pad).unwrap();
+    }
 }
 
 impl SubTest for TestBinEmit {
@@ -151,32 +187,10 @@ impl SubTest for TestBinEmit {
             pretty_error(&func, context.isa, e)
         })?;
 
-        // Collect all of the 'bin:' directives on instructions.
-        let mut bins = HashMap::new();
-        for comment in &context.details.comments {
-            if let Some(want) = match_directive(comment.text, "bin:") {
-                match comment.entity {
-                    AnyEntity::Inst(inst) => {
-                        if let Some(prev) = bins.insert(inst, want) {
-                            return Err(format!(
-                                "multiple 'bin:' directives on {}: '{}' and '{}'",
-                                func.dfg.display_inst(inst, isa),
-                                prev,
-                                want
-                            ));
-                        }
-                    }
-                    _ => {
-                        return Err(format!(
-                            "'bin:' directive on non-inst {}: {}",
-                            comment.entity,
-                            comment.text
-                        ))
-                    }
-                }
-            }
-        }
-        if bins.is_empty() {
+        // Collect all of the 'bin:' and 'dis:' directives on instructions.
+        let mut bins = collect_directives("bin:", &func, context)?;
+        let mut dises = collect_directives("dis:", &func, context)?;
+        if bins.is_empty() && dises.is_empty() {
             return Err("No 'bin:' directives found".to_string());
         }
 
@@ -221,14 +235,58 @@ impl SubTest for TestBinEmit {
                     }
                     let have = sink.text.trim();
                     if have != want {
+                        if let Some(blesser) = context.bless {
+                            blesser.replace(want, have);
+                        } else {
+                            return Err(format!(
+                                "Bad machine code for {}: {}\nWant: {}\nGot:  {}",
+                                inst,
+                                func.dfg.display_inst(inst, isa),
+                                want,
+                                have
+                            ));
+                        }
+                    }
+                }
+
+                // Check against dis: directives.
+                if let Some(want) = dises.remove(&inst) {
+                    if !enc.is_legal() {
                         return Err(format!(
-                            "Bad machine code for {}: {}\nWant: {}\nGot:  {}",
+                            "{} can't be encoded: {}",
                             inst,
-                            func.dfg.display_inst(inst, isa),
-                            want,
-                            have
+                            func.dfg.display_inst(inst, isa)
                         ));
                     }
+                    let offset = sink.offset - encinfo.bytes(enc);
+                    let have = match isa.decode_inst(&sink.code, offset) {
+                        Some(decoded) => {
+                            if decoded.size != encinfo.bytes(enc) {
+                                return Err(format!(
+                                    "Decoded {} bytes of {}-byte [{}] {}",
+                                    decoded.size,
+                                    encinfo.bytes(enc),
+                                    encinfo.display(enc),
+                                    func.dfg.display_inst(inst, isa)
+                                ));
+                            }
+                            decoded.asm
+                        }
+                        None => "<none>".to_string(),
+                    };
+                    if have != want {
+                        if let Some(blesser) = context.bless {
+                            blesser.replace(want, &have);
+                        } else {
+                            return Err(format!(
+                                "Bad disassembly for {}: {}\nWant: {}\nGot:  {}",
+                                inst,
+                                func.dfg.display_inst(inst, isa),
+                                want,
+                                have
+                            ));
+                        }
+                    }
                 }
             }
         }
@@ -244,3 +302,38 @@ impl SubTest for TestBinEmit {
         Ok(())
     }
 }
+
+/// Collect all of the `directive` comments on instructions in `func`.
+fn collect_directives<'a>(
+    directive: &str,
+    func: &ir::Function,
+    context: &Context<'a>,
+) -> Result<HashMap<ir::Inst, &'a str>> {
+    let mut found = HashMap::new();
+    for comment in &context.details.comments {
+        if let Some(want) = match_directive(comment.text, directive) {
+            match comment.entity {
+                AnyEntity::Inst(inst) => {
+                    if let Some(prev) = found.insert(inst, want) {
+                        return Err(format!(
+                            "multiple '{}' directives on {}: '{}' and '{}'",
+                            directive,
+                            func.dfg.display_inst(inst, context.isa),
+                            prev,
+                            want
+                        ));
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "'{}' directive on non-inst {}: {}",
+                        directive,
+                        comment.entity,
+                        comment.text
+                    ))
+                }
+            }
+        }
+    }
+    Ok(found)
+}
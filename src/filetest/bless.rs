@@ -0,0 +1,229 @@
+//! Updating the expectations in a test file.
+//!
+//! When `cton-util test --bless` is used, sub-tests that compare their output against directives
+//! in the test file record the actual output instead of failing. When all the tests in the file
+//! have run, the file is rewritten with the updated directives. The rest of the file, including
+//! comments that aren't directives, is preserved.
+//!
+//! The comments collected by the parser are slices of the test file source, so the position of a
+//! directive in the file can be computed directly from its comment text.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use cton_reader::Details;
+use filecheck::CheckerBuilder;
+
+/// A set of edits to the source text of a test file.
+pub struct Blesser<'a> {
+    source: &'a str,
+    edits: RefCell<Vec<Edit>>,
+}
+
+// Replace `source[begin..end]` with `text`.
+#[derive(PartialEq, Eq)]
+struct Edit {
+    begin: usize,
+    end: usize,
+    text: String,
+}
+
+impl<'a> Blesser<'a> {
+    /// Create a blesser for the test file with the given source text.
+    pub fn new(source: &'a str) -> Blesser<'a> {
+        Blesser {
+            source,
+            edits: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Replace `old`, which must be a slice of the test file source, with `new`.
+    pub fn replace(&self, old: &str, new: &str) {
+        let (begin, end) = self.range(old);
+        self.push(begin, end, new.to_string());
+    }
+
+    /// Replace the filecheck directives in the function described by `details` with directives
+    /// matching `text`.
+    ///
+    /// The new directives are inserted where the first directive on a line by itself was, or
+    /// after the function if there were none. Directives in the file preamble are not changed.
+    pub fn filecheck(&self, details: &Details, text: &str) {
+        let mut builder = CheckerBuilder::new();
+        let mut insert_at = None;
+        for comment in &details.comments {
+            match builder.directive(comment.text) {
+                Ok(false) => continue,
+                Ok(true) | Err(_) => {}
+            }
+            let (begin, end) = self.range(comment.text);
+            let line_begin = self.source[..begin].rfind('\n').map_or(0, |i| i + 1);
+            let indent = &self.source[line_begin..begin];
+            if indent.trim().is_empty() {
+                // The directive is on a line by itself. Remove the whole line.
+                let line_end = self.source[end..].find('\n').map_or(
+                    self.source.len(),
+                    |i| end + i + 1,
+                );
+                if insert_at.is_none() {
+                    insert_at = Some((line_begin, indent));
+                }
+                self.push(line_begin, line_end, String::new());
+            } else {
+                // Remove the directive following the code on the line.
+                let code_end = line_begin + indent.trim_right().len();
+                self.push(code_end, end, String::new());
+            }
+        }
+
+        let (offset, indent) = insert_at.unwrap_or_else(|| (self.function_end(details), ""));
+        let mut checks = String::new();
+        if !self.source[..offset].ends_with('\n') {
+            checks.push('\n');
+        }
+        let mut directive = "check";
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() {
+                directive = "check";
+                continue;
+            }
+            writeln!(checks, "{}; {}: {}", indent, directive, line.replace('$', "$$")).unwrap();
+            directive = "nextln";
+        }
+        self.push(offset, offset, checks);
+    }
+
+    /// Apply the edits to the source text.
+    ///
+    /// Returns the new source text, or `None` if nothing changed. Fails if two tests wanted to
+    /// update the same text differently, for example because they target different ISAs.
+    pub fn finish(self) -> Result<Option<String>, String> {
+        let mut edits = self.edits.into_inner();
+        edits.sort_by_key(|e| (e.begin, e.end));
+        edits.dedup();
+
+        let mut text = String::with_capacity(self.source.len());
+        let mut pos = 0;
+        let mut prev: Option<(usize, usize)> = None;
+        for edit in &edits {
+            if edit.begin < pos || prev == Some((edit.begin, edit.end)) {
+                let line = self.source[..edit.begin].matches('\n').count() + 1;
+                return Err(format!("conflicting updates to line {}", line));
+            }
+            text.push_str(&self.source[pos..edit.begin]);
+            text.push_str(&edit.text);
+            pos = edit.end;
+            prev = Some((edit.begin, edit.end));
+        }
+        text.push_str(&self.source[pos..]);
+
+        if text == self.source {
+            Ok(None)
+        } else {
+            Ok(Some(text))
+        }
+    }
+
+    fn push(&self, begin: usize, end: usize, text: String) {
+        self.edits.borrow_mut().push(Edit { begin, end, text });
+    }
+
+    // Get the byte range of `slice` in the source text.
+    fn range(&self, slice: &str) -> (usize, usize) {
+        let begin = (slice.as_ptr() as usize).wrapping_sub(self.source.as_ptr() as usize);
+        assert!(
+            begin <= self.source.len() && slice.len() <= self.source.len() - begin,
+            "not a slice of the test file"
+        );
+        (begin, begin + slice.len())
+    }
+
+    // Get the offset following the closing brace of the function described by `details`.
+    fn function_end(&self, details: &Details) -> usize {
+        let mut offset = 0;
+        for (idx, line) in self.source.split('\n').enumerate() {
+            let next = offset + line.len() + 1;
+            if idx + 1 >= details.location.line_number && line.starts_with('}') {
+                return next.min(self.source.len());
+            }
+            offset = next;
+        }
+        self.source.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cton_reader::parse_test;
+    use super::Blesser;
+
+    const SOURCE: &str = "test verifier
+
+function %f(i32) -> i32 {
+; A plain comment that must be kept.
+ebb0(v0: i32):
+    v1 = iadd v0, v0 ; bin: 01c0
+    return v1
+}
+; check: ebb0(v0: i32):
+; nextln: v1 = iadd v0, v0
+; Another plain comment.
+";
+
+    #[test]
+    fn filecheck() {
+        let testfile = parse_test(SOURCE).unwrap();
+        let blesser = Blesser::new(SOURCE);
+        blesser.filecheck(&testfile.functions[0].1, "ebb0(v0: i32):\n    v1 = imul v0, v0\n");
+        let text = blesser.finish().unwrap().unwrap();
+        assert_eq!(
+            text,
+            "test verifier
+
+function %f(i32) -> i32 {
+; A plain comment that must be kept.
+ebb0(v0: i32):
+    v1 = iadd v0, v0 ; bin: 01c0
+    return v1
+}
+; check: ebb0(v0: i32):
+; nextln: v1 = imul v0, v0
+; Another plain comment.
+"
+        );
+    }
+
+    #[test]
+    fn replace() {
+        let testfile = parse_test(SOURCE).unwrap();
+        let bin = testfile.functions[0]
+            .1
+            .comments
+            .iter()
+            .find(|c| c.text.starts_with("; bin:"))
+            .unwrap()
+            .text;
+        let want = &bin["; bin: ".len()..];
+
+        // The same update twice is fine.
+        let blesser = Blesser::new(SOURCE);
+        blesser.replace(want, "03c0");
+        blesser.replace(want, "03c0");
+        let text = blesser.finish().unwrap().unwrap();
+        assert!(text.contains("    v1 = iadd v0, v0 ; bin: 03c0\n"));
+        assert!(text.contains("; A plain comment that must be kept.\n"));
+
+        // Leaving the text as it was is not a change.
+        let blesser = Blesser::new(SOURCE);
+        blesser.replace(want, "01c0");
+        assert_eq!(blesser.finish(), Ok(None));
+
+        // Different updates to the same directive conflict.
+        let blesser = Blesser::new(SOURCE);
+        blesser.replace(want, "03c0");
+        blesser.replace(want, "0fafc0");
+        assert_eq!(
+            blesser.finish(),
+            Err("conflicting updates to line 6".to_string())
+        );
+    }
+}
@@ -1,12 +1,13 @@
 //! Test command for testing the code generator pipeline
 //!
-//! The `compile` test command runs each function through the full code generator pipeline
+//! The `compile` test command runs each function through the full code generator pipeline. If the
+//! function has any filecheck directives, they are matched against the compiled function.
 
 use cretonne::binemit;
 use cretonne::ir;
 use cretonne;
 use cton_reader::TestCommand;
-use filetest::subtest::{SubTest, Context, Result};
+use filetest::subtest::{SubTest, Context, Result, has_filecheck_directives, run_filecheck};
 use std::borrow::Cow;
 use utils::pretty_error;
 
@@ -67,6 +68,11 @@ impl SubTest for TestCompile {
             ));
         }
 
+        if has_filecheck_directives(context) {
+            let text = comp_ctx.func.display(isa).to_string();
+            run_filecheck(&text, context)?;
+        }
+
         Ok(())
     }
 }
@@ -99,5 +105,14 @@ impl binemit::CodeSink for SizeSink {
 
     fn reloc_ebb(&mut self, _reloc: binemit::Reloc, _ebb: ir::Ebb) {}
     fn reloc_func(&mut self, _reloc: binemit::Reloc, _fref: ir::FuncRef) {}
+    fn reloc_external(
+        &mut self,
+        _reloc: binemit::Reloc,
+        _name: &ir::FunctionName,
+        _addend: binemit::Addend,
+    ) {
+    }
     fn reloc_jt(&mut self, _reloc: binemit::Reloc, _jt: ir::JumpTable) {}
+    fn add_stackmap(&mut self, _map: binemit::StackMap) {}
+    fn add_call_site(&mut self, _landing_pad: ir::Ebb) {}
 }
@@ -3,14 +3,14 @@
 //! This module provides the `ConcurrentRunner` struct which uses a pool of threads to run tests
 //! concurrently.
 
-use std::panic::catch_unwind;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Sender, Receiver};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 use num_cpus;
-use filetest::{TestResult, runone};
+use filetest::{TestResult, Options, runone};
 
 // Request sent to worker threads contains jobid and path.
 struct Request(usize, PathBuf);
@@ -38,7 +38,7 @@ pub struct ConcurrentRunner {
 
 impl ConcurrentRunner {
     /// Create a new `ConcurrentRunner` with threads spun up.
-    pub fn new() -> ConcurrentRunner {
+    pub fn new(options: &Options) -> ConcurrentRunner {
         let (request_tx, request_rx) = channel();
         let request_mutex = Arc::new(Mutex::new(request_rx));
         let (reply_tx, reply_rx) = channel();
@@ -47,7 +47,12 @@ impl ConcurrentRunner {
 
         let handles = (0..num_cpus::get())
             .map(|num| {
-                worker_thread(num, request_mutex.clone(), reply_tx.clone())
+                worker_thread(
+                    num,
+                    request_mutex.clone(),
+                    reply_tx.clone(),
+                    options.clone(),
+                )
             })
             .collect();
 
@@ -109,6 +114,7 @@ fn worker_thread(
     thread_num: usize,
     requests: Arc<Mutex<Receiver<Request>>>,
     replies: Sender<Reply>,
+    options: Options,
 ) -> thread::JoinHandle<()> {
     thread::Builder::new()
         .name(format!("worker #{}", thread_num))
@@ -124,17 +130,20 @@ fn worker_thread(
                 // The receiver should always be present for this as long as we have jobs.
                 replies.send(Reply::Starting { jobid, thread_num }).unwrap();
 
-                let result = catch_unwind(|| runone::run(path.as_path())).unwrap_or_else(|e| {
-                    // The test panicked, leaving us a `Box<Any>`.
-                    // Panics are usually strings.
-                    if let Some(msg) = e.downcast_ref::<String>() {
-                        Err(format!("panicked in worker #{}: {}", thread_num, msg))
-                    } else if let Some(msg) = e.downcast_ref::<&'static str>() {
-                        Err(format!("panicked in worker #{}: {}", thread_num, msg))
-                    } else {
-                        Err(format!("panicked in worker #{}", thread_num))
-                    }
-                });
+                // The options are never modified, so they can't be left in a broken state.
+                let options = AssertUnwindSafe(&options);
+                let result = catch_unwind(|| runone::run(path.as_path(), &options))
+                    .unwrap_or_else(|e| {
+                        // The test panicked, leaving us a `Box<Any>`.
+                        // Panics are usually strings.
+                        if let Some(msg) = e.downcast_ref::<String>() {
+                            Err(format!("panicked in worker #{}: {}", thread_num, msg))
+                        } else if let Some(msg) = e.downcast_ref::<&'static str>() {
+                            Err(format!("panicked in worker #{}: {}", thread_num, msg))
+                        } else {
+                            Err(format!("panicked in worker #{}", thread_num))
+                        }
+                    });
 
                 if let Err(ref msg) = result {
                     dbg!("FAIL: {}", msg);
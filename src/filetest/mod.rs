@@ -6,16 +6,20 @@
 use std::path::Path;
 use std::time;
 use cton_reader::TestCommand;
+use regex::Regex;
 use CommandResult;
 use cat;
 use print_cfg;
 use filetest::runner::TestRunner;
 
+pub mod run;
 pub mod subtest;
 
 mod binemit;
+mod bless;
 mod compile;
 mod concurrent;
+mod dce;
 mod domtree;
 mod legalizer;
 mod licm;
@@ -26,7 +30,38 @@ mod simple_gvn;
 mod verifier;
 
 /// The result of running the test in a file.
-pub type TestResult = Result<time::Duration, String>;
+///
+/// A successful test returns the time it took to run, or `None` if the file was skipped because it
+/// has none of the selected test commands.
+pub type TestResult = Result<Option<time::Duration>, String>;
+
+/// Format of the report printed after running the tests.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// Human readable text, listing only failures and slow tests unless verbose.
+    Text,
+    /// A JUnit XML report.
+    JUnit,
+    /// A JSON report.
+    Json,
+}
+
+/// Options controlling how tests are selected and run.
+#[derive(Clone)]
+pub struct Options {
+    /// Print every test, not just the failures.
+    pub verbose: bool,
+    /// Update the expectations in test files instead of failing.
+    pub bless: bool,
+    /// Format of the report.
+    pub format: Format,
+    /// Only run test files whose path matches this regex.
+    pub filter: Option<Regex>,
+    /// Only run this test command in each file.
+    pub subtest: Option<String>,
+    /// Number of slowest tests to list after running the tests.
+    pub slowest: usize,
+}
 
 /// Main entry point for `cton-util test`.
 ///
@@ -37,8 +72,47 @@ pub type TestResult = Result<time::Duration, String>;
 /// Directories are scanned recursively for test cases ending in `.cton`. These test cases are
 /// executed on background threads.
 ///
-pub fn run(verbose: bool, files: Vec<String>) -> CommandResult {
-    let mut runner = TestRunner::new(verbose);
+/// With `bless`, tests that produce text update the expectations in their test file instead of
+/// failing on a mismatch.
+///
+/// The tests can be restricted to files whose path matches the `filter` regex and to the test
+/// command named `subtest`. Empty strings select everything.
+pub fn run(
+    verbose: bool,
+    bless: bool,
+    format: &str,
+    filter: &str,
+    subtest: &str,
+    slowest: usize,
+    files: Vec<String>,
+) -> CommandResult {
+    let format = match format {
+        "text" => Format::Text,
+        "junit" => Format::JUnit,
+        "json" => Format::Json,
+        _ => return Err(format!("unknown report format '{}'", format)),
+    };
+    let filter = if filter.is_empty() {
+        None
+    } else {
+        Some(Regex::new(filter).map_err(
+            |e| format!("bad filter: {}", e),
+        )?)
+    };
+    let subtest = if subtest.is_empty() {
+        None
+    } else {
+        Some(subtest.to_string())
+    };
+
+    let mut runner = TestRunner::new(Options {
+        verbose,
+        bless,
+        format,
+        filter,
+        subtest,
+        slowest,
+    });
 
     for path in files.iter().map(Path::new) {
         if path.is_file() {
@@ -61,11 +135,13 @@ fn new_subtest(parsed: &TestCommand) -> subtest::Result<Box<subtest::SubTest>> {
         "binemit" => binemit::subtest(parsed),
         "cat" => cat::subtest(parsed),
         "compile" => compile::subtest(parsed),
+        "dce" => dce::subtest(parsed),
         "domtree" => domtree::subtest(parsed),
         "legalizer" => legalizer::subtest(parsed),
         "licm" => licm::subtest(parsed),
         "print-cfg" => print_cfg::subtest(parsed),
         "regalloc" => regalloc::subtest(parsed),
+        "run" => run::subtest(parsed),
         "simple-gvn" => simple_gvn::subtest(parsed),
         "verifier" => verifier::subtest(parsed),
         _ => Err(format!("unknown test command '{}'", parsed.command)),
@@ -0,0 +1,203 @@
+//! Test command for running functions in the interpreter.
+//!
+//! The `test run` test command looks for annotations on the function like this:
+//!
+//!     ; run: %add(1, 2) == 3
+//!     ; run: %div(1, 0) == trap int_divz
+//!     ; run: %is_even(4)
+//!
+//! Each directive calls the named function in the interpreter and checks its results. A directive
+//! without `==` expects the function to return a single `true` boolean.
+
+use cretonne::ir::{Function, FunctionName, ArgumentPurpose};
+use cton_interpreter::{Interpreter, CallOutcome, DataValue};
+use cton_reader::TestCommand;
+use filetest::subtest::{SubTest, Context, Result};
+use std::borrow::Cow;
+use utils::match_directive;
+
+/// Number of zeroed bytes that a `vmctx` parameter points to.
+const VMCTX_SIZE: u64 = 4096;
+
+struct TestRun;
+
+pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
+    assert_eq!(parsed.command, "run");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestRun))
+    }
+}
+
+impl SubTest for TestRun {
+    fn name(&self) -> Cow<str> {
+        Cow::from("run")
+    }
+
+    fn needs_all_functions(&self) -> bool {
+        true
+    }
+
+    fn run(&self, _func: Cow<Function>, context: &Context) -> Result<()> {
+        for comment in &context.details.comments {
+            if let Some(tail) = match_directive(comment.text, "run:") {
+                run_directive(tail, context.functions).map_err(|e| {
+                    format!("run: {}: {}", tail, e)
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The expected outcome of a `run:` directive.
+pub enum Expected<'a> {
+    /// The function returns a single `true` boolean.
+    True,
+    /// The function returns these comma-separated values.
+    Values(&'a str),
+    /// The function traps with this code.
+    Trap(&'a str),
+}
+
+/// A parsed `run:` directive.
+pub struct Invocation<'a> {
+    /// The function to call.
+    pub name: FunctionName,
+    /// The text of the arguments, not including any `vmctx` argument.
+    pub args: Vec<&'a str>,
+    /// The expected outcome of the call.
+    pub expected: Expected<'a>,
+}
+
+/// Parse the text of a `run:` directive: `%name(args) == results`.
+pub fn parse_invocation(text: &str) -> Result<Invocation> {
+    let (call, expected) = match text.find("==") {
+        Some(pos) => {
+            let rhs = text[pos + 2..].trim();
+            let expected = if rhs.starts_with("trap ") {
+                Expected::Trap(rhs[5..].trim())
+            } else {
+                Expected::Values(rhs)
+            };
+            (text[..pos].trim(), expected)
+        }
+        None => (text.trim(), Expected::True),
+    };
+
+    if !call.starts_with('%') || !call.ends_with(')') {
+        return Err("expected a call like %name(args)".to_string());
+    }
+    let lparen = call.find('(').ok_or("missing argument list")?;
+    Ok(Invocation {
+        name: FunctionName::new(&call[1..lparen]),
+        args: split_list(&call[lparen + 1..call.len() - 1]),
+        expected,
+    })
+}
+
+/// Execute a single `run:` directive.
+fn run_directive(text: &str, functions: &[Function]) -> Result<()> {
+    let Invocation {
+        name,
+        args,
+        expected,
+    } = parse_invocation(text)?;
+    let mut args_text = args.into_iter();
+
+    let mut interp = Interpreter::new(functions);
+    let index = interp.find_function(&name).ok_or_else(
+        || format!("no function named {}", name),
+    )?;
+    let func = interp.function(index);
+
+    let mut args = Vec::new();
+    for param in &func.signature.params {
+        match param.purpose {
+            ArgumentPurpose::Normal => {
+                let text = args_text.next().ok_or("too few arguments")?;
+                args.push(DataValue::parse(text, param.value_type)?);
+            }
+            ArgumentPurpose::VMContext => {
+                let vmctx = interp.memory_mut().allocate(VMCTX_SIZE, 16).ok_or(
+                    "out of memory",
+                )?;
+                args.push(DataValue::from_bits(param.value_type, vmctx));
+            }
+            _ => return Err(format!("can't pass {} parameters", param.purpose)),
+        }
+    }
+    if args_text.next().is_some() {
+        return Err("too many arguments".to_string());
+    }
+
+    let outcome = interp.call(index, &args).map_err(|e| e.to_string())?;
+    match (outcome, expected) {
+        (CallOutcome::Trap(code), Expected::Trap(want)) => {
+            if code.to_string() == want {
+                Ok(())
+            } else {
+                Err(format!("trapped with {}, expected trap {}", code, want))
+            }
+        }
+        (CallOutcome::Trap(code), _) => Err(format!("unexpected trap {}", code)),
+        (CallOutcome::Return(results), Expected::Trap(want)) => {
+            Err(format!(
+                "returned {}, expected trap {}",
+                display_list(&results),
+                want
+            ))
+        }
+        (CallOutcome::Return(results), Expected::True) => {
+            if results.len() == 1 && results[0].same_as(&DataValue::B(true)) {
+                Ok(())
+            } else {
+                Err(format!("returned {}, expected true", display_list(&results)))
+            }
+        }
+        (CallOutcome::Return(results), Expected::Values(want)) => {
+            let want_text = split_list(want);
+            if want_text.len() != results.len() {
+                return Err(format!(
+                    "returned {} values, expected {}",
+                    results.len(),
+                    want_text.len()
+                ));
+            }
+            for ((text, got), ret) in want_text.iter().zip(&results).zip(
+                &func.signature.returns,
+            )
+            {
+                let want = DataValue::parse(text, ret.value_type)?;
+                if !got.same_as(&want) {
+                    return Err(format!(
+                        "returned {}, expected {}",
+                        display_list(&results),
+                        want
+                    ));
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Split a comma-separated list of values, which may be empty.
+fn split_list(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        Vec::new()
+    } else {
+        text.split(',').map(str::trim).collect()
+    }
+}
+
+/// Format a list of values separated by commas.
+pub fn display_list(values: &[DataValue]) -> String {
+    values
+        .iter()
+        .map(DataValue::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
@@ -4,10 +4,11 @@
 //! scanning directories for tests.
 
 use std::error::Error;
-use std::fmt::{self, Display};
+use std::fmt::{self, Display, Write};
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
-use filetest::{TestResult, runone};
+use std::time;
+use filetest::{TestResult, Format, Options, runone};
 use filetest::concurrent::{ConcurrentRunner, Reply};
 use CommandResult;
 
@@ -40,7 +41,7 @@ impl Display for QueueEntry {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let p = self.path.to_string_lossy();
         match self.state {
-            State::Done(Ok(dur)) => {
+            State::Done(Ok(Some(dur))) => {
                 write!(
                     f,
                     "{}.{:03} {}",
@@ -49,6 +50,7 @@ impl Display for QueueEntry {
                     p
                 )
             }
+            State::Done(Ok(None)) => write!(f, "skip {}", p),
             State::Done(Err(ref e)) => write!(f, "FAIL {}: {}", p, e),
             _ => write!(f, "{}", p),
         }
@@ -56,7 +58,7 @@ impl Display for QueueEntry {
 }
 
 pub struct TestRunner {
-    verbose: bool,
+    options: Options,
 
     // Directories that have not yet been scanned.
     dir_stack: Vec<PathBuf>,
@@ -73,6 +75,9 @@ pub struct TestRunner {
     // Number of errors seen so far.
     errors: usize,
 
+    // Paths that couldn't be read, with the error message. These count as failed tests.
+    path_errors: Vec<(PathBuf, String)>,
+
     // Number of ticks received since we saw any progress.
     ticks_since_progress: usize,
 
@@ -81,14 +86,15 @@ pub struct TestRunner {
 
 impl TestRunner {
     /// Create a new blank TrstRunner.
-    pub fn new(verbose: bool) -> TestRunner {
+    pub fn new(options: Options) -> TestRunner {
         TestRunner {
-            verbose,
+            options,
             dir_stack: Vec::new(),
             tests: Vec::new(),
             new_tests: 0,
             reported_tests: 0,
             errors: 0,
+            path_errors: Vec::new(),
             ticks_since_progress: 0,
             threads: None,
         }
@@ -105,9 +111,16 @@ impl TestRunner {
     /// Add a test to be executed later.
     ///
     /// Any problems reading `file` as a test case file will be reported as a test failure.
+    /// Files that don't match the filter are ignored.
     pub fn push_test<P: Into<PathBuf>>(&mut self, file: P) {
+        let path = file.into();
+        if let Some(ref filter) = self.options.filter {
+            if !filter.is_match(&path.to_string_lossy()) {
+                return;
+            }
+        }
         self.tests.push(QueueEntry {
-            path: file.into(),
+            path,
             state: State::New,
         });
     }
@@ -115,7 +128,7 @@ impl TestRunner {
     /// Begin running tests concurrently.
     pub fn start_threads(&mut self) {
         assert!(self.threads.is_none());
-        self.threads = Some(ConcurrentRunner::new());
+        self.threads = Some(ConcurrentRunner::new(&self.options));
     }
 
     /// Scan any directories pushed so far.
@@ -174,15 +187,27 @@ impl TestRunner {
     /// Report an error related to a path.
     fn path_error<E: Error>(&mut self, path: PathBuf, err: E) {
         self.errors += 1;
-        println!("{}: {}", path.to_string_lossy(), err);
+        self.message(format_args!("{}: {}", path.to_string_lossy(), err));
+        self.path_errors.push((path, err.to_string()));
+    }
+
+    /// Print a progress message.
+    ///
+    /// Messages go to stderr when stdout is used for a machine readable report.
+    fn message(&self, msg: fmt::Arguments) {
+        if self.options.format == Format::Text {
+            println!("{}", msg);
+        } else {
+            eprintln!("{}", msg);
+        }
     }
 
     /// Report on the next in-order job, if it's done.
     fn report_job(&self) -> bool {
         let jobid = self.reported_tests;
         if let Some(&QueueEntry { state: State::Done(ref result), .. }) = self.tests.get(jobid) {
-            if self.verbose || result.is_err() {
-                println!("{}", self.tests[jobid]);
+            if self.options.verbose || result.is_err() {
+                self.message(format_args!("{}", self.tests[jobid]));
             }
             true
         } else {
@@ -201,7 +226,7 @@ impl TestRunner {
             } else {
                 // Run test synchronously.
                 self.tests[jobid].state = State::Running;
-                let result = runone::run(self.tests[jobid].path());
+                let result = runone::run(self.tests[jobid].path(), &self.options);
                 self.finish_job(jobid, result);
             }
             self.new_tests = jobid + 1;
@@ -241,15 +266,15 @@ impl TestRunner {
             Reply::Tick => {
                 self.ticks_since_progress += 1;
                 if self.ticks_since_progress == TIMEOUT_SLOW {
-                    println!(
+                    self.message(format_args!(
                         "STALLED for {} seconds with {}/{} tests finished",
                         self.ticks_since_progress,
                         self.reported_tests,
                         self.tests.len()
-                    );
+                    ));
                     for jobid in self.reported_tests..self.tests.len() {
                         if self.tests[jobid].state == State::Running {
-                            println!("slow: {}", self.tests[jobid]);
+                            self.message(format_args!("slow: {}", self.tests[jobid]));
                         }
                     }
                 }
@@ -277,16 +302,21 @@ impl TestRunner {
         }
     }
 
-    /// Print out a report of slow tests.
-    fn report_slow_tests(&self) {
-        // Collect runtimes of succeeded tests.
-        let mut times = self.tests
+    /// Get the run times of the tests that passed.
+    fn times(&self) -> Vec<(time::Duration, &QueueEntry)> {
+        self.tests
             .iter()
             .filter_map(|entry| match *entry {
-                QueueEntry { state: State::Done(Ok(dur)), .. } => Some(dur),
+                QueueEntry { state: State::Done(Ok(Some(dur))), .. } => Some((dur, entry)),
                 _ => None,
             })
-            .collect::<Vec<_>>();
+            .collect()
+    }
+
+    /// Print out a report of slow tests.
+    fn report_slow_tests(&self) {
+        // Collect runtimes of succeeded tests.
+        let mut times = self.times().into_iter().map(|(dur, _)| dur).collect::<Vec<_>>();
 
         // Get me some real data, kid.
         let len = times.len();
@@ -309,27 +339,276 @@ impl TestRunner {
             return;
         }
 
-        for t in self.tests.iter().filter(|entry| match **entry {
-            QueueEntry { state: State::Done(Ok(dur)), .. } => dur > cut,
-            _ => false,
-        })
-        {
-            println!("slow: {}", t)
+        for (_, t) in self.times().into_iter().filter(|&(dur, _)| dur > cut) {
+            self.message(format_args!("slow: {}", t));
         }
+    }
 
+    /// Print out the `n` slowest tests, slowest first.
+    fn report_slowest_tests(&self, n: usize) {
+        let mut times = self.times();
+        if n == 0 || times.is_empty() {
+            return;
+        }
+        times.sort_by(|a, b| b.0.cmp(&a.0));
+        self.message(format_args!("slowest tests:"));
+        for (_, t) in times.into_iter().take(n) {
+            self.message(format_args!("  {}", t));
+        }
     }
 
     /// Scan pushed directories for tests and run them.
     pub fn run(&mut self) -> CommandResult {
+        let started = time::Instant::now();
         self.scan_dirs();
         self.schedule_jobs();
         self.drain_threads();
+        let elapsed = started.elapsed();
+
         self.report_slow_tests();
-        println!("{} tests", self.tests.len());
+        self.report_slowest_tests(self.options.slowest);
+        match self.options.format {
+            Format::Text => {}
+            Format::JUnit => print!("{}", self.junit_report(elapsed)),
+            Format::Json => print!("{}", self.json_report(elapsed)),
+        }
+        let (skipped, _) = self.count_skipped_failed();
+        if skipped > 0 {
+            self.message(format_args!(
+                "{} tests, {} skipped",
+                self.tests.len(),
+                skipped
+            ));
+        } else {
+            self.message(format_args!("{} tests", self.tests.len()));
+        }
         match self.errors {
             0 => Ok(()),
             1 => Err("1 failure".to_string()),
             n => Err(format!("{} failures", n)),
         }
     }
+
+    /// Count the tests that were skipped and the tests that failed, including unreadable paths.
+    fn count_skipped_failed(&self) -> (usize, usize) {
+        let mut skipped = 0;
+        let mut failed = self.path_errors.len();
+        for entry in &self.tests {
+            match entry.state {
+                State::Done(Ok(None)) => skipped += 1,
+                State::Done(Ok(Some(_))) => {}
+                _ => failed += 1,
+            }
+        }
+        (skipped, failed)
+    }
+
+    /// The number of tests in the reports, including unreadable paths.
+    fn count_reported(&self) -> usize {
+        self.tests.len() + self.path_errors.len()
+    }
+
+    /// Generate a JUnit XML report of the test results.
+    fn junit_report(&self, elapsed: time::Duration) -> String {
+        let (skipped, failures) = self.count_skipped_failed();
+
+        let mut out = String::new();
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(out, "<testsuites>").unwrap();
+        writeln!(
+            out,
+            r#"<testsuite name="filetests" tests="{}" failures="{}" skipped="{}" time="{}">"#,
+            self.count_reported(),
+            failures,
+            skipped,
+            secs(elapsed)
+        ).unwrap();
+        for entry in &self.tests {
+            let path = entry.path();
+            let classname = path.parent().map_or(String::new(), |dir| {
+                dir.to_string_lossy().replace('/', ".")
+            });
+            let name = path.file_name().map_or(String::new(), |name| {
+                name.to_string_lossy().into_owned()
+            });
+            write!(
+                out,
+                r#"  <testcase classname="{}" name="{}""#,
+                xml_escape(&classname),
+                xml_escape(&name)
+            ).unwrap();
+            match entry.state {
+                State::Done(Ok(Some(dur))) => writeln!(out, r#" time="{}"/>"#, secs(dur)),
+                State::Done(Ok(None)) => writeln!(out, "><skipped/></testcase>"),
+                State::Done(Err(ref e)) => {
+                    writeln!(
+                        out,
+                        r#"><failure message="{}"/></testcase>"#,
+                        xml_escape(e)
+                    )
+                }
+                _ => writeln!(out, r#"><error message="not run"/></testcase>"#),
+            }.unwrap();
+        }
+        for &(ref path, ref err) in &self.path_errors {
+            writeln!(
+                out,
+                r#"  <testcase classname="" name="{}"><failure message="{}"/></testcase>"#,
+                xml_escape(&path.to_string_lossy()),
+                xml_escape(err)
+            ).unwrap();
+        }
+        writeln!(out, "</testsuite>").unwrap();
+        writeln!(out, "</testsuites>").unwrap();
+        out
+    }
+
+    /// Generate a JSON report of the test results.
+    fn json_report(&self, elapsed: time::Duration) -> String {
+        let mut out = String::new();
+        let (skipped, failed) = self.count_skipped_failed();
+        writeln!(out, "{{").unwrap();
+        writeln!(out, r#"  "time": {},"#, secs(elapsed)).unwrap();
+        writeln!(
+            out,
+            r#"  "passed": {}, "failed": {}, "skipped": {},"#,
+            self.count_reported() - skipped - failed,
+            failed,
+            skipped
+        ).unwrap();
+        writeln!(out, r#"  "tests": ["#).unwrap();
+        let mut entries = Vec::new();
+        for entry in &self.tests {
+            let mut out = String::new();
+            write!(
+                out,
+                r#"    {{"path": "{}", "#,
+                json_escape(&entry.path().to_string_lossy())
+            ).unwrap();
+            match entry.state {
+                State::Done(Ok(Some(dur))) => {
+                    write!(out, r#""result": "pass", "time": {}}}"#, secs(dur))
+                }
+                State::Done(Ok(None)) => write!(out, r#""result": "skip"}}"#),
+                State::Done(Err(ref e)) => {
+                    write!(
+                        out,
+                        r#""result": "fail", "message": "{}"}}"#,
+                        json_escape(e)
+                    )
+                }
+                _ => write!(out, r#""result": "error", "message": "not run"}}"#),
+            }.unwrap();
+            entries.push(out);
+        }
+        for &(ref path, ref err) in &self.path_errors {
+            entries.push(format!(
+                r#"    {{"path": "{}", "result": "fail", "message": "{}"}}"#,
+                json_escape(&path.to_string_lossy()),
+                json_escape(err)
+            ));
+        }
+        if !entries.is_empty() {
+            writeln!(out, "{}", entries.join(",\n")).unwrap();
+        }
+        writeln!(out, "  ]").unwrap();
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+// Format a duration as fractional seconds.
+fn secs(dur: time::Duration) -> String {
+    format!("{}.{:03}", dur.as_secs(), dur.subsec_nanos() / 1000000)
+}
+
+// Escape a string for use in an XML attribute.
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\n' => out.push_str("&#10;"),
+            c if (c as u32) < 0x20 && c != '\t' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Escape a string for use in a JSON string literal.
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use filetest::{Format, Options};
+    use std::io;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use super::{TestRunner, QueueEntry, State, xml_escape, json_escape};
+
+    #[test]
+    fn escapes() {
+        assert_eq!(xml_escape("a<b & c>\"d\""), "a&lt;b &amp; c&gt;&quot;d&quot;");
+        assert_eq!(xml_escape("line\nnext\ttab\x01\x1f"), "line&#10;next\ttab");
+        assert_eq!(json_escape("a<b & c>\"d\""), "a<b & c>\\\"d\\\"");
+        assert_eq!(
+            json_escape("back\\slash\nnext\ttab\x01\x1f"),
+            "back\\\\slash\\nnext\\ttab\\u0001\\u001f"
+        );
+    }
+
+    #[test]
+    fn report_totals() {
+        let mut runner = TestRunner::new(Options {
+            verbose: false,
+            bless: false,
+            format: Format::Json,
+            filter: None,
+            subtest: None,
+            slowest: 0,
+        });
+        for (name, result) in vec![
+            ("pass.cton", Ok(Some(Duration::from_millis(5)))),
+            ("skip.cton", Ok(None)),
+            ("fail.cton", Err("bad <output>".to_string())),
+        ]
+        {
+            runner.tests.push(QueueEntry {
+                path: PathBuf::from(name),
+                state: State::Done(result),
+            });
+        }
+        runner.path_error(
+            PathBuf::from("missing"),
+            io::Error::new(io::ErrorKind::NotFound, "no such directory"),
+        );
+
+        let junit = runner.junit_report(Duration::from_secs(1));
+        assert!(junit.contains(r#"tests="4" failures="2" skipped="1""#));
+        assert!(junit.contains(r#"name="missing"><failure message="no such directory"/>"#));
+        assert!(junit.contains(r#"<failure message="bad &lt;output&gt;"/>"#));
+
+        let json = runner.json_report(Duration::from_secs(1));
+        assert!(json.contains(r#""passed": 1, "failed": 2, "skipped": 1,"#));
+        assert!(json.contains(
+            r#"{"path": "missing", "result": "fail", "message": "no such directory"}"#,
+        ));
+    }
 }
@@ -1,6 +1,8 @@
 //! Run the tests in a single test file.
 
 use std::borrow::Cow;
+use std::fs::File;
+use std::io::Write;
 use std::path::Path;
 use std::time;
 use cretonne::ir::Function;
@@ -10,13 +12,16 @@ use cretonne::verify_function;
 use cton_reader::parse_test;
 use cton_reader::IsaSpec;
 use utils::{read_to_string, pretty_verifier_error};
-use filetest::{TestResult, new_subtest};
+use filetest::{TestResult, Options, new_subtest};
+use filetest::bless::Blesser;
 use filetest::subtest::{SubTest, Context, Result};
 
 /// Load `path` and run the test in it.
 ///
 /// If running this test causes a panic, it will propagate as normal.
-pub fn run(path: &Path) -> TestResult {
+///
+/// When blessing, mismatched expectations are updated in the test file instead of failing.
+pub fn run(path: &Path, options: &Options) -> TestResult {
     dbg!("---\nFile: {}", path.to_string_lossy());
     let started = time::Instant::now();
     let buffer = read_to_string(path).map_err(|e| e.to_string())?;
@@ -32,6 +37,14 @@ pub fn run(path: &Path) -> TestResult {
         .map(new_subtest)
         .collect::<Result<Vec<_>>>()?;
 
+    // Skip the file if it doesn't have the selected test command.
+    if let Some(ref name) = options.subtest {
+        tests.retain(|st| st.name() == name.as_str());
+        if tests.is_empty() {
+            return Ok(None);
+        }
+    }
+
     // Flags to use for those tests that don't need an ISA.
     // This is the cumulative effect of all the `set` commands in the file.
     let flags = match testfile.isa_spec {
@@ -53,6 +66,14 @@ pub fn run(path: &Path) -> TestResult {
         Some(t) => t,
     };
 
+    // Tests that call between functions need a copy of all of them.
+    let functions = if tests.iter().any(|st| st.needs_all_functions()) {
+        testfile.functions.iter().map(|&(ref f, _)| f.clone()).collect()
+    } else {
+        Vec::new()
+    };
+
+    let blesser = Blesser::new(&buffer);
     for (func, details) in testfile.functions {
         let mut context = Context {
             preamble_comments: &testfile.preamble_comments,
@@ -60,6 +81,8 @@ pub fn run(path: &Path) -> TestResult {
             verified: false,
             flags,
             isa: None,
+            functions: &functions,
+            bless: if options.bless { Some(&blesser) } else { None },
         };
 
         for tuple in &tuples {
@@ -70,9 +93,13 @@ pub fn run(path: &Path) -> TestResult {
         run_one_test(last_tuple, Cow::Owned(func), &mut context)?;
     }
 
+    if let Some(text) = blesser.finish()? {
+        File::create(path)
+            .and_then(|mut file| file.write_all(text.as_bytes()))
+            .map_err(|e| format!("updating {}: {}", path.to_string_lossy(), e))?;
+    }
 
-    // TODO: Actually run the tests.
-    Ok(started.elapsed())
+    Ok(Some(started.elapsed()))
 }
 
 // Given a slice of tests, generate a vector of (test, flags, isa) tuples.
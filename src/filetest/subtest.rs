@@ -7,6 +7,7 @@ use cretonne::isa::TargetIsa;
 use cretonne::settings::{Flags, FlagsOrIsa};
 use cton_reader::{Details, Comment};
 use filecheck::{self, CheckerBuilder, Checker, Value as FCValue};
+use filetest::bless::Blesser;
 
 pub type Result<T> = result::Result<T, String>;
 
@@ -27,6 +28,14 @@ pub struct Context<'a> {
     /// Target ISA to test against. Only guaranteed to be present for sub-tests whose `needs_isa`
     /// method returned `true`. For other sub-tests, this is set if the test file has a unique ISA.
     pub isa: Option<&'a TargetIsa>,
+
+    /// All the functions in the test file. Only present for sub-tests whose
+    /// `needs_all_functions` method returned `true`. For other sub-tests, this is empty.
+    pub functions: &'a [Function],
+
+    /// Recorder for updated expectations when running with `--bless`. Sub-tests that compare
+    /// their output against directives should record the actual output here instead of failing.
+    pub bless: Option<&'a Blesser<'a>>,
 }
 
 impl<'a> Context<'a> {
@@ -63,6 +72,12 @@ pub trait SubTest {
         false
     }
 
+    /// Does this test need to see all the functions in the test file?
+    /// This is used to avoid cloning the functions for tests that only look at one of them.
+    fn needs_all_functions(&self) -> bool {
+        false
+    }
+
     /// Run this test on `func`.
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()>;
 }
@@ -83,7 +98,21 @@ impl<'a> filecheck::VariableMap for Context<'a> {
 }
 
 /// Run filecheck on `text`, using directives extracted from `context`.
+///
+/// When blessing, the function's directives are regenerated from `text` if they are missing or
+/// don't match.
 pub fn run_filecheck(text: &str, context: &Context) -> Result<()> {
+    if let Some(blesser) = context.bless {
+        let matches = match build_filechecker(context) {
+            Ok(checker) => checker.check(text, context).unwrap_or(false),
+            Err(_) => false,
+        };
+        if !matches {
+            blesser.filecheck(&context.details, text);
+        }
+        return Ok(());
+    }
+
     let checker = build_filechecker(context)?;
     if checker.check(text, context).map_err(
         |e| format!("filecheck: {}", e),
@@ -99,6 +128,16 @@ pub fn run_filecheck(text: &str, context: &Context) -> Result<()> {
     }
 }
 
+/// Are there any filecheck directives in the file preamble or the function's comments?
+///
+/// Malformed directives count, so the error is reported when the checker is built.
+pub fn has_filecheck_directives(context: &Context) -> bool {
+    let mut builder = CheckerBuilder::new();
+    context.preamble_comments.iter().chain(&context.details.comments).any(|comment| {
+        builder.directive(comment.text).unwrap_or(true)
+    })
+}
+
 /// Build a filechecker using the directives in the file preamble and the function's comments.
 pub fn build_filechecker(context: &Context) -> Result<Checker> {
     let mut builder = CheckerBuilder::new();
@@ -0,0 +1,26 @@
+//! The `fuzzgen` sub-command.
+//!
+//! Read a sequence of fuzzer input files and print the Cretonne IL functions generated from them.
+//! This is used to turn a crashing input found by one of the fuzz targets into a test case that
+//! can be passed to the other `cton-util` commands.
+
+use CommandResult;
+use cretonne::ir::FunctionName;
+use cton_fuzzgen::{generate_function, Config};
+use utils::read_to_end;
+
+pub fn run(files: Vec<String>) -> CommandResult {
+    let config = Config::default();
+    for (i, filename) in files.into_iter().enumerate() {
+        let data = read_to_end(&filename).map_err(
+            |e| format!("{}: {}", filename, e),
+        )?;
+        if i != 0 {
+            println!("");
+        }
+        let func = generate_function(FunctionName::new(format!("fuzz{}", i)), &data, &config);
+        println!("; {}", filename);
+        print!("{}", func);
+    }
+    Ok(())
+}
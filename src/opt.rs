@@ -0,0 +1,212 @@
+//! CLI tool to run a sequence of passes over Cretonne IL.
+//!
+//! Reads IR files into Cretonne IL and runs the passes named on the command line over each
+//! function, in order.
+
+use cretonne::Context;
+use cretonne::settings::FlagsOrIsa;
+use cretonne::verify_function;
+use cton_reader::parse_functions;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use utils::{pretty_error, pretty_verifier_error, read_to_string, parse_sets_and_isa};
+
+/// A pass that can be selected with `--passes`.
+#[derive(Clone, Copy)]
+enum Pass {
+    Legalize,
+    UnreachableCode,
+    Licm,
+    SimpleGvn,
+    Dce,
+    Regalloc,
+    PrologueEpilogue,
+    RelaxBranches,
+}
+
+/// All the passes, by name.
+const PASSES: [(&str, Pass); 8] = [
+    ("legalize", Pass::Legalize),
+    ("unreachable-code", Pass::UnreachableCode),
+    ("licm", Pass::Licm),
+    ("simple-gvn", Pass::SimpleGvn),
+    ("dce", Pass::Dce),
+    ("regalloc", Pass::Regalloc),
+    ("prologue-epilogue", Pass::PrologueEpilogue),
+    ("relax-branches", Pass::RelaxBranches),
+];
+
+impl Pass {
+    /// Look up a pass by name.
+    fn lookup(name: &str) -> Result<Pass, String> {
+        PASSES
+            .iter()
+            .find(|&&(n, _)| n == name)
+            .map(|&(_, pass)| pass)
+            .ok_or_else(|| {
+                let names: Vec<&str> = PASSES.iter().map(|&(n, _)| n).collect();
+                format!(
+                    "unknown pass '{}', available passes: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+
+    /// Does this pass need a target ISA?
+    fn needs_isa(self) -> bool {
+        match self {
+            Pass::Legalize |
+            Pass::Regalloc |
+            Pass::PrologueEpilogue |
+            Pass::RelaxBranches => true,
+            Pass::UnreachableCode | Pass::Licm | Pass::SimpleGvn | Pass::Dce => false,
+        }
+    }
+
+    /// Run this pass on the function in `ctx`.
+    ///
+    /// The analyses needed by the pass are recomputed first, since earlier passes may have
+    /// invalidated them.
+    fn run(self, ctx: &mut Context, fisa: FlagsOrIsa) -> Result<(), String> {
+        let result = match (self, fisa.isa) {
+            (Pass::Legalize, Some(isa)) => {
+                ctx.compute_cfg();
+                ctx.legalize(isa)
+            }
+            (Pass::UnreachableCode, _) => {
+                ctx.flowgraph();
+                ctx.eliminate_unreachable_code(fisa)
+            }
+            (Pass::Licm, _) => {
+                ctx.flowgraph();
+                ctx.compute_loop_analysis();
+                ctx.licm(fisa)
+            }
+            (Pass::SimpleGvn, _) => {
+                ctx.flowgraph();
+                ctx.simple_gvn(fisa)
+            }
+            (Pass::Dce, _) => {
+                ctx.flowgraph();
+                ctx.dce(fisa)
+            }
+            (Pass::Regalloc, Some(isa)) => {
+                ctx.flowgraph();
+                ctx.regalloc(isa)
+            }
+            (Pass::PrologueEpilogue, Some(isa)) => ctx.prologue_epilogue(isa),
+            (Pass::RelaxBranches, Some(isa)) => ctx.relax_branches(isa).map(|_| ()),
+            (_, None) => panic!("pass requires a target isa"),
+        };
+        result.map_err(|e| pretty_error(&ctx.func, fisa.isa, e))
+    }
+}
+
+pub fn run(
+    files: Vec<String>,
+    flag_passes: String,
+    flag_print: bool,
+    flag_print_after_all: bool,
+    flag_verify_each: bool,
+    flag_time_passes: bool,
+    flag_set: Vec<String>,
+    flag_isa: String,
+) -> Result<(), String> {
+    let parsed = parse_sets_and_isa(flag_set, flag_isa)?;
+    let fisa = parsed.as_fisa();
+
+    let mut passes = Vec::new();
+    for name in flag_passes.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let pass = Pass::lookup(name)?;
+        if pass.needs_isa() && fisa.isa.is_none() {
+            return Err(format!("pass '{}' requires a target isa", name));
+        }
+        passes.push((name, pass));
+    }
+
+    let mut times = vec![Duration::new(0, 0); passes.len()];
+    for filename in files {
+        let path = Path::new(&filename);
+        let name = String::from(path.as_os_str().to_string_lossy());
+        handle_module(
+            &passes,
+            &mut times,
+            flag_print,
+            flag_print_after_all,
+            flag_verify_each,
+            path,
+            &name,
+            fisa,
+        )?;
+    }
+
+    if flag_time_passes {
+        print_times(&passes, &times);
+    }
+    Ok(())
+}
+
+fn handle_module(
+    passes: &[(&str, Pass)],
+    times: &mut [Duration],
+    flag_print: bool,
+    flag_print_after_all: bool,
+    flag_verify_each: bool,
+    path: &Path,
+    name: &str,
+    fisa: FlagsOrIsa,
+) -> Result<(), String> {
+    let buffer = read_to_string(path).map_err(
+        |e| format!("{}: {}", name, e),
+    )?;
+    let items = parse_functions(&buffer).map_err(
+        |e| format!("{}: {}", name, e),
+    )?;
+    for func in items.into_iter() {
+        let mut context = Context::new();
+        context.func = func;
+        let func_name = context.func.name.to_string();
+
+        for (&(pass_name, pass), time) in passes.iter().zip(times.iter_mut()) {
+            let started = Instant::now();
+            pass.run(&mut context, fisa).map_err(|e| {
+                format!("{}: {} in {}: {}", name, pass_name, func_name, e)
+            })?;
+            *time += started.elapsed();
+
+            if flag_verify_each {
+                verify_function(&context.func, fisa).map_err(|e| {
+                    format!(
+                        "{}: after {} in {}: {}",
+                        name,
+                        pass_name,
+                        func_name,
+                        pretty_verifier_error(&context.func, fisa.isa, e)
+                    )
+                })?;
+            }
+            if flag_print_after_all {
+                println!("; after {}:", pass_name);
+                println!("{}", context.func.display(fisa.isa));
+            }
+        }
+
+        if flag_print && !flag_print_after_all {
+            println!("{}", context.func.display(fisa.isa));
+        }
+    }
+    Ok(())
+}
+
+/// Print the time spent in each pass, summed over all the functions.
+fn print_times(passes: &[(&str, Pass)], times: &[Duration]) {
+    let millis = |d: &Duration| d.as_secs() as f64 * 1e3 + f64::from(d.subsec_nanos()) / 1e6;
+    let mut total = 0.0;
+    println!("; pass timing:");
+    for (&(pass_name, _), time) in passes.iter().zip(times) {
+        total += millis(time);
+        println!(";   {:10.3} ms  {}", millis(time), pass_name);
+    }
+    println!(";   {:10.3} ms  total", total);
+}
@@ -1,10 +1,14 @@
 //! CLI tool to use the functions provided by the [cretonne-wasm](../cton_wasm/index.html) crate.
 //!
-//! Reads Wasm binary files (one Wasm module per file), translates the functions' code to Cretonne
-//! IL. Can also executes the `start` function of the module by laying out the memories, globals
-//! and tables, then emitting the translated code with hardcoded addresses to memory.
+//! Reads Wasm binary files (one Wasm module per file), validates them and translates the functions'
+//! code to Cretonne IL. Can also executes the `start` function of the module by laying out the
+//! memories, globals and tables, then emitting the translated code with hardcoded addresses to
+//! memory.
+//!
+//! When compiling, the functions of a module are translated and compiled in parallel.
 
-use cton_wasm::{translate_module, DummyEnvironment, ModuleEnvironment};
+use cton_wasm::{translate_module, validate_module, compile_module, DummyEnvironment,
+                ModuleEnvironment};
 use std::path::PathBuf;
 use cretonne::Context;
 use cretonne::settings::FlagsOrIsa;
@@ -13,6 +17,7 @@ use std::error::Error;
 use std::io;
 use std::path::Path;
 use std::process::Command;
+use num_cpus;
 use tempdir::TempDir;
 use term;
 use utils::{pretty_verifier_error, pretty_error, parse_sets_and_isa, read_to_end};
@@ -39,6 +44,7 @@ pub fn run(
     flag_just_decode: bool,
     flag_check_translation: bool,
     flag_print: bool,
+    flag_no_validate: bool,
     flag_set: Vec<String>,
     flag_isa: String,
 ) -> Result<(), String> {
@@ -52,6 +58,7 @@ pub fn run(
             flag_just_decode,
             flag_check_translation,
             flag_print,
+            flag_no_validate,
             path.to_path_buf(),
             name,
             parsed.as_fisa(),
@@ -65,6 +72,7 @@ fn handle_module(
     flag_just_decode: bool,
     flag_check_translation: bool,
     flag_print: bool,
+    flag_no_validate: bool,
     path: PathBuf,
     name: String,
     fisa: FlagsOrIsa,
@@ -74,9 +82,6 @@ fn handle_module(
     vprint!(flag_verbose, "Handling: ");
     terminal.reset().unwrap();
     vprintln!(flag_verbose, "\"{}\"", name);
-    terminal.fg(term::color::MAGENTA).unwrap();
-    vprint!(flag_verbose, "Translating... ");
-    terminal.reset().unwrap();
     let mut data = read_to_end(path.clone()).map_err(|err| {
         String::from(err.description())
     })?;
@@ -98,7 +103,26 @@ fn handle_module(
             |err| String::from(err.description()),
         )?;
     }
-    let mut dummy_environ = DummyEnvironment::with_flags(fisa.flags.clone());
+    if !flag_no_validate {
+        terminal.fg(term::color::MAGENTA).unwrap();
+        vprint!(flag_verbose, "Validating... ");
+        terminal.reset().unwrap();
+        validate_module(&data).map_err(|err| err.to_string())?;
+        terminal.fg(term::color::GREEN).unwrap();
+        vprintln!(flag_verbose, "ok");
+        terminal.reset().unwrap();
+    }
+    terminal.fg(term::color::MAGENTA).unwrap();
+    vprint!(flag_verbose, "Translating... ");
+    terminal.reset().unwrap();
+    // When compiling, the function bodies are only collected here, and they are translated in
+    // parallel by `compile_module` below.
+    let compiling = !flag_just_decode && !flag_check_translation;
+    let mut dummy_environ = if compiling {
+        DummyEnvironment::with_flags_deferred(fisa.flags.clone())
+    } else {
+        DummyEnvironment::with_flags(fisa.flags.clone())
+    };
     translate_module(&data, &mut dummy_environ)?;
     terminal.fg(term::color::GREEN).unwrap();
     vprintln!(flag_verbose, "ok");
@@ -114,23 +138,30 @@ fn handle_module(
     }
     terminal.reset().unwrap();
     let num_func_imports = dummy_environ.get_num_func_imports();
-    for (def_index, func) in dummy_environ.info.function_bodies.iter().enumerate() {
-        let func_index = num_func_imports + def_index;
-        let mut context = Context::new();
-        context.func = func.clone();
-        if flag_check_translation {
+    if flag_check_translation {
+        for func in &dummy_environ.info.function_bodies {
+            let mut context = Context::new();
+            context.func = func.clone();
             context.verify(fisa).map_err(|err| {
                 pretty_verifier_error(&context.func, fisa.isa, err)
             })?;
-            continue;
-        }
-        if let Some(isa) = fisa.isa {
-            context.compile(isa).map_err(|err| {
-                pretty_error(&context.func, fisa.isa, err)
-            })?;
-        } else {
-            return Err(String::from("compilation requires a target isa"));
         }
+        terminal.fg(term::color::GREEN).unwrap();
+        vprintln!(flag_verbose, "ok");
+        terminal.reset().unwrap();
+        return Ok(());
+    }
+    let isa = match fisa.isa {
+        Some(isa) => isa,
+        None => return Err(String::from("compilation requires a target isa")),
+    };
+    let compiled = {
+        let bodies = dummy_environ.deferred_function_bodies();
+        compile_module(&bodies, isa, num_cpus::get(), || dummy_environ.func_env())
+            .map_err(|err| pretty_error(&err.func, fisa.isa, err.error))?
+    };
+    for (def_index, compiled_func) in compiled.iter().enumerate() {
+        let func_index = num_func_imports + def_index;
         if flag_print {
             vprintln!(flag_verbose, "");
             if let Some(start_func) = dummy_environ.info.start_func {
@@ -141,7 +172,7 @@ fn handle_module(
             for export_name in &dummy_environ.info.functions[func_index].export_names {
                 println!("; Exported as \"{}\"", export_name);
             }
-            println!("{}", context.func.display(fisa.isa));
+            println!("{}", compiled_func.func.display(fisa.isa));
             vprintln!(flag_verbose, "");
         }
     }
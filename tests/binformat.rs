@@ -0,0 +1,70 @@
+extern crate cretonne;
+extern crate cton_reader;
+
+use self::cretonne::binformat::{read_functions, write_functions};
+use self::cretonne::ir::Function;
+use self::cton_reader::parse_test;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// Collect all the `.cton` files under `dir`.
+fn collect_files(dir: &Path, files: &mut Vec<String>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else if path.extension().map_or(false, |ext| ext == "cton") {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+}
+
+fn check_same(orig: &Function, copy: &Function) {
+    assert_eq!(copy.name, orig.name);
+    assert_eq!(copy.signature.argument_bytes, orig.signature.argument_bytes);
+    assert_eq!(copy.dfg.num_values(), orig.dfg.num_values());
+    for inst in orig.encodings.keys() {
+        assert_eq!(copy.encodings[inst], orig.encodings[inst]);
+    }
+    for v in orig.locations.keys() {
+        assert_eq!(copy.locations[v], orig.locations[v]);
+    }
+    for inst in orig.srclocs.keys() {
+        assert_eq!(copy.srclocs[inst], orig.srclocs[inst]);
+    }
+}
+
+#[test]
+fn roundtrip_filetests() {
+    let mut files = Vec::new();
+    collect_files(Path::new("filetests"), &mut files);
+    files.sort();
+    assert!(!files.is_empty());
+
+    for file in files {
+        let mut text = String::new();
+        File::open(&file)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        let test = parse_test(&text).unwrap_or_else(|e| panic!("{}: {}", file, e));
+        let isa = test.isa_spec.unique_isa();
+        let funcs: Vec<_> = test.functions.into_iter().map(|(func, _)| func).collect();
+
+        let bytes = write_functions(&funcs, true);
+        let copies = read_functions(&bytes).unwrap_or_else(|e| panic!("{}: {}", file, e));
+        assert_eq!(copies.len(), funcs.len(), "{}", file);
+        for (orig, copy) in funcs.iter().zip(&copies) {
+            assert_eq!(
+                copy.display(isa).to_string(),
+                orig.display(isa).to_string(),
+                "{}",
+                file
+            );
+            check_same(orig, copy);
+        }
+        assert_eq!(write_functions(&copies, true), bytes, "{}", file);
+    }
+}
+
@@ -0,0 +1,44 @@
+(module
+  (type $pair (func (param i32 i32) (result i32 i32)))
+  (type $split (func (param i32) (result i32 i32)))
+  (type $merge (func (param i32 i32) (result i32)))
+  (func $swap (type $pair)
+      (get_local 1)
+      (get_local 0)
+      (block (type $pair))
+  )
+  (func $fib (type $split) (local i32 i32)
+      (i32.const 0)
+      (i32.const 1)
+      (loop (type $pair)
+          (set_local 2)
+          (set_local 1)
+          (get_local 2)
+          (get_local 1)
+          (get_local 2)
+          (i32.add)
+          (br_if 0 (tee_local 0 (i32.sub (get_local 0) (i32.const 1))))
+      )
+  )
+  (func $choose (type $merge)
+      (get_local 0)
+      (get_local 1)
+      (if (type $merge) (get_local 0)
+          (then (i32.add))
+          (else (i32.sub))
+      )
+      (if (type $split) (get_local 1)
+          (then (i32.const 1))
+          (else (i32.const 2))
+      )
+      (i32.mul)
+  )
+  (func $call_swap (type $merge)
+      (call $swap (get_local 0) (get_local 1))
+      (i32.sub)
+  )
+  (func $unreachable (type $pair)
+      (unreachable)
+      (block (type $pair))
+  )
+)